@@ -0,0 +1,497 @@
+//! Imports and exports of the decks of a user without the bot, as Anki packages, CSV files or
+//! folders of Markdown files, review logs of other tools, and backups of the whole account, and
+//! generation of cards from local source files and documents. The storage is configured with the same
+//! environment as the bot, see [`StorageSettings::from_env`], and media are read from and written
+//! to `BLOB_STORAGE_PATH`.
+
+use anyhow::Context;
+use clap::{Parser, Subcommand};
+use flashcard_gpt_core::anki::export::{AnkiExporter, ExportOptions};
+use flashcard_gpt_core::anki::import::{AnkiImporter, ImportOptions};
+use flashcard_gpt_core::backup::export::BackupExporter;
+use flashcard_gpt_core::backup::restore::{BackupRestorer, RestoreMode, RestoreOptions};
+use flashcard_gpt_core::backup::Archive;
+use flashcard_gpt_core::blob::BlobStore;
+use flashcard_gpt_core::csv::export::{CsvExporter, ExportOptions as CsvExportOptions};
+use flashcard_gpt_core::csv::import::{CsvImporter, ImportOptions as CsvImportOptions};
+use flashcard_gpt_core::csv::{delimiter_for, ColumnMapping};
+use flashcard_gpt_core::document::generate::{DocumentGenerator, GenerateOptions};
+use flashcard_gpt_core::document::Document;
+use flashcard_gpt_core::llm::batch::{read_dir, BatchGenerator, BatchOptions, Grouping};
+use flashcard_gpt_core::llm::card_generator_service::CardGeneratorService;
+use flashcard_gpt_core::llm::custom_executor::CustomExecutor;
+use flashcard_gpt_core::logging::init_tracing;
+use flashcard_gpt_core::markdown_folder::export::{
+    ExportOptions as MarkdownExportOptions, MarkdownExporter,
+};
+use flashcard_gpt_core::markdown_folder::import::{
+    ImportOptions as MarkdownImportOptions, MarkdownImporter,
+};
+use flashcard_gpt_core::reexports::db::sql::{thing, Thing};
+use flashcard_gpt_core::review_log::import::{
+    ImportOptions as ReviewImportOptions, ReviewImporter,
+};
+use flashcard_gpt_core::review_log::{from_anki, from_csv, GradeMapping};
+use flashcard_gpt_core::store::any::{
+    AnyCardGroupRepo, AnyCardRepo, AnyDb, AnyDeckRepo, AnyGlobalSettingsRepo, AnyHistoryRepo,
+    AnyStorage, AnyTagRepo, AnyUserRepo, StorageSettings,
+};
+use std::path::PathBuf;
+use std::str::FromStr;
+use tracing::{span, Level, Span};
+
+#[derive(Debug, Parser)]
+#[command(version, about)]
+struct Cli {
+    #[command(subcommand)]
+    command: Command,
+}
+
+#[derive(Debug, Subcommand)]
+enum Command {
+    /// Export the decks of a user to an Anki package (.apkg)
+    ExportAnki {
+        /// Record id of the user, `user:..`
+        #[arg(long, value_parser = parse_thing)]
+        user: Thing,
+
+        /// Only export this deck and its subdecks
+        #[arg(long, value_parser = parse_thing)]
+        deck: Option<Thing>,
+
+        /// Where the package is written
+        #[arg(long, short)]
+        out: PathBuf,
+    },
+
+    /// Import an Anki package (.apkg or .colpkg) into the decks of a user
+    ImportAnki {
+        /// Record id of the user, `user:..`
+        #[arg(long, value_parser = parse_thing)]
+        user: Thing,
+
+        /// Skip the review log of the package
+        #[arg(long)]
+        no_history: bool,
+
+        file: PathBuf,
+    },
+
+    /// Export the cards of a user to a CSV file, or a TSV file when `out` ends with .tsv
+    ExportCsv {
+        /// Record id of the user, `user:..`
+        #[arg(long, value_parser = parse_thing)]
+        user: Thing,
+
+        /// Only export this deck and its subdecks
+        #[arg(long, value_parser = parse_thing)]
+        deck: Option<Thing>,
+
+        /// Where the file is written
+        #[arg(long, short)]
+        out: PathBuf,
+    },
+
+    /// Import the rows of a CSV or TSV file as cards, nothing is imported when a row is invalid
+    ImportCsv {
+        /// Record id of the user, `user:..`
+        #[arg(long, value_parser = parse_thing)]
+        user: Thing,
+
+        /// Deck of the rows without one
+        #[arg(long, value_parser = parse_thing)]
+        deck: Option<Thing>,
+
+        /// Column of a field, by header name or index, like `front=Question` or `back=1`
+        #[arg(long = "column", value_name = "FIELD=COLUMN")]
+        columns: Vec<String>,
+
+        /// The first row is a card, not the names of the columns
+        #[arg(long)]
+        no_headers: bool,
+
+        /// Only check the rows
+        #[arg(long)]
+        dry_run: bool,
+
+        file: PathBuf,
+    },
+
+    /// Export the decks of a user to a folder of Markdown files, a file per card or card group
+    ExportMarkdown {
+        /// Record id of the user, `user:..`
+        #[arg(long, value_parser = parse_thing)]
+        user: Thing,
+
+        /// Only export this deck and its subdecks
+        #[arg(long, value_parser = parse_thing)]
+        deck: Option<Thing>,
+
+        /// The folder, files already in it for the same cards are overwritten
+        #[arg(long, short)]
+        out: PathBuf,
+    },
+
+    /// Import a folder of Markdown files, updating the cards imported before
+    ImportMarkdown {
+        /// Record id of the user, `user:..`
+        #[arg(long, value_parser = parse_thing)]
+        user: Thing,
+
+        /// Deck of the files at the root of the folder without one
+        #[arg(long, value_parser = parse_thing)]
+        deck: Option<Thing>,
+
+        /// Don't write the ids of new cards back to their files
+        #[arg(long)]
+        no_write_ids: bool,
+
+        dir: PathBuf,
+    },
+
+    /// Import the reviews of an Anki package or of a CSV file with the columns
+    /// `card,timestamp,grade[,interval]` into the history of the cards a user already has
+    ImportReviews {
+        /// Record id of the user, `user:..`
+        #[arg(long, value_parser = parse_thing)]
+        user: Thing,
+
+        /// Difficulty of every grade, like `1=8,2=4,3=2,4=0`, the eases of Anki by default
+        #[arg(long, value_parser = parse_grades)]
+        grades: Option<GradeMapping>,
+
+        /// An .apkg or .colpkg package, or a CSV or TSV file
+        file: PathBuf,
+    },
+
+    /// Generate card groups with the LLM from the source files of a folder, like a repository of
+    /// LeetCode solutions, skipping the files generated before. The LLM is configured with the
+    /// same environment as the bot, like `LLM_PROVIDER` or `OPENAI_API_KEY`
+    GenerateCards {
+        /// Record id of the user, `user:..`
+        #[arg(long, value_parser = parse_thing)]
+        user: Thing,
+
+        /// Deck of the new card groups
+        #[arg(long, value_parser = parse_thing)]
+        deck: Thing,
+
+        /// `stem` makes a problem of the files with the same name in a folder, `directory` of all
+        /// the files of a folder
+        #[arg(long, default_value = "stem", value_parser = Grouping::from_str)]
+        grouping: Grouping,
+
+        /// How many problems the LLM is asked about at once
+        #[arg(long, default_value_t = 4)]
+        concurrency: usize,
+
+        /// Ask for wrong answers too, which turns the cards into multiple-choice ones
+        #[arg(long)]
+        distractors: bool,
+
+        /// Also write the report to this file
+        #[arg(long, short)]
+        out: Option<PathBuf>,
+
+        dir: PathBuf,
+    },
+
+    /// Generate card groups with the LLM from the sections of a Markdown, plain text or PDF
+    /// document. The LLM is configured like for `generate-cards`
+    GenerateFromDocument {
+        /// Record id of the user, `user:..`
+        #[arg(long, value_parser = parse_thing)]
+        user: Thing,
+
+        /// Deck of the new card groups
+        #[arg(long, value_parser = parse_thing)]
+        deck: Thing,
+
+        /// How many tokens of the document the LLM is asked about at once
+        #[arg(long, default_value_t = 1500)]
+        max_tokens: usize,
+
+        /// How many parts of the document the LLM is asked about at once
+        #[arg(long, default_value_t = 4)]
+        concurrency: usize,
+
+        /// Ask for wrong answers too, which turns the cards into multiple-choice ones
+        #[arg(long)]
+        distractors: bool,
+
+        file: PathBuf,
+    },
+
+    /// Back up the settings, tags, decks, cards, card groups and history of a user to a JSON file
+    Backup {
+        /// Record id of the user, `user:..`
+        #[arg(long, value_parser = parse_thing)]
+        user: Thing,
+
+        /// Where the backup is written
+        #[arg(long, short)]
+        out: PathBuf,
+    },
+
+    /// Restore a backup into the account of a user, who doesn't have to be the one it was made for
+    Restore {
+        /// Record id of the user, `user:..`
+        #[arg(long, value_parser = parse_thing)]
+        user: Thing,
+
+        /// `merge` into the records of the user or `replace` them
+        #[arg(long, default_value = "merge", value_parser = RestoreMode::from_str)]
+        mode: RestoreMode,
+
+        file: PathBuf,
+    },
+}
+
+fn parse_thing(value: &str) -> Result<Thing, String> {
+    thing(value).map_err(|err| err.to_string())
+}
+
+fn parse_grades(value: &str) -> Result<GradeMapping, String> {
+    value.parse::<GradeMapping>().map_err(|err| err.to_string())
+}
+
+#[tokio::main]
+async fn main() -> anyhow::Result<()> {
+    init_tracing()?;
+    let cli = Cli::parse();
+
+    let db = StorageSettings::from_env()?.connect().await?;
+    let span = span!(Level::INFO, "cli");
+    let blobs = BlobStore::from_env();
+
+    match cli.command {
+        Command::ExportAnki { user, deck, out } => {
+            let exporter = AnkiExporter::<AnyStorage>::new(AnyDeckRepo::new(&db, span));
+            let options = ExportOptions::builder()
+                .maybe_deck(deck)
+                .maybe_blobs(blobs)
+                .build();
+            let (package, report) = exporter.export(user, options).await?;
+            tokio::fs::write(&out, package)
+                .await
+                .with_context(|| format!("Failed to write {}", out.display()))?;
+            println!("{report}");
+        }
+        Command::ImportAnki {
+            user,
+            no_history,
+            file,
+        } => {
+            let package = tokio::fs::read(&file)
+                .await
+                .with_context(|| format!("Failed to read {}", file.display()))?;
+            let options = ImportOptions::builder()
+                .history(!no_history)
+                .maybe_blobs(blobs)
+                .build();
+            let report = importer(&db, span).import(user, package, options).await?;
+            println!("{report}");
+        }
+        Command::ExportCsv { user, deck, out } => {
+            let exporter = CsvExporter::<AnyStorage>::new(AnyDeckRepo::new(&db, span));
+            let options = CsvExportOptions::builder()
+                .delimiter(delimiter_for(&out.to_string_lossy()))
+                .maybe_deck(deck)
+                .build();
+            let (file, report) = exporter.export(user, options).await?;
+            tokio::fs::write(&out, file)
+                .await
+                .with_context(|| format!("Failed to write {}", out.display()))?;
+            println!("{report}");
+        }
+        Command::ImportCsv {
+            user,
+            deck,
+            columns,
+            no_headers,
+            dry_run,
+            file,
+        } => {
+            let mut mapping = ColumnMapping::default();
+            for entry in &columns {
+                mapping.apply(entry)?;
+            }
+            let contents = tokio::fs::read(&file)
+                .await
+                .with_context(|| format!("Failed to read {}", file.display()))?;
+            let options = CsvImportOptions::builder()
+                .delimiter(delimiter_for(&file.to_string_lossy()))
+                .headers(!no_headers)
+                .columns(mapping)
+                .maybe_deck(deck)
+                .dry_run(dry_run)
+                .build();
+            let importer = CsvImporter::<AnyStorage>::new(AnyDeckRepo::new(&db, span));
+            let report = importer.import(user, &contents, options).await?;
+            println!("{report}");
+            if !report.errors.is_empty() {
+                anyhow::bail!("{} has invalid rows", file.display());
+            }
+        }
+        Command::ExportMarkdown { user, deck, out } => {
+            let exporter = MarkdownExporter::<AnyStorage>::new(AnyDeckRepo::new(&db, span));
+            let options = MarkdownExportOptions::builder().maybe_deck(deck).build();
+            let report = exporter.export(user, &out, options).await?;
+            println!("{report}");
+        }
+        Command::ImportMarkdown {
+            user,
+            deck,
+            no_write_ids,
+            dir,
+        } => {
+            let importer = MarkdownImporter::<AnyStorage>::new(
+                AnyCardRepo::new(&db, span.clone()),
+                AnyCardGroupRepo::new(&db, span.clone()),
+                AnyDeckRepo::new(&db, span.clone()),
+                AnyTagRepo::new(&db, span),
+            );
+            let options = MarkdownImportOptions::builder()
+                .maybe_deck(deck)
+                .write_ids(!no_write_ids)
+                .build();
+            let report = importer.import(user, &dir, options).await?;
+            println!("{report}");
+        }
+        Command::ImportReviews { user, grades, file } => {
+            let contents = tokio::fs::read(&file)
+                .await
+                .with_context(|| format!("Failed to read {}", file.display()))?;
+            let name = file.to_string_lossy().to_lowercase();
+            let reviews = if name.ends_with(".apkg") || name.ends_with(".colpkg") {
+                from_anki(contents).await?
+            } else {
+                from_csv(&contents, delimiter_for(&name))?
+            };
+            let importer = ReviewImporter::<AnyStorage>::new(
+                AnyDeckRepo::new(&db, span.clone()),
+                AnyHistoryRepo::new(&db, span),
+            );
+            let options = ReviewImportOptions::builder()
+                .grades(grades.unwrap_or_default())
+                .build();
+            let report = importer.import(user, reviews, options).await?;
+            println!("{report}");
+        }
+        Command::GenerateCards {
+            user,
+            deck,
+            grouping,
+            concurrency,
+            distractors,
+            out,
+            dir,
+        } => {
+            let files = read_dir(&dir)
+                .await
+                .with_context(|| format!("Failed to read {}", dir.display()))?;
+            let generator = BatchGenerator::<AnyStorage>::new(CardGeneratorService::new(
+                CustomExecutor::from_env()?,
+                AnyCardRepo::new(&db, span.clone()),
+                AnyCardGroupRepo::new(&db, span.clone()),
+                AnyDeckRepo::new(&db, span.clone()),
+                AnyTagRepo::new(&db, span),
+            ));
+            let options = BatchOptions::builder()
+                .grouping(grouping)
+                .concurrency(concurrency)
+                .distractors(distractors)
+                .build();
+            let report = generator.generate(user, deck, files, options).await?;
+            println!("{report}");
+            if let Some(out) = out {
+                tokio::fs::write(&out, report.to_string())
+                    .await
+                    .with_context(|| format!("Failed to write {}", out.display()))?;
+            }
+            if !report.failed.is_empty() {
+                anyhow::bail!("{} problems failed", report.failed.len());
+            }
+        }
+        Command::GenerateFromDocument {
+            user,
+            deck,
+            max_tokens,
+            concurrency,
+            distractors,
+            file,
+        } => {
+            let bytes = tokio::fs::read(&file)
+                .await
+                .with_context(|| format!("Failed to read {}", file.display()))?;
+            let name = file
+                .file_name()
+                .map(|name| name.to_string_lossy().into_owned())
+                .unwrap_or_default();
+            let document = Document::parse(&name, bytes).await?;
+            let generator = DocumentGenerator::<AnyStorage>::new(CardGeneratorService::new(
+                CustomExecutor::from_env()?,
+                AnyCardRepo::new(&db, span.clone()),
+                AnyCardGroupRepo::new(&db, span.clone()),
+                AnyDeckRepo::new(&db, span.clone()),
+                AnyTagRepo::new(&db, span),
+            ));
+            let options = GenerateOptions::builder()
+                .max_tokens(max_tokens)
+                .concurrency(concurrency)
+                .distractors(distractors)
+                .build();
+            let report = generator.generate(user, deck, &document, options).await?;
+            println!("{report}");
+            if !report.failed.is_empty() {
+                anyhow::bail!("{} parts of the document failed", report.failed.len());
+            }
+        }
+        Command::Backup { user, out } => {
+            let exporter = BackupExporter::<AnyStorage>::new(
+                AnyUserRepo::new(&db, span.clone()),
+                AnyTagRepo::new(&db, span.clone()),
+                AnyCardRepo::new(&db, span.clone()),
+                AnyCardGroupRepo::new(&db, span.clone()),
+                AnyDeckRepo::new(&db, span.clone()),
+                AnyHistoryRepo::new(&db, span.clone()),
+                AnyGlobalSettingsRepo::new(&db, span),
+            );
+            let archive = exporter.export(user).await?;
+            tokio::fs::write(&out, archive.to_json()?)
+                .await
+                .with_context(|| format!("Failed to write {}", out.display()))?;
+            println!("{}", archive.report());
+        }
+        Command::Restore { user, mode, file } => {
+            let json = tokio::fs::read(&file)
+                .await
+                .with_context(|| format!("Failed to read {}", file.display()))?;
+            let archive = Archive::parse(&json)?;
+            let restorer = BackupRestorer::<AnyStorage>::new(
+                AnyUserRepo::new(&db, span.clone()),
+                AnyTagRepo::new(&db, span.clone()),
+                AnyCardRepo::new(&db, span.clone()),
+                AnyCardGroupRepo::new(&db, span.clone()),
+                AnyDeckRepo::new(&db, span.clone()),
+                AnyHistoryRepo::new(&db, span.clone()),
+                AnyGlobalSettingsRepo::new(&db, span),
+            );
+            let options = RestoreOptions::builder().mode(mode).build();
+            let report = restorer.restore(user, &archive, options).await?;
+            println!("{report}");
+        }
+    }
+
+    Ok(())
+}
+
+fn importer(db: &AnyDb, span: Span) -> AnkiImporter<AnyStorage> {
+    AnkiImporter::new(
+        AnyCardRepo::new(db, span.clone()),
+        AnyCardGroupRepo::new(db, span.clone()),
+        AnyDeckRepo::new(db, span.clone()),
+        AnyTagRepo::new(db, span.clone()),
+        AnyHistoryRepo::new(db, span),
+    )
+}
@@ -0,0 +1,160 @@
+-- SQLite counterpart of db-migrations/migrations/20240902_185441_Initial.surql.
+-- Timestamps are UTC text in the `%F %T%.f%:z` format, which sorts chronologically as a string.
+-- Arrays of references (tags, card group cards) are JSON arrays of ids, as in SurrealDB.
+
+create table user
+(
+    id         integer primary key,
+    email      text not null unique,
+    name       text not null,
+    password   text not null,
+    created_at text not null,
+    updated_at text not null,
+    deleted_at text
+);
+
+create table tag
+(
+    id         integer primary key,
+    user       integer not null references user (id) on delete cascade,
+    name       text    not null,
+    slug       text    not null,
+    created_at text    not null,
+    updated_at text    not null,
+    deleted_at text,
+    unique (user, slug)
+);
+
+create table card
+(
+    id         integer primary key,
+    user       integer not null references user (id) on delete cascade,
+    title      text    not null,
+    front      text,
+    back       text,
+    data       text,
+    hints      text    not null default '[]',
+    difficulty integer not null check (difficulty between 0 and 10),
+    importance integer not null check (importance between 0 and 10),
+    tags       text    not null default '[]',
+    created_at text    not null,
+    updated_at text    not null,
+    deleted_at text
+);
+
+create index card_user on card (user);
+
+create table card_group
+(
+    id         integer primary key,
+    user       integer not null references user (id) on delete cascade,
+    title      text    not null,
+    importance integer not null check (importance between 0 and 10),
+    difficulty integer not null check (difficulty between 0 and 10),
+    data       text,
+    cards      text    not null default '[]',
+    tags       text    not null default '[]',
+    created_at text    not null,
+    updated_at text    not null,
+    deleted_at text
+);
+
+create index card_group_user on card_group (user);
+
+create table deck
+(
+    id          integer primary key,
+    user        integer not null references user (id) on delete cascade,
+    title       text    not null,
+    description text,
+    parent      integer references deck (id) on delete set null,
+    -- null when the deck has no settings; the schema default of settings.daily_limit is 0
+    daily_limit integer,
+    tags        text    not null default '[]',
+    created_at  text    not null,
+    updated_at  text    not null,
+    deleted_at  text
+);
+
+create index deck_user on deck (user);
+
+create table deck_card
+(
+    id         integer primary key,
+    deck       integer not null references deck (id) on delete cascade,
+    card       integer not null references card (id) on delete cascade,
+    created_at text    not null,
+    updated_at text    not null,
+    deleted_at text,
+    unique (deck, card)
+);
+
+create table deck_card_group
+(
+    id         integer primary key,
+    deck       integer not null references deck (id) on delete cascade,
+    card_group integer not null references card_group (id) on delete cascade,
+    created_at text    not null,
+    updated_at text    not null,
+    deleted_at text,
+    unique (deck, card_group)
+);
+
+create table history
+(
+    id              integer primary key,
+    user            integer not null references user (id) on delete cascade,
+    deck_card       integer references deck_card (id) on delete cascade,
+    deck_card_group integer references deck_card_group (id) on delete cascade,
+    difficulty      integer not null check (difficulty between 0 and 10),
+    -- milliseconds
+    hide_for        integer,
+    hide_till       text,
+    created_at      text    not null,
+    updated_at      text    not null,
+    deleted_at      text
+);
+
+create index history_deck_card on history (deck_card, created_at);
+create index history_deck_card_group on history (deck_card_group, created_at);
+
+-- History together with the deck and the card or card group every answer was given to.
+create view answer as
+select h.id,
+       h.deck_card,
+       h.deck_card_group,
+       coalesce(dc.deck, dcg.deck) as deck,
+       dc.card,
+       dcg.card_group,
+       h.difficulty,
+       h.hide_till,
+       h.created_at
+from history h
+         left join deck_card dc on dc.id = h.deck_card
+         left join deck_card_group dcg on dcg.id = h.deck_card_group;
+
+create table binding
+(
+    id            integer primary key,
+    user          integer not null references user (id) on delete cascade,
+    source_id     text    not null unique,
+    type_name     text    not null,
+    data          text,
+    banned_bot_at text,
+    created_at    text    not null,
+    updated_at    text    not null,
+    deleted_at    text
+);
+
+create table global_settings
+(
+    id          integer primary key,
+    user        integer not null unique references user (id) on delete cascade,
+    daily_limit integer not null check (daily_limit between 0 and 10000),
+    -- JSON array of [start, end] pairs in milliseconds since midnight
+    timetable   text    not null,
+    timezone    text    not null,
+    created_at  text    not null,
+    updated_at  text    not null,
+    deleted_at  text
+);
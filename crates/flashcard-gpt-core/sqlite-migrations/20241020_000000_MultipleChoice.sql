@@ -0,0 +1,4 @@
+-- SQLite counterpart of db-migrations/migrations/20241020_000000_MultipleChoice.surql.
+
+-- JSON object with the `options` and the `correct` option indexes of a multiple-choice card.
+alter table card add column choices text;
@@ -0,0 +1,7 @@
+-- SQLite counterpart of db-migrations/migrations/20241021_000000_ReversedCards.surql.
+
+-- The reversed card of a card, or the card it reverses.
+alter table card add column sibling integer references card (id) on delete set null;
+
+-- settings.reverse_cards, only meaningful when daily_limit is not null.
+alter table deck add column reverse_cards integer not null default 0;
@@ -0,0 +1,5 @@
+-- SQLite counterpart of db-migrations/migrations/20241022_000000_ProgressiveCardGroups.surql.
+
+-- JSON array with the difficulty of every card of a progressively answered card group, null for
+-- cards passed without grading.
+alter table history add column steps text not null default '[]';
@@ -0,0 +1,70 @@
+-- SQLite counterpart of db-migrations/migrations/20241023_000000_TypedCardData.surql.
+
+-- `data` of cards and card groups keeps the well-known attachments, `leetcode_link` became
+-- `source_link` and everything else, links that are not URLs included, moves to `extra`.
+-- json_patch drops the keys that are null.
+update card
+set data = json_patch('{}', json_object(
+    'source_link', case
+        when coalesce(json_extract(data, '$.source_link'), json_extract(data, '$.leetcode_link'))
+            like 'http://_%'
+            or coalesce(json_extract(data, '$.source_link'), json_extract(data, '$.leetcode_link'))
+            like 'https://_%'
+        then coalesce(json_extract(data, '$.source_link'), json_extract(data, '$.leetcode_link'))
+    end,
+    'article', json_extract(data, '$.article'),
+    'commented_code', json_extract(data, '$.commented_code'),
+    'notes', json_extract(data, '$.notes'),
+    'answer', json_extract(data, '$.answer'),
+    'cloze', json_extract(data, '$.cloze'),
+    'extra', json((
+        select case when count(*) > 0 then json_group_object(
+            key,
+            case when type in ('object', 'array') then json(value) else value end
+        ) end
+        from json_each(card.data)
+        where not (
+            key in ('article', 'commented_code', 'notes', 'answer', 'cloze')
+            or key in ('source_link', 'leetcode_link')
+                and value = coalesce(
+                    json_extract(card.data, '$.source_link'),
+                    json_extract(card.data, '$.leetcode_link')
+                )
+                and (value like 'http://_%' or value like 'https://_%')
+        )
+    ))
+))
+where data is not null;
+
+update card_group
+set data = json_patch('{}', json_object(
+    'source_link', case
+        when coalesce(json_extract(data, '$.source_link'), json_extract(data, '$.leetcode_link'))
+            like 'http://_%'
+            or coalesce(json_extract(data, '$.source_link'), json_extract(data, '$.leetcode_link'))
+            like 'https://_%'
+        then coalesce(json_extract(data, '$.source_link'), json_extract(data, '$.leetcode_link'))
+    end,
+    'article', json_extract(data, '$.article'),
+    'commented_code', json_extract(data, '$.commented_code'),
+    'notes', json_extract(data, '$.notes'),
+    'answer', json_extract(data, '$.answer'),
+    'cloze', json_extract(data, '$.cloze'),
+    'extra', json((
+        select case when count(*) > 0 then json_group_object(
+            key,
+            case when type in ('object', 'array') then json(value) else value end
+        ) end
+        from json_each(card_group.data)
+        where not (
+            key in ('article', 'commented_code', 'notes', 'answer', 'cloze')
+            or key in ('source_link', 'leetcode_link')
+                and value = coalesce(
+                    json_extract(card_group.data, '$.source_link'),
+                    json_extract(card_group.data, '$.leetcode_link')
+                )
+                and (value like 'http://_%' or value like 'https://_%')
+        )
+    ))
+))
+where data is not null;
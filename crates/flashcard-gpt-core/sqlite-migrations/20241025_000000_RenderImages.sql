@@ -0,0 +1,3 @@
+-- SQLite counterpart of db-migrations/migrations/20241025_000000_RenderImages.surql.
+
+alter table global_settings add column render_images integer not null default 0;
@@ -0,0 +1,5 @@
+-- SQLite counterpart of db-migrations/migrations/20241026_000000_Annotations.surql.
+
+-- JSON object with the personal notes and flags, see model::annotation::Annotations.
+alter table card add column annotations text not null default '{}';
+alter table card_group add column annotations text not null default '{}';
@@ -0,0 +1,4 @@
+-- SQLite counterpart of db-migrations/migrations/20241029_000000_LlmChoice.surql.
+
+-- JSON object with the provider and the model, see llm::provider::LlmChoice.
+alter table global_settings add column llm text;
@@ -0,0 +1,3 @@
+-- SQLite counterpart of db-migrations/migrations/20241030_000000_MultipleChoiceSetting.surql.
+
+alter table global_settings add column multiple_choice integer not null default 0;
@@ -0,0 +1,727 @@
+//! Export of decks to an Anki package in the legacy collection schema, which every Anki version
+//! imports. Every deck becomes an Anki deck named after its parents, and every card a note:
+//!
+//! - a card becomes a "Basic" note with its front, back and hints;
+//! - a card with a reversed sibling becomes a "Basic (and reversed card)" note, the sibling is
+//!   the second card of the note, see [`crate::store::siblings`]. The note is built from the
+//!   original card and goes to its deck, a sibling whose pair is not exported stays a basic note;
+//! - the cards of a cloze text become a single "Cloze" note, see [`crate::model::cloze`].
+//!
+//! Card groups have no counterpart in Anki, their cards are exported with a
+//! `card_group::<title>` tag. Notes keep the id of the card in their guid, so that exporting the
+//! same deck again updates the notes already in Anki instead of duplicating them. The history is
+//! not exported, the cards are new in Anki.
+
+use crate::anki::{html_to_text, DECK_SEPARATOR, FIELD_SEPARATOR};
+use crate::blob::BlobStore;
+use crate::error::CoreError;
+use crate::model::card::Card;
+use crate::model::deck::{selected_decks, Deck};
+use crate::model::media::{CardSide, MediaKind};
+use crate::reexports::db::sql::Thing;
+use crate::store::any::AnyStorage;
+use crate::store::{DeckStore, Storage};
+use bon::Builder;
+use chrono::Utc;
+use rusqlite::{params, Connection};
+use serde_json::{json, Value};
+use sha1::{Digest, Sha1};
+use std::collections::{BTreeMap, HashMap, HashSet};
+use std::fmt::{Debug, Display, Formatter};
+use std::io::{Cursor, Write};
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::Arc;
+use zip::write::SimpleFileOptions;
+use zip::ZipWriter;
+
+/// The deck every collection has, Anki recreates it when it is missing.
+const DEFAULT_DECK_ID: i64 = 1;
+const DEFAULT_DECK_NAME: &str = "Default";
+const DEFAULT_CONFIG_ID: i64 = 1;
+
+const BASIC_MODEL_ID: i64 = 1_700_000_000_001;
+const REVERSED_MODEL_ID: i64 = 1_700_000_000_002;
+const CLOZE_MODEL_ID: i64 = 1_700_000_000_003;
+
+const CARD_GROUP_TAG: &str = "card_group";
+
+const SCHEMA: &str = "
+    create table col (
+        id integer primary key, crt integer not null, mod integer not null,
+        scm integer not null, ver integer not null, dty integer not null, usn integer not null,
+        ls integer not null, conf text not null, models text not null, decks text not null,
+        dconf text not null, tags text not null
+    );
+    create table notes (
+        id integer primary key, guid text not null, mid integer not null, mod integer not null,
+        usn integer not null, tags text not null, flds text not null, sfld integer not null,
+        csum integer not null, flags integer not null, data text not null
+    );
+    create table cards (
+        id integer primary key, nid integer not null, did integer not null, ord integer not null,
+        mod integer not null, usn integer not null, type integer not null,
+        queue integer not null, due integer not null, ivl integer not null,
+        factor integer not null, reps integer not null, lapses integer not null,
+        left integer not null, odue integer not null, odid integer not null,
+        flags integer not null, data text not null
+    );
+    create table revlog (
+        id integer primary key, cid integer not null, usn integer not null,
+        ease integer not null, ivl integer not null, lastIvl integer not null,
+        factor integer not null, time integer not null, type integer not null
+    );
+    create table graves (usn integer not null, oid integer not null, type integer not null);
+    create index ix_notes_usn on notes (usn);
+    create index ix_cards_usn on cards (usn);
+    create index ix_revlog_usn on revlog (usn);
+    create index ix_cards_nid on cards (nid);
+    create index ix_cards_sched on cards (did, queue, due);
+    create index ix_revlog_cid on revlog (cid);
+    create index ix_notes_csum on notes (csum);
+";
+
+#[derive(Debug, Clone, Builder)]
+pub struct ExportOptions {
+    /// Only export this deck and its subdecks, all decks of the user without it.
+    pub deck: Option<Thing>,
+
+    /// Where the photos of the cards are read from, they are skipped without it.
+    pub blobs: Option<BlobStore>,
+}
+
+impl Default for ExportOptions {
+    fn default() -> Self {
+        Self::builder().build()
+    }
+}
+
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct ExportReport {
+    pub decks: usize,
+    pub notes: usize,
+    /// Anki cards, a cloze note or a reversed note has more than one.
+    pub cards: usize,
+    pub media: usize,
+    /// Documents, and photos without a local copy.
+    pub skipped_media: usize,
+    /// Cards without a front.
+    pub skipped_cards: usize,
+}
+
+impl Display for ExportReport {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        writeln!(f, "Decks: {}", self.decks)?;
+        writeln!(f, "Notes: {}", self.notes)?;
+        writeln!(f, "Cards: {}", self.cards)?;
+        write!(f, "Media: {}", self.media)?;
+        if self.skipped_media > 0 {
+            write!(f, ", {} skipped", self.skipped_media)?;
+        }
+        if self.skipped_cards > 0 {
+            write!(f, "\nSkipped cards without a front: {}", self.skipped_cards)?;
+        }
+        Ok(())
+    }
+}
+
+#[derive(Clone)]
+pub struct AnkiExporter<S: Storage = AnyStorage> {
+    pub decks: S::Decks,
+}
+
+impl<S: Storage> Debug for AnkiExporter<S> {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        write!(f, "AnkiExporter")
+    }
+}
+
+impl<S: Storage> AnkiExporter<S> {
+    pub fn new(decks: S::Decks) -> Self {
+        Self { decks }
+    }
+
+    /// The `.apkg` file with the decks of `user`.
+    pub async fn export(
+        &self,
+        user: impl Into<Thing>,
+        options: ExportOptions,
+    ) -> Result<(Vec<u8>, ExportReport), CoreError> {
+        let user = user.into();
+        let all_decks = self.decks.list_by_user_id(user.clone()).await?;
+        let decks = selected_decks(&all_decks, options.deck.as_ref())?;
+
+        let mut export = Export::new();
+        let mut contents = vec![];
+        for deck in decks {
+            let anki_deck = export.deck(deck_name(deck, &all_decks), deck.description.as_deref());
+            let cards = self.decks.list_cards(user.clone(), deck.id.clone()).await?;
+            let card_groups = self
+                .decks
+                .list_card_groups(user.clone(), deck.id.clone())
+                .await?;
+            contents.push((anki_deck, cards, card_groups));
+        }
+
+        // the tags are known before any card is written, a card may be in several groups
+        let mut card_groups = HashMap::<Thing, Vec<String>>::new();
+        for (_, _, groups) in &contents {
+            for group in groups {
+                let tag = format!("{CARD_GROUP_TAG}{DECK_SEPARATOR}{}", tag_name(&group.title));
+                for card in &group.cards {
+                    card_groups
+                        .entry(card.id.clone())
+                        .or_default()
+                        .push(tag.clone());
+                }
+            }
+        }
+
+        let contents = contents
+            .iter()
+            .map(|(anki_deck, cards, groups)| {
+                let cards = groups
+                    .iter()
+                    .flat_map(|group| group.cards.iter().map(Arc::as_ref))
+                    .chain(cards)
+                    .collect::<Vec<_>>();
+                (*anki_deck, cards)
+            })
+            .collect::<Vec<_>>();
+        let exported = contents
+            .iter()
+            .flat_map(|(_, cards)| cards)
+            .map(|&card| (card.id.clone(), card))
+            .collect::<HashMap<_, _>>();
+
+        for (anki_deck, cards) in &contents {
+            export
+                .cards(
+                    *anki_deck,
+                    cards,
+                    &exported,
+                    &card_groups,
+                    options.blobs.as_ref(),
+                )
+                .await?;
+        }
+
+        let report = export.report.clone();
+        let package = tokio::task::spawn_blocking(move || export.write_package()).await??;
+        Ok((package, report))
+    }
+}
+
+/// The Anki name of `deck`: the titles of its parents and its own, `Parent::Child`.
+fn deck_name(deck: &Deck, decks: &[Deck]) -> String {
+    deck.path(decks)
+        .into_iter()
+        .map(|title| title.replace(DECK_SEPARATOR, ":"))
+        .collect::<Vec<_>>()
+        .join(DECK_SEPARATOR)
+}
+
+/// Anki tags are separated by spaces.
+fn tag_name(name: &str) -> String {
+    name.split_whitespace().collect::<Vec<_>>().join("_")
+}
+
+/// Markdown text as the HTML of a note field.
+fn field_html(text: &str) -> String {
+    markdown::to_html(text).trim().to_string()
+}
+
+struct AnkiNote {
+    id: i64,
+    guid: String,
+    model: i64,
+    tags: Vec<String>,
+    fields: Vec<String>,
+    /// Ordinals of its cards.
+    ords: Vec<u8>,
+    deck: i64,
+}
+
+struct Export {
+    /// Start of the ids, which are times in milliseconds in Anki.
+    now: i64,
+    next_id: i64,
+    /// Anki decks by name.
+    decks: BTreeMap<String, (i64, String)>,
+    notes: Vec<AnkiNote>,
+    /// Media entries of the package by file name.
+    media: BTreeMap<String, Vec<u8>>,
+    /// Cards already exported, along with their siblings.
+    seen: HashSet<Thing>,
+    report: ExportReport,
+}
+
+impl Export {
+    fn new() -> Self {
+        let now = Utc::now().timestamp_millis();
+        Self {
+            now,
+            next_id: now,
+            decks: BTreeMap::new(),
+            notes: vec![],
+            media: BTreeMap::new(),
+            seen: HashSet::new(),
+            report: ExportReport::default(),
+        }
+    }
+
+    fn id(&mut self) -> i64 {
+        self.next_id += 1;
+        self.next_id
+    }
+
+    fn deck(&mut self, name: String, description: Option<&str>) -> i64 {
+        if let Some((id, _)) = self.decks.get(&name) {
+            return *id;
+        }
+        // a deck named "Default" is the default deck of Anki
+        let id = if name == DEFAULT_DECK_NAME {
+            DEFAULT_DECK_ID
+        } else {
+            self.id()
+        };
+        let description = description.map(field_html).unwrap_or_default();
+        self.decks.insert(name, (id, description));
+        self.report.decks += 1;
+        id
+    }
+
+    /// Adds the notes of `cards` to `deck`, `exported` are the cards of every deck of the export.
+    async fn cards(
+        &mut self,
+        deck: i64,
+        cards: &[&Card],
+        exported: &HashMap<Thing, &Card>,
+        card_groups: &HashMap<Thing, Vec<String>>,
+        blobs: Option<&BlobStore>,
+    ) -> Result<(), CoreError> {
+        // cloze cards share their front, one note has a card per index
+        let mut cloze_notes = HashMap::<Arc<str>, usize>::new();
+
+        for &card in cards {
+            // the original card of an exported pair makes the note, wherever it comes
+            let sibling = card
+                .sibling
+                .as_ref()
+                .and_then(|sibling| exported.get(sibling));
+            if card.is_reversed() && sibling.is_some_and(|sibling| !sibling.is_reversed()) {
+                continue;
+            }
+            if !self.seen.insert(card.id.clone()) {
+                continue;
+            }
+            let Some(front) = card.front.clone() else {
+                self.report.skipped_cards += 1;
+                continue;
+            };
+
+            let mut tags = card
+                .tags
+                .iter()
+                .map(|tag| tag_name(&tag.name))
+                .filter(|tag| !tag.is_empty())
+                .collect::<Vec<_>>();
+            tags.extend(card_groups.get(&card.id).into_iter().flatten().cloned());
+            tags.sort();
+            tags.dedup();
+
+            let mut front_html = field_html(&front);
+            let mut back_html = card.back.as_deref().map(field_html).unwrap_or_default();
+            front_html.push_str(&self.media(card, CardSide::Front, blobs).await?);
+            back_html.push_str(&self.media(card, CardSide::Back, blobs).await?);
+
+            if let Some(index) = card.cloze_index() {
+                if let Some(&note) = cloze_notes.get(&front) {
+                    self.notes[note].ords.push(index - 1);
+                    self.report.cards += 1;
+                    continue;
+                }
+                cloze_notes.insert(front, self.notes.len());
+                self.note(
+                    card,
+                    CLOZE_MODEL_ID,
+                    tags,
+                    vec![front_html, back_html],
+                    deck,
+                );
+                self.notes.last_mut().expect("just added").ords = vec![index - 1];
+                continue;
+            }
+
+            let hints = card.hints.join("\n\n");
+            let fields = vec![front_html, back_html, field_html(&hints)];
+            let model = match sibling {
+                Some(sibling) if self.seen.insert(sibling.id.clone()) => {
+                    self.report.cards += 1;
+                    REVERSED_MODEL_ID
+                }
+                _ => BASIC_MODEL_ID,
+            };
+            self.note(card, model, tags, fields, deck);
+            if model == REVERSED_MODEL_ID {
+                self.notes.last_mut().expect("just added").ords.push(1);
+            }
+        }
+
+        Ok(())
+    }
+
+    fn note(&mut self, card: &Card, model: i64, tags: Vec<String>, fields: Vec<String>, deck: i64) {
+        let id = self.id();
+        self.notes.push(AnkiNote {
+            id,
+            guid: format!("fgpt-{}", card.id),
+            model,
+            tags,
+            fields,
+            ords: vec![0],
+            deck,
+        });
+        self.report.notes += 1;
+        self.report.cards += 1;
+    }
+
+    /// `<img>` tags of the photos on `side` of the card, which are added to the package.
+    async fn media(
+        &mut self,
+        card: &Card,
+        side: CardSide,
+        blobs: Option<&BlobStore>,
+    ) -> Result<String, CoreError> {
+        let mut html = String::new();
+        let Some(data) = &card.data else {
+            return Ok(html);
+        };
+        for media in data.media(side) {
+            let (MediaKind::Photo, Some(key), Some(blobs)) = (media.kind, &media.blob, blobs)
+            else {
+                self.report.skipped_media += 1;
+                continue;
+            };
+            if !self.media.contains_key(key.as_ref()) {
+                if !blobs.contains(key).await? {
+                    self.report.skipped_media += 1;
+                    continue;
+                }
+                self.media.insert(key.to_string(), blobs.get(key).await?);
+                self.report.media += 1;
+            }
+            html.push_str(&format!("<img src=\"{key}\">"));
+        }
+        Ok(html)
+    }
+
+    fn write_package(self) -> Result<Vec<u8>, CoreError> {
+        let collection = self.write_collection()?;
+
+        let options = SimpleFileOptions::default();
+        let mut zip = ZipWriter::new(Cursor::new(vec![]));
+        zip.start_file("collection.anki2", options)?;
+        zip.write_all(&collection)?;
+
+        let mut names = serde_json::Map::new();
+        for (index, (name, bytes)) in self.media.iter().enumerate() {
+            zip.start_file(index.to_string(), options)?;
+            zip.write_all(bytes)?;
+            names.insert(index.to_string(), Value::from(name.as_str()));
+        }
+        zip.start_file("media", options)?;
+        zip.write_all(Value::Object(names).to_string().as_bytes())?;
+
+        Ok(zip.finish()?.into_inner())
+    }
+
+    /// SQLite can't serialize a database in memory, the collection is written to a temporary
+    /// file.
+    fn write_collection(&self) -> Result<Vec<u8>, CoreError> {
+        static COUNTER: AtomicUsize = AtomicUsize::new(0);
+        let path = std::env::temp_dir().join(format!(
+            "flashcard-gpt-anki-export-{}-{}.sqlite",
+            std::process::id(),
+            COUNTER.fetch_add(1, Ordering::Relaxed)
+        ));
+        let _ = std::fs::remove_file(&path);
+        let written = Connection::open(&path)
+            .map_err(CoreError::from)
+            .and_then(|conn| self.insert_collection(&conn));
+        let bytes = written.and_then(|()| Ok(std::fs::read(&path)?));
+        std::fs::remove_file(&path)?;
+        bytes
+    }
+
+    fn insert_collection(&self, conn: &Connection) -> Result<(), CoreError> {
+        conn.execute_batch(SCHEMA)?;
+
+        let seconds = self.now / 1000;
+        conn.execute(
+            "insert into col values (1, ?1, ?2, ?2, 11, 0, 0, 0, ?3, ?4, ?5, ?6, '{}')",
+            params![
+                seconds,
+                self.now,
+                self.config().to_string(),
+                self.models().to_string(),
+                self.decks_json().to_string(),
+                json!({ DEFAULT_CONFIG_ID.to_string(): deck_config() }).to_string(),
+            ],
+        )?;
+
+        let mut due = 0;
+        let mut card_id = self.now;
+        for note in &self.notes {
+            let sort_field = html_to_text(&note.fields[0]);
+            conn.execute(
+                "insert into notes values (?1, ?2, ?3, ?4, -1, ?5, ?6, ?7, ?8, 0, '')",
+                params![
+                    note.id,
+                    note.guid,
+                    note.model,
+                    seconds,
+                    format!(" {} ", note.tags.join(" ")),
+                    note.fields.join(&FIELD_SEPARATOR.to_string()),
+                    sort_field,
+                    checksum(&sort_field),
+                ],
+            )?;
+            due += 1;
+            for &ord in &note.ords {
+                card_id += 1;
+                // a new card, `due` is its position in the queue of new cards
+                conn.execute(
+                    "insert into cards values
+                     (?1, ?2, ?3, ?4, ?5, -1, 0, 0, ?6, 0, 0, 0, 0, 0, 0, 0, 0, '')",
+                    params![card_id, note.id, note.deck, ord, seconds, due],
+                )?;
+            }
+        }
+
+        Ok(())
+    }
+
+    fn config(&self) -> Value {
+        json!({
+            "nextPos": self.notes.len() + 1,
+            "estTimes": true,
+            "activeDecks": [DEFAULT_DECK_ID],
+            "sortType": "noteFld",
+            "timeLim": 0,
+            "sortBackwards": false,
+            "addToCur": true,
+            "curDeck": DEFAULT_DECK_ID,
+            "newSpread": 0,
+            "dueCounts": true,
+            "curModel": BASIC_MODEL_ID,
+            "collapseTime": 1200,
+        })
+    }
+
+    fn decks_json(&self) -> Value {
+        let mut decks = serde_json::Map::new();
+        decks.insert(
+            DEFAULT_DECK_ID.to_string(),
+            deck_json(DEFAULT_DECK_ID, DEFAULT_DECK_NAME, "", self.now),
+        );
+        for (name, (id, description)) in &self.decks {
+            decks.insert(id.to_string(), deck_json(*id, name, description, self.now));
+        }
+        Value::Object(decks)
+    }
+
+    fn models(&self) -> Value {
+        let front = "{{Front}}{{#Hints}}<br><br>{{hint:Hints}}{{/Hints}}";
+        let back = "{{FrontSide}}<hr id=answer>{{Back}}";
+        let reversed_back = "{{FrontSide}}<hr id=answer>{{Front}}";
+        let fields = ["Front", "Back", "Hints"];
+        let basic = model_json(
+            BASIC_MODEL_ID,
+            "Basic",
+            0,
+            &fields,
+            &[("Card 1", front, back)],
+            self.now,
+        );
+        let reversed = model_json(
+            REVERSED_MODEL_ID,
+            "Basic (and reversed card)",
+            0,
+            &fields,
+            &[
+                ("Card 1", front, back),
+                ("Card 2", "{{Back}}", reversed_back),
+            ],
+            self.now,
+        );
+        let cloze = model_json(
+            CLOZE_MODEL_ID,
+            "Cloze",
+            1,
+            &["Text", "Back Extra"],
+            &[(
+                "Cloze",
+                "{{cloze:Text}}",
+                "{{cloze:Text}}<br>{{Back Extra}}",
+            )],
+            self.now,
+        );
+        json!({
+            BASIC_MODEL_ID.to_string(): basic,
+            REVERSED_MODEL_ID.to_string(): reversed,
+            CLOZE_MODEL_ID.to_string(): cloze,
+        })
+    }
+}
+
+fn deck_json(id: i64, name: &str, description: &str, now: i64) -> Value {
+    json!({
+        "id": id,
+        "name": name,
+        "desc": description,
+        "mod": now / 1000,
+        "usn": -1,
+        "dyn": 0,
+        "conf": DEFAULT_CONFIG_ID,
+        "collapsed": false,
+        "browserCollapsed": false,
+        "extendNew": 0,
+        "extendRev": 0,
+        "newToday": [0, 0],
+        "revToday": [0, 0],
+        "lrnToday": [0, 0],
+        "timeToday": [0, 0],
+    })
+}
+
+fn deck_config() -> Value {
+    json!({
+        "id": DEFAULT_CONFIG_ID,
+        "name": "Default",
+        "mod": 0,
+        "usn": 0,
+        "maxTaken": 60,
+        "autoplay": true,
+        "timer": 0,
+        "replayq": true,
+        "dyn": false,
+        "new": {
+            "delays": [1.0, 10.0],
+            "ints": [1, 4, 0],
+            "initialFactor": 2500,
+            "order": 1,
+            "perDay": 20,
+            "bury": false,
+        },
+        "rev": {
+            "perDay": 200,
+            "ease4": 1.3,
+            "ivlFct": 1.0,
+            "maxIvl": 36500,
+            "bury": false,
+            "hardFactor": 1.2,
+        },
+        "lapse": {
+            "delays": [10.0],
+            "mult": 0.0,
+            "minInt": 1,
+            "leechFails": 8,
+            "leechAction": 1,
+        },
+    })
+}
+
+/// A note type with `templates` of (name, question, answer).
+fn model_json(
+    id: i64,
+    name: &str,
+    kind: i64,
+    fields: &[&str],
+    templates: &[(&str, &str, &str)],
+    now: i64,
+) -> Value {
+    let fields = fields
+        .iter()
+        .enumerate()
+        .map(|(ord, name)| {
+            json!({
+                "name": name,
+                "ord": ord,
+                "sticky": false,
+                "rtl": false,
+                "font": "Arial",
+                "size": 20,
+                "media": [],
+            })
+        })
+        .collect::<Vec<_>>();
+    let templates = templates
+        .iter()
+        .enumerate()
+        .map(|(ord, (name, question, answer))| {
+            json!({
+                "name": name,
+                "ord": ord,
+                "qfmt": question,
+                "afmt": answer,
+                "bqfmt": "",
+                "bafmt": "",
+                "did": null,
+            })
+        })
+        .collect::<Vec<_>>();
+    // the field a template needs to make a card: the front of a card and the back of a reversed one
+    let required = (0..templates.len())
+        .map(|ord| json!([ord, "any", [if kind == 0 && ord == 1 { 1 } else { 0 }]]))
+        .collect::<Vec<_>>();
+    json!({
+        "id": id,
+        "name": name,
+        "type": kind,
+        "mod": now / 1000,
+        "usn": -1,
+        "sortf": 0,
+        "did": DEFAULT_DECK_ID,
+        "flds": fields,
+        "tmpls": templates,
+        "req": required,
+        "tags": [],
+        "vers": [],
+        "css": ".card {\n font-family: arial;\n font-size: 20px;\n text-align: center;\n \
+                color: black;\n background-color: white;\n}\n",
+        "latexPre": "\\documentclass[12pt]{article}\n\\special{papersize=3in,5in}\n\
+                     \\usepackage[utf8]{inputenc}\n\\usepackage{amssymb,amsmath}\n\
+                     \\pagestyle{empty}\n\\setlength{\\parindent}{0in}\n\\begin{document}\n",
+        "latexPost": "\\end{document}",
+        "latexsvg": false,
+    })
+}
+
+/// The duplicate check of Anki: the first 8 hex digits of the SHA-1 of the sort field.
+fn checksum(text: &str) -> i64 {
+    i64::from(u32::from_be_bytes(
+        Sha1::digest(text.as_bytes())[..4]
+            .try_into()
+            .expect("4 bytes"),
+    ))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_checksum() {
+        // `echo -n abc | sha1sum` is a9993e36...
+        assert_eq!(checksum("abc"), 0xa9993e36);
+        assert_eq!(checksum(""), 0xda39a3ee);
+        // spans several blocks
+        assert_eq!(checksum(&"a".repeat(1000)), 0x291e9a6c);
+    }
+
+    #[test]
+    fn test_tag_name() {
+        assert_eq!(tag_name(" spanish  verbs "), "spanish_verbs");
+        assert_eq!(tag_name("rust"), "rust");
+    }
+}
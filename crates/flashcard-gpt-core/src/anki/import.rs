@@ -0,0 +1,1035 @@
+//! Import of Anki packages. Every Anki deck becomes a deck with its parents, and every note is
+//! mapped by the fields its card templates show:
+//!
+//! - a note with a single card becomes a card;
+//! - a note with a card and its reverse, like "Basic (and reversed card)", becomes a card with a
+//!   reversed sibling, see [`crate::store::siblings`];
+//! - a note with more cards becomes a card group;
+//! - a cloze note becomes a card per cloze index, see [`crate::model::cloze`].
+//!
+//! A `{{type:Field}}` on the front turns the card into a typed-answer card. Notes of any other
+//! note type are skipped and counted in the [`ImportReport`]. Importing the same package twice
+//! creates everything twice.
+
+use crate::anki::{html_to_text, media_references, DECK_SEPARATOR, FIELD_SEPARATOR};
+use crate::blob::BlobStore;
+use crate::error::CoreError;
+use crate::model::card::{CreateCard, UpdateCard};
+use crate::model::card_data::CardData;
+use crate::model::card_group::CreateCardGroup;
+use crate::model::deck::{CreateDeck, DeckSettings};
+use crate::model::deck_card::CreateDeckCard;
+use crate::model::deck_card_group::CreateDeckCardGroup;
+use crate::model::history::CreateHistory;
+use crate::model::media::{blob_key, CardSide, Media, MediaKind};
+use crate::model::time::Time;
+use crate::reexports::db::sql::Thing;
+use crate::review_log::{GradeMapping, Review};
+use crate::store::any::AnyStorage;
+use crate::store::{CardGroupStore, CardStore, DeckStore, HistoryStore, Storage, TagStore};
+use bon::Builder;
+use chrono::{DateTime, TimeDelta, Utc};
+use rusqlite::{Connection, OpenFlags};
+use serde::Deserialize;
+use std::collections::{BTreeMap, HashMap, HashSet};
+use std::fmt::{Debug, Display, Formatter};
+use std::hash::{DefaultHasher, Hasher};
+use std::io::{Cursor, Read};
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::Arc;
+use zip::result::ZipError;
+use zip::ZipArchive;
+
+const STANDARD_NOTE_TYPE: i64 = 0;
+const CLOZE_NOTE_TYPE: i64 = 1;
+
+const MAX_TITLE_LENGTH: usize = 64;
+
+#[derive(Debug, Clone, Builder)]
+pub struct ImportOptions {
+    /// Also import the review log into the history, so that the cards are scheduled as if they
+    /// had been answered here.
+    #[builder(default)]
+    pub history: bool,
+
+    /// Difficulty of the `ease` of every review, see [`GradeMapping`].
+    #[builder(default)]
+    pub grades: GradeMapping,
+
+    /// Daily limit of the created decks.
+    #[builder(default = 20)]
+    pub daily_limit: usize,
+
+    /// Where the media of the notes are copied to, they are skipped without it.
+    pub blobs: Option<BlobStore>,
+}
+
+impl Default for ImportOptions {
+    fn default() -> Self {
+        Self::builder().build()
+    }
+}
+
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct ImportReport {
+    pub decks: usize,
+    pub cards: usize,
+    pub card_groups: usize,
+    pub history: usize,
+    pub media: usize,
+    /// Media that are missing from the package or could not be stored.
+    pub skipped_media: usize,
+    /// Notes that were not imported, counted by note type and reason.
+    pub skipped: BTreeMap<Arc<str>, usize>,
+}
+
+impl ImportReport {
+    fn skip(&mut self, note_type: &str, reason: &str) {
+        *self
+            .skipped
+            .entry(Arc::from(format!("{note_type}: {reason}")))
+            .or_default() += 1;
+    }
+}
+
+impl Display for ImportReport {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        writeln!(f, "Decks: {}", self.decks)?;
+        writeln!(f, "Cards: {}", self.cards)?;
+        writeln!(f, "Card groups: {}", self.card_groups)?;
+        writeln!(f, "History records: {}", self.history)?;
+        write!(f, "Media: {}", self.media)?;
+        if self.skipped_media > 0 {
+            write!(f, ", {} skipped", self.skipped_media)?;
+        }
+        if !self.skipped.is_empty() {
+            write!(f, "\nSkipped notes:")?;
+            for (reason, count) in &self.skipped {
+                write!(f, "\n  {reason}: {count}")?;
+            }
+        }
+        Ok(())
+    }
+}
+
+#[derive(Clone)]
+pub struct AnkiImporter<S: Storage = AnyStorage> {
+    pub cards: S::Cards,
+    pub card_groups: S::CardGroups,
+    pub decks: S::Decks,
+    pub tags: S::Tags,
+    pub history: S::History,
+}
+
+impl<S: Storage> Debug for AnkiImporter<S> {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        write!(f, "AnkiImporter")
+    }
+}
+
+impl<S: Storage> AnkiImporter<S> {
+    pub fn new(
+        cards: S::Cards,
+        card_groups: S::CardGroups,
+        decks: S::Decks,
+        tags: S::Tags,
+        history: S::History,
+    ) -> Self {
+        Self {
+            cards,
+            card_groups,
+            decks,
+            tags,
+            history,
+        }
+    }
+
+    /// Imports the `.apkg` or `.colpkg` file in `package` for `user`.
+    pub async fn import(
+        &self,
+        user: impl Into<Thing>,
+        package: Vec<u8>,
+        options: ImportOptions,
+    ) -> Result<ImportReport, CoreError> {
+        let collection = tokio::task::spawn_blocking(move || read_package(&package)).await??;
+        let mut import = Import {
+            importer: self,
+            user: user.into(),
+            collection: &collection,
+            options: &options,
+            report: ImportReport::default(),
+            decks: HashMap::new(),
+            edges: HashMap::new(),
+        };
+
+        let mut cards_by_note = HashMap::<i64, Vec<&AnkiCard>>::new();
+        for card in &collection.cards {
+            cards_by_note.entry(card.note).or_default().push(card);
+        }
+        for note in &collection.notes {
+            let cards = cards_by_note.remove(&note.id).unwrap_or_default();
+            import.note(note, &cards).await?;
+        }
+        if options.history {
+            import.history().await?;
+        }
+
+        Ok(import.report)
+    }
+}
+
+/// What a note is scheduled as: the deck card or the deck card group it became.
+enum Edge {
+    DeckCard(Thing),
+    DeckCardGroup(Thing),
+}
+
+struct Import<'a, S: Storage> {
+    importer: &'a AnkiImporter<S>,
+    user: Thing,
+    collection: &'a Collection,
+    options: &'a ImportOptions,
+    report: ImportReport,
+    /// Created decks by their full Anki name.
+    decks: HashMap<String, Thing>,
+    /// By the id of the Anki card.
+    edges: HashMap<i64, Edge>,
+}
+
+impl<S: Storage> Import<'_, S> {
+    async fn note(&mut self, note: &Note, cards: &[&AnkiCard]) -> Result<(), CoreError> {
+        let collection = self.collection;
+        let Some(model) = collection.models.get(&note.model) else {
+            self.report.skip("unknown note type", "missing");
+            return Ok(());
+        };
+        if cards.is_empty() {
+            self.report.skip(&model.name, "no cards");
+            return Ok(());
+        }
+
+        let tags = self
+            .importer
+            .tags
+            .get_or_create_tags(
+                self.user.clone(),
+                note.tags.iter().map(|tag| Arc::from(tag.as_str())),
+            )
+            .await?
+            .into_iter()
+            .map(|tag| tag.id)
+            .collect::<Vec<_>>();
+
+        match model.kind {
+            STANDARD_NOTE_TYPE => self.standard_note(model, note, cards, tags).await,
+            CLOZE_NOTE_TYPE => self.cloze_note(model, note, cards, tags).await,
+            _ => {
+                self.report.skip(&model.name, "unsupported note type");
+                Ok(())
+            }
+        }
+    }
+
+    async fn standard_note(
+        &mut self,
+        model: &Model,
+        note: &Note,
+        cards: &[&AnkiCard],
+        tags: Vec<Thing>,
+    ) -> Result<(), CoreError> {
+        let mut dtos = vec![];
+        for &card in cards {
+            let Some(template) = model.templates.iter().find(|t| t.ord == card.ord) else {
+                continue;
+            };
+            if let Some(dto) = self.card(model, note, template, tags.clone()).await? {
+                dtos.push((card, template, dto));
+            }
+        }
+
+        match dtos.as_slice() {
+            [] => {
+                self.report.skip(&model.name, "empty front");
+                Ok(())
+            }
+            [(card, _, dto)] => {
+                let created = self.importer.cards.create(dto.clone()).await?;
+                self.relate_card(card, created.id).await
+            }
+            [(card, template, dto), (reversed_card, reversed_template, reversed_dto)]
+                if template.front == reversed_template.back
+                    && template.back == reversed_template.front =>
+            {
+                let created = self.importer.cards.create(dto.clone()).await?;
+                let reversed = self
+                    .importer
+                    .cards
+                    .create(CreateCard {
+                        sibling: Some(created.id.clone()),
+                        ..reversed_dto.clone()
+                    })
+                    .await?;
+                let update = UpdateCard {
+                    importance: None,
+                    difficulty: None,
+                    sibling: Some(reversed.id.clone()),
+                    annotations: None,
+                    title: None,
+                    front: None,
+                    back: None,
+                    hints: None,
+                    tags: None,
+                };
+                self.importer
+                    .cards
+                    .patch(created.id.clone(), update)
+                    .await?;
+                self.relate_card(card, created.id).await?;
+                self.relate_card(reversed_card, reversed.id).await
+            }
+            _ => {
+                let mut created = vec![];
+                for (_, _, dto) in &dtos {
+                    created.push(self.importer.cards.create(dto.clone()).await?.id);
+                }
+                self.report.cards += created.len();
+                let card_group = self
+                    .importer
+                    .card_groups
+                    .create(CreateCardGroup {
+                        user: self.user.clone(),
+                        title: note_title(model, note),
+                        importance: 0,
+                        difficulty: 0,
+                        data: None,
+                        cards: created,
+                        tags,
+                    })
+                    .await?;
+                self.report.card_groups += 1;
+
+                let deck = self.deck(dtos[0].0.deck).await?;
+                let deck_card_group = self
+                    .importer
+                    .decks
+                    .relate_card_group(CreateDeckCardGroup {
+                        deck,
+                        card_group: card_group.id,
+                    })
+                    .await?;
+                for (card, _, _) in &dtos {
+                    let edge = Edge::DeckCardGroup(deck_card_group.id.clone());
+                    self.edges.insert(card.id, edge);
+                }
+                Ok(())
+            }
+        }
+    }
+
+    async fn cloze_note(
+        &mut self,
+        model: &Model,
+        note: &Note,
+        cards: &[&AnkiCard],
+        tags: Vec<Thing>,
+    ) -> Result<(), CoreError> {
+        let Some(template) = model.templates.first() else {
+            self.report.skip(&model.name, "no card templates");
+            return Ok(());
+        };
+        let Some(dto) = self.card(model, note, template, tags).await? else {
+            self.report.skip(&model.name, "empty front");
+            return Ok(());
+        };
+        if dto
+            .front
+            .as_deref()
+            .unwrap_or_default()
+            .contains("image-occlusion:")
+        {
+            self.report.skip(&model.name, "image occlusion");
+            return Ok(());
+        }
+
+        let mut imported = false;
+        for dto in dto.into_cloze_cards() {
+            // the Anki card of `{{cN::..}}` has the ordinal N - 1
+            let index = dto.data.as_ref().and_then(|data| data.cloze);
+            let Some(card) =
+                index.and_then(|index| cards.iter().find(|card| card.ord == i64::from(index) - 1))
+            else {
+                continue;
+            };
+            let created = self.importer.cards.create(dto).await?;
+            self.relate_card(card, created.id).await?;
+            imported = true;
+        }
+        if !imported {
+            self.report.skip(&model.name, "no cloze deletions");
+        }
+
+        Ok(())
+    }
+
+    /// The card of `template`, `None` when its front is empty.
+    async fn card(
+        &mut self,
+        model: &Model,
+        note: &Note,
+        template: &Template,
+        tags: Vec<Thing>,
+    ) -> Result<Option<CreateCard>, CoreError> {
+        let front = note.text(&template.front);
+        let back = note.text(&template.back);
+
+        let mut media = self.media(note, &template.front, CardSide::Front).await?;
+        media.extend(self.media(note, &template.back, CardSide::Back).await?);
+        if front.is_empty() && media.iter().all(|media| media.side != CardSide::Front) {
+            return Ok(None);
+        }
+
+        let data = CardData {
+            answer: template
+                .typed
+                .map(|field| note.text(&[field]))
+                .filter(|answer| !answer.is_empty())
+                .map(Arc::from),
+            media,
+            ..CardData::default()
+        };
+
+        Ok(Some(CreateCard {
+            user: self.user.clone(),
+            title: note_title(model, note),
+            front: Some(Arc::from(front)),
+            back: (!back.is_empty()).then(|| Arc::from(back)),
+            hints: note.hints(&template.hints),
+            difficulty: 0,
+            importance: 0,
+            data: (data != CardData::default()).then_some(data),
+            choices: None,
+            sibling: None,
+            reverse: false,
+            tags,
+        }))
+    }
+
+    /// Copies the media the `fields` refer to into the blob store.
+    async fn media(
+        &mut self,
+        note: &Note,
+        fields: &[usize],
+        side: CardSide,
+    ) -> Result<Vec<Media>, CoreError> {
+        let mut media = vec![];
+        let names = fields
+            .iter()
+            .filter_map(|&field| note.fields.get(field))
+            .flat_map(|field| media_references(field));
+        for name in names {
+            let (Some(blobs), Some(bytes)) =
+                (&self.options.blobs, self.collection.media.get(&name))
+            else {
+                self.report.skipped_media += 1;
+                continue;
+            };
+
+            let mut hasher = DefaultHasher::new();
+            hasher.write(bytes);
+            let key = blob_key(&format!("anki-{:016x}", hasher.finish()), Some(&name));
+            blobs.put(&key, bytes).await?;
+            self.report.media += 1;
+
+            let extension = name.rsplit_once('.').map(|(_, extension)| extension);
+            let kind = match extension.map(str::to_ascii_lowercase).as_deref() {
+                Some("jpg" | "jpeg" | "png" | "webp") => MediaKind::Photo,
+                _ => MediaKind::Document,
+            };
+            media.push(Media {
+                kind,
+                side,
+                file_id: Arc::from(""),
+                file_name: Some(Arc::from(name)),
+                mime_type: None,
+                blob: Some(key),
+            });
+        }
+        Ok(media)
+    }
+
+    async fn relate_card(&mut self, card: &AnkiCard, created: Thing) -> Result<(), CoreError> {
+        let deck = self.deck(card.deck).await?;
+        let deck_card = self
+            .importer
+            .decks
+            .relate_card(CreateDeckCard {
+                deck,
+                card: created,
+            })
+            .await?;
+        self.report.cards += 1;
+        self.edges.insert(card.id, Edge::DeckCard(deck_card.id));
+        Ok(())
+    }
+
+    /// The deck with the Anki id `id`, created along with its parents on first use.
+    async fn deck(&mut self, id: i64) -> Result<Thing, CoreError> {
+        let name = self
+            .collection
+            .decks
+            .get(&id)
+            .map_or("Default", String::as_str);
+
+        let mut parent: Option<Thing> = None;
+        let mut path = String::new();
+        for title in name.split(DECK_SEPARATOR) {
+            if !path.is_empty() {
+                path.push_str(DECK_SEPARATOR);
+            }
+            path.push_str(title);
+
+            let deck = match self.decks.get(&path) {
+                Some(deck) => deck.clone(),
+                None => {
+                    let deck = self
+                        .importer
+                        .decks
+                        .create(CreateDeck {
+                            description: None,
+                            parent: parent.clone(),
+                            settings: Some(DeckSettings {
+                                daily_limit: self.options.daily_limit,
+                                reverse_cards: false,
+                            }),
+                            tags: vec![],
+                            title: Arc::from(title),
+                            user: self.user.clone(),
+                        })
+                        .await?
+                        .id;
+                    self.report.decks += 1;
+                    self.decks.insert(path.clone(), deck.clone());
+                    deck
+                }
+            };
+            parent = Some(deck);
+        }
+
+        parent.ok_or_else(|| invalid(format!("Deck {id} has no name")))
+    }
+
+    async fn history(&mut self) -> Result<(), CoreError> {
+        for review in &self.collection.reviews {
+            let (Some(edge), Some(difficulty)) = (
+                self.edges.get(&review.card),
+                self.options.grades.difficulty(review.ease),
+            ) else {
+                continue;
+            };
+            let (deck_card, deck_card_group) = match edge {
+                Edge::DeckCard(id) => (Some(id.clone()), None),
+                Edge::DeckCardGroup(id) => (None, Some(id.clone())),
+            };
+            self.importer
+                .history
+                .create_custom(CreateHistory {
+                    user: self.user.clone(),
+                    deck_card,
+                    deck_card_group,
+                    difficulty,
+                    time: Some(Time {
+                        created_at: review.time,
+                        updated_at: review.time,
+                        deleted_at: None,
+                    }),
+                    hide_for: None,
+                    steps: vec![],
+                })
+                .await?;
+            self.report.history += 1;
+        }
+        Ok(())
+    }
+}
+
+/// The review log of the `.apkg` or `.colpkg` file in `package`. A card is named by its front as
+/// [`AnkiImporter`] writes it, or by its title for a cloze card, whose front is the whole note.
+pub(crate) fn read_reviews(package: &[u8]) -> Result<Vec<Review>, CoreError> {
+    let collection = read_package(package)?;
+    let notes = collection
+        .notes
+        .iter()
+        .map(|note| (note.id, note))
+        .collect::<HashMap<_, _>>();
+
+    let mut names = HashMap::new();
+    for card in &collection.cards {
+        let Some(note) = notes.get(&card.note) else {
+            continue;
+        };
+        let Some(model) = collection.models.get(&note.model) else {
+            continue;
+        };
+        let name = match model.kind {
+            CLOZE_NOTE_TYPE => format!("{} (c{})", note_title(model, note), card.ord + 1),
+            _ => match model.templates.iter().find(|t| t.ord == card.ord) {
+                Some(template) => note.text(&template.front),
+                None => continue,
+            },
+        };
+        if !name.is_empty() {
+            names.insert(card.id, Arc::<str>::from(name));
+        }
+    }
+
+    let reviews = collection
+        .reviews
+        .iter()
+        .filter_map(|review| {
+            let interval = match review.interval {
+                0 => None,
+                days if days > 0 => Some(TimeDelta::days(days)),
+                seconds => Some(TimeDelta::seconds(-seconds)),
+            };
+            Some(Review {
+                card: names.get(&review.card)?.clone(),
+                time: review.time,
+                grade: review.ease,
+                interval,
+            })
+        })
+        .collect();
+    Ok(reviews)
+}
+
+fn note_title(model: &Model, note: &Note) -> Arc<str> {
+    let text = html_to_text(&note.sort_field);
+    let line = text.lines().find(|line| !line.trim().is_empty());
+    match line {
+        Some(line) if line.chars().count() > MAX_TITLE_LENGTH => {
+            let line = line.chars().take(MAX_TITLE_LENGTH - 1).collect::<String>();
+            Arc::from(format!("{}…", line.trim_end()))
+        }
+        Some(line) => Arc::from(line.trim()),
+        None => Arc::from(format!("{} {}", model.name, note.id)),
+    }
+}
+
+fn invalid(message: impl Into<String>) -> CoreError {
+    CoreError::InvalidAnkiPackage(Arc::from(message.into()))
+}
+
+/// The parts of a collection the import needs, with the media the notes refer to.
+#[derive(Debug, Default)]
+struct Collection {
+    models: HashMap<i64, Model>,
+    decks: HashMap<i64, String>,
+    notes: Vec<Note>,
+    cards: Vec<AnkiCard>,
+    /// Oldest first.
+    reviews: Vec<RevlogEntry>,
+    media: HashMap<String, Vec<u8>>,
+}
+
+/// A note type.
+#[derive(Debug)]
+struct Model {
+    name: String,
+    kind: i64,
+    templates: Vec<Template>,
+}
+
+#[derive(Debug)]
+struct Template {
+    ord: i64,
+    /// Indexes of the fields on the front.
+    front: Vec<usize>,
+    /// Indexes of the fields on the back that are not on the front.
+    back: Vec<usize>,
+    /// The field of a `{{type:Field}}` on the front.
+    typed: Option<usize>,
+    /// The fields of a `{{hint:Field}}` on either side, a hint per line.
+    hints: Vec<usize>,
+}
+
+#[derive(Debug)]
+struct Note {
+    id: i64,
+    model: i64,
+    tags: Vec<String>,
+    fields: Vec<String>,
+    sort_field: String,
+}
+
+impl Note {
+    /// The text of the non-empty `fields`, a paragraph each.
+    fn text(&self, fields: &[usize]) -> String {
+        fields
+            .iter()
+            .filter_map(|&field| self.fields.get(field))
+            .map(|field| html_to_text(field))
+            .filter(|text| !text.is_empty())
+            .collect::<Vec<_>>()
+            .join("\n\n")
+    }
+
+    /// The non-empty lines of the `fields`, a hint each.
+    fn hints(&self, fields: &[usize]) -> Vec<Arc<str>> {
+        self.text(fields)
+            .lines()
+            .map(str::trim)
+            .filter(|line| !line.is_empty())
+            .map(Arc::from)
+            .collect()
+    }
+}
+
+#[derive(Debug)]
+struct AnkiCard {
+    id: i64,
+    note: i64,
+    deck: i64,
+    ord: i64,
+}
+
+#[derive(Debug)]
+struct RevlogEntry {
+    card: i64,
+    time: DateTime<Utc>,
+    ease: u8,
+    /// Days until the card is due, or seconds when negative.
+    interval: i64,
+}
+
+#[derive(Deserialize)]
+struct ModelJson {
+    name: String,
+    #[serde(rename = "type", default)]
+    kind: i64,
+    flds: Vec<FieldJson>,
+    tmpls: Vec<TemplateJson>,
+}
+
+#[derive(Deserialize)]
+struct FieldJson {
+    name: String,
+    ord: usize,
+}
+
+#[derive(Deserialize)]
+struct TemplateJson {
+    ord: i64,
+    qfmt: String,
+    afmt: String,
+}
+
+#[derive(Deserialize)]
+struct DeckJson {
+    name: String,
+}
+
+impl From<ModelJson> for Model {
+    fn from(mut model: ModelJson) -> Self {
+        model.flds.sort_by_key(|field| field.ord);
+        let fields = model
+            .flds
+            .into_iter()
+            .map(|field| field.name)
+            .collect::<Vec<_>>();
+
+        let templates = model
+            .tmpls
+            .into_iter()
+            .map(|template| {
+                let front = template_fields(&template.qfmt, &fields);
+                let back = template_fields(&template.afmt, &fields);
+                let mut hints = back.hints;
+                hints.retain(|field| !front.hints.contains(field));
+                hints.splice(0..0, front.hints);
+                let mut back = back
+                    .shown
+                    .into_iter()
+                    .filter(|field| !front.shown.contains(field) && !hints.contains(field))
+                    .collect::<Vec<_>>();
+                if let Some(typed) = front.typed
+                    && !back.contains(&typed)
+                {
+                    back.insert(0, typed);
+                }
+                Template {
+                    ord: template.ord,
+                    front: front.shown,
+                    back,
+                    typed: front.typed,
+                    hints,
+                }
+            })
+            .collect();
+
+        Model {
+            name: model.name,
+            kind: model.kind,
+            templates,
+        }
+    }
+}
+
+/// The fields a template refers to.
+#[derive(Debug, Default, PartialEq)]
+struct TemplateFields {
+    /// Indexes of the fields shown in order.
+    shown: Vec<usize>,
+    /// The field of a `{{type:Field}}`, which is not shown.
+    typed: Option<usize>,
+    /// The fields of a `{{hint:Field}}`, which are shown on demand.
+    hints: Vec<usize>,
+}
+
+fn template_fields(template: &str, fields: &[String]) -> TemplateFields {
+    let mut found = TemplateFields::default();
+    let mut rest = template;
+    while let Some(start) = rest.find("{{") {
+        rest = &rest[start + 2..];
+        let Some(end) = rest.find("}}") else {
+            break;
+        };
+        let tag = rest[..end].trim();
+        rest = &rest[end + 2..];
+
+        // sections and comments
+        if tag.starts_with(['#', '^', '/', '!']) {
+            continue;
+        }
+        // filters come before the field name, `{{text:cloze:Text}}`
+        let name = tag.rsplit(':').next().unwrap_or(tag).trim();
+        let Some(index) = fields.iter().position(|field| field == name) else {
+            continue;
+        };
+        if tag.starts_with("type:") {
+            found.typed = Some(index);
+        } else if tag.starts_with("hint:") {
+            if !found.hints.contains(&index) {
+                found.hints.push(index);
+            }
+        } else if !found.shown.contains(&index) {
+            found.shown.push(index);
+        }
+    }
+    found
+}
+
+fn read_package(package: &[u8]) -> Result<Collection, CoreError> {
+    let mut archive = ZipArchive::new(Cursor::new(package))?;
+    let names = archive
+        .file_names()
+        .map(ToOwned::to_owned)
+        .collect::<HashSet<_>>();
+
+    // packages in the new format also have a `collection.anki2` asking to update Anki
+    let entry = if names.contains("collection.anki21") {
+        "collection.anki21"
+    } else if names.contains("collection.anki21b") {
+        return Err(invalid(
+            "the package is in the format of Anki 2.1.50+, export it again with \
+             \"Support older Anki versions\" checked",
+        ));
+    } else if names.contains("collection.anki2") {
+        "collection.anki2"
+    } else {
+        return Err(invalid("the package has no collection"));
+    };
+    let mut bytes = vec![];
+    archive.by_name(entry)?.read_to_end(&mut bytes)?;
+    let mut collection = read_collection(&bytes)?;
+
+    let media_names: HashMap<String, String> = match archive.by_name("media") {
+        Ok(mut file) => {
+            let mut json = String::new();
+            file.read_to_string(&mut json)?;
+            if json.trim().is_empty() {
+                HashMap::new()
+            } else {
+                serde_json::from_str(&json)?
+            }
+        }
+        Err(ZipError::FileNotFound) => HashMap::new(),
+        Err(err) => return Err(err.into()),
+    };
+    let referenced = collection
+        .notes
+        .iter()
+        .flat_map(|note| &note.fields)
+        .flat_map(|field| media_references(field))
+        .collect::<HashSet<_>>();
+    for (entry, name) in media_names {
+        if !referenced.contains(&name) {
+            continue;
+        }
+        let mut bytes = vec![];
+        match archive.by_name(&entry) {
+            Ok(mut file) => file.read_to_end(&mut bytes)?,
+            Err(ZipError::FileNotFound) => continue,
+            Err(err) => return Err(err.into()),
+        };
+        collection.media.insert(name, bytes);
+    }
+
+    Ok(collection)
+}
+
+/// SQLite can't open a database in memory, the collection is read from a temporary copy.
+fn read_collection(bytes: &[u8]) -> Result<Collection, CoreError> {
+    static COUNTER: AtomicUsize = AtomicUsize::new(0);
+    let path = std::env::temp_dir().join(format!(
+        "flashcard-gpt-anki-{}-{}.sqlite",
+        std::process::id(),
+        COUNTER.fetch_add(1, Ordering::Relaxed)
+    ));
+    std::fs::write(&path, bytes)?;
+    let collection = Connection::open_with_flags(&path, OpenFlags::SQLITE_OPEN_READ_ONLY)
+        .map_err(CoreError::from)
+        .and_then(|conn| query_collection(&conn));
+    std::fs::remove_file(&path)?;
+    collection
+}
+
+fn query_collection(conn: &Connection) -> Result<Collection, CoreError> {
+    let (models, decks): (String, String) =
+        conn.query_row("select models, decks from col", [], |row| {
+            Ok((row.get(0)?, row.get(1)?))
+        })?;
+    if models.trim().is_empty() || models.trim() == "{}" {
+        return Err(invalid(
+            "the collection has no note types, export it again with \
+             \"Support older Anki versions\" checked",
+        ));
+    }
+    let models = serde_json::from_str::<HashMap<String, ModelJson>>(&models)?
+        .into_iter()
+        .filter_map(|(id, model)| Some((id.parse().ok()?, Model::from(model))))
+        .collect();
+    let decks = serde_json::from_str::<HashMap<String, DeckJson>>(&decks)?
+        .into_iter()
+        .filter_map(|(id, deck)| Some((id.parse().ok()?, deck.name)))
+        .collect();
+
+    let mut stmt =
+        conn.prepare("select id, mid, tags, flds, cast(sfld as text) from notes order by id")?;
+    let notes = stmt
+        .query_map([], |row| {
+            let tags: String = row.get(2)?;
+            let fields: String = row.get(3)?;
+            Ok(Note {
+                id: row.get(0)?,
+                model: row.get(1)?,
+                tags: tags.split_whitespace().map(ToOwned::to_owned).collect(),
+                fields: fields
+                    .split(FIELD_SEPARATOR)
+                    .map(ToOwned::to_owned)
+                    .collect(),
+                sort_field: row.get(4)?,
+            })
+        })?
+        .collect::<Result<Vec<_>, _>>()?;
+
+    // cards in a filtered deck keep their home deck in `odid`
+    let mut stmt = conn.prepare(
+        "select id, nid, case when odid != 0 then odid else did end, ord from cards
+         order by nid, ord",
+    )?;
+    let cards = stmt
+        .query_map([], |row| {
+            Ok(AnkiCard {
+                id: row.get(0)?,
+                note: row.get(1)?,
+                deck: row.get(2)?,
+                ord: row.get(3)?,
+            })
+        })?
+        .collect::<Result<Vec<_>, _>>()?;
+
+    // the id of a review is its time in milliseconds
+    let mut stmt = conn.prepare("select cid, id, ease, ivl from revlog order by id")?;
+    let reviews = stmt
+        .query_map([], |row| {
+            let millis: i64 = row.get(1)?;
+            Ok(RevlogEntry {
+                card: row.get(0)?,
+                time: DateTime::from_timestamp_millis(millis).unwrap_or_default(),
+                ease: row.get(2)?,
+                interval: row.get(3)?,
+            })
+        })?
+        .collect::<Result<Vec<_>, _>>()?;
+
+    Ok(Collection {
+        models,
+        decks,
+        notes,
+        cards,
+        reviews,
+        media: HashMap::new(),
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn fields(names: &[&str]) -> Vec<String> {
+        names.iter().map(|name| name.to_string()).collect()
+    }
+
+    #[test]
+    fn test_template_fields() {
+        let fields = fields(&["Front", "Back", "Extra", "Text"]);
+        let shown = |shown: Vec<usize>| TemplateFields {
+            shown,
+            ..TemplateFields::default()
+        };
+        assert_eq!(
+            template_fields("{{Front}}<br>{{#Extra}}{{hint:Extra}}{{/Extra}}", &fields),
+            TemplateFields {
+                shown: vec![0],
+                typed: None,
+                hints: vec![2],
+            }
+        );
+        assert_eq!(
+            template_fields("{{Front}}\n\n{{type:Back}}", &fields),
+            TemplateFields {
+                shown: vec![0],
+                typed: Some(1),
+                hints: vec![],
+            }
+        );
+        assert_eq!(
+            template_fields("{{FrontSide}}<hr id=answer>{{ Back }}{{Missing}}", &fields),
+            shown(vec![1])
+        );
+        assert_eq!(template_fields("{{cloze:Text}}", &fields), shown(vec![3]));
+    }
+
+    #[test]
+    fn test_model_from_json() {
+        let model: ModelJson = serde_json::from_str(
+            r#"{
+                "name": "Basic (type in the answer)",
+                "type": 0,
+                "flds": [{"name": "Back", "ord": 1}, {"name": "Front", "ord": 0}],
+                "tmpls": [{
+                    "ord": 0,
+                    "qfmt": "{{Front}}\n\n{{type:Back}}",
+                    "afmt": "{{Front}}<hr id=answer>{{type:Back}}"
+                }]
+            }"#,
+        )
+        .unwrap();
+        let model = Model::from(model);
+        let template = &model.templates[0];
+        assert_eq!(template.front, vec![0]);
+        assert_eq!(template.back, vec![1]);
+        assert_eq!(template.typed, Some(1));
+        assert!(template.hints.is_empty());
+    }
+}
@@ -0,0 +1,185 @@
+//! Anki packages: `.apkg` files with some decks and `.colpkg` files with a whole collection. Both
+//! are zip archives of a SQLite collection, a `media` file mapping the numbered entries of the
+//! archive to file names, and the media files themselves.
+//!
+//! Only the legacy collection schema is supported, the one Anki writes when "Support older Anki
+//! versions" is checked in the export dialog. Newer packages keep a zstd compressed
+//! `collection.anki21b` with the note types in protobuf.
+
+pub mod export;
+pub mod import;
+
+/// Separates the fields of a note.
+pub const FIELD_SEPARATOR: char = '\x1f';
+
+/// Separates the levels of a deck name, `Parent::Child`.
+pub const DECK_SEPARATOR: &str = "::";
+
+/// The HTML of a note field as Markdown text: line breaks and blocks become new lines, bold,
+/// italic and code are kept, media references and other tags are dropped.
+pub fn html_to_text(html: &str) -> String {
+    let mut text = String::with_capacity(html.len());
+    let mut rest = html;
+    while let Some(start) = rest.find(['<', '[']) {
+        text.push_str(&decode_entities(&rest[..start]));
+        rest = &rest[start..];
+
+        if rest.starts_with("[sound:")
+            && let Some(end) = rest.find(']')
+        {
+            rest = &rest[end + 1..];
+            continue;
+        }
+        if rest.starts_with('[') {
+            text.push('[');
+            rest = &rest[1..];
+            continue;
+        }
+
+        let Some(end) = rest.find('>') else {
+            text.push('<');
+            rest = &rest[1..];
+            continue;
+        };
+        let tag = rest[1..end].trim().to_ascii_lowercase();
+        rest = &rest[end + 1..];
+
+        let closing = tag.starts_with('/');
+        let name = tag
+            .trim_start_matches('/')
+            .split(|c: char| c.is_whitespace() || c == '/')
+            .next()
+            .unwrap_or_default();
+        match name {
+            "br" => text.push('\n'),
+            "div" | "p" | "tr" | "li" | "ul" | "ol" if closing => text.push('\n'),
+            "li" => text.push_str("- "),
+            "b" | "strong" => text.push_str("**"),
+            "i" | "em" => text.push('_'),
+            "code" => text.push('`'),
+            _ => {}
+        }
+    }
+    text.push_str(&decode_entities(rest));
+
+    let lines = text.lines().map(str::trim_end).collect::<Vec<_>>();
+    let mut text = lines.join("\n");
+    while text.contains("\n\n\n") {
+        text = text.replace("\n\n\n", "\n\n");
+    }
+    text.trim().to_string()
+}
+
+/// Names of the media files a note field refers to with `<img src="..">` or `[sound:..]`.
+pub fn media_references(html: &str) -> Vec<String> {
+    let mut names = vec![];
+
+    let lowercase = html.to_ascii_lowercase();
+    let mut offset = 0;
+    while let Some(start) = lowercase[offset..].find("<img").map(|start| offset + start) {
+        let end = lowercase[start..]
+            .find('>')
+            .map_or(html.len(), |end| start + end);
+        offset = end;
+        let Some(src) = lowercase[start..end]
+            .find("src=")
+            .map(|src| start + src + 4)
+        else {
+            continue;
+        };
+        let value = &html[src..end];
+        let name = match value.chars().next() {
+            Some(quote @ ('"' | '\'')) => value[1..].split(quote).next(),
+            _ => value.split(|c: char| c.is_whitespace() || c == '/').next(),
+        };
+        names.extend(name.filter(|name| !name.is_empty()).map(decode_entities));
+    }
+
+    let mut rest = html;
+    while let Some(start) = rest.find("[sound:") {
+        rest = &rest[start + 7..];
+        let Some(end) = rest.find(']') else {
+            break;
+        };
+        names.push(decode_entities(&rest[..end]));
+        rest = &rest[end + 1..];
+    }
+
+    names
+}
+
+fn decode_entities(text: &str) -> String {
+    let mut decoded = String::with_capacity(text.len());
+    let mut rest = text;
+    while let Some(start) = rest.find('&') {
+        decoded.push_str(&rest[..start]);
+        rest = &rest[start..];
+        let entity = rest[1..]
+            .find(';')
+            .filter(|&end| end <= 8)
+            .and_then(|end| Some((decode_entity(&rest[1..end + 1])?, end + 2)));
+        match entity {
+            Some((c, len)) => {
+                decoded.push(c);
+                rest = &rest[len..];
+            }
+            None => {
+                decoded.push('&');
+                rest = &rest[1..];
+            }
+        }
+    }
+    decoded.push_str(rest);
+    decoded
+}
+
+fn decode_entity(entity: &str) -> Option<char> {
+    match entity {
+        "amp" => Some('&'),
+        "lt" => Some('<'),
+        "gt" => Some('>'),
+        "quot" => Some('"'),
+        "apos" => Some('\''),
+        "nbsp" => Some(' '),
+        _ => {
+            let code = entity.strip_prefix('#')?;
+            let code = match code.strip_prefix(['x', 'X']) {
+                Some(hex) => u32::from_str_radix(hex, 16).ok()?,
+                None => code.parse().ok()?,
+            };
+            char::from_u32(code)
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_html_to_text() {
+        assert_eq!(
+            html_to_text("<div>What is <b>2&nbsp;+ 2</b>?</div><div><br></div><div>a &lt; b</div>"),
+            "What is **2 + 2**?\n\na < b"
+        );
+        assert_eq!(
+            html_to_text("<ul><li>one</li><li>two</li></ul>[sound:hello.mp3]"),
+            "- one\n- two"
+        );
+        assert_eq!(html_to_text("see [1] <img src=\"a.png\">"), "see [1]");
+        assert_eq!(html_to_text("AT&T &#65;&#x42;"), "AT&T AB");
+        assert_eq!(html_to_text("1 < 2"), "1 < 2");
+    }
+
+    #[test]
+    fn test_media_references() {
+        assert_eq!(
+            media_references(
+                r#"<IMG class="x" src="paste&amp;1.png"> <img src='b.jpg'/> <img src=c.gif>
+                [sound:d.mp3]"#
+            ),
+            vec!["paste&1.png", "b.jpg", "c.gif", "d.mp3"]
+        );
+        assert!(media_references("<img alt=\"none\">").is_empty());
+    }
+}
@@ -0,0 +1,235 @@
+//! Backup of everything a user owns into an [`Archive`], see the [format](super). Media are
+//! referenced by the cards but not copied into the archive.
+
+use crate::backup::{
+    Archive, ArchivedAnswer, ArchivedCard, ArchivedCardGroup, ArchivedDeck, ArchivedDeckCard,
+    ArchivedDeckCardGroup, ArchivedSettings, ArchivedTag, ArchivedUser, VERSION,
+};
+use crate::error::CoreError;
+use crate::model::tag::Tag;
+use crate::reexports::db::sql::Thing;
+use crate::store::any::AnyStorage;
+use crate::store::{
+    CardGroupStore, CardStore, DeckStore, GlobalSettingsStore, HistoryStore, Storage, TagStore,
+    UserStore,
+};
+use chrono::Utc;
+use std::collections::HashSet;
+use std::fmt::{Debug, Formatter};
+use std::sync::Arc;
+use surrealdb::sql::Duration;
+
+#[derive(Clone)]
+pub struct BackupExporter<S: Storage = AnyStorage> {
+    pub users: S::Users,
+    pub tags: S::Tags,
+    pub cards: S::Cards,
+    pub card_groups: S::CardGroups,
+    pub decks: S::Decks,
+    pub history: S::History,
+    pub global_settings: S::GlobalSettings,
+}
+
+impl<S: Storage> Debug for BackupExporter<S> {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        write!(f, "BackupExporter")
+    }
+}
+
+impl<S: Storage> BackupExporter<S> {
+    pub fn new(
+        users: S::Users,
+        tags: S::Tags,
+        cards: S::Cards,
+        card_groups: S::CardGroups,
+        decks: S::Decks,
+        history: S::History,
+        global_settings: S::GlobalSettings,
+    ) -> Self {
+        Self {
+            users,
+            tags,
+            cards,
+            card_groups,
+            decks,
+            history,
+            global_settings,
+        }
+    }
+
+    /// Everything `user` owns, see the [module](self) documentation.
+    pub async fn export(&self, user: impl Into<Thing>) -> Result<Archive, CoreError> {
+        let user = self.users.get_by_id(user.into()).await?;
+        let settings = match self.global_settings.get_by_user_id(user.id.clone()).await {
+            Ok(settings) => Some(ArchivedSettings {
+                daily_limit: settings.daily_limit,
+                timetable: settings
+                    .timetable
+                    .iter()
+                    .map(|[start, end]| [millis(start), millis(end)])
+                    .collect(),
+                timezone: settings.timezone,
+                render_images: settings.render_images,
+                multiple_choice: settings.multiple_choice,
+                llm: settings.llm,
+            }),
+            Err(err) if err.is_not_found() => None,
+            Err(err) => return Err(err),
+        };
+
+        let tags = self
+            .tags
+            .list_by_user_id(user.id.clone())
+            .await?
+            .into_iter()
+            .map(|tag| ArchivedTag {
+                id: tag.id.to_string(),
+                name: tag.name,
+            })
+            .collect();
+
+        let decks = self.decks.list_by_user_id(user.id.clone()).await?;
+        let deck_ids = decks.iter().map(|deck| &deck.id).collect::<HashSet<_>>();
+        let mut archived_decks = decks
+            .iter()
+            .map(|deck| {
+                let archived = ArchivedDeck {
+                    id: deck.id.to_string(),
+                    title: deck.title.clone(),
+                    description: deck.description.clone(),
+                    // a parent that is gone would dangle in the archive
+                    parent: deck
+                        .parent
+                        .as_ref()
+                        .filter(|parent| deck_ids.contains(parent))
+                        .map(ToString::to_string),
+                    settings: deck.settings.clone(),
+                    tags: tag_ids(&deck.tags),
+                };
+                (deck.path(&decks).len(), archived)
+            })
+            .collect::<Vec<_>>();
+        archived_decks.sort_by_key(|(depth, _)| *depth);
+
+        let mut deck_cards = vec![];
+        let mut deck_card_groups = vec![];
+        for deck in &decks {
+            let cards = self
+                .decks
+                .list_cards(user.id.clone(), deck.id.clone())
+                .await?;
+            for card in cards {
+                deck_cards.push(ArchivedDeckCard {
+                    deck: deck.id.to_string(),
+                    card: card.id.to_string(),
+                });
+            }
+            let card_groups = self
+                .decks
+                .list_card_groups(user.id.clone(), deck.id.clone())
+                .await?;
+            for card_group in card_groups {
+                deck_card_groups.push(ArchivedDeckCardGroup {
+                    deck: deck.id.to_string(),
+                    card_group: card_group.id.to_string(),
+                });
+            }
+        }
+
+        let cards = self
+            .cards
+            .list_by_user_id(user.id.clone())
+            .await?
+            .into_iter()
+            .map(|card| ArchivedCard {
+                id: card.id.to_string(),
+                title: card.title,
+                front: card.front,
+                back: card.back,
+                hints: card.hints,
+                difficulty: card.difficulty,
+                importance: card.importance,
+                data: card.data,
+                choices: card.choices,
+                sibling: card.sibling.as_ref().map(ToString::to_string),
+                tags: tag_ids(&card.tags),
+                annotations: card.annotations,
+            })
+            .collect();
+
+        let card_groups = self
+            .card_groups
+            .list_by_user_id(user.id.clone())
+            .await?
+            .into_iter()
+            .map(|card_group| ArchivedCardGroup {
+                id: card_group.id.to_string(),
+                title: card_group.title,
+                importance: card_group.importance,
+                difficulty: card_group.difficulty,
+                data: card_group.data,
+                cards: card_group
+                    .cards
+                    .iter()
+                    .map(|card| card.id.to_string())
+                    .collect(),
+                tags: tag_ids(&card_group.tags),
+                annotations: card_group.annotations,
+            })
+            .collect();
+
+        let mut history = self
+            .history
+            .list_by_user_id(user.id.clone())
+            .await?
+            .into_iter()
+            .filter_map(|record| {
+                let (deck, card, card_group) = match (&record.deck_card, &record.deck_card_group) {
+                    (Some(deck_card), _) => (&deck_card.deck, Some(&deck_card.card.id), None),
+                    (None, Some(deck_card_group)) => (
+                        &deck_card_group.deck,
+                        None,
+                        Some(&deck_card_group.card_group.id),
+                    ),
+                    (None, None) => return None,
+                };
+                Some(ArchivedAnswer {
+                    deck: deck.id.to_string(),
+                    card: card.map(ToString::to_string),
+                    card_group: card_group.map(ToString::to_string),
+                    difficulty: record.difficulty,
+                    steps: record.steps,
+                    hide_for: record.hide_for.as_ref().map(millis),
+                    created_at: record.time.created_at,
+                })
+            })
+            .collect::<Vec<_>>();
+        history.sort_by_key(|answer| answer.created_at);
+
+        Ok(Archive {
+            version: VERSION,
+            created_at: Utc::now(),
+            user: ArchivedUser {
+                id: user.id.to_string(),
+                name: user.name,
+                email: user.email,
+            },
+            settings,
+            tags,
+            decks: archived_decks.into_iter().map(|(_, deck)| deck).collect(),
+            cards,
+            card_groups,
+            deck_cards,
+            deck_card_groups,
+            history,
+        })
+    }
+}
+
+fn tag_ids(tags: &[Arc<Tag>]) -> Vec<String> {
+    tags.iter().map(|tag| tag.id.to_string()).collect()
+}
+
+fn millis(duration: &Duration) -> u64 {
+    duration.0.as_millis() as u64
+}
@@ -0,0 +1,262 @@
+//! Backups of a user's account as a single JSON document, to keep a copy of it or to move it to
+//! another instance of the bot. The archive has everything the user owns: global settings, tags,
+//! decks with their parents, cards, card groups, the cards and card groups of every deck and the
+//! whole history of answers.
+//!
+//! Records reference each other by the ids they had when the backup was made. These ids only
+//! hold inside the archive, the [restorer](restore) creates everything anew and maps the old ids
+//! to the new ones.
+//!
+//! Every archive has the [`VERSION`] of the format it was written in. An archive of an older
+//! version is upgraded step by step when it is [parsed](Archive::parse), one of a newer version
+//! is rejected.
+
+pub mod export;
+pub mod restore;
+
+use crate::error::CoreError;
+use crate::llm::provider::LlmChoice;
+use crate::model::annotation::Annotations;
+use crate::model::card_data::CardData;
+use crate::model::deck::DeckSettings;
+use crate::model::multiple_choice::MultipleChoice;
+use chrono::{DateTime, Utc};
+use chrono_tz::Tz;
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use std::fmt::{Display, Formatter};
+use std::sync::Arc;
+
+/// Version of the format written by this build.
+pub const VERSION: u32 = 1;
+
+pub const EXTENSION: &str = "json";
+
+/// Turns an archive of the version at its index plus one into one of the next version.
+type Upgrade = fn(&mut Value) -> Result<(), CoreError>;
+
+/// Upgrades from every older version, in order. A change to the format bumps [`VERSION`] and adds
+/// a step here.
+const UPGRADES: [Upgrade; VERSION as usize - 1] = [];
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Archive {
+    pub version: u32,
+    pub created_at: DateTime<Utc>,
+    pub user: ArchivedUser,
+    pub settings: Option<ArchivedSettings>,
+    pub tags: Vec<ArchivedTag>,
+    /// Parents come before their subdecks.
+    pub decks: Vec<ArchivedDeck>,
+    pub cards: Vec<ArchivedCard>,
+    pub card_groups: Vec<ArchivedCardGroup>,
+    pub deck_cards: Vec<ArchivedDeckCard>,
+    pub deck_card_groups: Vec<ArchivedDeckCardGroup>,
+    /// Oldest first.
+    pub history: Vec<ArchivedAnswer>,
+}
+
+/// Who the backup was made for. Restoring never changes the account it is restored to.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct ArchivedUser {
+    pub id: String,
+    pub name: Arc<str>,
+    pub email: Arc<str>,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct ArchivedSettings {
+    pub daily_limit: u16,
+    /// Pairs of the start and the end of a period in milliseconds since midnight.
+    pub timetable: Vec<[u64; 2]>,
+    pub timezone: Tz,
+    pub render_images: bool,
+    #[serde(default)]
+    pub multiple_choice: bool,
+    #[serde(default)]
+    pub llm: Option<LlmChoice>,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct ArchivedTag {
+    pub id: String,
+    pub name: Arc<str>,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct ArchivedDeck {
+    pub id: String,
+    pub title: Arc<str>,
+    pub description: Option<Arc<str>>,
+    pub parent: Option<String>,
+    pub settings: Option<DeckSettings>,
+    pub tags: Vec<String>,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct ArchivedCard {
+    pub id: String,
+    pub title: Arc<str>,
+    pub front: Option<Arc<str>>,
+    pub back: Option<Arc<str>>,
+    pub hints: Vec<Arc<str>>,
+    pub difficulty: u8,
+    pub importance: u8,
+    pub data: Option<CardData>,
+    pub choices: Option<MultipleChoice>,
+    pub sibling: Option<String>,
+    pub tags: Vec<String>,
+    #[serde(default)]
+    pub annotations: Annotations,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct ArchivedCardGroup {
+    pub id: String,
+    pub title: Arc<str>,
+    pub importance: u8,
+    pub difficulty: u8,
+    pub data: Option<CardData>,
+    pub cards: Vec<String>,
+    pub tags: Vec<String>,
+    #[serde(default)]
+    pub annotations: Annotations,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct ArchivedDeckCard {
+    pub deck: String,
+    pub card: String,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct ArchivedDeckCardGroup {
+    pub deck: String,
+    pub card_group: String,
+}
+
+/// An answer to a card or a card group of a deck.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct ArchivedAnswer {
+    pub deck: String,
+    pub card: Option<String>,
+    pub card_group: Option<String>,
+    pub difficulty: u8,
+    #[serde(default)]
+    pub steps: Vec<Option<u8>>,
+    /// Milliseconds the answered card was hidden for.
+    pub hide_for: Option<u64>,
+    pub created_at: DateTime<Utc>,
+}
+
+/// What an archive has, see [`Archive::report`].
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct Report {
+    pub tags: usize,
+    pub decks: usize,
+    pub cards: usize,
+    pub card_groups: usize,
+    pub history: usize,
+}
+
+impl Display for Report {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        writeln!(f, "Tags: {}", self.tags)?;
+        writeln!(f, "Decks: {}", self.decks)?;
+        writeln!(f, "Cards: {}", self.cards)?;
+        writeln!(f, "Card groups: {}", self.card_groups)?;
+        write!(f, "History records: {}", self.history)
+    }
+}
+
+impl Archive {
+    pub fn report(&self) -> Report {
+        Report {
+            tags: self.tags.len(),
+            decks: self.decks.len(),
+            cards: self.cards.len(),
+            card_groups: self.card_groups.len(),
+            history: self.history.len(),
+        }
+    }
+
+    /// Reads an archive of this or an older version.
+    pub fn parse(json: &[u8]) -> Result<Self, CoreError> {
+        let archive = serde_json::from_slice(json)?;
+        let archive = upgrade(archive, &UPGRADES)?;
+        Ok(serde_json::from_value(archive)?)
+    }
+
+    pub fn to_json(&self) -> Result<Vec<u8>, CoreError> {
+        Ok(serde_json::to_vec_pretty(self)?)
+    }
+}
+
+/// Applies the `upgrades` an archive of its version needs, `upgrades[0]` being the one from the
+/// first version.
+fn upgrade(mut archive: Value, upgrades: &[Upgrade]) -> Result<Value, CoreError> {
+    let latest = upgrades.len() as u64 + 1;
+    let version = archive
+        .get("version")
+        .and_then(Value::as_u64)
+        .ok_or_else(|| invalid("the version is missing"))?;
+    if version == 0 || version > latest {
+        return Err(invalid(format!(
+            "version {version} is not supported, the latest one is {latest}"
+        )));
+    }
+
+    for step in &upgrades[version as usize - 1..] {
+        step(&mut archive)?;
+    }
+    archive["version"] = Value::from(latest);
+    Ok(archive)
+}
+
+fn invalid(message: impl Into<String>) -> CoreError {
+    CoreError::InvalidBackup(Arc::from(message.into()))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+    use testresult::TestResult;
+
+    fn rename_decks(archive: &mut Value) -> Result<(), CoreError> {
+        let decks = archive["folders"].take();
+        archive["decks"] = decks;
+        Ok(())
+    }
+
+    fn add_history(archive: &mut Value) -> Result<(), CoreError> {
+        archive["history"] = json!([]);
+        Ok(())
+    }
+
+    #[test]
+    fn test_upgrade() -> TestResult {
+        let upgrades: [Upgrade; 2] = [rename_decks, add_history];
+
+        let archive = upgrade(json!({"version": 1, "folders": [1]}), &upgrades)?;
+        assert_eq!(
+            archive,
+            json!({"version": 3, "folders": null, "decks": [1], "history": []})
+        );
+
+        let archive = upgrade(json!({"version": 2, "decks": [1]}), &upgrades)?;
+        assert_eq!(archive, json!({"version": 3, "decks": [1], "history": []}));
+
+        let archive = upgrade(json!({"version": 3, "decks": []}), &upgrades)?;
+        assert_eq!(archive, json!({"version": 3, "decks": []}));
+
+        for archive in [json!({"version": 4}), json!({"version": 0}), json!({})] {
+            assert!(matches!(
+                upgrade(archive, &upgrades),
+                Err(CoreError::InvalidBackup(_))
+            ));
+        }
+
+        Ok(())
+    }
+}
@@ -0,0 +1,537 @@
+//! Restore of an [`Archive`] into the account of a user, who doesn't have to be the one the backup
+//! was made for. Everything in the archive is created anew with new ids, see [`RestoreMode`] for
+//! what happens to the records the user already has.
+//!
+//! The archive is checked before anything is written, an archive referencing a record it doesn't
+//! have is rejected as a whole. Answers keep the time they were given at, so the cards are
+//! scheduled as they were before the backup; what is left of the time a card was hidden for is
+//! counted from the restore.
+
+use crate::anki::DECK_SEPARATOR;
+use crate::backup::{invalid, Archive, ArchivedAnswer, ArchivedSettings};
+use crate::error::CoreError;
+use crate::model::card::{CreateCard, UpdateCard};
+use crate::model::card_group::{CreateCardGroup, UpdateCardGroup};
+use crate::model::deck::CreateDeck;
+use crate::model::deck_card::CreateDeckCard;
+use crate::model::deck_card_group::CreateDeckCardGroup;
+use crate::model::global_settings::CreateGlobalSettings;
+use crate::model::history::CreateHistory;
+use crate::model::time::Time;
+use crate::reexports::db::sql::Thing;
+use crate::store::any::AnyStorage;
+use crate::store::{
+    CardGroupStore, CardStore, DeckStore, GlobalSettingsStore, HistoryStore, Storage, TagStore,
+    UserStore,
+};
+use bon::Builder;
+use chrono::Utc;
+use std::collections::{HashMap, HashSet};
+use std::fmt::{Debug, Display, Formatter};
+use std::str::FromStr;
+use std::sync::Arc;
+use surrealdb::sql::Duration;
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum RestoreMode {
+    /// Keeps the records of the user. Tags are matched by name, decks by their path and the
+    /// global settings are only restored when the user has none. Cards, card groups and answers
+    /// are added, so restoring the same archive twice creates them twice.
+    #[default]
+    Merge,
+    /// Deletes the records of the user first, see [`UserStore::clear_data`].
+    Replace,
+}
+
+impl FromStr for RestoreMode {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.trim().to_lowercase().as_str() {
+            "merge" => Ok(RestoreMode::Merge),
+            "replace" => Ok(RestoreMode::Replace),
+            _ => Err(format!(
+                "Unknown restore mode `{s}`, expected merge or replace"
+            )),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Default, Builder)]
+pub struct RestoreOptions {
+    #[builder(default)]
+    pub mode: RestoreMode,
+}
+
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct RestoreReport {
+    pub tags: usize,
+    pub decks: usize,
+    /// Decks of the archive merged into the decks of the user with the same path.
+    pub merged_decks: usize,
+    pub cards: usize,
+    pub card_groups: usize,
+    pub history: usize,
+    /// Whether the global settings of the archive were restored.
+    pub settings: bool,
+}
+
+impl Display for RestoreReport {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        writeln!(f, "Tags: {}", self.tags)?;
+        write!(f, "Decks: {}", self.decks)?;
+        if self.merged_decks > 0 {
+            write!(f, ", {} merged into existing ones", self.merged_decks)?;
+        }
+        writeln!(f)?;
+        writeln!(f, "Cards: {}", self.cards)?;
+        writeln!(f, "Card groups: {}", self.card_groups)?;
+        writeln!(f, "History records: {}", self.history)?;
+        let settings = if self.settings { "restored" } else { "kept" };
+        write!(f, "Settings: {settings}")
+    }
+}
+
+#[derive(Clone)]
+pub struct BackupRestorer<S: Storage = AnyStorage> {
+    pub users: S::Users,
+    pub tags: S::Tags,
+    pub cards: S::Cards,
+    pub card_groups: S::CardGroups,
+    pub decks: S::Decks,
+    pub history: S::History,
+    pub global_settings: S::GlobalSettings,
+}
+
+impl<S: Storage> Debug for BackupRestorer<S> {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        write!(f, "BackupRestorer")
+    }
+}
+
+impl<S: Storage> BackupRestorer<S> {
+    pub fn new(
+        users: S::Users,
+        tags: S::Tags,
+        cards: S::Cards,
+        card_groups: S::CardGroups,
+        decks: S::Decks,
+        history: S::History,
+        global_settings: S::GlobalSettings,
+    ) -> Self {
+        Self {
+            users,
+            tags,
+            cards,
+            card_groups,
+            decks,
+            history,
+            global_settings,
+        }
+    }
+
+    /// Restores `archive` into the account of `user`, see the [module](self) documentation.
+    pub async fn restore(
+        &self,
+        user: impl Into<Thing>,
+        archive: &Archive,
+        options: RestoreOptions,
+    ) -> Result<RestoreReport, CoreError> {
+        validate(archive)?;
+        let user = self.users.get_by_id(user.into()).await?.id;
+        if options.mode == RestoreMode::Replace {
+            self.users.clear_data(user.clone()).await?;
+        }
+
+        let mut restore = Restore {
+            restorer: self,
+            user,
+            archive,
+            report: RestoreReport::default(),
+            ids: HashMap::new(),
+            deck_cards: HashMap::new(),
+            deck_card_groups: HashMap::new(),
+        };
+        restore.tags().await?;
+        restore.decks(options.mode).await?;
+        restore.cards().await?;
+        restore.card_groups().await?;
+        restore.relations().await?;
+        restore.history().await?;
+        if let Some(settings) = &archive.settings {
+            restore.settings(settings).await?;
+        }
+
+        Ok(restore.report)
+    }
+}
+
+struct Restore<'a, S: Storage> {
+    restorer: &'a BackupRestorer<S>,
+    user: Thing,
+    archive: &'a Archive,
+    report: RestoreReport,
+    /// New ids of the tags, decks, cards and card groups by their ids in the archive.
+    ids: HashMap<&'a str, Thing>,
+    /// New deck cards by the ids of the deck and the card in the archive.
+    deck_cards: HashMap<(&'a str, &'a str), Thing>,
+    deck_card_groups: HashMap<(&'a str, &'a str), Thing>,
+}
+
+impl<'a, S: Storage> Restore<'a, S> {
+    /// The new id of a record the archive was checked to have.
+    fn id(&self, id: &str) -> Thing {
+        self.ids[id].clone()
+    }
+
+    fn tag_ids(&self, tags: &[String]) -> Vec<Thing> {
+        tags.iter().map(|tag| self.id(tag)).collect()
+    }
+
+    async fn tags(&mut self) -> Result<(), CoreError> {
+        let archive = self.archive;
+        for tag in &archive.tags {
+            let created = self
+                .restorer
+                .tags
+                .get_or_create_tags(self.user.clone(), [tag.name.clone()])
+                .await?
+                .into_iter()
+                .next()
+                .ok_or_else(|| CoreError::NotFound(Arc::from(format!("Tag {}", tag.name))))?;
+            self.ids.insert(&tag.id, created.id);
+            self.report.tags += 1;
+        }
+        Ok(())
+    }
+
+    async fn decks(&mut self, mode: RestoreMode) -> Result<(), CoreError> {
+        let archive = self.archive;
+        let existing: HashMap<String, Thing> = match mode {
+            RestoreMode::Merge => {
+                let decks = self
+                    .restorer
+                    .decks
+                    .list_by_user_id(self.user.clone())
+                    .await?;
+                decks
+                    .iter()
+                    .map(|deck| (deck.path(&decks).join(DECK_SEPARATOR), deck.id.clone()))
+                    .collect()
+            }
+            RestoreMode::Replace => HashMap::new(),
+        };
+
+        // parents come first, so their paths are known
+        let mut paths = HashMap::<&str, String>::new();
+        for deck in &archive.decks {
+            let path = match &deck.parent {
+                Some(parent) => format!("{}{DECK_SEPARATOR}{}", paths[parent.as_str()], deck.title),
+                None => deck.title.to_string(),
+            };
+            let id = match existing.get(&path) {
+                Some(id) => {
+                    self.report.merged_decks += 1;
+                    id.clone()
+                }
+                None => {
+                    let created = self
+                        .restorer
+                        .decks
+                        .create(CreateDeck {
+                            description: deck.description.clone(),
+                            parent: deck.parent.as_deref().map(|parent| self.id(parent)),
+                            settings: deck.settings.clone(),
+                            tags: self.tag_ids(&deck.tags),
+                            title: deck.title.clone(),
+                            user: self.user.clone(),
+                        })
+                        .await?;
+                    self.report.decks += 1;
+                    created.id
+                }
+            };
+            self.ids.insert(&deck.id, id);
+            paths.insert(&deck.id, path);
+        }
+        Ok(())
+    }
+
+    async fn cards(&mut self) -> Result<(), CoreError> {
+        let archive = self.archive;
+        let cards = &archive.cards;
+        for card in cards {
+            let created = self
+                .restorer
+                .cards
+                .create(CreateCard {
+                    user: self.user.clone(),
+                    title: card.title.clone(),
+                    front: card.front.clone(),
+                    back: card.back.clone(),
+                    hints: card.hints.clone(),
+                    difficulty: card.difficulty,
+                    importance: card.importance,
+                    data: card.data.clone(),
+                    choices: card.choices.clone(),
+                    sibling: None,
+                    reverse: false,
+                    tags: self.tag_ids(&card.tags),
+                })
+                .await?;
+            self.ids.insert(&card.id, created.id);
+            self.report.cards += 1;
+        }
+
+        // siblings reference each other, so they are set once both cards exist
+        for card in cards {
+            if card.sibling.is_none() && card.annotations.is_empty() {
+                continue;
+            }
+            let update = UpdateCard::builder()
+                .maybe_sibling(card.sibling.as_deref().map(|sibling| self.id(sibling)))
+                .annotations(card.annotations.clone())
+                .build();
+            self.restorer.cards.patch(self.id(&card.id), update).await?;
+        }
+        Ok(())
+    }
+
+    async fn card_groups(&mut self) -> Result<(), CoreError> {
+        let archive = self.archive;
+        for card_group in &archive.card_groups {
+            let created = self
+                .restorer
+                .card_groups
+                .create(CreateCardGroup {
+                    user: self.user.clone(),
+                    title: card_group.title.clone(),
+                    importance: card_group.importance,
+                    difficulty: card_group.difficulty,
+                    data: card_group.data.clone(),
+                    cards: card_group.cards.iter().map(|card| self.id(card)).collect(),
+                    tags: self.tag_ids(&card_group.tags),
+                })
+                .await?;
+            if !card_group.annotations.is_empty() {
+                let update = UpdateCardGroup::builder()
+                    .annotations(card_group.annotations.clone())
+                    .build();
+                self.restorer
+                    .card_groups
+                    .patch(created.id.clone(), update)
+                    .await?;
+            }
+            self.ids.insert(&card_group.id, created.id);
+            self.report.card_groups += 1;
+        }
+        Ok(())
+    }
+
+    async fn relations(&mut self) -> Result<(), CoreError> {
+        let archive = self.archive;
+        for relation in &archive.deck_cards {
+            let created = self
+                .restorer
+                .decks
+                .relate_card(CreateDeckCard {
+                    deck: self.id(&relation.deck),
+                    card: self.id(&relation.card),
+                })
+                .await?;
+            self.deck_cards
+                .insert((&relation.deck, &relation.card), created.id);
+        }
+        for relation in &archive.deck_card_groups {
+            let created = self
+                .restorer
+                .decks
+                .relate_card_group(CreateDeckCardGroup {
+                    deck: self.id(&relation.deck),
+                    card_group: self.id(&relation.card_group),
+                })
+                .await?;
+            self.deck_card_groups
+                .insert((&relation.deck, &relation.card_group), created.id);
+        }
+        Ok(())
+    }
+
+    async fn history(&mut self) -> Result<(), CoreError> {
+        let now = Utc::now();
+        for answer in &self.archive.history {
+            let deck = answer.deck.as_str();
+            let deck_card = answer
+                .card
+                .as_deref()
+                .map(|card| self.deck_cards[&(deck, card)].clone());
+            let deck_card_group = answer
+                .card_group
+                .as_deref()
+                .map(|card_group| self.deck_card_groups[&(deck, card_group)].clone());
+            let hide_for = answer
+                .hide_for
+                .map(|millis| answer.created_at + chrono::Duration::milliseconds(millis as i64))
+                .and_then(|hidden_till| (hidden_till - now).to_std().ok())
+                .filter(|left| !left.is_zero())
+                .map(Duration::from);
+
+            self.restorer
+                .history
+                .create_custom(CreateHistory {
+                    user: self.user.clone(),
+                    deck_card,
+                    deck_card_group,
+                    difficulty: answer.difficulty,
+                    time: Some(Time {
+                        created_at: answer.created_at,
+                        updated_at: answer.created_at,
+                        deleted_at: None,
+                    }),
+                    hide_for,
+                    steps: answer.steps.clone(),
+                })
+                .await?;
+            self.report.history += 1;
+        }
+        Ok(())
+    }
+
+    /// Creates the global settings unless the user has them, they were deleted by a replace.
+    async fn settings(&mut self, settings: &ArchivedSettings) -> Result<(), CoreError> {
+        let global_settings = &self.restorer.global_settings;
+        match global_settings.get_by_user_id(self.user.clone()).await {
+            Ok(_) => return Ok(()),
+            Err(err) if err.is_not_found() => {}
+            Err(err) => return Err(err),
+        }
+
+        let millis = |millis: u64| Duration::from(std::time::Duration::from_millis(millis));
+        global_settings
+            .create(CreateGlobalSettings {
+                user: self.user.clone(),
+                daily_limit: settings.daily_limit,
+                timetable: settings
+                    .timetable
+                    .iter()
+                    .map(|&[start, end]| [millis(start), millis(end)])
+                    .collect(),
+                timezone: settings.timezone,
+                render_images: settings.render_images,
+                multiple_choice: settings.multiple_choice,
+                llm: settings.llm.clone(),
+            })
+            .await?;
+        self.report.settings = true;
+        Ok(())
+    }
+}
+
+/// Checks that every reference of the archive points to a record it has, and that parents come
+/// before their subdecks.
+fn validate(archive: &Archive) -> Result<(), CoreError> {
+    fn check(known: &HashSet<&str>, id: &str, what: &str, of: &str) -> Result<(), CoreError> {
+        if known.contains(id) {
+            Ok(())
+        } else {
+            Err(invalid(format!("{what} {id} of {of} is missing")))
+        }
+    }
+
+    let tags = archive
+        .tags
+        .iter()
+        .map(|tag| tag.id.as_str())
+        .collect::<HashSet<_>>();
+    let check_tags = |ids: &[String], of: &str| {
+        ids.iter()
+            .try_for_each(|id| check(&tags, id, "the tag", of))
+    };
+
+    let mut decks = HashSet::new();
+    for deck in &archive.decks {
+        let of = format!("deck {}", deck.id);
+        if let Some(parent) = &deck.parent {
+            check(&decks, parent, "the parent deck", &of)?;
+        }
+        check_tags(&deck.tags, &of)?;
+        decks.insert(deck.id.as_str());
+    }
+
+    let cards = archive
+        .cards
+        .iter()
+        .map(|card| card.id.as_str())
+        .collect::<HashSet<_>>();
+    for card in &archive.cards {
+        let of = format!("card {}", card.id);
+        if let Some(sibling) = &card.sibling {
+            check(&cards, sibling, "the sibling", &of)?;
+        }
+        check_tags(&card.tags, &of)?;
+    }
+
+    let mut card_groups = HashSet::new();
+    for card_group in &archive.card_groups {
+        let of = format!("card group {}", card_group.id);
+        for card in &card_group.cards {
+            check(&cards, card, "the card", &of)?;
+        }
+        check_tags(&card_group.tags, &of)?;
+        card_groups.insert(card_group.id.as_str());
+    }
+
+    let mut deck_cards = HashSet::new();
+    for relation in &archive.deck_cards {
+        check(&decks, &relation.deck, "the deck", "a deck card")?;
+        check(&cards, &relation.card, "the card", "a deck card")?;
+        deck_cards.insert((relation.deck.as_str(), relation.card.as_str()));
+    }
+    let mut deck_card_groups = HashSet::new();
+    for relation in &archive.deck_card_groups {
+        check(&decks, &relation.deck, "the deck", "a deck card group")?;
+        check(
+            &card_groups,
+            &relation.card_group,
+            "the card group",
+            "a deck card group",
+        )?;
+        deck_card_groups.insert((relation.deck.as_str(), relation.card_group.as_str()));
+    }
+
+    for ArchivedAnswer {
+        deck,
+        card,
+        card_group,
+        created_at,
+        ..
+    } in &archive.history
+    {
+        let answered = match (card, card_group) {
+            (Some(card), None) => deck_cards.contains(&(deck.as_str(), card.as_str())),
+            (None, Some(card_group)) => {
+                deck_card_groups.contains(&(deck.as_str(), card_group.as_str()))
+            }
+            _ => false,
+        };
+        if !answered {
+            return Err(invalid(format!(
+                "the answer given at {created_at} is not to a card or a card group of deck {deck}"
+            )));
+        }
+    }
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_restore_mode_from_str() {
+        assert_eq!("merge".parse(), Ok(RestoreMode::Merge));
+        assert_eq!(" Replace ".parse(), Ok(RestoreMode::Replace));
+        assert!("overwrite".parse::<RestoreMode>().is_err());
+    }
+}
@@ -0,0 +1,86 @@
+//! Local copies of the files attached to cards. Telegram keeps the files itself, but a `file_id`
+//! only works for the bot that received it, so the copies outlive a change of the bot token.
+
+use crate::error::CoreError;
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+
+#[derive(Debug, Clone)]
+pub struct BlobStore {
+    root: Arc<Path>,
+}
+
+impl BlobStore {
+    pub fn new(root: impl Into<PathBuf>) -> Self {
+        Self {
+            root: Arc::from(root.into()),
+        }
+    }
+
+    /// The store at `BLOB_STORAGE_PATH`, `None` when it is not set.
+    pub fn from_env() -> Option<Self> {
+        std::env::var("BLOB_STORAGE_PATH").ok().map(Self::new)
+    }
+
+    /// Keys are plain file names: ASCII letters, digits, `-`, `_` and `.`, not starting with `.`.
+    pub fn is_valid_key(key: &str) -> bool {
+        !key.is_empty()
+            && !key.starts_with('.')
+            && key
+                .chars()
+                .all(|c| c.is_ascii_alphanumeric() || matches!(c, '-' | '_' | '.'))
+    }
+
+    pub fn path(&self, key: &str) -> Result<PathBuf, CoreError> {
+        if !Self::is_valid_key(key) {
+            return Err(CoreError::InvalidCardData(Arc::from(format!(
+                "Invalid blob key `{key}`"
+            ))));
+        }
+        Ok(self.root.join(key))
+    }
+
+    pub async fn put(&self, key: &str, bytes: &[u8]) -> Result<PathBuf, CoreError> {
+        let path = self.path(key)?;
+        tokio::fs::create_dir_all(&self.root).await?;
+        tokio::fs::write(&path, bytes).await?;
+        Ok(path)
+    }
+
+    pub async fn get(&self, key: &str) -> Result<Vec<u8>, CoreError> {
+        Ok(tokio::fs::read(self.path(key)?).await?)
+    }
+
+    pub async fn contains(&self, key: &str) -> Result<bool, CoreError> {
+        Ok(tokio::fs::try_exists(self.path(key)?).await?)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use testresult::TestResult;
+
+    #[test]
+    fn test_is_valid_key() {
+        assert!(BlobStore::is_valid_key("AQADgq0xG-abc_1.jpg"));
+        for key in ["", ".hidden", "../card", "a/b", "a b"] {
+            assert!(!BlobStore::is_valid_key(key), "{key}");
+        }
+    }
+
+    #[tokio::test]
+    async fn test_put_get() -> TestResult {
+        let root = std::env::temp_dir().join(format!("blob_store_{}", std::process::id()));
+        let store = BlobStore::new(&root);
+
+        assert!(!store.contains("photo.jpg").await?);
+        store.put("photo.jpg", b"jpeg").await?;
+        assert!(store.contains("photo.jpg").await?);
+        assert_eq!(store.get("photo.jpg").await?, b"jpeg");
+        assert!(store.put("../photo.jpg", b"jpeg").await.is_err());
+
+        tokio::fs::remove_dir_all(root).await?;
+        Ok(())
+    }
+}
@@ -0,0 +1,225 @@
+use crate::error::CoreError;
+use crate::ext::response_ext::ResponseExt;
+use std::sync::Arc;
+use surrealdb::engine::any::{connect, Any};
+use surrealdb::opt::auth::Root;
+use surrealdb::opt::capabilities::Capabilities;
+use surrealdb::opt::Config;
+use surrealdb::Surreal;
+use tracing::info;
+
+/// Endpoint schemes served by an engine running inside the current process.
+const EMBEDDED_SCHEMES: [&str; 3] = ["mem://", "surrealkv://", "rocksdb://"];
+
+static SCRIPT_MIGRATION_SCHEMA: &str =
+    include_str!("../db-migrations/schemas/script_migration.surql");
+
+/// Migrations in the order they must be applied, keyed by the same script name
+/// `surrealdb-migrations` records in the `script_migration` table.
+static MIGRATIONS: [(&str, &str); 13] = [
+    (
+        "20240902_185441_Initial",
+        include_str!("../db-migrations/migrations/20240902_185441_Initial.surql"),
+    ),
+    (
+        "20241020_000000_MultipleChoice",
+        include_str!("../db-migrations/migrations/20241020_000000_MultipleChoice.surql"),
+    ),
+    (
+        "20241021_000000_ReversedCards",
+        include_str!("../db-migrations/migrations/20241021_000000_ReversedCards.surql"),
+    ),
+    (
+        "20241022_000000_ProgressiveCardGroups",
+        include_str!("../db-migrations/migrations/20241022_000000_ProgressiveCardGroups.surql"),
+    ),
+    (
+        "20241023_000000_TypedCardData",
+        include_str!("../db-migrations/migrations/20241023_000000_TypedCardData.surql"),
+    ),
+    (
+        "20241024_000000_CardMedia",
+        include_str!("../db-migrations/migrations/20241024_000000_CardMedia.surql"),
+    ),
+    (
+        "20241025_000000_RenderImages",
+        include_str!("../db-migrations/migrations/20241025_000000_RenderImages.surql"),
+    ),
+    (
+        "20241026_000000_Annotations",
+        include_str!("../db-migrations/migrations/20241026_000000_Annotations.surql"),
+    ),
+    (
+        "20241027_000000_SourceHash",
+        include_str!("../db-migrations/migrations/20241027_000000_SourceHash.surql"),
+    ),
+    (
+        "20241028_000000_SourceReference",
+        include_str!("../db-migrations/migrations/20241028_000000_SourceReference.surql"),
+    ),
+    (
+        "20241029_000000_LlmChoice",
+        include_str!("../db-migrations/migrations/20241029_000000_LlmChoice.surql"),
+    ),
+    (
+        "20241030_000000_MultipleChoiceSetting",
+        include_str!("../db-migrations/migrations/20241030_000000_MultipleChoiceSetting.surql"),
+    ),
+    (
+        "20241031_000000_UserAccessKey",
+        include_str!("../db-migrations/migrations/20241031_000000_UserAccessKey.surql"),
+    ),
+];
+
+/// The `user` record access of the migrations with the JWT key left as `{key}`. The migrations
+/// don't declare a key, so the key of every deployment comes from its own settings.
+static USER_ACCESS: &str = "DEFINE ACCESS OVERWRITE user ON DATABASE TYPE RECORD \
+    SIGNUP (CREATE user CONTENT { email: $email, name: $name, password: crypto::argon2::generate($password) }) \
+    SIGNIN (SELECT * FROM user WHERE email = $email AND crypto::argon2::compare(password, $password)) \
+    WITH JWT ALGORITHM HS512 KEY '{key}' WITH ISSUER KEY '{key}' \
+    DURATION FOR TOKEN 15m, FOR SESSION 12h;";
+
+/// HS512 keys shorter than this are easy to brute force.
+const MIN_ACCESS_KEY_LEN: usize = 32;
+
+#[derive(Debug, Clone)]
+pub struct DbSettings {
+    /// Any endpoint understood by `surrealdb::engine::any`: `ws://host:port`,
+    /// `mem://`, `surrealkv://path/to/dir` or `rocksdb://path/to/dir`.
+    pub endpoint: Arc<str>,
+    pub namespace: Arc<str>,
+    pub database: Arc<str>,
+    pub username: Option<Arc<str>>,
+    pub password: Option<Arc<str>>,
+    /// Key of the `user` record access, used to issue tokens for per-user sessions. Required for
+    /// remote endpoints, embedded engines without one keep the random key of the migrations.
+    pub access_key: Option<Arc<str>>,
+}
+
+impl DbSettings {
+    pub fn in_memory() -> Self {
+        Self {
+            endpoint: Arc::from("mem://"),
+            namespace: Arc::from("test"),
+            database: Arc::from("test"),
+            username: None,
+            password: None,
+            access_key: None,
+        }
+    }
+
+    pub fn from_env() -> Self {
+        let var = |name: &str| std::env::var(name).ok().map(Arc::<str>::from);
+
+        Self {
+            endpoint: var("SURREALDB_ENDPOINT")
+                .unwrap_or_else(|| Arc::from("ws://127.0.0.1:8477")),
+            namespace: var("SURREALDB_NAMESPACE").unwrap_or_else(|| Arc::from("flashcards_gpt")),
+            database: var("SURREALDB_DATABASE").unwrap_or_else(|| Arc::from("flashcards")),
+            username: var("SURREALDB_USERNAME").or_else(|| Some(Arc::from("root"))),
+            password: var("SURREALDB_PASSWORD").or_else(|| Some(Arc::from("root"))),
+            access_key: var("SURREALDB_ACCESS_KEY"),
+        }
+    }
+
+    pub fn is_embedded(&self) -> bool {
+        EMBEDDED_SCHEMES
+            .iter()
+            .any(|scheme| self.endpoint.starts_with(scheme))
+    }
+
+    /// Connects to the configured endpoint. Embedded engines have nobody to run
+    /// `surrealdb-migrations` against them, so their schema is migrated here.
+    ///
+    /// The `user` access is redefined with [`Self::access_key`], which remote endpoints must
+    /// have, so the signed in user needs the rights to define accesses.
+    #[tracing::instrument(level = "info", skip_all, err, fields(endpoint = %self.endpoint))]
+    pub async fn connect(&self) -> Result<Surreal<Any>, CoreError> {
+        if !self.is_embedded() && self.access_key.is_none() {
+            return Err(CoreError::InvalidConfig(Arc::from(
+                "SURREALDB_ACCESS_KEY is required for remote endpoints",
+            )));
+        }
+
+        let db = self.open().await?;
+
+        if !self.is_embedded()
+            && let (Some(username), Some(password)) = (&self.username, &self.password)
+        {
+            db.signin(Root {
+                username: username.as_ref(),
+                password: password.as_ref(),
+            })
+            .await?;
+        }
+
+        db.use_ns(self.namespace.as_ref())
+            .use_db(self.database.as_ref())
+            .await?;
+
+        if self.is_embedded() {
+            apply_migrations(&db).await?;
+        }
+
+        if let Some(key) = &self.access_key {
+            define_user_access(&db, key).await?;
+        }
+
+        Ok(db)
+    }
+
+    /// Opens a connection without signing in or selecting a namespace.
+    pub(crate) async fn open(&self) -> Result<Surreal<Any>, CoreError> {
+        // fn::trend is written in JavaScript, so scripting must be allowed
+        let config = Config::new().capabilities(Capabilities::all());
+        Ok(connect((self.endpoint.as_ref(), config)).await?)
+    }
+}
+
+/// Installs `key` as the JWT key of the `user` access. `DEFINE ACCESS` doesn't take parameters,
+/// so the key is spliced into the statement and only keys that can't leave the string are
+/// accepted.
+async fn define_user_access(db: &Surreal<Any>, key: &str) -> Result<(), CoreError> {
+    if key.len() < MIN_ACCESS_KEY_LEN {
+        return Err(CoreError::InvalidConfig(Arc::from(format!(
+            "SURREALDB_ACCESS_KEY must have at least {MIN_ACCESS_KEY_LEN} characters"
+        ))));
+    }
+    if key.contains(['\'', '\\']) || key.contains(char::is_control) {
+        return Err(CoreError::InvalidConfig(Arc::from(
+            "SURREALDB_ACCESS_KEY must not contain quotes, backslashes or control characters",
+        )));
+    }
+
+    db.query(USER_ACCESS.replace("{key}", key))
+        .await?
+        .errors_or_ok()?;
+    Ok(())
+}
+
+pub async fn apply_migrations(db: &Surreal<Any>) -> Result<(), CoreError> {
+    let mut response = db
+        .query("select value script_name from script_migration")
+        .await?;
+    response.errors_or_ok()?;
+    let applied: Vec<String> = response.take(0)?;
+
+    if applied.is_empty() {
+        db.query(SCRIPT_MIGRATION_SCHEMA).await?.errors_or_ok()?;
+    }
+
+    for (name, script) in MIGRATIONS {
+        if applied.iter().any(|applied| applied == name) {
+            continue;
+        }
+
+        info!(%name, "Applying migration");
+        db.query(script).await?.errors_or_ok()?;
+        db.query("create script_migration set script_name = $name")
+            .bind(("name", name))
+            .await?
+            .errors_or_ok()?;
+    }
+
+    Ok(())
+}
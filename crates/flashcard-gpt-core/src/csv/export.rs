@@ -0,0 +1,120 @@
+//! Export of the cards of decks to a CSV or TSV file in the shape the importer reads with the
+//! default [`ColumnMapping`](crate::csv::ColumnMapping): a header row with the names of every
+//! [`Field`], then a card a row. The deck of a row is its path, so that decks with the same title
+//! under different parents stay apart. Card groups and the history are not exported.
+
+use crate::anki::DECK_SEPARATOR;
+use crate::csv::Field;
+use crate::error::CoreError;
+use crate::model::deck::selected_decks;
+use crate::reexports::db::sql::Thing;
+use crate::store::any::AnyStorage;
+use crate::store::{DeckStore, Storage};
+use ::csv::WriterBuilder;
+use bon::Builder;
+use std::fmt::{Debug, Display, Formatter};
+use std::sync::Arc;
+
+#[derive(Debug, Clone, Builder)]
+pub struct ExportOptions {
+    #[builder(default = b',')]
+    pub delimiter: u8,
+
+    /// Separates the hints and the tags in a cell.
+    #[builder(default = ';')]
+    pub list_separator: char,
+
+    /// Only export this deck and its subdecks, all decks of the user without it.
+    pub deck: Option<Thing>,
+}
+
+impl Default for ExportOptions {
+    fn default() -> Self {
+        Self::builder().build()
+    }
+}
+
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct ExportReport {
+    pub decks: usize,
+    pub cards: usize,
+    /// Cards without a front, the importer would reject them.
+    pub skipped_cards: usize,
+}
+
+impl Display for ExportReport {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        writeln!(f, "Decks: {}", self.decks)?;
+        write!(f, "Cards: {}", self.cards)?;
+        if self.skipped_cards > 0 {
+            write!(f, "\nSkipped cards without a front: {}", self.skipped_cards)?;
+        }
+        Ok(())
+    }
+}
+
+#[derive(Clone)]
+pub struct CsvExporter<S: Storage = AnyStorage> {
+    pub decks: S::Decks,
+}
+
+impl<S: Storage> Debug for CsvExporter<S> {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        write!(f, "CsvExporter")
+    }
+}
+
+impl<S: Storage> CsvExporter<S> {
+    pub fn new(decks: S::Decks) -> Self {
+        Self { decks }
+    }
+
+    /// The file with the cards of the decks of `user`, see the [module](self) documentation.
+    pub async fn export(
+        &self,
+        user: impl Into<Thing>,
+        options: ExportOptions,
+    ) -> Result<(Vec<u8>, ExportReport), CoreError> {
+        let user = user.into();
+        let all_decks = self.decks.list_by_user_id(user.clone()).await?;
+        let decks = selected_decks(&all_decks, options.deck.as_ref())?;
+        let separator = options.list_separator.to_string();
+
+        let mut writer = WriterBuilder::new()
+            .delimiter(options.delimiter)
+            .from_writer(vec![]);
+        writer.write_record(Field::ALL.map(Field::name))?;
+
+        let mut report = ExportReport::default();
+        for deck in decks {
+            report.decks += 1;
+            let path = deck.path(&all_decks).join(DECK_SEPARATOR);
+            let cards = self.decks.list_cards(user.clone(), deck.id.clone()).await?;
+            for card in cards {
+                let Some(front) = card.front.as_deref() else {
+                    report.skipped_cards += 1;
+                    continue;
+                };
+                let hints = card.hints.iter().map(Arc::as_ref).collect::<Vec<_>>();
+                let tags = card.tags.iter().map(|tag| &*tag.name).collect::<Vec<_>>();
+                let row = Field::ALL.map(|field| match field {
+                    Field::Title => card.title.to_string(),
+                    Field::Front => front.to_string(),
+                    Field::Back => card.back.as_deref().unwrap_or_default().to_string(),
+                    Field::Hints => hints.join(&separator),
+                    Field::Difficulty => card.difficulty.to_string(),
+                    Field::Importance => card.importance.to_string(),
+                    Field::Tags => tags.join(&separator),
+                    Field::Deck => path.clone(),
+                });
+                writer.write_record(row)?;
+                report.cards += 1;
+            }
+        }
+
+        let file = writer
+            .into_inner()
+            .map_err(|err| CoreError::CsvError(err.into_error().into()))?;
+        Ok((file, report))
+    }
+}
@@ -0,0 +1,299 @@
+//! Import of the rows of a CSV or TSV file as cards. Every row is checked before anything is
+//! stored: the front must not be blank, the difficulty and the importance are numbers from 0 to
+//! 10, and the deck must exist. A single invalid row fails the whole import, the report lists the
+//! errors of every row, and the cards of a valid file are created along with their tags in a
+//! single transaction.
+
+use crate::anki::DECK_SEPARATOR;
+use crate::csv::{ColumnMapping, Field};
+use crate::error::CoreError;
+use crate::model::card::{title_from_front, CreateCard};
+use crate::model::deck::Deck;
+use crate::model::deck_card::CreateCardInDeck;
+use crate::reexports::db::sql::Thing;
+use crate::store::any::AnyStorage;
+use crate::store::{DeckStore, Storage};
+use ::csv::{ReaderBuilder, StringRecord};
+use bon::Builder;
+use std::collections::{BTreeMap, HashMap};
+use std::fmt::{Debug, Display, Formatter};
+use std::sync::Arc;
+
+const MAX_LEVEL: u8 = 10;
+
+#[derive(Debug, Clone, Builder)]
+pub struct ImportOptions {
+    #[builder(default = b',')]
+    pub delimiter: u8,
+
+    /// Whether the first row holds the names of the columns.
+    #[builder(default = true)]
+    pub headers: bool,
+
+    /// Separates the hints and the tags in a cell.
+    #[builder(default = ';')]
+    pub list_separator: char,
+
+    #[builder(default)]
+    pub columns: ColumnMapping,
+
+    /// The deck of the rows without one, a row without a deck is an error without it. It must be a
+    /// deck of the user.
+    pub deck: Option<Thing>,
+
+    /// Only check the rows, nothing is stored.
+    #[builder(default)]
+    pub dry_run: bool,
+}
+
+impl Default for ImportOptions {
+    fn default() -> Self {
+        Self::builder().build()
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct RowError {
+    /// Line of the row in the file, starting at 1.
+    pub row: u64,
+    pub message: Arc<str>,
+}
+
+impl Display for RowError {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        write!(f, "row {}: {}", self.row, self.message)
+    }
+}
+
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct ImportReport {
+    pub rows: usize,
+    /// Cards created, or that would be created by a dry run of a valid file.
+    pub cards: usize,
+    pub errors: Vec<RowError>,
+    pub dry_run: bool,
+}
+
+impl ImportReport {
+    /// Whether the cards were stored.
+    pub fn imported(&self) -> bool {
+        !self.dry_run && self.errors.is_empty()
+    }
+}
+
+impl Display for ImportReport {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        writeln!(f, "Rows: {}", self.rows)?;
+        if self.errors.is_empty() {
+            if self.dry_run {
+                write!(f, "Valid cards, nothing imported: {}", self.cards)
+            } else {
+                write!(f, "Cards: {}", self.cards)
+            }
+        } else {
+            write!(f, "Invalid rows, nothing imported: {}", self.errors.len())?;
+            for error in &self.errors {
+                write!(f, "\n  {error}")?;
+            }
+            Ok(())
+        }
+    }
+}
+
+#[derive(Clone)]
+pub struct CsvImporter<S: Storage = AnyStorage> {
+    pub decks: S::Decks,
+}
+
+impl<S: Storage> Debug for CsvImporter<S> {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        write!(f, "CsvImporter")
+    }
+}
+
+impl<S: Storage> CsvImporter<S> {
+    pub fn new(decks: S::Decks) -> Self {
+        Self { decks }
+    }
+
+    /// Checks the rows of `file` and creates a card for each of them when all are valid, see the
+    /// [module](self) documentation.
+    pub async fn import(
+        &self,
+        user: impl Into<Thing>,
+        file: &[u8],
+        options: ImportOptions,
+    ) -> Result<ImportReport, CoreError> {
+        let user = user.into();
+        let mut reader = ReaderBuilder::new()
+            .delimiter(options.delimiter)
+            .has_headers(options.headers)
+            .flexible(true)
+            .from_reader(file);
+        let headers = if options.headers {
+            Some(
+                reader
+                    .headers()?
+                    .iter()
+                    .map(String::from)
+                    .collect::<Vec<_>>(),
+            )
+        } else {
+            None
+        };
+        let columns = options.columns.resolve(headers.as_deref())?;
+
+        let decks = self.decks.list_by_user_id(user.clone()).await?;
+        if let Some(deck) = &options.deck {
+            if !decks.iter().any(|existing| &existing.id == deck) {
+                return Err(CoreError::NotFound(Arc::from(format!(
+                    "deck {deck} of the user {user}"
+                ))));
+            }
+        }
+        let deck_index = DeckIndex::new(&decks);
+
+        let mut report = ImportReport {
+            dry_run: options.dry_run,
+            ..ImportReport::default()
+        };
+        let mut rows = vec![];
+        for record in reader.records() {
+            let record = record?;
+            report.rows += 1;
+            let row = Row {
+                record: &record,
+                columns: &columns,
+                list_separator: options.list_separator,
+            };
+            match row.parse(&user, &deck_index, options.deck.as_ref()) {
+                Ok(parsed) => rows.push(parsed),
+                Err(message) => report.errors.push(RowError {
+                    row: record.position().map_or(0, |position| position.line()),
+                    message: Arc::from(message),
+                }),
+            }
+        }
+        report.cards = rows.len();
+        if !report.imported() {
+            return Ok(report);
+        }
+
+        self.decks.create_cards(rows).await?;
+
+        Ok(report)
+    }
+}
+
+/// Decks by their paths, and by their titles when no other deck has the same title.
+struct DeckIndex {
+    paths: HashMap<String, Thing>,
+    titles: HashMap<String, Option<Thing>>,
+}
+
+impl DeckIndex {
+    fn new(decks: &[Deck]) -> Self {
+        let mut paths = HashMap::new();
+        let mut titles = HashMap::<String, Option<Thing>>::new();
+        for deck in decks {
+            let path = deck.path(decks).join(DECK_SEPARATOR);
+            paths.insert(path.to_lowercase(), deck.id.clone());
+            titles
+                .entry(deck.title.to_lowercase())
+                .and_modify(|id| *id = None)
+                .or_insert_with(|| Some(deck.id.clone()));
+        }
+        Self { paths, titles }
+    }
+
+    fn get(&self, name: &str) -> Result<Thing, String> {
+        let key = name.to_lowercase();
+        if let Some(id) = self.paths.get(&key) {
+            return Ok(id.clone());
+        }
+        match self.titles.get(&key) {
+            Some(Some(id)) => Ok(id.clone()),
+            Some(None) => Err(format!(
+                "there is more than one deck {name:?}, use its path like \"Parent::{name}\""
+            )),
+            None => Err(format!("there is no deck {name:?}")),
+        }
+    }
+}
+
+struct Row<'a> {
+    record: &'a StringRecord,
+    columns: &'a BTreeMap<Field, usize>,
+    list_separator: char,
+}
+
+impl Row<'_> {
+    /// The trimmed cell of `field`, `None` when it is empty or not in the file.
+    fn get(&self, field: Field) -> Option<&str> {
+        let index = *self.columns.get(&field)?;
+        Some(self.record.get(index)?.trim()).filter(|value| !value.is_empty())
+    }
+
+    fn list(&self, field: Field) -> Vec<Arc<str>> {
+        self.get(field)
+            .into_iter()
+            .flat_map(|value| value.split(self.list_separator))
+            .map(str::trim)
+            .filter(|item| !item.is_empty())
+            .map(Arc::from)
+            .collect()
+    }
+
+    fn level(&self, field: Field) -> Result<u8, String> {
+        let Some(value) = self.get(field) else {
+            return Ok(0);
+        };
+        match value.parse::<u8>() {
+            Ok(level) if level <= MAX_LEVEL => Ok(level),
+            _ => Err(format!(
+                "the {} must be a number from 0 to {MAX_LEVEL}, got {value:?}",
+                field.name()
+            )),
+        }
+    }
+
+    /// The card of the row with the names of its tags.
+    fn parse(
+        &self,
+        user: &Thing,
+        decks: &DeckIndex,
+        default_deck: Option<&Thing>,
+    ) -> Result<CreateCardInDeck, String> {
+        let Some(front) = self.get(Field::Front) else {
+            return Err("the front is empty".to_string());
+        };
+        let deck = match (self.get(Field::Deck), default_deck) {
+            (Some(name), _) => decks.get(name)?,
+            (None, Some(deck)) => deck.clone(),
+            (None, None) => return Err("the deck is empty".to_string()),
+        };
+        let title = self
+            .get(Field::Title)
+            .map_or_else(|| title_from_front(front), Arc::from);
+
+        let card = CreateCard {
+            user: user.clone(),
+            title,
+            front: Some(Arc::from(front)),
+            back: self.get(Field::Back).map(Arc::from),
+            hints: self.list(Field::Hints),
+            difficulty: self.level(Field::Difficulty)?,
+            importance: self.level(Field::Importance)?,
+            data: None,
+            choices: None,
+            sibling: None,
+            reverse: false,
+            tags: vec![],
+        };
+        Ok(CreateCardInDeck {
+            deck,
+            card,
+            tag_names: self.list(Field::Tags),
+        })
+    }
+}
@@ -0,0 +1,228 @@
+//! Cards in CSV and TSV files, a card a row. A [`ColumnMapping`] tells which column holds which
+//! field of the card, by default the columns are found by the names of the header row. The
+//! exporter writes the columns the default mapping reads, so an exported file imports as is.
+//!
+//! Hints and tags are lists in a single cell, separated by the `list_separator` of the options.
+//! The deck of a row is the title of an existing deck, or its path like `Languages::Spanish`
+//! when titles repeat.
+
+pub mod export;
+pub mod import;
+
+use crate::error::CoreError;
+use std::collections::BTreeMap;
+use std::convert::Infallible;
+use std::fmt::{Display, Formatter};
+use std::str::FromStr;
+use std::sync::Arc;
+
+/// The usual delimiter of a file: a tab for `.tsv` and `.tab` files, a comma otherwise.
+pub fn delimiter_for(file_name: &str) -> u8 {
+    let extension = file_name
+        .rsplit_once('.')
+        .map(|(_, extension)| extension.to_ascii_lowercase());
+    match extension.as_deref() {
+        Some("tsv" | "tab") => b'\t',
+        _ => b',',
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub enum Field {
+    Title,
+    Front,
+    Back,
+    Hints,
+    Difficulty,
+    Importance,
+    Tags,
+    Deck,
+}
+
+impl Field {
+    pub const ALL: [Field; 8] = [
+        Field::Title,
+        Field::Front,
+        Field::Back,
+        Field::Hints,
+        Field::Difficulty,
+        Field::Importance,
+        Field::Tags,
+        Field::Deck,
+    ];
+
+    pub fn name(self) -> &'static str {
+        match self {
+            Field::Title => "title",
+            Field::Front => "front",
+            Field::Back => "back",
+            Field::Hints => "hints",
+            Field::Difficulty => "difficulty",
+            Field::Importance => "importance",
+            Field::Tags => "tags",
+            Field::Deck => "deck",
+        }
+    }
+}
+
+impl FromStr for Field {
+    type Err = CoreError;
+
+    fn from_str(value: &str) -> Result<Self, Self::Err> {
+        Field::ALL
+            .into_iter()
+            .find(|field| field.name().eq_ignore_ascii_case(value.trim()))
+            .ok_or_else(|| invalid(format!("unknown field {value:?}")))
+    }
+}
+
+/// A column of a file, by the name in its header row or by its index starting at 0.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Column {
+    Name(Arc<str>),
+    Index(usize),
+}
+
+impl From<&str> for Column {
+    fn from(value: &str) -> Self {
+        match value.trim().parse() {
+            Ok(index) => Column::Index(index),
+            Err(_) => Column::Name(Arc::from(value.trim())),
+        }
+    }
+}
+
+impl FromStr for Column {
+    type Err = Infallible;
+
+    fn from_str(value: &str) -> Result<Self, Self::Err> {
+        Ok(Column::from(value))
+    }
+}
+
+impl Display for Column {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Column::Name(name) => write!(f, "{name:?}"),
+            Column::Index(index) => write!(f, "#{index}"),
+        }
+    }
+}
+
+/// The column of every field. Only the front is required, missing columns leave the fields empty.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ColumnMapping {
+    columns: BTreeMap<Field, Column>,
+}
+
+impl Default for ColumnMapping {
+    /// Every field in the column named after it.
+    fn default() -> Self {
+        let columns = Field::ALL
+            .into_iter()
+            .map(|field| (field, Column::Name(Arc::from(field.name()))))
+            .collect();
+        Self { columns }
+    }
+}
+
+impl ColumnMapping {
+    pub fn get(&self, field: Field) -> Option<&Column> {
+        self.columns.get(&field)
+    }
+
+    pub fn set(&mut self, field: Field, column: Column) {
+        self.columns.insert(field, column);
+    }
+
+    pub fn remove(&mut self, field: Field) {
+        self.columns.remove(&field);
+    }
+
+    /// Applies an entry like `front=Question` or `back=2`.
+    pub fn apply(&mut self, entry: &str) -> Result<(), CoreError> {
+        let Some((field, column)) = entry.split_once('=') else {
+            return Err(invalid(format!("expected field=column, got {entry:?}")));
+        };
+        self.set(field.parse()?, Column::from(column));
+        Ok(())
+    }
+
+    /// Index of the column of every mapped field found in `headers`, all of them by index when
+    /// the file has no header row.
+    fn resolve(&self, headers: Option<&[String]>) -> Result<BTreeMap<Field, usize>, CoreError> {
+        let mut indexes = BTreeMap::new();
+        for (&field, column) in &self.columns {
+            let index = match (column, headers) {
+                (Column::Index(index), _) => Some(*index),
+                (Column::Name(name), Some(headers)) => headers
+                    .iter()
+                    .position(|header| header.trim().eq_ignore_ascii_case(name)),
+                (Column::Name(name), None) if field == Field::Front => {
+                    return Err(invalid(format!(
+                        "the file has no header row to find the front column {name:?} in"
+                    )));
+                }
+                (Column::Name(_), None) => None,
+            };
+            if let Some(index) = index {
+                indexes.insert(field, index);
+            }
+        }
+        if !indexes.contains_key(&Field::Front) {
+            let column = self.get(Field::Front).map_or_else(
+                || "is not mapped".to_string(),
+                |column| format!("{column} is missing"),
+            );
+            return Err(invalid(format!("the front column {column}")));
+        }
+        Ok(indexes)
+    }
+}
+
+fn invalid(message: impl Into<String>) -> CoreError {
+    CoreError::InvalidCsv(Arc::from(message.into()))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn headers(names: &[&str]) -> Vec<String> {
+        names.iter().map(|name| name.to_string()).collect()
+    }
+
+    #[test]
+    fn test_resolve() {
+        let mut mapping = ColumnMapping::default();
+        let indexes = mapping
+            .resolve(Some(&headers(&["Front", "Back", "Extra"])))
+            .unwrap();
+        assert_eq!(
+            indexes.into_iter().collect::<Vec<_>>(),
+            vec![(Field::Front, 0), (Field::Back, 1)]
+        );
+
+        mapping.apply("hints = Extra").unwrap();
+        mapping.apply("deck=3").unwrap();
+        let indexes = mapping
+            .resolve(Some(&headers(&["Front", "Back", "Extra"])))
+            .unwrap();
+        assert_eq!(indexes.get(&Field::Hints), Some(&2));
+        assert_eq!(indexes.get(&Field::Deck), Some(&3));
+
+        assert!(mapping.resolve(None).is_err());
+        mapping.apply("front=0").unwrap();
+        assert_eq!(mapping.resolve(None).unwrap().len(), 2);
+
+        assert!(mapping.apply("answer=1").is_err());
+        assert!(mapping.apply("front").is_err());
+    }
+
+    #[test]
+    fn test_delimiter_for() {
+        assert_eq!(delimiter_for("cards.TSV"), b'\t');
+        assert_eq!(delimiter_for("cards.csv"), b',');
+        assert_eq!(delimiter_for("cards"), b',');
+    }
+}
@@ -0,0 +1,205 @@
+//! Generation of a card group for every chunk of a document, see
+//! [`CardGeneratorService::generate_text_cards`]. The card group and its cards keep the
+//! [source](crate::model::card_data::SourceRef) of the chunk, and the text of the chunk is kept as
+//! the article of the card group, the context of graded answers.
+//!
+//! Like the [batch generation](crate::llm::batch), the LLM is asked about several chunks at once
+//! and a chunk that fails is reported without stopping the others.
+
+use crate::document::{Chunk, Document};
+use crate::error::CoreError;
+use crate::llm::card_generator_service::CardGeneratorService;
+use crate::reexports::db::sql::Thing;
+use crate::store::any::AnyStorage;
+use crate::store::Storage;
+use bon::Builder;
+use std::fmt::{Debug, Display, Formatter};
+use std::sync::Arc;
+use tokio::task::JoinSet;
+use tracing::{info, warn};
+
+#[derive(Debug, Clone, Builder)]
+pub struct GenerateOptions {
+    /// The budget of a chunk, in estimated tokens, see [`crate::document::tokens`].
+    #[builder(default = 1500)]
+    pub max_tokens: usize,
+
+    /// How many chunks the LLM is asked about at once.
+    #[builder(default = 4)]
+    pub concurrency: usize,
+
+    /// Ask for wrong options too, which turns the cards into multiple-choice ones.
+    #[builder(default)]
+    pub distractors: bool,
+}
+
+impl Default for GenerateOptions {
+    fn default() -> Self {
+        Self::builder().build()
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Generated {
+    /// The source of the chunk, like `raft.md › Leader election`.
+    pub source: Arc<str>,
+    /// The title of the card group.
+    pub title: Arc<str>,
+    pub cards: usize,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ChunkError {
+    pub source: Arc<str>,
+    pub message: Arc<str>,
+}
+
+impl Display for ChunkError {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}: {}", self.source, self.message)
+    }
+}
+
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct GenerateReport {
+    pub chunks: usize,
+    pub created: Vec<Generated>,
+    /// Chunks the LLM found nothing worth a card in.
+    pub empty: Vec<Arc<str>>,
+    pub failed: Vec<ChunkError>,
+}
+
+impl Display for GenerateReport {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        write!(f, "Chunks: {}", self.chunks)?;
+        write!(f, "\nCreated: {}", self.created.len())?;
+        for generated in &self.created {
+            write!(
+                f,
+                "\n  {}: {} ({} cards)",
+                generated.source, generated.title, generated.cards
+            )?;
+        }
+        write!(f, "\nWithout cards: {}", self.empty.len())?;
+        for source in &self.empty {
+            write!(f, "\n  {source}")?;
+        }
+        write!(f, "\nFailed: {}", self.failed.len())?;
+        for error in &self.failed {
+            write!(f, "\n  {error}")?;
+        }
+        Ok(())
+    }
+}
+
+#[derive(Clone)]
+pub struct DocumentGenerator<S: Storage = AnyStorage> {
+    pub generator: CardGeneratorService<S>,
+}
+
+impl<S: Storage> Debug for DocumentGenerator<S> {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        write!(f, "DocumentGenerator")
+    }
+}
+
+impl<S: Storage> DocumentGenerator<S> {
+    pub fn new(generator: CardGeneratorService<S>) -> Self {
+        Self { generator }
+    }
+
+    /// Generates a card group in `deck` for every chunk of `document`, see the
+    /// [module](self) documentation.
+    pub async fn generate(
+        &self,
+        user: impl Into<Thing>,
+        deck: impl Into<Thing>,
+        document: &Document,
+        options: GenerateOptions,
+    ) -> Result<GenerateReport, CoreError> {
+        let chunks = document.chunks(options.max_tokens);
+        self.generate_chunks(user, deck, chunks, options).await
+    }
+
+    /// Generates a card group in `deck` for every chunk, for callers that chunked the document
+    /// themselves. `options.max_tokens` is not used.
+    pub async fn generate_chunks(
+        &self,
+        user: impl Into<Thing>,
+        deck: impl Into<Thing>,
+        chunks: Vec<Chunk>,
+        options: GenerateOptions,
+    ) -> Result<GenerateReport, CoreError> {
+        let user = user.into();
+        let deck = deck.into();
+        let mut report = GenerateReport {
+            chunks: chunks.len(),
+            ..GenerateReport::default()
+        };
+
+        let mut pending = chunks.into_iter().enumerate();
+        let mut tasks = JoinSet::new();
+        let mut results = vec![];
+        loop {
+            while tasks.len() < options.concurrency.max(1) {
+                let Some((index, chunk)) = pending.next() else {
+                    break;
+                };
+                let generator = self.generator.clone();
+                let distractors = options.distractors;
+                tasks.spawn(async move {
+                    info!(source = %chunk.source, "Generating cards");
+                    let result = generator
+                        .generate_text_cards(chunk.prompt(), distractors)
+                        .await;
+                    (index, chunk, result)
+                });
+            }
+            let Some(joined) = tasks.join_next().await else {
+                break;
+            };
+            results.push(joined?);
+        }
+
+        // the card groups are created in the order of the document
+        results.sort_by_key(|(index, _, _)| *index);
+        for (_, chunk, result) in results {
+            let source = Arc::from(chunk.source.to_string());
+            let result = match result {
+                Ok(gpt_card_group) if gpt_card_group.cards.is_empty() => {
+                    report.empty.push(source);
+                    continue;
+                }
+                Ok(mut gpt_card_group) => {
+                    let Chunk {
+                        source: chunk_source,
+                        text,
+                    } = chunk;
+                    let data = gpt_card_group.data.get_or_insert_with(Default::default);
+                    data.source = Some(chunk_source);
+                    data.article = Some(Arc::from(text));
+                    self.generator
+                        .create_cards(user.clone(), deck.clone(), gpt_card_group)
+                        .await
+                }
+                Err(err) => Err(err),
+            };
+            match result {
+                Ok(deck_card_group) => report.created.push(Generated {
+                    source,
+                    title: deck_card_group.card_group.title.clone(),
+                    cards: deck_card_group.card_group.cards.len(),
+                }),
+                Err(err) => {
+                    warn!(%source, ?err, "Failed to generate cards");
+                    report.failed.push(ChunkError {
+                        source,
+                        message: Arc::from(err.to_string()),
+                    });
+                }
+            }
+        }
+
+        Ok(report)
+    }
+}
@@ -0,0 +1,444 @@
+//! Documents cards are generated from, see [`generate`]. A [`Document`] is read from a Markdown,
+//! plain text or PDF file and split into [`Chunk`]s that fit the prompt of the LLM:
+//!
+//! - a Markdown file is split by its headings, a section is under all the headings above it;
+//! - a plain text file is a single section;
+//! - a PDF file is a section per page, the text is extracted locally.
+//!
+//! A section longer than the token budget is split between paragraphs, and a paragraph longer than
+//! that between words. Consecutive sections without a heading, like pages, are joined up to the
+//! budget.
+
+pub mod generate;
+
+use crate::error::CoreError;
+use crate::model::card_data::SourceRef;
+use itertools::Itertools;
+use lopdf::Document as PdfDocument;
+use std::sync::Arc;
+
+/// Roughly how many characters of English text make a token.
+const CHARS_PER_TOKEN: usize = 4;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum DocumentFormat {
+    Markdown,
+    Text,
+    Pdf,
+}
+
+impl DocumentFormat {
+    /// The format of a file by its extension.
+    pub fn from_file_name(name: &str) -> Option<Self> {
+        let (_, extension) = name.rsplit_once('.')?;
+        match extension.to_lowercase().as_str() {
+            "md" | "markdown" => Some(DocumentFormat::Markdown),
+            "txt" | "text" => Some(DocumentFormat::Text),
+            "pdf" => Some(DocumentFormat::Pdf),
+            _ => None,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct Section {
+    /// The headings the section is under, separated by ` › `.
+    pub heading: Option<Arc<str>>,
+    /// The page of a PDF document, from 1.
+    pub page: Option<u32>,
+    pub text: String,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Document {
+    /// The name of the file.
+    pub name: Arc<str>,
+    /// The sections with text, in the order of the document.
+    pub sections: Vec<Section>,
+}
+
+/// A part of a document the LLM is asked about at once.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Chunk {
+    pub source: SourceRef,
+    pub text: String,
+}
+
+impl Chunk {
+    /// The text under the names of the document and the section.
+    pub fn prompt(&self) -> String {
+        let mut prompt = format!("Document: {}\n", self.source.file);
+        if let Some(section) = &self.source.section {
+            prompt.push_str(&format!("Section: {section}\n"));
+        }
+        if let Some(page) = self.source.page {
+            prompt.push_str(&format!("Page: {page}\n"));
+        }
+        prompt.push('\n');
+        prompt.push_str(&self.text);
+        prompt
+    }
+}
+
+impl Document {
+    /// Reads the file `name`, its format is guessed by the extension, see [`DocumentFormat`].
+    pub async fn parse(name: &str, file: Vec<u8>) -> Result<Self, CoreError> {
+        let Some(format) = DocumentFormat::from_file_name(name) else {
+            return Err(invalid(format!(
+                "{name} is not a Markdown, text or PDF file"
+            )));
+        };
+        let sections = match format {
+            DocumentFormat::Markdown => markdown_sections(&utf8(name, file)?),
+            DocumentFormat::Text => vec![Section {
+                text: utf8(name, file)?,
+                ..Section::default()
+            }],
+            DocumentFormat::Pdf => {
+                tokio::task::spawn_blocking(move || pdf_sections(&file)).await??
+            }
+        };
+
+        let sections = sections
+            .into_iter()
+            .filter(|section| !section.text.trim().is_empty())
+            .collect::<Vec<_>>();
+        if sections.is_empty() {
+            return Err(invalid(format!("{name} has no text")));
+        }
+        Ok(Self {
+            name: Arc::from(name),
+            sections,
+        })
+    }
+
+    /// The sections split and joined to fit in `max_tokens` each, as far as words allow.
+    pub fn chunks(&self, max_tokens: usize) -> Vec<Chunk> {
+        let max_tokens = max_tokens.max(1);
+        let mut chunks: Vec<Chunk> = vec![];
+        for section in &self.sections {
+            for part in split(&section.text, max_tokens) {
+                match chunks.last_mut() {
+                    Some(last)
+                        if section.heading.is_none()
+                            && last.source.section.is_none()
+                            && tokens(&last.text) + tokens(&part) <= max_tokens =>
+                    {
+                        last.text.push_str("\n\n");
+                        last.text.push_str(&part);
+                    }
+                    _ => chunks.push(Chunk {
+                        source: SourceRef {
+                            file: self.name.clone(),
+                            section: section.heading.clone(),
+                            page: section.page,
+                        },
+                        text: part,
+                    }),
+                }
+            }
+        }
+        chunks
+    }
+}
+
+/// An estimate of the tokens of `text`.
+pub fn tokens(text: &str) -> usize {
+    text.chars().count().div_ceil(CHARS_PER_TOKEN)
+}
+
+fn utf8(name: &str, file: Vec<u8>) -> Result<String, CoreError> {
+    String::from_utf8(file).map_err(|_| invalid(format!("{name} is not UTF-8 text")))
+}
+
+/// The sections under the headings of a Markdown text, lines in code blocks are never headings
+/// and the front matter is skipped.
+fn markdown_sections(text: &str) -> Vec<Section> {
+    let mut lines = text.lines().peekable();
+    if lines.peek().is_some_and(|line| line.trim_end() == "---") {
+        lines.next();
+        lines.find(|line| line.trim_end() == "---");
+    }
+
+    let mut sections = vec![];
+    let mut headings: Vec<(usize, &str)> = vec![];
+    let mut current = Section::default();
+    let mut fence: Option<&str> = None;
+    for line in lines {
+        let trimmed = line.trim_start();
+        if let Some(open) = fence {
+            if trimmed.starts_with(open) {
+                fence = None;
+            }
+        } else if trimmed.starts_with("```") || trimmed.starts_with("~~~") {
+            fence = Some(&trimmed[..3]);
+        } else if let Some((level, heading)) = markdown_heading(trimmed) {
+            sections.push(std::mem::take(&mut current));
+            headings.retain(|(other, _)| *other < level);
+            headings.push((level, heading));
+            current.heading = Some(Arc::from(
+                headings.iter().map(|(_, heading)| heading).join(" › "),
+            ));
+            continue;
+        }
+        current.text.push_str(line);
+        current.text.push('\n');
+    }
+    sections.push(current);
+    sections
+}
+
+/// The level and the text of an ATX heading like `## Title ##`.
+fn markdown_heading(line: &str) -> Option<(usize, &str)> {
+    let level = line.chars().take_while(|c| *c == '#').count();
+    let rest = &line[level..];
+    if !(1..=6).contains(&level) || !(rest.is_empty() || rest.starts_with([' ', '\t'])) {
+        return None;
+    }
+    let heading = rest.trim().trim_end_matches('#').trim_end();
+    (!heading.is_empty()).then_some((level, heading))
+}
+
+fn pdf_sections(file: &[u8]) -> Result<Vec<Section>, CoreError> {
+    let pdf = PdfDocument::load_mem(file).map_err(|err| invalid(format!("invalid PDF: {err}")))?;
+    let mut sections = vec![];
+    for page in pdf.get_pages().into_keys() {
+        let text = pdf
+            .extract_text(&[page])
+            .map_err(|err| invalid(format!("can't read page {page}: {err}")))?;
+        sections.push(Section {
+            heading: None,
+            page: Some(page),
+            text,
+        });
+    }
+    Ok(sections)
+}
+
+/// The paragraphs of `text` packed into parts of at most `max_tokens`.
+fn split(text: &str, max_tokens: usize) -> Vec<String> {
+    let mut parts = vec![];
+    let mut current = String::new();
+    for paragraph in paragraphs(text) {
+        for piece in pieces(&paragraph, max_tokens) {
+            if !current.is_empty() && tokens(&current) + tokens(&piece) > max_tokens {
+                parts.push(std::mem::take(&mut current));
+            }
+            if !current.is_empty() {
+                current.push_str("\n\n");
+            }
+            current.push_str(&piece);
+        }
+    }
+    if !current.is_empty() {
+        parts.push(current);
+    }
+    parts
+}
+
+fn paragraphs(text: &str) -> Vec<String> {
+    let mut paragraphs = vec![];
+    let mut current = vec![];
+    for line in text.lines() {
+        if line.trim().is_empty() {
+            if !current.is_empty() {
+                paragraphs.push(current.join("\n"));
+                current.clear();
+            }
+        } else {
+            current.push(line.trim_end());
+        }
+    }
+    if !current.is_empty() {
+        paragraphs.push(current.join("\n"));
+    }
+    paragraphs
+}
+
+/// A paragraph longer than `max_tokens` split between words.
+fn pieces(paragraph: &str, max_tokens: usize) -> Vec<String> {
+    if tokens(paragraph) <= max_tokens {
+        return vec![paragraph.to_string()];
+    }
+    let mut pieces = vec![];
+    let mut current = String::new();
+    for word in paragraph.split_whitespace() {
+        if !current.is_empty() && tokens(&current) + tokens(word) + 1 > max_tokens {
+            pieces.push(std::mem::take(&mut current));
+        }
+        if !current.is_empty() {
+            current.push(' ');
+        }
+        current.push_str(word);
+    }
+    if !current.is_empty() {
+        pieces.push(current);
+    }
+    pieces
+}
+
+fn invalid(message: impl Into<String>) -> CoreError {
+    CoreError::InvalidDocument(Arc::from(message.into()))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use lopdf::content::{Content, Operation};
+    use lopdf::{dictionary, Object, Stream};
+
+    #[test]
+    fn test_format() {
+        assert_eq!(
+            DocumentFormat::from_file_name("notes.MD"),
+            Some(DocumentFormat::Markdown)
+        );
+        assert_eq!(
+            DocumentFormat::from_file_name("paper.pdf"),
+            Some(DocumentFormat::Pdf)
+        );
+        assert_eq!(DocumentFormat::from_file_name("main.rs"), None);
+        assert_eq!(DocumentFormat::from_file_name("README"), None);
+    }
+
+    #[tokio::test]
+    async fn test_parse_markdown() -> Result<(), CoreError> {
+        let text =
+            "---\ntitle: Raft\n---\nIntro.\n\n# Raft\n\nConsensus.\n\n## Leader election\n\n\
+                    ```\n# not a heading\n```\n\n## Log replication ##\n\nAppendEntries.\n\n\
+                    #hashtag\n\n# Safety\n";
+        let document = Document::parse("raft.md", text.as_bytes().to_vec()).await?;
+        let headings = document
+            .sections
+            .iter()
+            .map(|section| section.heading.as_deref())
+            .collect::<Vec<_>>();
+        assert_eq!(
+            headings,
+            [
+                None,
+                Some("Raft"),
+                Some("Raft › Leader election"),
+                Some("Raft › Log replication")
+            ]
+        );
+        assert_eq!(document.sections[0].text.trim(), "Intro.");
+        assert!(document.sections[2].text.contains("# not a heading"));
+        assert!(document.sections[3].text.contains("#hashtag"));
+
+        let result = Document::parse("empty.md", b"# Title\n\n".to_vec()).await;
+        assert!(matches!(result, Err(CoreError::InvalidDocument(_))));
+        let result = Document::parse("main.rs", b"fn main() {}".to_vec()).await;
+        assert!(matches!(result, Err(CoreError::InvalidDocument(_))));
+        Ok(())
+    }
+
+    #[test]
+    fn test_chunks() {
+        let document = Document {
+            name: Arc::from("book.pdf"),
+            sections: vec![
+                Section {
+                    heading: None,
+                    page: Some(1),
+                    text: "a".repeat(20),
+                },
+                Section {
+                    heading: None,
+                    page: Some(2),
+                    text: "b".repeat(20),
+                },
+                Section {
+                    heading: Some(Arc::from("Long")),
+                    page: None,
+                    text: format!("{}\n\n{}", "word ".repeat(10), "c".repeat(20)),
+                },
+            ],
+        };
+
+        let chunks = document.chunks(15);
+        let sources = chunks
+            .iter()
+            .map(|chunk| chunk.source.to_string())
+            .collect::<Vec<_>>();
+        // the pages are joined, the paragraphs of the section are not
+        assert_eq!(
+            sources,
+            ["book.pdf, page 1", "book.pdf › Long", "book.pdf › Long"]
+        );
+        assert!(chunks.iter().all(|chunk| tokens(&chunk.text) <= 15));
+        assert_eq!(
+            chunks[0].text,
+            format!("{}\n\n{}", "a".repeat(20), "b".repeat(20))
+        );
+
+        // a paragraph longer than the budget is split between words
+        let chunks = document.chunks(3);
+        assert!(chunks.iter().any(|chunk| chunk.text == "word word"));
+        assert!(chunks[2]
+            .prompt()
+            .starts_with("Document: book.pdf\nSection: Long\n\n"));
+    }
+
+    #[tokio::test]
+    async fn test_parse_pdf() -> Result<(), CoreError> {
+        let mut pdf = PdfDocument::with_version("1.5");
+        let pages_id = pdf.new_object_id();
+        let font_id = pdf.add_object(dictionary! {
+            "Type" => "Font",
+            "Subtype" => "Type1",
+            "BaseFont" => "Courier",
+        });
+        let resources_id = pdf.add_object(dictionary! {
+            "Font" => dictionary! {
+                "F1" => font_id,
+            },
+        });
+        let mut kids = vec![];
+        for text in ["Quorums intersect", "Terms order leaders"] {
+            let content = Content {
+                operations: vec![
+                    Operation::new("BT", vec![]),
+                    Operation::new("Tf", vec!["F1".into(), 24.into()]),
+                    Operation::new("Td", vec![100.into(), 600.into()]),
+                    Operation::new("Tj", vec![Object::string_literal(text)]),
+                    Operation::new("ET", vec![]),
+                ],
+            };
+            let content_id = pdf.add_object(Stream::new(
+                dictionary! {},
+                content.encode().map_err(|err| invalid(err.to_string()))?,
+            ));
+            let page_id = pdf.add_object(dictionary! {
+                "Type" => "Page",
+                "Parent" => pages_id,
+                "Contents" => content_id,
+            });
+            kids.push(page_id.into());
+        }
+        pdf.objects.insert(
+            pages_id,
+            Object::Dictionary(dictionary! {
+                "Type" => "Pages",
+                "Count" => kids.len() as i64,
+                "Kids" => kids,
+                "Resources" => resources_id,
+                "MediaBox" => vec![0.into(), 0.into(), 595.into(), 842.into()],
+            }),
+        );
+        let catalog_id = pdf.add_object(dictionary! {
+            "Type" => "Catalog",
+            "Pages" => pages_id,
+        });
+        pdf.trailer.set("Root", catalog_id);
+        let mut file = vec![];
+        pdf.save_to(&mut file)
+            .map_err(|err| invalid(err.to_string()))?;
+
+        let document = Document::parse("raft.pdf", file).await?;
+        assert_eq!(document.sections.len(), 2);
+        assert_eq!(document.sections[1].page, Some(2));
+        assert!(document.sections[1].text.contains("Terms order leaders"));
+        Ok(())
+    }
+}
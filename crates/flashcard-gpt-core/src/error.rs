@@ -9,12 +9,57 @@ pub enum CoreError {
     #[error("Database error: {0}")]
     DbQueryHasErrors(Arc<str>),
 
+    #[error("SQLite error: {0}")]
+    SqliteError(#[from] rusqlite::Error),
+
+    #[error("Blocking task failed: {0}")]
+    BlockingTaskError(#[from] tokio::task::JoinError),
+
+    #[error("Token error: {0}")]
+    TokenError(#[from] jsonwebtoken::errors::Error),
+
+    #[error("Invalid configuration: {0}")]
+    InvalidConfig(Arc<str>),
+
     #[error("Result not found: {0}")]
     DbQueryResultNotFound(Arc<str>),
 
     #[error("Not found: {0}")]
     NotFound(Arc<str>),
 
+    #[error("Invalid card data: {0}")]
+    InvalidCardData(Arc<str>),
+
+    #[error("IO error: {0}")]
+    IoError(#[from] std::io::Error),
+
+    #[error("Zip error: {0}")]
+    ZipError(#[from] zip::result::ZipError),
+
+    #[error("Invalid Anki package: {0}")]
+    InvalidAnkiPackage(Arc<str>),
+
+    #[error("CSV error: {0}")]
+    CsvError(#[from] csv::Error),
+
+    #[error("Invalid CSV file: {0}")]
+    InvalidCsv(Arc<str>),
+
+    #[error("YAML error: {0}")]
+    YamlError(#[from] serde_yaml::Error),
+
+    #[error("Invalid Markdown file: {0}")]
+    InvalidMarkdown(Arc<str>),
+
+    #[error("Invalid backup: {0}")]
+    InvalidBackup(Arc<str>),
+
+    #[error("Invalid review log: {0}")]
+    InvalidReviewLog(Arc<str>),
+
+    #[error("Invalid document: {0}")]
+    InvalidDocument(Arc<str>),
+
     #[error("Mutex is poisoned: {0}")]
     MutexPoisoned(String),
 
@@ -27,11 +72,14 @@ pub enum CoreError {
     #[error("Tracking init error: {0:?}")]
     TrackingInitError(#[from] TryInitError),
 
-    #[error("Format and execute error: {0}")]
-    LlmFormatAndExecuteError(#[from] llm_chain::frame::FormatAndExecuteError),
+    #[error("Prompt template error: {0}")]
+    LlmTemplateError(#[from] llm_chain::prompt::StringTemplateError),
 
-    #[error("Executor error: {0}")]
-    LlmExecutorError(#[from] llm_chain::traits::ExecutorError),
+    #[error("LLM request failed: {0}")]
+    LlmRequestError(#[from] reqwest::Error),
+
+    #[error("LLM provider error: {0}")]
+    LlmProviderError(Arc<str>),
 
     #[error("No LLM steps provided: {0}")]
     LlmNoLlmStepsProvided(Arc<str>),
@@ -44,4 +92,17 @@ pub enum CoreError {
 
     #[error("LLM result is missing: {0}")]
     LlmResultMissing(Arc<str>),
+
+    #[error("No LLM fixture for the prompt: {0}")]
+    LlmFixtureMissing(Arc<str>),
+}
+
+impl CoreError {
+    /// Whether a record that was looked up doesn't exist.
+    pub fn is_not_found(&self) -> bool {
+        matches!(
+            self,
+            CoreError::NotFound(_) | CoreError::DbQueryResultNotFound(_)
+        )
+    }
 }
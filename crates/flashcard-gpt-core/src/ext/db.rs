@@ -5,7 +5,7 @@ use serde::de::DeserializeOwned;
 use std::fmt::Debug;
 use std::future::Future;
 use std::sync::Arc;
-use surrealdb::engine::remote::ws::Client;
+use surrealdb::engine::any::Any;
 use surrealdb::Surreal;
 use tracing::debug;
 
@@ -22,7 +22,7 @@ pub trait DbExt {
         R: DeserializeOwned;
 }
 
-impl DbExt for Surreal<Client> {
+impl DbExt for Surreal<Any> {
     async fn create_entity<W, R>(
         &self,
         table: &'static str,
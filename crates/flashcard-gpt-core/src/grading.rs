@@ -0,0 +1,234 @@
+//! Grading of typed answers. Both texts are normalized, compared by edit distance and by token
+//! overlap, and the better of the two similarities suggests the difficulty of the answer.
+
+use crate::model::card::Card;
+use crate::model::cloze;
+use serde::{Deserialize, Serialize};
+use std::collections::HashSet;
+
+/// Highest difficulty of the answer keyboard, suggested for an answer with nothing in common.
+pub const MAX_DIFFICULTY: u8 = 10;
+
+#[derive(Debug, Clone, Copy, Serialize, Deserialize, PartialEq)]
+pub struct Grade {
+    /// `1 - distance / length` of the normalized texts.
+    pub edit_similarity: f64,
+    /// Dice coefficient of the normalized tokens.
+    pub token_overlap: f64,
+    /// The larger of the two: edit distance forgives typos, token overlap forgives word order.
+    pub similarity: f64,
+    pub difficulty: u8,
+}
+
+/// A word of the expected answer or of the given one, aligned by their longest common
+/// subsequence of normalized words.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Diff<'a> {
+    Same(&'a str),
+    /// Expected but not given.
+    Missing(&'a str),
+    /// Given but not expected.
+    Extra(&'a str),
+}
+
+/// Lowercase alphanumeric words separated by single spaces.
+pub fn normalize(text: &str) -> String {
+    text.chars()
+        .map(|c| if c.is_alphanumeric() { c } else { ' ' })
+        .flat_map(char::to_lowercase)
+        .collect::<String>()
+        .split_whitespace()
+        .collect::<Vec<_>>()
+        .join(" ")
+}
+
+pub fn levenshtein(a: &str, b: &str) -> usize {
+    let b = b.chars().collect::<Vec<_>>();
+    let mut previous = (0..=b.len()).collect::<Vec<_>>();
+    let mut current = vec![0; b.len() + 1];
+    for (i, a) in a.chars().enumerate() {
+        current[0] = i + 1;
+        for (j, b) in b.iter().enumerate() {
+            let substitution = previous[j] + usize::from(a != *b);
+            current[j + 1] = substitution.min(previous[j + 1] + 1).min(current[j] + 1);
+        }
+        std::mem::swap(&mut previous, &mut current);
+    }
+    previous[b.len()]
+}
+
+pub fn grade(expected: &str, answer: &str) -> Grade {
+    let expected = normalize(expected);
+    let answer = normalize(answer);
+
+    let length = expected.chars().count().max(answer.chars().count());
+    let edit_similarity = if length == 0 {
+        1.0
+    } else {
+        1.0 - levenshtein(&expected, &answer) as f64 / length as f64
+    };
+
+    let expected_tokens = expected.split(' ').filter(|token| !token.is_empty());
+    let answer_tokens = answer.split(' ').filter(|token| !token.is_empty());
+    let expected_tokens = expected_tokens.collect::<HashSet<_>>();
+    let answer_tokens = answer_tokens.collect::<HashSet<_>>();
+    let tokens = expected_tokens.len() + answer_tokens.len();
+    let token_overlap = if tokens == 0 {
+        1.0
+    } else {
+        2.0 * expected_tokens.intersection(&answer_tokens).count() as f64 / tokens as f64
+    };
+
+    let similarity = edit_similarity.max(token_overlap);
+    let difficulty = ((1.0 - similarity) * MAX_DIFFICULTY as f64).round() as u8;
+
+    Grade {
+        edit_similarity,
+        token_overlap,
+        similarity,
+        difficulty,
+    }
+}
+
+/// Word diff of `answer` against `expected`, words are compared normalized.
+pub fn diff<'a>(expected: &'a str, answer: &'a str) -> Vec<Diff<'a>> {
+    let expected = expected.split_whitespace().collect::<Vec<_>>();
+    let answer = answer.split_whitespace().collect::<Vec<_>>();
+    let expected_keys = expected
+        .iter()
+        .map(|word| normalize(word))
+        .collect::<Vec<_>>();
+    let answer_keys = answer
+        .iter()
+        .map(|word| normalize(word))
+        .collect::<Vec<_>>();
+
+    // lcs[i][j] is the length of the common subsequence of expected[i..] and answer[j..]
+    let mut lcs = vec![vec![0usize; answer.len() + 1]; expected.len() + 1];
+    for i in (0..expected.len()).rev() {
+        for j in (0..answer.len()).rev() {
+            lcs[i][j] = if expected_keys[i] == answer_keys[j] {
+                lcs[i + 1][j + 1] + 1
+            } else {
+                lcs[i + 1][j].max(lcs[i][j + 1])
+            };
+        }
+    }
+
+    let mut diff = Vec::with_capacity(expected.len().max(answer.len()));
+    let (mut i, mut j) = (0, 0);
+    while i < expected.len() && j < answer.len() {
+        if expected_keys[i] == answer_keys[j] {
+            diff.push(Diff::Same(answer[j]));
+            i += 1;
+            j += 1;
+        } else if lcs[i + 1][j] >= lcs[i][j + 1] {
+            diff.push(Diff::Missing(expected[i]));
+            i += 1;
+        } else {
+            diff.push(Diff::Extra(answer[j]));
+            j += 1;
+        }
+    }
+    diff.extend(expected[i..].iter().map(|word| Diff::Missing(word)));
+    diff.extend(answer[j..].iter().map(|word| Diff::Extra(word)));
+    diff
+}
+
+impl Card {
+    /// What a typed answer is compared with: the `answer` of `data` or the hidden deletions of a
+    /// cloze card. Answers to other cards are free-form explanations, graded by the LLM.
+    pub fn expected_answer(&self) -> Option<String> {
+        if let Some(answer) = self.typed_answer() {
+            return Some(answer.to_string());
+        }
+
+        if let Some(index) = self.cloze_index()
+            && let Some(front) = self.front.as_deref()
+        {
+            let answers = cloze::deletions(front)
+                .into_iter()
+                .filter(|deletion| deletion.index == index)
+                .map(|deletion| deletion.answer)
+                .collect::<Vec<_>>();
+            return Some(answers.join(" "));
+        }
+
+        None
+    }
+
+    /// The answer of a typed-answer card, `None` for cards answered by self-assessment.
+    pub fn typed_answer(&self) -> Option<&str> {
+        self.data.as_ref()?.answer.as_deref()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_normalize() {
+        assert_eq!(normalize("  Hash-Map, O(1)!  "), "hash map o 1");
+        assert_eq!(normalize("Ärger\n\tüber`Vec`"), "ärger über vec");
+        assert_eq!(normalize("?!"), "");
+    }
+
+    #[test]
+    fn test_levenshtein() {
+        assert_eq!(levenshtein("kitten", "sitting"), 3);
+        assert_eq!(levenshtein("", "abc"), 3);
+        assert_eq!(levenshtein("über", "uber"), 1);
+        assert_eq!(levenshtein("same", "same"), 0);
+    }
+
+    #[test]
+    fn test_grade_exact_match() {
+        let grade = grade("A binary heap", "a binary heap.");
+        assert_eq!(grade.similarity, 1.0);
+        assert_eq!(grade.difficulty, 0);
+    }
+
+    #[test]
+    fn test_grade_forgives_typos_and_word_order() {
+        let typo = grade("binary search tree", "binary serach tree");
+        assert!(typo.edit_similarity > 0.8, "{typo:?}");
+        assert!(typo.difficulty <= 2, "{typo:?}");
+
+        let reordered = grade("stack and queue", "queue and stack");
+        assert_eq!(reordered.token_overlap, 1.0);
+        assert_eq!(reordered.difficulty, 0);
+    }
+
+    #[test]
+    fn test_grade_wrong_answer() {
+        let wrong = grade("depth first search", "dijkstra");
+        assert!(wrong.difficulty >= 7, "{wrong:?}");
+        assert_eq!(grade("anything", "").difficulty, MAX_DIFFICULTY);
+    }
+
+    #[test]
+    fn test_diff() {
+        assert_eq!(
+            diff("Uses a min heap", "uses the min-heap"),
+            [
+                Diff::Same("uses"),
+                Diff::Missing("a"),
+                Diff::Missing("min"),
+                Diff::Missing("heap"),
+                Diff::Extra("the"),
+                Diff::Extra("min-heap"),
+            ]
+        );
+        assert_eq!(
+            diff("sorted in O(n log n)", "in o(n) log n"),
+            [
+                Diff::Missing("sorted"),
+                Diff::Same("in"),
+                Diff::Same("o(n)"),
+                Diff::Same("log"),
+                Diff::Same("n"),
+            ]
+        );
+    }
+}
@@ -4,10 +4,21 @@
 #![feature(iter_array_chunks)]
 
 pub mod model;
+pub mod anki;
+pub mod backup;
+pub mod blob;
+pub mod connection;
+pub mod csv;
+pub mod document;
 pub mod error;
 pub mod ext;
+pub mod grading;
 pub mod llm;
 pub mod logging;
 pub mod macros;
+pub mod markdown_folder;
 pub mod reexports;
 pub mod repo;
+pub mod review_log;
+pub mod session;
+pub mod store;
@@ -0,0 +1,88 @@
+use crate::error::CoreError;
+use crate::llm::custom_executor::{CustomExecutor, CustomStep};
+use crate::model::card::Card;
+use crate::model::llm::GptGrade;
+use std::fmt::{Debug, Formatter, Write as _};
+use std::sync::Arc;
+
+/// The name of the chain of [`AnswerGrader::grade`].
+pub const GRADE_ANSWER_CHAIN: &str = "grade_answer";
+
+/// Grades free-form answers to cards by comparing them to the back, the hints and the article of
+/// the card group with the LLM.
+#[derive(Clone)]
+pub struct AnswerGrader {
+    pub executor: CustomExecutor,
+}
+
+impl Debug for AnswerGrader {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        write!(f, "AnswerGrader")
+    }
+}
+
+impl AnswerGrader {
+    pub fn new(executor: CustomExecutor) -> Self {
+        Self { executor }
+    }
+
+    pub async fn grade(
+        &self,
+        card: &Card,
+        article: Option<&str>,
+        answer: &str,
+    ) -> Result<GptGrade, CoreError> {
+        let grade_step = CustomStep {
+            name: Arc::from("Grade Answer"),
+            system_template: r#"
+You are a strict but fair examiner grading answers to flashcards for a highly knowledgeable
+audience.
+Compare the user's answer to the reference answer, the hints and the article. Judge whether the
+user understood the key ideas, wording does not matter. Points the user did not mention are missed
+points, statements of the user that are wrong are misconceptions.
+Respond **only** in the following JSON format (do not include any additional text outside the JSON):
+
+{
+  "score": <rate the answer on a scale of 0-10 (0 = wrong or empty, 10 = complete and correct)>,
+  "missed_points": ["<Key points of the reference answer the user did not mention>"],
+  "misconceptions": ["<Wrong statements of the user, each with a short correction>"]
+}
+"#
+            .into(),
+            user_template: "Grade the answer:\n{{answer_context}}".into(),
+            input_param_names: vec![Arc::from("answer_context")],
+            output_param_name: Arc::from("grade"),
+        };
+
+        let context = answer_context(card, article, answer);
+        let (grade, _) = self
+            .executor
+            .execute_custom_chain(GRADE_ANSWER_CHAIN, &[grade_step], context)
+            .await?;
+
+        Ok(GptGrade::from_gpt_response(&grade)?)
+    }
+}
+
+fn answer_context(card: &Card, article: Option<&str>, answer: &str) -> String {
+    let mut context = String::new();
+    let _ = writeln!(context, "# Question\n{}\n", card.title);
+    if let Some(front) = card.front.as_deref() {
+        let _ = writeln!(context, "{front}\n");
+    }
+    if let Some(back) = card.back.as_deref() {
+        let _ = writeln!(context, "# Reference answer\n{back}\n");
+    }
+    if !card.hints.is_empty() {
+        let _ = writeln!(context, "# Hints");
+        for hint in &card.hints {
+            let _ = writeln!(context, "- {hint}");
+        }
+        let _ = writeln!(context);
+    }
+    if let Some(article) = article {
+        let _ = writeln!(context, "# Article\n{article}\n");
+    }
+    let _ = write!(context, "# User's answer\n{answer}");
+    context
+}
@@ -0,0 +1,532 @@
+//! Generation of card groups from local source files, e.g. a repository of LeetCode solutions.
+//! The files of a folder or a zip archive are grouped into problems by [`Grouping`], and every
+//! problem is generated like a snippet sent to the bot, see
+//! [`CardGeneratorService::generate_code_card_group`].
+//!
+//! The SHA-1 of the code of a problem is kept as the `source_hash` of the data of its card group.
+//! Problems the user already has a card group with the same hash for are skipped, so generating
+//! from the same folder again only generates the problems that are new or changed since.
+//!
+//! The LLM is asked about several problems at once, see [`BatchOptions::concurrency`], the card
+//! groups are created one at a time. A problem that fails is reported and doesn't stop the others.
+
+use crate::error::CoreError;
+use crate::llm::card_generator_service::CardGeneratorService;
+use crate::reexports::db::sql::Thing;
+use crate::store::any::AnyStorage;
+use crate::store::{CardGroupStore, Storage};
+use bon::Builder;
+use itertools::Itertools;
+use sha1::{Digest, Sha1};
+use std::collections::{BTreeMap, HashSet};
+use std::fmt::{Debug, Display, Formatter};
+use std::io::{Cursor, Read};
+use std::path::Path;
+use std::str::FromStr;
+use std::sync::Arc;
+use tokio::task::JoinSet;
+use tracing::{info, warn};
+use zip::ZipArchive;
+
+/// Larger files are not read, they are rarely a solution.
+pub const MAX_FILE_SIZE: u64 = 64 * 1024;
+
+pub const SOURCE_EXTENSIONS: &[&str] = &[
+    "c", "cc", "cpp", "cs", "dart", "ex", "go", "h", "hpp", "java", "js", "kt", "php", "py", "rb",
+    "rs", "scala", "sql", "swift", "ts",
+];
+
+/// Folders of dependencies and build outputs, skipped like hidden folders.
+const IGNORED_DIRS: &[&str] = &[
+    "__pycache__",
+    "build",
+    "dist",
+    "node_modules",
+    "target",
+    "vendor",
+    "venv",
+];
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct SourceFile {
+    /// The path relative to the folder or to the root of the archive, separated by `/`.
+    pub path: Arc<str>,
+    pub code: Arc<str>,
+}
+
+impl SourceFile {
+    /// Whether `path` has one of the [`SOURCE_EXTENSIONS`] and is not in a hidden or ignored
+    /// folder.
+    pub fn is_source(path: &str) -> bool {
+        let mut components = path.split('/').filter(|component| !component.is_empty());
+        let Some(name) = components.next_back() else {
+            return false;
+        };
+        if components.any(is_ignored_dir) || name.starts_with('.') {
+            return false;
+        }
+        name.rsplit_once('.').is_some_and(|(_, extension)| {
+            SOURCE_EXTENSIONS.contains(&extension.to_lowercase().as_str())
+        })
+    }
+
+    fn dir(&self) -> &str {
+        self.path.rsplit_once('/').map_or("", |(dir, _)| dir)
+    }
+
+    fn stem(&self) -> &str {
+        let name = self.path.rsplit('/').next().unwrap_or_default();
+        name.rsplit_once('.').map_or(name, |(stem, _)| stem)
+    }
+}
+
+/// How source files are grouped into problems.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum Grouping {
+    /// Files with the same name in the same folder, like `two_sum.py` and `two_sum.rs`.
+    #[default]
+    Stem,
+    /// The files of a folder, like `0001-two-sum/main.rs` and `0001-two-sum/lib.rs`. Files at the
+    /// root are problems of their own.
+    Directory,
+}
+
+impl FromStr for Grouping {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.trim().to_lowercase().as_str() {
+            "stem" => Ok(Grouping::Stem),
+            "directory" => Ok(Grouping::Directory),
+            _ => Err(format!(
+                "Unknown grouping `{s}`, expected stem or directory"
+            )),
+        }
+    }
+}
+
+/// Source files generated as one card group.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Problem {
+    pub name: Arc<str>,
+    /// Sorted by path.
+    pub files: Vec<SourceFile>,
+}
+
+impl Problem {
+    /// The problems of `files`, sorted by name.
+    pub fn group(files: Vec<SourceFile>, grouping: Grouping) -> Vec<Problem> {
+        let mut problems = BTreeMap::<String, Vec<SourceFile>>::new();
+        for file in files {
+            let name = match grouping {
+                Grouping::Directory if !file.dir().is_empty() => file.dir().to_string(),
+                Grouping::Directory => file.stem().to_string(),
+                Grouping::Stem if file.dir().is_empty() => file.stem().to_string(),
+                Grouping::Stem => format!("{}/{}", file.dir(), file.stem()),
+            };
+            problems.entry(name).or_default().push(file);
+        }
+
+        problems
+            .into_iter()
+            .map(|(name, mut files)| {
+                files.sort_by(|a, b| a.path.cmp(&b.path));
+                Problem {
+                    name: Arc::from(name),
+                    files,
+                }
+            })
+            .collect()
+    }
+
+    /// What the LLM is asked to comment: the code of a single file, or the code of every file
+    /// under its path.
+    pub fn code(&self) -> String {
+        match self.files.as_slice() {
+            [file] => file.code.to_string(),
+            files => files
+                .iter()
+                .map(|file| format!("{}:\n```\n{}\n```", file.path, file.code.trim_end()))
+                .join("\n\n"),
+        }
+    }
+
+    /// The hex SHA-1 of the code of the files, which doesn't change when the files are moved.
+    pub fn hash(&self) -> Arc<str> {
+        let mut bytes = vec![];
+        for file in &self.files {
+            bytes.extend_from_slice(file.code.as_bytes());
+            bytes.push(0);
+        }
+        Arc::from(
+            Sha1::digest(&bytes)
+                .iter()
+                .map(|byte| format!("{byte:02x}"))
+                .collect::<String>(),
+        )
+    }
+}
+
+/// The source files of `dir` and its subfolders, see [`SourceFile::is_source`].
+pub async fn read_dir(dir: &Path) -> Result<Vec<SourceFile>, CoreError> {
+    let mut files = vec![];
+    let mut dirs = vec![dir.to_path_buf()];
+    while let Some(next) = dirs.pop() {
+        let mut entries = tokio::fs::read_dir(&next).await?;
+        while let Some(entry) = entries.next_entry().await? {
+            let path = entry.path();
+            let file_type = entry.file_type().await?;
+            if file_type.is_dir() {
+                if !is_ignored_dir(&entry.file_name().to_string_lossy()) {
+                    dirs.push(path);
+                }
+                continue;
+            }
+
+            let Ok(relative) = path.strip_prefix(dir) else {
+                continue;
+            };
+            let relative = relative
+                .components()
+                .map(|component| component.as_os_str().to_string_lossy())
+                .join("/");
+            if !file_type.is_file() || !SourceFile::is_source(&relative) {
+                continue;
+            }
+            if entry.metadata().await?.len() > MAX_FILE_SIZE {
+                warn!(path = %relative, "Skipping a large source file");
+                continue;
+            }
+            let Ok(code) = String::from_utf8(tokio::fs::read(&path).await?) else {
+                warn!(path = %relative, "Skipping a source file that is not UTF-8");
+                continue;
+            };
+            files.push(SourceFile {
+                path: Arc::from(relative),
+                code: Arc::from(code),
+            });
+        }
+    }
+    files.sort_by(|a, b| a.path.cmp(&b.path));
+    Ok(files)
+}
+
+/// The source files of a zip archive, see [`SourceFile::is_source`].
+pub fn read_zip(archive: &[u8]) -> Result<Vec<SourceFile>, CoreError> {
+    let mut archive = ZipArchive::new(Cursor::new(archive))?;
+    let mut files = vec![];
+    for index in 0..archive.len() {
+        let mut entry = archive.by_index(index)?;
+        let Some(path) = entry.enclosed_name() else {
+            continue;
+        };
+        let path = path
+            .components()
+            .map(|component| component.as_os_str().to_string_lossy())
+            .join("/");
+        if !entry.is_file() || !SourceFile::is_source(&path) {
+            continue;
+        }
+        if entry.size() > MAX_FILE_SIZE {
+            warn!(%path, "Skipping a large source file");
+            continue;
+        }
+        let mut code = String::new();
+        if entry.read_to_string(&mut code).is_err() {
+            warn!(%path, "Skipping a source file that is not UTF-8");
+            continue;
+        }
+        files.push(SourceFile {
+            path: Arc::from(path),
+            code: Arc::from(code),
+        });
+    }
+    files.sort_by(|a, b| a.path.cmp(&b.path));
+    Ok(files)
+}
+
+fn is_ignored_dir(name: &str) -> bool {
+    name.starts_with('.') || IGNORED_DIRS.contains(&name)
+}
+
+#[derive(Debug, Clone, Builder)]
+pub struct BatchOptions {
+    #[builder(default)]
+    pub grouping: Grouping,
+
+    /// How many problems the LLM is asked about at once.
+    #[builder(default = 4)]
+    pub concurrency: usize,
+
+    /// Ask for wrong options too, see [`CardGeneratorService::generate_code_cards`].
+    #[builder(default)]
+    pub distractors: bool,
+}
+
+impl Default for BatchOptions {
+    fn default() -> Self {
+        Self::builder().build()
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Generated {
+    pub problem: Arc<str>,
+    /// The title of the card group.
+    pub title: Arc<str>,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ProblemError {
+    pub problem: Arc<str>,
+    pub message: Arc<str>,
+}
+
+impl Display for ProblemError {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}: {}", self.problem, self.message)
+    }
+}
+
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct BatchReport {
+    /// Source files of all the problems.
+    pub files: usize,
+    pub created: Vec<Generated>,
+    /// Problems with a card group already.
+    pub skipped: Vec<Arc<str>>,
+    pub failed: Vec<ProblemError>,
+}
+
+impl Display for BatchReport {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        write!(f, "Files: {}", self.files)?;
+        write!(f, "\nCreated: {}", self.created.len())?;
+        for generated in &self.created {
+            write!(f, "\n  {}: {}", generated.problem, generated.title)?;
+        }
+        write!(f, "\nSkipped: {}", self.skipped.len())?;
+        for problem in &self.skipped {
+            write!(f, "\n  {problem}")?;
+        }
+        write!(f, "\nFailed: {}", self.failed.len())?;
+        for error in &self.failed {
+            write!(f, "\n  {error}")?;
+        }
+        Ok(())
+    }
+}
+
+#[derive(Clone)]
+pub struct BatchGenerator<S: Storage = AnyStorage> {
+    pub generator: CardGeneratorService<S>,
+}
+
+impl<S: Storage> Debug for BatchGenerator<S> {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        write!(f, "BatchGenerator")
+    }
+}
+
+impl<S: Storage> BatchGenerator<S> {
+    pub fn new(generator: CardGeneratorService<S>) -> Self {
+        Self { generator }
+    }
+
+    /// Generates a card group in `deck` for every problem of `files` that `user` has no card
+    /// group for, see the [module](self) documentation.
+    pub async fn generate(
+        &self,
+        user: impl Into<Thing>,
+        deck: impl Into<Thing>,
+        files: Vec<SourceFile>,
+        options: BatchOptions,
+    ) -> Result<BatchReport, CoreError> {
+        let user = user.into();
+        let deck = deck.into();
+        let mut hashes = self
+            .generator
+            .card_groups
+            .list_by_user_id(user.clone())
+            .await?
+            .into_iter()
+            .filter_map(|card_group| card_group.data?.source_hash)
+            .collect::<HashSet<_>>();
+
+        let mut report = BatchReport::default();
+        let mut pending = vec![];
+        for problem in Problem::group(files, options.grouping) {
+            report.files += problem.files.len();
+            let hash = problem.hash();
+            if hashes.insert(hash.clone()) {
+                pending.push((problem, hash));
+            } else {
+                report.skipped.push(problem.name);
+            }
+        }
+
+        let mut pending = pending.into_iter();
+        let mut tasks = JoinSet::new();
+        loop {
+            while tasks.len() < options.concurrency.max(1) {
+                let Some((problem, hash)) = pending.next() else {
+                    break;
+                };
+                let generator = self.generator.clone();
+                let distractors = options.distractors;
+                tasks.spawn(async move {
+                    info!(problem = %problem.name, "Generating cards");
+                    let result = generator
+                        .generate_code_card_group(problem.code(), distractors)
+                        .await;
+                    (problem.name, hash, result)
+                });
+            }
+            let Some(joined) = tasks.join_next().await else {
+                break;
+            };
+
+            let (problem, hash, result) = joined?;
+            let result = match result {
+                Ok(mut gpt_card_group) => {
+                    let data = gpt_card_group.data.get_or_insert_with(Default::default);
+                    data.source_hash = Some(hash);
+                    self.generator
+                        .create_cards(user.clone(), deck.clone(), gpt_card_group)
+                        .await
+                }
+                Err(err) => Err(err),
+            };
+            match result {
+                Ok(deck_card_group) => report.created.push(Generated {
+                    problem,
+                    title: deck_card_group.card_group.title.clone(),
+                }),
+                Err(err) => {
+                    warn!(%problem, ?err, "Failed to generate cards");
+                    report.failed.push(ProblemError {
+                        problem,
+                        message: Arc::from(err.to_string()),
+                    });
+                }
+            }
+        }
+
+        report.created.sort_by(|a, b| a.problem.cmp(&b.problem));
+        report.failed.sort_by(|a, b| a.problem.cmp(&b.problem));
+        Ok(report)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::io::Write;
+    use zip::write::SimpleFileOptions;
+    use zip::ZipWriter;
+
+    fn file(path: &str, code: &str) -> SourceFile {
+        SourceFile {
+            path: Arc::from(path),
+            code: Arc::from(code),
+        }
+    }
+
+    #[test]
+    fn test_is_source() {
+        for path in ["two_sum.rs", "easy/two_sum.PY", "src/main.rs"] {
+            assert!(SourceFile::is_source(path), "{path}");
+        }
+        for path in [
+            "README.md",
+            "two_sum",
+            ".hidden.rs",
+            ".git/hooks/pre-commit.sh",
+            "target/debug/build.rs",
+            "web/node_modules/left-pad/index.js",
+        ] {
+            assert!(!SourceFile::is_source(path), "{path}");
+        }
+    }
+
+    #[test]
+    fn test_group() {
+        let files = || {
+            vec![
+                file("0001-two-sum/main.rs", "fn main() {}"),
+                file("0001-two-sum/lib.rs", "fn two_sum() {}"),
+                file("easy/two_sum.py", "def two_sum(): pass"),
+                file("easy/two_sum.rs", "fn two_sum() {}"),
+                file("add_two.rs", "fn add_two() {}"),
+            ]
+        };
+
+        let by_stem = Problem::group(files(), Grouping::Stem);
+        let names = by_stem.iter().map(|p| &*p.name).collect::<Vec<_>>();
+        assert_eq!(
+            names,
+            [
+                "0001-two-sum/lib",
+                "0001-two-sum/main",
+                "add_two",
+                "easy/two_sum"
+            ]
+        );
+        assert_eq!(by_stem[3].files.len(), 2);
+        assert_eq!(by_stem[2].code(), "fn add_two() {}");
+        assert_eq!(
+            by_stem[3].code(),
+            "easy/two_sum.py:\n```\ndef two_sum(): pass\n```\n\n\
+             easy/two_sum.rs:\n```\nfn two_sum() {}\n```"
+        );
+
+        let by_directory = Problem::group(files(), Grouping::Directory);
+        let names = by_directory.iter().map(|p| &*p.name).collect::<Vec<_>>();
+        assert_eq!(names, ["0001-two-sum", "add_two", "easy"]);
+        assert_eq!(
+            by_directory[0].files[0].path.as_ref(),
+            "0001-two-sum/lib.rs"
+        );
+    }
+
+    #[test]
+    fn test_hash() {
+        let problem = Problem::group(
+            vec![file("a/two_sum.rs", "fn two_sum() {}")],
+            Grouping::Stem,
+        )
+        .remove(0);
+        let moved = Problem::group(
+            vec![file("b/two_sum.rs", "fn two_sum() {}")],
+            Grouping::Stem,
+        )
+        .remove(0);
+        let changed = Problem::group(
+            vec![file("a/two_sum.rs", "fn two_sum() { }")],
+            Grouping::Stem,
+        )
+        .remove(0);
+        assert_eq!(problem.hash().len(), 40);
+        assert_eq!(problem.hash(), moved.hash());
+        assert_ne!(problem.hash(), changed.hash());
+    }
+
+    #[test]
+    fn test_read_zip() -> Result<(), CoreError> {
+        let mut writer = ZipWriter::new(Cursor::new(vec![]));
+        for (path, contents) in [
+            ("solutions/two_sum.rs", &b"fn two_sum() {}"[..]),
+            ("solutions/README.md", b"# Solutions"),
+            ("solutions/target/build.rs", b"fn main() {}"),
+            ("solutions/binary.rs", &[0xff, 0xfe]),
+        ] {
+            writer.start_file(path, SimpleFileOptions::default())?;
+            writer.write_all(contents)?;
+        }
+        let archive = writer.finish()?.into_inner();
+
+        let files = read_zip(&archive)?;
+        assert_eq!(files, [file("solutions/two_sum.rs", "fn two_sum() {}")]);
+        Ok(())
+    }
+}
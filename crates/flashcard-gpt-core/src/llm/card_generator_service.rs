@@ -1,40 +1,90 @@
 use crate::model::card::CreateCard;
+use crate::model::card_data::CardData;
 use crate::model::card_group::CreateCardGroup;
+use crate::model::deck_card::CreateDeckCard;
 use crate::model::deck_card_group::{CreateDeckCardGroup, DeckCardGroup};
 use crate::model::llm::GptCardGroup;
 use crate::error::CoreError;
 use crate::llm::custom_executor::{CustomExecutor, CustomStep};
+use crate::llm::provider::LlmChoice;
 use crate::reexports::db::sql::Thing;
-use crate::repo::card::CardRepo;
-use crate::repo::card_group::CardGroupRepo;
-use crate::repo::deck::DeckRepo;
-use crate::repo::tag::TagRepo;
+use crate::store::any::AnyStorage;
+use crate::store::siblings::CardSiblingsExt;
+use crate::store::{CardGroupStore, DeckStore, Storage, TagStore};
 use std::collections::BTreeMap;
 use std::fmt::{Debug, Formatter};
 use std::sync::Arc;
 
+/// The name of the chain of [`CardGeneratorService::generate_code_cards`].
+pub const CODE_CARDS_CHAIN: &str = "code_cards";
+
+/// The name of the chain of [`CardGeneratorService::generate_text_cards`].
+pub const TEXT_CARDS_CHAIN: &str = "text_cards";
+
+static DISTRACTORS_PROMPT: &str = r#"
+Also add a "distractors" field to every card that is not a cloze card: a list of 3 plausible but
+wrong answers to its "front", as short as "back" and in the same style, e.g.
+"distractors": ["<wrong answer>", "<wrong answer>", "<wrong answer>"]
+"#;
+
+static TEXT_FLASHCARDS_PROMPT: &str = r#"
+You are a bot that converts a section of a document into flashcards for a highly knowledgeable
+audience.
+Create flashcards for the facts, definitions and ideas of the section that are worth remembering,
+skip trivia and anything that only makes sense with the document at hand.
+Every card asks a single question, the back answers it concisely and the hints point in the right
+direction without giving the answer away.
+Do not create more than **5 cards per section**, and no cards at all for a section without anything
+worth remembering, like a table of contents.
+Facts that are worth remembering verbatim (names, numbers, formulas) may be written as cloze cards:
+put the whole sentence in "front", mark every blank as {% raw %}{{c1::answer}}{% endraw %} or
+{% raw %}{{c1::answer::hint}}{% endraw %} (blanks with the same number are asked together) and use
+"back" for additional context.
+Respond **only** in the following JSON format (do not include any additional text outside the JSON):
+
+{
+ "title": "<A title of the section>",
+ "difficulty": <rate the difficulty of the section on a scale of 1-10 (1 = easiest, 10 = hardest)>,
+ "importance": <rate the importance of the section on a scale of 1-10 (1 = least important, 10 = most important)>,
+ "tags": [<list of relevant tags for the section, e.g., 'Databases', 'Consensus', 'History'>],
+ "cards": [
+   {
+     "title": "<Card title>",
+     "front": "<Card front text>",
+     "back": "<Card back text>",
+     "hints": ["<List of hints>"],
+     "difficulty": <rate the difficulty of the card on a scale of 1-10 (1 = easiest, 10 = hardest)>,
+     "importance": <rate the importance of the card on a scale of 1-10 (1 = least important, 10 = most important), based on the concepts it covers>,
+     "tags": ["<List of relevant tags for the card>"],
+     "reversible": <true if the card also makes sense asked back to front, e.g. a term and its definition, otherwise false>
+   },
+   ...
+ ]
+}
+"#;
+
 #[derive(Clone)]
-pub struct CardGeneratorService {
+pub struct CardGeneratorService<S: Storage = AnyStorage> {
     pub card_generator: CustomExecutor,
-    pub cards: CardRepo,
-    pub card_groups: CardGroupRepo,
-    pub decks: DeckRepo,
-    pub tags: TagRepo,
+    pub cards: S::Cards,
+    pub card_groups: S::CardGroups,
+    pub decks: S::Decks,
+    pub tags: S::Tags,
 }
 
-impl Debug for CardGeneratorService {
+impl<S: Storage> Debug for CardGeneratorService<S> {
     fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
         write!(f, "CardGeneratorService")
     }
 }
 
-impl CardGeneratorService {
+impl<S: Storage> CardGeneratorService<S> {
     pub fn new(
         card_generator: CustomExecutor,
-        cards: CardRepo,
-        card_groups: CardGroupRepo,
-        decks: DeckRepo,
-        tags: TagRepo,
+        cards: S::Cards,
+        card_groups: S::CardGroups,
+        decks: S::Decks,
+        tags: S::Tags,
     ) -> Self {
         Self {
             card_generator,
@@ -44,9 +94,22 @@ impl CardGeneratorService {
             tags,
         }
     }
+
+    /// The service for a user with the LLM `choice` of their settings, see
+    /// [`CustomExecutor::for_user`].
+    pub fn for_user(&self, choice: Option<LlmChoice>) -> Self {
+        Self {
+            card_generator: self.card_generator.for_user(choice),
+            ..self.clone()
+        }
+    }
+
+    /// With `distractors` the LLM is asked for wrong options too, which turns the cards into
+    /// multiple-choice ones.
     pub async fn generate_code_cards(
         &self,
         code: impl AsRef<str>,
+        distractors: bool,
     ) -> Result<(String, BTreeMap<Arc<str>, Arc<str>>), CoreError> {
         let code_comment_step = CustomStep {
             name: "Code Comment".into(),
@@ -88,9 +151,8 @@ multiple steps if necessary to achieve clarity.
             output_param_name: Arc::from("article"),
         };
 
-        let create_flashcards_step = CustomStep {
-            name: Arc::from("Create Flashcards"),
-            system_template: Arc::from(r#"
+        let mut create_flashcards_prompt = String::from(
+            r#"
 You are a bot that converts given LeetCode code and articles into flashcards.
 For each problem, create flashcards that include hints pointing in the right direction without fully
 exposing the solution.
@@ -99,6 +161,10 @@ one to guide the user toward the solution.
 The number of hints and cards should correspond to the complexity of the problem, but do not create 
 more than **3 cards per problem**.
 Ensure that the hints are sufficient for the user to recall the solution.
+Facts that are worth remembering verbatim (complexities, API names) may be written as cloze cards:
+put the whole sentence in "front", mark every blank as {% raw %}{{c1::answer}}{% endraw %} or
+{% raw %}{{c1::answer::hint}}{% endraw %} (blanks with the same number are asked together) and use
+"back" for additional context.
 Respond **only** in the following JSON format (do not include any additional text outside the JSON):
 
 {
@@ -107,7 +173,7 @@ Respond **only** in the following JSON format (do not include any additional tex
  "importance": <rate the importance of the problem on a scale of 1-10 (1 = least important, 10 = most important), based on its popularity and the frequency of the concepts used in FAANG interviews>,
  "tags": [<list of relevant tags for the problem and solution, e.g., 'Dynamic Programming', 'Graphs', 'Recursion'>],
  "data": {
-   "leetcode_link": "https://..."
+   "source_link": "https://..."
  },
  "cards": [
    {
@@ -117,12 +183,21 @@ Respond **only** in the following JSON format (do not include any additional tex
      "hints": ["<List of hints>"],
      "difficulty": <rate the difficulty of the card on a scale of 1-10 (1 = easiest, 10 = hardest)>,
      "importance": <rate the importance of the card on a scale of 1-10 (1 = least important, 10 = most important), based on the concepts it covers>,
-     "tags": ["<List of relevant tags for the card>"]
+     "tags": ["<List of relevant tags for the card>"],
+     "reversible": <true if the card also makes sense asked back to front, e.g. a term and its definition, otherwise false>
    },
    ...
  ]
 }
-"#),
+"#,
+        );
+        if distractors {
+            create_flashcards_prompt.push_str(DISTRACTORS_PROMPT);
+        }
+
+        let create_flashcards_step = CustomStep {
+            name: Arc::from("Create Flashcards"),
+            system_template: Arc::from(create_flashcards_prompt),
             user_template: Arc::from("Convert given article and solution into flashcards:\nArticle:\n{{article}}\n\nCode:\n{{commented_code}}"),
             input_param_names: vec!["article".into(), "commented_code".into()],
             output_param_name: Arc::from("flashcards"),
@@ -131,6 +206,7 @@ Respond **only** in the following JSON format (do not include any additional tex
         let result = self
             .card_generator
             .execute_custom_chain(
+                CODE_CARDS_CHAIN,
                 &[
                     code_comment_step,
                     write_article_step,
@@ -143,6 +219,51 @@ Respond **only** in the following JSON format (do not include any additional tex
         Ok(result)
     }
 
+    /// The card group of `code`, with the commented code and the article written on the way as
+    /// its attachments.
+    pub async fn generate_code_card_group(
+        &self,
+        code: impl AsRef<str>,
+        distractors: bool,
+    ) -> Result<GptCardGroup, CoreError> {
+        let (code_cards, params) = self.generate_code_cards(code, distractors).await?;
+        let mut gpt_card_group = GptCardGroup::from_gpt_response(&code_cards)?;
+
+        // the intermediate steps of the generation are kept as attachments of the card group
+        let data = gpt_card_group.data.get_or_insert_with(Default::default);
+        data.article = params.get("article").cloned();
+        data.commented_code = params.get("commented_code").cloned();
+        Ok(gpt_card_group)
+    }
+
+    /// A card group for a section of a document, `text` starts with the name of the document and
+    /// of the section, see [`crate::document::Chunk::prompt`].
+    pub async fn generate_text_cards(
+        &self,
+        text: impl AsRef<str>,
+        distractors: bool,
+    ) -> Result<GptCardGroup, CoreError> {
+        let mut create_flashcards_prompt = String::from(TEXT_FLASHCARDS_PROMPT);
+        if distractors {
+            create_flashcards_prompt.push_str(DISTRACTORS_PROMPT);
+        }
+
+        let create_flashcards_step = CustomStep {
+            name: Arc::from("Create Flashcards From Text"),
+            system_template: Arc::from(create_flashcards_prompt),
+            user_template: Arc::from("Convert the section below into flashcards:\n{{text}}"),
+            input_param_names: vec![Arc::from("text")],
+            output_param_name: Arc::from("flashcards"),
+        };
+
+        let (flashcards, _) = self
+            .card_generator
+            .execute_custom_chain(TEXT_CARDS_CHAIN, &[create_flashcards_step], text.as_ref())
+            .await?;
+        Ok(GptCardGroup::from_gpt_response(&flashcards)?)
+    }
+
+    /// The cards get the [source](crate::model::card_data::SourceRef) of the card group.
     pub async fn create_cards(
         &self,
         user: impl Into<Thing>,
@@ -151,28 +272,42 @@ Respond **only** in the following JSON format (do not include any additional tex
     ) -> Result<DeckCardGroup, CoreError> {
         let user = user.into();
         let deck = deck.into();
+        let card_data = gpt_card_group
+            .data
+            .as_ref()
+            .and_then(|data| data.source.clone())
+            .map(|source| CardData {
+                source: Some(source),
+                ..CardData::default()
+            });
 
         let mut cards = vec![];
+        let mut reversed_cards = vec![];
         for card in gpt_card_group.cards {
             let tags = self
                 .tags
                 .get_or_create_tags(user.clone(), card.tags)
                 .await?;
-            let card = self
-                .cards
-                .create(CreateCard {
-                    user: user.clone(),
-                    title: card.title,
-                    front: Some(card.front),
-                    back: Some(card.back),
-                    hints: card.hints,
-                    difficulty: card.difficulty,
-                    importance: card.importance,
-                    data: None,
-                    tags: tags.into_iter().map(|t| t.id).collect(),
-                })
-                .await?;
-            cards.push(card);
+            let dto = CreateCard {
+                user: user.clone(),
+                choices: card.choices(),
+                sibling: None,
+                reverse: card.reversible,
+                title: card.title,
+                front: Some(card.front),
+                back: Some(card.back),
+                hints: card.hints,
+                difficulty: card.difficulty,
+                importance: card.importance,
+                data: card_data.clone(),
+                tags: tags.into_iter().map(|t| t.id).collect(),
+            };
+            for dto in dto.into_cloze_cards() {
+                // the reversed sibling is asked on its own rather than as a part of the group
+                let mut created = self.cards.create_with_sibling(dto).await?.into_iter();
+                cards.extend(created.next());
+                reversed_cards.extend(created);
+            }
         }
 
         let tags = self
@@ -189,7 +324,7 @@ Respond **only** in the following JSON format (do not include any additional tex
                 user: user.clone(),
                 importance: gpt_card_group.importance,
                 title: gpt_card_group.title,
-                data: gpt_card_group.data.map(Arc::new),
+                data: gpt_card_group.data,
                 cards: cards.into_iter().map(|c| c.id).collect(),
                 difficulty: gpt_card_group.difficulty,
                 tags,
@@ -204,6 +339,15 @@ Respond **only** in the following JSON format (do not include any additional tex
             })
             .await?;
 
+        for card in reversed_cards {
+            self.decks
+                .relate_card(CreateDeckCard {
+                    deck: deck.clone(),
+                    card: card.id,
+                })
+                .await?;
+        }
+
         Ok(deck_card_group)
     }
 }
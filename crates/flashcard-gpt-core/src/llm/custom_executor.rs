@@ -1,13 +1,30 @@
+//! Execution of chains of [`CustomStep`]s, the output of a step is an input of the next ones. Every
+//! step is sent to a [provider](crate::llm::provider) picked by the first of:
+//!
+//! - the choice of the user, see [`CustomExecutor::for_user`];
+//! - the choice for the chain, see [`CustomExecutor::with_chain`];
+//! - the default choice.
+//!
+//! A choice of a provider that is not configured is skipped. With
+//! [fixtures](crate::llm::fixtures) the steps are recorded or replayed, see
+//! [`CustomExecutor::with_fixtures`].
+
 use crate::error::CoreError;
+use crate::llm::fixtures::{FixtureMode, LlmFixtures};
+use crate::llm::mock_executor::MockExecutor;
+use crate::llm::provider::ollama::OllamaProvider;
+use crate::llm::provider::openai::OpenAiProvider;
+use crate::llm::provider::{AnyProvider, Completion, LlmChoice, LlmProvider, ProviderKind};
 use itertools::Itertools;
-use llm_chain::frame::Frame;
+use llm_chain::prompt::ChatRole;
 use llm_chain::step::Step;
 use llm_chain::{prompt, Parameters};
-use llm_chain_openai::chatgpt::Executor;
 use serde::{Deserialize, Serialize};
 use std::collections::BTreeMap;
+use std::fmt::{Debug, Formatter};
+use std::path::PathBuf;
 use std::sync::Arc;
-use tracing::info;
+use tracing::{info, warn};
 
 #[derive(Debug, Serialize, Deserialize)]
 pub struct CustomStep {
@@ -23,22 +40,235 @@ impl CustomStep {
         let prompt = prompt!(self.system_template.as_ref(), self.user_template.as_ref());
         Step::for_prompt_template(prompt)
     }
+
+    /// The step with its templates rendered with `parameters`.
+    fn to_completion(&self, parameters: &Parameters) -> Result<Completion, CoreError> {
+        let prompt = self.to_step().format(parameters)?;
+        let mut system = vec![];
+        let mut user = vec![];
+        for message in prompt.to_chat().iter() {
+            match message.role() {
+                ChatRole::System => system.push(message.body().clone()),
+                _ => user.push(message.body().clone()),
+            }
+        }
+
+        let inputs = self
+            .input_param_names
+            .iter()
+            .filter_map(|name| Some((name.clone(), Arc::from(parameters.get(name.as_ref())?))))
+            .collect();
+        Ok(Completion {
+            step: self.name.clone(),
+            model: None,
+            system: system.join("\n"),
+            user: user.join("\n"),
+            inputs,
+        })
+    }
 }
 
 #[derive(Clone)]
 pub struct CustomExecutor {
-    executor: Executor,
+    providers: Arc<BTreeMap<ProviderKind, AnyProvider>>,
+    default: LlmChoice,
+    chains: Arc<BTreeMap<Arc<str>, LlmChoice>>,
+    user: Option<LlmChoice>,
+    fixtures: Option<Arc<LlmFixtures>>,
+}
+
+impl Debug for CustomExecutor {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("CustomExecutor")
+            .field("providers", &self.providers.keys().collect_vec())
+            .field("default", &self.default)
+            .field("chains", &self.chains)
+            .field("user", &self.user)
+            .field("fixtures", &self.fixtures)
+            .finish()
+    }
 }
 
 impl CustomExecutor {
-    pub fn new(executor: Executor) -> Self {
-        Self { executor }
+    /// An executor sending every chain to `provider` with its default model.
+    pub fn new(provider: impl Into<AnyProvider>) -> Self {
+        let provider = provider.into();
+        let default = LlmChoice::new(provider.kind());
+        Self {
+            providers: Arc::new(BTreeMap::from([(provider.kind(), provider)])),
+            default,
+            chains: Arc::default(),
+            user: None,
+            fixtures: None,
+        }
+    }
+
+    /// An executor that answers from `mock` instead of calling the LLM.
+    pub fn mock(mock: MockExecutor) -> Self {
+        Self::new(mock)
+    }
+
+    /// An executor that answers from the fixtures recorded at `path`, see
+    /// [`LlmFixtures::replay`].
+    pub fn replay(path: impl Into<PathBuf>) -> Result<Self, CoreError> {
+        // replayed steps never reach the provider
+        Ok(Self::mock(MockExecutor::default()).with_fixtures(LlmFixtures::replay(path)?))
+    }
+
+    /// The providers configured in the environment, see [`OpenAiProvider::from_env`] and
+    /// [`OllamaProvider::from_env`], and a mock answering from the JSON file at
+    /// `LLM_MOCK_RESPONSES`, see [`MockExecutor::from_file`].
+    ///
+    /// `LLM_PROVIDER` is the default choice, like `ollama:llama3.1`, the first configured of
+    /// OpenAI, Ollama and the mock otherwise. `LLM_CHAINS` has the choices of chains, like
+    /// `grade_answer=ollama,text_cards=openai:gpt-4o-mini`.
+    ///
+    /// Steps are recorded or replayed with the fixtures of [`LlmFixtures::from_env`], replaying
+    /// doesn't need a provider.
+    pub fn from_env() -> Result<Self, CoreError> {
+        let invalid = |message: String| CoreError::InvalidConfig(Arc::from(message));
+        let mut providers = BTreeMap::new();
+        let mut add = |provider: AnyProvider| providers.insert(provider.kind(), provider);
+        if let Some(provider) = OpenAiProvider::from_env() {
+            add(provider.into());
+        }
+        if let Some(provider) = OllamaProvider::from_env() {
+            add(provider.into());
+        }
+        if let Ok(path) = std::env::var("LLM_MOCK_RESPONSES") {
+            add(MockExecutor::from_file(path)?.into());
+        }
+        let fixtures = LlmFixtures::from_env()?;
+        let replay = fixtures
+            .as_ref()
+            .is_some_and(|fixtures| fixtures.mode() == FixtureMode::Replay);
+        if providers.is_empty() && replay {
+            providers.insert(ProviderKind::Mock, MockExecutor::default().into());
+        }
+
+        let default = match std::env::var("LLM_PROVIDER") {
+            Ok(choice) => choice
+                .parse::<LlmChoice>()
+                .map_err(|err| invalid(format!("LLM_PROVIDER: {err}")))?,
+            Err(_) => match providers.keys().next() {
+                Some(&provider) => LlmChoice::new(provider),
+                None => {
+                    return Err(invalid(
+                        "No LLM provider is configured, set OPENAI_API_KEY, OPENAI_BASE_URL, \
+                         OLLAMA_BASE_URL, LLM_MOCK_RESPONSES or LLM_FIXTURES to replay"
+                            .to_string(),
+                    ))
+                }
+            },
+        };
+        if !providers.contains_key(&default.provider) {
+            return Err(invalid(format!(
+                "LLM_PROVIDER is {default}, but the provider is not configured"
+            )));
+        }
+
+        let mut executor = Self {
+            providers: Arc::new(providers),
+            default,
+            chains: Arc::default(),
+            user: None,
+            fixtures: fixtures.map(Arc::new),
+        };
+        if let Ok(chains) = std::env::var("LLM_CHAINS") {
+            for chain in chains.split(',').filter(|chain| !chain.trim().is_empty()) {
+                let Some((name, choice)) = chain.split_once('=') else {
+                    return Err(invalid(format!(
+                        "LLM_CHAINS: expected chain=provider:model, got {chain:?}"
+                    )));
+                };
+                let choice = choice
+                    .parse::<LlmChoice>()
+                    .map_err(|err| invalid(format!("LLM_CHAINS: {err}")))?;
+                executor = executor.with_chain(name.trim(), choice);
+            }
+        }
+        Ok(executor)
+    }
+
+    /// Also sends chains to `provider` when they or the user choose it.
+    pub fn with_provider(mut self, provider: impl Into<AnyProvider>) -> Self {
+        let provider = provider.into();
+        Arc::make_mut(&mut self.providers).insert(provider.kind(), provider);
+        self
+    }
+
+    /// Sends the chain `name` to `choice` unless the user chooses otherwise.
+    pub fn with_chain(mut self, name: impl Into<Arc<str>>, choice: LlmChoice) -> Self {
+        Arc::make_mut(&mut self.chains).insert(name.into(), choice);
+        self
+    }
+
+    /// Records the responses of the providers to `fixtures` or replays them from it, depending on
+    /// its [mode](LlmFixtures::mode).
+    pub fn with_fixtures(mut self, fixtures: LlmFixtures) -> Self {
+        self.fixtures = Some(Arc::new(fixtures));
+        self
+    }
+
+    /// The executor for a user with the `choice` of their settings.
+    pub fn for_user(&self, choice: Option<LlmChoice>) -> Self {
+        Self {
+            user: choice,
+            ..self.clone()
+        }
+    }
+
+    /// The providers chains can be sent to.
+    pub fn providers(&self) -> impl Iterator<Item = ProviderKind> + '_ {
+        self.providers.keys().copied()
+    }
+
+    /// The choice the chain `name` is sent to, see the [module](self) documentation.
+    pub fn choice(&self, chain: &str) -> &LlmChoice {
+        let choices = [self.user.as_ref(), self.chains.get(chain)];
+        for choice in choices.into_iter().flatten() {
+            if self.providers.contains_key(&choice.provider) {
+                return choice;
+            }
+            warn!(%choice, chain, "The chosen LLM provider is not configured");
+        }
+        &self.default
+    }
+
+    async fn execute_step(
+        &self,
+        choice: &LlmChoice,
+        custom_step: &CustomStep,
+        parameters: &Parameters,
+    ) -> Result<Option<String>, CoreError> {
+        let provider = self.providers.get(&choice.provider);
+        let completion = Completion {
+            model: choice.model.clone(),
+            ..custom_step.to_completion(parameters)?
+        };
+        if let Some(fixtures) = &self.fixtures {
+            return fixtures.complete(provider, &completion).await;
+        }
+
+        let Some(provider) = provider else {
+            return Err(CoreError::InvalidConfig(Arc::from(format!(
+                "The LLM provider {} is not configured",
+                choice.provider
+            ))));
+        };
+        provider.complete(&completion).await
     }
+
+    /// Executes the steps of the chain `chain` in order, `text` is the only input of the first
+    /// one.
     pub async fn execute_custom_chain(
         &self,
+        chain: &str,
         custom_steps: &[CustomStep],
         text: impl Into<String>,
     ) -> Result<(String, BTreeMap<Arc<str>, Arc<str>>), CoreError> {
+        let choice = self.choice(chain).clone();
+        info!(chain, %choice, "Executing chain");
         let mut parameters = Parameters::new();
 
         let Some(first_step) = custom_steps.first() else {
@@ -61,15 +291,7 @@ impl CustomExecutor {
 
         for custom_step in custom_steps {
             info!(%custom_step.name, "Executing step");
-            let step = custom_step.to_step();
-            let result = Frame::new(&self.executor, &step)
-                .format_and_execute(&parameters)
-                .await?
-                .to_immediate()
-                .await?
-                .as_content()
-                .extract_last_body()
-                .cloned();
+            let result = self.execute_step(&choice, custom_step, &parameters).await?;
 
             let Some(result) = result else {
                 return Err(CoreError::LlmBodyExtractError(
@@ -0,0 +1,177 @@
+//! Recorded responses of the LLM, to test chains deterministically and without the network, see
+//! [`CustomExecutor::with_fixtures`](crate::llm::custom_executor::CustomExecutor::with_fixtures).
+//!
+//! In [`FixtureMode::Record`] every step is still sent to its provider, and the prompt and the
+//! response are stored in a JSON file keyed by a hash of the rendered prompt, see
+//! [`LlmFixtures::key`]. In [`FixtureMode::Replay`] the responses come from the file and a prompt
+//! that wasn't recorded fails with [`CoreError::LlmFixtureMissing`], so a changed template shows
+//! up as a failing test until the fixtures are recorded again.
+
+use crate::error::CoreError;
+use crate::ext::mutex::MutexExt;
+use crate::llm::provider::{AnyProvider, Completion, LlmProvider};
+use serde::{Deserialize, Serialize};
+use sha1::{Digest, Sha1};
+use std::collections::BTreeMap;
+use std::fmt::{Display, Formatter};
+use std::path::{Path, PathBuf};
+use std::str::FromStr;
+use std::sync::{Arc, Mutex};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum FixtureMode {
+    Record,
+    Replay,
+}
+
+impl FromStr for FixtureMode {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.trim().to_lowercase().as_str() {
+            "record" => Ok(Self::Record),
+            "replay" => Ok(Self::Replay),
+            other => Err(format!(
+                "Unknown fixture mode {other:?}, expected record or replay"
+            )),
+        }
+    }
+}
+
+impl Display for FixtureMode {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        let name = match self {
+            Self::Record => "record",
+            Self::Replay => "replay",
+        };
+        write!(f, "{name}")
+    }
+}
+
+/// A recorded step. The prompt is kept next to the response to make the fixtures readable.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Fixture {
+    pub step: Arc<str>,
+    pub system: String,
+    pub user: String,
+    pub response: Option<String>,
+}
+
+#[derive(Debug)]
+pub struct LlmFixtures {
+    path: PathBuf,
+    mode: FixtureMode,
+    fixtures: Mutex<BTreeMap<Arc<str>, Fixture>>,
+}
+
+impl LlmFixtures {
+    /// Records the steps to the file at `path`. The file is written anew, so fixtures of prompts
+    /// that are no longer sent don't pile up.
+    pub fn record(path: impl Into<PathBuf>) -> Self {
+        Self {
+            path: path.into(),
+            mode: FixtureMode::Record,
+            fixtures: Mutex::default(),
+        }
+    }
+
+    /// Replays the steps recorded in the file at `path`.
+    pub fn replay(path: impl Into<PathBuf>) -> Result<Self, CoreError> {
+        let path = path.into();
+        let file = std::fs::read(&path)?;
+        let fixtures = serde_json::from_slice(&file)?;
+        Ok(Self {
+            path,
+            mode: FixtureMode::Replay,
+            fixtures: Mutex::new(fixtures),
+        })
+    }
+
+    /// The fixtures at `LLM_FIXTURES` in the mode of `LLM_FIXTURES_MODE`, `replay` by default,
+    /// `None` if `LLM_FIXTURES` is unset.
+    pub fn from_env() -> Result<Option<Self>, CoreError> {
+        let Ok(path) = std::env::var("LLM_FIXTURES") else {
+            return Ok(None);
+        };
+        let mode = match std::env::var("LLM_FIXTURES_MODE") {
+            Ok(mode) => mode.parse::<FixtureMode>().map_err(|err| {
+                CoreError::InvalidConfig(Arc::from(format!("LLM_FIXTURES_MODE: {err}")))
+            })?,
+            Err(_) => FixtureMode::Replay,
+        };
+        match mode {
+            FixtureMode::Record => Ok(Some(Self::record(path))),
+            FixtureMode::Replay => Self::replay(path).map(Some),
+        }
+    }
+
+    pub fn mode(&self) -> FixtureMode {
+        self.mode
+    }
+
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+
+    /// The hex SHA-1 of the rendered system and user prompts of `completion`. The name of the
+    /// step and the model are left out, a response is recorded for a prompt.
+    pub fn key(completion: &Completion) -> Arc<str> {
+        let mut bytes = vec![];
+        bytes.extend_from_slice(completion.system.as_bytes());
+        bytes.push(0);
+        bytes.extend_from_slice(completion.user.as_bytes());
+        Arc::from(
+            Sha1::digest(&bytes)
+                .iter()
+                .map(|byte| format!("{byte:02x}"))
+                .collect::<String>(),
+        )
+    }
+
+    /// The response to `completion`, from `provider` when recording and from the file when
+    /// replaying.
+    pub(crate) async fn complete(
+        &self,
+        provider: Option<&AnyProvider>,
+        completion: &Completion,
+    ) -> Result<Option<String>, CoreError> {
+        let key = Self::key(completion);
+        match self.mode {
+            FixtureMode::Replay => match self.fixtures.lock_sync()?.get(&key) {
+                Some(fixture) => Ok(fixture.response.clone()),
+                None => Err(CoreError::LlmFixtureMissing(Arc::from(format!(
+                    "step {:?} with key {key} is not in {}, record the fixtures again",
+                    completion.step,
+                    self.path.display()
+                )))),
+            },
+            FixtureMode::Record => {
+                let Some(provider) = provider else {
+                    return Err(CoreError::InvalidConfig(Arc::from(format!(
+                        "No LLM provider to record the step {:?}",
+                        completion.step
+                    ))));
+                };
+                let response = provider.complete(completion).await?;
+
+                let mut fixtures = self.fixtures.lock_sync()?;
+                fixtures.insert(
+                    key,
+                    Fixture {
+                        step: completion.step.clone(),
+                        system: completion.system.clone(),
+                        user: completion.user.clone(),
+                        response: response.clone(),
+                    },
+                );
+                // written while locked, steps of concurrent chains don't overwrite each other
+                if let Some(parent) = self.path.parent() {
+                    std::fs::create_dir_all(parent)?;
+                }
+                std::fs::write(&self.path, serde_json::to_vec_pretty(&*fixtures)?)?;
+                Ok(response)
+            }
+        }
+    }
+}
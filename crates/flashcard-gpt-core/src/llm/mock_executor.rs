@@ -0,0 +1,65 @@
+use crate::error::CoreError;
+use crate::ext::mutex::MutexExt;
+use crate::llm::provider::{Completion, LlmProvider};
+use std::collections::BTreeMap;
+use std::path::Path;
+use std::sync::{Arc, Mutex};
+
+/// A step the mock was asked to execute.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct MockCall {
+    pub step: Arc<str>,
+    /// Values of the input parameters of the step.
+    pub inputs: BTreeMap<Arc<str>, Arc<str>>,
+}
+
+/// Answers steps with canned responses by step name instead of calling the LLM, and records what
+/// it was asked.
+#[derive(Debug, Clone, Default)]
+pub struct MockExecutor {
+    responses: Arc<BTreeMap<Arc<str>, Arc<str>>>,
+    calls: Arc<Mutex<Vec<MockCall>>>,
+}
+
+impl MockExecutor {
+    /// Steps without a response fail like an LLM response without a body.
+    pub fn new<K, V>(responses: impl IntoIterator<Item = (K, V)>) -> Self
+    where
+        K: Into<Arc<str>>,
+        V: Into<Arc<str>>,
+    {
+        let responses = responses
+            .into_iter()
+            .map(|(step, response)| (step.into(), response.into()))
+            .collect();
+        Self {
+            responses: Arc::new(responses),
+            calls: Arc::default(),
+        }
+    }
+
+    pub fn calls(&self) -> Result<Vec<MockCall>, CoreError> {
+        Ok(self.calls.lock_sync()?.clone())
+    }
+
+    /// Responses from a JSON object of step names and responses at `path`.
+    pub fn from_file(path: impl AsRef<Path>) -> Result<Self, CoreError> {
+        let file = std::fs::read(path)?;
+        let responses: BTreeMap<Arc<str>, Arc<str>> = serde_json::from_slice(&file)?;
+        Ok(Self::new(responses))
+    }
+}
+
+impl LlmProvider for MockExecutor {
+    async fn complete(&self, completion: &Completion) -> Result<Option<String>, CoreError> {
+        self.calls.lock_sync()?.push(MockCall {
+            step: completion.step.clone(),
+            inputs: completion.inputs.clone(),
+        });
+
+        Ok(self
+            .responses
+            .get(&completion.step)
+            .map(|response| response.to_string()))
+    }
+}
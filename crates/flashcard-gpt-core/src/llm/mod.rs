@@ -1,2 +1,7 @@
+pub mod answer_grader;
+pub mod batch;
 pub mod card_generator_service;
 pub mod custom_executor;
+pub mod fixtures;
+pub mod mock_executor;
+pub mod provider;
@@ -0,0 +1,229 @@
+//! The LLMs the steps of a chain are sent to. A [`LlmProvider`] completes a single step whose
+//! templates are already rendered, see [`crate::llm::custom_executor`]:
+//!
+//! - [`openai::OpenAiProvider`] talks to the OpenAI API or to any server with a compatible
+//!   `/chat/completions` endpoint, like llama.cpp, vLLM or LM Studio;
+//! - [`ollama::OllamaProvider`] talks to an Ollama server;
+//! - [`MockExecutor`] answers with canned responses by step name.
+//!
+//! [`AnyProvider`] picks one of them at runtime, and [`LlmChoice`] names a provider and a model, as
+//! a user or a chain selects them.
+
+pub mod ollama;
+pub mod openai;
+
+use crate::error::CoreError;
+use crate::llm::mock_executor::MockExecutor;
+use crate::llm::provider::ollama::OllamaProvider;
+use crate::llm::provider::openai::OpenAiProvider;
+use reqwest::RequestBuilder;
+use serde::de::DeserializeOwned;
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use std::fmt::{Display, Formatter};
+use std::future::Future;
+use std::str::FromStr;
+use std::sync::Arc;
+
+/// A step of a chain with its templates rendered.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Completion {
+    /// The name of the step.
+    pub step: Arc<str>,
+    /// The model to use instead of the default one of the provider.
+    pub model: Option<Arc<str>>,
+    pub system: String,
+    pub user: String,
+    /// Values of the input parameters of the step.
+    pub inputs: BTreeMap<Arc<str>, Arc<str>>,
+}
+
+impl Completion {
+    fn messages(&self) -> [ChatMessage<'_>; 2] {
+        [
+            ChatMessage {
+                role: "system",
+                content: &self.system,
+            },
+            ChatMessage {
+                role: "user",
+                content: &self.user,
+            },
+        ]
+    }
+}
+
+pub trait LlmProvider: Send + Sync {
+    /// The response of the LLM, `None` if it has no text.
+    fn complete(
+        &self,
+        completion: &Completion,
+    ) -> impl Future<Output = Result<Option<String>, CoreError>> + Send;
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum ProviderKind {
+    OpenAi,
+    Ollama,
+    Mock,
+}
+
+impl FromStr for ProviderKind {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.trim().to_lowercase().as_str() {
+            "openai" => Ok(Self::OpenAi),
+            "ollama" => Ok(Self::Ollama),
+            "mock" => Ok(Self::Mock),
+            other => Err(format!(
+                "Unknown LLM provider {other:?}, expected openai, ollama or mock"
+            )),
+        }
+    }
+}
+
+impl Display for ProviderKind {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        let name = match self {
+            Self::OpenAi => "openai",
+            Self::Ollama => "ollama",
+            Self::Mock => "mock",
+        };
+        write!(f, "{name}")
+    }
+}
+
+/// A provider and a model of it, written as `provider` or `provider:model`, like
+/// `ollama:llama3.1:8b`. Without a model the provider uses its default one.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct LlmChoice {
+    pub provider: ProviderKind,
+    #[serde(default)]
+    pub model: Option<Arc<str>>,
+}
+
+impl LlmChoice {
+    pub fn new(provider: ProviderKind) -> Self {
+        Self {
+            provider,
+            model: None,
+        }
+    }
+}
+
+impl FromStr for LlmChoice {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let (provider, model) = match s.trim().split_once(':') {
+            Some((provider, model)) => (provider, Some(model.trim())),
+            None => (s, None),
+        };
+        Ok(Self {
+            provider: provider.parse()?,
+            model: model.filter(|model| !model.is_empty()).map(Arc::from),
+        })
+    }
+}
+
+impl Display for LlmChoice {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}", self.provider)?;
+        if let Some(model) = &self.model {
+            write!(f, ":{model}")?;
+        }
+        Ok(())
+    }
+}
+
+#[derive(Debug, Clone)]
+pub enum AnyProvider {
+    OpenAi(OpenAiProvider),
+    Ollama(OllamaProvider),
+    Mock(MockExecutor),
+}
+
+impl AnyProvider {
+    pub fn kind(&self) -> ProviderKind {
+        match self {
+            Self::OpenAi(_) => ProviderKind::OpenAi,
+            Self::Ollama(_) => ProviderKind::Ollama,
+            Self::Mock(_) => ProviderKind::Mock,
+        }
+    }
+}
+
+impl LlmProvider for AnyProvider {
+    async fn complete(&self, completion: &Completion) -> Result<Option<String>, CoreError> {
+        match self {
+            Self::OpenAi(provider) => provider.complete(completion).await,
+            Self::Ollama(provider) => provider.complete(completion).await,
+            Self::Mock(provider) => provider.complete(completion).await,
+        }
+    }
+}
+
+impl From<OpenAiProvider> for AnyProvider {
+    fn from(value: OpenAiProvider) -> Self {
+        Self::OpenAi(value)
+    }
+}
+
+impl From<OllamaProvider> for AnyProvider {
+    fn from(value: OllamaProvider) -> Self {
+        Self::Ollama(value)
+    }
+}
+
+impl From<MockExecutor> for AnyProvider {
+    fn from(value: MockExecutor) -> Self {
+        Self::Mock(value)
+    }
+}
+
+#[derive(Debug, Serialize)]
+struct ChatMessage<'a> {
+    role: &'static str,
+    content: &'a str,
+}
+
+/// Sends `request` and reads the JSON body of a successful response.
+async fn send_json<T: DeserializeOwned>(request: RequestBuilder) -> Result<T, CoreError> {
+    let response = request.send().await?;
+    let status = response.status();
+    if !status.is_success() {
+        let body = response.text().await.unwrap_or_default();
+        return Err(CoreError::LlmProviderError(Arc::from(format!(
+            "{status}: {body}"
+        ))));
+    }
+    Ok(response.json().await?)
+}
+
+/// The value of the environment variable `name` unless it is unset or blank.
+fn env(name: &str) -> Option<Arc<str>> {
+    std::env::var(name)
+        .ok()
+        .filter(|value| !value.trim().is_empty())
+        .map(Arc::from)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_llm_choice() {
+        let choice = "ollama:llama3.1:8b".parse::<LlmChoice>().unwrap();
+        assert_eq!(choice.provider, ProviderKind::Ollama);
+        assert_eq!(choice.model.as_deref(), Some("llama3.1:8b"));
+        assert_eq!(choice.to_string(), "ollama:llama3.1:8b");
+
+        let choice = " OpenAI ".parse::<LlmChoice>().unwrap();
+        assert_eq!(choice, LlmChoice::new(ProviderKind::OpenAi));
+        assert_eq!("mock:".parse::<LlmChoice>().unwrap().model, None);
+        assert!("claude".parse::<LlmChoice>().is_err());
+    }
+}
@@ -0,0 +1,75 @@
+use crate::error::CoreError;
+use crate::llm::provider::{env, send_json, ChatMessage, Completion, LlmProvider};
+use serde::{Deserialize, Serialize};
+use std::sync::Arc;
+
+pub const DEFAULT_BASE_URL: &str = "http://localhost:11434";
+pub const DEFAULT_MODEL: &str = "llama3.1";
+
+/// Chat completions of an Ollama server.
+#[derive(Debug, Clone)]
+pub struct OllamaProvider {
+    client: reqwest::Client,
+    /// The URL the `/api/chat` path is appended to.
+    pub base_url: Arc<str>,
+    /// The model of completions that don't name one.
+    pub model: Arc<str>,
+}
+
+impl OllamaProvider {
+    pub fn new(base_url: impl Into<Arc<str>>, model: impl Into<Arc<str>>) -> Self {
+        Self {
+            client: reqwest::Client::new(),
+            base_url: base_url.into(),
+            model: model.into(),
+        }
+    }
+
+    /// The server at `OLLAMA_BASE_URL` with the default model in `OLLAMA_MODEL`, either is
+    /// enough, `None` if both are unset.
+    pub fn from_env() -> Option<Self> {
+        let base_url = env("OLLAMA_BASE_URL");
+        let model = env("OLLAMA_MODEL");
+        if base_url.is_none() && model.is_none() {
+            return None;
+        }
+        Some(Self::new(
+            base_url.unwrap_or_else(|| Arc::from(DEFAULT_BASE_URL)),
+            model.unwrap_or_else(|| Arc::from(DEFAULT_MODEL)),
+        ))
+    }
+}
+
+#[derive(Debug, Serialize)]
+struct Request<'a> {
+    model: &'a str,
+    messages: [ChatMessage<'a>; 2],
+    stream: bool,
+}
+
+#[derive(Debug, Deserialize)]
+struct Response {
+    message: Option<ResponseMessage>,
+}
+
+#[derive(Debug, Deserialize)]
+struct ResponseMessage {
+    content: String,
+}
+
+impl LlmProvider for OllamaProvider {
+    async fn complete(&self, completion: &Completion) -> Result<Option<String>, CoreError> {
+        let url = format!("{}/api/chat", self.base_url.trim_end_matches('/'));
+        let request = self.client.post(url).json(&Request {
+            model: completion.model.as_deref().unwrap_or(&self.model),
+            messages: completion.messages(),
+            stream: false,
+        });
+
+        let response: Response = send_json(request).await?;
+        Ok(response
+            .message
+            .map(|message| message.content)
+            .filter(|content| !content.is_empty()))
+    }
+}
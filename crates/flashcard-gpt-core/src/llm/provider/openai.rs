@@ -0,0 +1,101 @@
+use crate::error::CoreError;
+use crate::llm::provider::{env, send_json, ChatMessage, Completion, LlmProvider};
+use serde::{Deserialize, Serialize};
+use std::fmt::{Debug, Formatter};
+use std::sync::Arc;
+
+pub const DEFAULT_BASE_URL: &str = "https://api.openai.com/v1";
+pub const DEFAULT_MODEL: &str = "chatgpt-4o-latest";
+
+/// Chat completions of the OpenAI API, or of any server compatible with it.
+#[derive(Clone)]
+pub struct OpenAiProvider {
+    client: reqwest::Client,
+    /// The URL the `/chat/completions` path is appended to.
+    pub base_url: Arc<str>,
+    pub api_key: Option<Arc<str>>,
+    /// The model of completions that don't name one.
+    pub model: Arc<str>,
+}
+
+impl Debug for OpenAiProvider {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        // the API key is left out
+        f.debug_struct("OpenAiProvider")
+            .field("base_url", &self.base_url)
+            .field("model", &self.model)
+            .finish()
+    }
+}
+
+impl OpenAiProvider {
+    pub fn new(
+        base_url: impl Into<Arc<str>>,
+        api_key: Option<Arc<str>>,
+        model: impl Into<Arc<str>>,
+    ) -> Self {
+        Self {
+            client: reqwest::Client::new(),
+            base_url: base_url.into(),
+            api_key,
+            model: model.into(),
+        }
+    }
+
+    /// The provider at `OPENAI_BASE_URL` with the key in `OPENAI_API_KEY` and the default model
+    /// in `OPENAI_MODEL`. A local server doesn't need a key, so either of the first two is
+    /// enough, `None` if both are unset.
+    pub fn from_env() -> Option<Self> {
+        let base_url = env("OPENAI_BASE_URL");
+        let api_key = env("OPENAI_API_KEY");
+        if base_url.is_none() && api_key.is_none() {
+            return None;
+        }
+        Some(Self::new(
+            base_url.unwrap_or_else(|| Arc::from(DEFAULT_BASE_URL)),
+            api_key,
+            env("OPENAI_MODEL").unwrap_or_else(|| Arc::from(DEFAULT_MODEL)),
+        ))
+    }
+}
+
+#[derive(Debug, Serialize)]
+struct Request<'a> {
+    model: &'a str,
+    messages: [ChatMessage<'a>; 2],
+}
+
+#[derive(Debug, Deserialize)]
+struct Response {
+    choices: Vec<Choice>,
+}
+
+#[derive(Debug, Deserialize)]
+struct Choice {
+    message: ResponseMessage,
+}
+
+#[derive(Debug, Deserialize)]
+struct ResponseMessage {
+    content: Option<String>,
+}
+
+impl LlmProvider for OpenAiProvider {
+    async fn complete(&self, completion: &Completion) -> Result<Option<String>, CoreError> {
+        let url = format!("{}/chat/completions", self.base_url.trim_end_matches('/'));
+        let mut request = self.client.post(url).json(&Request {
+            model: completion.model.as_deref().unwrap_or(&self.model),
+            messages: completion.messages(),
+        });
+        if let Some(api_key) = &self.api_key {
+            request = request.bearer_auth(api_key);
+        }
+
+        let response: Response = send_json(request).await?;
+        Ok(response
+            .choices
+            .into_iter()
+            .next()
+            .and_then(|choice| choice.message.content))
+    }
+}
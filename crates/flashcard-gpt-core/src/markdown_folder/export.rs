@@ -0,0 +1,246 @@
+//! Export of decks to a folder of Markdown files, see the [format](super). Every deck is a folder
+//! named after its path, with a file for each of its cards and card groups. Files that are already
+//! in the folder for the same card or card group are overwritten in place, wherever they were
+//! moved to, and keep the fields of their front matter this format doesn't know. Media, choices
+//! and the history are not exported.
+
+use crate::anki::DECK_SEPARATOR;
+use crate::error::CoreError;
+use crate::markdown_folder::{
+    markdown_files, CardText, FrontMatter, Kind, MarkdownFile, EXTENSION,
+};
+use crate::model::card::Card;
+use crate::model::deck::selected_decks;
+use crate::reexports::db::sql::Thing;
+use crate::store::any::AnyStorage;
+use crate::store::{DeckStore, Storage};
+use bon::Builder;
+use std::collections::{BTreeMap, HashMap, HashSet};
+use std::fmt::{Debug, Display, Formatter};
+use std::path::{Path, PathBuf};
+
+#[derive(Debug, Clone, Default, Builder)]
+pub struct ExportOptions {
+    /// Only export this deck and its subdecks, all decks of the user without it.
+    pub deck: Option<Thing>,
+}
+
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct ExportReport {
+    pub decks: usize,
+    pub cards: usize,
+    pub card_groups: usize,
+    /// Cards without a front, the importer would reject them. A card group with such a card is
+    /// skipped along with all of its cards.
+    pub skipped_cards: usize,
+}
+
+impl Display for ExportReport {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        writeln!(f, "Decks: {}", self.decks)?;
+        writeln!(f, "Cards: {}", self.cards)?;
+        write!(f, "Card groups: {}", self.card_groups)?;
+        if self.skipped_cards > 0 {
+            write!(f, "\nSkipped cards without a front: {}", self.skipped_cards)?;
+        }
+        Ok(())
+    }
+}
+
+#[derive(Clone)]
+pub struct MarkdownExporter<S: Storage = AnyStorage> {
+    pub decks: S::Decks,
+}
+
+impl<S: Storage> Debug for MarkdownExporter<S> {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        write!(f, "MarkdownExporter")
+    }
+}
+
+impl<S: Storage> MarkdownExporter<S> {
+    pub fn new(decks: S::Decks) -> Self {
+        Self { decks }
+    }
+
+    /// Writes the decks of `user` to `dir`, see the [module](self) documentation.
+    pub async fn export(
+        &self,
+        user: impl Into<Thing>,
+        dir: impl AsRef<Path>,
+        options: ExportOptions,
+    ) -> Result<ExportReport, CoreError> {
+        let dir = dir.as_ref();
+        let user = user.into();
+        let all_decks = self.decks.list_by_user_id(user.clone()).await?;
+        let decks = selected_decks(&all_decks, options.deck.as_ref())?;
+
+        tokio::fs::create_dir_all(dir).await?;
+        let (existing, taken) = existing_files(dir).await?;
+        let mut export = Export {
+            existing,
+            taken,
+            report: ExportReport::default(),
+        };
+
+        for deck in decks {
+            export.report.decks += 1;
+            let titles = deck.path(&all_decks);
+            let folder = titles.iter().fold(dir.to_path_buf(), |folder, title| {
+                folder.join(file_name(title))
+            });
+            tokio::fs::create_dir_all(&folder).await?;
+            let deck_path = titles.join(DECK_SEPARATOR);
+
+            for card in self.decks.list_cards(user.clone(), deck.id.clone()).await? {
+                let Some(text) = card_text(&card, false) else {
+                    export.report.skipped_cards += 1;
+                    continue;
+                };
+                let front_matter = FrontMatter {
+                    id: Some(card.id.to_string()),
+                    kind: Kind::Card,
+                    title: Some(card.title.to_string()),
+                    deck: Some(deck_path.clone()),
+                    tags: card.tags.iter().map(|tag| tag.name.to_string()).collect(),
+                    difficulty: Some(card.difficulty),
+                    importance: Some(card.importance),
+                    cards: vec![],
+                    extra: BTreeMap::new(),
+                };
+                export.write(&folder, front_matter, vec![text]).await?;
+                export.report.cards += 1;
+            }
+
+            let card_groups = self
+                .decks
+                .list_card_groups(user.clone(), deck.id.clone())
+                .await?;
+            for card_group in card_groups {
+                let texts = card_group
+                    .cards
+                    .iter()
+                    .map(|card| card_text(card, true))
+                    .collect::<Option<Vec<_>>>();
+                let Some(texts) = texts.filter(|texts| !texts.is_empty()) else {
+                    export.report.skipped_cards += card_group.cards.len();
+                    continue;
+                };
+                let front_matter = FrontMatter {
+                    id: Some(card_group.id.to_string()),
+                    kind: Kind::Group,
+                    title: Some(card_group.title.to_string()),
+                    deck: Some(deck_path.clone()),
+                    tags: card_group
+                        .tags
+                        .iter()
+                        .map(|tag| tag.name.to_string())
+                        .collect(),
+                    difficulty: Some(card_group.difficulty),
+                    importance: Some(card_group.importance),
+                    cards: card_group
+                        .cards
+                        .iter()
+                        .map(|card| card.id.to_string())
+                        .collect(),
+                    extra: BTreeMap::new(),
+                };
+                export.write(&folder, front_matter, texts).await?;
+                export.report.card_groups += 1;
+            }
+        }
+
+        Ok(export.report)
+    }
+}
+
+/// Files by the id in their front matter, with the front matter.
+type ExistingFiles = HashMap<String, (PathBuf, FrontMatter)>;
+
+struct Export {
+    existing: ExistingFiles,
+    /// Paths of the files already in the folder or written by this export.
+    taken: HashSet<PathBuf>,
+    report: ExportReport,
+}
+
+impl Export {
+    async fn write(
+        &mut self,
+        folder: &Path,
+        mut front_matter: FrontMatter,
+        cards: Vec<CardText>,
+    ) -> Result<(), CoreError> {
+        let id = front_matter.id.clone().unwrap_or_default();
+        let path = match self.existing.get(&id) {
+            Some((path, existing)) => {
+                front_matter.extra = existing.extra.clone();
+                path.clone()
+            }
+            None => {
+                let title = front_matter.title.as_deref().unwrap_or_default();
+                self.free_path(folder, &slug::slugify(title))
+            }
+        };
+        self.taken.insert(path.clone());
+
+        let file = MarkdownFile {
+            front_matter,
+            cards,
+        };
+        tokio::fs::write(&path, file.render()?).await?;
+        Ok(())
+    }
+
+    /// A path in `folder` for a new file named after `stem`, numbered when it is taken.
+    fn free_path(&self, folder: &Path, stem: &str) -> PathBuf {
+        let stem = if stem.is_empty() { "card" } else { stem };
+        (1..)
+            .map(|number| {
+                let name = match number {
+                    1 => format!("{stem}.{EXTENSION}"),
+                    _ => format!("{stem}-{number}.{EXTENSION}"),
+                };
+                folder.join(name)
+            })
+            .find(|path| !self.taken.contains(path))
+            .expect("the numbers run out after the paths")
+    }
+}
+
+/// The sections of `card`, `None` for a card without a front.
+fn card_text(card: &Card, with_title: bool) -> Option<CardText> {
+    let front = card
+        .front
+        .clone()
+        .filter(|front| !front.trim().is_empty())?;
+    Some(CardText {
+        title: with_title.then(|| card.title.clone()),
+        front: Some(front),
+        back: card.back.clone(),
+        hints: card.hints.clone(),
+    })
+}
+
+/// The files of `dir` with an id by their ids, and the paths of all its files.
+async fn existing_files(dir: &Path) -> Result<(ExistingFiles, HashSet<PathBuf>), CoreError> {
+    let mut files = HashMap::new();
+    let mut paths = HashSet::new();
+    for path in markdown_files(dir).await? {
+        paths.insert(path.clone());
+        let text = tokio::fs::read_to_string(&path).await?;
+        // a file that doesn't parse is left alone, a new file is written next to it
+        let Ok(file) = MarkdownFile::parse(&text) else {
+            continue;
+        };
+        if let Some(id) = file.front_matter.id.clone() {
+            files.insert(id, (path, file.front_matter));
+        }
+    }
+    Ok((files, paths))
+}
+
+/// A deck title as a folder name.
+fn file_name(title: &str) -> String {
+    title.replace(['/', '\\'], "-")
+}
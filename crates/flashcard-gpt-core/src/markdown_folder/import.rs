@@ -0,0 +1,522 @@
+//! Import of a folder of Markdown files, see the [format](super). Every file is imported on its
+//! own: a file that fails is reported and skipped, the other files are still imported. Cards and
+//! card groups with an id in their front matter are updated, the others are created and their ids
+//! are written back to their files. Decks missing from the user are created. A card moved to
+//! another deck is added to it and stays in the deck it was in.
+
+use crate::anki::DECK_SEPARATOR;
+use crate::error::CoreError;
+use crate::markdown_folder::{
+    invalid, markdown_files, replace_front_matter, CardText, Kind, MarkdownFile,
+};
+use crate::model::card::{title_from_front, CreateCard, UpdateCard};
+use crate::model::card_group::{CreateCardGroup, UpdateCardGroup};
+use crate::model::deck::{CreateDeck, Deck};
+use crate::model::deck_card::CreateDeckCard;
+use crate::model::deck_card_group::CreateDeckCardGroup;
+use crate::reexports::db::sql::{thing, Thing};
+use crate::store::any::AnyStorage;
+use crate::store::{CardGroupStore, CardStore, DeckStore, Storage, TagStore};
+use bon::Builder;
+use std::collections::{HashMap, HashSet};
+use std::fmt::{Debug, Display, Formatter};
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+
+const MAX_LEVEL: u8 = 10;
+
+#[derive(Debug, Clone, Builder)]
+pub struct ImportOptions {
+    /// The deck of the files at the root of the folder without a deck in their front matter.
+    pub deck: Option<Thing>,
+
+    /// Write the ids of new cards and card groups back to their files.
+    #[builder(default = true)]
+    pub write_ids: bool,
+}
+
+impl Default for ImportOptions {
+    fn default() -> Self {
+        Self::builder().build()
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct FileError {
+    pub path: PathBuf,
+    pub message: Arc<str>,
+}
+
+impl Display for FileError {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}: {}", self.path.display(), self.message)
+    }
+}
+
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct ImportReport {
+    pub files: usize,
+    pub decks: usize,
+    pub created_cards: usize,
+    pub updated_cards: usize,
+    pub created_card_groups: usize,
+    pub updated_card_groups: usize,
+    pub errors: Vec<FileError>,
+}
+
+impl Display for ImportReport {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        writeln!(f, "Files: {}", self.files)?;
+        writeln!(f, "Decks: {}", self.decks)?;
+        writeln!(
+            f,
+            "Cards: {} created, {} updated",
+            self.created_cards, self.updated_cards
+        )?;
+        write!(
+            f,
+            "Card groups: {} created, {} updated",
+            self.created_card_groups, self.updated_card_groups
+        )?;
+        if !self.errors.is_empty() {
+            write!(f, "\nSkipped files:")?;
+            for error in &self.errors {
+                write!(f, "\n  {error}")?;
+            }
+        }
+        Ok(())
+    }
+}
+
+#[derive(Clone)]
+pub struct MarkdownImporter<S: Storage = AnyStorage> {
+    pub cards: S::Cards,
+    pub card_groups: S::CardGroups,
+    pub decks: S::Decks,
+    pub tags: S::Tags,
+}
+
+impl<S: Storage> Debug for MarkdownImporter<S> {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        write!(f, "MarkdownImporter")
+    }
+}
+
+impl<S: Storage> MarkdownImporter<S> {
+    pub fn new(
+        cards: S::Cards,
+        card_groups: S::CardGroups,
+        decks: S::Decks,
+        tags: S::Tags,
+    ) -> Self {
+        Self {
+            cards,
+            card_groups,
+            decks,
+            tags,
+        }
+    }
+
+    /// Imports the Markdown files of `dir` and its subfolders, see the [module](self)
+    /// documentation.
+    pub async fn import(
+        &self,
+        user: impl Into<Thing>,
+        dir: impl AsRef<Path>,
+        options: ImportOptions,
+    ) -> Result<ImportReport, CoreError> {
+        let dir = dir.as_ref();
+        let user = user.into();
+        let decks = self.decks.list_by_user_id(user.clone()).await?;
+        let mut import = Import {
+            importer: self,
+            user,
+            decks: decks
+                .iter()
+                .map(|deck| (deck.path(&decks).join(DECK_SEPARATOR), deck.id.clone()))
+                .collect(),
+            titles: unique_titles(&decks),
+            deck_cards: HashMap::new(),
+            deck_card_groups: HashMap::new(),
+            report: ImportReport::default(),
+        };
+
+        for path in markdown_files(dir).await? {
+            import.report.files += 1;
+            let folder = path
+                .parent()
+                .and_then(|parent| parent.strip_prefix(dir).ok())
+                .map(folder_deck)
+                .filter(|deck| !deck.is_empty());
+            if let Err(err) = import.file(&path, folder, &options).await {
+                import.report.errors.push(FileError {
+                    path: path.strip_prefix(dir).unwrap_or(&path).to_path_buf(),
+                    message: Arc::from(err.to_string()),
+                });
+            }
+        }
+
+        Ok(import.report)
+    }
+}
+
+struct Import<'a, S: Storage> {
+    importer: &'a MarkdownImporter<S>,
+    user: Thing,
+    /// Decks by their paths, `Parent::Child`.
+    decks: HashMap<String, Thing>,
+    /// Decks by their titles, for the titles no other deck of the user has.
+    titles: HashMap<String, Thing>,
+    /// Cards of the decks, loaded on first use.
+    deck_cards: HashMap<Thing, HashSet<Thing>>,
+    deck_card_groups: HashMap<Thing, HashSet<Thing>>,
+    report: ImportReport,
+}
+
+impl<S: Storage> Import<'_, S> {
+    async fn file(
+        &mut self,
+        path: &Path,
+        folder: Option<String>,
+        options: &ImportOptions,
+    ) -> Result<(), CoreError> {
+        let text = tokio::fs::read_to_string(path).await?;
+        let file = MarkdownFile::parse(&text)?;
+        let mut front_matter = file.front_matter.clone();
+        validate(&file)?;
+
+        let deck = match (front_matter.deck.as_deref(), folder, &options.deck) {
+            (Some(name), _, _) => self.deck(name).await?,
+            (None, Some(folder), _) => self.deck(&folder).await?,
+            (None, None, Some(deck)) => deck.clone(),
+            (None, None, None) => return Err(invalid("the file has no deck")),
+        };
+        let tags = self.tags(&front_matter.tags).await?;
+        let importance = front_matter.importance.unwrap_or_default();
+        let difficulty = front_matter.difficulty.unwrap_or_default();
+        let id = front_matter.id.as_deref().map(parse_id).transpose()?;
+
+        match front_matter.kind {
+            Kind::Card => {
+                let card = &file.cards[0];
+                let title = front_matter.title.as_deref().map(Arc::from);
+                let dto = self.card_dto(card, title, importance, difficulty, tags);
+                let id = self.card(id, dto).await?;
+                self.relate_card(&deck, &id).await?;
+                front_matter.id = Some(id.to_string());
+            }
+            Kind::Group => {
+                let mut cards = vec![];
+                for (index, card) in file.cards.iter().enumerate() {
+                    let id = front_matter
+                        .cards
+                        .get(index)
+                        .map(String::as_str)
+                        .map(parse_id)
+                        .transpose()?;
+                    let dto = self.card_dto(card, None, importance, difficulty, vec![]);
+                    cards.push(self.card(id, dto).await?);
+                }
+                let title = front_matter
+                    .title
+                    .as_deref()
+                    .map_or_else(|| file_title(path), Arc::from);
+                let id = self
+                    .card_group(id, title, importance, difficulty, cards.clone(), tags)
+                    .await?;
+                self.relate_card_group(&deck, &id).await?;
+                front_matter.id = Some(id.to_string());
+                front_matter.cards = cards.iter().map(Thing::to_string).collect();
+            }
+        }
+
+        if options.write_ids && front_matter != file.front_matter {
+            tokio::fs::write(path, replace_front_matter(&text, &front_matter)?).await?;
+        }
+        Ok(())
+    }
+
+    fn card_dto(
+        &self,
+        card: &CardText,
+        title: Option<Arc<str>>,
+        importance: u8,
+        difficulty: u8,
+        tags: Vec<Thing>,
+    ) -> CreateCard {
+        let front = card.front.clone().unwrap_or_default();
+        CreateCard {
+            user: self.user.clone(),
+            title: title
+                .or_else(|| card.title.clone())
+                .unwrap_or_else(|| title_from_front(&front)),
+            front: Some(front),
+            back: card.back.clone(),
+            hints: card.hints.clone(),
+            difficulty,
+            importance,
+            data: None,
+            choices: None,
+            sibling: None,
+            reverse: false,
+            tags,
+        }
+    }
+
+    /// Updates the card `id` of the user, or creates it when there is none.
+    async fn card(&mut self, id: Option<Thing>, dto: CreateCard) -> Result<Thing, CoreError> {
+        let cards = &self.importer.cards;
+        if let Some(id) = id
+            && self.owns(cards.get_by_id(id.clone()).await.map(|card| card.user.id))?
+        {
+            let update = UpdateCard {
+                importance: Some(dto.importance),
+                difficulty: Some(dto.difficulty),
+                sibling: None,
+                annotations: None,
+                title: Some(dto.title),
+                front: dto.front,
+                back: dto.back,
+                hints: Some(dto.hints),
+                tags: Some(dto.tags),
+            };
+            cards.patch(id.clone(), update).await?;
+            self.report.updated_cards += 1;
+            return Ok(id);
+        }
+
+        let card = cards.create(dto).await?;
+        self.report.created_cards += 1;
+        Ok(card.id)
+    }
+
+    async fn card_group(
+        &mut self,
+        id: Option<Thing>,
+        title: Arc<str>,
+        importance: u8,
+        difficulty: u8,
+        cards: Vec<Thing>,
+        tags: Vec<Thing>,
+    ) -> Result<Thing, CoreError> {
+        let card_groups = &self.importer.card_groups;
+        if let Some(id) = id
+            && self.owns(
+                card_groups
+                    .get_by_id(id.clone())
+                    .await
+                    .map(|group| group.user.id),
+            )?
+        {
+            let update = UpdateCardGroup {
+                importance: Some(importance),
+                difficulty: Some(difficulty),
+                cards: Some(cards),
+                annotations: None,
+                title: Some(title),
+                tags: Some(tags),
+            };
+            card_groups.patch(id.clone(), update).await?;
+            self.report.updated_card_groups += 1;
+            return Ok(id);
+        }
+
+        let card_group = card_groups
+            .create(CreateCardGroup {
+                user: self.user.clone(),
+                title,
+                importance,
+                difficulty,
+                data: None,
+                cards,
+                tags,
+            })
+            .await?;
+        self.report.created_card_groups += 1;
+        Ok(card_group.id)
+    }
+
+    /// Whether the record that was looked up exists and belongs to the user. An id of another
+    /// user, or of a deleted record, is ignored and the record is created again.
+    fn owns(&self, owner: Result<Thing, CoreError>) -> Result<bool, CoreError> {
+        match owner {
+            Ok(owner) => Ok(owner == self.user),
+            Err(err) if err.is_not_found() => Ok(false),
+            Err(err) => Err(err),
+        }
+    }
+
+    async fn relate_card(&mut self, deck: &Thing, card: &Thing) -> Result<(), CoreError> {
+        let decks = &self.importer.decks;
+        if !self.deck_cards.contains_key(deck) {
+            let cards = decks.list_cards(self.user.clone(), deck.clone()).await?;
+            let ids = cards.into_iter().map(|card| card.id).collect();
+            self.deck_cards.insert(deck.clone(), ids);
+        }
+        let ids = self.deck_cards.get_mut(deck).expect("the deck was loaded");
+        if ids.insert(card.clone()) {
+            decks
+                .relate_card(CreateDeckCard {
+                    deck: deck.clone(),
+                    card: card.clone(),
+                })
+                .await?;
+        }
+        Ok(())
+    }
+
+    async fn relate_card_group(
+        &mut self,
+        deck: &Thing,
+        card_group: &Thing,
+    ) -> Result<(), CoreError> {
+        let decks = &self.importer.decks;
+        if !self.deck_card_groups.contains_key(deck) {
+            let groups = decks
+                .list_card_groups(self.user.clone(), deck.clone())
+                .await?;
+            let ids = groups.into_iter().map(|group| group.id).collect();
+            self.deck_card_groups.insert(deck.clone(), ids);
+        }
+        let ids = self
+            .deck_card_groups
+            .get_mut(deck)
+            .expect("the deck was loaded");
+        if ids.insert(card_group.clone()) {
+            decks
+                .relate_card_group(CreateDeckCardGroup {
+                    deck: deck.clone(),
+                    card_group: card_group.clone(),
+                })
+                .await?;
+        }
+        Ok(())
+    }
+
+    /// The deck with the path or the title `name`, created along with its parents when the user
+    /// has none.
+    async fn deck(&mut self, name: &str) -> Result<Thing, CoreError> {
+        if let Some(deck) = self.decks.get(name).or_else(|| self.titles.get(name)) {
+            return Ok(deck.clone());
+        }
+
+        let mut parent: Option<Thing> = None;
+        let mut path = String::new();
+        for title in name.split(DECK_SEPARATOR).map(str::trim) {
+            if title.is_empty() {
+                return Err(invalid(format!("the deck {name:?} has an empty title")));
+            }
+            if !path.is_empty() {
+                path.push_str(DECK_SEPARATOR);
+            }
+            path.push_str(title);
+
+            let deck = match self.decks.get(&path) {
+                Some(deck) => deck.clone(),
+                None => {
+                    let deck = self
+                        .importer
+                        .decks
+                        .create(CreateDeck {
+                            description: None,
+                            parent: parent.clone(),
+                            settings: None,
+                            tags: vec![],
+                            title: Arc::from(title),
+                            user: self.user.clone(),
+                        })
+                        .await?
+                        .id;
+                    self.report.decks += 1;
+                    self.decks.insert(path.clone(), deck.clone());
+                    deck
+                }
+            };
+            parent = Some(deck);
+        }
+
+        parent.ok_or_else(|| invalid("the deck has no title"))
+    }
+
+    async fn tags(&self, names: &[String]) -> Result<Vec<Thing>, CoreError> {
+        if names.is_empty() {
+            return Ok(vec![]);
+        }
+        let tags = self
+            .importer
+            .tags
+            .get_or_create_tags(
+                self.user.clone(),
+                names
+                    .iter()
+                    .map(|name| Arc::from(name.trim()))
+                    .collect::<Vec<_>>(),
+            )
+            .await?;
+        Ok(tags.into_iter().map(|tag| tag.id).collect())
+    }
+}
+
+fn validate(file: &MarkdownFile) -> Result<(), CoreError> {
+    let front_matter = &file.front_matter;
+    for (field, value) in [
+        ("difficulty", front_matter.difficulty),
+        ("importance", front_matter.importance),
+    ] {
+        if value.is_some_and(|value| value > MAX_LEVEL) {
+            return Err(invalid(format!(
+                "the {field} must be a number from 0 to {MAX_LEVEL}"
+            )));
+        }
+    }
+    if file.cards.is_empty() {
+        return Err(invalid("the file has no cards"));
+    }
+    if front_matter.kind == Kind::Card && file.cards.len() > 1 {
+        return Err(invalid("a card file has more than one card"));
+    }
+    for card in &file.cards {
+        if card.front.as_deref().map_or(true, str::is_empty) {
+            let name = card.title.as_deref().unwrap_or("the card");
+            return Err(invalid(format!("{name} has no front")));
+        }
+    }
+    Ok(())
+}
+
+fn parse_id(id: &str) -> Result<Thing, CoreError> {
+    thing(id).map_err(|_| invalid(format!("{id:?} is not a record id")))
+}
+
+/// Decks by their titles, for the titles no other deck has.
+fn unique_titles(decks: &[Deck]) -> HashMap<String, Thing> {
+    let mut titles = HashMap::<String, Option<Thing>>::new();
+    for deck in decks {
+        titles
+            .entry(deck.title.to_string())
+            .and_modify(|id| *id = None)
+            .or_insert_with(|| Some(deck.id.clone()));
+    }
+    titles
+        .into_iter()
+        .filter_map(|(title, id)| Some((title, id?)))
+        .collect()
+}
+
+/// The deck path of a folder, `Languages/Spanish` is `Languages::Spanish`.
+fn folder_deck(folder: &Path) -> String {
+    folder
+        .components()
+        .map(|component| component.as_os_str().to_string_lossy())
+        .collect::<Vec<_>>()
+        .join(DECK_SEPARATOR)
+}
+
+fn file_title(path: &Path) -> Arc<str> {
+    Arc::from(
+        path.file_stem()
+            .unwrap_or_default()
+            .to_string_lossy()
+            .as_ref(),
+    )
+}
@@ -0,0 +1,411 @@
+//! Decks as a folder of Markdown files, one file per card or card group, to keep them in git next
+//! to other notes. A card file has YAML front matter and a section for each side of the card:
+//!
+//! ```markdown
+//! ---
+//! id: card:x8vqkc4ttkp1f3p5bl8b
+//! title: hablar
+//! deck: Languages::Spanish
+//! tags: [verbs]
+//! difficulty: 3
+//! importance: 5
+//! ---
+//!
+//! ## Front
+//!
+//! **hablar**
+//!
+//! ## Back
+//!
+//! to speak
+//!
+//! ## Hints
+//!
+//! - an -ar verb
+//! ```
+//!
+//! Every field of the front matter is optional. A card without a title is named after the first
+//! line of its front, and a file without a deck goes to the deck named after its folder, like
+//! `Languages/Spanish/hablar.md`. Hints are the lines of their section, list markers are dropped.
+//! Only the `## Front`, `## Back` and `## Hints` headings start a section, any other heading is a
+//! part of the text, which is the same Markdown the bot renders.
+//!
+//! A card group has `kind: group` in its front matter and a `# Title` heading before the sections
+//! of each of its cards. Its `cards` are the ids of these cards, in order.
+//!
+//! The importer writes the ids of the cards it creates back to the front matter, so that the next
+//! import of the same file updates the cards instead of creating them again.
+
+pub mod export;
+pub mod import;
+
+use crate::error::CoreError;
+use markdown::mdast::Node;
+use markdown::{Constructs, ParseOptions};
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+
+pub const EXTENSION: &str = "md";
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Kind {
+    #[default]
+    Card,
+    Group,
+}
+
+impl Kind {
+    fn is_card(&self) -> bool {
+        *self == Kind::Card
+    }
+}
+
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct FrontMatter {
+    /// Record id of the card or the card group, set once it is imported.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub id: Option<String>,
+    #[serde(default, skip_serializing_if = "Kind::is_card")]
+    pub kind: Kind,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub title: Option<String>,
+    /// Title or path of the deck, `Parent::Child`.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub deck: Option<String>,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub tags: Vec<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub difficulty: Option<u8>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub importance: Option<u8>,
+    /// Record ids of the cards of a group, in order.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub cards: Vec<String>,
+    /// Fields this format doesn't know, kept when the front matter is written back.
+    #[serde(flatten)]
+    pub extra: BTreeMap<String, serde_yaml::Value>,
+}
+
+/// The sections of a card, and the title of its heading in a group.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct CardText {
+    pub title: Option<Arc<str>>,
+    pub front: Option<Arc<str>>,
+    pub back: Option<Arc<str>>,
+    pub hints: Vec<Arc<str>>,
+}
+
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct MarkdownFile {
+    pub front_matter: FrontMatter,
+    /// A single card unless the file is a card group.
+    pub cards: Vec<CardText>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Section {
+    Front,
+    Back,
+    Hints,
+}
+
+impl Section {
+    fn from_heading(text: &str) -> Option<Section> {
+        match text.trim().to_lowercase().as_str() {
+            "front" => Some(Section::Front),
+            "back" => Some(Section::Back),
+            "hints" => Some(Section::Hints),
+            _ => None,
+        }
+    }
+}
+
+/// A heading that starts a card or a section, with the byte range of the heading line.
+struct Boundary {
+    section: Option<Section>,
+    text: String,
+    start: usize,
+    end: usize,
+}
+
+/// The dialect of the bot, see `MarkdownFormatter`, with front matter.
+fn parse_options() -> ParseOptions {
+    ParseOptions {
+        constructs: Constructs {
+            frontmatter: true,
+            math_flow: true,
+            math_text: true,
+            ..Constructs::gfm()
+        },
+        ..ParseOptions::gfm()
+    }
+}
+
+fn to_mdast(text: &str) -> Result<Node, CoreError> {
+    markdown::to_mdast(text, &parse_options()).map_err(|err| invalid(format!("{err:?}")))
+}
+
+impl MarkdownFile {
+    pub fn parse(text: &str) -> Result<Self, CoreError> {
+        let ast = to_mdast(text)?;
+        let mut front_matter = FrontMatter::default();
+        let mut headings = vec![];
+        for node in ast.children().into_iter().flatten() {
+            match node {
+                Node::Yaml(yaml) if !yaml.value.trim().is_empty() => {
+                    front_matter = serde_yaml::from_str(&yaml.value)?;
+                }
+                Node::Heading(heading) if heading.depth <= 2 => {
+                    let Some(position) = &heading.position else {
+                        continue;
+                    };
+                    let text = node.to_string();
+                    headings.push(Boundary {
+                        section: Section::from_heading(&text).filter(|_| heading.depth == 2),
+                        text: text.trim().to_string(),
+                        start: position.start.offset,
+                        end: position.end.offset,
+                    });
+                }
+                _ => {}
+            }
+        }
+
+        let group = front_matter.kind == Kind::Group;
+        headings.retain(|heading| heading.section.is_some() || group);
+        let mut cards = Vec::<CardText>::new();
+        for (index, heading) in headings.iter().enumerate() {
+            let end = headings
+                .get(index + 1)
+                .map_or(text.len(), |next| next.start);
+            let content = text[heading.end..end].trim();
+            let Some(section) = heading.section else {
+                cards.push(CardText {
+                    title: Some(Arc::from(heading.text.as_str())),
+                    ..CardText::default()
+                });
+                continue;
+            };
+            if cards.is_empty() {
+                if group {
+                    return Err(invalid(format!(
+                        "the section {:?} is before the heading of the first card",
+                        heading.text
+                    )));
+                }
+                cards.push(CardText::default());
+            }
+            let card = cards.last_mut().expect("a card was pushed");
+            let duplicate = match section {
+                Section::Front => card.front.replace(Arc::from(content)).is_some(),
+                Section::Back => card.back.replace(Arc::from(content)).is_some(),
+                Section::Hints => {
+                    let hints = std::mem::replace(&mut card.hints, hints(content));
+                    !hints.is_empty()
+                }
+            };
+            if duplicate {
+                return Err(invalid(format!("the section {:?} repeats", heading.text)));
+            }
+        }
+
+        Ok(Self {
+            front_matter,
+            cards,
+        })
+    }
+
+    pub fn render(&self) -> Result<String, CoreError> {
+        let mut text = front_matter_block(&self.front_matter)?;
+        let group = self.front_matter.kind == Kind::Group;
+        for card in &self.cards {
+            if group {
+                text.push_str(&format!(
+                    "\n# {}\n",
+                    card.title.as_deref().unwrap_or_default()
+                ));
+            }
+            text.push_str(&format!(
+                "\n## Front\n\n{}\n",
+                card.front.as_deref().unwrap_or_default()
+            ));
+            if let Some(back) = card.back.as_deref().filter(|back| !back.is_empty()) {
+                text.push_str(&format!("\n## Back\n\n{back}\n"));
+            }
+            if !card.hints.is_empty() {
+                text.push_str("\n## Hints\n\n");
+                for hint in &card.hints {
+                    text.push_str(&format!("- {hint}\n"));
+                }
+            }
+        }
+        Ok(text)
+    }
+}
+
+/// `text` with its front matter replaced by `front_matter`, the rest of the file as it is.
+pub fn replace_front_matter(text: &str, front_matter: &FrontMatter) -> Result<String, CoreError> {
+    let block = front_matter_block(front_matter)?;
+    let ast = to_mdast(text)?;
+    let yaml = ast
+        .children()
+        .into_iter()
+        .flatten()
+        .find(|node| matches!(node, Node::Yaml(_)))
+        .and_then(Node::position);
+    Ok(match yaml {
+        Some(position) => {
+            let rest = text[position.end.offset..].trim_start_matches(['\r', '\n']);
+            format!("{}{block}\n{rest}", &text[..position.start.offset])
+        }
+        None => format!("{block}\n{text}"),
+    })
+}
+
+fn front_matter_block(front_matter: &FrontMatter) -> Result<String, CoreError> {
+    Ok(format!(
+        "---\n{}---\n",
+        serde_yaml::to_string(front_matter)?
+    ))
+}
+
+/// The lines of a hints section without their list markers.
+fn hints(content: &str) -> Vec<Arc<str>> {
+    content
+        .lines()
+        .map(|line| {
+            let line = line.trim();
+            ["- ", "* ", "+ "]
+                .into_iter()
+                .find_map(|marker| line.strip_prefix(marker))
+                .unwrap_or(line)
+                .trim()
+        })
+        .filter(|line| !line.is_empty())
+        .map(Arc::from)
+        .collect()
+}
+
+/// The Markdown files of `dir` and its subfolders, sorted by path.
+pub(crate) async fn markdown_files(dir: &Path) -> Result<Vec<PathBuf>, CoreError> {
+    let mut files = vec![];
+    let mut dirs = vec![dir.to_path_buf()];
+    while let Some(dir) = dirs.pop() {
+        let mut entries = tokio::fs::read_dir(&dir).await?;
+        while let Some(entry) = entries.next_entry().await? {
+            let path = entry.path();
+            let hidden = entry.file_name().to_string_lossy().starts_with('.');
+            if hidden {
+                continue;
+            }
+            if entry.file_type().await?.is_dir() {
+                dirs.push(path);
+            } else if path
+                .extension()
+                .is_some_and(|extension| extension == EXTENSION)
+            {
+                files.push(path);
+            }
+        }
+    }
+    files.sort();
+    Ok(files)
+}
+
+fn invalid(message: impl Into<String>) -> CoreError {
+    CoreError::InvalidMarkdown(Arc::from(message.into()))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const CARD: &str = "---
+id: card:1
+title: hablar
+tags: [verbs]
+difficulty: 3
+source: notes
+---
+
+## Front
+
+**hablar**
+
+## Back
+
+to speak
+
+### Examples
+
+```text
+## Front
+```
+
+## Hints
+
+- an -ar verb
+* starts with h
+";
+
+    #[test]
+    fn test_parse_card() {
+        let file = MarkdownFile::parse(CARD).unwrap();
+        assert_eq!(file.front_matter.id.as_deref(), Some("card:1"));
+        assert_eq!(file.front_matter.tags, vec!["verbs".to_string()]);
+        assert_eq!(file.front_matter.difficulty, Some(3));
+        assert!(file.front_matter.extra.contains_key("source"));
+        assert_eq!(file.cards.len(), 1);
+
+        let card = &file.cards[0];
+        assert_eq!(card.front.as_deref(), Some("**hablar**"));
+        // other headings and code blocks are a part of the section
+        let back = card.back.as_deref().unwrap();
+        assert!(back.starts_with("to speak\n\n### Examples"));
+        assert!(back.ends_with("## Front\n```"));
+        let hints = card.hints.iter().map(|hint| &**hint).collect::<Vec<_>>();
+        assert_eq!(hints, vec!["an -ar verb", "starts with h"]);
+    }
+
+    #[test]
+    fn test_parse_group() {
+        let text = "---\nkind: group\ntitle: Common words\n---\n\n# perro\n\n## Front\n\nperro\n\n\
+            ## Back\n\ndog\n\n# gato\n\n## Front\n\ngato\n";
+        let file = MarkdownFile::parse(text).unwrap();
+        assert_eq!(file.front_matter.kind, Kind::Group);
+        let titles = file
+            .cards
+            .iter()
+            .map(|card| card.title.as_deref().unwrap())
+            .collect::<Vec<_>>();
+        assert_eq!(titles, vec!["perro", "gato"]);
+        assert_eq!(file.cards[1].front.as_deref(), Some("gato"));
+        assert_eq!(file.cards[1].back, None);
+
+        assert!(MarkdownFile::parse("---\nkind: group\n---\n\n## Front\n\nperro\n").is_err());
+        assert!(MarkdownFile::parse("## Front\n\na\n\n## Front\n\nb\n").is_err());
+    }
+
+    #[test]
+    fn test_render_round_trip() {
+        let file = MarkdownFile::parse(CARD).unwrap();
+        let rendered = MarkdownFile::parse(&file.render().unwrap()).unwrap();
+        assert_eq!(rendered, file);
+    }
+
+    #[test]
+    fn test_replace_front_matter() {
+        let mut front_matter = MarkdownFile::parse(CARD).unwrap().front_matter;
+        front_matter.id = Some("card:2".to_string());
+        let text = replace_front_matter(CARD, &front_matter).unwrap();
+        assert!(text.contains("id: card:2"));
+        assert!(text.ends_with(&CARD[CARD.find("\n## Front").unwrap()..]));
+
+        let text = replace_front_matter("## Front\n\na\n", &front_matter).unwrap();
+        assert!(text.starts_with("---\nid: card:2\n"));
+        assert!(text.ends_with("---\n\n## Front\n\na\n"));
+    }
+}
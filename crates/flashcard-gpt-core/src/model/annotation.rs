@@ -0,0 +1,94 @@
+//! Personal notes and flags of a card or a card group, added while answering and shown whenever it
+//! is asked again.
+
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeSet;
+use std::str::FromStr;
+use std::sync::Arc;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Flag {
+    Bookmark,
+    /// Something is wrong with the card and it should be edited.
+    NeedsFix,
+    Favorite,
+}
+
+impl Flag {
+    pub const ALL: [Flag; 3] = [Flag::Bookmark, Flag::NeedsFix, Flag::Favorite];
+
+    pub fn name(self) -> &'static str {
+        match self {
+            Flag::Bookmark => "bookmark",
+            Flag::NeedsFix => "needs_fix",
+            Flag::Favorite => "favorite",
+        }
+    }
+
+    pub fn icon(self) -> &'static str {
+        match self {
+            Flag::Bookmark => "🔖",
+            Flag::NeedsFix => "🛠",
+            Flag::Favorite => "⭐",
+        }
+    }
+}
+
+impl FromStr for Flag {
+    type Err = String;
+
+    /// Accepts the names with `-` or `_`, in any case.
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let name = s.trim().to_lowercase().replace('-', "_");
+        Flag::ALL
+            .into_iter()
+            .find(|flag| flag.name() == name)
+            .ok_or_else(|| format!("Unknown flag `{s}`"))
+    }
+}
+
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct Annotations {
+    /// Oldest first.
+    pub notes: Vec<Arc<str>>,
+    pub flags: BTreeSet<Flag>,
+}
+
+impl Annotations {
+    pub fn is_empty(&self) -> bool {
+        self.notes.is_empty() && self.flags.is_empty()
+    }
+
+    /// Sets the flag if it is not set and clears it otherwise, returns whether it is set now.
+    pub fn toggle(&mut self, flag: Flag) -> bool {
+        if self.flags.remove(&flag) {
+            false
+        } else {
+            self.flags.insert(flag);
+            true
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_flag_from_str() {
+        assert_eq!("needs-fix".parse(), Ok(Flag::NeedsFix));
+        assert_eq!(" Bookmark".parse(), Ok(Flag::Bookmark));
+        assert!("fix".parse::<Flag>().is_err());
+    }
+
+    #[test]
+    fn test_toggle() {
+        let mut annotations = Annotations::default();
+        assert!(annotations.toggle(Flag::Favorite));
+        assert!(!annotations.is_empty());
+        assert!(!annotations.toggle(Flag::Favorite));
+        assert!(annotations.is_empty());
+    }
+}
@@ -5,7 +5,7 @@ use serde::{Deserialize, Serialize};
 use std::sync::Arc;
 use surrealdb::sql::Thing;
 
-#[derive(Debug, Serialize, Deserialize, Builder)]
+#[derive(Debug, Clone, Serialize, Deserialize, Builder)]
 pub struct Binding {
     pub id: Thing,
     pub source_id: Arc<str>,
@@ -15,7 +15,7 @@ pub struct Binding {
     pub time: Time,
 }
 
-#[derive(Debug, Serialize, Deserialize, Builder)]
+#[derive(Debug, Clone, Serialize, Deserialize, Builder)]
 pub struct GetOrCreateBinding {
     pub source_id: Arc<str>,
     pub type_name: Arc<str>,
@@ -1,30 +1,41 @@
 use super::skip_nulls;
+use crate::model::annotation::Annotations;
+use crate::model::card_data::CardData;
+use crate::model::cloze;
+use crate::model::multiple_choice::MultipleChoice;
 use crate::model::tag::Tag;
 use crate::model::time::Time;
 use crate::model::user::User;
 use bon::Builder;
 use serde::{Deserialize, Serialize};
-use serde_json::Value;
 use std::sync::Arc;
 use surrealdb::sql::Thing;
 
-#[derive(Debug, Serialize, Deserialize, Builder)]
+#[derive(Debug, Clone, Serialize, Deserialize, Builder)]
 pub struct Card {
     pub id: Thing,
     pub user: Arc<User>,
     pub title: Arc<str>,
     pub front: Option<Arc<str>>,
     pub back: Option<Arc<str>>,
-    pub data: Option<Arc<Value>>,
+    pub data: Option<CardData>,
+    /// Set for multiple-choice cards.
+    pub choices: Option<MultipleChoice>,
+    /// The reversed card of this one, or the card this one reverses.
+    #[serde(default)]
+    pub sibling: Option<Thing>,
     pub hints: Vec<Arc<str>>,
     pub difficulty: u8,
     pub importance: u8,
     #[serde(deserialize_with = "skip_nulls")]
     pub tags: Vec<Arc<Tag>>,
+    #[serde(default)]
+    #[builder(default)]
+    pub annotations: Annotations,
     pub time: Option<Time>,
 }
 
-#[derive(Debug, Serialize, Deserialize, Builder)]
+#[derive(Debug, Clone, Serialize, Deserialize, Builder)]
 pub struct CreateCard {
     pub user: Thing,
     pub title: Arc<str>,
@@ -33,10 +44,47 @@ pub struct CreateCard {
     pub hints: Vec<Arc<str>>,
     pub difficulty: u8,
     pub importance: u8,
-    pub data: Option<Arc<Value>>,
+    pub data: Option<CardData>,
+    pub choices: Option<MultipleChoice>,
+    pub sibling: Option<Thing>,
+    /// Also create the reversed card, see [`crate::store::siblings`].
+    #[serde(skip)]
+    #[builder(default)]
+    pub reverse: bool,
     pub tags: Vec<Thing>,
 }
 
+impl CreateCard {
+    /// The card asking for the front by the back, `None` for cards that can't be reversed: those
+    /// without a back, cloze and multiple-choice cards. It has no hints, the hints of the card are
+    /// about its front.
+    pub fn reversed(&self) -> Option<CreateCard> {
+        let front = self.front.as_deref()?;
+        let back = self.back.clone()?;
+        if self.choices.is_some() || !cloze::indexes(front).is_empty() {
+            return None;
+        }
+
+        Some(CreateCard {
+            title: sibling_title(&self.title),
+            front: Some(back),
+            back: Some(Arc::from(front)),
+            hints: vec![],
+            sibling: None,
+            reverse: false,
+            ..self.clone()
+        })
+    }
+}
+
+impl Card {
+    /// Whether this card is the reversed sibling of another, by the title
+    /// [`CreateCard::reversed`] gives it.
+    pub fn is_reversed(&self) -> bool {
+        self.sibling.is_some() && self.title.ends_with(REVERSED_SUFFIX)
+    }
+}
+
 impl From<Card> for Thing {
     fn from(value: Card) -> Self {
         value.id
@@ -49,8 +97,44 @@ impl From<&Card> for Thing {
     }
 }
 
-#[derive(Debug, Serialize, Deserialize, Builder)]
+#[derive(Debug, Clone, Serialize, Deserialize, Builder)]
 pub struct UpdateCard {
     pub importance: Option<u8>,
     pub difficulty: Option<u8>,
+    pub sibling: Option<Thing>,
+    /// Replaces the annotations, not shared with the sibling.
+    pub annotations: Option<Annotations>,
+    pub title: Option<Arc<str>>,
+    pub front: Option<Arc<str>>,
+    pub back: Option<Arc<str>>,
+    pub hints: Option<Vec<Arc<str>>>,
+    pub tags: Option<Vec<Thing>>,
+}
+
+const MAX_TITLE_LENGTH: usize = 64;
+
+const REVERSED_SUFFIX: &str = " (reversed)";
+
+/// The title of the reversed sibling of a card titled `title`, or of the original card when
+/// `title` is the one of a reversed sibling.
+pub fn sibling_title(title: &str) -> Arc<str> {
+    match title.strip_suffix(REVERSED_SUFFIX) {
+        Some(title) => Arc::from(title),
+        None => Arc::from(format!("{title}{REVERSED_SUFFIX}")),
+    }
+}
+
+/// A title for a card without one: the first line of its front, shortened.
+pub fn title_from_front(front: &str) -> Arc<str> {
+    let line = front
+        .lines()
+        .map(str::trim)
+        .find(|line| !line.is_empty())
+        .unwrap_or_default();
+    if line.chars().count() > MAX_TITLE_LENGTH {
+        let line = line.chars().take(MAX_TITLE_LENGTH - 1).collect::<String>();
+        Arc::from(format!("{}…", line.trim_end()))
+    } else {
+        Arc::from(line)
+    }
 }
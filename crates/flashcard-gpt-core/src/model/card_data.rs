@@ -0,0 +1,218 @@
+//! The `data` of cards and card groups. Every well-known attachment is a field of [`CardData`],
+//! the stores check it with [`CardData::validate`] before writing it.
+
+use crate::error::CoreError;
+use crate::model::media::{CardSide, Media};
+use bon::Builder;
+use serde::{Deserialize, Serialize};
+use std::fmt::{Display, Formatter};
+use std::sync::Arc;
+
+#[derive(Debug, Clone, Default, Serialize, Deserialize, PartialEq, Eq, Builder)]
+#[serde(default)]
+pub struct CardData {
+    /// Where the card comes from, e.g. a LeetCode problem. Links the title of the card.
+    #[serde(alias = "leetcode_link")]
+    pub source_link: Option<Arc<str>>,
+    /// Explanation of the problem and its solution, also the context of graded answers.
+    pub article: Option<Arc<str>>,
+    /// The solution with comments.
+    pub commented_code: Option<Arc<str>>,
+    /// Anything else worth looking up while answering.
+    pub notes: Option<Arc<str>>,
+    /// The expected answer of a typed-answer card, see [`crate::grading`].
+    pub answer: Option<Arc<str>>,
+    /// The cloze index of a cloze card, see [`crate::model::cloze`].
+    pub cloze: Option<u8>,
+    /// Photos and files shown with the front or the back of the card.
+    #[builder(default)]
+    pub media: Vec<Media>,
+    /// SHA-1 of the local files a card group was generated from, see [`crate::llm::batch`].
+    pub source_hash: Option<Arc<str>>,
+    /// The part of a document the card was generated from, see [`crate::document`].
+    pub source: Option<SourceRef>,
+    /// Keys of older `data` nothing reads anymore, kept by the migrations instead of dropping
+    /// them.
+    #[serde(skip_serializing_if = "serde_json::Map::is_empty")]
+    #[builder(default)]
+    pub extra: serde_json::Map<String, serde_json::Value>,
+}
+
+/// A section or a page of a document.
+#[derive(Debug, Clone, Default, Serialize, Deserialize, PartialEq, Eq)]
+#[serde(default)]
+pub struct SourceRef {
+    /// The name of the file.
+    pub file: Arc<str>,
+    /// The headings the section is under, separated by ` › `.
+    pub section: Option<Arc<str>>,
+    /// The page the section starts on, from 1.
+    pub page: Option<u32>,
+}
+
+impl Display for SourceRef {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}", self.file)?;
+        if let Some(section) = &self.section {
+            write!(f, " › {section}")?;
+        }
+        if let Some(page) = self.page {
+            write!(f, ", page {page}")?;
+        }
+        Ok(())
+    }
+}
+
+/// The attachments of [`CardData`] that are sent to the user on request.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Attachment {
+    SourceLink,
+    Article,
+    CommentedCode,
+    Notes,
+}
+
+impl Attachment {
+    pub fn name(self) -> &'static str {
+        match self {
+            Attachment::SourceLink => "source link",
+            Attachment::Article => "article",
+            Attachment::CommentedCode => "commented code",
+            Attachment::Notes => "notes",
+        }
+    }
+}
+
+impl CardData {
+    pub fn attachment(&self, attachment: Attachment) -> Option<&str> {
+        match attachment {
+            Attachment::SourceLink => self.source_link.as_deref(),
+            Attachment::Article => self.article.as_deref(),
+            Attachment::CommentedCode => self.commented_code.as_deref(),
+            Attachment::Notes => self.notes.as_deref(),
+        }
+    }
+
+    pub fn media(&self, side: CardSide) -> impl Iterator<Item = &Media> {
+        self.media.iter().filter(move |media| media.side == side)
+    }
+
+    /// Texts are not blank, the source link is an http(s) URL, cloze indexes and source pages
+    /// start at 1 and media have a file id or a local copy.
+    pub fn validate(&self) -> Result<(), CoreError> {
+        let texts = [
+            ("source_link", &self.source_link),
+            ("article", &self.article),
+            ("commented_code", &self.commented_code),
+            ("notes", &self.notes),
+            ("answer", &self.answer),
+            ("source_hash", &self.source_hash),
+        ];
+        for (name, text) in texts {
+            if text.as_deref().is_some_and(|text| text.trim().is_empty()) {
+                return Err(invalid(format!("{name} is blank")));
+            }
+        }
+
+        if let Some(link) = self.source_link.as_deref()
+            && !is_url(link)
+        {
+            return Err(invalid(format!(
+                "source_link `{link}` is not an http(s) URL"
+            )));
+        }
+
+        if let Some(source) = &self.source {
+            if source.file.trim().is_empty()
+                || source
+                    .section
+                    .as_deref()
+                    .is_some_and(|section| section.trim().is_empty())
+            {
+                return Err(invalid(format!("source {source:?} has a blank name")));
+            }
+            if source.page == Some(0) {
+                return Err(invalid("source pages start at 1".to_string()));
+            }
+        }
+
+        if self.cloze == Some(0) {
+            return Err(invalid("cloze indexes start at 1".to_string()));
+        }
+
+        if let Some(media) = self.media.iter().find(|media| !media.is_valid()) {
+            return Err(invalid(format!("invalid media {media:?}")));
+        }
+
+        Ok(())
+    }
+}
+
+impl Display for CardData {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        let json = serde_json::to_string(self).map_err(|_| std::fmt::Error)?;
+        write!(f, "{json}")
+    }
+}
+
+fn is_url(link: &str) -> bool {
+    let rest = link
+        .strip_prefix("https://")
+        .or_else(|| link.strip_prefix("http://"));
+    rest.is_some_and(|rest| !rest.is_empty() && !rest.contains(char::is_whitespace))
+}
+
+fn invalid(message: String) -> CoreError {
+    CoreError::InvalidCardData(Arc::from(message))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    #[test]
+    fn test_validate() {
+        let data = CardData::builder()
+            .source_link(Arc::from("https://leetcode.com/problems/largest-number/"))
+            .article(Arc::from("Sort by concatenation"))
+            .cloze(1)
+            .build();
+        assert!(data.validate().is_ok());
+        assert!(CardData::default().validate().is_ok());
+
+        for invalid in [
+            CardData::builder()
+                .source_link(Arc::from("leetcode.com"))
+                .build(),
+            CardData::builder()
+                .source_link(Arc::from("https://"))
+                .build(),
+            CardData::builder().notes(Arc::from("  ")).build(),
+            CardData::builder().cloze(0).build(),
+            CardData::builder()
+                .source(SourceRef {
+                    file: Arc::from("notes.pdf"),
+                    section: None,
+                    page: Some(0),
+                })
+                .build(),
+        ] {
+            assert!(invalid.validate().is_err(), "{invalid:?}");
+        }
+    }
+
+    #[test]
+    fn test_deserialize_legacy_keys() {
+        let data: CardData = serde_json::from_value(json!({
+            "leetcode_link": "https://leetcode.com",
+            "article": "article",
+            "unknown": 1
+        }))
+        .unwrap();
+        assert_eq!(data.source_link.as_deref(), Some("https://leetcode.com"));
+        assert_eq!(data.attachment(Attachment::Article), Some("article"));
+        assert_eq!(data.cloze, None);
+    }
+}
@@ -1,15 +1,16 @@
 use super::skip_nulls;
+use crate::model::annotation::Annotations;
 use crate::model::card::Card;
+use crate::model::card_data::CardData;
 use crate::model::tag::Tag;
 use crate::model::time::Time;
 use crate::model::user::User;
 use bon::Builder;
 use serde::{Deserialize, Serialize};
-use serde_json::Value;
 use std::sync::Arc;
 use surrealdb::sql::Thing;
 
-#[derive(Debug, Serialize, Deserialize, Builder)]
+#[derive(Debug, Clone, Serialize, Deserialize, Builder)]
 pub struct CardGroup {
     pub id: Thing,
     pub user: User,
@@ -17,7 +18,7 @@ pub struct CardGroup {
     pub importance: u8,
     pub difficulty: u8,
     pub title: Arc<str>,
-    pub data: Option<Arc<Value>>,
+    pub data: Option<CardData>,
 
     pub time: Time,
 
@@ -26,21 +27,53 @@ pub struct CardGroup {
 
     #[serde(deserialize_with = "skip_nulls")]
     pub tags: Vec<Arc<Tag>>,
+
+    #[serde(default)]
+    #[builder(default)]
+    pub annotations: Annotations,
 }
 
-#[derive(Debug, Serialize, Deserialize, Builder)]
+#[derive(Debug, Clone, Serialize, Deserialize, Builder)]
 pub struct CreateCardGroup {
     pub user: Thing,
     pub title: Arc<str>,
     pub importance: u8,
     pub difficulty: u8,
-    pub data: Option<Arc<Value>>,
+    pub data: Option<CardData>,
     pub cards: Vec<Thing>,
     pub tags: Vec<Thing>,
 }
 
-#[derive(Debug, Serialize, Deserialize, Builder)]
+#[derive(Debug, Clone, Serialize, Deserialize, Builder)]
 pub struct UpdateCardGroup {
     pub importance: Option<u8>,
     pub difficulty: Option<u8>,
+    /// The cards in their new order.
+    pub cards: Option<Vec<Thing>>,
+    /// Replaces the annotations.
+    pub annotations: Option<Annotations>,
+    pub title: Option<Arc<str>>,
+    pub tags: Option<Vec<Thing>>,
+}
+
+impl CardGroup {
+    /// The cards taken in `order`, `None` unless `order` is a permutation of the card indexes.
+    pub fn reordered(&self, order: &[usize]) -> Option<Vec<Thing>> {
+        if order.len() != self.cards.len() {
+            return None;
+        }
+        let mut seen = vec![false; self.cards.len()];
+        for &index in order {
+            if std::mem::replace(seen.get_mut(index)?, true) {
+                return None;
+            }
+        }
+
+        Some(
+            order
+                .iter()
+                .map(|&index| self.cards[index].id.clone())
+                .collect(),
+        )
+    }
 }
@@ -0,0 +1,224 @@
+//! Cloze deletions in the `{{c1::answer}}` / `{{c1::answer::hint}}` syntax of Anki. A card whose
+//! front has deletions is stored once per cloze index, so every index is its own deck card with
+//! its own history and ranking.
+
+use crate::model::card::{Card, CreateCard};
+use crate::model::card_data::CardData;
+use std::collections::BTreeSet;
+use std::ops::Range;
+use std::sync::Arc;
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Deletion<'a> {
+    pub index: u8,
+    pub answer: &'a str,
+    pub hint: Option<&'a str>,
+    /// Byte range of the whole `{{c..}}` in the text.
+    pub range: Range<usize>,
+}
+
+/// The well-formed deletions of `text` in order. Anything that does not parse is left as text.
+pub fn deletions(text: &str) -> Vec<Deletion<'_>> {
+    let mut deletions = vec![];
+    let mut offset = 0;
+    while let Some(start) = text[offset..].find("{{c").map(|start| offset + start) {
+        offset = start + 3;
+        if let Some(deletion) = parse_deletion(text, start) {
+            offset = deletion.range.end;
+            deletions.push(deletion);
+        }
+    }
+    deletions
+}
+
+fn parse_deletion(text: &str, start: usize) -> Option<Deletion<'_>> {
+    let rest = &text[start + 3..];
+    let digits = rest.find(|c: char| !c.is_ascii_digit())?;
+    let index = rest[..digits]
+        .parse::<u8>()
+        .ok()
+        .filter(|&index| index > 0)?;
+    let body = rest[digits..].strip_prefix("::")?;
+    let end = body.find("}}")?;
+    let (answer, hint) = match body[..end].split_once("::") {
+        Some((answer, hint)) => (answer, Some(hint)),
+        None => (&body[..end], None),
+    };
+
+    let body_start = start + 3 + digits + 2;
+    Some(Deletion {
+        index,
+        answer,
+        hint,
+        range: start..body_start + end + 2,
+    })
+}
+
+/// Cloze indexes used in `text`.
+pub fn indexes(text: &str) -> BTreeSet<u8> {
+    deletions(text)
+        .into_iter()
+        .map(|deletion| deletion.index)
+        .collect()
+}
+
+/// `text` with the deletions of `index` replaced by `hide` and all others by their answer.
+pub fn render(text: &str, index: u8, hide: impl Fn(&Deletion) -> String) -> String {
+    let mut rendered = String::with_capacity(text.len());
+    let mut offset = 0;
+    for deletion in deletions(text) {
+        rendered.push_str(&text[offset..deletion.range.start]);
+        if deletion.index == index {
+            rendered.push_str(&hide(&deletion));
+        } else {
+            rendered.push_str(deletion.answer);
+        }
+        offset = deletion.range.end;
+    }
+    rendered.push_str(&text[offset..]);
+    rendered
+}
+
+/// `text` with every deletion replaced by its answer.
+pub fn reveal(text: &str) -> String {
+    render(text, 0, |_| unreachable!("cloze indexes start at 1"))
+}
+
+impl CreateCard {
+    /// One card per cloze index of `front`, or the card itself when it has no deletions. The index
+    /// goes to [`CardData::cloze`] of each card, a card without data gets data that holds only the
+    /// index.
+    pub fn into_cloze_cards(self) -> Vec<CreateCard> {
+        let cloze_indexes = self.front.as_deref().map(indexes).unwrap_or_default();
+        if cloze_indexes.is_empty() {
+            return vec![self];
+        }
+
+        cloze_indexes
+            .into_iter()
+            .map(|index| CreateCard {
+                title: Arc::from(format!("{} (c{index})", self.title)),
+                data: Some(CardData {
+                    cloze: Some(index),
+                    ..self.data.clone().unwrap_or_default()
+                }),
+                ..self.clone()
+            })
+            .collect()
+    }
+}
+
+impl Card {
+    /// The cloze index this card asks for, `None` for front / back cards.
+    pub fn cloze_index(&self) -> Option<u8> {
+        self.data.as_ref()?.cloze
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+    use surrealdb::sql::Thing;
+
+    #[test]
+    fn test_deletions() {
+        let text = "Lookup in a {{c1::hash map}} is {{c2::O(1)::complexity}} on {{c1::average}}";
+        let deletions = deletions(text);
+        assert_eq!(deletions.len(), 3);
+        assert_eq!(deletions[0].answer, "hash map");
+        assert_eq!(deletions[1].hint, Some("complexity"));
+        assert_eq!(&text[deletions[2].range.clone()], "{{c1::average}}");
+        assert_eq!(indexes(text), BTreeSet::from([1, 2]));
+    }
+
+    #[test]
+    fn test_malformed_deletions_are_text() {
+        for text in [
+            "{{c::a}}",
+            "{{c0::a}}",
+            "{{c1:a}}",
+            "{{c1::a",
+            "{{c300::a}}",
+        ] {
+            assert!(deletions(text).is_empty(), "{text}");
+        }
+        assert_eq!(indexes("{{c1::a {{c2::b}}"), BTreeSet::from([1]));
+    }
+
+    #[test]
+    fn test_render() {
+        let text = "{{c1::Vec}} has {{c2::amortized O(1)::complexity}} push";
+        let hidden = render(text, 2, |deletion| {
+            format!("[{}]", deletion.hint.unwrap_or("..."))
+        });
+        assert_eq!(hidden, "Vec has [complexity] push");
+        assert_eq!(reveal(text), "Vec has amortized O(1) push");
+    }
+
+    #[test]
+    fn test_into_cloze_cards() {
+        let card = CreateCard {
+            user: Thing::from(("user", "user")),
+            title: Arc::from("Vec"),
+            front: Some(Arc::from("{{c1::Vec}} has {{c2::amortized O(1)}} push")),
+            back: None,
+            hints: vec![],
+            difficulty: 1,
+            importance: 1,
+            data: Some(CardData::builder().notes(Arc::from("docs")).build()),
+            choices: None,
+            sibling: None,
+            reverse: false,
+            tags: vec![],
+        };
+
+        let cards = card.clone().into_cloze_cards();
+        assert_eq!(cards.len(), 2);
+        assert_eq!(cards[1].title.as_ref(), "Vec (c2)");
+        let data = cards[1].data.as_ref().unwrap();
+        assert_eq!(data.cloze, Some(2));
+        assert_eq!(data.notes.as_deref(), Some("docs"));
+
+        let plain = CreateCard {
+            front: Some(Arc::from("no deletions")),
+            ..card
+        };
+        assert_eq!(plain.into_cloze_cards().len(), 1);
+    }
+
+    #[test]
+    fn test_into_cloze_cards_keeps_the_index() -> Result<(), serde_json::Error> {
+        let card = CreateCard {
+            user: Thing::from(("user", "user")),
+            title: Arc::from("Vec"),
+            front: Some(Arc::from("{{c1::Vec}} has {{c2::amortized O(1)}} push")),
+            back: None,
+            hints: vec![],
+            difficulty: 1,
+            importance: 1,
+            data: None,
+            choices: None,
+            sibling: None,
+            reverse: false,
+            tags: vec![],
+        };
+
+        // the index has nowhere to go in data that isn't an object, such a card is rejected
+        let mut value = serde_json::to_value(&card)?;
+        serde_json::from_value::<CreateCard>(value.clone())?;
+        value["data"] = json!("docs");
+        assert!(serde_json::from_value::<CreateCard>(value).is_err());
+
+        let data = card
+            .into_cloze_cards()
+            .into_iter()
+            .map(|card| card.data.unwrap_or_default())
+            .collect::<Vec<_>>();
+        assert_eq!(
+            data,
+            [1, 2].map(|index| CardData::builder().cloze(index).build())
+        );
+        Ok(())
+    }
+}
@@ -1,4 +1,5 @@
 use super::skip_nulls;
+use crate::error::CoreError;
 use crate::model::tag::Tag;
 use crate::model::time::Time;
 use crate::model::user::User;
@@ -7,12 +8,16 @@ use serde::{Deserialize, Serialize};
 use std::sync::Arc;
 use surrealdb::sql::Thing;
 
-#[derive(Debug, Serialize, Deserialize, Builder)]
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize, Builder)]
 pub struct DeckSettings {
     pub daily_limit: usize,
+    /// Cards added to the deck get a reversed sibling, see [`crate::store::siblings`].
+    #[serde(default)]
+    #[builder(default)]
+    pub reverse_cards: bool,
 }
 
-#[derive(Debug, Serialize, Deserialize, Builder)]
+#[derive(Debug, Clone, Serialize, Deserialize, Builder)]
 pub struct Deck {
     pub id: Thing,
     pub description: Option<Arc<str>>,
@@ -25,7 +30,7 @@ pub struct Deck {
     pub user: User,
 }
 
-#[derive(Debug, Serialize, Deserialize, Builder)]
+#[derive(Debug, Clone, Serialize, Deserialize, Builder)]
 pub struct CreateDeck {
     pub description: Option<Arc<str>>,
     pub parent: Option<Thing>,
@@ -35,6 +40,62 @@ pub struct CreateDeck {
     pub user: Thing,
 }
 
+impl Deck {
+    /// Titles of the parents of the deck and its own, the root first. `decks` are the decks of
+    /// the user, a parent missing from them ends the path.
+    pub fn path<'a>(&'a self, decks: &'a [Deck]) -> Vec<&'a str> {
+        let mut titles = vec![self.title.as_ref()];
+        let mut parent = self.parent.as_ref();
+        // a cycle of parents can't be stored, but a broken one shouldn't hang
+        while let Some(id) = parent
+            && titles.len() <= decks.len()
+        {
+            let Some(deck) = decks.iter().find(|deck| &deck.id == id) else {
+                break;
+            };
+            titles.push(&deck.title);
+            parent = deck.parent.as_ref();
+        }
+        titles.reverse();
+        titles
+    }
+}
+
+/// The deck `root` and its subdecks at any depth, in the order of `decks`.
+pub fn with_subdecks<'a>(decks: &'a [Deck], root: &Thing) -> Vec<&'a Deck> {
+    let mut selected = vec![root];
+    let mut index = 0;
+    while index < selected.len() {
+        let parent = selected[index];
+        for deck in decks {
+            if deck.parent.as_ref() == Some(parent) && !selected.contains(&&deck.id) {
+                selected.push(&deck.id);
+            }
+        }
+        index += 1;
+    }
+    decks
+        .iter()
+        .filter(|deck| selected.contains(&&deck.id))
+        .collect()
+}
+
+/// The deck `id` with its subdecks, or every deck without it.
+pub fn selected_decks<'a>(
+    decks: &'a [Deck],
+    id: Option<&Thing>,
+) -> Result<Vec<&'a Deck>, CoreError> {
+    let Some(id) = id else {
+        return Ok(decks.iter().collect());
+    };
+    if !decks.iter().any(|deck| &deck.id == id) {
+        return Err(CoreError::NotFound(Arc::from(format!(
+            "Deck {id} not found"
+        ))));
+    }
+    Ok(with_subdecks(decks, id))
+}
+
 impl From<Deck> for Thing {
     fn from(value: Deck) -> Self {
         value.id
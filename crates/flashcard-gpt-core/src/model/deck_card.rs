@@ -1,4 +1,4 @@
-use crate::model::card::Card;
+use crate::model::card::{Card, CreateCard};
 use crate::model::deck::Deck;
 use crate::model::time::Time;
 use crate::reexports::db::sql::Thing;
@@ -6,7 +6,7 @@ use bon::Builder;
 use serde::{Deserialize, Serialize};
 use std::sync::Arc;
 
-#[derive(Debug, Serialize, Deserialize, Builder)]
+#[derive(Debug, Clone, Serialize, Deserialize, Builder)]
 pub struct DeckCard {
     pub id: Thing,
 
@@ -20,8 +20,20 @@ pub struct DeckCard {
     pub time: Option<Time>,
 }
 
-#[derive(Debug, Serialize, Deserialize, Builder)]
+#[derive(Debug, Clone, Serialize, Deserialize, Builder)]
 pub struct CreateDeckCard {
     pub deck: Thing,
     pub card: Thing,
 }
+
+/// A new card along with the deck it goes to, see [`crate::store::DeckStore::create_cards`].
+#[derive(Debug, Clone, Serialize, Deserialize, Builder)]
+pub struct CreateCardInDeck {
+    pub deck: Thing,
+    pub card: CreateCard,
+    /// Names of tags of the user that are added to the card, created in the same transaction when
+    /// they don't exist.
+    #[serde(default)]
+    #[builder(default)]
+    pub tag_names: Vec<Arc<str>>,
+}
@@ -6,7 +6,7 @@ use serde::{Deserialize, Serialize};
 use std::sync::Arc;
 use surrealdb::sql::Thing;
 
-#[derive(Debug, Serialize, Deserialize, Builder)]
+#[derive(Debug, Clone, Serialize, Deserialize, Builder)]
 pub struct DeckCardGroup {
     pub id: Thing,
 
@@ -20,7 +20,7 @@ pub struct DeckCardGroup {
     pub time: Time,
 }
 
-#[derive(Debug, Serialize, Deserialize, Builder)]
+#[derive(Debug, Clone, Serialize, Deserialize, Builder)]
 pub struct CreateDeckCardGroup {
     pub deck: Thing,
     pub card_group: Thing,
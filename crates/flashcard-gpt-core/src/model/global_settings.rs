@@ -1,3 +1,4 @@
+use crate::llm::provider::LlmChoice;
 use crate::model::time::Time;
 use crate::model::user::User;
 use crate::reexports::db::sql::Thing;
@@ -7,12 +8,21 @@ use chrono_tz::Tz;
 use serde::{Deserialize, Serialize};
 use surrealdb::sql::Duration;
 
-#[derive(Debug, Serialize, Deserialize, Builder)]
+#[derive(Debug, Clone, Serialize, Deserialize, Builder)]
 pub struct GlobalSettings {
     pub id: Thing,
     pub daily_limit: u16,
     pub timetable: Vec<[Duration; 2]>,
     pub timezone: Tz,
+    /// Send code blocks and math of cards as images too.
+    #[serde(default)]
+    pub render_images: bool,
+    /// Generate cards with wrong options too, which turns them into multiple-choice ones.
+    #[serde(default)]
+    pub multiple_choice: bool,
+    /// The LLM provider and model of the user instead of the ones of the chains.
+    #[serde(default)]
+    pub llm: Option<LlmChoice>,
     pub user: User,
     pub time: Time,
 }
@@ -37,12 +47,20 @@ impl GlobalSettings {
     }
 }
 
-#[derive(Debug, Serialize, Deserialize, Builder)]
+#[derive(Debug, Clone, Serialize, Deserialize, Builder)]
 pub struct CreateGlobalSettings {
     pub user: Thing,
     pub daily_limit: u16,
     pub timetable: Vec<[Duration; 2]>,
     pub timezone: Tz,
+    #[serde(default)]
+    #[builder(default)]
+    pub render_images: bool,
+    #[serde(default)]
+    #[builder(default)]
+    pub multiple_choice: bool,
+    #[serde(default)]
+    pub llm: Option<LlmChoice>,
 }
 
 impl From<GlobalSettings> for Thing {
@@ -71,6 +89,9 @@ mod tests {
             time: Time::default(),
             timetable: durations,
             timezone: Tz::Europe__Dublin,
+            render_images: false,
+            multiple_choice: false,
+            llm: None,
             user: User {
                 id: Thing::from(("test_user", "aaa")),
                 email: Arc::from("aaa@aaa.aa"),
@@ -7,7 +7,7 @@ use serde::{Deserialize, Serialize};
 use std::sync::Arc;
 use surrealdb::sql::Duration;
 
-#[derive(Debug, Serialize, Deserialize, Builder)]
+#[derive(Debug, Clone, Serialize, Deserialize, Builder)]
 pub struct HistoryRecord {
     pub id: Thing,
 
@@ -20,10 +20,15 @@ pub struct HistoryRecord {
 
     pub difficulty: u8,
 
+    /// Difficulties of the cards of a progressively answered card group, see
+    /// [`crate::model::progress`].
+    #[serde(default)]
+    pub steps: Vec<Option<u8>>,
+
     pub time: Time,
 }
 
-#[derive(Debug, Serialize, Deserialize, Builder)]
+#[derive(Debug, Clone, Serialize, Deserialize, Builder)]
 pub struct CreateHistory {
     pub user: Thing,
     pub deck_card: Option<Thing>,
@@ -31,4 +36,7 @@ pub struct CreateHistory {
     pub difficulty: u8,
     pub time: Option<Time>,
     pub hide_for: Option<Duration>,
+    #[serde(default)]
+    #[builder(default)]
+    pub steps: Vec<Option<u8>>,
 }
@@ -1,5 +1,8 @@
+use crate::model::card_data::CardData;
+use crate::model::multiple_choice::{MultipleChoice, MAX_OPTIONS};
+use crate::model::stats::RECALLED_DIFFICULTY;
 use serde::{Deserialize, Serialize};
-use serde_json::Value;
+use std::collections::BTreeSet;
 use std::sync::Arc;
 
 #[derive(Debug, Serialize, Deserialize)]
@@ -8,7 +11,7 @@ pub struct GptCardGroup {
     pub difficulty: u8,
     pub title: Arc<str>,
     pub tags: Vec<Arc<str>>,
-    pub data: Option<Value>,
+    pub data: Option<CardData>,
     pub cards: Vec<GptCard>,
 }
 
@@ -21,18 +24,73 @@ pub struct GptCard {
     pub difficulty: u8,
     pub importance: u8,
     pub tags: Vec<Arc<str>>,
+    /// Wrong answers to `front`, only asked for when generating multiple-choice cards.
+    #[serde(default)]
+    pub distractors: Vec<Arc<str>>,
+    /// Whether the card also makes sense asked back to front, e.g. a term and its definition.
+    #[serde(default)]
+    pub reversible: bool,
+}
+
+impl GptCard {
+    /// `back` and the distractors as options of a multiple-choice card, `None` without
+    /// distractors.
+    pub fn choices(&self) -> Option<MultipleChoice> {
+        let mut options = vec![self.back.clone()];
+        for distractor in &self.distractors {
+            if options.len() < MAX_OPTIONS && !options.contains(distractor) {
+                options.push(distractor.clone());
+            }
+        }
+
+        (options.len() > 1).then(|| MultipleChoice {
+            options,
+            correct: BTreeSet::from([0]),
+        })
+    }
 }
 
 impl GptCardGroup {
     pub fn from_gpt_response(input: &str) -> Result<Self, serde_json::Error> {
-        let input = input.strip_prefix("```json").unwrap_or(input);
-        let input = input.strip_prefix("```").unwrap_or(input);
-        let input = input.strip_suffix("```").unwrap_or(input);
+        serde_json::from_str(strip_code_fence(input))
+    }
+}
+
+/// A free-form answer graded by the LLM.
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub struct GptGrade {
+    /// 0 for a wrong answer to 10 for a complete and correct one.
+    pub score: u8,
+    #[serde(default)]
+    pub missed_points: Vec<Arc<str>>,
+    #[serde(default)]
+    pub misconceptions: Vec<Arc<str>>,
+}
+
+impl GptGrade {
+    pub fn from_gpt_response(input: &str) -> Result<Self, serde_json::Error> {
+        serde_json::from_str(strip_code_fence(input))
+    }
 
-        serde_json::from_str(input)
+    /// The inverted score, an answer with misconceptions does not count as recalled however much
+    /// it covers.
+    pub fn difficulty(&self) -> u8 {
+        let difficulty = 10 - self.score.min(10);
+        if self.misconceptions.is_empty() {
+            difficulty
+        } else {
+            difficulty.max(RECALLED_DIFFICULTY + 1)
+        }
     }
 }
 
+fn strip_code_fence(input: &str) -> &str {
+    let input = input.trim();
+    let input = input.strip_prefix("```json").unwrap_or(input);
+    let input = input.strip_prefix("```").unwrap_or(input);
+    input.strip_suffix("```").unwrap_or(input)
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -43,8 +101,58 @@ mod tests {
         let input = "```json\n{\n    \"importance\": 8,  \n    \"difficulty\": 6,  \n    \"title\": \"Largest Number (LeetCode)\", \n    \"tags\": [\"Medium\", \"Sorting\", \"Custom Sort\", \"String Manipulation\", \"Edge Cases\"], \n    \"data\": {\n        \"leetcode_link\": \"https://leetcode.com/problems/largest-number/\"\n    },\n    \"cards\": [\n        {\n            \"title\": \"Why Compare Numbers as Strings in Largest Number Problem?\", \n            \"front\": \"Why is it necessary to compare numbers as strings in the 'Largest Number' problem instead of comparing them as integers?\", \n            \"back\": \"Comparing numbers as strings allows us to evaluate the result of concatenating numbers in different orders. This ensures that the concatenation yielding the largest combined number is prioritized. For example, comparing 30 and 3 as integers would place 30 before 3, but concatenating them as strings ('330' vs. '303') reveals '330' is larger, so 3 should come first.\", \n            \"hints\": [\n                \"What happens if you compare 30 and 3 as integers?\", \n                \"What is the goal of concatenating numbers in different orders?\", \n                \"Consider two methods of concatenation: ab and ba.\"\n            ], \n            \"difficulty\": 6,  \n            \"importance\": 8,  \n            \"tags\": [\"String Manipulation\", \"Sorting\", \"Custom Comparator\"]\n        },\n        {\n            \"title\": \"Custom Sorting Logic for Largest Number\", \n            \"front\": \"Describe the custom sorting logic used to solve the 'Largest Number' problem.\", \n            \"back\": \"The custom sorting logic involves comparing two numbers as strings by concatenating them in both possible orders (e.g., ab and ba). The sorting decision is based on which concatenation produces a larger result. For example, for numbers a = '3' and b = '30', '330' > '303', so '3' should come before '30'.\", \n            \"hints\": [\n                \"What does ab and ba represent in the context of this logic?\",\n                \"Think about string comparison, not the absolute integer values.\"\n            ], \n            \"difficulty\": 7,  \n            \"importance\": 8,  \n            \"tags\": [\"Custom Sort\", \"String Comparison\", \"Greedy Strategy\"]\n        },\n        {\n            \"title\": \"Edge Case for Arrays of Zeros in Largest Number\", \n            \"front\": \"How do you handle the edge case where the input array contains multiple zeros (e.g., [0, 0, 0])? Why is this necessary?\", \n            \"back\": \"If the first number in the sorted list is '0', all other numbers must also be zeros. In this case, we return '0' instead of '000'. This is necessary to avoid leading zeros in the final result, which should express the zero concisely as just '0'.\", \n            \"hints\": [\n                \"What happens when all numbers in the input are zeros?\",\n                \"Consider what the final result should look like when the array has zeros.\"\n            ], \n            \"difficulty\": 4,  \n            \"importance\": 6,  \n            \"tags\": [\"Edge Cases\", \"Array Input\", \"String Manipulation\"]\n        }\n    ]\n}\n```";
         let card_group = GptCardGroup::from_gpt_response(input)?;
 
-        assert!(card_group.data.is_some());
+        let source_link = card_group
+            .data
+            .as_ref()
+            .and_then(|data| data.source_link.as_deref());
+        assert_eq!(
+            source_link,
+            Some("https://leetcode.com/problems/largest-number/")
+        );
         assert_eq!(card_group.cards.len(), 3);
+        assert!(card_group.cards[0].choices().is_none());
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_choices() -> TestResult {
+        let input = r#"{
+            "title": "Hash map lookup",
+            "front": "Average complexity of a hash map lookup?",
+            "back": "O(1)",
+            "hints": [],
+            "difficulty": 1,
+            "importance": 5,
+            "tags": [],
+            "distractors": ["O(log n)", "O(n)", "O(1)"]
+        }"#;
+        let card: GptCard = serde_json::from_str(input)?;
+
+        let choices = card.choices().ok_or("no choices")?;
+        let options = choices
+            .options
+            .iter()
+            .map(|o| o.as_ref())
+            .collect::<Vec<_>>();
+        assert_eq!(options, ["O(1)", "O(log n)", "O(n)"]);
+        assert_eq!(choices.correct, BTreeSet::from([0]));
+
+        Ok(())
+    }
+    #[test]
+    fn test_grade() -> TestResult {
+        let input = "```json\n{\"score\": 9, \"missed_points\": [\"Edge case\"]}\n```";
+        let grade = GptGrade::from_gpt_response(input)?;
+        assert_eq!(grade.missed_points.len(), 1);
+        assert!(grade.misconceptions.is_empty());
+        assert_eq!(grade.difficulty(), 1);
+
+        let grade = GptGrade {
+            misconceptions: vec![Arc::from("Sorts numerically")],
+            ..grade
+        };
+        assert_eq!(grade.difficulty(), RECALLED_DIFFICULTY + 1);
 
         Ok(())
     }
@@ -0,0 +1,99 @@
+//! Photos and files attached to a side of a card. Telegram keeps the files, a card stores their
+//! `file_id` and, with a [`BlobStore`], the key of a local copy.
+
+use crate::blob::BlobStore;
+use bon::Builder;
+use serde::{Deserialize, Serialize};
+use std::sync::Arc;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum MediaKind {
+    /// Sent with `sendPhoto`, shown inline.
+    Photo,
+    /// Sent with `sendDocument`, any other file.
+    Document,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum CardSide {
+    Front,
+    Back,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize, Builder)]
+pub struct Media {
+    pub kind: MediaKind,
+    pub side: CardSide,
+    /// Only valid for the bot that received the file, empty for imported files that only have a
+    /// local copy.
+    pub file_id: Arc<str>,
+    pub file_name: Option<Arc<str>>,
+    pub mime_type: Option<Arc<str>>,
+    /// Key of the local copy in the [`BlobStore`].
+    pub blob: Option<Arc<str>>,
+}
+
+impl Media {
+    pub fn is_valid(&self) -> bool {
+        (!self.file_id.trim().is_empty() || self.blob.is_some())
+            && self.blob.as_deref().map_or(true, BlobStore::is_valid_key)
+    }
+}
+
+/// Key of the local copy of a file: its Telegram `file_unique_id`, which is the same for every
+/// bot, with the extension of `file_name`.
+pub fn blob_key(file_unique_id: &str, file_name: Option<&str>) -> Arc<str> {
+    let extension = file_name
+        .and_then(|name| name.rsplit_once('.'))
+        .map(|(_, extension)| extension)
+        .filter(|extension| BlobStore::is_valid_key(extension));
+    match extension {
+        Some(extension) => Arc::from(format!("{file_unique_id}.{extension}")),
+        None => Arc::from(file_unique_id),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_blob_key() {
+        assert_eq!(
+            blob_key("AgADBQ", Some("diagram.v2.png")).as_ref(),
+            "AgADBQ.png"
+        );
+        assert_eq!(blob_key("AgADBQ", Some("README")).as_ref(), "AgADBQ");
+        assert_eq!(blob_key("AgADBQ", Some("a./b")).as_ref(), "AgADBQ");
+        assert_eq!(blob_key("AgADBQ", None).as_ref(), "AgADBQ");
+    }
+
+    #[test]
+    fn test_is_valid() {
+        let media = Media::builder()
+            .kind(MediaKind::Photo)
+            .side(CardSide::Back)
+            .file_id(Arc::from("AgACAgIAAxkBAAI"))
+            .blob(Arc::from("AgADBQ.jpg"))
+            .build();
+        assert!(media.is_valid());
+        assert!(!Media {
+            blob: Some(Arc::from("../AgADBQ.jpg")),
+            ..media.clone()
+        }
+        .is_valid());
+        assert!(Media {
+            file_id: Arc::from(""),
+            ..media.clone()
+        }
+        .is_valid());
+        assert!(!Media {
+            file_id: Arc::from(" "),
+            blob: None,
+            ..media
+        }
+        .is_valid());
+    }
+}
@@ -3,15 +3,22 @@ use serde::{Deserialize, Deserializer};
 use std::fmt;
 use std::marker::PhantomData;
 
+pub mod annotation;
 pub mod binding;
 pub mod card;
+pub mod card_data;
 pub mod card_group;
+pub mod cloze;
 pub mod deck;
 pub mod deck_card;
 pub mod deck_card_group;
 pub mod global_settings;
 pub mod history;
 pub mod llm;
+pub mod media;
+pub mod multiple_choice;
+pub mod progress;
+pub mod stats;
 pub mod tag;
 pub mod time;
 pub mod user;
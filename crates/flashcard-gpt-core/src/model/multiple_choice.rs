@@ -0,0 +1,82 @@
+use bon::Builder;
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeSet;
+use std::sync::Arc;
+
+/// Telegram polls take 2 to 10 options.
+pub const MAX_OPTIONS: usize = 10;
+
+/// Difficulty of an answer that picked exactly the correct options.
+pub const CORRECT_DIFFICULTY: u8 = 2;
+
+/// The options of a multiple-choice card, its `front` is the question.
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq, Eq, Builder)]
+pub struct MultipleChoice {
+    pub options: Vec<Arc<str>>,
+    /// Indexes of the correct options.
+    pub correct: BTreeSet<usize>,
+}
+
+impl MultipleChoice {
+    pub fn is_valid(&self) -> bool {
+        (2..=MAX_OPTIONS).contains(&self.options.len())
+            && !self.correct.is_empty()
+            && self.correct.iter().all(|&index| index < self.options.len())
+    }
+
+    /// Whether there is a single correct option, which makes it a quiz in Telegram.
+    pub fn is_quiz(&self) -> bool {
+        self.correct.len() == 1
+    }
+
+    /// The difficulty an answer picking `selected` is committed with: [`CORRECT_DIFFICULTY`]
+    /// without mistakes, 6 to 10 depending on the share of options that were picked or missed by
+    /// mistake.
+    pub fn difficulty(&self, selected: &[usize]) -> u8 {
+        let selected = selected.iter().copied().collect::<BTreeSet<_>>();
+        let mistakes = self.correct.symmetric_difference(&selected).count();
+        if mistakes == 0 {
+            return CORRECT_DIFFICULTY;
+        }
+
+        let options = self.options.len().max(1);
+        (5 + (5 * mistakes).div_ceil(options)).min(10) as u8
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn choice(options: usize, correct: impl IntoIterator<Item = usize>) -> MultipleChoice {
+        MultipleChoice {
+            options: (0..options)
+                .map(|option| Arc::from(option.to_string()))
+                .collect(),
+            correct: correct.into_iter().collect(),
+        }
+    }
+
+    #[test]
+    fn test_is_valid() {
+        assert!(choice(4, [1]).is_valid());
+        assert!(choice(4, [0, 3]).is_valid());
+        assert!(!choice(1, [0]).is_valid());
+        assert!(!choice(11, [0]).is_valid());
+        assert!(!choice(4, []).is_valid());
+        assert!(!choice(4, [4]).is_valid());
+    }
+
+    #[test]
+    fn test_difficulty() {
+        let quiz = choice(4, [1]);
+        assert_eq!(quiz.difficulty(&[1]), CORRECT_DIFFICULTY);
+        // picking a wrong option also misses the correct one
+        assert_eq!(quiz.difficulty(&[2]), 8);
+
+        let multiple = choice(4, [0, 1]);
+        assert_eq!(multiple.difficulty(&[1, 0]), CORRECT_DIFFICULTY);
+        assert_eq!(multiple.difficulty(&[0]), 7);
+        assert_eq!(multiple.difficulty(&[2, 3]), 10);
+    }
+}
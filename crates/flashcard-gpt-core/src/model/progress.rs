@@ -0,0 +1,101 @@
+//! Progressive answering of card groups. The cards of a group are asked in their order and graded
+//! one by one, the session stops at the first card answered wrong and the next one resumes from
+//! it. The steps of a session are kept in the history record of the group, `None` for cards that
+//! were passed without grading.
+
+use crate::model::stats::RECALLED_DIFFICULTY;
+
+pub fn is_wrong(difficulty: u8) -> bool {
+    difficulty > RECALLED_DIFFICULTY
+}
+
+/// Whether the session ends after `steps`: at the first wrong card or after the last one.
+pub fn is_finished(steps: &[Option<u8>], cards: usize) -> bool {
+    steps.len() >= cards || steps.last().copied().flatten().is_some_and(is_wrong)
+}
+
+/// The mean difficulty of the graded steps, cards that were not reached count with the difficulty
+/// of the card the session stopped at. `None` without graded steps.
+pub fn group_difficulty(steps: &[Option<u8>], cards: usize) -> Option<u8> {
+    let graded = steps
+        .iter()
+        .flatten()
+        .map(|&difficulty| difficulty as usize)
+        .collect::<Vec<_>>();
+    let last = *graded.last()?;
+    let unreached = cards.saturating_sub(steps.len());
+
+    let total = graded.iter().sum::<usize>() + unreached * last;
+    let count = graded.len() + unreached;
+    Some((total as f64 / count as f64).round() as u8)
+}
+
+/// Records `difficulty` as the step of the card at `seq`, the cards before it that were passed
+/// without grading stay `None`. The difficulty the group is committed with once the session is
+/// finished, `None` while it goes on.
+pub fn record_step(
+    steps: &mut Vec<Option<u8>>,
+    seq: usize,
+    difficulty: u8,
+    cards: usize,
+) -> Option<u8> {
+    steps.resize(seq, None);
+    steps.push(Some(difficulty));
+    if !is_finished(steps, cards) {
+        return None;
+    }
+    Some(group_difficulty(steps, cards).unwrap_or(difficulty))
+}
+
+/// Index of the card the next session starts with: the card the previous session stopped at, or
+/// the first one after a complete pass.
+pub fn resume_seq(steps: &[Option<u8>], cards: usize) -> usize {
+    match steps.last() {
+        Some(&Some(difficulty)) if is_wrong(difficulty) && steps.len() <= cards => steps.len() - 1,
+        _ => 0,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_is_finished() {
+        assert!(!is_finished(&[], 3));
+        assert!(!is_finished(&[Some(2), None], 3));
+        assert!(is_finished(&[Some(2), Some(8)], 3));
+        assert!(is_finished(&[Some(2), None, Some(3)], 3));
+    }
+
+    #[test]
+    fn test_group_difficulty() {
+        assert_eq!(group_difficulty(&[], 3), None);
+        assert_eq!(group_difficulty(&[None, None], 2), None);
+        assert_eq!(group_difficulty(&[Some(2), None, Some(4)], 3), Some(3));
+        // the two cards after the wrong one count as wrong too
+        assert_eq!(group_difficulty(&[Some(0), Some(9)], 4), Some(7));
+    }
+
+    #[test]
+    fn test_record_step() {
+        let mut steps = vec![];
+        assert_eq!(record_step(&mut steps, 1, 2, 3), None);
+        assert_eq!(steps, vec![None, Some(2)]);
+        assert_eq!(record_step(&mut steps, 2, 4, 3), Some(3));
+        assert_eq!(steps, vec![None, Some(2), Some(4)]);
+
+        // a step again after going back drops the later ones
+        assert_eq!(record_step(&mut steps, 0, 9, 3), Some(9));
+        assert_eq!(steps, vec![Some(9)]);
+    }
+
+    #[test]
+    fn test_resume_seq() {
+        assert_eq!(resume_seq(&[], 3), 0);
+        assert_eq!(resume_seq(&[Some(2), Some(7)], 3), 1);
+        assert_eq!(resume_seq(&[Some(2), Some(3), Some(1)], 3), 0);
+        // the group lost cards since
+        assert_eq!(resume_seq(&[Some(2), Some(3), Some(7)], 2), 0);
+    }
+}
@@ -0,0 +1,265 @@
+use bon::Builder;
+use chrono::{DateTime, Days, NaiveDate, Timelike, Utc};
+use chrono_tz::Tz;
+use serde::{Deserialize, Serialize};
+use std::collections::{BTreeMap, HashMap};
+use std::sync::Arc;
+use surrealdb::sql::Thing;
+
+/// Answers up to this difficulty count as recalled, the top row of the answer keyboard.
+pub const RECALLED_DIFFICULTY: u8 = 4;
+
+/// How many of the hardest cards and card groups [`Stats`] lists.
+pub const HARDEST_LIMIT: usize = 5;
+
+/// Which answers to aggregate: the answers of `user` in `from..till`, optionally only those
+/// given in `deck` or to cards and card groups tagged with `tag`.
+#[derive(Debug, Clone, Serialize, Deserialize, Builder)]
+pub struct StatsQuery {
+    pub user: Thing,
+    pub deck: Option<Thing>,
+    pub tag: Option<Thing>,
+    pub from: DateTime<Utc>,
+    pub till: DateTime<Utc>,
+}
+
+/// A history record together with the deck and the card or card group it answered.
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub struct Answer {
+    pub id: Thing,
+    pub deck: Thing,
+    /// The card or card group.
+    pub item: Thing,
+    pub title: Arc<str>,
+    pub difficulty: u8,
+    pub created_at: DateTime<Utc>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub struct HardItem {
+    pub item: Thing,
+    pub title: Arc<str>,
+    pub answers: usize,
+    pub average_difficulty: f64,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub struct Stats {
+    pub reviews: usize,
+    /// Days without reviews are left out.
+    pub reviews_per_day: BTreeMap<NaiveDate, usize>,
+    pub average_difficulty: Option<f64>,
+    /// Change of the difficulty per day, negative when answers get easier.
+    pub difficulty_trend: Option<f64>,
+    /// Share of the answers with a difficulty of at most [`RECALLED_DIFFICULTY`].
+    pub retention: Option<f64>,
+    /// Days in a row with reviews up to the last day of the range, or the day before it.
+    pub current_streak: usize,
+    pub best_streak: usize,
+    /// Reviews by the hour of the day they were given at.
+    pub hours: [usize; 24],
+    pub hardest: Vec<HardItem>,
+}
+
+impl Stats {
+    /// Days and hours are taken in `timezone`. `answers` must come from `query`.
+    pub fn new(query: &StatsQuery, answers: &[Answer], timezone: Tz) -> Self {
+        let local = |time: &DateTime<Utc>| time.with_timezone(&timezone);
+
+        let mut reviews_per_day = BTreeMap::new();
+        let mut hours = [0; 24];
+        for answer in answers {
+            let time = local(&answer.created_at);
+            *reviews_per_day.entry(time.date_naive()).or_default() += 1;
+            hours[time.hour() as usize] += 1;
+        }
+
+        let difficulties = answers.iter().map(|answer| answer.difficulty as f64);
+        let average_difficulty = mean(difficulties.clone());
+        let retention = mean(difficulties.map(|difficulty| {
+            if difficulty <= RECALLED_DIFFICULTY as f64 {
+                1.0
+            } else {
+                0.0
+            }
+        }));
+
+        let points = answers
+            .iter()
+            .map(|answer| {
+                let days = (answer.created_at - query.from).num_seconds() as f64 / 86_400.0;
+                (days, answer.difficulty as f64)
+            })
+            .collect::<Vec<_>>();
+        let difficulty_trend = slope(&points);
+
+        let (current_streak, best_streak) =
+            streaks(&reviews_per_day, local(&query.till).date_naive());
+
+        Self {
+            reviews: answers.len(),
+            reviews_per_day,
+            average_difficulty,
+            difficulty_trend,
+            retention,
+            current_streak,
+            best_streak,
+            hours,
+            hardest: hardest(answers),
+        }
+    }
+}
+
+fn mean(values: impl Iterator<Item = f64>) -> Option<f64> {
+    let (sum, count) = values.fold((0.0, 0), |(sum, count), value| (sum + value, count + 1));
+    (count > 0).then(|| sum / count as f64)
+}
+
+/// Slope of the least squares line through `points`, `None` if they don't define one.
+fn slope(points: &[(f64, f64)]) -> Option<f64> {
+    let mean_x = mean(points.iter().map(|&(x, _)| x))?;
+    let mean_y = mean(points.iter().map(|&(_, y)| y))?;
+    let (numerator, denominator) = points.iter().fold((0.0, 0.0), |(num, den), &(x, y)| {
+        (
+            num + (x - mean_x) * (y - mean_y),
+            den + (x - mean_x) * (x - mean_x),
+        )
+    });
+    (denominator > 0.0).then(|| numerator / denominator)
+}
+
+/// The current and the best streak of consecutive days in `days`.
+fn streaks(days: &BTreeMap<NaiveDate, usize>, last_day: NaiveDate) -> (usize, usize) {
+    let mut best = 0;
+    let mut run = 0;
+    let mut previous: Option<NaiveDate> = None;
+    for &day in days.keys() {
+        run = match previous {
+            Some(previous) if previous.checked_add_days(Days::new(1)) == Some(day) => run + 1,
+            _ => 1,
+        };
+        best = best.max(run);
+        previous = Some(day);
+    }
+
+    let yesterday = last_day.checked_sub_days(Days::new(1));
+    let current = match previous {
+        Some(day) if day == last_day || Some(day) == yesterday => run,
+        _ => 0,
+    };
+
+    (current, best)
+}
+
+fn hardest(answers: &[Answer]) -> Vec<HardItem> {
+    let mut items: HashMap<&Thing, HardItem> = HashMap::new();
+    for answer in answers {
+        let item = items.entry(&answer.item).or_insert_with(|| HardItem {
+            item: answer.item.clone(),
+            title: answer.title.clone(),
+            answers: 0,
+            average_difficulty: 0.0,
+        });
+        item.answers += 1;
+        item.average_difficulty +=
+            (answer.difficulty as f64 - item.average_difficulty) / item.answers as f64;
+    }
+
+    let mut items = items.into_values().collect::<Vec<_>>();
+    items.sort_by(|a, b| {
+        b.average_difficulty
+            .total_cmp(&a.average_difficulty)
+            .then(b.answers.cmp(&a.answers))
+            .then_with(|| a.title.cmp(&b.title))
+    });
+    items.truncate(HARDEST_LIMIT);
+    items
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chrono::TimeZone;
+
+    fn answer(item: &str, difficulty: u8, created_at: DateTime<Utc>) -> Answer {
+        Answer {
+            id: Thing::from(("history", "id")),
+            deck: Thing::from(("deck", "deck")),
+            item: Thing::from(("card", item)),
+            title: Arc::from(item),
+            difficulty,
+            created_at,
+        }
+    }
+
+    fn query(from: DateTime<Utc>, till: DateTime<Utc>) -> StatsQuery {
+        StatsQuery {
+            user: Thing::from(("user", "user")),
+            deck: None,
+            tag: None,
+            from,
+            till,
+        }
+    }
+
+    fn day(day: u32, hour: u32) -> DateTime<Utc> {
+        Utc.with_ymd_and_hms(2024, 10, day, hour, 0, 0).unwrap()
+    }
+
+    #[test]
+    fn test_empty() {
+        let stats = Stats::new(&query(day(1, 0), day(10, 0)), &[], Tz::UTC);
+        assert_eq!(stats.reviews, 0);
+        assert_eq!(stats.average_difficulty, None);
+        assert_eq!(stats.difficulty_trend, None);
+        assert_eq!(stats.retention, None);
+        assert_eq!((stats.current_streak, stats.best_streak), (0, 0));
+        assert!(stats.hardest.is_empty());
+    }
+
+    #[test]
+    fn test_aggregates() {
+        let answers = [
+            answer("a", 8, day(1, 9)),
+            answer("b", 2, day(2, 9)),
+            answer("a", 6, day(3, 21)),
+            answer("b", 0, day(3, 22)),
+            answer("a", 4, day(6, 9)),
+            answer("b", 0, day(7, 9)),
+        ];
+        let stats = Stats::new(&query(day(1, 0), day(7, 12)), &answers, Tz::UTC);
+
+        assert_eq!(stats.reviews, 6);
+        assert_eq!(stats.reviews_per_day.len(), 5);
+        assert_eq!(stats.reviews_per_day[&day(3, 0).date_naive()], 2);
+        assert_eq!(stats.average_difficulty, Some(20.0 / 6.0));
+        assert!(stats.difficulty_trend.is_some_and(|trend| trend < 0.0));
+        assert_eq!(stats.retention, Some(4.0 / 6.0));
+        assert_eq!((stats.current_streak, stats.best_streak), (2, 3));
+        assert_eq!(stats.hours[9], 4);
+
+        let hardest = stats.hardest.iter().map(|item| item.title.as_ref());
+        assert_eq!(hardest.collect::<Vec<_>>(), ["a", "b"]);
+        assert_eq!(stats.hardest[0].average_difficulty, 6.0);
+    }
+
+    #[test]
+    fn test_days_and_hours_follow_timezone() {
+        let answers = [answer("a", 1, day(1, 23))];
+        let stats = Stats::new(&query(day(1, 0), day(2, 12)), &answers, Tz::Europe__Berlin);
+
+        assert_eq!(
+            stats.reviews_per_day.keys().collect::<Vec<_>>(),
+            [&day(2, 0).date_naive()]
+        );
+        assert_eq!(stats.hours[1], 1);
+        assert_eq!(stats.current_streak, 1);
+    }
+
+    #[test]
+    fn test_streak_breaks_after_a_missed_day() {
+        let answers = [answer("a", 1, day(1, 9)), answer("a", 1, day(2, 9))];
+        let stats = Stats::new(&query(day(1, 0), day(4, 12)), &answers, Tz::UTC);
+        assert_eq!((stats.current_streak, stats.best_streak), (0, 2));
+    }
+}
@@ -4,7 +4,7 @@ use serde::{Deserialize, Serialize};
 use std::sync::Arc;
 use surrealdb::sql::Thing;
 
-#[derive(Debug, Serialize, Deserialize, PartialEq, Eq, Hash, Builder)]
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq, Eq, Hash, Builder)]
 pub struct Tag {
     pub id: Thing,
     pub name: Arc<str>,
@@ -19,7 +19,7 @@ impl AsRef<Tag> for Tag {
     }
 }
 
-#[derive(Debug, Serialize, Deserialize, PartialEq, Eq, Hash, Builder)]
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq, Eq, Hash, Builder)]
 pub struct CreateTag {
     pub name: Arc<str>,
     pub slug: Arc<str>,
@@ -5,7 +5,7 @@ use std::fmt::{Display, Formatter};
 use std::sync::Arc;
 use surrealdb::sql::Thing;
 
-#[derive(Debug, Serialize, Deserialize, PartialEq, Eq, Hash, Builder)]
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq, Eq, Hash, Builder)]
 pub struct User {
     pub id: Thing,
     pub email: Arc<str>,
@@ -14,7 +14,7 @@ pub struct User {
     pub time: Option<Time>,
 }
 
-#[derive(Debug, Serialize, Deserialize)]
+#[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct RegisterUser {
     pub email: Arc<str>,
     pub name: Arc<str>,
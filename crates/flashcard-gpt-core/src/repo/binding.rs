@@ -4,14 +4,14 @@ use crate::ext::response_ext::ResponseExt;
 use crate::repo::generic_repo::GenericRepo;
 use crate::{multi_object_query, single_object_query};
 use std::sync::Arc;
-use surrealdb::engine::remote::ws::Client;
+use surrealdb::engine::any::Any;
 use surrealdb::sql::Thing;
 use surrealdb::Surreal;
 use tracing::Span;
 
 pub type BindingRepo = GenericRepo<GetOrCreateBinding, Binding, ()>;
 impl BindingRepo {
-    pub fn new_binding(db: Surreal<Client>, span: Span, enable_transactions: bool) -> Self {
+    pub fn new_binding(db: Surreal<Any>, span: Span, enable_transactions: bool) -> Self {
         Self::new(db, span, "binding", "", "user", enable_transactions)
     }
 
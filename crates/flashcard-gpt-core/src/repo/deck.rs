@@ -1,22 +1,24 @@
-use crate::model::card::Card;
+use crate::model::card::{Card, CreateCard};
+use crate::model::card_group::CardGroup;
 use crate::model::deck::{CreateDeck, Deck};
-use crate::model::deck_card::{CreateDeckCard, DeckCard};
+use crate::model::deck_card::{CreateCardInDeck, CreateDeckCard, DeckCard};
 use crate::model::deck_card_group::{CreateDeckCardGroup, DeckCardGroup};
 use crate::error::CoreError;
 use crate::ext::response_ext::ResponseExt;
 use crate::repo::generic_repo::GenericRepo;
 use crate::{multi_object_query, single_object_query};
 use chrono::Utc;
+use serde::Serialize;
 use std::sync::Arc;
-use surrealdb::engine::remote::ws::Client;
-use surrealdb::sql::Thing;
+use surrealdb::engine::any::Any;
+use surrealdb::sql::{Id, Thing};
 use surrealdb::Surreal;
 use tracing::Span;
 
 pub type DeckRepo = GenericRepo<CreateDeck, Deck, ()>;
 
 impl DeckRepo {
-    pub fn new_deck(db: Surreal<Client>, span: Span, enable_transactions: bool) -> Self {
+    pub fn new_deck(db: Surreal<Any>, span: Span, enable_transactions: bool) -> Self {
         Self::new(db, span, "deck", "", "user, tags", enable_transactions)
     }
 
@@ -38,6 +40,72 @@ impl DeckRepo {
         single_object_query!(self.db, &query, ("dto", dto))
     }
 
+    #[tracing::instrument(level = "info", skip_all, parent = self.span.clone(), err, fields(cards = cards.len()))]
+    pub async fn create_cards(
+        &self,
+        cards: Vec<CreateCardInDeck>,
+    ) -> Result<Vec<Thing>, CoreError> {
+        // the ids are known upfront, so the edges are related in the same loop
+        #[derive(Debug, Serialize)]
+        struct Item {
+            id: Thing,
+            deck: Thing,
+            card: CreateCard,
+            tag_pairs: Vec<(Arc<str>, Arc<str>)>,
+        }
+
+        let items = cards
+            .into_iter()
+            .map(
+                |CreateCardInDeck {
+                     deck,
+                     card,
+                     tag_names,
+                 }| Item {
+                    id: Thing::from(("card", Id::rand())),
+                    deck,
+                    card,
+                    tag_pairs: tag_names
+                        .into_iter()
+                        .map(|name| {
+                            let slug = Arc::from(slug::slugify(&name));
+                            (name, slug)
+                        })
+                        .collect(),
+                },
+            )
+            .collect::<Vec<_>>();
+        let ids = items.iter().map(|item| item.id.clone()).collect();
+
+        // all or nothing is the point of this query, it is a transaction regardless of the settings
+        let query = r#"
+            begin transaction;
+            for $item in $items {
+                for $pair in $item.tag_pairs {
+                    if select * from tag where slug=$pair[1] && user=$item.card.user {
+                        continue;
+                    };
+                    insert into tag {
+                        user: $item.card.user,
+                        name: $pair[0],
+                        slug: $pair[1]
+                    };
+                };
+                $slugs = $item.tag_pairs.map(|$pair| $pair[1]);
+                $tags = select value id from tag where slug in $slugs && user=$item.card.user;
+                create $item.id content $item.card;
+                update $item.id set tags = array::union(tags, $tags);
+                relate ($item.deck) -> deck_card -> ($item.id);
+            };
+            commit transaction;
+        "#;
+
+        let mut response = self.db.query(query).bind(("items", items)).await?;
+        response.errors_or_ok()?;
+
+        Ok(ids)
+    }
+
     #[tracing::instrument(level = "info", skip_all, parent = self.span.clone(), err, fields(?dto))]
     pub async fn relate_card_group(
         &self,
@@ -92,6 +160,39 @@ impl DeckRepo {
         Ok(response.take(response.num_statements() - 1)?)
     }
 
+    pub async fn list_card_groups(
+        &self,
+        user: impl Into<Thing>,
+        deck: impl Into<Thing>,
+    ) -> Result<Vec<CardGroup>, CoreError> {
+        let query = format!(
+            r#"
+            {begin_transaction}
+            let $results = (
+                select ->deck_card_group->card_group as card_groups FROM $deck
+                where user = $user
+                fetch card_groups, card_groups.user, card_groups.tags, card_groups.cards,
+                    card_groups.cards.user, card_groups.cards.tags
+            )[0].card_groups;
+            return select * from $results order by title;
+            {commit_transaction}
+            "#,
+            begin_transaction = self.begin_transaction_statement(),
+            commit_transaction = self.commit_transaction_statement()
+        );
+
+        let mut response = self
+            .db
+            .query(query)
+            .bind(("user", user.into()))
+            .bind(("deck", deck.into()))
+            .await?;
+
+        response.errors_or_ok()?;
+
+        Ok(response.take(response.num_statements() - 1)?)
+    }
+
     pub async fn list_top_ranked_card_groups(
         &self,
         user: impl Into<Thing>,
@@ -147,6 +248,7 @@ impl DeckRepo {
                 fn::num_answers_for_deck(in, <datetime> $since) <= in.settings.daily_limit and
                 fn::appears_in_card_groups_in_this_deck(out, in) = 0 and
                 fn::deck_card_answered_times(id, <datetime> $since) = 0 and
+                (out.sibling = NONE or fn::card_answered_times(out.sibling, <datetime> $since) = 0) and
                 fn::hidden_till(id) < time::now()
             order by rank desc
             limit 10
@@ -160,6 +262,43 @@ impl DeckRepo {
         multi_object_query!(self.db, query, ("user", user.into()), ("since", since))
     }
 
+    pub async fn list_deck_cards(
+        &self,
+        user: impl Into<Thing>,
+    ) -> Result<Vec<DeckCard>, CoreError> {
+        let query = r#"
+        select 
+            *
+            from deck_card
+            where in.user = $user
+            fetch 
+                in, out,
+                in.user, in.tags, out.user, out.tags
+        ;
+        "#;
+
+        multi_object_query!(self.db, query, ("user", user.into()))
+    }
+
+    pub async fn list_deck_card_groups(
+        &self,
+        user: impl Into<Thing>,
+    ) -> Result<Vec<DeckCardGroup>, CoreError> {
+        let query = r#"
+        select 
+            *
+            from deck_card_group
+            where in.user = $user
+            fetch 
+                in, out,
+                in.user, in.tags, out.user, out.cards, out.tags,
+                out.cards.tags, out.cards.user
+        ;
+        "#;
+
+        multi_object_query!(self.db, query, ("user", user.into()))
+    }
+
     pub async fn get_deck_card_group(
         &self,
         id: impl Into<Thing>,
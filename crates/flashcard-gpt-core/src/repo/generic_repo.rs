@@ -4,14 +4,14 @@ use crate::ext::response_ext::ResponseExt;
 use crate::{multi_object_query, single_object_query};
 use std::fmt::Debug;
 use std::sync::Arc;
-use surrealdb::engine::remote::ws::Client;
+use surrealdb::engine::any::Any;
 use surrealdb::sql::Thing;
 use surrealdb::Surreal;
 use tracing::{Instrument, Span};
 
 #[derive(Debug)]
 pub struct GenericRepo<Create, Read, Update> {
-    pub(super) db: Surreal<Client>,
+    pub(super) db: Surreal<Any>,
     pub(super) span: Span,
     pub(super) table_name: &'static str,
     pub(super) additional_query: &'static str,
@@ -46,7 +46,7 @@ where
     Update: serde::Serialize + Debug + 'static,
 {
     pub fn new(
-        db: Surreal<Client>,
+        db: Surreal<Any>,
         span: Span,
         table_name: &'static str,
         additional_query: &'static str,
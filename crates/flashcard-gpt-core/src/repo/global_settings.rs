@@ -1,16 +1,80 @@
+use crate::error::CoreError;
+use crate::ext::response_ext::ResponseExt;
+use crate::llm::provider::LlmChoice;
 use crate::model::global_settings::{CreateGlobalSettings, GlobalSettings};
 use crate::repo::generic_repo::GenericRepo;
-use surrealdb::engine::remote::ws::Client;
+use crate::single_object_query;
+use std::sync::Arc;
+use surrealdb::engine::any::Any;
+use surrealdb::sql::Thing;
 use surrealdb::Surreal;
 use tracing::Span;
 
 pub type GlobalSettingsRepo = GenericRepo<CreateGlobalSettings, GlobalSettings, ()>;
 
 impl GlobalSettingsRepo {
-    pub fn new_global_settings(db: Surreal<Client>, span: Span, enable_transactions: bool) -> Self {
+    pub fn new_global_settings(db: Surreal<Any>, span: Span, enable_transactions: bool) -> Self {
         Self::new(db, span, "global_settings", "", "user", enable_transactions)
     }
 
+    pub async fn set_render_images(
+        &self,
+        user: impl Into<Thing>,
+        render_images: bool,
+    ) -> Result<GlobalSettings, CoreError> {
+        let query = format!(
+            r#"
+            update {table_name} set render_images = $render_images where user = $user_id;
+            select * from {table_name} where user = $user_id {fetch};
+            "#,
+            table_name = self.table_name,
+            fetch = self.fetch_statement(),
+        );
+        single_object_query!(
+            self.db,
+            &query,
+            ("user_id", user.into()),
+            ("render_images", render_images)
+        )
+    }
+
+    pub async fn set_multiple_choice(
+        &self,
+        user: impl Into<Thing>,
+        multiple_choice: bool,
+    ) -> Result<GlobalSettings, CoreError> {
+        let query = format!(
+            r#"
+            update {table_name} set multiple_choice = $multiple_choice where user = $user_id;
+            select * from {table_name} where user = $user_id {fetch};
+            "#,
+            table_name = self.table_name,
+            fetch = self.fetch_statement(),
+        );
+        single_object_query!(
+            self.db,
+            &query,
+            ("user_id", user.into()),
+            ("multiple_choice", multiple_choice)
+        )
+    }
+
+    pub async fn set_llm(
+        &self,
+        user: impl Into<Thing>,
+        llm: Option<LlmChoice>,
+    ) -> Result<GlobalSettings, CoreError> {
+        let query = format!(
+            r#"
+            update {table_name} set llm = $llm where user = $user_id;
+            select * from {table_name} where user = $user_id {fetch};
+            "#,
+            table_name = self.table_name,
+            fetch = self.fetch_statement(),
+        );
+        single_object_query!(self.db, &query, ("user_id", user.into()), ("llm", llm))
+    }
+
     // duplicate create method with custom serializer in the query
     // I think it's identical to https://github.com/surrealdb/surrealdb/issues/3550
 }
@@ -1,18 +1,20 @@
 use crate::model::history::{CreateHistory, HistoryRecord};
+use crate::model::stats::{Answer, StatsQuery};
 use crate::error::CoreError;
 use crate::ext::response_ext::ResponseExt;
 use std::sync::Arc;
+use surrealdb::sql::Thing;
 
 use crate::repo::generic_repo::GenericRepo;
-use crate::single_object_query;
-use surrealdb::engine::remote::ws::Client;
+use crate::{multi_object_query, single_object_query};
+use surrealdb::engine::any::Any;
 use surrealdb::Surreal;
 use tracing::Span;
 
 pub type HistoryRepo = GenericRepo<CreateHistory, HistoryRecord, ()>;
 
 impl HistoryRepo {
-    pub fn new_history(db: Surreal<Client>, span: Span, enable_transactions: bool) -> Self {
+    pub fn new_history(db: Surreal<Any>, span: Span, enable_transactions: bool) -> Self {
         Self::new(
             db,
             span,
@@ -44,6 +46,7 @@ impl HistoryRepo {
                 deck_card: $dto.deck_card,
                 deck_card_group: $dto.deck_card_group,
                 difficulty: $dto.difficulty,
+                steps: $dto.steps,
                 hide_for: <option<duration>> $dto.hide_for,
                 time: {{
                     created_at: <datetime> ($dto.time.created_at or time::now()),
@@ -67,4 +70,60 @@ impl HistoryRepo {
 
         single_object_query!(self.db, &query, ("dto", dto))
     }
+
+    /// The latest answer to a deck card or a deck card group.
+    pub async fn get_last_answer(
+        &self,
+        edge: impl Into<Thing>,
+    ) -> Result<Option<HistoryRecord>, CoreError> {
+        let query = format!(
+            r#"
+            select * {additional_query}
+                from history
+                where deck_card = $edge or deck_card_group = $edge
+                order by time.created_at desc
+                limit 1
+                {fetch};
+            "#,
+            fetch = self.fetch_statement(),
+            additional_query = self.additional_query
+        );
+
+        let records: Result<Vec<HistoryRecord>, CoreError> =
+            multi_object_query!(self.db, &query, ("edge", edge.into()));
+        Ok(records?.into_iter().next())
+    }
+
+    /// Answers matching `query`, oldest first.
+    pub async fn list_answers(&self, query: StatsQuery) -> Result<Vec<Answer>, CoreError> {
+        let sql = r#"
+        select
+            id,
+            difficulty,
+            time.created_at as created_at,
+            (deck_card.in ?? deck_card_group.in) as deck,
+            (deck_card.out ?? deck_card_group.out) as item,
+            (deck_card.out.title ?? deck_card_group.out.title) as title
+            from history
+            where
+                user = $user and
+                time.created_at >= <datetime> $from and
+                time.created_at < <datetime> $till and
+                (deck_card.in ?? deck_card_group.in) != none and
+                ($deck = none or (deck_card.in ?? deck_card_group.in) = $deck) and
+                ($tag = none or $tag in (deck_card.out.tags ?? deck_card_group.out.tags))
+            order by created_at
+        ;
+        "#;
+
+        multi_object_query!(
+            self.db,
+            sql,
+            ("user", query.user),
+            ("deck", query.deck),
+            ("tag", query.tag),
+            ("from", query.from),
+            ("till", query.till)
+        )
+    }
 }
@@ -4,7 +4,7 @@ use crate::ext::response_ext::ResponseExt;
 use crate::repo::generic_repo::GenericRepo;
 use itertools::Itertools;
 use std::sync::Arc;
-use surrealdb::engine::remote::ws::Client;
+use surrealdb::engine::any::Any;
 use surrealdb::sql::Thing;
 use surrealdb::Surreal;
 use tracing::Span;
@@ -12,7 +12,7 @@ use tracing::Span;
 pub type TagRepo = GenericRepo<CreateTag, Tag, ()>;
 
 impl TagRepo {
-    pub fn new_tag(db: Surreal<Client>, span: Span, enable_transactions: bool) -> Self {
+    pub fn new_tag(db: Surreal<Any>, span: Span, enable_transactions: bool) -> Self {
         Self::new(db, span, "tag", "", "", enable_transactions)
     }
 
@@ -4,13 +4,14 @@ use crate::ext::response_ext::ResponseExt;
 use crate::repo::generic_repo::GenericRepo;
 use crate::single_object_query;
 use std::sync::Arc;
-use surrealdb::engine::remote::ws::Client;
+use surrealdb::engine::any::Any;
+use surrealdb::sql::Thing;
 use surrealdb::Surreal;
 
 pub type UserRepo = GenericRepo<RegisterUser, User, ()>;
 
 impl UserRepo {
-    pub fn new_user(db: Surreal<Client>, span: tracing::Span, enable_transactions: bool) -> Self {
+    pub fn new_user(db: Surreal<Any>, span: tracing::Span, enable_transactions: bool) -> Self {
         Self::new(db, span, "user", "", "", enable_transactions)
     }
     #[tracing::instrument(level = "debug", skip_all, parent = self.span.clone(), err)]
@@ -32,4 +33,23 @@ impl UserRepo {
 
         single_object_query!(self.db, query, ("user", user))
     }
+
+    /// Deletes everything of the user but the user and its bindings. All or nothing is the point
+    /// of this query, it is a transaction regardless of the settings.
+    #[tracing::instrument(level = "info", skip_all, parent = self.span.clone(), err, fields(?id))]
+    pub async fn clear_data(&self, id: Thing) -> Result<(), CoreError> {
+        let query = r#"
+            begin transaction;
+            delete history where user = $user;
+            delete deck_card where in.user = $user;
+            delete deck_card_group where in.user = $user;
+            delete deck, card, card_group, tag, global_settings where user = $user;
+            commit transaction;
+        "#;
+
+        let mut response = self.db.query(query).bind(("user", id)).await?;
+        response.errors_or_ok()?;
+
+        Ok(())
+    }
 }
@@ -0,0 +1,262 @@
+//! Import of a review log into the history of the cards a user already has, e.g. after importing
+//! the notes of an Anki package without their history.
+//!
+//! A review is matched to a deck card or a deck card group by the id of the card or the card
+//! group, then by the front of a card, then by the title of a card or a card group. The cards of a
+//! card group are answered with the group. A review matching more than one of them is skipped, and
+//! so is a review the history already has an answer at the same time for, so importing a log again
+//! only adds the reviews that are new.
+//!
+//! Answers keep the time of the review. Ranking reads the whole history, so only the hide time of
+//! the latest answer is recomputed: a card is hidden for what is left of the interval the tool
+//! scheduled it away for.
+
+use crate::error::CoreError;
+use crate::model::history::CreateHistory;
+use crate::model::time::Time;
+use crate::reexports::db::sql::Thing;
+use crate::review_log::{GradeMapping, Review};
+use crate::store::any::AnyStorage;
+use crate::store::{DeckStore, HistoryStore, Storage};
+use bon::Builder;
+use chrono::{DateTime, Utc};
+use std::collections::{HashMap, HashSet};
+use std::fmt::{Debug, Display, Formatter};
+use std::sync::Arc;
+use surrealdb::sql::Duration;
+
+#[derive(Debug, Clone, Default, Builder)]
+pub struct ImportOptions {
+    #[builder(default)]
+    pub grades: GradeMapping,
+}
+
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct ImportReport {
+    pub reviews: usize,
+    /// Reviews added to the history.
+    pub history: usize,
+    /// Reviews the history already had.
+    pub duplicates: usize,
+    /// Reviews with a grade the mapping doesn't have.
+    pub ungraded: usize,
+    /// Reviews of a card that was not found.
+    pub unmatched: usize,
+    /// Reviews matching several cards or card groups.
+    pub ambiguous: usize,
+    /// Cards and card groups hidden for the rest of their interval.
+    pub hidden: usize,
+}
+
+impl Display for ImportReport {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        writeln!(f, "Reviews: {}", self.reviews)?;
+        writeln!(f, "History records: {}", self.history)?;
+        writeln!(f, "Already in the history: {}", self.duplicates)?;
+        writeln!(f, "Without a difficulty: {}", self.ungraded)?;
+        writeln!(f, "Of unknown cards: {}", self.unmatched)?;
+        writeln!(f, "Of several cards: {}", self.ambiguous)?;
+        write!(f, "Hidden: {}", self.hidden)
+    }
+}
+
+#[derive(Clone)]
+pub struct ReviewImporter<S: Storage = AnyStorage> {
+    pub decks: S::Decks,
+    pub history: S::History,
+}
+
+impl<S: Storage> Debug for ReviewImporter<S> {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        write!(f, "ReviewImporter")
+    }
+}
+
+impl<S: Storage> ReviewImporter<S> {
+    pub fn new(decks: S::Decks, history: S::History) -> Self {
+        Self { decks, history }
+    }
+
+    /// Adds the `reviews` of the cards of `user` to the history, see the [module](self)
+    /// documentation.
+    pub async fn import(
+        &self,
+        user: impl Into<Thing>,
+        mut reviews: Vec<Review>,
+        options: ImportOptions,
+    ) -> Result<ImportReport, CoreError> {
+        let user = user.into();
+        let mut index = Index::default();
+        for deck_card in self.decks.list_deck_cards(user.clone()).await? {
+            let edge = Edge::DeckCard(deck_card.id.clone());
+            let card = &deck_card.card;
+            index.ids.add(card.id.to_string(), &edge);
+            index
+                .fronts
+                .add(card.front.as_deref().unwrap_or_default(), &edge);
+            index.titles.add(&*card.title, &edge);
+        }
+        for deck_card_group in self.decks.list_deck_card_groups(user.clone()).await? {
+            let edge = Edge::DeckCardGroup(deck_card_group.id.clone());
+            let card_group = &deck_card_group.card_group;
+            index.ids.add(card_group.id.to_string(), &edge);
+            index.titles.add(&*card_group.title, &edge);
+            for card in &card_group.cards {
+                index.ids.add(card.id.to_string(), &edge);
+                index
+                    .fronts
+                    .add(card.front.as_deref().unwrap_or_default(), &edge);
+                index.titles.add(&*card.title, &edge);
+            }
+        }
+
+        // the time of the latest answer of every edge and every time it was answered at
+        let mut last_answered = HashMap::<Thing, DateTime<Utc>>::new();
+        let mut answered = HashSet::<(Thing, DateTime<Utc>)>::new();
+        for record in self.history.list_by_user_id(user.clone()).await? {
+            let edge = match (&record.deck_card, &record.deck_card_group) {
+                (Some(deck_card), _) => deck_card.id.clone(),
+                (None, Some(deck_card_group)) => deck_card_group.id.clone(),
+                (None, None) => continue,
+            };
+            let last = last_answered
+                .entry(edge.clone())
+                .or_insert(record.time.created_at);
+            *last = (*last).max(record.time.created_at);
+            answered.insert((edge, record.time.created_at));
+        }
+
+        let mut report = ImportReport::default();
+        let mut answers = vec![];
+        reviews.sort_by_key(|review| review.time);
+        for review in &reviews {
+            report.reviews += 1;
+            let Some(difficulty) = options.grades.difficulty(review.grade) else {
+                report.ungraded += 1;
+                continue;
+            };
+            let edge = match index.find(&review.card) {
+                Found::One(edge) => edge,
+                Found::None => {
+                    report.unmatched += 1;
+                    continue;
+                }
+                Found::Many => {
+                    report.ambiguous += 1;
+                    continue;
+                }
+            };
+            if !answered.insert((edge.id().clone(), review.time)) {
+                report.duplicates += 1;
+                continue;
+            }
+
+            let last = last_answered
+                .entry(edge.id().clone())
+                .or_insert(review.time);
+            *last = (*last).max(review.time);
+            answers.push((edge, review, difficulty));
+        }
+
+        let now = Utc::now();
+        for (edge, review, difficulty) in answers {
+            // the latest answer of the edge, unless the history has a later one
+            let hide_for = if last_answered.get(edge.id()) == Some(&review.time) {
+                last_answered.remove(edge.id());
+                review
+                    .interval
+                    .map(|interval| review.time + interval)
+                    .and_then(|due| (due - now).to_std().ok())
+                    .filter(|left| !left.is_zero())
+                    .map(Duration::from)
+            } else {
+                None
+            };
+            if hide_for.is_some() {
+                report.hidden += 1;
+            }
+
+            let (deck_card, deck_card_group) = match edge {
+                Edge::DeckCard(id) => (Some(id.clone()), None),
+                Edge::DeckCardGroup(id) => (None, Some(id.clone())),
+            };
+            self.history
+                .create_custom(CreateHistory {
+                    user: user.clone(),
+                    deck_card,
+                    deck_card_group,
+                    difficulty,
+                    time: Some(Time {
+                        created_at: review.time,
+                        updated_at: review.time,
+                        deleted_at: None,
+                    }),
+                    hide_for,
+                    steps: vec![],
+                })
+                .await?;
+            report.history += 1;
+        }
+
+        Ok(report)
+    }
+}
+
+/// What a card is answered as: its deck card, or the deck card group of its card group.
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+enum Edge {
+    DeckCard(Thing),
+    DeckCardGroup(Thing),
+}
+
+impl Edge {
+    fn id(&self) -> &Thing {
+        match self {
+            Edge::DeckCard(id) | Edge::DeckCardGroup(id) => id,
+        }
+    }
+}
+
+enum Found<'a> {
+    None,
+    One(&'a Edge),
+    Many,
+}
+
+#[derive(Default)]
+struct Names(HashMap<Arc<str>, Vec<Edge>>);
+
+impl Names {
+    fn add(&mut self, name: impl AsRef<str>, edge: &Edge) {
+        let name = name.as_ref().trim();
+        if name.is_empty() {
+            return;
+        }
+        let edges = self.0.entry(Arc::from(name)).or_default();
+        if !edges.contains(edge) {
+            edges.push(edge.clone());
+        }
+    }
+}
+
+#[derive(Default)]
+struct Index {
+    ids: Names,
+    fronts: Names,
+    titles: Names,
+}
+
+impl Index {
+    /// The first of the ids, fronts and titles with the `name` decides.
+    fn find(&self, name: &str) -> Found<'_> {
+        let name = name.trim();
+        for names in [&self.ids, &self.fronts, &self.titles] {
+            match names.0.get(name).map(Vec::as_slice) {
+                None | Some([]) => continue,
+                Some([edge]) => return Found::One(edge),
+                Some(_) => return Found::Many,
+            }
+        }
+        Found::None
+    }
+}
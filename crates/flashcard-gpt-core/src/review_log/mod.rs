@@ -0,0 +1,242 @@
+//! Review history from other spaced repetition tools, imported into the history of cards that
+//! already exist, see [`import`]. A log is a list of [`Review`]s read from:
+//!
+//! - the review log of an Anki package, see [`from_anki`];
+//! - a CSV or TSV file with the columns `card,timestamp,grade` and an optional `interval` in
+//!   days, see [`from_csv`].
+//!
+//! The grades of a tool are turned into difficulties with a [`GradeMapping`].
+
+pub mod import;
+
+use crate::error::CoreError;
+use crate::grading::MAX_DIFFICULTY;
+use chrono::{DateTime, NaiveDateTime, TimeDelta, Utc};
+use std::collections::BTreeMap;
+use std::fmt::{Display, Formatter};
+use std::str::FromStr;
+use std::sync::Arc;
+
+/// A review of a card in another tool.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Review {
+    /// How the log names the card: its id, like `card:..`, its front or its title.
+    pub card: Arc<str>,
+    pub time: DateTime<Utc>,
+    /// The answer in the scale of the tool, see [`GradeMapping`].
+    pub grade: u8,
+    /// How long the tool scheduled the card away for after this review.
+    pub interval: Option<TimeDelta>,
+}
+
+/// Difficulty of every grade of a tool, from 0 to [`MAX_DIFFICULTY`]. Reviews with a grade that
+/// is not mapped are skipped.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct GradeMapping {
+    difficulties: BTreeMap<u8, u8>,
+}
+
+impl Default for GradeMapping {
+    /// The `ease` of the Anki review log: 1 is "Again", 2 "Hard", 3 "Good" and 4 "Easy". The
+    /// entries that are not answers, like manual rescheduling, have an ease of 0.
+    fn default() -> Self {
+        Self {
+            difficulties: BTreeMap::from([(1, 8), (2, 4), (3, 2), (4, 0)]),
+        }
+    }
+}
+
+impl GradeMapping {
+    /// A mapping without any grade.
+    pub fn empty() -> Self {
+        Self {
+            difficulties: BTreeMap::new(),
+        }
+    }
+
+    pub fn difficulty(&self, grade: u8) -> Option<u8> {
+        self.difficulties.get(&grade).copied()
+    }
+
+    pub fn set(&mut self, grade: u8, difficulty: u8) -> Result<(), CoreError> {
+        if difficulty > MAX_DIFFICULTY {
+            return Err(invalid(format!(
+                "difficulty {difficulty} of grade {grade} is above {MAX_DIFFICULTY}"
+            )));
+        }
+        self.difficulties.insert(grade, difficulty);
+        Ok(())
+    }
+
+    /// Applies an entry like `1=8`.
+    pub fn apply(&mut self, entry: &str) -> Result<(), CoreError> {
+        let parsed = entry.split_once('=').and_then(|(grade, difficulty)| {
+            Some((grade.trim().parse().ok()?, difficulty.trim().parse().ok()?))
+        });
+        let Some((grade, difficulty)) = parsed else {
+            return Err(invalid(format!("expected grade=difficulty, got {entry:?}")));
+        };
+        self.set(grade, difficulty)
+    }
+}
+
+impl FromStr for GradeMapping {
+    type Err = CoreError;
+
+    /// Entries separated by commas, like `0=10,1=7,2=4,3=0`.
+    fn from_str(value: &str) -> Result<Self, Self::Err> {
+        let mut mapping = GradeMapping::empty();
+        for entry in value.split(',').filter(|entry| !entry.trim().is_empty()) {
+            mapping.apply(entry)?;
+        }
+        if mapping.difficulties.is_empty() {
+            return Err(invalid("the grade mapping is empty"));
+        }
+        Ok(mapping)
+    }
+}
+
+impl Display for GradeMapping {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        let entries = self
+            .difficulties
+            .iter()
+            .map(|(grade, difficulty)| format!("{grade}={difficulty}"))
+            .collect::<Vec<_>>();
+        write!(f, "{}", entries.join(","))
+    }
+}
+
+/// The review log of the `.apkg` or `.colpkg` file in `package`, only the cards of notes the
+/// [Anki importer](crate::anki::import) imports are named.
+pub async fn from_anki(package: Vec<u8>) -> Result<Vec<Review>, CoreError> {
+    tokio::task::spawn_blocking(move || crate::anki::import::read_reviews(&package)).await?
+}
+
+/// The rows of a CSV or TSV file with the card, the time and the grade of a review, and
+/// optionally the interval in days. A first row without a numeric grade is a header row.
+///
+/// A time is an RFC 3339 timestamp, a `YYYY-MM-DD HH:MM:SS` time in UTC or a Unix timestamp in
+/// seconds, or in milliseconds when it has 13 digits or more.
+pub fn from_csv(file: &[u8], delimiter: u8) -> Result<Vec<Review>, CoreError> {
+    let mut reader = ::csv::ReaderBuilder::new()
+        .delimiter(delimiter)
+        .has_headers(false)
+        .flexible(true)
+        .from_reader(file);
+
+    let mut reviews = vec![];
+    for (index, record) in reader.records().enumerate() {
+        let record = record?;
+        let field = |index: usize| record.get(index).map(str::trim).unwrap_or_default();
+        if index == 0 && field(2).parse::<u8>().is_err() {
+            continue;
+        }
+
+        let line = record
+            .position()
+            .map_or(index + 1, |position| position.line() as usize);
+        let row_error = |message: String| invalid(format!("line {line}: {message}"));
+        let card = field(0);
+        if card.is_empty() {
+            return Err(row_error("the card is empty".to_string()));
+        }
+        let time = parse_time(field(1))
+            .ok_or_else(|| row_error(format!("invalid time {:?}", field(1))))?;
+        let grade = field(2)
+            .parse()
+            .map_err(|_| row_error(format!("invalid grade {:?}", field(2))))?;
+        let interval = match field(3) {
+            "" => None,
+            days => {
+                let days = days
+                    .parse::<f64>()
+                    .ok()
+                    .filter(|days| days.is_finite() && *days >= 0.0)
+                    .ok_or_else(|| row_error(format!("invalid interval {days:?}")))?;
+                Some(TimeDelta::seconds((days * 86_400.0) as i64))
+            }
+        };
+
+        reviews.push(Review {
+            card: Arc::from(card),
+            time,
+            grade,
+            interval,
+        });
+    }
+    Ok(reviews)
+}
+
+fn parse_time(value: &str) -> Option<DateTime<Utc>> {
+    if let Ok(time) = DateTime::parse_from_rfc3339(value) {
+        return Some(time.to_utc());
+    }
+    if let Ok(time) = NaiveDateTime::parse_from_str(value, "%Y-%m-%d %H:%M:%S") {
+        return Some(time.and_utc());
+    }
+    let timestamp = value.parse::<i64>().ok()?;
+    if value.trim_start_matches('-').len() >= 13 {
+        DateTime::from_timestamp_millis(timestamp)
+    } else {
+        DateTime::from_timestamp(timestamp, 0)
+    }
+}
+
+fn invalid(message: impl Into<String>) -> CoreError {
+    CoreError::InvalidReviewLog(Arc::from(message.into()))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_grade_mapping() {
+        let anki = GradeMapping::default();
+        assert_eq!(anki.difficulty(1), Some(8));
+        assert_eq!(anki.difficulty(4), Some(0));
+        assert_eq!(anki.difficulty(0), None);
+
+        let mapping = "0=10, 3=5,5=0".parse::<GradeMapping>().unwrap();
+        assert_eq!(mapping.difficulty(3), Some(5));
+        assert_eq!(mapping.difficulty(1), None);
+        assert_eq!(mapping.to_string(), "0=10,3=5,5=0");
+
+        assert!("1=11".parse::<GradeMapping>().is_err());
+        assert!("1".parse::<GradeMapping>().is_err());
+        assert!("".parse::<GradeMapping>().is_err());
+    }
+
+    #[test]
+    fn test_from_csv() {
+        let file = "card,timestamp,grade,interval\n\
+                    card:1,2024-01-02T03:04:05Z,3,1.5\n\
+                    hablar,2024-01-02 03:04:05,1,\n\
+                    \"la casa, the house\",1704164645,4\n\
+                    perro,1704164645000,2,0\n";
+        let reviews = from_csv(file.as_bytes(), b',').unwrap();
+        assert_eq!(reviews.len(), 4);
+        assert_eq!(&*reviews[0].card, "card:1");
+        assert_eq!(reviews[0].interval, Some(TimeDelta::hours(36)));
+        assert_eq!(reviews[1].interval, None);
+        assert_eq!(&*reviews[2].card, "la casa, the house");
+        assert!(reviews.iter().all(|review| review.time == reviews[0].time));
+        assert_eq!(reviews[3].grade, 2);
+
+        let without_headers = from_csv(b"hablar\t1704164645\t3", b'\t').unwrap();
+        assert_eq!(without_headers.len(), 1);
+
+        for file in [
+            "hablar,yesterday,3",
+            "hablar,1704164645,3\nperro,1,good",
+            ",1,1",
+        ] {
+            let result = from_csv(file.as_bytes(), b',');
+            assert!(
+                matches!(result, Err(CoreError::InvalidReviewLog(_))),
+                "{file}"
+            );
+        }
+    }
+}
@@ -0,0 +1,147 @@
+//! Per-user database sessions. A session authenticated through the `user` record access runs
+//! every query with `$auth.id` set to that user, so the table permissions of the schema apply on
+//! top of the `where user = $user` filters of the repositories.
+
+use crate::connection::DbSettings;
+use crate::error::CoreError;
+use crate::ext::mutex::MutexExt;
+use chrono::{DateTime, TimeDelta, Utc};
+use jsonwebtoken::{Algorithm, EncodingKey, Header};
+use serde::Serialize;
+use std::collections::HashMap;
+use std::sync::{Arc, Mutex};
+use surrealdb::engine::any::Any;
+use surrealdb::sql::Thing;
+use surrealdb::Surreal;
+use tracing::{info, warn};
+
+/// Name of the record access defined by the initial migration.
+const ACCESS: &str = "user";
+
+/// Matches `DURATION FOR TOKEN` of the access.
+const TOKEN_DURATION: TimeDelta = TimeDelta::minutes(15);
+
+/// Sessions expire after `DURATION FOR SESSION` (12h), cached connections re-authenticate long
+/// before that.
+const SESSION_RENEWAL: TimeDelta = TimeDelta::hours(1);
+
+#[derive(Debug, Serialize)]
+struct RecordClaims<'a> {
+    iss: &'a str,
+    iat: i64,
+    nbf: i64,
+    exp: i64,
+    #[serde(rename = "NS")]
+    ns: &'a str,
+    #[serde(rename = "DB")]
+    db: &'a str,
+    #[serde(rename = "AC")]
+    ac: &'a str,
+    #[serde(rename = "ID")]
+    id: String,
+}
+
+/// Issues a token of the `user` record access for `user`, the same SurrealDB hands out on signin.
+/// Users of bindings never learn their password, so the bot signs the token with the access key.
+pub fn issue_token(settings: &DbSettings, user: &Thing) -> Result<String, CoreError> {
+    let key = settings.access_key.as_ref().ok_or_else(|| {
+        CoreError::InvalidConfig(Arc::from(
+            "SURREALDB_ACCESS_KEY is required for user sessions",
+        ))
+    })?;
+
+    let now = Utc::now();
+    let claims = RecordClaims {
+        iss: "SurrealDB",
+        iat: now.timestamp(),
+        nbf: now.timestamp(),
+        exp: (now + TOKEN_DURATION).timestamp(),
+        ns: &settings.namespace,
+        db: &settings.database,
+        ac: ACCESS,
+        id: user.to_string(),
+    };
+
+    Ok(jsonwebtoken::encode(
+        &Header::new(Algorithm::HS512),
+        &claims,
+        &EncodingKey::from_secret(key.as_bytes()),
+    )?)
+}
+
+/// Switches the session of `db` to `user`. The session is shared by all clones of `db`.
+pub async fn authenticate(
+    db: &Surreal<Any>,
+    settings: &DbSettings,
+    user: &Thing,
+) -> Result<(), CoreError> {
+    let token = issue_token(settings, user)?;
+    db.use_ns(settings.namespace.as_ref())
+        .use_db(settings.database.as_ref())
+        .await?;
+    db.authenticate(token).await?;
+    Ok(())
+}
+
+#[derive(Debug, Clone)]
+struct UserSession {
+    db: Surreal<Any>,
+    authenticated_at: DateTime<Utc>,
+}
+
+/// A connection per user, each authenticated as its user.
+#[derive(Debug, Clone)]
+pub struct UserSessions {
+    settings: DbSettings,
+    sessions: Arc<Mutex<HashMap<Thing, UserSession>>>,
+}
+
+impl UserSessions {
+    /// `None` for embedded engines: every connection to them opens its own datastore, so the
+    /// process keeps working on the connection it migrated. That connection is not authenticated
+    /// as any user, so the table permissions are not enforced and the `where user = $user`
+    /// filters of the repositories are all that keeps users apart.
+    pub fn new(settings: DbSettings) -> Option<Self> {
+        if settings.is_embedded() {
+            warn!(
+                endpoint = %settings.endpoint,
+                "Embedded engines have no user sessions, record permissions are not enforced"
+            );
+            return None;
+        }
+        if settings.access_key.is_none() {
+            return None;
+        }
+
+        Some(Self {
+            settings,
+            sessions: Arc::default(),
+        })
+    }
+
+    /// The connection of `user`, opened on first use.
+    #[tracing::instrument(level = "debug", skip_all, err, fields(%user))]
+    pub async fn connect(&self, user: &Thing) -> Result<Surreal<Any>, CoreError> {
+        let now = Utc::now();
+        let cached = self.sessions.lock_sync()?.get(user).cloned();
+        let db = match cached {
+            Some(session) if now - session.authenticated_at < SESSION_RENEWAL => {
+                return Ok(session.db)
+            }
+            Some(session) => session.db,
+            None => {
+                info!(%user, "Opening user session");
+                self.settings.open().await?
+            }
+        };
+
+        authenticate(&db, &self.settings, user).await?;
+        let session = UserSession {
+            db: db.clone(),
+            authenticated_at: now,
+        };
+        self.sessions.lock_sync()?.insert(user.clone(), session);
+
+        Ok(db)
+    }
+}
@@ -0,0 +1,466 @@
+//! A [`Storage`] whose backend is picked at runtime, the same way `surrealdb::engine::any` picks
+//! an engine. Every repository is an enum over the backends and dispatches each call to the
+//! variant it holds.
+
+use crate::connection::DbSettings;
+use crate::error::CoreError;
+use crate::llm::provider::LlmChoice;
+use crate::model::binding::{Binding, GetOrCreateBinding};
+use crate::model::card::{Card, CreateCard, UpdateCard};
+use crate::model::card_group::{CardGroup, CreateCardGroup, UpdateCardGroup};
+use crate::model::deck::{CreateDeck, Deck};
+use crate::model::deck_card::{CreateCardInDeck, CreateDeckCard, DeckCard};
+use crate::model::deck_card_group::{CreateDeckCardGroup, DeckCardGroup};
+use crate::model::global_settings::{CreateGlobalSettings, GlobalSettings};
+use crate::model::history::{CreateHistory, HistoryRecord};
+use crate::model::stats::{Answer, StatsQuery};
+use crate::model::tag::Tag;
+use crate::model::user::{RegisterUser, User};
+use crate::repo::binding::BindingRepo;
+use crate::repo::card::CardRepo;
+use crate::repo::card_group::CardGroupRepo;
+use crate::repo::deck::DeckRepo;
+use crate::repo::global_settings::GlobalSettingsRepo;
+use crate::repo::history::HistoryRepo;
+use crate::repo::tag::TagRepo;
+use crate::repo::user::UserRepo;
+use crate::session::UserSessions;
+use crate::store::memory::{
+    MemoryBindingRepo, MemoryCardGroupRepo, MemoryCardRepo, MemoryDb, MemoryDeckRepo,
+    MemoryGlobalSettingsRepo, MemoryHistoryRepo, MemoryRepo, MemoryTagRepo, MemoryUserRepo,
+};
+use crate::store::sqlite::{
+    SqliteBindingRepo, SqliteCardGroupRepo, SqliteCardRepo, SqliteDb, SqliteDeckRepo,
+    SqliteGlobalSettingsRepo, SqliteHistoryRepo, SqliteRepo, SqliteTagRepo, SqliteUserRepo,
+};
+use crate::store::{
+    BindingStore, CardGroupStore, CardStore, DeckStore, GlobalSettingsStore, HistoryStore, Storage,
+    TagStore, UserStore,
+};
+use chrono::{DateTime, Utc};
+use std::sync::Arc;
+use surrealdb::engine::any::Any;
+use surrealdb::sql::Thing;
+use surrealdb::Surreal;
+use tracing::Span;
+
+#[derive(Debug, Clone)]
+pub enum StorageSettings {
+    Surreal(DbSettings),
+    Sqlite { path: Arc<str> },
+    Memory,
+}
+
+impl StorageSettings {
+    /// `STORAGE_BACKEND` is one of `surrealdb` (the default), `sqlite` or `memory`. SQLite keeps
+    /// its database at `SQLITE_PATH`.
+    pub fn from_env() -> Result<Self, CoreError> {
+        let backend = std::env::var("STORAGE_BACKEND").ok();
+        match backend.as_deref() {
+            None | Some("surrealdb") => Ok(Self::Surreal(DbSettings::from_env())),
+            Some("sqlite") => Ok(Self::Sqlite {
+                path: std::env::var("SQLITE_PATH")
+                    .map(Arc::from)
+                    .unwrap_or_else(|_| Arc::from("flashcards.sqlite3")),
+            }),
+            Some("memory") => Ok(Self::Memory),
+            Some(other) => Err(CoreError::InvalidConfig(Arc::from(format!(
+                "Unknown STORAGE_BACKEND {other:?}, expected surrealdb, sqlite or memory"
+            )))),
+        }
+    }
+
+    pub async fn connect(&self) -> Result<AnyDb, CoreError> {
+        Ok(match self {
+            Self::Surreal(settings) => AnyDb::Surreal(settings.connect().await?),
+            Self::Sqlite { path } => AnyDb::Sqlite(SqliteDb::open(path.as_ref())?),
+            Self::Memory => AnyDb::Memory(MemoryDb::new()),
+        })
+    }
+
+    /// Sessions scoped to a single user. Only a SurrealDB server has them, the other backends
+    /// rely on the `user` filters of their queries alone.
+    pub fn user_sessions(&self) -> Option<UserSessions> {
+        match self {
+            Self::Surreal(settings) => UserSessions::new(settings.clone()),
+            Self::Sqlite { .. } | Self::Memory => None,
+        }
+    }
+}
+
+/// A connection to any of the backends.
+#[derive(Debug, Clone)]
+pub enum AnyDb {
+    Surreal(Surreal<Any>),
+    Sqlite(SqliteDb),
+    Memory(MemoryDb),
+}
+
+macro_rules! any_repo {
+    ($name:ident, $surreal:ident::$new_surreal:ident, $sqlite:ty, $memory:ty) => {
+        #[derive(Debug, Clone)]
+        pub enum $name {
+            Surreal($surreal),
+            Sqlite($sqlite),
+            Memory($memory),
+        }
+
+        impl $name {
+            pub fn new(db: &AnyDb, span: Span) -> Self {
+                match db {
+                    AnyDb::Surreal(db) => {
+                        Self::Surreal($surreal::$new_surreal(db.clone(), span, true))
+                    }
+                    AnyDb::Sqlite(db) => Self::Sqlite(SqliteRepo::new(db.clone())),
+                    AnyDb::Memory(db) => Self::Memory(MemoryRepo::new(db.clone())),
+                }
+            }
+        }
+    };
+}
+
+macro_rules! dispatch {
+    ($self:ident, $repo:ident => $call:expr) => {
+        match $self {
+            Self::Surreal($repo) => $call.await,
+            Self::Sqlite($repo) => $call.await,
+            Self::Memory($repo) => $call.await,
+        }
+    };
+}
+
+any_repo!(
+    AnyUserRepo,
+    UserRepo::new_user,
+    SqliteUserRepo,
+    MemoryUserRepo
+);
+any_repo!(AnyTagRepo, TagRepo::new_tag, SqliteTagRepo, MemoryTagRepo);
+any_repo!(
+    AnyCardRepo,
+    CardRepo::new_card,
+    SqliteCardRepo,
+    MemoryCardRepo
+);
+any_repo!(
+    AnyCardGroupRepo,
+    CardGroupRepo::new_card_group,
+    SqliteCardGroupRepo,
+    MemoryCardGroupRepo
+);
+any_repo!(
+    AnyDeckRepo,
+    DeckRepo::new_deck,
+    SqliteDeckRepo,
+    MemoryDeckRepo
+);
+any_repo!(
+    AnyHistoryRepo,
+    HistoryRepo::new_history,
+    SqliteHistoryRepo,
+    MemoryHistoryRepo
+);
+any_repo!(
+    AnyBindingRepo,
+    BindingRepo::new_binding,
+    SqliteBindingRepo,
+    MemoryBindingRepo
+);
+any_repo!(
+    AnyGlobalSettingsRepo,
+    GlobalSettingsRepo::new_global_settings,
+    SqliteGlobalSettingsRepo,
+    MemoryGlobalSettingsRepo
+);
+
+#[derive(Debug, Clone)]
+pub struct AnyStorage;
+
+impl Storage for AnyStorage {
+    type Users = AnyUserRepo;
+    type Tags = AnyTagRepo;
+    type Cards = AnyCardRepo;
+    type CardGroups = AnyCardGroupRepo;
+    type Decks = AnyDeckRepo;
+    type History = AnyHistoryRepo;
+    type Bindings = AnyBindingRepo;
+    type GlobalSettings = AnyGlobalSettingsRepo;
+}
+
+impl UserStore for AnyUserRepo {
+    async fn create_user(&self, user: RegisterUser) -> Result<User, CoreError> {
+        dispatch!(self, repo => UserStore::create_user(repo, user))
+    }
+
+    async fn get_by_id(&self, id: impl Into<Thing> + Send) -> Result<User, CoreError> {
+        let id = id.into();
+        dispatch!(self, repo => UserStore::get_by_id(repo, id))
+    }
+
+    async fn clear_data(&self, id: impl Into<Thing> + Send) -> Result<(), CoreError> {
+        let id = id.into();
+        dispatch!(self, repo => UserStore::clear_data(repo, id))
+    }
+}
+
+impl TagStore for AnyTagRepo {
+    async fn get_or_create_tags(
+        &self,
+        user_id: impl Into<Thing> + Send,
+        tags: impl IntoIterator<Item = Arc<str>> + Send,
+    ) -> Result<Vec<Tag>, CoreError> {
+        let user_id = user_id.into();
+        let tags = tags.into_iter().collect::<Vec<_>>();
+        dispatch!(self, repo => TagStore::get_or_create_tags(repo, user_id, tags))
+    }
+
+    async fn list_by_user_id(&self, id: impl Into<Thing> + Send) -> Result<Vec<Tag>, CoreError> {
+        let id = id.into();
+        dispatch!(self, repo => TagStore::list_by_user_id(repo, id))
+    }
+}
+
+impl CardStore for AnyCardRepo {
+    async fn create(&self, dto: CreateCard) -> Result<Card, CoreError> {
+        dispatch!(self, repo => CardStore::create(repo, dto))
+    }
+
+    async fn get_by_id(&self, id: impl Into<Thing> + Send) -> Result<Card, CoreError> {
+        let id = id.into();
+        dispatch!(self, repo => CardStore::get_by_id(repo, id))
+    }
+
+    async fn patch(
+        &self,
+        id: impl Into<Thing> + Send,
+        update: UpdateCard,
+    ) -> Result<Card, CoreError> {
+        let id = id.into();
+        dispatch!(self, repo => CardStore::patch(repo, id, update))
+    }
+
+    async fn list_by_user_id(&self, id: impl Into<Thing> + Send) -> Result<Vec<Card>, CoreError> {
+        let id = id.into();
+        dispatch!(self, repo => CardStore::list_by_user_id(repo, id))
+    }
+
+    async fn delete(&self, id: impl Into<Thing> + Send) -> Result<(), CoreError> {
+        let id = id.into();
+        dispatch!(self, repo => CardStore::delete(repo, id))
+    }
+}
+
+impl CardGroupStore for AnyCardGroupRepo {
+    async fn create(&self, dto: CreateCardGroup) -> Result<CardGroup, CoreError> {
+        dispatch!(self, repo => CardGroupStore::create(repo, dto))
+    }
+
+    async fn get_by_id(&self, id: impl Into<Thing> + Send) -> Result<CardGroup, CoreError> {
+        let id = id.into();
+        dispatch!(self, repo => CardGroupStore::get_by_id(repo, id))
+    }
+
+    async fn patch(
+        &self,
+        id: impl Into<Thing> + Send,
+        update: UpdateCardGroup,
+    ) -> Result<CardGroup, CoreError> {
+        let id = id.into();
+        dispatch!(self, repo => CardGroupStore::patch(repo, id, update))
+    }
+
+    async fn list_by_user_id(
+        &self,
+        id: impl Into<Thing> + Send,
+    ) -> Result<Vec<CardGroup>, CoreError> {
+        let id = id.into();
+        dispatch!(self, repo => CardGroupStore::list_by_user_id(repo, id))
+    }
+
+    async fn delete(&self, id: impl Into<Thing> + Send) -> Result<(), CoreError> {
+        let id = id.into();
+        dispatch!(self, repo => CardGroupStore::delete(repo, id))
+    }
+}
+
+impl DeckStore for AnyDeckRepo {
+    async fn create(&self, dto: CreateDeck) -> Result<Deck, CoreError> {
+        dispatch!(self, repo => DeckStore::create(repo, dto))
+    }
+
+    async fn get_by_id(&self, id: impl Into<Thing> + Send) -> Result<Deck, CoreError> {
+        let id = id.into();
+        dispatch!(self, repo => DeckStore::get_by_id(repo, id))
+    }
+
+    async fn list_by_user_id(&self, id: impl Into<Thing> + Send) -> Result<Vec<Deck>, CoreError> {
+        let id = id.into();
+        dispatch!(self, repo => DeckStore::list_by_user_id(repo, id))
+    }
+
+    async fn relate_card(&self, dto: CreateDeckCard) -> Result<DeckCard, CoreError> {
+        dispatch!(self, repo => DeckStore::relate_card(repo, dto))
+    }
+
+    async fn create_cards(&self, cards: Vec<CreateCardInDeck>) -> Result<Vec<Thing>, CoreError> {
+        dispatch!(self, repo => DeckStore::create_cards(repo, cards))
+    }
+
+    async fn relate_card_group(
+        &self,
+        dto: CreateDeckCardGroup,
+    ) -> Result<DeckCardGroup, CoreError> {
+        dispatch!(self, repo => DeckStore::relate_card_group(repo, dto))
+    }
+
+    async fn list_cards(
+        &self,
+        user: impl Into<Thing> + Send,
+        deck: impl Into<Thing> + Send,
+    ) -> Result<Vec<Card>, CoreError> {
+        let (user, deck) = (user.into(), deck.into());
+        dispatch!(self, repo => DeckStore::list_cards(repo, user, deck))
+    }
+
+    async fn list_card_groups(
+        &self,
+        user: impl Into<Thing> + Send,
+        deck: impl Into<Thing> + Send,
+    ) -> Result<Vec<CardGroup>, CoreError> {
+        let (user, deck) = (user.into(), deck.into());
+        dispatch!(self, repo => DeckStore::list_card_groups(repo, user, deck))
+    }
+
+    async fn list_deck_cards(
+        &self,
+        user: impl Into<Thing> + Send,
+    ) -> Result<Vec<DeckCard>, CoreError> {
+        let user = user.into();
+        dispatch!(self, repo => DeckStore::list_deck_cards(repo, user))
+    }
+
+    async fn list_deck_card_groups(
+        &self,
+        user: impl Into<Thing> + Send,
+    ) -> Result<Vec<DeckCardGroup>, CoreError> {
+        let user = user.into();
+        dispatch!(self, repo => DeckStore::list_deck_card_groups(repo, user))
+    }
+
+    async fn get_deck_card(&self, id: impl Into<Thing> + Send) -> Result<DeckCard, CoreError> {
+        let id = id.into();
+        dispatch!(self, repo => DeckStore::get_deck_card(repo, id))
+    }
+
+    async fn get_deck_card_group(
+        &self,
+        id: impl Into<Thing> + Send,
+    ) -> Result<DeckCardGroup, CoreError> {
+        let id = id.into();
+        dispatch!(self, repo => DeckStore::get_deck_card_group(repo, id))
+    }
+
+    async fn list_top_ranked_cards(
+        &self,
+        user: impl Into<Thing> + Send,
+        since: DateTime<Utc>,
+    ) -> Result<Vec<DeckCard>, CoreError> {
+        let user = user.into();
+        dispatch!(self, repo => DeckStore::list_top_ranked_cards(repo, user, since))
+    }
+
+    async fn list_top_ranked_card_groups(
+        &self,
+        user: impl Into<Thing> + Send,
+        since: DateTime<Utc>,
+    ) -> Result<Vec<DeckCardGroup>, CoreError> {
+        let user = user.into();
+        dispatch!(self, repo => DeckStore::list_top_ranked_card_groups(repo, user, since))
+    }
+}
+
+impl HistoryStore for AnyHistoryRepo {
+    async fn create_custom(&self, dto: CreateHistory) -> Result<HistoryRecord, CoreError> {
+        dispatch!(self, repo => HistoryStore::create_custom(repo, dto))
+    }
+
+    async fn list_by_user_id(
+        &self,
+        id: impl Into<Thing> + Send,
+    ) -> Result<Vec<HistoryRecord>, CoreError> {
+        let id = id.into();
+        dispatch!(self, repo => HistoryStore::list_by_user_id(repo, id))
+    }
+
+    async fn get_last_answer(
+        &self,
+        edge: impl Into<Thing> + Send,
+    ) -> Result<Option<HistoryRecord>, CoreError> {
+        let edge = edge.into();
+        dispatch!(self, repo => HistoryStore::get_last_answer(repo, edge))
+    }
+
+    async fn list_answers(&self, query: StatsQuery) -> Result<Vec<Answer>, CoreError> {
+        dispatch!(self, repo => HistoryStore::list_answers(repo, query))
+    }
+}
+
+impl BindingStore for AnyBindingRepo {
+    async fn get_by_source_id(&self, source_id: Arc<str>) -> Result<Option<Binding>, CoreError> {
+        dispatch!(self, repo => BindingStore::get_by_source_id(repo, source_id))
+    }
+
+    async fn get_or_create_binding(&self, dto: GetOrCreateBinding) -> Result<Binding, CoreError> {
+        dispatch!(self, repo => BindingStore::get_or_create_binding(repo, dto))
+    }
+
+    async fn set_banned(&self, id: impl Into<Thing> + Send) -> Result<Binding, CoreError> {
+        let id = id.into();
+        dispatch!(self, repo => BindingStore::set_banned(repo, id))
+    }
+
+    async fn list_all_not_banned(&self) -> Result<Vec<Binding>, CoreError> {
+        dispatch!(self, repo => BindingStore::list_all_not_banned(repo))
+    }
+}
+
+impl GlobalSettingsStore for AnyGlobalSettingsRepo {
+    async fn create(&self, dto: CreateGlobalSettings) -> Result<GlobalSettings, CoreError> {
+        dispatch!(self, repo => GlobalSettingsStore::create(repo, dto))
+    }
+
+    async fn get_by_user_id(
+        &self,
+        id: impl Into<Thing> + Send,
+    ) -> Result<GlobalSettings, CoreError> {
+        let id = id.into();
+        dispatch!(self, repo => GlobalSettingsStore::get_by_user_id(repo, id))
+    }
+
+    async fn set_render_images(
+        &self,
+        user: impl Into<Thing> + Send,
+        render_images: bool,
+    ) -> Result<GlobalSettings, CoreError> {
+        let user = user.into();
+        dispatch!(self, repo => GlobalSettingsStore::set_render_images(repo, user, render_images))
+    }
+
+    async fn set_multiple_choice(
+        &self,
+        user: impl Into<Thing> + Send,
+        multiple_choice: bool,
+    ) -> Result<GlobalSettings, CoreError> {
+        let user = user.into();
+        dispatch!(self, repo => GlobalSettingsStore::set_multiple_choice(repo, user, multiple_choice))
+    }
+
+    async fn set_llm(
+        &self,
+        user: impl Into<Thing> + Send,
+        llm: Option<LlmChoice>,
+    ) -> Result<GlobalSettings, CoreError> {
+        let user = user.into();
+        dispatch!(self, repo => GlobalSettingsStore::set_llm(repo, user, llm))
+    }
+}
@@ -0,0 +1,1296 @@
+//! In-memory backend for tests. It follows the SurrealDB schema closely: references must point
+//! to existing records, unique indexes are enforced and reads come back fully fetched. Passwords
+//! are stored as given.
+
+use crate::error::CoreError;
+use crate::ext::mutex::MutexExt;
+use crate::llm::provider::LlmChoice;
+use crate::model::annotation::Annotations;
+use crate::model::binding::{Binding, GetOrCreateBinding};
+use crate::model::card::{Card, CreateCard, UpdateCard};
+use crate::model::card_group::{CardGroup, CreateCardGroup, UpdateCardGroup};
+use crate::model::deck::{CreateDeck, Deck};
+use crate::model::deck_card::{CreateCardInDeck, CreateDeckCard, DeckCard};
+use crate::model::deck_card_group::{CreateDeckCardGroup, DeckCardGroup};
+use crate::model::global_settings::{CreateGlobalSettings, GlobalSettings};
+use crate::model::history::{CreateHistory, HistoryRecord};
+use crate::model::stats::{Answer, StatsQuery};
+use crate::model::tag::{CreateTag, Tag};
+use crate::model::time::Time;
+use crate::model::user::{RegisterUser, User};
+use crate::store::rank::{self, TOP_RANKED_LIMIT, TREND_WINDOW};
+use crate::store::{
+    BindingStore, CardGroupStore, CardStore, DeckStore, GlobalSettingsStore, HistoryStore, Storage,
+    TagStore, UserStore,
+};
+use chrono::{DateTime, Utc};
+use itertools::Itertools;
+use serde_json::Value;
+use std::collections::{HashMap, HashSet};
+use std::fmt::Debug;
+use std::marker::PhantomData;
+use std::sync::{Arc, Mutex};
+use surrealdb::sql::{Id, Thing};
+
+#[derive(Debug, Clone)]
+struct Row<T> {
+    id: Thing,
+    dto: T,
+    time: Time,
+}
+
+#[derive(Debug, Clone)]
+struct HistoryRow {
+    dto: CreateHistory,
+    hide_till: Option<DateTime<Utc>>,
+}
+
+#[derive(Debug, Clone)]
+struct BindingRow {
+    source_id: Arc<str>,
+    type_name: Arc<str>,
+    user: Thing,
+    data: Option<Arc<Value>>,
+    banned_bot_at: Option<DateTime<Utc>>,
+}
+
+type Table<T> = HashMap<Thing, Row<T>>;
+
+#[derive(Debug, Default)]
+struct Tables {
+    last_id: i64,
+    users: HashMap<Thing, User>,
+    tags: HashMap<Thing, Tag>,
+    cards: Table<CreateCard>,
+    card_groups: Table<CreateCardGroup>,
+    decks: Table<CreateDeck>,
+    deck_cards: Table<CreateDeckCard>,
+    deck_card_groups: Table<CreateDeckCardGroup>,
+    history: Table<HistoryRow>,
+    bindings: Table<BindingRow>,
+    global_settings: Table<CreateGlobalSettings>,
+    /// Annotations of cards and card groups, which are not part of their create DTOs.
+    annotations: HashMap<Thing, Annotations>,
+}
+
+fn not_found(id: &Thing) -> CoreError {
+    CoreError::NotFound(Arc::from(id.to_string()))
+}
+
+fn get<'a, T>(table: &'a HashMap<Thing, T>, id: &Thing) -> Result<&'a T, CoreError> {
+    table.get(id).ok_or_else(|| not_found(id))
+}
+
+fn ensure_exists<T>(table: &HashMap<Thing, T>, id: &Thing) -> Result<(), CoreError> {
+    get(table, id).map(|_| ())
+}
+
+fn unique_violation(index: &str, value: impl Debug) -> CoreError {
+    CoreError::DbQueryHasErrors(Arc::from(format!(
+        "Index {index} already contains {value:?}"
+    )))
+}
+
+fn new_time(now: DateTime<Utc>) -> Time {
+    Time {
+        created_at: now,
+        updated_at: now,
+        deleted_at: None,
+    }
+}
+
+impl Tables {
+    fn next_id(&mut self, table: &str) -> Thing {
+        self.last_id += 1;
+        Thing::from((table, Id::Number(self.last_id)))
+    }
+
+    fn insert<T>(&mut self, select: fn(&mut Self) -> &mut Table<T>, table: &str, dto: T) -> Thing {
+        let id = self.next_id(table);
+        let row = Row {
+            id: id.clone(),
+            dto,
+            time: new_time(Utc::now()),
+        };
+        select(self).insert(id.clone(), row);
+        id
+    }
+
+    fn user(&self, id: &Thing) -> Result<User, CoreError> {
+        get(&self.users, id).cloned()
+    }
+
+    /// Dangling tags are skipped, the same way `skip_nulls` drops them after a fetch.
+    fn tags(&self, ids: &[Thing]) -> Vec<Arc<Tag>> {
+        ids.iter()
+            .filter_map(|id| self.tags.get(id))
+            .map(|tag| Arc::new(tag.clone()))
+            .collect()
+    }
+
+    fn card(&self, id: &Thing) -> Result<Card, CoreError> {
+        let row = get(&self.cards, id)?;
+        let dto = &row.dto;
+        Ok(Card {
+            id: row.id.clone(),
+            user: Arc::new(self.user(&dto.user)?),
+            title: dto.title.clone(),
+            front: dto.front.clone(),
+            back: dto.back.clone(),
+            data: dto.data.clone(),
+            choices: dto.choices.clone(),
+            sibling: dto.sibling.clone(),
+            hints: dto.hints.clone(),
+            difficulty: dto.difficulty,
+            importance: dto.importance,
+            tags: self.tags(&dto.tags),
+            annotations: self.annotations.get(id).cloned().unwrap_or_default(),
+            time: Some(row.time.clone()),
+        })
+    }
+
+    fn card_group(&self, id: &Thing) -> Result<CardGroup, CoreError> {
+        let row = get(&self.card_groups, id)?;
+        let dto = &row.dto;
+        Ok(CardGroup {
+            id: row.id.clone(),
+            user: self.user(&dto.user)?,
+            importance: dto.importance,
+            difficulty: dto.difficulty,
+            title: dto.title.clone(),
+            data: dto.data.clone(),
+            time: row.time.clone(),
+            cards: dto
+                .cards
+                .iter()
+                .filter_map(|id| self.card(id).ok())
+                .map(Arc::new)
+                .collect(),
+            tags: self.tags(&dto.tags),
+            annotations: self.annotations.get(id).cloned().unwrap_or_default(),
+        })
+    }
+
+    fn deck(&self, id: &Thing) -> Result<Deck, CoreError> {
+        let row = get(&self.decks, id)?;
+        let dto = &row.dto;
+        Ok(Deck {
+            id: row.id.clone(),
+            description: dto.description.clone(),
+            parent: dto.parent.clone(),
+            settings: dto.settings.clone(),
+            tags: self.tags(&dto.tags),
+            time: row.time.clone(),
+            title: dto.title.clone(),
+            user: self.user(&dto.user)?,
+        })
+    }
+
+    fn deck_card(&self, id: &Thing) -> Result<DeckCard, CoreError> {
+        let row = get(&self.deck_cards, id)?;
+        Ok(DeckCard {
+            id: row.id.clone(),
+            deck: Arc::new(self.deck(&row.dto.deck)?),
+            card: Arc::new(self.card(&row.dto.card)?),
+            num_answered: None,
+            time: Some(row.time.clone()),
+        })
+    }
+
+    fn deck_card_group(&self, id: &Thing) -> Result<DeckCardGroup, CoreError> {
+        let row = get(&self.deck_card_groups, id)?;
+        Ok(DeckCardGroup {
+            id: row.id.clone(),
+            deck: Arc::new(self.deck(&row.dto.deck)?),
+            card_group: Arc::new(self.card_group(&row.dto.card_group)?),
+            num_answered: None,
+            time: row.time.clone(),
+        })
+    }
+
+    fn history_record(&self, id: &Thing) -> Result<HistoryRecord, CoreError> {
+        let row = get(&self.history, id)?;
+        let dto = &row.dto.dto;
+        Ok(HistoryRecord {
+            id: row.id.clone(),
+            user: dto.user.clone(),
+            deck_card: dto
+                .deck_card
+                .as_ref()
+                .map(|id| self.deck_card(id).map(Arc::new))
+                .transpose()?,
+            deck_card_group: dto
+                .deck_card_group
+                .as_ref()
+                .map(|id| self.deck_card_group(id).map(Arc::new))
+                .transpose()?,
+            hide_for: dto.hide_for,
+            difficulty: dto.difficulty,
+            steps: dto.steps.clone(),
+            time: row.time.clone(),
+        })
+    }
+
+    /// The answer of a history row with the tags of what it answered.
+    fn answer<'a>(&'a self, row: &Row<HistoryRow>) -> Option<(Answer, &'a [Thing])> {
+        let dto = &row.dto.dto;
+        let (deck, item, title, tags) = if let Some(id) = &dto.deck_card {
+            let deck_card = &self.deck_cards.get(id)?.dto;
+            let card = &self.cards.get(&deck_card.card)?.dto;
+            (&deck_card.deck, &deck_card.card, &card.title, &card.tags)
+        } else {
+            let deck_card_group = &self
+                .deck_card_groups
+                .get(dto.deck_card_group.as_ref()?)?
+                .dto;
+            let card_group = &self.card_groups.get(&deck_card_group.card_group)?.dto;
+            (
+                &deck_card_group.deck,
+                &deck_card_group.card_group,
+                &card_group.title,
+                &card_group.tags,
+            )
+        };
+
+        let answer = Answer {
+            id: row.id.clone(),
+            deck: deck.clone(),
+            item: item.clone(),
+            title: title.clone(),
+            difficulty: dto.difficulty,
+            created_at: row.time.created_at,
+        };
+        Some((answer, tags))
+    }
+
+    fn binding(&self, id: &Thing) -> Result<Binding, CoreError> {
+        let row = get(&self.bindings, id)?;
+        Ok(Binding {
+            id: row.id.clone(),
+            source_id: row.dto.source_id.clone(),
+            type_name: row.dto.type_name.clone(),
+            data: row.dto.data.clone(),
+            user: Arc::new(self.user(&row.dto.user)?),
+            time: row.time.clone(),
+        })
+    }
+
+    fn global_settings(&self, id: &Thing) -> Result<GlobalSettings, CoreError> {
+        let row = get(&self.global_settings, id)?;
+        let dto = &row.dto;
+        Ok(GlobalSettings {
+            id: row.id.clone(),
+            daily_limit: dto.daily_limit,
+            timetable: dto.timetable.clone(),
+            timezone: dto.timezone,
+            render_images: dto.render_images,
+            multiple_choice: dto.multiple_choice,
+            llm: dto.llm.clone(),
+            user: self.user(&dto.user)?,
+            time: row.time.clone(),
+        })
+    }
+
+    fn create_user(&mut self, user: RegisterUser) -> Result<User, CoreError> {
+        if self
+            .users
+            .values()
+            .any(|existing| existing.email == user.email)
+        {
+            return Err(unique_violation("email", &user.email));
+        }
+
+        let id = self.next_id("user");
+        let user = User {
+            id: id.clone(),
+            email: user.email,
+            name: user.name,
+            password: user.password,
+            time: Some(new_time(Utc::now())),
+        };
+        self.users.insert(id, user.clone());
+        Ok(user)
+    }
+
+    /// The id of the tag of `user` with the slug of `name`, created when there is none.
+    fn get_or_create_tag(&mut self, user: &Thing, name: Arc<str>) -> Thing {
+        let slug = Arc::<str>::from(slug::slugify(&name));
+        if let Some(tag) = self
+            .tags
+            .values()
+            .find(|tag| &tag.user == user && tag.slug == slug)
+        {
+            return tag.id.clone();
+        }
+
+        let id = self.next_id("tag");
+        let tag = Tag {
+            id: id.clone(),
+            name,
+            slug,
+            user: user.clone(),
+            time: new_time(Utc::now()),
+        };
+        self.tags.insert(id.clone(), tag);
+        id
+    }
+
+    /// History of a deck card or a deck card group.
+    fn answers_to<'a>(&'a self, edge: &'a Thing) -> impl Iterator<Item = &'a Row<HistoryRow>> {
+        self.history.values().filter(move |row| {
+            row.dto.dto.deck_card.as_ref() == Some(edge)
+                || row.dto.dto.deck_card_group.as_ref() == Some(edge)
+        })
+    }
+
+    /// History of a card or a card group in any deck.
+    fn answers_of<'a>(&'a self, target: &'a Thing) -> impl Iterator<Item = &'a Row<HistoryRow>> {
+        self.history.values().filter(move |row| {
+            let dto = &row.dto.dto;
+            let via_card = dto
+                .deck_card
+                .as_ref()
+                .and_then(|id| self.deck_cards.get(id))
+                .is_some_and(|dc| &dc.dto.card == target);
+            let via_card_group = dto
+                .deck_card_group
+                .as_ref()
+                .and_then(|id| self.deck_card_groups.get(id))
+                .is_some_and(|dcg| &dcg.dto.card_group == target);
+            via_card || via_card_group
+        })
+    }
+
+    fn answered_times(&self, edge: &Thing, since: DateTime<Utc>) -> usize {
+        self.answers_to(edge)
+            .filter(|row| row.time.created_at >= since)
+            .count()
+    }
+
+    /// Whether a card or a card group was answered in any deck since `since`.
+    fn answered_since(&self, target: &Thing, since: DateTime<Utc>) -> bool {
+        self.answers_of(target)
+            .any(|row| row.time.created_at >= since)
+    }
+
+    fn num_answers_for_deck(&self, deck: &Thing, since: DateTime<Utc>) -> usize {
+        self.history
+            .values()
+            .filter(|row| row.time.created_at >= since)
+            .filter_map(|row| {
+                let dto = &row.dto.dto;
+                let deck_card = dto.deck_card.as_ref().filter(|id| {
+                    self.deck_cards
+                        .get(id)
+                        .is_some_and(|dc| &dc.dto.deck == deck)
+                });
+                let deck_card_group = dto.deck_card_group.as_ref().filter(|id| {
+                    self.deck_card_groups
+                        .get(id)
+                        .is_some_and(|dcg| &dcg.dto.deck == deck)
+                });
+                deck_card.or(deck_card_group)
+            })
+            .unique()
+            .count()
+    }
+
+    fn appears_in_card_groups_in_this_deck(&self, card: &Thing, deck: &Thing) -> bool {
+        self.deck_card_groups
+            .values()
+            .filter(|dcg| &dcg.dto.deck == deck)
+            .filter_map(|dcg| self.card_groups.get(&dcg.dto.card_group))
+            .any(|cg| cg.dto.cards.contains(card))
+    }
+
+    fn is_available(
+        &self,
+        edge: &Thing,
+        deck: &Thing,
+        since: DateTime<Utc>,
+        now: DateTime<Utc>,
+    ) -> bool {
+        // settings.daily_limit defaults to 0 in the schema
+        let daily_limit = self
+            .decks
+            .get(deck)
+            .and_then(|deck| deck.dto.settings.as_ref())
+            .map_or(0, |settings| settings.daily_limit);
+        let hidden_till = self
+            .answers_to(edge)
+            .filter_map(|row| row.dto.hide_till)
+            .max();
+
+        self.num_answers_for_deck(deck, since) <= daily_limit
+            && self.answered_times(edge, since) == 0
+            && hidden_till.map_or(true, |hidden_till| hidden_till < now)
+    }
+
+    fn rank(&self, target: &Thing, importance: u8, difficulty: u8, now: DateTime<Utc>) -> f64 {
+        let answers = self
+            .answers_of(target)
+            .map(|row| (row.time.created_at, row.dto.dto.difficulty))
+            .sorted()
+            .collect_vec();
+        let last_answered_at = answers.last().map(|&(created_at, _)| created_at);
+        let trend = answers[answers.len().saturating_sub(TREND_WINDOW)..]
+            .iter()
+            .map(|&(created_at, difficulty)| (created_at.timestamp(), difficulty))
+            .collect_vec();
+
+        rank::rank(
+            importance,
+            difficulty,
+            rank::trend_slope(&trend),
+            rank::since_last(last_answered_at, now),
+        )
+    }
+}
+
+/// Shared state behind every in-memory repository created from it.
+#[derive(Debug, Clone, Default)]
+pub struct MemoryDb {
+    tables: Arc<Mutex<Tables>>,
+}
+
+impl MemoryDb {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    fn read<R>(&self, f: impl FnOnce(&Tables) -> Result<R, CoreError>) -> Result<R, CoreError> {
+        f(&*self.tables.lock_sync()?)
+    }
+
+    fn write<R>(
+        &self,
+        f: impl FnOnce(&mut Tables) -> Result<R, CoreError>,
+    ) -> Result<R, CoreError> {
+        f(&mut *self.tables.lock_sync()?)
+    }
+}
+
+#[derive(Debug)]
+pub struct MemoryRepo<Create, Read, Update> {
+    db: MemoryDb,
+
+    _create_phantom: PhantomData<Create>,
+    _read_phantom: PhantomData<Read>,
+    _update_phantom: PhantomData<Update>,
+}
+
+impl<Create, Read, Update> Clone for MemoryRepo<Create, Read, Update> {
+    fn clone(&self) -> Self {
+        Self::new(self.db.clone())
+    }
+}
+
+impl<Create, Read, Update> MemoryRepo<Create, Read, Update> {
+    pub fn new(db: MemoryDb) -> Self {
+        Self {
+            db,
+            _create_phantom: PhantomData,
+            _read_phantom: PhantomData,
+            _update_phantom: PhantomData,
+        }
+    }
+}
+
+pub type MemoryUserRepo = MemoryRepo<RegisterUser, User, ()>;
+pub type MemoryTagRepo = MemoryRepo<CreateTag, Tag, ()>;
+pub type MemoryCardRepo = MemoryRepo<CreateCard, Card, UpdateCard>;
+pub type MemoryCardGroupRepo = MemoryRepo<CreateCardGroup, CardGroup, UpdateCardGroup>;
+pub type MemoryDeckRepo = MemoryRepo<CreateDeck, Deck, ()>;
+pub type MemoryHistoryRepo = MemoryRepo<CreateHistory, HistoryRecord, ()>;
+pub type MemoryBindingRepo = MemoryRepo<GetOrCreateBinding, Binding, ()>;
+pub type MemoryGlobalSettingsRepo = MemoryRepo<CreateGlobalSettings, GlobalSettings, ()>;
+
+#[derive(Debug, Clone)]
+pub struct MemoryStorage;
+
+impl Storage for MemoryStorage {
+    type Users = MemoryUserRepo;
+    type Tags = MemoryTagRepo;
+    type Cards = MemoryCardRepo;
+    type CardGroups = MemoryCardGroupRepo;
+    type Decks = MemoryDeckRepo;
+    type History = MemoryHistoryRepo;
+    type Bindings = MemoryBindingRepo;
+    type GlobalSettings = MemoryGlobalSettingsRepo;
+}
+
+impl UserStore for MemoryUserRepo {
+    async fn create_user(&self, user: RegisterUser) -> Result<User, CoreError> {
+        self.db.write(|tables| tables.create_user(user))
+    }
+
+    async fn get_by_id(&self, id: impl Into<Thing> + Send) -> Result<User, CoreError> {
+        let id = id.into();
+        self.db.read(|tables| tables.user(&id))
+    }
+
+    async fn clear_data(&self, id: impl Into<Thing> + Send) -> Result<(), CoreError> {
+        let id = id.into();
+        self.db.write(|tables| {
+            ensure_exists(&tables.users, &id)?;
+            let decks = tables
+                .decks
+                .values()
+                .filter(|row| row.dto.user == id)
+                .map(|row| row.id.clone())
+                .collect::<HashSet<_>>();
+
+            tables.history.retain(|_, row| row.dto.dto.user != id);
+            tables
+                .deck_cards
+                .retain(|_, row| !decks.contains(&row.dto.deck));
+            tables
+                .deck_card_groups
+                .retain(|_, row| !decks.contains(&row.dto.deck));
+            tables.decks.retain(|_, row| row.dto.user != id);
+            tables.cards.retain(|_, row| row.dto.user != id);
+            tables.card_groups.retain(|_, row| row.dto.user != id);
+            tables.tags.retain(|_, tag| tag.user != id);
+            tables.global_settings.retain(|_, row| row.dto.user != id);
+            let Tables {
+                cards,
+                card_groups,
+                annotations,
+                ..
+            } = tables;
+            annotations.retain(|id, _| cards.contains_key(id) || card_groups.contains_key(id));
+            Ok(())
+        })
+    }
+}
+
+impl TagStore for MemoryTagRepo {
+    async fn get_or_create_tags(
+        &self,
+        user_id: impl Into<Thing> + Send,
+        tags: impl IntoIterator<Item = Arc<str>> + Send,
+    ) -> Result<Vec<Tag>, CoreError> {
+        let user_id = user_id.into();
+        let tags = tags.into_iter().unique().collect_vec();
+
+        self.db.write(|tables| {
+            ensure_exists(&tables.users, &user_id)?;
+
+            let ids = tags
+                .into_iter()
+                .map(|name| tables.get_or_create_tag(&user_id, name))
+                .collect_vec();
+
+            Ok(tables
+                .tags
+                .values()
+                .filter(|tag| ids.contains(&tag.id))
+                .sorted_by(|a, b| a.slug.cmp(&b.slug))
+                .cloned()
+                .collect())
+        })
+    }
+
+    async fn list_by_user_id(&self, id: impl Into<Thing> + Send) -> Result<Vec<Tag>, CoreError> {
+        let id = id.into();
+        self.db.read(|tables| {
+            Ok(tables
+                .tags
+                .values()
+                .filter(|tag| tag.user == id)
+                .cloned()
+                .collect())
+        })
+    }
+}
+
+impl CardStore for MemoryCardRepo {
+    async fn create(&self, dto: CreateCard) -> Result<Card, CoreError> {
+        if let Some(data) = &dto.data {
+            data.validate()?;
+        }
+        self.db.write(|tables| {
+            ensure_exists(&tables.users, &dto.user)?;
+            let id = tables.insert(|tables| &mut tables.cards, "card", dto);
+            tables.card(&id)
+        })
+    }
+
+    async fn get_by_id(&self, id: impl Into<Thing> + Send) -> Result<Card, CoreError> {
+        let id = id.into();
+        self.db.read(|tables| tables.card(&id))
+    }
+
+    async fn patch(
+        &self,
+        id: impl Into<Thing> + Send,
+        update: UpdateCard,
+    ) -> Result<Card, CoreError> {
+        let id = id.into();
+        self.db.write(|tables| {
+            let row = tables.cards.get_mut(&id).ok_or_else(|| not_found(&id))?;
+            if let Some(importance) = update.importance {
+                row.dto.importance = importance;
+            }
+            if let Some(difficulty) = update.difficulty {
+                row.dto.difficulty = difficulty;
+            }
+            if let Some(sibling) = update.sibling {
+                row.dto.sibling = Some(sibling);
+            }
+            if let Some(title) = update.title {
+                row.dto.title = title;
+            }
+            if let Some(front) = update.front {
+                row.dto.front = Some(front);
+            }
+            if let Some(back) = update.back {
+                row.dto.back = Some(back);
+            }
+            if let Some(hints) = update.hints {
+                row.dto.hints = hints;
+            }
+            if let Some(tags) = update.tags {
+                row.dto.tags = tags;
+            }
+            row.time.updated_at = Utc::now();
+            if let Some(annotations) = update.annotations {
+                tables.annotations.insert(id.clone(), annotations);
+            }
+            tables.card(&id)
+        })
+    }
+
+    async fn list_by_user_id(&self, id: impl Into<Thing> + Send) -> Result<Vec<Card>, CoreError> {
+        let id = id.into();
+        self.db.read(|tables| {
+            tables
+                .cards
+                .values()
+                .filter(|row| row.dto.user == id)
+                .map(|row| tables.card(&row.id))
+                .collect()
+        })
+    }
+
+    async fn delete(&self, id: impl Into<Thing> + Send) -> Result<(), CoreError> {
+        let id = id.into();
+        self.db.write(|tables| {
+            tables.cards.remove(&id);
+            tables.annotations.remove(&id);
+            Ok(())
+        })
+    }
+}
+
+impl CardGroupStore for MemoryCardGroupRepo {
+    async fn create(&self, dto: CreateCardGroup) -> Result<CardGroup, CoreError> {
+        if let Some(data) = &dto.data {
+            data.validate()?;
+        }
+        self.db.write(|tables| {
+            ensure_exists(&tables.users, &dto.user)?;
+            let id = tables.insert(|tables| &mut tables.card_groups, "card_group", dto);
+            tables.card_group(&id)
+        })
+    }
+
+    async fn get_by_id(&self, id: impl Into<Thing> + Send) -> Result<CardGroup, CoreError> {
+        let id = id.into();
+        self.db.read(|tables| tables.card_group(&id))
+    }
+
+    async fn patch(
+        &self,
+        id: impl Into<Thing> + Send,
+        update: UpdateCardGroup,
+    ) -> Result<CardGroup, CoreError> {
+        let id = id.into();
+        self.db.write(|tables| {
+            let row = tables
+                .card_groups
+                .get_mut(&id)
+                .ok_or_else(|| not_found(&id))?;
+            if let Some(importance) = update.importance {
+                row.dto.importance = importance;
+            }
+            if let Some(difficulty) = update.difficulty {
+                row.dto.difficulty = difficulty;
+            }
+            if let Some(cards) = update.cards {
+                row.dto.cards = cards;
+            }
+            if let Some(title) = update.title {
+                row.dto.title = title;
+            }
+            if let Some(tags) = update.tags {
+                row.dto.tags = tags;
+            }
+            row.time.updated_at = Utc::now();
+            if let Some(annotations) = update.annotations {
+                tables.annotations.insert(id.clone(), annotations);
+            }
+            tables.card_group(&id)
+        })
+    }
+
+    async fn list_by_user_id(
+        &self,
+        id: impl Into<Thing> + Send,
+    ) -> Result<Vec<CardGroup>, CoreError> {
+        let id = id.into();
+        self.db.read(|tables| {
+            tables
+                .card_groups
+                .values()
+                .filter(|row| row.dto.user == id)
+                .map(|row| tables.card_group(&row.id))
+                .collect()
+        })
+    }
+
+    async fn delete(&self, id: impl Into<Thing> + Send) -> Result<(), CoreError> {
+        let id = id.into();
+        self.db.write(|tables| {
+            tables.card_groups.remove(&id);
+            tables.annotations.remove(&id);
+            Ok(())
+        })
+    }
+}
+
+impl DeckStore for MemoryDeckRepo {
+    async fn create(&self, dto: CreateDeck) -> Result<Deck, CoreError> {
+        self.db.write(|tables| {
+            ensure_exists(&tables.users, &dto.user)?;
+            if let Some(parent) = &dto.parent {
+                ensure_exists(&tables.decks, parent)?;
+            }
+            let id = tables.insert(|tables| &mut tables.decks, "deck", dto);
+            tables.deck(&id)
+        })
+    }
+
+    async fn get_by_id(&self, id: impl Into<Thing> + Send) -> Result<Deck, CoreError> {
+        let id = id.into();
+        self.db.read(|tables| tables.deck(&id))
+    }
+
+    async fn list_by_user_id(&self, id: impl Into<Thing> + Send) -> Result<Vec<Deck>, CoreError> {
+        let id = id.into();
+        self.db.read(|tables| {
+            tables
+                .decks
+                .values()
+                .filter(|row| row.dto.user == id)
+                .map(|row| tables.deck(&row.id))
+                .collect()
+        })
+    }
+
+    async fn relate_card(&self, dto: CreateDeckCard) -> Result<DeckCard, CoreError> {
+        self.db.write(|tables| {
+            ensure_exists(&tables.decks, &dto.deck)?;
+            ensure_exists(&tables.cards, &dto.card)?;
+            let exists = tables
+                .deck_cards
+                .values()
+                .any(|row| row.dto.deck == dto.deck && row.dto.card == dto.card);
+            if exists {
+                return Err(unique_violation("unique_in_out", (&dto.deck, &dto.card)));
+            }
+            let id = tables.insert(|tables| &mut tables.deck_cards, "deck_card", dto);
+            tables.deck_card(&id)
+        })
+    }
+
+    async fn create_cards(&self, cards: Vec<CreateCardInDeck>) -> Result<Vec<Thing>, CoreError> {
+        for dto in &cards {
+            if let Some(data) = &dto.card.data {
+                data.validate()?;
+            }
+        }
+        self.db.write(|tables| {
+            // everything is checked before the first insert, so a failure leaves no trace
+            for dto in &cards {
+                ensure_exists(&tables.users, &dto.card.user)?;
+                ensure_exists(&tables.decks, &dto.deck)?;
+            }
+            let mut ids = vec![];
+            for CreateCardInDeck {
+                deck,
+                mut card,
+                tag_names,
+            } in cards
+            {
+                for name in tag_names {
+                    let tag = tables.get_or_create_tag(&card.user, name);
+                    if !card.tags.contains(&tag) {
+                        card.tags.push(tag);
+                    }
+                }
+                let card = tables.insert(|tables| &mut tables.cards, "card", card);
+                let dto = CreateDeckCard {
+                    deck,
+                    card: card.clone(),
+                };
+                tables.insert(|tables| &mut tables.deck_cards, "deck_card", dto);
+                ids.push(card);
+            }
+            Ok(ids)
+        })
+    }
+
+    async fn relate_card_group(
+        &self,
+        dto: CreateDeckCardGroup,
+    ) -> Result<DeckCardGroup, CoreError> {
+        self.db.write(|tables| {
+            ensure_exists(&tables.decks, &dto.deck)?;
+            ensure_exists(&tables.card_groups, &dto.card_group)?;
+            let exists = tables
+                .deck_card_groups
+                .values()
+                .any(|row| row.dto.deck == dto.deck && row.dto.card_group == dto.card_group);
+            if exists {
+                return Err(unique_violation(
+                    "unique_in_out",
+                    (&dto.deck, &dto.card_group),
+                ));
+            }
+            let id = tables.insert(
+                |tables| &mut tables.deck_card_groups,
+                "deck_card_group",
+                dto,
+            );
+            tables.deck_card_group(&id)
+        })
+    }
+
+    async fn list_cards(
+        &self,
+        user: impl Into<Thing> + Send,
+        deck: impl Into<Thing> + Send,
+    ) -> Result<Vec<Card>, CoreError> {
+        let (user, deck) = (user.into(), deck.into());
+        self.db.read(|tables| {
+            let owned = tables
+                .decks
+                .get(&deck)
+                .is_some_and(|row| row.dto.user == user);
+            if !owned {
+                return Ok(vec![]);
+            }
+
+            let cards = tables
+                .deck_cards
+                .values()
+                .filter(|row| row.dto.deck == deck)
+                .map(|row| tables.card(&row.dto.card))
+                .collect::<Result<Vec<_>, _>>()?;
+
+            Ok(cards
+                .into_iter()
+                .sorted_by(|a, b| a.title.cmp(&b.title))
+                .collect())
+        })
+    }
+
+    async fn list_card_groups(
+        &self,
+        user: impl Into<Thing> + Send,
+        deck: impl Into<Thing> + Send,
+    ) -> Result<Vec<CardGroup>, CoreError> {
+        let (user, deck) = (user.into(), deck.into());
+        self.db.read(|tables| {
+            let owned = tables
+                .decks
+                .get(&deck)
+                .is_some_and(|row| row.dto.user == user);
+            if !owned {
+                return Ok(vec![]);
+            }
+
+            let card_groups = tables
+                .deck_card_groups
+                .values()
+                .filter(|row| row.dto.deck == deck)
+                .map(|row| tables.card_group(&row.dto.card_group))
+                .collect::<Result<Vec<_>, _>>()?;
+
+            Ok(card_groups
+                .into_iter()
+                .sorted_by(|a, b| a.title.cmp(&b.title))
+                .collect())
+        })
+    }
+
+    async fn list_deck_cards(
+        &self,
+        user: impl Into<Thing> + Send,
+    ) -> Result<Vec<DeckCard>, CoreError> {
+        let user = user.into();
+        self.db.read(|tables| {
+            tables
+                .deck_cards
+                .values()
+                .filter(|row| {
+                    tables
+                        .decks
+                        .get(&row.dto.deck)
+                        .is_some_and(|deck| deck.dto.user == user)
+                })
+                .map(|row| tables.deck_card(&row.id))
+                .collect()
+        })
+    }
+
+    async fn list_deck_card_groups(
+        &self,
+        user: impl Into<Thing> + Send,
+    ) -> Result<Vec<DeckCardGroup>, CoreError> {
+        let user = user.into();
+        self.db.read(|tables| {
+            tables
+                .deck_card_groups
+                .values()
+                .filter(|row| {
+                    tables
+                        .decks
+                        .get(&row.dto.deck)
+                        .is_some_and(|deck| deck.dto.user == user)
+                })
+                .map(|row| tables.deck_card_group(&row.id))
+                .collect()
+        })
+    }
+
+    async fn get_deck_card(&self, id: impl Into<Thing> + Send) -> Result<DeckCard, CoreError> {
+        let id = id.into();
+        self.db.read(|tables| tables.deck_card(&id))
+    }
+
+    async fn get_deck_card_group(
+        &self,
+        id: impl Into<Thing> + Send,
+    ) -> Result<DeckCardGroup, CoreError> {
+        let id = id.into();
+        self.db.read(|tables| tables.deck_card_group(&id))
+    }
+
+    async fn list_top_ranked_cards(
+        &self,
+        user: impl Into<Thing> + Send,
+        since: DateTime<Utc>,
+    ) -> Result<Vec<DeckCard>, CoreError> {
+        let user = user.into();
+        let now = Utc::now();
+        self.db.read(|tables| {
+            tables
+                .deck_cards
+                .values()
+                .filter_map(|row| {
+                    let card = &tables.cards.get(&row.dto.card)?.dto;
+                    let available = card.user == user
+                        && !tables
+                            .appears_in_card_groups_in_this_deck(&row.dto.card, &row.dto.deck)
+                        && !card
+                            .sibling
+                            .as_ref()
+                            .is_some_and(|sibling| tables.answered_since(sibling, since))
+                        && tables.is_available(&row.id, &row.dto.deck, since, now);
+                    available.then(|| {
+                        let rank =
+                            tables.rank(&row.dto.card, card.importance, card.difficulty, now);
+                        (rank, row)
+                    })
+                })
+                .sorted_by(|(a, _), (b, _)| b.total_cmp(a))
+                .take(TOP_RANKED_LIMIT)
+                .map(|(_, row)| {
+                    let mut deck_card = tables.deck_card(&row.id)?;
+                    deck_card.num_answered = Some(tables.answered_times(&row.id, since));
+                    Ok(deck_card)
+                })
+                .collect()
+        })
+    }
+
+    async fn list_top_ranked_card_groups(
+        &self,
+        user: impl Into<Thing> + Send,
+        since: DateTime<Utc>,
+    ) -> Result<Vec<DeckCardGroup>, CoreError> {
+        let user = user.into();
+        let now = Utc::now();
+        self.db.read(|tables| {
+            tables
+                .deck_card_groups
+                .values()
+                .filter_map(|row| {
+                    let card_group = &tables.card_groups.get(&row.dto.card_group)?.dto;
+                    let available = card_group.user == user
+                        && tables.is_available(&row.id, &row.dto.deck, since, now);
+                    available.then(|| {
+                        let rank = tables.rank(
+                            &row.dto.card_group,
+                            card_group.importance,
+                            card_group.difficulty,
+                            now,
+                        );
+                        (rank, row)
+                    })
+                })
+                .sorted_by(|(a, _), (b, _)| b.total_cmp(a))
+                .take(TOP_RANKED_LIMIT)
+                .map(|(_, row)| {
+                    let mut deck_card_group = tables.deck_card_group(&row.id)?;
+                    deck_card_group.num_answered = Some(tables.answered_times(&row.id, since));
+                    Ok(deck_card_group)
+                })
+                .collect()
+        })
+    }
+}
+
+impl HistoryStore for MemoryHistoryRepo {
+    async fn create_custom(&self, dto: CreateHistory) -> Result<HistoryRecord, CoreError> {
+        self.db.write(|tables| {
+            ensure_exists(&tables.users, &dto.user)?;
+            if let Some(deck_card) = &dto.deck_card {
+                ensure_exists(&tables.deck_cards, deck_card)?;
+            }
+            if let Some(deck_card_group) = &dto.deck_card_group {
+                ensure_exists(&tables.deck_card_groups, deck_card_group)?;
+            }
+
+            let now = Utc::now();
+            let hide_till = dto
+                .hide_for
+                .map(|hide_for| chrono::Duration::from_std(hide_for.0))
+                .transpose()
+                .map_err(|err| CoreError::DbQueryHasErrors(Arc::from(err.to_string())))?
+                .map(|hide_for| now + hide_for);
+            let time = dto.time.clone().unwrap_or_else(|| new_time(now));
+
+            let id = tables.next_id("history");
+            let row = Row {
+                id: id.clone(),
+                dto: HistoryRow { dto, hide_till },
+                time,
+            };
+            tables.history.insert(id.clone(), row);
+            tables.history_record(&id)
+        })
+    }
+
+    async fn list_by_user_id(
+        &self,
+        id: impl Into<Thing> + Send,
+    ) -> Result<Vec<HistoryRecord>, CoreError> {
+        let id = id.into();
+        self.db.read(|tables| {
+            tables
+                .history
+                .values()
+                .filter(|row| row.dto.dto.user == id)
+                .map(|row| tables.history_record(&row.id))
+                .collect()
+        })
+    }
+
+    async fn get_last_answer(
+        &self,
+        edge: impl Into<Thing> + Send,
+    ) -> Result<Option<HistoryRecord>, CoreError> {
+        let edge = edge.into();
+        self.db.read(|tables| {
+            tables
+                .answers_to(&edge)
+                .max_by_key(|row| row.time.created_at)
+                .map(|row| tables.history_record(&row.id))
+                .transpose()
+        })
+    }
+
+    async fn list_answers(&self, query: StatsQuery) -> Result<Vec<Answer>, CoreError> {
+        self.db.read(|tables| {
+            Ok(tables
+                .history
+                .values()
+                .filter(|row| row.dto.dto.user == query.user)
+                .filter(|row| (query.from..query.till).contains(&row.time.created_at))
+                .filter_map(|row| tables.answer(row))
+                .filter(|(answer, _)| {
+                    query
+                        .deck
+                        .as_ref()
+                        .map_or(true, |deck| &answer.deck == deck)
+                })
+                .filter(|(_, tags)| query.tag.as_ref().map_or(true, |tag| tags.contains(tag)))
+                .map(|(answer, _)| answer)
+                .sorted_by_key(|answer| answer.created_at)
+                .collect())
+        })
+    }
+}
+
+impl BindingStore for MemoryBindingRepo {
+    async fn get_by_source_id(&self, source_id: Arc<str>) -> Result<Option<Binding>, CoreError> {
+        self.db.read(|tables| {
+            tables
+                .bindings
+                .values()
+                .find(|row| row.dto.source_id == source_id)
+                .map(|row| tables.binding(&row.id))
+                .transpose()
+        })
+    }
+
+    async fn get_or_create_binding(&self, dto: GetOrCreateBinding) -> Result<Binding, CoreError> {
+        self.db.write(|tables| {
+            let existing = tables
+                .bindings
+                .values()
+                .find(|row| row.dto.source_id == dto.source_id)
+                .map(|row| row.id.clone());
+            if let Some(id) = existing {
+                return tables.binding(&id);
+            }
+
+            let existing_user = tables
+                .users
+                .values()
+                .find(|user| user.email == dto.email)
+                .map(|user| user.id.clone());
+            let user = match existing_user {
+                Some(user) => user,
+                None => {
+                    tables
+                        .create_user(RegisterUser {
+                            email: dto.email,
+                            name: dto.name,
+                            password: dto.password,
+                        })?
+                        .id
+                }
+            };
+
+            let row = BindingRow {
+                source_id: dto.source_id,
+                type_name: dto.type_name,
+                user,
+                data: dto.data.map(Arc::new),
+                banned_bot_at: None,
+            };
+            let id = tables.insert(|tables| &mut tables.bindings, "binding", row);
+            tables.binding(&id)
+        })
+    }
+
+    async fn set_banned(&self, id: impl Into<Thing> + Send) -> Result<Binding, CoreError> {
+        let id = id.into();
+        self.db.write(|tables| {
+            let row = tables.bindings.get_mut(&id).ok_or_else(|| not_found(&id))?;
+            row.dto.banned_bot_at = Some(Utc::now());
+            tables.binding(&id)
+        })
+    }
+
+    async fn list_all_not_banned(&self) -> Result<Vec<Binding>, CoreError> {
+        self.db.read(|tables| {
+            tables
+                .bindings
+                .values()
+                .filter(|row| row.dto.banned_bot_at.is_none())
+                .map(|row| tables.binding(&row.id))
+                .collect()
+        })
+    }
+}
+
+impl GlobalSettingsStore for MemoryGlobalSettingsRepo {
+    async fn create(&self, dto: CreateGlobalSettings) -> Result<GlobalSettings, CoreError> {
+        self.db.write(|tables| {
+            ensure_exists(&tables.users, &dto.user)?;
+            let exists = tables
+                .global_settings
+                .values()
+                .any(|row| row.dto.user == dto.user);
+            if exists {
+                return Err(unique_violation("unique_for_user", &dto.user));
+            }
+            let id = tables.insert(|tables| &mut tables.global_settings, "global_settings", dto);
+            tables.global_settings(&id)
+        })
+    }
+
+    async fn get_by_user_id(
+        &self,
+        id: impl Into<Thing> + Send,
+    ) -> Result<GlobalSettings, CoreError> {
+        let id = id.into();
+        self.db.read(|tables| {
+            let row = tables
+                .global_settings
+                .values()
+                .find(|row| row.dto.user == id)
+                .ok_or_else(|| not_found(&id))?;
+            tables.global_settings(&row.id)
+        })
+    }
+
+    async fn set_render_images(
+        &self,
+        user: impl Into<Thing> + Send,
+        render_images: bool,
+    ) -> Result<GlobalSettings, CoreError> {
+        let user = user.into();
+        self.db.write(|tables| {
+            let row = tables
+                .global_settings
+                .values_mut()
+                .find(|row| row.dto.user == user)
+                .ok_or_else(|| not_found(&user))?;
+            row.dto.render_images = render_images;
+            let id = row.id.clone();
+            tables.global_settings(&id)
+        })
+    }
+
+    async fn set_multiple_choice(
+        &self,
+        user: impl Into<Thing> + Send,
+        multiple_choice: bool,
+    ) -> Result<GlobalSettings, CoreError> {
+        let user = user.into();
+        self.db.write(|tables| {
+            let row = tables
+                .global_settings
+                .values_mut()
+                .find(|row| row.dto.user == user)
+                .ok_or_else(|| not_found(&user))?;
+            row.dto.multiple_choice = multiple_choice;
+            let id = row.id.clone();
+            tables.global_settings(&id)
+        })
+    }
+
+    async fn set_llm(
+        &self,
+        user: impl Into<Thing> + Send,
+        llm: Option<LlmChoice>,
+    ) -> Result<GlobalSettings, CoreError> {
+        let user = user.into();
+        self.db.write(|tables| {
+            let row = tables
+                .global_settings
+                .values_mut()
+                .find(|row| row.dto.user == user)
+                .ok_or_else(|| not_found(&user))?;
+            row.dto.llm = llm;
+            let id = row.id.clone();
+            tables.global_settings(&id)
+        })
+    }
+}
@@ -0,0 +1,283 @@
+use crate::error::CoreError;
+use crate::llm::provider::LlmChoice;
+use crate::model::binding::{Binding, GetOrCreateBinding};
+use crate::model::card::{Card, CreateCard, UpdateCard};
+use crate::model::card_group::{CardGroup, CreateCardGroup, UpdateCardGroup};
+use crate::model::deck::{CreateDeck, Deck};
+use crate::model::deck_card::{CreateCardInDeck, CreateDeckCard, DeckCard};
+use crate::model::deck_card_group::{CreateDeckCardGroup, DeckCardGroup};
+use crate::model::global_settings::{CreateGlobalSettings, GlobalSettings};
+use crate::model::history::{CreateHistory, HistoryRecord};
+use crate::model::stats::{Answer, StatsQuery};
+use crate::model::tag::Tag;
+use crate::model::user::{RegisterUser, User};
+use chrono::Utc;
+use std::fmt::Debug;
+use std::future::Future;
+use std::sync::Arc;
+use surrealdb::sql::Thing;
+
+pub mod any;
+pub mod memory;
+pub mod rank;
+pub mod siblings;
+pub mod sqlite;
+pub mod stats;
+pub mod surreal;
+
+pub trait UserStore: Send + Sync {
+    fn create_user(
+        &self,
+        user: RegisterUser,
+    ) -> impl Future<Output = Result<User, CoreError>> + Send;
+
+    fn get_by_id(
+        &self,
+        id: impl Into<Thing> + Send,
+    ) -> impl Future<Output = Result<User, CoreError>> + Send;
+
+    /// Deletes the tags, decks, cards, card groups, history and global settings of the user in a
+    /// single transaction. The user and its bindings are kept.
+    fn clear_data(
+        &self,
+        id: impl Into<Thing> + Send,
+    ) -> impl Future<Output = Result<(), CoreError>> + Send;
+}
+
+pub trait TagStore: Send + Sync {
+    fn get_or_create_tags(
+        &self,
+        user_id: impl Into<Thing> + Send,
+        tags: impl IntoIterator<Item = Arc<str>> + Send,
+    ) -> impl Future<Output = Result<Vec<Tag>, CoreError>> + Send;
+
+    fn list_by_user_id(
+        &self,
+        id: impl Into<Thing> + Send,
+    ) -> impl Future<Output = Result<Vec<Tag>, CoreError>> + Send;
+}
+
+pub trait CardStore: Send + Sync {
+    fn create(&self, dto: CreateCard) -> impl Future<Output = Result<Card, CoreError>> + Send;
+
+    fn get_by_id(
+        &self,
+        id: impl Into<Thing> + Send,
+    ) -> impl Future<Output = Result<Card, CoreError>> + Send;
+
+    fn patch(
+        &self,
+        id: impl Into<Thing> + Send,
+        update: UpdateCard,
+    ) -> impl Future<Output = Result<Card, CoreError>> + Send;
+
+    fn list_by_user_id(
+        &self,
+        id: impl Into<Thing> + Send,
+    ) -> impl Future<Output = Result<Vec<Card>, CoreError>> + Send;
+
+    fn delete(
+        &self,
+        id: impl Into<Thing> + Send,
+    ) -> impl Future<Output = Result<(), CoreError>> + Send;
+}
+
+pub trait CardGroupStore: Send + Sync {
+    fn create(
+        &self,
+        dto: CreateCardGroup,
+    ) -> impl Future<Output = Result<CardGroup, CoreError>> + Send;
+
+    fn get_by_id(
+        &self,
+        id: impl Into<Thing> + Send,
+    ) -> impl Future<Output = Result<CardGroup, CoreError>> + Send;
+
+    fn patch(
+        &self,
+        id: impl Into<Thing> + Send,
+        update: UpdateCardGroup,
+    ) -> impl Future<Output = Result<CardGroup, CoreError>> + Send;
+
+    fn list_by_user_id(
+        &self,
+        id: impl Into<Thing> + Send,
+    ) -> impl Future<Output = Result<Vec<CardGroup>, CoreError>> + Send;
+
+    fn delete(
+        &self,
+        id: impl Into<Thing> + Send,
+    ) -> impl Future<Output = Result<(), CoreError>> + Send;
+}
+
+pub trait DeckStore: Send + Sync {
+    fn create(&self, dto: CreateDeck) -> impl Future<Output = Result<Deck, CoreError>> + Send;
+
+    fn get_by_id(
+        &self,
+        id: impl Into<Thing> + Send,
+    ) -> impl Future<Output = Result<Deck, CoreError>> + Send;
+
+    fn list_by_user_id(
+        &self,
+        id: impl Into<Thing> + Send,
+    ) -> impl Future<Output = Result<Vec<Deck>, CoreError>> + Send;
+
+    fn relate_card(
+        &self,
+        dto: CreateDeckCard,
+    ) -> impl Future<Output = Result<DeckCard, CoreError>> + Send;
+
+    fn relate_card_group(
+        &self,
+        dto: CreateDeckCardGroup,
+    ) -> impl Future<Output = Result<DeckCardGroup, CoreError>> + Send;
+
+    /// Creates the cards and relates each of them to its deck in a single transaction, nothing is
+    /// stored when any of them fails. Returns the ids of the cards in order.
+    fn create_cards(
+        &self,
+        cards: Vec<CreateCardInDeck>,
+    ) -> impl Future<Output = Result<Vec<Thing>, CoreError>> + Send;
+
+    /// Cards related to the deck, ordered by title.
+    fn list_cards(
+        &self,
+        user: impl Into<Thing> + Send,
+        deck: impl Into<Thing> + Send,
+    ) -> impl Future<Output = Result<Vec<Card>, CoreError>> + Send;
+
+    /// Card groups related to the deck, ordered by title.
+    fn list_card_groups(
+        &self,
+        user: impl Into<Thing> + Send,
+        deck: impl Into<Thing> + Send,
+    ) -> impl Future<Output = Result<Vec<CardGroup>, CoreError>> + Send;
+
+    /// Every deck card of the user's decks.
+    fn list_deck_cards(
+        &self,
+        user: impl Into<Thing> + Send,
+    ) -> impl Future<Output = Result<Vec<DeckCard>, CoreError>> + Send;
+
+    /// Every deck card group of the user's decks.
+    fn list_deck_card_groups(
+        &self,
+        user: impl Into<Thing> + Send,
+    ) -> impl Future<Output = Result<Vec<DeckCardGroup>, CoreError>> + Send;
+
+    fn get_deck_card(
+        &self,
+        id: impl Into<Thing> + Send,
+    ) -> impl Future<Output = Result<DeckCard, CoreError>> + Send;
+
+    fn get_deck_card_group(
+        &self,
+        id: impl Into<Thing> + Send,
+    ) -> impl Future<Output = Result<DeckCardGroup, CoreError>> + Send;
+
+    /// Up to 10 deck cards that may be asked right now, best ranked first. See [`rank`].
+    fn list_top_ranked_cards(
+        &self,
+        user: impl Into<Thing> + Send,
+        since: chrono::DateTime<Utc>,
+    ) -> impl Future<Output = Result<Vec<DeckCard>, CoreError>> + Send;
+
+    /// Up to 10 deck card groups that may be asked right now, best ranked first.
+    fn list_top_ranked_card_groups(
+        &self,
+        user: impl Into<Thing> + Send,
+        since: chrono::DateTime<Utc>,
+    ) -> impl Future<Output = Result<Vec<DeckCardGroup>, CoreError>> + Send;
+}
+
+pub trait HistoryStore: Send + Sync {
+    fn create_custom(
+        &self,
+        dto: CreateHistory,
+    ) -> impl Future<Output = Result<HistoryRecord, CoreError>> + Send;
+
+    fn list_by_user_id(
+        &self,
+        id: impl Into<Thing> + Send,
+    ) -> impl Future<Output = Result<Vec<HistoryRecord>, CoreError>> + Send;
+
+    /// The latest answer to a deck card or a deck card group.
+    fn get_last_answer(
+        &self,
+        edge: impl Into<Thing> + Send,
+    ) -> impl Future<Output = Result<Option<HistoryRecord>, CoreError>> + Send;
+
+    /// Answers matching `query`, oldest first.
+    fn list_answers(
+        &self,
+        query: StatsQuery,
+    ) -> impl Future<Output = Result<Vec<Answer>, CoreError>> + Send;
+}
+
+pub trait BindingStore: Send + Sync {
+    fn get_by_source_id(
+        &self,
+        source_id: Arc<str>,
+    ) -> impl Future<Output = Result<Option<Binding>, CoreError>> + Send;
+
+    /// Returns the binding with `dto.source_id`, creating it (and the user, matched by email)
+    /// when it does not exist yet.
+    fn get_or_create_binding(
+        &self,
+        dto: GetOrCreateBinding,
+    ) -> impl Future<Output = Result<Binding, CoreError>> + Send;
+
+    fn set_banned(
+        &self,
+        id: impl Into<Thing> + Send,
+    ) -> impl Future<Output = Result<Binding, CoreError>> + Send;
+
+    fn list_all_not_banned(&self) -> impl Future<Output = Result<Vec<Binding>, CoreError>> + Send;
+}
+
+pub trait GlobalSettingsStore: Send + Sync {
+    fn create(
+        &self,
+        dto: CreateGlobalSettings,
+    ) -> impl Future<Output = Result<GlobalSettings, CoreError>> + Send;
+
+    fn get_by_user_id(
+        &self,
+        id: impl Into<Thing> + Send,
+    ) -> impl Future<Output = Result<GlobalSettings, CoreError>> + Send;
+
+    fn set_render_images(
+        &self,
+        user: impl Into<Thing> + Send,
+        render_images: bool,
+    ) -> impl Future<Output = Result<GlobalSettings, CoreError>> + Send;
+
+    /// Whether cards are generated as multiple-choice ones, see
+    /// [`GlobalSettings::multiple_choice`].
+    fn set_multiple_choice(
+        &self,
+        user: impl Into<Thing> + Send,
+        multiple_choice: bool,
+    ) -> impl Future<Output = Result<GlobalSettings, CoreError>> + Send;
+
+    /// Sets the LLM provider and model of the user, `None` goes back to the ones of the chains.
+    fn set_llm(
+        &self,
+        user: impl Into<Thing> + Send,
+        llm: Option<LlmChoice>,
+    ) -> impl Future<Output = Result<GlobalSettings, CoreError>> + Send;
+}
+
+/// A family of store implementations sharing the same backend. Services are generic over a
+/// `Storage` rather than over every store separately.
+pub trait Storage: Debug + Clone + Send + Sync + 'static {
+    type Users: UserStore + Debug + Clone + 'static;
+    type Tags: TagStore + Debug + Clone + 'static;
+    type Cards: CardStore + Debug + Clone + 'static;
+    type CardGroups: CardGroupStore + Debug + Clone + 'static;
+    type Decks: DeckStore + Debug + Clone + 'static;
+    type History: HistoryStore + Debug + Clone + 'static;
+    type Bindings: BindingStore + Debug + Clone + 'static;
+    type GlobalSettings: GlobalSettingsStore + Debug + Clone + 'static;
+}
@@ -0,0 +1,93 @@
+//! Ranking used to pick the next card or card group to ask. This mirrors `fn::trend`,
+//! `fn::since_last` and `fn::rank` from the SurrealDB schema so that backends which can't run
+//! those functions rank the same way.
+
+use chrono::{DateTime, TimeDelta, Utc};
+
+/// How many of the latest answers `fn::trend` looks at.
+pub const TREND_WINDOW: usize = 10;
+
+/// The slope `fn::trend` reports when there are not enough answers to fit a line.
+pub const DEFAULT_SLOPE: f64 = 10.0;
+
+/// How many cards or card groups the ranking queries return.
+pub const TOP_RANKED_LIMIT: usize = 10;
+
+/// How long ago a never answered item is considered to be seen.
+pub const NEVER_ANSWERED: TimeDelta = TimeDelta::days(30);
+
+/// Slope of the least squares line through `(unix timestamp, difficulty)` answers, plus one.
+/// `answers` are expected in chronological order and limited to [`TREND_WINDOW`].
+pub fn trend_slope(answers: &[(i64, u8)]) -> f64 {
+    if answers.is_empty() {
+        return DEFAULT_SLOPE;
+    }
+
+    let len = answers.len() as f64;
+    let mean_time = answers.iter().map(|&(ts, _)| ts as f64).sum::<f64>() / len;
+    let mean_difficulty = answers.iter().map(|&(_, d)| d as f64).sum::<f64>() / len;
+
+    let (numerator, denominator) =
+        answers
+            .iter()
+            .fold((0.0, 0.0), |(numerator, denominator), &(ts, difficulty)| {
+                let time_diff = ts as f64 - mean_time;
+                let difficulty_diff = difficulty as f64 - mean_difficulty;
+                (
+                    numerator + time_diff * difficulty_diff,
+                    denominator + time_diff * time_diff,
+                )
+            });
+
+    if denominator == 0.0 {
+        DEFAULT_SLOPE
+    } else {
+        1.0 + numerator / denominator
+    }
+}
+
+pub fn since_last(last_answered_at: Option<DateTime<Utc>>, now: DateTime<Utc>) -> TimeDelta {
+    last_answered_at
+        .map(|last| now - last)
+        .unwrap_or(NEVER_ANSWERED)
+}
+
+pub fn rank(importance: u8, difficulty: u8, slope: f64, since_last: TimeDelta) -> f64 {
+    (importance as f64 + 1.0) * (difficulty as f64 + 1.0) * slope * since_last.num_minutes() as f64
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_trend_slope_without_enough_answers() {
+        assert_eq!(trend_slope(&[]), DEFAULT_SLOPE);
+        assert_eq!(trend_slope(&[(100, 5)]), DEFAULT_SLOPE);
+        assert_eq!(trend_slope(&[(100, 5), (100, 7)]), DEFAULT_SLOPE);
+    }
+
+    #[test]
+    fn test_trend_slope_follows_difficulty() {
+        assert_eq!(trend_slope(&[(0, 2), (10, 2), (20, 2)]), 1.0);
+        assert_eq!(trend_slope(&[(0, 0), (1, 1), (2, 2)]), 2.0);
+        assert!(trend_slope(&[(0, 8), (60, 4), (120, 1)]) < 1.0);
+    }
+
+    #[test]
+    fn test_since_last() {
+        let now = Utc::now();
+        assert_eq!(since_last(None, now), NEVER_ANSWERED);
+        assert_eq!(
+            since_last(Some(now - TimeDelta::minutes(5)), now),
+            TimeDelta::minutes(5)
+        );
+    }
+
+    #[test]
+    fn test_rank() {
+        assert_eq!(rank(0, 0, 1.0, TimeDelta::minutes(10)), 10.0);
+        assert_eq!(rank(1, 2, 0.5, TimeDelta::seconds(150)), 6.0);
+        assert_eq!(rank(9, 9, DEFAULT_SLOPE, TimeDelta::seconds(59)), 0.0);
+    }
+}
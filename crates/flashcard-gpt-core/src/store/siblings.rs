@@ -0,0 +1,135 @@
+//! Reversed cards. A card created with [`CreateCard::reverse`] gets a sibling asking for its front
+//! by its back. Both cards point at each other with `sibling` and are scheduled separately, each
+//! with its own difficulty, but a card is not asked while its sibling was answered recently. Edits
+//! of the content and the importance and deletions apply to both of them, the front of one card is
+//! the back of the other. Hints are never shared, they help with the front of the card they belong
+//! to.
+
+use crate::error::CoreError;
+use crate::model::card::{sibling_title, Card, CreateCard, UpdateCard};
+use crate::store::CardStore;
+use std::future::Future;
+use surrealdb::sql::Thing;
+use tracing::warn;
+
+pub trait CardSiblingsExt: CardStore {
+    /// Creates the card and, with `dto.reverse`, its reversed sibling. The card comes first. The
+    /// pair takes three writes, the cards are deleted again when a later one fails.
+    fn create_with_sibling(
+        &self,
+        dto: CreateCard,
+    ) -> impl Future<Output = Result<Vec<Card>, CoreError>> + Send;
+
+    /// Applies `update` to the card and its content and importance to the sibling: the front and
+    /// the back swapped and the title. The difficulty, the hints, the annotations and the tags are
+    /// the card's own.
+    fn patch_with_sibling(
+        &self,
+        id: impl Into<Thing> + Send,
+        update: UpdateCard,
+    ) -> impl Future<Output = Result<Card, CoreError>> + Send;
+
+    fn delete_with_sibling(
+        &self,
+        id: impl Into<Thing> + Send,
+    ) -> impl Future<Output = Result<(), CoreError>> + Send;
+}
+
+impl<T: CardStore> CardSiblingsExt for T {
+    async fn create_with_sibling(&self, dto: CreateCard) -> Result<Vec<Card>, CoreError> {
+        let reversed = dto.reverse.then(|| dto.reversed()).flatten();
+        let card = self.create(dto).await?;
+        let Some(reversed) = reversed else {
+            return Ok(vec![card]);
+        };
+
+        let reversed = match self
+            .create(CreateCard {
+                sibling: Some(card.id.clone()),
+                ..reversed
+            })
+            .await
+        {
+            Ok(reversed) => reversed,
+            Err(err) => {
+                delete_created(self, [card.id]).await;
+                return Err(err);
+            }
+        };
+        let linked = self
+            .patch(
+                card.id.clone(),
+                UpdateCard {
+                    importance: None,
+                    difficulty: None,
+                    sibling: Some(reversed.id.clone()),
+                    annotations: None,
+                    title: None,
+                    front: None,
+                    back: None,
+                    hints: None,
+                    tags: None,
+                },
+            )
+            .await;
+        match linked {
+            Ok(card) => Ok(vec![card, reversed]),
+            Err(err) => {
+                delete_created(self, [reversed.id, card.id]).await;
+                Err(err)
+            }
+        }
+    }
+
+    async fn patch_with_sibling(
+        &self,
+        id: impl Into<Thing> + Send,
+        update: UpdateCard,
+    ) -> Result<Card, CoreError> {
+        let sibling_update = UpdateCard {
+            importance: update.importance,
+            difficulty: None,
+            sibling: None,
+            annotations: None,
+            title: update.title.as_deref().map(sibling_title),
+            front: update.back.clone(),
+            back: update.front.clone(),
+            hints: None,
+            tags: None,
+        };
+        let card = self.patch(id, update).await?;
+        let Some(sibling) = card.sibling.clone() else {
+            return Ok(card);
+        };
+
+        let UpdateCard {
+            importance,
+            title,
+            front,
+            back,
+            ..
+        } = &sibling_update;
+        if importance.is_some() || title.is_some() || front.is_some() || back.is_some() {
+            self.patch(sibling, sibling_update).await?;
+        }
+        Ok(card)
+    }
+
+    async fn delete_with_sibling(&self, id: impl Into<Thing> + Send) -> Result<(), CoreError> {
+        let card = self.get_by_id(id).await?;
+        if let Some(sibling) = card.sibling {
+            self.delete(sibling).await?;
+        }
+        self.delete(card.id).await
+    }
+}
+
+/// Deletes the cards of a pair that failed to be created. The error of the failed write is the one
+/// reported, a card that can't be deleted is only logged.
+async fn delete_created<T: CardStore>(store: &T, ids: impl IntoIterator<Item = Thing>) {
+    for id in ids {
+        if let Err(err) = store.delete(id.clone()).await {
+            warn!(%id, ?err, "Failed to delete a card of a half-created pair");
+        }
+    }
+}
@@ -0,0 +1,1642 @@
+//! SQLite backend. Records are addressed with the same `table:id` things as in SurrealDB, the
+//! key of every thing being the rowid. Ranking runs in Rust on top of [`rank`], the same way the
+//! in-memory backend does it.
+
+use crate::error::CoreError;
+use crate::ext::mutex::MutexExt;
+use crate::llm::provider::LlmChoice;
+use crate::model::binding::{Binding, GetOrCreateBinding};
+use crate::model::card::{Card, CreateCard, UpdateCard};
+use crate::model::card_group::{CardGroup, CreateCardGroup, UpdateCardGroup};
+use crate::model::deck::{CreateDeck, Deck, DeckSettings};
+use crate::model::deck_card::{CreateCardInDeck, CreateDeckCard, DeckCard};
+use crate::model::deck_card_group::{CreateDeckCardGroup, DeckCardGroup};
+use crate::model::global_settings::{CreateGlobalSettings, GlobalSettings};
+use crate::model::history::{CreateHistory, HistoryRecord};
+use crate::model::stats::{Answer, StatsQuery};
+use crate::model::tag::{CreateTag, Tag};
+use crate::model::time::Time;
+use crate::model::user::{RegisterUser, User};
+use crate::store::rank::{self, TOP_RANKED_LIMIT, TREND_WINDOW};
+use crate::store::{
+    BindingStore, CardGroupStore, CardStore, DeckStore, GlobalSettingsStore, HistoryStore, Storage,
+    TagStore, UserStore,
+};
+use chrono::{DateTime, Utc};
+use chrono_tz::Tz;
+use itertools::Itertools;
+use rusqlite::types::{FromSql, FromSqlError, FromSqlResult, ValueRef};
+use rusqlite::{named_params, params, Connection, OptionalExtension, Params, Row};
+use serde::de::DeserializeOwned;
+use serde_json::Value;
+use std::marker::PhantomData;
+use std::path::Path;
+use std::sync::{Arc, Mutex};
+use surrealdb::sql::{Duration, Id, Thing};
+use tracing::info;
+
+/// Migrations in the order they must be applied, recorded by name in `script_migration`.
+static MIGRATIONS: [(&str, &str); 9] = [
+    (
+        "20241019_000000_Initial",
+        include_str!("../../sqlite-migrations/20241019_000000_Initial.sql"),
+    ),
+    (
+        "20241020_000000_MultipleChoice",
+        include_str!("../../sqlite-migrations/20241020_000000_MultipleChoice.sql"),
+    ),
+    (
+        "20241021_000000_ReversedCards",
+        include_str!("../../sqlite-migrations/20241021_000000_ReversedCards.sql"),
+    ),
+    (
+        "20241022_000000_ProgressiveCardGroups",
+        include_str!("../../sqlite-migrations/20241022_000000_ProgressiveCardGroups.sql"),
+    ),
+    (
+        "20241023_000000_TypedCardData",
+        include_str!("../../sqlite-migrations/20241023_000000_TypedCardData.sql"),
+    ),
+    (
+        "20241025_000000_RenderImages",
+        include_str!("../../sqlite-migrations/20241025_000000_RenderImages.sql"),
+    ),
+    (
+        "20241026_000000_Annotations",
+        include_str!("../../sqlite-migrations/20241026_000000_Annotations.sql"),
+    ),
+    (
+        "20241029_000000_LlmChoice",
+        include_str!("../../sqlite-migrations/20241029_000000_LlmChoice.sql"),
+    ),
+    (
+        "20241030_000000_MultipleChoiceSetting",
+        include_str!("../../sqlite-migrations/20241030_000000_MultipleChoiceSetting.sql"),
+    ),
+];
+
+/// Deck cards and deck card groups that may be asked right now, with what [`rank`] needs.
+static AVAILABLE_DECK_CARDS: &str = "
+    select dc.id, dc.card, c.importance, c.difficulty
+    from deck_card dc
+        join card c on c.id = dc.card
+        join deck d on d.id = dc.deck
+    where c.user = :user
+        and not exists (
+            select 1
+            from deck_card_group dcg
+                join card_group cg on cg.id = dcg.card_group,
+                json_each(cg.cards) member
+            where dcg.deck = dc.deck and member.value = dc.card
+        )
+        and not exists (
+            select 1 from history h
+            where h.deck_card = dc.id and (h.created_at >= :since or h.hide_till >= :now)
+        )
+        and not exists (
+            select 1 from answer a where a.card = c.sibling and a.created_at >= :since
+        )
+        and (
+            select count(distinct coalesce('dc' || a.deck_card, 'dcg' || a.deck_card_group))
+            from answer a
+            where a.deck = d.id and a.created_at >= :since
+        ) <= coalesce(d.daily_limit, 0)
+";
+
+static AVAILABLE_DECK_CARD_GROUPS: &str = "
+    select dcg.id, dcg.card_group, cg.importance, cg.difficulty
+    from deck_card_group dcg
+        join card_group cg on cg.id = dcg.card_group
+        join deck d on d.id = dcg.deck
+    where cg.user = :user
+        and not exists (
+            select 1 from history h
+            where h.deck_card_group = dcg.id and (h.created_at >= :since or h.hide_till >= :now)
+        )
+        and (
+            select count(distinct coalesce('dc' || a.deck_card, 'dcg' || a.deck_card_group))
+            from answer a
+            where a.deck = d.id and a.created_at >= :since
+        ) <= coalesce(d.daily_limit, 0)
+";
+
+/// The latest answers to a card in any deck, newest first.
+static CARD_ANSWERS: &str =
+    "select created_at, difficulty from answer where card = ?1 order by created_at desc limit ?2";
+
+static CARD_GROUP_ANSWERS: &str = "
+    select created_at, difficulty from answer where card_group = ?1
+    order by created_at desc limit ?2
+";
+
+/// A JSON text column.
+struct Json<T>(T);
+
+impl<T: DeserializeOwned> FromSql for Json<T> {
+    fn column_result(value: ValueRef<'_>) -> FromSqlResult<Self> {
+        serde_json::from_slice(value.as_bytes()?)
+            .map(Json)
+            .map_err(|err| FromSqlError::Other(Box::new(err)))
+    }
+}
+
+struct Timezone(Tz);
+
+impl FromSql for Timezone {
+    fn column_result(value: ValueRef<'_>) -> FromSqlResult<Self> {
+        value
+            .as_str()?
+            .parse()
+            .map(Timezone)
+            .map_err(|err| FromSqlError::Other(err.to_string().into()))
+    }
+}
+
+fn record(table: &str, key: i64) -> Thing {
+    Thing::from((table, Id::Number(key)))
+}
+
+fn not_found(id: &Thing) -> CoreError {
+    CoreError::NotFound(Arc::from(id.to_string()))
+}
+
+/// Rowid of `id`. Things of other tables or with non-numeric ids can't exist in this backend.
+fn key(id: &Thing, table: &str) -> Result<i64, CoreError> {
+    match id.id {
+        Id::Number(key) if id.tb == table => Ok(key),
+        _ => Err(not_found(id)),
+    }
+}
+
+fn keys_json(ids: &[Thing], table: &str) -> Result<String, CoreError> {
+    let keys = ids
+        .iter()
+        .map(|id| key(id, table))
+        .collect::<Result<Vec<_>, _>>()?;
+    Ok(serde_json::to_string(&keys)?)
+}
+
+fn found<T>(result: rusqlite::Result<T>, id: &Thing) -> Result<T, CoreError> {
+    result.optional()?.ok_or_else(|| not_found(id))
+}
+
+fn duration_millis(duration: &Duration) -> u64 {
+    duration.0.as_millis() as u64
+}
+
+fn duration_from_millis(millis: u64) -> Duration {
+    Duration::from(std::time::Duration::from_millis(millis))
+}
+
+fn select_keys(conn: &Connection, sql: &str, params: impl Params) -> rusqlite::Result<Vec<i64>> {
+    conn.prepare_cached(sql)?
+        .query_map(params, |row| row.get(0))?
+        .collect()
+}
+
+fn get_time(row: &Row) -> rusqlite::Result<Time> {
+    Ok(Time {
+        created_at: row.get("created_at")?,
+        updated_at: row.get("updated_at")?,
+        deleted_at: row.get("deleted_at")?,
+    })
+}
+
+fn get_data(row: &Row) -> rusqlite::Result<Option<Arc<Value>>> {
+    Ok(row.get::<_, Option<Value>>("data")?.map(Arc::new))
+}
+
+fn fetch_user(conn: &Connection, key: i64) -> rusqlite::Result<User> {
+    conn.query_row("select * from user where id = ?1", [key], |row| {
+        Ok(User {
+            id: record("user", key),
+            email: row.get("email")?,
+            name: row.get("name")?,
+            password: row.get("password")?,
+            time: Some(get_time(row)?),
+        })
+    })
+}
+
+fn tag_from_row(row: &Row) -> rusqlite::Result<Tag> {
+    Ok(Tag {
+        id: record("tag", row.get("id")?),
+        name: row.get("name")?,
+        slug: row.get("slug")?,
+        user: record("user", row.get("user")?),
+        time: get_time(row)?,
+    })
+}
+
+/// Dangling tags are skipped, the same way `skip_nulls` drops them after a fetch.
+fn fetch_tags(conn: &Connection, row: &Row) -> rusqlite::Result<Vec<Arc<Tag>>> {
+    let Json(keys) = row.get::<_, Json<Vec<i64>>>("tags")?;
+    let mut statement = conn.prepare_cached("select * from tag where id = ?1")?;
+    let mut tags = vec![];
+    for key in keys {
+        if let Some(tag) = statement.query_row([key], tag_from_row).optional()? {
+            tags.push(Arc::new(tag));
+        }
+    }
+    Ok(tags)
+}
+
+fn fetch_card(conn: &Connection, key: i64) -> rusqlite::Result<Card> {
+    conn.query_row("select * from card where id = ?1", [key], |row| {
+        Ok(Card {
+            id: record("card", key),
+            user: Arc::new(fetch_user(conn, row.get("user")?)?),
+            title: row.get("title")?,
+            front: row.get("front")?,
+            back: row.get("back")?,
+            data: row.get::<_, Option<Json<_>>>("data")?.map(|data| data.0),
+            choices: row
+                .get::<_, Option<Json<_>>>("choices")?
+                .map(|choices| choices.0),
+            sibling: row
+                .get::<_, Option<i64>>("sibling")?
+                .map(|sibling| record("card", sibling)),
+            hints: row.get::<_, Json<_>>("hints")?.0,
+            difficulty: row.get("difficulty")?,
+            importance: row.get("importance")?,
+            tags: fetch_tags(conn, row)?,
+            annotations: row.get::<_, Json<_>>("annotations")?.0,
+            time: Some(get_time(row)?),
+        })
+    })
+}
+
+fn fetch_card_group(conn: &Connection, key: i64) -> rusqlite::Result<CardGroup> {
+    conn.query_row("select * from card_group where id = ?1", [key], |row| {
+        let Json(card_keys) = row.get::<_, Json<Vec<i64>>>("cards")?;
+        let mut cards = vec![];
+        for card_key in card_keys {
+            if let Some(card) = fetch_card(conn, card_key).optional()? {
+                cards.push(Arc::new(card));
+            }
+        }
+
+        Ok(CardGroup {
+            id: record("card_group", key),
+            user: fetch_user(conn, row.get("user")?)?,
+            importance: row.get("importance")?,
+            difficulty: row.get("difficulty")?,
+            title: row.get("title")?,
+            data: row.get::<_, Option<Json<_>>>("data")?.map(|data| data.0),
+            time: get_time(row)?,
+            cards,
+            tags: fetch_tags(conn, row)?,
+            annotations: row.get::<_, Json<_>>("annotations")?.0,
+        })
+    })
+}
+
+fn fetch_deck(conn: &Connection, key: i64) -> rusqlite::Result<Deck> {
+    conn.query_row("select * from deck where id = ?1", [key], |row| {
+        Ok(Deck {
+            id: record("deck", key),
+            description: row.get("description")?,
+            parent: row
+                .get::<_, Option<i64>>("parent")?
+                .map(|parent| record("deck", parent)),
+            settings: match row.get::<_, Option<usize>>("daily_limit")? {
+                Some(daily_limit) => Some(DeckSettings {
+                    daily_limit,
+                    reverse_cards: row.get("reverse_cards")?,
+                }),
+                None => None,
+            },
+            tags: fetch_tags(conn, row)?,
+            time: get_time(row)?,
+            title: row.get("title")?,
+            user: fetch_user(conn, row.get("user")?)?,
+        })
+    })
+}
+
+fn fetch_deck_card(conn: &Connection, key: i64) -> rusqlite::Result<DeckCard> {
+    conn.query_row("select * from deck_card where id = ?1", [key], |row| {
+        Ok(DeckCard {
+            id: record("deck_card", key),
+            deck: Arc::new(fetch_deck(conn, row.get("deck")?)?),
+            card: Arc::new(fetch_card(conn, row.get("card")?)?),
+            num_answered: None,
+            time: Some(get_time(row)?),
+        })
+    })
+}
+
+fn fetch_deck_card_group(conn: &Connection, key: i64) -> rusqlite::Result<DeckCardGroup> {
+    conn.query_row(
+        "select * from deck_card_group where id = ?1",
+        [key],
+        |row| {
+            Ok(DeckCardGroup {
+                id: record("deck_card_group", key),
+                deck: Arc::new(fetch_deck(conn, row.get("deck")?)?),
+                card_group: Arc::new(fetch_card_group(conn, row.get("card_group")?)?),
+                num_answered: None,
+                time: get_time(row)?,
+            })
+        },
+    )
+}
+
+fn fetch_history_record(conn: &Connection, key: i64) -> rusqlite::Result<HistoryRecord> {
+    conn.query_row("select * from history where id = ?1", [key], |row| {
+        Ok(HistoryRecord {
+            id: record("history", key),
+            user: record("user", row.get("user")?),
+            deck_card: row
+                .get::<_, Option<i64>>("deck_card")?
+                .map(|key| fetch_deck_card(conn, key).map(Arc::new))
+                .transpose()?,
+            deck_card_group: row
+                .get::<_, Option<i64>>("deck_card_group")?
+                .map(|key| fetch_deck_card_group(conn, key).map(Arc::new))
+                .transpose()?,
+            hide_for: row
+                .get::<_, Option<u64>>("hide_for")?
+                .map(duration_from_millis),
+            difficulty: row.get("difficulty")?,
+            steps: row.get::<_, Json<_>>("steps")?.0,
+            time: get_time(row)?,
+        })
+    })
+}
+
+fn fetch_binding(conn: &Connection, key: i64) -> rusqlite::Result<Binding> {
+    conn.query_row("select * from binding where id = ?1", [key], |row| {
+        Ok(Binding {
+            id: record("binding", key),
+            source_id: row.get("source_id")?,
+            type_name: row.get("type_name")?,
+            data: get_data(row)?,
+            user: Arc::new(fetch_user(conn, row.get("user")?)?),
+            time: get_time(row)?,
+        })
+    })
+}
+
+fn fetch_global_settings(conn: &Connection, key: i64) -> rusqlite::Result<GlobalSettings> {
+    conn.query_row(
+        "select * from global_settings where id = ?1",
+        [key],
+        |row| {
+            let Json(timetable) = row.get::<_, Json<Vec<[u64; 2]>>>("timetable")?;
+            Ok(GlobalSettings {
+                id: record("global_settings", key),
+                daily_limit: row.get("daily_limit")?,
+                timetable: timetable
+                    .into_iter()
+                    .map(|[start, end]| [duration_from_millis(start), duration_from_millis(end)])
+                    .collect(),
+                timezone: row.get::<_, Timezone>("timezone")?.0,
+                render_images: row.get("render_images")?,
+                multiple_choice: row.get("multiple_choice")?,
+                llm: row.get::<_, Option<Json<_>>>("llm")?.map(|llm| llm.0),
+                user: fetch_user(conn, row.get("user")?)?,
+                time: get_time(row)?,
+            })
+        },
+    )
+}
+
+fn insert_user(conn: &Connection, user: &RegisterUser) -> rusqlite::Result<i64> {
+    let now = Utc::now();
+    conn.execute(
+        "insert into user (email, name, password, created_at, updated_at)
+         values (?1, ?2, ?3, ?4, ?4)",
+        params![user.email, user.name, user.password, now],
+    )?;
+    Ok(conn.last_insert_rowid())
+}
+
+fn insert_card(conn: &Connection, dto: &CreateCard) -> Result<i64, CoreError> {
+    conn.execute(
+        "insert into card (
+            user, title, front, back, data, choices, hints, difficulty, importance, tags, sibling,
+            created_at, updated_at
+         )
+         values (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8, ?9, ?10, ?11, ?12, ?12)",
+        params![
+            key(&dto.user, "user")?,
+            dto.title,
+            dto.front,
+            dto.back,
+            dto.data.as_ref().map(serde_json::to_string).transpose()?,
+            dto.choices
+                .as_ref()
+                .map(serde_json::to_string)
+                .transpose()?,
+            serde_json::to_string(&dto.hints)?,
+            dto.difficulty,
+            dto.importance,
+            keys_json(&dto.tags, "tag")?,
+            dto.sibling.as_ref().map(|id| key(id, "card")).transpose()?,
+            Utc::now(),
+        ],
+    )?;
+    Ok(conn.last_insert_rowid())
+}
+
+/// The key of the tag of `user` with the slug of `name`, created when there is none.
+fn get_or_create_tag(conn: &Connection, user: &Thing, name: &str) -> Result<i64, CoreError> {
+    let user = key(user, "user")?;
+    let slug = slug::slugify(name);
+    conn.execute(
+        "insert into tag (user, name, slug, created_at, updated_at)
+         values (?1, ?2, ?3, ?4, ?4)
+         on conflict (user, slug) do nothing",
+        params![user, name, slug, Utc::now()],
+    )?;
+    Ok(conn.query_row(
+        "select id from tag where user = ?1 and slug = ?2",
+        params![user, slug],
+        |row| row.get(0),
+    )?)
+}
+
+/// `(key, target, importance, difficulty)` of the deck cards or deck card groups `sql` selects.
+fn available(
+    conn: &Connection,
+    sql: &str,
+    user: i64,
+    since: DateTime<Utc>,
+    now: DateTime<Utc>,
+) -> rusqlite::Result<Vec<(i64, i64, u8, u8)>> {
+    conn.prepare_cached(sql)?
+        .query_map(
+            named_params! {":user": user, ":since": since, ":now": now},
+            |row| Ok((row.get(0)?, row.get(1)?, row.get(2)?, row.get(3)?)),
+        )?
+        .collect()
+}
+
+fn rank(
+    conn: &Connection,
+    answers_sql: &str,
+    target: i64,
+    importance: u8,
+    difficulty: u8,
+    now: DateTime<Utc>,
+) -> rusqlite::Result<f64> {
+    let answers = conn
+        .prepare_cached(answers_sql)?
+        .query_map(params![target, TREND_WINDOW], |row| {
+            Ok((row.get::<_, DateTime<Utc>>(0)?, row.get::<_, u8>(1)?))
+        })?
+        .collect::<Result<Vec<_>, _>>()?;
+    let last_answered_at = answers.first().map(|&(created_at, _)| created_at);
+    let trend = answers
+        .iter()
+        .rev()
+        .map(|&(created_at, difficulty)| (created_at.timestamp(), difficulty))
+        .collect_vec();
+
+    Ok(rank::rank(
+        importance,
+        difficulty,
+        rank::trend_slope(&trend),
+        rank::since_last(last_answered_at, now),
+    ))
+}
+
+/// Ranks the available items and returns the keys of the best ones, best first.
+fn top_ranked(
+    conn: &Connection,
+    available_sql: &str,
+    answers_sql: &str,
+    user: i64,
+    since: DateTime<Utc>,
+) -> rusqlite::Result<Vec<i64>> {
+    let now = Utc::now();
+    let ranked = available(conn, available_sql, user, since, now)?
+        .into_iter()
+        .map(|(key, target, importance, difficulty)| {
+            rank(conn, answers_sql, target, importance, difficulty, now).map(|rank| (rank, key))
+        })
+        .collect::<Result<Vec<_>, _>>()?;
+
+    Ok(ranked
+        .into_iter()
+        .sorted_by(|(a, _), (b, _)| b.total_cmp(a))
+        .take(TOP_RANKED_LIMIT)
+        .map(|(_, key)| key)
+        .collect())
+}
+
+fn answered_times(
+    conn: &Connection,
+    column: &str,
+    key: i64,
+    since: DateTime<Utc>,
+) -> rusqlite::Result<usize> {
+    conn.query_row(
+        &format!("select count(*) from history where {column} = ?1 and created_at >= ?2"),
+        params![key, since],
+        |row| row.get(0),
+    )
+}
+
+pub fn apply_migrations(conn: &mut Connection) -> Result<(), CoreError> {
+    conn.execute_batch(
+        "create table if not exists script_migration (
+            script_name text primary key,
+            executed_at text not null
+        )",
+    )?;
+
+    for (name, script) in MIGRATIONS {
+        let tx = conn.transaction()?;
+        let applied = tx.query_row(
+            "select exists(select 1 from script_migration where script_name = ?1)",
+            [name],
+            |row| row.get::<_, bool>(0),
+        )?;
+        if applied {
+            continue;
+        }
+
+        info!(%name, "Applying migration");
+        tx.execute_batch(script)?;
+        tx.execute(
+            "insert into script_migration (script_name, executed_at) values (?1, ?2)",
+            params![name, Utc::now()],
+        )?;
+        tx.commit()?;
+    }
+
+    Ok(())
+}
+
+/// A migrated SQLite connection shared by every repository created from it. Queries run on the
+/// blocking thread pool one at a time.
+#[derive(Debug, Clone)]
+pub struct SqliteDb {
+    conn: Arc<Mutex<Connection>>,
+}
+
+impl SqliteDb {
+    pub fn open(path: impl AsRef<Path>) -> Result<Self, CoreError> {
+        Self::init(Connection::open(path)?)
+    }
+
+    pub fn in_memory() -> Result<Self, CoreError> {
+        Self::init(Connection::open_in_memory()?)
+    }
+
+    fn init(mut conn: Connection) -> Result<Self, CoreError> {
+        conn.pragma_update(None, "foreign_keys", true)?;
+        apply_migrations(&mut conn)?;
+        Ok(Self {
+            conn: Arc::new(Mutex::new(conn)),
+        })
+    }
+
+    async fn call<R: Send + 'static>(
+        &self,
+        f: impl FnOnce(&mut Connection) -> Result<R, CoreError> + Send + 'static,
+    ) -> Result<R, CoreError> {
+        let conn = self.conn.clone();
+        tokio::task::spawn_blocking(move || f(&mut *conn.lock_sync()?)).await?
+    }
+}
+
+#[derive(Debug)]
+pub struct SqliteRepo<Create, Read, Update> {
+    db: SqliteDb,
+
+    _create_phantom: PhantomData<Create>,
+    _read_phantom: PhantomData<Read>,
+    _update_phantom: PhantomData<Update>,
+}
+
+impl<Create, Read, Update> Clone for SqliteRepo<Create, Read, Update> {
+    fn clone(&self) -> Self {
+        Self::new(self.db.clone())
+    }
+}
+
+impl<Create, Read, Update> SqliteRepo<Create, Read, Update> {
+    pub fn new(db: SqliteDb) -> Self {
+        Self {
+            db,
+            _create_phantom: PhantomData,
+            _read_phantom: PhantomData,
+            _update_phantom: PhantomData,
+        }
+    }
+}
+
+pub type SqliteUserRepo = SqliteRepo<RegisterUser, User, ()>;
+pub type SqliteTagRepo = SqliteRepo<CreateTag, Tag, ()>;
+pub type SqliteCardRepo = SqliteRepo<CreateCard, Card, UpdateCard>;
+pub type SqliteCardGroupRepo = SqliteRepo<CreateCardGroup, CardGroup, UpdateCardGroup>;
+pub type SqliteDeckRepo = SqliteRepo<CreateDeck, Deck, ()>;
+pub type SqliteHistoryRepo = SqliteRepo<CreateHistory, HistoryRecord, ()>;
+pub type SqliteBindingRepo = SqliteRepo<GetOrCreateBinding, Binding, ()>;
+pub type SqliteGlobalSettingsRepo = SqliteRepo<CreateGlobalSettings, GlobalSettings, ()>;
+
+#[derive(Debug, Clone)]
+pub struct SqliteStorage;
+
+impl Storage for SqliteStorage {
+    type Users = SqliteUserRepo;
+    type Tags = SqliteTagRepo;
+    type Cards = SqliteCardRepo;
+    type CardGroups = SqliteCardGroupRepo;
+    type Decks = SqliteDeckRepo;
+    type History = SqliteHistoryRepo;
+    type Bindings = SqliteBindingRepo;
+    type GlobalSettings = SqliteGlobalSettingsRepo;
+}
+
+impl UserStore for SqliteUserRepo {
+    async fn create_user(&self, user: RegisterUser) -> Result<User, CoreError> {
+        self.db
+            .call(move |conn| {
+                let key = insert_user(conn, &user)?;
+                Ok(fetch_user(conn, key)?)
+            })
+            .await
+    }
+
+    async fn get_by_id(&self, id: impl Into<Thing> + Send) -> Result<User, CoreError> {
+        let id = id.into();
+        self.db
+            .call(move |conn| found(fetch_user(conn, key(&id, "user")?), &id))
+            .await
+    }
+
+    async fn clear_data(&self, id: impl Into<Thing> + Send) -> Result<(), CoreError> {
+        let id = id.into();
+        self.db
+            .call(move |conn| {
+                let user = key(&id, "user")?;
+                found(fetch_user(conn, user), &id)?;
+                // deck relations go with the decks and the cards
+                let tx = conn.transaction()?;
+                for table in [
+                    "history",
+                    "deck",
+                    "card_group",
+                    "card",
+                    "tag",
+                    "global_settings",
+                ] {
+                    tx.execute(&format!("delete from {table} where user = ?1"), [user])?;
+                }
+                tx.commit()?;
+                Ok(())
+            })
+            .await
+    }
+}
+
+impl TagStore for SqliteTagRepo {
+    async fn get_or_create_tags(
+        &self,
+        user_id: impl Into<Thing> + Send,
+        tags: impl IntoIterator<Item = Arc<str>> + Send,
+    ) -> Result<Vec<Tag>, CoreError> {
+        let user_id = user_id.into();
+        let tags = tags.into_iter().unique().collect_vec();
+
+        self.db
+            .call(move |conn| {
+                let user = key(&user_id, "user")?;
+                let tx = conn.transaction()?;
+
+                let mut slugs = vec![];
+                for name in tags {
+                    get_or_create_tag(&tx, &user_id, &name)?;
+                    slugs.push(slug::slugify(&name));
+                }
+
+                let tags = tx
+                    .prepare(
+                        "select * from tag
+                         where user = ?1 and slug in (select value from json_each(?2))
+                         order by slug",
+                    )?
+                    .query_map(params![user, serde_json::to_string(&slugs)?], tag_from_row)?
+                    .collect::<Result<Vec<_>, _>>()?;
+                tx.commit()?;
+                Ok(tags)
+            })
+            .await
+    }
+
+    async fn list_by_user_id(&self, id: impl Into<Thing> + Send) -> Result<Vec<Tag>, CoreError> {
+        let id = id.into();
+        self.db
+            .call(move |conn| {
+                Ok(conn
+                    .prepare_cached("select * from tag where user = ?1 order by id")?
+                    .query_map([key(&id, "user")?], tag_from_row)?
+                    .collect::<Result<Vec<_>, _>>()?)
+            })
+            .await
+    }
+}
+
+impl CardStore for SqliteCardRepo {
+    async fn create(&self, dto: CreateCard) -> Result<Card, CoreError> {
+        if let Some(data) = &dto.data {
+            data.validate()?;
+        }
+        self.db
+            .call(move |conn| Ok(fetch_card(conn, insert_card(conn, &dto)?)?))
+            .await
+    }
+
+    async fn get_by_id(&self, id: impl Into<Thing> + Send) -> Result<Card, CoreError> {
+        let id = id.into();
+        self.db
+            .call(move |conn| found(fetch_card(conn, key(&id, "card")?), &id))
+            .await
+    }
+
+    async fn patch(
+        &self,
+        id: impl Into<Thing> + Send,
+        update: UpdateCard,
+    ) -> Result<Card, CoreError> {
+        let id = id.into();
+        self.db
+            .call(move |conn| {
+                let sibling = update
+                    .sibling
+                    .as_ref()
+                    .map(|id| key(id, "card"))
+                    .transpose()?;
+                let annotations = update
+                    .annotations
+                    .as_ref()
+                    .map(serde_json::to_string)
+                    .transpose()?;
+                let hints = update
+                    .hints
+                    .as_ref()
+                    .map(serde_json::to_string)
+                    .transpose()?;
+                let tags = update
+                    .tags
+                    .as_ref()
+                    .map(|tags| keys_json(tags, "tag"))
+                    .transpose()?;
+                let key = key(&id, "card")?;
+                let updated = conn.execute(
+                    "update card
+                     set importance = coalesce(?2, importance),
+                         difficulty = coalesce(?3, difficulty),
+                         sibling = coalesce(?4, sibling),
+                         annotations = coalesce(?5, annotations),
+                         title = coalesce(?6, title),
+                         front = coalesce(?7, front),
+                         back = coalesce(?8, back),
+                         hints = coalesce(?9, hints),
+                         tags = coalesce(?10, tags),
+                         updated_at = ?11
+                     where id = ?1",
+                    params![
+                        key,
+                        update.importance,
+                        update.difficulty,
+                        sibling,
+                        annotations,
+                        update.title,
+                        update.front,
+                        update.back,
+                        hints,
+                        tags,
+                        Utc::now()
+                    ],
+                )?;
+                if updated == 0 {
+                    return Err(not_found(&id));
+                }
+                Ok(fetch_card(conn, key)?)
+            })
+            .await
+    }
+
+    async fn list_by_user_id(&self, id: impl Into<Thing> + Send) -> Result<Vec<Card>, CoreError> {
+        let id = id.into();
+        self.db
+            .call(move |conn| {
+                let keys = select_keys(
+                    conn,
+                    "select id from card where user = ?1 order by id",
+                    [key(&id, "user")?],
+                )?;
+                Ok(keys
+                    .into_iter()
+                    .map(|key| fetch_card(conn, key))
+                    .collect::<Result<Vec<_>, _>>()?)
+            })
+            .await
+    }
+
+    async fn delete(&self, id: impl Into<Thing> + Send) -> Result<(), CoreError> {
+        let id = id.into();
+        self.db
+            .call(move |conn| {
+                conn.execute("delete from card where id = ?1", [key(&id, "card")?])?;
+                Ok(())
+            })
+            .await
+    }
+}
+
+impl CardGroupStore for SqliteCardGroupRepo {
+    async fn create(&self, dto: CreateCardGroup) -> Result<CardGroup, CoreError> {
+        if let Some(data) = &dto.data {
+            data.validate()?;
+        }
+        self.db
+            .call(move |conn| {
+                let now = Utc::now();
+                conn.execute(
+                    "insert into card_group (
+                        user, title, importance, difficulty, data, cards, tags,
+                        created_at, updated_at
+                     )
+                     values (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8, ?8)",
+                    params![
+                        key(&dto.user, "user")?,
+                        dto.title,
+                        dto.importance,
+                        dto.difficulty,
+                        dto.data.as_ref().map(serde_json::to_string).transpose()?,
+                        keys_json(&dto.cards, "card")?,
+                        keys_json(&dto.tags, "tag")?,
+                        now,
+                    ],
+                )?;
+                Ok(fetch_card_group(conn, conn.last_insert_rowid())?)
+            })
+            .await
+    }
+
+    async fn get_by_id(&self, id: impl Into<Thing> + Send) -> Result<CardGroup, CoreError> {
+        let id = id.into();
+        self.db
+            .call(move |conn| found(fetch_card_group(conn, key(&id, "card_group")?), &id))
+            .await
+    }
+
+    async fn patch(
+        &self,
+        id: impl Into<Thing> + Send,
+        update: UpdateCardGroup,
+    ) -> Result<CardGroup, CoreError> {
+        let id = id.into();
+        self.db
+            .call(move |conn| {
+                let cards = update
+                    .cards
+                    .as_ref()
+                    .map(|cards| keys_json(cards, "card"))
+                    .transpose()?;
+                let annotations = update
+                    .annotations
+                    .as_ref()
+                    .map(serde_json::to_string)
+                    .transpose()?;
+                let tags = update
+                    .tags
+                    .as_ref()
+                    .map(|tags| keys_json(tags, "tag"))
+                    .transpose()?;
+                let key = key(&id, "card_group")?;
+                let updated = conn.execute(
+                    "update card_group
+                     set importance = coalesce(?2, importance),
+                         difficulty = coalesce(?3, difficulty),
+                         cards = coalesce(?4, cards),
+                         annotations = coalesce(?5, annotations),
+                         title = coalesce(?6, title),
+                         tags = coalesce(?7, tags),
+                         updated_at = ?8
+                     where id = ?1",
+                    params![
+                        key,
+                        update.importance,
+                        update.difficulty,
+                        cards,
+                        annotations,
+                        update.title,
+                        tags,
+                        Utc::now()
+                    ],
+                )?;
+                if updated == 0 {
+                    return Err(not_found(&id));
+                }
+                Ok(fetch_card_group(conn, key)?)
+            })
+            .await
+    }
+
+    async fn list_by_user_id(
+        &self,
+        id: impl Into<Thing> + Send,
+    ) -> Result<Vec<CardGroup>, CoreError> {
+        let id = id.into();
+        self.db
+            .call(move |conn| {
+                let keys = select_keys(
+                    conn,
+                    "select id from card_group where user = ?1 order by id",
+                    [key(&id, "user")?],
+                )?;
+                Ok(keys
+                    .into_iter()
+                    .map(|key| fetch_card_group(conn, key))
+                    .collect::<Result<Vec<_>, _>>()?)
+            })
+            .await
+    }
+
+    async fn delete(&self, id: impl Into<Thing> + Send) -> Result<(), CoreError> {
+        let id = id.into();
+        self.db
+            .call(move |conn| {
+                conn.execute(
+                    "delete from card_group where id = ?1",
+                    [key(&id, "card_group")?],
+                )?;
+                Ok(())
+            })
+            .await
+    }
+}
+
+impl DeckStore for SqliteDeckRepo {
+    async fn create(&self, dto: CreateDeck) -> Result<Deck, CoreError> {
+        self.db
+            .call(move |conn| {
+                let now = Utc::now();
+                let parent = dto
+                    .parent
+                    .as_ref()
+                    .map(|parent| key(parent, "deck"))
+                    .transpose()?;
+                conn.execute(
+                    "insert into deck (
+                        user, title, description, parent, daily_limit, reverse_cards, tags,
+                        created_at, updated_at
+                     )
+                     values (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8, ?8)",
+                    params![
+                        key(&dto.user, "user")?,
+                        dto.title,
+                        dto.description,
+                        parent,
+                        dto.settings.as_ref().map(|settings| settings.daily_limit),
+                        dto.settings
+                            .as_ref()
+                            .is_some_and(|settings| settings.reverse_cards),
+                        keys_json(&dto.tags, "tag")?,
+                        now,
+                    ],
+                )?;
+                Ok(fetch_deck(conn, conn.last_insert_rowid())?)
+            })
+            .await
+    }
+
+    async fn get_by_id(&self, id: impl Into<Thing> + Send) -> Result<Deck, CoreError> {
+        let id = id.into();
+        self.db
+            .call(move |conn| found(fetch_deck(conn, key(&id, "deck")?), &id))
+            .await
+    }
+
+    async fn list_by_user_id(&self, id: impl Into<Thing> + Send) -> Result<Vec<Deck>, CoreError> {
+        let id = id.into();
+        self.db
+            .call(move |conn| {
+                let keys = select_keys(
+                    conn,
+                    "select id from deck where user = ?1 order by id",
+                    [key(&id, "user")?],
+                )?;
+                Ok(keys
+                    .into_iter()
+                    .map(|key| fetch_deck(conn, key))
+                    .collect::<Result<Vec<_>, _>>()?)
+            })
+            .await
+    }
+
+    async fn relate_card(&self, dto: CreateDeckCard) -> Result<DeckCard, CoreError> {
+        self.db
+            .call(move |conn| {
+                conn.execute(
+                    "insert into deck_card (deck, card, created_at, updated_at)
+                     values (?1, ?2, ?3, ?3)",
+                    params![key(&dto.deck, "deck")?, key(&dto.card, "card")?, Utc::now()],
+                )?;
+                Ok(fetch_deck_card(conn, conn.last_insert_rowid())?)
+            })
+            .await
+    }
+
+    async fn create_cards(&self, cards: Vec<CreateCardInDeck>) -> Result<Vec<Thing>, CoreError> {
+        for dto in &cards {
+            if let Some(data) = &dto.card.data {
+                data.validate()?;
+            }
+        }
+        self.db
+            .call(move |conn| {
+                let tx = conn.transaction()?;
+                let mut ids = vec![];
+                for CreateCardInDeck {
+                    deck,
+                    mut card,
+                    tag_names,
+                } in cards
+                {
+                    for name in tag_names {
+                        let tag = record("tag", get_or_create_tag(&tx, &card.user, &name)?);
+                        if !card.tags.contains(&tag) {
+                            card.tags.push(tag);
+                        }
+                    }
+                    let card = insert_card(&tx, &card)?;
+                    tx.execute(
+                        "insert into deck_card (deck, card, created_at, updated_at)
+                         values (?1, ?2, ?3, ?3)",
+                        params![key(&deck, "deck")?, card, Utc::now()],
+                    )?;
+                    ids.push(record("card", card));
+                }
+                tx.commit()?;
+                Ok(ids)
+            })
+            .await
+    }
+
+    async fn relate_card_group(
+        &self,
+        dto: CreateDeckCardGroup,
+    ) -> Result<DeckCardGroup, CoreError> {
+        self.db
+            .call(move |conn| {
+                conn.execute(
+                    "insert into deck_card_group (deck, card_group, created_at, updated_at)
+                     values (?1, ?2, ?3, ?3)",
+                    params![
+                        key(&dto.deck, "deck")?,
+                        key(&dto.card_group, "card_group")?,
+                        Utc::now()
+                    ],
+                )?;
+                Ok(fetch_deck_card_group(conn, conn.last_insert_rowid())?)
+            })
+            .await
+    }
+
+    async fn list_cards(
+        &self,
+        user: impl Into<Thing> + Send,
+        deck: impl Into<Thing> + Send,
+    ) -> Result<Vec<Card>, CoreError> {
+        let (user, deck) = (user.into(), deck.into());
+        self.db
+            .call(move |conn| {
+                let keys = select_keys(
+                    conn,
+                    "select c.id
+                     from deck_card dc
+                         join deck d on d.id = dc.deck
+                         join card c on c.id = dc.card
+                     where d.id = ?1 and d.user = ?2
+                     order by c.title",
+                    [key(&deck, "deck")?, key(&user, "user")?],
+                )?;
+                Ok(keys
+                    .into_iter()
+                    .map(|key| fetch_card(conn, key))
+                    .collect::<Result<Vec<_>, _>>()?)
+            })
+            .await
+    }
+
+    async fn list_card_groups(
+        &self,
+        user: impl Into<Thing> + Send,
+        deck: impl Into<Thing> + Send,
+    ) -> Result<Vec<CardGroup>, CoreError> {
+        let (user, deck) = (user.into(), deck.into());
+        self.db
+            .call(move |conn| {
+                let keys = select_keys(
+                    conn,
+                    "select cg.id
+                     from deck_card_group dcg
+                         join deck d on d.id = dcg.deck
+                         join card_group cg on cg.id = dcg.card_group
+                     where d.id = ?1 and d.user = ?2
+                     order by cg.title",
+                    [key(&deck, "deck")?, key(&user, "user")?],
+                )?;
+                Ok(keys
+                    .into_iter()
+                    .map(|key| fetch_card_group(conn, key))
+                    .collect::<Result<Vec<_>, _>>()?)
+            })
+            .await
+    }
+
+    async fn list_deck_cards(
+        &self,
+        user: impl Into<Thing> + Send,
+    ) -> Result<Vec<DeckCard>, CoreError> {
+        let user = user.into();
+        self.db
+            .call(move |conn| {
+                let keys = select_keys(
+                    conn,
+                    "select dc.id
+                     from deck_card dc
+                         join deck d on d.id = dc.deck
+                     where d.user = ?1
+                     order by dc.id",
+                    [key(&user, "user")?],
+                )?;
+                Ok(keys
+                    .into_iter()
+                    .map(|key| fetch_deck_card(conn, key))
+                    .collect::<Result<Vec<_>, _>>()?)
+            })
+            .await
+    }
+
+    async fn list_deck_card_groups(
+        &self,
+        user: impl Into<Thing> + Send,
+    ) -> Result<Vec<DeckCardGroup>, CoreError> {
+        let user = user.into();
+        self.db
+            .call(move |conn| {
+                let keys = select_keys(
+                    conn,
+                    "select dcg.id
+                     from deck_card_group dcg
+                         join deck d on d.id = dcg.deck
+                     where d.user = ?1
+                     order by dcg.id",
+                    [key(&user, "user")?],
+                )?;
+                Ok(keys
+                    .into_iter()
+                    .map(|key| fetch_deck_card_group(conn, key))
+                    .collect::<Result<Vec<_>, _>>()?)
+            })
+            .await
+    }
+
+    async fn get_deck_card(&self, id: impl Into<Thing> + Send) -> Result<DeckCard, CoreError> {
+        let id = id.into();
+        self.db
+            .call(move |conn| found(fetch_deck_card(conn, key(&id, "deck_card")?), &id))
+            .await
+    }
+
+    async fn get_deck_card_group(
+        &self,
+        id: impl Into<Thing> + Send,
+    ) -> Result<DeckCardGroup, CoreError> {
+        let id = id.into();
+        self.db
+            .call(move |conn| {
+                found(
+                    fetch_deck_card_group(conn, key(&id, "deck_card_group")?),
+                    &id,
+                )
+            })
+            .await
+    }
+
+    async fn list_top_ranked_cards(
+        &self,
+        user: impl Into<Thing> + Send,
+        since: DateTime<Utc>,
+    ) -> Result<Vec<DeckCard>, CoreError> {
+        let user = user.into();
+        self.db
+            .call(move |conn| {
+                let user = key(&user, "user")?;
+                let keys = top_ranked(conn, AVAILABLE_DECK_CARDS, CARD_ANSWERS, user, since)?;
+                Ok(keys
+                    .into_iter()
+                    .map(|key| {
+                        let mut deck_card = fetch_deck_card(conn, key)?;
+                        deck_card.num_answered =
+                            Some(answered_times(conn, "deck_card", key, since)?);
+                        Ok(deck_card)
+                    })
+                    .collect::<rusqlite::Result<Vec<_>>>()?)
+            })
+            .await
+    }
+
+    async fn list_top_ranked_card_groups(
+        &self,
+        user: impl Into<Thing> + Send,
+        since: DateTime<Utc>,
+    ) -> Result<Vec<DeckCardGroup>, CoreError> {
+        let user = user.into();
+        self.db
+            .call(move |conn| {
+                let user = key(&user, "user")?;
+                let keys = top_ranked(
+                    conn,
+                    AVAILABLE_DECK_CARD_GROUPS,
+                    CARD_GROUP_ANSWERS,
+                    user,
+                    since,
+                )?;
+                Ok(keys
+                    .into_iter()
+                    .map(|key| {
+                        let mut deck_card_group = fetch_deck_card_group(conn, key)?;
+                        deck_card_group.num_answered =
+                            Some(answered_times(conn, "deck_card_group", key, since)?);
+                        Ok(deck_card_group)
+                    })
+                    .collect::<rusqlite::Result<Vec<_>>>()?)
+            })
+            .await
+    }
+}
+
+impl HistoryStore for SqliteHistoryRepo {
+    async fn create_custom(&self, dto: CreateHistory) -> Result<HistoryRecord, CoreError> {
+        self.db
+            .call(move |conn| {
+                let now = Utc::now();
+                let hide_till = dto
+                    .hide_for
+                    .map(|hide_for| chrono::Duration::from_std(hide_for.0))
+                    .transpose()
+                    .map_err(|err| CoreError::DbQueryHasErrors(Arc::from(err.to_string())))?
+                    .map(|hide_for| now + hide_for);
+                let time = dto.time.clone().unwrap_or(Time {
+                    created_at: now,
+                    updated_at: now,
+                    deleted_at: None,
+                });
+
+                conn.execute(
+                    "insert into history (
+                        user, deck_card, deck_card_group, difficulty, steps, hide_for, hide_till,
+                        created_at, updated_at, deleted_at
+                     )
+                     values (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8, ?9, ?10)",
+                    params![
+                        key(&dto.user, "user")?,
+                        dto.deck_card
+                            .as_ref()
+                            .map(|id| key(id, "deck_card"))
+                            .transpose()?,
+                        dto.deck_card_group
+                            .as_ref()
+                            .map(|id| key(id, "deck_card_group"))
+                            .transpose()?,
+                        dto.difficulty,
+                        serde_json::to_string(&dto.steps)?,
+                        dto.hide_for.as_ref().map(duration_millis),
+                        hide_till,
+                        time.created_at,
+                        time.updated_at,
+                        time.deleted_at,
+                    ],
+                )?;
+                Ok(fetch_history_record(conn, conn.last_insert_rowid())?)
+            })
+            .await
+    }
+
+    async fn list_by_user_id(
+        &self,
+        id: impl Into<Thing> + Send,
+    ) -> Result<Vec<HistoryRecord>, CoreError> {
+        let id = id.into();
+        self.db
+            .call(move |conn| {
+                let keys = select_keys(
+                    conn,
+                    "select id from history where user = ?1 order by id",
+                    [key(&id, "user")?],
+                )?;
+                Ok(keys
+                    .into_iter()
+                    .map(|key| fetch_history_record(conn, key))
+                    .collect::<Result<Vec<_>, _>>()?)
+            })
+            .await
+    }
+
+    async fn get_last_answer(
+        &self,
+        edge: impl Into<Thing> + Send,
+    ) -> Result<Option<HistoryRecord>, CoreError> {
+        let edge = edge.into();
+        self.db
+            .call(move |conn| {
+                let column = match edge.tb.as_str() {
+                    "deck_card" => "deck_card",
+                    _ => "deck_card_group",
+                };
+                let key = conn
+                    .query_row(
+                        &format!(
+                            "select id from history where {column} = ?1
+                             order by created_at desc, id desc limit 1"
+                        ),
+                        [key(&edge, column)?],
+                        |row| row.get(0),
+                    )
+                    .optional()?;
+                Ok(key.map(|key| fetch_history_record(conn, key)).transpose()?)
+            })
+            .await
+    }
+
+    async fn list_answers(&self, query: StatsQuery) -> Result<Vec<Answer>, CoreError> {
+        self.db
+            .call(move |conn| {
+                let user = key(&query.user, "user")?;
+                let deck = query.deck.as_ref().map(|id| key(id, "deck")).transpose()?;
+                let tag = query.tag.as_ref().map(|id| key(id, "tag")).transpose()?;
+                Ok(conn
+                    .prepare_cached(
+                        "select a.id, a.deck, a.card, a.card_group,
+                                coalesce(c.title, cg.title) as title, a.difficulty, a.created_at
+                         from answer a
+                                  join history h on h.id = a.id
+                                  left join card c on c.id = a.card
+                                  left join card_group cg on cg.id = a.card_group
+                         where h.user = :user
+                           and a.deck is not null
+                           and a.created_at >= :from
+                           and a.created_at < :till
+                           and (:deck is null or a.deck = :deck)
+                           and (:tag is null or exists (
+                               select 1 from json_each(coalesce(c.tags, cg.tags))
+                               where value = :tag
+                           ))
+                         order by a.created_at, a.id",
+                    )?
+                    .query_map(
+                        named_params! {
+                            ":user": user,
+                            ":from": query.from,
+                            ":till": query.till,
+                            ":deck": deck,
+                            ":tag": tag,
+                        },
+                        |row| {
+                            let item = match row.get::<_, Option<i64>>("card")? {
+                                Some(card) => record("card", card),
+                                None => record("card_group", row.get("card_group")?),
+                            };
+                            Ok(Answer {
+                                id: record("history", row.get("id")?),
+                                deck: record("deck", row.get("deck")?),
+                                item,
+                                title: row.get("title")?,
+                                difficulty: row.get("difficulty")?,
+                                created_at: row.get("created_at")?,
+                            })
+                        },
+                    )?
+                    .collect::<Result<Vec<_>, _>>()?)
+            })
+            .await
+    }
+}
+
+impl BindingStore for SqliteBindingRepo {
+    async fn get_by_source_id(&self, source_id: Arc<str>) -> Result<Option<Binding>, CoreError> {
+        self.db
+            .call(move |conn| {
+                let key = conn
+                    .query_row(
+                        "select id from binding where source_id = ?1",
+                        [&source_id],
+                        |row| row.get(0),
+                    )
+                    .optional()?;
+                Ok(key.map(|key| fetch_binding(conn, key)).transpose()?)
+            })
+            .await
+    }
+
+    async fn get_or_create_binding(&self, dto: GetOrCreateBinding) -> Result<Binding, CoreError> {
+        self.db
+            .call(move |conn| {
+                let tx = conn.transaction()?;
+                let existing = tx
+                    .query_row(
+                        "select id from binding where source_id = ?1",
+                        [&dto.source_id],
+                        |row| row.get(0),
+                    )
+                    .optional()?;
+                if let Some(key) = existing {
+                    return Ok(fetch_binding(&tx, key)?);
+                }
+
+                let existing_user = tx
+                    .query_row(
+                        "select id from user where email = ?1",
+                        [&dto.email],
+                        |row| row.get(0),
+                    )
+                    .optional()?;
+                let user = match existing_user {
+                    Some(user) => user,
+                    None => insert_user(
+                        &tx,
+                        &RegisterUser {
+                            email: dto.email,
+                            name: dto.name,
+                            password: dto.password,
+                        },
+                    )?,
+                };
+
+                tx.execute(
+                    "insert into binding (user, source_id, type_name, data, created_at, updated_at)
+                     values (?1, ?2, ?3, ?4, ?5, ?5)",
+                    params![user, dto.source_id, dto.type_name, dto.data, Utc::now()],
+                )?;
+                let binding = fetch_binding(&tx, tx.last_insert_rowid())?;
+                tx.commit()?;
+                Ok(binding)
+            })
+            .await
+    }
+
+    async fn set_banned(&self, id: impl Into<Thing> + Send) -> Result<Binding, CoreError> {
+        let id = id.into();
+        self.db
+            .call(move |conn| {
+                let key = key(&id, "binding")?;
+                let updated = conn.execute(
+                    "update binding set banned_bot_at = ?2 where id = ?1",
+                    params![key, Utc::now()],
+                )?;
+                if updated == 0 {
+                    return Err(not_found(&id));
+                }
+                Ok(fetch_binding(conn, key)?)
+            })
+            .await
+    }
+
+    async fn list_all_not_banned(&self) -> Result<Vec<Binding>, CoreError> {
+        self.db
+            .call(move |conn| {
+                let keys = select_keys(
+                    conn,
+                    "select id from binding where banned_bot_at is null order by id",
+                    [],
+                )?;
+                Ok(keys
+                    .into_iter()
+                    .map(|key| fetch_binding(conn, key))
+                    .collect::<Result<Vec<_>, _>>()?)
+            })
+            .await
+    }
+}
+
+impl GlobalSettingsStore for SqliteGlobalSettingsRepo {
+    async fn create(&self, dto: CreateGlobalSettings) -> Result<GlobalSettings, CoreError> {
+        self.db
+            .call(move |conn| {
+                let timetable = dto
+                    .timetable
+                    .iter()
+                    .map(|[start, end]| [duration_millis(start), duration_millis(end)])
+                    .collect_vec();
+                conn.execute(
+                    "insert into global_settings (
+                        user, daily_limit, timetable, timezone, render_images, multiple_choice,
+                        llm, created_at, updated_at
+                     )
+                     values (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8, ?8)",
+                    params![
+                        key(&dto.user, "user")?,
+                        dto.daily_limit,
+                        serde_json::to_string(&timetable)?,
+                        dto.timezone.name(),
+                        dto.render_images,
+                        dto.multiple_choice,
+                        dto.llm.as_ref().map(serde_json::to_string).transpose()?,
+                        Utc::now(),
+                    ],
+                )?;
+                Ok(fetch_global_settings(conn, conn.last_insert_rowid())?)
+            })
+            .await
+    }
+
+    async fn get_by_user_id(
+        &self,
+        id: impl Into<Thing> + Send,
+    ) -> Result<GlobalSettings, CoreError> {
+        let id = id.into();
+        self.db
+            .call(move |conn| {
+                let key = conn
+                    .query_row(
+                        "select id from global_settings where user = ?1",
+                        [key(&id, "user")?],
+                        |row| row.get(0),
+                    )
+                    .optional()?
+                    .ok_or_else(|| not_found(&id))?;
+                Ok(fetch_global_settings(conn, key)?)
+            })
+            .await
+    }
+
+    async fn set_render_images(
+        &self,
+        user: impl Into<Thing> + Send,
+        render_images: bool,
+    ) -> Result<GlobalSettings, CoreError> {
+        let user = user.into();
+        self.db
+            .call(move |conn| {
+                let key = conn
+                    .query_row(
+                        "update global_settings set render_images = ?2, updated_at = ?3
+                         where user = ?1
+                         returning id",
+                        params![key(&user, "user")?, render_images, Utc::now()],
+                        |row| row.get(0),
+                    )
+                    .optional()?
+                    .ok_or_else(|| not_found(&user))?;
+                Ok(fetch_global_settings(conn, key)?)
+            })
+            .await
+    }
+
+    async fn set_multiple_choice(
+        &self,
+        user: impl Into<Thing> + Send,
+        multiple_choice: bool,
+    ) -> Result<GlobalSettings, CoreError> {
+        let user = user.into();
+        self.db
+            .call(move |conn| {
+                let key = conn
+                    .query_row(
+                        "update global_settings set multiple_choice = ?2, updated_at = ?3
+                         where user = ?1
+                         returning id",
+                        params![key(&user, "user")?, multiple_choice, Utc::now()],
+                        |row| row.get(0),
+                    )
+                    .optional()?
+                    .ok_or_else(|| not_found(&user))?;
+                Ok(fetch_global_settings(conn, key)?)
+            })
+            .await
+    }
+
+    async fn set_llm(
+        &self,
+        user: impl Into<Thing> + Send,
+        llm: Option<LlmChoice>,
+    ) -> Result<GlobalSettings, CoreError> {
+        let user = user.into();
+        self.db
+            .call(move |conn| {
+                let llm = llm.as_ref().map(serde_json::to_string).transpose()?;
+                let key = conn
+                    .query_row(
+                        "update global_settings set llm = ?2, updated_at = ?3
+                         where user = ?1
+                         returning id",
+                        params![key(&user, "user")?, llm, Utc::now()],
+                        |row| row.get(0),
+                    )
+                    .optional()?
+                    .ok_or_else(|| not_found(&user))?;
+                Ok(fetch_global_settings(conn, key)?)
+            })
+            .await
+    }
+}
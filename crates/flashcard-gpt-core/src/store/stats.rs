@@ -0,0 +1,24 @@
+use crate::error::CoreError;
+use crate::model::stats::{Stats, StatsQuery};
+use crate::store::any::AnyStorage;
+use crate::store::{HistoryStore, Storage};
+use chrono_tz::Tz;
+
+/// Statistics over the answers in `history`, available with every backend.
+#[derive(Debug, Clone)]
+pub struct StatsRepo<S: Storage = AnyStorage> {
+    pub history: S::History,
+}
+
+impl<S: Storage> StatsRepo<S> {
+    pub fn new(history: S::History) -> Self {
+        Self { history }
+    }
+
+    /// Days and hours of the statistics are taken in `timezone`.
+    #[tracing::instrument(level = "debug", skip_all, err, fields(user = %query.user))]
+    pub async fn get_stats(&self, query: StatsQuery, timezone: Tz) -> Result<Stats, CoreError> {
+        let answers = self.history.list_answers(query.clone()).await?;
+        Ok(Stats::new(&query, &answers, timezone))
+    }
+}
@@ -0,0 +1,304 @@
+//! SurrealDB backend. Every method forwards to the inherent method of the repository with the
+//! same name; inherent methods take precedence, so the calls below do not recurse.
+
+use crate::error::CoreError;
+use crate::llm::provider::LlmChoice;
+use crate::model::binding::{Binding, GetOrCreateBinding};
+use crate::model::card::{Card, CreateCard, UpdateCard};
+use crate::model::card_group::{CardGroup, CreateCardGroup, UpdateCardGroup};
+use crate::model::deck::{CreateDeck, Deck};
+use crate::model::deck_card::{CreateCardInDeck, CreateDeckCard, DeckCard};
+use crate::model::deck_card_group::{CreateDeckCardGroup, DeckCardGroup};
+use crate::model::global_settings::{CreateGlobalSettings, GlobalSettings};
+use crate::model::history::{CreateHistory, HistoryRecord};
+use crate::model::stats::{Answer, StatsQuery};
+use crate::model::tag::Tag;
+use crate::model::user::{RegisterUser, User};
+use crate::repo::binding::BindingRepo;
+use crate::repo::card::CardRepo;
+use crate::repo::card_group::CardGroupRepo;
+use crate::repo::deck::DeckRepo;
+use crate::repo::global_settings::GlobalSettingsRepo;
+use crate::repo::history::HistoryRepo;
+use crate::repo::tag::TagRepo;
+use crate::repo::user::UserRepo;
+use crate::store::{
+    BindingStore, CardGroupStore, CardStore, DeckStore, GlobalSettingsStore, HistoryStore, Storage,
+    TagStore, UserStore,
+};
+use chrono::Utc;
+use std::sync::Arc;
+use surrealdb::sql::Thing;
+
+#[derive(Debug, Clone)]
+pub struct SurrealStorage;
+
+impl Storage for SurrealStorage {
+    type Users = UserRepo;
+    type Tags = TagRepo;
+    type Cards = CardRepo;
+    type CardGroups = CardGroupRepo;
+    type Decks = DeckRepo;
+    type History = HistoryRepo;
+    type Bindings = BindingRepo;
+    type GlobalSettings = GlobalSettingsRepo;
+}
+
+impl UserStore for UserRepo {
+    async fn create_user(&self, user: RegisterUser) -> Result<User, CoreError> {
+        self.create_user(user).await
+    }
+
+    async fn get_by_id(&self, id: impl Into<Thing> + Send) -> Result<User, CoreError> {
+        self.get_by_id(id.into()).await
+    }
+
+    async fn clear_data(&self, id: impl Into<Thing> + Send) -> Result<(), CoreError> {
+        self.clear_data(id.into()).await
+    }
+}
+
+impl TagStore for TagRepo {
+    async fn get_or_create_tags(
+        &self,
+        user_id: impl Into<Thing> + Send,
+        tags: impl IntoIterator<Item = Arc<str>> + Send,
+    ) -> Result<Vec<Tag>, CoreError> {
+        self.get_or_create_tags(user_id.into(), tags).await
+    }
+
+    async fn list_by_user_id(&self, id: impl Into<Thing> + Send) -> Result<Vec<Tag>, CoreError> {
+        self.list_by_user_id(id.into()).await
+    }
+}
+
+impl CardStore for CardRepo {
+    async fn create(&self, dto: CreateCard) -> Result<Card, CoreError> {
+        if let Some(data) = &dto.data {
+            data.validate()?;
+        }
+        self.create(dto).await
+    }
+
+    async fn get_by_id(&self, id: impl Into<Thing> + Send) -> Result<Card, CoreError> {
+        self.get_by_id(id.into()).await
+    }
+
+    async fn patch(
+        &self,
+        id: impl Into<Thing> + Send,
+        update: UpdateCard,
+    ) -> Result<Card, CoreError> {
+        self.patch(id.into(), update).await
+    }
+
+    async fn list_by_user_id(&self, id: impl Into<Thing> + Send) -> Result<Vec<Card>, CoreError> {
+        self.list_by_user_id(id.into()).await
+    }
+
+    async fn delete(&self, id: impl Into<Thing> + Send) -> Result<(), CoreError> {
+        self.delete(id.into()).await
+    }
+}
+
+impl CardGroupStore for CardGroupRepo {
+    async fn create(&self, dto: CreateCardGroup) -> Result<CardGroup, CoreError> {
+        if let Some(data) = &dto.data {
+            data.validate()?;
+        }
+        self.create(dto).await
+    }
+
+    async fn get_by_id(&self, id: impl Into<Thing> + Send) -> Result<CardGroup, CoreError> {
+        self.get_by_id(id.into()).await
+    }
+
+    async fn patch(
+        &self,
+        id: impl Into<Thing> + Send,
+        update: UpdateCardGroup,
+    ) -> Result<CardGroup, CoreError> {
+        self.patch(id.into(), update).await
+    }
+
+    async fn list_by_user_id(
+        &self,
+        id: impl Into<Thing> + Send,
+    ) -> Result<Vec<CardGroup>, CoreError> {
+        self.list_by_user_id(id.into()).await
+    }
+
+    async fn delete(&self, id: impl Into<Thing> + Send) -> Result<(), CoreError> {
+        self.delete(id.into()).await
+    }
+}
+
+impl DeckStore for DeckRepo {
+    async fn create(&self, dto: CreateDeck) -> Result<Deck, CoreError> {
+        self.create(dto).await
+    }
+
+    async fn get_by_id(&self, id: impl Into<Thing> + Send) -> Result<Deck, CoreError> {
+        self.get_by_id(id.into()).await
+    }
+
+    async fn list_by_user_id(&self, id: impl Into<Thing> + Send) -> Result<Vec<Deck>, CoreError> {
+        self.list_by_user_id(id.into()).await
+    }
+
+    async fn relate_card(&self, dto: CreateDeckCard) -> Result<DeckCard, CoreError> {
+        self.relate_card(dto).await
+    }
+
+    async fn create_cards(&self, cards: Vec<CreateCardInDeck>) -> Result<Vec<Thing>, CoreError> {
+        for dto in &cards {
+            if let Some(data) = &dto.card.data {
+                data.validate()?;
+            }
+        }
+        self.create_cards(cards).await
+    }
+
+    async fn relate_card_group(
+        &self,
+        dto: CreateDeckCardGroup,
+    ) -> Result<DeckCardGroup, CoreError> {
+        self.relate_card_group(dto).await
+    }
+
+    async fn list_cards(
+        &self,
+        user: impl Into<Thing> + Send,
+        deck: impl Into<Thing> + Send,
+    ) -> Result<Vec<Card>, CoreError> {
+        self.list_cards(user.into(), deck.into()).await
+    }
+
+    async fn list_card_groups(
+        &self,
+        user: impl Into<Thing> + Send,
+        deck: impl Into<Thing> + Send,
+    ) -> Result<Vec<CardGroup>, CoreError> {
+        self.list_card_groups(user.into(), deck.into()).await
+    }
+
+    async fn list_deck_cards(
+        &self,
+        user: impl Into<Thing> + Send,
+    ) -> Result<Vec<DeckCard>, CoreError> {
+        self.list_deck_cards(user.into()).await
+    }
+
+    async fn list_deck_card_groups(
+        &self,
+        user: impl Into<Thing> + Send,
+    ) -> Result<Vec<DeckCardGroup>, CoreError> {
+        self.list_deck_card_groups(user.into()).await
+    }
+
+    async fn get_deck_card(&self, id: impl Into<Thing> + Send) -> Result<DeckCard, CoreError> {
+        self.get_deck_card(id.into()).await
+    }
+
+    async fn get_deck_card_group(
+        &self,
+        id: impl Into<Thing> + Send,
+    ) -> Result<DeckCardGroup, CoreError> {
+        self.get_deck_card_group(id.into()).await
+    }
+
+    async fn list_top_ranked_cards(
+        &self,
+        user: impl Into<Thing> + Send,
+        since: chrono::DateTime<Utc>,
+    ) -> Result<Vec<DeckCard>, CoreError> {
+        self.list_top_ranked_cards(user.into(), since).await
+    }
+
+    async fn list_top_ranked_card_groups(
+        &self,
+        user: impl Into<Thing> + Send,
+        since: chrono::DateTime<Utc>,
+    ) -> Result<Vec<DeckCardGroup>, CoreError> {
+        self.list_top_ranked_card_groups(user.into(), since).await
+    }
+}
+
+impl HistoryStore for HistoryRepo {
+    async fn create_custom(&self, dto: CreateHistory) -> Result<HistoryRecord, CoreError> {
+        self.create_custom(dto).await
+    }
+
+    async fn list_by_user_id(
+        &self,
+        id: impl Into<Thing> + Send,
+    ) -> Result<Vec<HistoryRecord>, CoreError> {
+        self.list_by_user_id(id.into()).await
+    }
+
+    async fn get_last_answer(
+        &self,
+        edge: impl Into<Thing> + Send,
+    ) -> Result<Option<HistoryRecord>, CoreError> {
+        self.get_last_answer(edge.into()).await
+    }
+
+    async fn list_answers(&self, query: StatsQuery) -> Result<Vec<Answer>, CoreError> {
+        self.list_answers(query).await
+    }
+}
+
+impl BindingStore for BindingRepo {
+    async fn get_by_source_id(&self, source_id: Arc<str>) -> Result<Option<Binding>, CoreError> {
+        self.get_by_source_id(source_id).await
+    }
+
+    async fn get_or_create_binding(&self, dto: GetOrCreateBinding) -> Result<Binding, CoreError> {
+        self.get_or_create_binding(dto).await
+    }
+
+    async fn set_banned(&self, id: impl Into<Thing> + Send) -> Result<Binding, CoreError> {
+        self.set_banned(id.into()).await
+    }
+
+    async fn list_all_not_banned(&self) -> Result<Vec<Binding>, CoreError> {
+        self.list_all_not_banned().await
+    }
+}
+
+impl GlobalSettingsStore for GlobalSettingsRepo {
+    async fn create(&self, dto: CreateGlobalSettings) -> Result<GlobalSettings, CoreError> {
+        self.create(dto).await
+    }
+
+    async fn get_by_user_id(
+        &self,
+        id: impl Into<Thing> + Send,
+    ) -> Result<GlobalSettings, CoreError> {
+        self.get_by_user_id(id.into()).await
+    }
+
+    async fn set_render_images(
+        &self,
+        user: impl Into<Thing> + Send,
+        render_images: bool,
+    ) -> Result<GlobalSettings, CoreError> {
+        self.set_render_images(user.into(), render_images).await
+    }
+
+    async fn set_multiple_choice(
+        &self,
+        user: impl Into<Thing> + Send,
+        multiple_choice: bool,
+    ) -> Result<GlobalSettings, CoreError> {
+        self.set_multiple_choice(user.into(), multiple_choice).await
+    }
+
+    async fn set_llm(
+        &self,
+        user: impl Into<Thing> + Send,
+        llm: Option<LlmChoice>,
+    ) -> Result<GlobalSettings, CoreError> {
+        self.set_llm(user.into(), llm).await
+    }
+}
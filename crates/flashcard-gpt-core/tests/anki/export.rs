@@ -0,0 +1,310 @@
+use flashcard_gpt_core::anki::export::{AnkiExporter, ExportOptions};
+use flashcard_gpt_core::anki::import::{AnkiImporter, ImportOptions};
+use flashcard_gpt_core::blob::BlobStore;
+use flashcard_gpt_core::model::card::{Card, CreateCard};
+use flashcard_gpt_core::model::card_data::CardData;
+use flashcard_gpt_core::model::card_group::CreateCardGroup;
+use flashcard_gpt_core::model::deck_card::CreateDeckCard;
+use flashcard_gpt_core::model::deck_card_group::CreateDeckCardGroup;
+use flashcard_gpt_core::model::media::{CardSide, Media, MediaKind};
+use flashcard_gpt_core::model::user::User;
+use flashcard_gpt_core::store::memory::{
+    MemoryCardGroupRepo, MemoryCardRepo, MemoryDb, MemoryDeckRepo, MemoryRepo, MemoryStorage,
+    MemoryTagRepo,
+};
+use flashcard_gpt_core::store::siblings::CardSiblingsExt;
+use flashcard_gpt_core::store::{CardGroupStore, CardStore, DeckStore, TagStore};
+use flashcard_gpt_tests::db::memory::{card, create_deck, create_user};
+use std::sync::Arc;
+use testresult::TestResult;
+
+/// Decks of a user with a basic card with hints and a photo, a reversed card, a cloze card and a
+/// card group.
+async fn create_decks(db: &MemoryDb, user: &User, blobs: &BlobStore) -> TestResult {
+    let cards = MemoryCardRepo::new(db.clone());
+    let decks = MemoryDeckRepo::new(db.clone());
+
+    let languages = create_deck()
+        .db(db)
+        .user(user)
+        .title("Languages")
+        .call()
+        .await?;
+    let spanish = create_deck()
+        .db(db)
+        .user(user)
+        .title("Spanish")
+        .parent(languages.id.clone())
+        .call()
+        .await?;
+
+    let tags = MemoryTagRepo::new(db.clone())
+        .get_or_create_tags(user.id.clone(), [Arc::from("spanish verbs")])
+        .await?;
+    blobs.put("mouth.jpg", b"jpeg").await?;
+    let photo = Media {
+        kind: MediaKind::Photo,
+        side: CardSide::Back,
+        file_id: Arc::from("file-id"),
+        file_name: Some(Arc::from("mouth.jpg")),
+        mime_type: None,
+        blob: Some(Arc::from("mouth.jpg")),
+    };
+    let hablar = CreateCard {
+        hints: vec![Arc::from("first hint"), Arc::from("second hint")],
+        data: Some(CardData::builder().media(vec![photo]).build()),
+        tags: tags.into_iter().map(|tag| tag.id).collect(),
+        ..card(user, "**hablar**", Some("to speak"))
+    };
+    let mut created = vec![cards.create(hablar).await?];
+    created.extend(
+        cards
+            .create_with_sibling(CreateCard {
+                reverse: true,
+                ..card(user, "la casa", Some("the house"))
+            })
+            .await?,
+    );
+    let cloze = card(user, "{{c1::Madrid}} is the capital of {{c2::Spain}}", None);
+    for dto in cloze.into_cloze_cards() {
+        created.push(cards.create(dto).await?);
+    }
+    for card in created {
+        decks
+            .relate_card(CreateDeckCard {
+                deck: spanish.id.clone(),
+                card: card.id,
+            })
+            .await?;
+    }
+
+    let mut words = vec![];
+    for (front, back) in [("perro", "dog"), ("gato", "cat")] {
+        words.push(cards.create(card(user, front, Some(back))).await?.id);
+    }
+    let card_group = MemoryCardGroupRepo::new(db.clone())
+        .create(CreateCardGroup {
+            user: user.id.clone(),
+            title: Arc::from("Common words"),
+            importance: 0,
+            difficulty: 0,
+            data: None,
+            cards: words,
+            tags: vec![],
+        })
+        .await?;
+    decks
+        .relate_card_group(CreateDeckCardGroup {
+            deck: languages.id,
+            card_group: card_group.id,
+        })
+        .await?;
+
+    Ok(())
+}
+
+fn find<'a>(cards: &'a [Card], front: &str) -> &'a Card {
+    cards
+        .iter()
+        .find(|card| card.front.as_deref() == Some(front))
+        .unwrap_or_else(|| panic!("no card {front}"))
+}
+
+#[tokio::test]
+async fn test_export_round_trip() -> TestResult {
+    let blobs_root = std::env::temp_dir().join(format!("anki_export_blobs_{}", std::process::id()));
+    let blobs = BlobStore::new(&blobs_root);
+
+    let db = MemoryDb::new();
+    let user = create_user(&db, "anki").await?;
+    create_decks(&db, &user, &blobs).await?;
+
+    let options = ExportOptions::builder().blobs(blobs.clone()).build();
+    let (package, report) = AnkiExporter::<MemoryStorage>::new(MemoryRepo::new(db.clone()))
+        .export(&user, options)
+        .await?;
+    assert_eq!(report.decks, 2);
+    assert_eq!(report.notes, 5);
+    assert_eq!(report.cards, 7);
+    assert_eq!(report.media, 1);
+    assert_eq!(report.skipped_media, 0);
+
+    let db = MemoryDb::new();
+    let user = create_user(&db, "anki").await?;
+    let importer = AnkiImporter::<MemoryStorage>::new(
+        MemoryRepo::new(db.clone()),
+        MemoryRepo::new(db.clone()),
+        MemoryRepo::new(db.clone()),
+        MemoryRepo::new(db.clone()),
+        MemoryRepo::new(db.clone()),
+    );
+    let options = ImportOptions::builder().blobs(blobs.clone()).build();
+    let report = importer.import(&user, package, options).await?;
+    assert_eq!(report.decks, 2);
+    assert_eq!(report.cards, 7);
+    assert_eq!(report.media, 1);
+    assert!(report.skipped.is_empty());
+
+    let decks = MemoryDeckRepo::new(db.clone());
+    let imported = decks.list_by_user_id(&user).await?;
+    let languages = imported
+        .iter()
+        .find(|deck| &*deck.title == "Languages")
+        .unwrap();
+    let spanish = imported
+        .iter()
+        .find(|deck| &*deck.title == "Spanish")
+        .unwrap();
+    assert_eq!(spanish.parent.as_ref(), Some(&languages.id));
+
+    let cards = decks.list_cards(&user, spanish.id.clone()).await?;
+    assert_eq!(cards.len(), 5);
+    let hablar = find(&cards, "**hablar**");
+    assert_eq!(hablar.back.as_deref(), Some("to speak"));
+    let hints = hablar.hints.iter().map(|hint| &**hint).collect::<Vec<_>>();
+    assert_eq!(hints, vec!["first hint", "second hint"]);
+    assert_eq!(&*hablar.tags[0].name, "spanish_verbs");
+    let media = hablar
+        .data
+        .as_ref()
+        .unwrap()
+        .media(CardSide::Back)
+        .collect::<Vec<_>>();
+    assert_eq!(blobs.get(media[0].blob.as_deref().unwrap()).await?, b"jpeg");
+
+    let house = find(&cards, "la casa");
+    let reversed = find(&cards, "the house");
+    assert_eq!(house.sibling.as_ref(), Some(&reversed.id));
+
+    let mut cloze = cards
+        .iter()
+        .filter_map(|card| card.cloze_index())
+        .collect::<Vec<_>>();
+    cloze.sort();
+    assert_eq!(cloze, vec![1, 2]);
+
+    // card groups come back as cards tagged with the group
+    let words = decks.list_cards(&user, languages.id.clone()).await?;
+    assert_eq!(words.len(), 2);
+    for word in &words {
+        let tags = word.tags.iter().map(|tag| &*tag.name).collect::<Vec<_>>();
+        assert_eq!(tags, vec!["card_group::Common_words"]);
+    }
+
+    tokio::fs::remove_dir_all(&blobs_root).await?;
+    Ok(())
+}
+
+#[tokio::test]
+async fn test_export_deck() -> TestResult {
+    let blobs_root = std::env::temp_dir().join(format!("anki_export_deck_{}", std::process::id()));
+    let blobs = BlobStore::new(&blobs_root);
+
+    let db = MemoryDb::new();
+    let user = create_user(&db, "anki").await?;
+    create_decks(&db, &user, &blobs).await?;
+    let spanish = MemoryDeckRepo::new(db.clone())
+        .list_by_user_id(&user)
+        .await?
+        .into_iter()
+        .find(|deck| &*deck.title == "Spanish")
+        .unwrap();
+
+    // a subdeck keeps the name of its parent, photos are skipped without the blob store
+    let options = ExportOptions::builder().deck(spanish.id).build();
+    let (_, report) = AnkiExporter::<MemoryStorage>::new(MemoryRepo::new(db.clone()))
+        .export(&user, options)
+        .await?;
+    assert_eq!(report.decks, 1);
+    assert_eq!(report.notes, 3);
+    assert_eq!(report.media, 0);
+    assert_eq!(report.skipped_media, 1);
+
+    tokio::fs::remove_dir_all(&blobs_root).await?;
+    Ok(())
+}
+
+/// The cards of `package` once imported by a new user.
+async fn import(package: Vec<u8>) -> TestResult<Vec<Card>> {
+    let db = MemoryDb::new();
+    let user = create_user(&db, "anki").await?;
+    let importer = AnkiImporter::<MemoryStorage>::new(
+        MemoryRepo::new(db.clone()),
+        MemoryRepo::new(db.clone()),
+        MemoryRepo::new(db.clone()),
+        MemoryRepo::new(db.clone()),
+        MemoryRepo::new(db.clone()),
+    );
+    importer
+        .import(&user, package, ImportOptions::default())
+        .await?;
+
+    Ok(MemoryCardRepo::new(db).list_by_user_id(&user).await?)
+}
+
+#[tokio::test]
+async fn test_export_reversed_pair_across_decks() -> TestResult {
+    let db = MemoryDb::new();
+    let user = create_user(&db, "anki").await?;
+    let cards = MemoryCardRepo::new(db.clone());
+    let decks = MemoryDeckRepo::new(db.clone());
+
+    // the deck of the reversed card is exported first
+    let reversed_deck = create_deck()
+        .db(&db)
+        .user(&user)
+        .title("Reversed")
+        .call()
+        .await?;
+    let original_deck = create_deck()
+        .db(&db)
+        .user(&user)
+        .title("Original")
+        .call()
+        .await?;
+    let pair = cards
+        .create_with_sibling(CreateCard {
+            reverse: true,
+            ..card(&user, "la casa", Some("the house"))
+        })
+        .await?;
+    for (deck, card) in [(&original_deck, &pair[0]), (&reversed_deck, &pair[1])] {
+        decks
+            .relate_card(CreateDeckCard {
+                deck: deck.id.clone(),
+                card: card.id.clone(),
+            })
+            .await?;
+    }
+    let exporter = AnkiExporter::<MemoryStorage>::new(MemoryRepo::new(db.clone()));
+
+    let (package, report) = exporter.export(&user, ExportOptions::default()).await?;
+    assert_eq!(report.notes, 1);
+    assert_eq!(report.cards, 2);
+    let imported = import(package).await?;
+    assert_eq!(imported.len(), 2);
+    let house = find(&imported, "la casa");
+    assert_eq!(house.back.as_deref(), Some("the house"));
+    assert_eq!(
+        house.sibling.as_ref(),
+        Some(&find(&imported, "the house").id)
+    );
+
+    // without its pair a card of the pair is a basic note
+    for (deck, front, back) in [
+        (&original_deck, "la casa", "the house"),
+        (&reversed_deck, "the house", "la casa"),
+    ] {
+        let options = ExportOptions::builder().deck(deck.id.clone()).build();
+        let (package, report) = exporter.export(&user, options).await?;
+        assert_eq!(report.notes, 1);
+        assert_eq!(report.cards, 1);
+        let imported = import(package).await?;
+        assert_eq!(imported.len(), 1);
+        assert_eq!(imported[0].front.as_deref(), Some(front));
+        assert_eq!(imported[0].back.as_deref(), Some(back));
+        assert_eq!(imported[0].sibling, None);
+    }
+
+    Ok(())
+}
@@ -0,0 +1,283 @@
+use flashcard_gpt_core::anki::import::{AnkiImporter, ImportOptions};
+use flashcard_gpt_core::blob::BlobStore;
+use flashcard_gpt_core::error::CoreError;
+use flashcard_gpt_core::model::media::CardSide;
+use flashcard_gpt_core::store::memory::{
+    MemoryCardGroupRepo, MemoryCardRepo, MemoryDb, MemoryDeckRepo, MemoryHistoryRepo, MemoryRepo,
+    MemoryStorage,
+};
+use flashcard_gpt_core::store::{CardGroupStore, CardStore, DeckStore, HistoryStore};
+use flashcard_gpt_tests::db::memory::create_user;
+use rusqlite::{params, Connection};
+use serde_json::json;
+use std::io::{Cursor, Write};
+use std::sync::Arc;
+use testresult::TestResult;
+use zip::write::SimpleFileOptions;
+use zip::ZipWriter;
+
+const BASIC: i64 = 100;
+const REVERSED: i64 = 200;
+const CLOZE: i64 = 300;
+const THREE_WAY: i64 = 400;
+
+const DEFAULT_DECK: i64 = 1;
+const SPANISH_DECK: i64 = 10;
+
+fn template(ord: usize, qfmt: &str, afmt: &str) -> serde_json::Value {
+    json!({"ord": ord, "qfmt": qfmt, "afmt": afmt})
+}
+
+fn fields(names: &[&str]) -> serde_json::Value {
+    let fields = names
+        .iter()
+        .enumerate()
+        .map(|(ord, name)| json!({"name": name, "ord": ord}))
+        .collect::<Vec<_>>();
+    json!(fields)
+}
+
+/// A package in the legacy format with only the columns the importer reads.
+pub fn package() -> TestResult<Vec<u8>> {
+    let answer = "{{FrontSide}}<hr id=answer>";
+    let models = json!({
+        BASIC.to_string(): {
+            "name": "Basic", "type": 0, "flds": fields(&["Front", "Back"]),
+            "tmpls": [template(0, "{{Front}}", &format!("{answer}{{{{Back}}}}"))],
+        },
+        REVERSED.to_string(): {
+            "name": "Basic (and reversed card)", "type": 0, "flds": fields(&["Front", "Back"]),
+            "tmpls": [
+                template(0, "{{Front}}", &format!("{answer}{{{{Back}}}}")),
+                template(1, "{{Back}}", &format!("{answer}{{{{Front}}}}")),
+            ],
+        },
+        CLOZE.to_string(): {
+            "name": "Cloze", "type": 1, "flds": fields(&["Text", "Back Extra"]),
+            "tmpls": [template(0, "{{cloze:Text}}", "{{cloze:Text}}<br>{{Back Extra}}")],
+        },
+        THREE_WAY.to_string(): {
+            "name": "Word", "type": 0, "flds": fields(&["Word", "Meaning", "Example"]),
+            "tmpls": [
+                template(0, "{{Word}}", "{{Meaning}}"),
+                template(1, "{{Meaning}}", "{{Word}}"),
+                template(2, "{{Example}}", "{{Word}}"),
+            ],
+        },
+    });
+    let decks = json!({
+        DEFAULT_DECK.to_string(): {"name": "Default"},
+        SPANISH_DECK.to_string(): {"name": "Languages::Spanish"},
+    });
+
+    let path = std::env::temp_dir().join(format!("anki_import_{}.anki2", std::process::id()));
+    let _ = std::fs::remove_file(&path);
+    let conn = Connection::open(&path)?;
+    conn.execute_batch(
+        "create table col (models text not null, decks text not null);
+         create table notes (id integer, mid integer, tags text, flds text, sfld text);
+         create table cards (id integer, nid integer, did integer, odid integer, ord integer);
+         create table revlog (id integer, cid integer, ease integer, ivl integer);",
+    )?;
+    conn.execute(
+        "insert into col (models, decks) values (?1, ?2)",
+        params![models.to_string(), decks.to_string()],
+    )?;
+
+    let notes: [(i64, i64, &str, &[&str]); 5] = [
+        (
+            1,
+            BASIC,
+            " spanish verbs ",
+            &["<b>hablar</b>", "to speak<img src=\"mouth.jpg\">"],
+        ),
+        (2, REVERSED, "", &["la casa", "the house"]),
+        (
+            3,
+            CLOZE,
+            "",
+            &["{{c1::Madrid}} is the capital of {{c2::Spain}}", ""],
+        ),
+        (4, THREE_WAY, "", &["perro", "dog", "El perro ladra."]),
+        (5, 999, "", &["orphan", "note"]),
+    ];
+    for (id, model, tags, fields) in notes {
+        conn.execute(
+            "insert into notes (id, mid, tags, flds, sfld) values (?1, ?2, ?3, ?4, ?5)",
+            params![id, model, tags, fields.join("\x1f"), fields[0]],
+        )?;
+    }
+
+    // (card id, note id, deck id, ord), the reversed card is in a filtered deck
+    let cards = [
+        (11, 1, SPANISH_DECK, 0, 0),
+        (21, 2, DEFAULT_DECK, 0, 0),
+        (22, 2, 99, DEFAULT_DECK, 1),
+        (31, 3, DEFAULT_DECK, 0, 0),
+        (32, 3, DEFAULT_DECK, 0, 1),
+        (41, 4, SPANISH_DECK, 0, 0),
+        (42, 4, SPANISH_DECK, 0, 1),
+        (43, 4, SPANISH_DECK, 0, 2),
+        (51, 5, DEFAULT_DECK, 0, 0),
+    ];
+    for (id, note, deck, original_deck, ord) in cards {
+        conn.execute(
+            "insert into cards (id, nid, did, odid, ord) values (?1, ?2, ?3, ?4, ?5)",
+            params![id, note, deck, original_deck, ord],
+        )?;
+    }
+
+    // a lapse, a good answer and a manual reschedule of the basic card, and an answer of the group,
+    // with intervals in days or in seconds when negative
+    let reviews = [
+        (1_700_000_000_000_i64, 11, 1, -600),
+        (1_700_086_400_000, 11, 3, 3),
+        (1_700_172_800_000, 11, 0, 5),
+        (1_700_000_000_000, 42, 4, 4),
+    ];
+    for (id, card, ease, interval) in reviews {
+        conn.execute(
+            "insert into revlog (id, cid, ease, ivl) values (?1, ?2, ?3, ?4)",
+            params![id, card, ease, interval],
+        )?;
+    }
+    drop(conn);
+    let collection = std::fs::read(&path)?;
+    std::fs::remove_file(&path)?;
+
+    let mut zip = ZipWriter::new(Cursor::new(vec![]));
+    zip.start_file("collection.anki2", SimpleFileOptions::default())?;
+    zip.write_all(&collection)?;
+    zip.start_file("media", SimpleFileOptions::default())?;
+    zip.write_all(json!({"0": "mouth.jpg"}).to_string().as_bytes())?;
+    zip.start_file("0", SimpleFileOptions::default())?;
+    zip.write_all(b"jpeg")?;
+
+    Ok(zip.finish()?.into_inner())
+}
+
+pub fn importer(db: &MemoryDb) -> AnkiImporter<MemoryStorage> {
+    AnkiImporter::new(
+        MemoryRepo::new(db.clone()),
+        MemoryRepo::new(db.clone()),
+        MemoryRepo::new(db.clone()),
+        MemoryRepo::new(db.clone()),
+        MemoryRepo::new(db.clone()),
+    )
+}
+
+#[tokio::test]
+async fn test_import() -> TestResult {
+    let db = MemoryDb::new();
+    let user = create_user(&db, "anki").await?;
+    let blobs_root = std::env::temp_dir().join(format!("anki_import_blobs_{}", std::process::id()));
+    let blobs = BlobStore::new(&blobs_root);
+
+    let options = ImportOptions::builder()
+        .history(true)
+        .blobs(blobs.clone())
+        .build();
+    let report = importer(&db).import(&user, package()?, options).await?;
+
+    assert_eq!(report.decks, 3);
+    assert_eq!(report.cards, 8);
+    assert_eq!(report.card_groups, 1);
+    assert_eq!(report.history, 3);
+    assert_eq!(report.media, 1);
+    assert_eq!(report.skipped_media, 0);
+    assert_eq!(
+        report.skipped.into_iter().collect::<Vec<_>>(),
+        vec![(Arc::from("unknown note type: missing"), 1)]
+    );
+
+    let decks = MemoryDeckRepo::new(db.clone())
+        .list_by_user_id(&user)
+        .await?;
+    let languages = decks
+        .iter()
+        .find(|deck| &*deck.title == "Languages")
+        .unwrap();
+    let spanish = decks.iter().find(|deck| &*deck.title == "Spanish").unwrap();
+    assert_eq!(spanish.parent.as_ref(), Some(&languages.id));
+
+    let cards = MemoryCardRepo::new(db.clone())
+        .list_by_user_id(&user)
+        .await?;
+    let hablar = cards
+        .iter()
+        .find(|card| &*card.title == "**hablar**")
+        .unwrap();
+    assert_eq!(hablar.front.as_deref(), Some("**hablar**"));
+    assert_eq!(hablar.back.as_deref(), Some("to speak"));
+    assert_eq!(hablar.tags.len(), 2);
+    let media = hablar
+        .data
+        .as_ref()
+        .unwrap()
+        .media(CardSide::Back)
+        .collect::<Vec<_>>();
+    assert_eq!(media.len(), 1);
+    assert_eq!(blobs.get(media[0].blob.as_deref().unwrap()).await?, b"jpeg");
+
+    let house = cards
+        .iter()
+        .find(|card| card.front.as_deref() == Some("la casa"))
+        .unwrap();
+    let reversed = cards
+        .iter()
+        .find(|card| card.front.as_deref() == Some("the house"))
+        .unwrap();
+    assert_eq!(house.sibling.as_ref(), Some(&reversed.id));
+    assert_eq!(reversed.sibling.as_ref(), Some(&house.id));
+
+    let mut cloze = cards
+        .iter()
+        .filter_map(|card| card.cloze_index())
+        .collect::<Vec<_>>();
+    cloze.sort();
+    assert_eq!(cloze, vec![1, 2]);
+
+    let card_groups = MemoryCardGroupRepo::new(db.clone())
+        .list_by_user_id(&user)
+        .await?;
+    assert_eq!(card_groups.len(), 1);
+    assert_eq!(card_groups[0].cards.len(), 3);
+
+    let history = MemoryHistoryRepo::new(db.clone())
+        .list_by_user_id(&user)
+        .await?;
+    let difficulties = history
+        .iter()
+        .map(|record| record.difficulty)
+        .collect::<Vec<_>>();
+    assert_eq!(difficulties.len(), 3);
+    assert!(difficulties.contains(&8) && difficulties.contains(&2) && difficulties.contains(&0));
+    assert_eq!(
+        history
+            .iter()
+            .filter(|record| record.deck_card_group.is_some())
+            .count(),
+        1
+    );
+
+    tokio::fs::remove_dir_all(&blobs_root).await?;
+    Ok(())
+}
+
+#[tokio::test]
+async fn test_new_format_is_rejected() -> TestResult {
+    let db = MemoryDb::new();
+    let user = create_user(&db, "anki").await?;
+
+    let mut zip = ZipWriter::new(Cursor::new(vec![]));
+    zip.start_file("collection.anki2", SimpleFileOptions::default())?;
+    zip.start_file("collection.anki21b", SimpleFileOptions::default())?;
+    let package = zip.finish()?.into_inner();
+
+    let result = importer(&db)
+        .import(&user, package, ImportOptions::default())
+        .await;
+    assert!(matches!(result, Err(CoreError::InvalidAnkiPackage(_))));
+
+    Ok(())
+}
@@ -0,0 +1,315 @@
+use chrono::{TimeDelta, Utc};
+use flashcard_gpt_core::backup::export::BackupExporter;
+use flashcard_gpt_core::backup::restore::{BackupRestorer, RestoreMode, RestoreOptions};
+use flashcard_gpt_core::backup::{Archive, VERSION};
+use flashcard_gpt_core::error::CoreError;
+use flashcard_gpt_core::model::annotation::{Annotations, Flag};
+use flashcard_gpt_core::model::card::{CreateCard, UpdateCard};
+use flashcard_gpt_core::model::card_group::CreateCardGroup;
+use flashcard_gpt_core::model::deck::DeckSettings;
+use flashcard_gpt_core::model::deck_card::CreateDeckCard;
+use flashcard_gpt_core::model::deck_card_group::CreateDeckCardGroup;
+use flashcard_gpt_core::model::global_settings::CreateGlobalSettings;
+use flashcard_gpt_core::model::history::CreateHistory;
+use flashcard_gpt_core::model::time::Time;
+use flashcard_gpt_core::model::user::User;
+use flashcard_gpt_core::store::memory::{
+    MemoryCardGroupRepo, MemoryCardRepo, MemoryDb, MemoryDeckRepo, MemoryGlobalSettingsRepo,
+    MemoryHistoryRepo, MemoryRepo, MemoryStorage, MemoryTagRepo,
+};
+use flashcard_gpt_core::store::{
+    CardGroupStore, CardStore, DeckStore, GlobalSettingsStore, HistoryStore, TagStore,
+};
+use flashcard_gpt_tests::db::memory::{card, create_deck, create_user};
+use std::sync::Arc;
+use surrealdb::sql::Duration;
+use testresult::TestResult;
+
+fn time(days_ago: i64) -> Option<Time> {
+    let at = Utc::now() - TimeDelta::days(days_ago);
+    Some(Time {
+        created_at: at,
+        updated_at: at,
+        deleted_at: None,
+    })
+}
+
+/// Settings, decks Languages and Spanish in it, a card and its reversed sibling in Spanish with an
+/// answer, and a card group in Languages with an answer.
+async fn create_account(db: &MemoryDb, user: &User) -> TestResult {
+    MemoryGlobalSettingsRepo::new(db.clone())
+        .create(CreateGlobalSettings {
+            user: user.id.clone(),
+            daily_limit: 30,
+            timetable: vec![[Duration::from_hours(9), Duration::from_hours(21)]],
+            timezone: chrono_tz::Tz::Europe__Madrid,
+            render_images: true,
+            multiple_choice: true,
+            llm: None,
+        })
+        .await?;
+
+    let cards = MemoryCardRepo::new(db.clone());
+    let decks = MemoryDeckRepo::new(db.clone());
+    let history = MemoryHistoryRepo::new(db.clone());
+    let settings = DeckSettings {
+        daily_limit: 15,
+        reverse_cards: false,
+    };
+    let languages = create_deck()
+        .db(db)
+        .user(user)
+        .title("Languages")
+        .description("description")
+        .settings(settings.clone())
+        .call()
+        .await?;
+    let spanish = create_deck()
+        .db(db)
+        .user(user)
+        .title("Spanish")
+        .parent(languages.id.clone())
+        .description("description")
+        .settings(settings)
+        .call()
+        .await?;
+
+    let tags = MemoryTagRepo::new(db.clone())
+        .get_or_create_tags(user.id.clone(), [Arc::from("verbs")])
+        .await?;
+    let hablar = cards
+        .create(CreateCard {
+            hints: vec![Arc::from("an -ar verb")],
+            difficulty: 3,
+            tags: tags.into_iter().map(|tag| tag.id).collect(),
+            ..card(user, "hablar", Some("to speak"))
+        })
+        .await?;
+    let reversed = cards.create(card(user, "to speak", Some("hablar"))).await?;
+    let annotations = Annotations {
+        notes: vec![Arc::from("irregular in some tenses")],
+        flags: [Flag::Favorite].into(),
+    };
+    cards
+        .patch(
+            hablar.id.clone(),
+            UpdateCard::builder()
+                .sibling(reversed.id.clone())
+                .annotations(annotations)
+                .build(),
+        )
+        .await?;
+    cards
+        .patch(
+            reversed.id.clone(),
+            UpdateCard::builder().sibling(hablar.id.clone()).build(),
+        )
+        .await?;
+    for card in [&hablar, &reversed] {
+        let deck_card = decks
+            .relate_card(CreateDeckCard {
+                deck: spanish.id.clone(),
+                card: card.id.clone(),
+            })
+            .await?;
+        history
+            .create_custom(CreateHistory {
+                user: user.id.clone(),
+                deck_card: Some(deck_card.id),
+                deck_card_group: None,
+                difficulty: 4,
+                time: time(3),
+                hide_for: Some(Duration::from_hours(24)),
+                steps: vec![],
+            })
+            .await?;
+    }
+
+    let mut words = vec![];
+    for (front, back) in [("perro", "dog"), ("gato", "cat")] {
+        words.push(cards.create(card(user, front, Some(back))).await?.id);
+    }
+    let card_group = MemoryCardGroupRepo::new(db.clone())
+        .create(CreateCardGroup {
+            user: user.id.clone(),
+            title: Arc::from("Common words"),
+            importance: 2,
+            difficulty: 0,
+            data: None,
+            cards: words,
+            tags: vec![],
+        })
+        .await?;
+    let deck_card_group = decks
+        .relate_card_group(CreateDeckCardGroup {
+            deck: languages.id,
+            card_group: card_group.id,
+        })
+        .await?;
+    history
+        .create_custom(CreateHistory {
+            user: user.id.clone(),
+            deck_card: None,
+            deck_card_group: Some(deck_card_group.id),
+            difficulty: 7,
+            time: time(1),
+            hide_for: None,
+            steps: vec![Some(7), None],
+        })
+        .await?;
+
+    Ok(())
+}
+
+fn exporter(db: &MemoryDb) -> BackupExporter<MemoryStorage> {
+    BackupExporter::new(
+        MemoryRepo::new(db.clone()),
+        MemoryRepo::new(db.clone()),
+        MemoryRepo::new(db.clone()),
+        MemoryRepo::new(db.clone()),
+        MemoryRepo::new(db.clone()),
+        MemoryRepo::new(db.clone()),
+        MemoryRepo::new(db.clone()),
+    )
+}
+
+fn restorer(db: &MemoryDb) -> BackupRestorer<MemoryStorage> {
+    BackupRestorer::new(
+        MemoryRepo::new(db.clone()),
+        MemoryRepo::new(db.clone()),
+        MemoryRepo::new(db.clone()),
+        MemoryRepo::new(db.clone()),
+        MemoryRepo::new(db.clone()),
+        MemoryRepo::new(db.clone()),
+        MemoryRepo::new(db.clone()),
+    )
+}
+
+fn options(mode: RestoreMode) -> RestoreOptions {
+    RestoreOptions::builder().mode(mode).build()
+}
+
+#[tokio::test]
+async fn test_backup_round_trip() -> TestResult {
+    let db = MemoryDb::new();
+    let user = create_user(&db, "backup").await?;
+    create_account(&db, &user).await?;
+
+    let archive = exporter(&db).export(&user).await?;
+    assert_eq!(archive.version, VERSION);
+    assert_eq!(&*archive.user.name, "backup");
+    let report = archive.report();
+    assert_eq!(report.tags, 1);
+    assert_eq!(report.decks, 2);
+    assert_eq!(report.cards, 4);
+    assert_eq!(report.card_groups, 1);
+    assert_eq!(report.history, 3);
+    // parents first, oldest answers first
+    assert_eq!(&*archive.decks[0].title, "Languages");
+    assert!(archive.history[0].created_at < archive.history[2].created_at);
+
+    // restore into another account on another instance
+    let json = archive.to_json()?;
+    let db = MemoryDb::new();
+    let user = create_user(&db, "restored").await?;
+    let report = restorer(&db)
+        .restore(&user, &Archive::parse(&json)?, RestoreOptions::default())
+        .await?;
+    assert_eq!(report.decks, 2);
+    assert_eq!(report.cards, 4);
+    assert_eq!(report.history, 3);
+    assert!(report.settings);
+
+    let restored = exporter(&db).export(&user).await?;
+    assert_eq!(restored.report(), archive.report());
+    assert_eq!(restored.settings, archive.settings);
+    assert_eq!(restored.decks[1].settings, archive.decks[1].settings);
+    assert_eq!(restored.decks[1].parent, Some(restored.decks[0].id.clone()));
+    let times = |archive: &Archive| {
+        archive
+            .history
+            .iter()
+            .map(|answer| (answer.created_at, answer.difficulty, answer.steps.clone()))
+            .collect::<Vec<_>>()
+    };
+    assert_eq!(times(&restored), times(&archive));
+
+    let cards = MemoryCardRepo::new(db.clone())
+        .list_by_user_id(&user)
+        .await?;
+    let hablar = cards.iter().find(|card| &*card.title == "hablar").unwrap();
+    let reversed = cards
+        .iter()
+        .find(|card| &*card.title == "to speak")
+        .unwrap();
+    assert_eq!(hablar.sibling.as_ref(), Some(&reversed.id));
+    assert_eq!(reversed.sibling.as_ref(), Some(&hablar.id));
+    assert_eq!(hablar.annotations.notes.len(), 1);
+    assert!(hablar.annotations.flags.contains(&Flag::Favorite));
+    assert_eq!(&*hablar.tags[0].name, "verbs");
+
+    // answered three days ago and hidden for a day, so it is not hidden anymore
+    let history = MemoryHistoryRepo::new(db.clone())
+        .list_by_user_id(&user)
+        .await?;
+    assert!(history.iter().all(|record| record.hide_for.is_none()));
+
+    // a merge keeps the decks and the settings and adds the cards again
+    let report = restorer(&db)
+        .restore(&user, &archive, options(RestoreMode::Merge))
+        .await?;
+    assert_eq!(report.decks, 0);
+    assert_eq!(report.merged_decks, 2);
+    assert!(!report.settings);
+    let merged = exporter(&db).export(&user).await?.report();
+    assert_eq!(merged.decks, 2);
+    assert_eq!(merged.tags, 1);
+    assert_eq!(merged.cards, 8);
+    assert_eq!(merged.history, 6);
+
+    // a replace starts over
+    restorer(&db)
+        .restore(&user, &archive, options(RestoreMode::Replace))
+        .await?;
+    assert_eq!(
+        exporter(&db).export(&user).await?.report(),
+        archive.report()
+    );
+
+    Ok(())
+}
+
+#[tokio::test]
+async fn test_restore_invalid_archive() -> TestResult {
+    let db = MemoryDb::new();
+    let user = create_user(&db, "invalid").await?;
+    create_account(&db, &user).await?;
+    let archive = exporter(&db).export(&user).await?;
+
+    // a card group referencing a card the archive doesn't have is rejected before the replace
+    let mut broken = archive.clone();
+    let missing = broken.card_groups[0].cards[0].clone();
+    broken.cards.retain(|card| card.id != missing);
+    let result = restorer(&db)
+        .restore(&user, &broken, options(RestoreMode::Replace))
+        .await;
+    assert!(matches!(result, Err(CoreError::InvalidBackup(_))));
+    assert_eq!(
+        exporter(&db).export(&user).await?.report(),
+        archive.report()
+    );
+
+    let mut broken = archive.clone();
+    broken.decks.reverse();
+    let result = restorer(&db)
+        .restore(&user, &broken, RestoreOptions::default())
+        .await;
+    assert!(matches!(result, Err(CoreError::InvalidBackup(_))));
+
+    let mut json = serde_json::to_value(&archive)?;
+    json["version"] = (VERSION + 1).into();
+    let result = Archive::parse(&serde_json::to_vec(&json)?);
+    assert!(matches!(result, Err(CoreError::InvalidBackup(_))));
+
+    Ok(())
+}
@@ -0,0 +1,197 @@
+use flashcard_gpt_core::csv::export::{CsvExporter, ExportOptions};
+use flashcard_gpt_core::csv::import::{CsvImporter, ImportOptions};
+use flashcard_gpt_core::csv::ColumnMapping;
+use flashcard_gpt_core::error::CoreError;
+use flashcard_gpt_core::store::memory::{
+    MemoryCardRepo, MemoryDb, MemoryDeckRepo, MemoryRepo, MemoryStorage,
+};
+use flashcard_gpt_core::store::{CardStore, DeckStore};
+use flashcard_gpt_tests::db::memory::{create_deck, create_user};
+use testresult::TestResult;
+
+fn importer(db: &MemoryDb) -> CsvImporter<MemoryStorage> {
+    CsvImporter::new(MemoryRepo::new(db.clone()))
+}
+
+#[tokio::test]
+async fn test_import_round_trip() -> TestResult {
+    let db = MemoryDb::new();
+    let user = create_user(&db, "csv").await?;
+    let languages = create_deck()
+        .db(&db)
+        .user(&user)
+        .title("Languages")
+        .call()
+        .await?;
+    let spanish = create_deck()
+        .db(&db)
+        .user(&user)
+        .title("Spanish")
+        .parent(languages.id.clone())
+        .call()
+        .await?;
+
+    let file = "\
+Question\tAnswer\tLevel\tLabels\tHints
+hablar\tto speak\t3\tverbs; spanish\tan -ar verb;starts with h
+la casa\tthe house\t\tnouns\t
+";
+    let mut columns = ColumnMapping::default();
+    for entry in [
+        "front=Question",
+        "back=Answer",
+        "difficulty=Level",
+        "tags=Labels",
+    ] {
+        columns.apply(entry)?;
+    }
+    let options = ImportOptions::builder()
+        .delimiter(b'\t')
+        .columns(columns)
+        .deck(spanish.id.clone())
+        .build();
+    let report = importer(&db)
+        .import(&user, file.as_bytes(), options)
+        .await?;
+    assert!(report.imported(), "{report}");
+    assert_eq!(report.rows, 2);
+    assert_eq!(report.cards, 2);
+
+    let decks = MemoryDeckRepo::new(db.clone());
+    let cards = decks.list_cards(&user, spanish.id.clone()).await?;
+    let hablar = cards.iter().find(|card| &*card.title == "hablar").unwrap();
+    assert_eq!(hablar.back.as_deref(), Some("to speak"));
+    assert_eq!(hablar.difficulty, 3);
+    let hints = hablar.hints.iter().map(|hint| &**hint).collect::<Vec<_>>();
+    assert_eq!(hints, vec!["an -ar verb", "starts with h"]);
+    let mut tags = hablar.tags.iter().map(|tag| &*tag.name).collect::<Vec<_>>();
+    tags.sort();
+    assert_eq!(tags, vec!["spanish", "verbs"]);
+
+    // the exported file imports as is into the same decks of another user
+    let (exported, report) = CsvExporter::<MemoryStorage>::new(MemoryRepo::new(db.clone()))
+        .export(&user, ExportOptions::default())
+        .await?;
+    assert_eq!(report.decks, 2);
+    assert_eq!(report.cards, 2);
+
+    let db = MemoryDb::new();
+    let user = create_user(&db, "csv").await?;
+    let languages = create_deck()
+        .db(&db)
+        .user(&user)
+        .title("Languages")
+        .call()
+        .await?;
+    let spanish = create_deck()
+        .db(&db)
+        .user(&user)
+        .title("Spanish")
+        .parent(languages.id.clone())
+        .call()
+        .await?;
+    // a second deck with the same title is told apart by the path
+    create_deck()
+        .db(&db)
+        .user(&user)
+        .title("Spanish")
+        .call()
+        .await?;
+    let report = importer(&db)
+        .import(&user, &exported, ImportOptions::default())
+        .await?;
+    assert!(report.imported(), "{report}");
+
+    let cards = MemoryDeckRepo::new(db.clone())
+        .list_cards(&user, spanish.id)
+        .await?;
+    assert_eq!(cards.len(), 2);
+    let hablar = cards.iter().find(|card| &*card.title == "hablar").unwrap();
+    assert_eq!(hablar.difficulty, 3);
+    assert_eq!(hablar.hints.len(), 2);
+    assert_eq!(hablar.tags.len(), 2);
+
+    Ok(())
+}
+
+#[tokio::test]
+async fn test_import_row_errors() -> TestResult {
+    let db = MemoryDb::new();
+    let user = create_user(&db, "csv").await?;
+    create_deck()
+        .db(&db)
+        .user(&user)
+        .title("Spanish")
+        .call()
+        .await?;
+
+    let file = "\
+front,back,difficulty,importance,deck
+hablar,to speak,3,2,Spanish
+,no front,,,Spanish
+comer,to eat,11,,Spanish
+beber,to drink,,x,Spanish
+vivir,to live,,,French
+dormir,to sleep,,,
+";
+    let report = importer(&db)
+        .import(&user, file.as_bytes(), ImportOptions::default())
+        .await?;
+    assert!(!report.imported());
+    assert_eq!(report.rows, 6);
+    let rows = report
+        .errors
+        .iter()
+        .map(|error| error.row)
+        .collect::<Vec<_>>();
+    assert_eq!(rows, vec![3, 4, 5, 6, 7]);
+
+    // nothing is stored when a row is invalid
+    let cards = MemoryCardRepo::new(db.clone())
+        .list_by_user_id(&user)
+        .await?;
+    assert!(cards.is_empty());
+
+    // a dry run of a valid file stores nothing either
+    let file = "front,deck\nhablar,Spanish\n";
+    let options = ImportOptions::builder().dry_run(true).build();
+    let report = importer(&db)
+        .import(&user, file.as_bytes(), options)
+        .await?;
+    assert!(report.errors.is_empty());
+    assert_eq!(report.cards, 1);
+    assert!(!report.imported());
+    let cards = MemoryCardRepo::new(db.clone())
+        .list_by_user_id(&user)
+        .await?;
+    assert!(cards.is_empty());
+
+    // the front column is required
+    let file = "question,answer\nhablar,to speak\n";
+    assert!(importer(&db)
+        .import(&user, file.as_bytes(), ImportOptions::default())
+        .await
+        .is_err());
+
+    // the default deck must be a deck of the user
+    let other = create_user(&db, "other").await?;
+    let theirs = create_deck()
+        .db(&db)
+        .user(&other)
+        .title("Theirs")
+        .call()
+        .await?;
+    let file = "front\nhablar\n";
+    let options = ImportOptions::builder().deck(theirs.id.clone()).build();
+    let err = importer(&db)
+        .import(&user, file.as_bytes(), options)
+        .await
+        .unwrap_err();
+    assert!(matches!(err, CoreError::NotFound(_)), "{err:?}");
+    assert!(MemoryDeckRepo::new(db.clone())
+        .list_cards(&other, theirs.id)
+        .await?
+        .is_empty());
+
+    Ok(())
+}
@@ -1,10 +1,10 @@
 use flashcard_gpt_core::model::card::{CreateCard, UpdateCard};
+use flashcard_gpt_core::model::card_data::CardData;
 use flashcard_gpt_core::repo::card::CardRepo;
 use flashcard_gpt_core::repo::tag::TagRepo;
 use flashcard_gpt_tests::db::utils::{create_card, create_tag, create_user};
 use flashcard_gpt_tests::db::TestDbExt;
 use flashcard_gpt_tests::db::TEST_DB;
-use serde_json::json;
 use std::sync::Arc;
 use testresult::TestResult;
 use tracing::{span, Level};
@@ -20,9 +20,10 @@ async fn test_create() -> TestResult {
         title: Arc::from("title"),
         front: Some(Arc::from("a")),
         back: Some(Arc::from("b")),
-        data: Some(Arc::from(json!({
-            "a": "b"
-        }))),
+        data: Some(CardData::builder().notes(Arc::from("a")).build()),
+        choices: None,
+        sibling: None,
+        reverse: false,
         hints: vec![Arc::from("a")],
         difficulty: 3,
         importance: 2,
@@ -86,9 +87,10 @@ async fn test_patch_card() -> TestResult {
         title: Arc::from("title"),
         front: Some(Arc::from("a")),
         back: Some(Arc::from("b")),
-        data: Some(Arc::from(json!({
-            "a": "b"
-        }))),
+        data: Some(CardData::builder().notes(Arc::from("a")).build()),
+        choices: None,
+        sibling: None,
+        reverse: false,
         hints: vec![Arc::from("a")],
         difficulty: 3,
         importance: 2,
@@ -103,6 +105,13 @@ async fn test_patch_card() -> TestResult {
             UpdateCard {
                 importance: Some(6),
                 difficulty: Some(7),
+                sibling: None,
+                annotations: None,
+                title: None,
+                front: None,
+                back: None,
+                hints: None,
+                tags: None,
             },
         )
         .await?;
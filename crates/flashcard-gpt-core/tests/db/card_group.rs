@@ -1,8 +1,8 @@
+use flashcard_gpt_core::model::card_data::CardData;
 use flashcard_gpt_core::model::card_group::{CreateCardGroup, UpdateCardGroup};
 use flashcard_gpt_tests::db::utils::{
     create_card, create_card_group_repo, create_tag, create_user,
 };
-use serde_json::json;
 use std::sync::Arc;
 use testresult::TestResult;
 
@@ -37,9 +37,7 @@ async fn test_create() -> TestResult {
         tags: vec![tag.id],
         cards: vec![card1.id, card2.id],
         difficulty: 2,
-        data: Some(Arc::from(json!({
-            "a": "b"
-        }))),
+        data: Some(CardData::builder().notes(Arc::from("a")).build()),
     };
 
     let card_group = repo.create(card_group).await?;
@@ -68,9 +66,7 @@ async fn test_patch() -> TestResult {
         tags: vec![tag.id],
         cards: vec![],
         difficulty: 2,
-        data: Some(Arc::from(json!({
-            "a": "b"
-        }))),
+        data: Some(CardData::builder().notes(Arc::from("a")).build()),
     };
     let card_group = repo.create(card_group).await?;
 
@@ -80,6 +76,10 @@ async fn test_patch() -> TestResult {
             UpdateCardGroup {
                 importance: Some(3),
                 difficulty: Some(4),
+                cards: None,
+                annotations: None,
+                title: None,
+                tags: None,
             },
         )
         .await?;
@@ -92,6 +92,10 @@ async fn test_patch() -> TestResult {
             UpdateCardGroup {
                 importance: None,
                 difficulty: None,
+                cards: None,
+                annotations: None,
+                title: None,
+                tags: None,
             },
         )
         .await?;
@@ -104,6 +108,10 @@ async fn test_patch() -> TestResult {
             UpdateCardGroup {
                 importance: Some(7),
                 difficulty: None,
+                cards: None,
+                annotations: None,
+                title: None,
+                tags: None,
             },
         )
         .await?;
@@ -0,0 +1,112 @@
+use flashcard_gpt_core::connection::{apply_migrations, DbSettings};
+use flashcard_gpt_core::ext::response_ext::ResponseExt;
+use flashcard_gpt_core::model::card_data::CardData;
+use serde_json::json;
+use surrealdb::engine::any::connect;
+use surrealdb::opt::capabilities::Capabilities;
+use surrealdb::opt::Config;
+use testresult::TestResult;
+
+/// The migrations that ran before `data` got its fields.
+static BEFORE_TYPED_CARD_DATA: [(&str, &str); 4] = [
+    (
+        "20240902_185441_Initial",
+        include_str!("../../db-migrations/migrations/20240902_185441_Initial.surql"),
+    ),
+    (
+        "20241020_000000_MultipleChoice",
+        include_str!("../../db-migrations/migrations/20241020_000000_MultipleChoice.surql"),
+    ),
+    (
+        "20241021_000000_ReversedCards",
+        include_str!("../../db-migrations/migrations/20241021_000000_ReversedCards.surql"),
+    ),
+    (
+        "20241022_000000_ProgressiveCardGroups",
+        include_str!("../../db-migrations/migrations/20241022_000000_ProgressiveCardGroups.surql"),
+    ),
+];
+
+#[tokio::test]
+async fn test_apply_migrations_is_idempotent() -> TestResult {
+    let db = DbSettings::in_memory().connect().await?;
+
+    // connect() has already migrated the embedded database once
+    apply_migrations(&db).await?;
+
+    let mut response = db
+        .query("select value script_name from script_migration")
+        .await?;
+    let mut applied: Vec<String> = response.take(0)?;
+    applied.sort();
+    assert_eq!(
+        applied,
+        [
+            "20240902_185441_Initial",
+            "20241020_000000_MultipleChoice",
+            "20241021_000000_ReversedCards",
+            "20241022_000000_ProgressiveCardGroups",
+            "20241023_000000_TypedCardData",
+            "20241024_000000_CardMedia",
+            "20241025_000000_RenderImages",
+            "20241026_000000_Annotations",
+            "20241027_000000_SourceHash",
+            "20241028_000000_SourceReference",
+            "20241029_000000_LlmChoice",
+            "20241030_000000_MultipleChoiceSetting",
+            "20241031_000000_UserAccessKey",
+        ]
+    );
+
+    let mut response = db.query("select * from user").await?;
+    let users: Vec<serde_json::Value> = response.take(0)?;
+    assert!(users.is_empty());
+
+    Ok(())
+}
+
+#[tokio::test]
+async fn test_typed_card_data_keeps_unknown_keys() -> TestResult {
+    let db = connect(("mem://", Config::new().capabilities(Capabilities::all()))).await?;
+    db.use_ns("test").use_db("test").await?;
+    db.query(include_str!(
+        "../../db-migrations/schemas/script_migration.surql"
+    ))
+    .await?
+    .errors_or_ok()?;
+    for (name, script) in BEFORE_TYPED_CARD_DATA {
+        db.query(script).await?.errors_or_ok()?;
+        db.query("create script_migration set script_name = $name")
+            .bind(("name", name))
+            .await?
+            .errors_or_ok()?;
+    }
+
+    db.query(
+        "create user:alice set email = 'alice@example.com', name = 'alice', password = 'alice';
+        create card:legacy set user = user:alice, title = 'legacy', data = {
+            leetcode_link: 'leetcode.com/problems/largest-number',
+            article: 'article',
+            difficulty_notes: 'hard'
+        };",
+    )
+    .await?
+    .errors_or_ok()?;
+
+    apply_migrations(&db).await?;
+
+    let mut response = db.query("select value data from card:legacy").await?;
+    let data: Option<CardData> = response.take(0)?;
+    let data = data.ok_or("the card has no data")?;
+    assert_eq!(data.source_link, None);
+    assert_eq!(data.article.as_deref(), Some("article"));
+    assert_eq!(
+        serde_json::Value::Object(data.extra),
+        json!({
+            "leetcode_link": "leetcode.com/problems/largest-number",
+            "difficulty_notes": "hard"
+        })
+    );
+
+    Ok(())
+}
@@ -41,7 +41,10 @@ async fn test_create() -> TestResult {
         .user(&user)
         .tags([&tag])
         .parent(deck.id.clone())
-        .settings(DeckSettings { daily_limit: 200 })
+        .settings(DeckSettings {
+            daily_limit: 200,
+            reverse_cards: false,
+        })
         .call()
         .await?;
 
@@ -166,6 +169,7 @@ async fn test_get_top_ranked_card_group() -> TestResult {
             .title(format!("sample deck {deck_index}"))
             .settings(DeckSettings {
                 daily_limit: deck_index + 1,
+                reverse_cards: false,
             })
             .tags([&tag])
             .user(&user)
@@ -235,6 +239,7 @@ async fn test_get_top_ranked_card_group() -> TestResult {
                     deleted_at: None,
                 }),
                 hide_for: Some(Duration::from_secs(10000)),
+                steps: vec![],
             })
             .await?;
 
@@ -254,6 +259,7 @@ async fn test_get_top_ranked_card_group() -> TestResult {
                     deleted_at: None,
                 }),
                 hide_for: Some(Duration::from_secs(10000)),
+                steps: vec![],
             })
             .await?;
 
@@ -22,6 +22,9 @@ async fn test_create() -> TestResult {
                 [Duration::from_hours(17), Duration::from_hours(18)],
             ],
             timezone: Tz::Europe__Dublin,
+            render_images: false,
+            multiple_choice: false,
+            llm: None,
         })
         .await?;
 
@@ -46,6 +49,9 @@ async fn test_create() -> TestResult {
                 [Duration::from_hours(17), Duration::from_hours(18)],
             ],
             timezone: Tz::Europe__Dublin,
+            render_images: false,
+            multiple_choice: false,
+            llm: None,
         })
         .await;
     assert!(result.is_err());
@@ -55,6 +55,7 @@ async fn test_create() -> TestResult {
             difficulty: 3,
             time: None,
             hide_for: None,
+            steps: vec![],
         })
         .await?;
     assert_ne!(history.time.created_at, time);
@@ -72,6 +73,7 @@ async fn test_create() -> TestResult {
                 deleted_at: None,
             }),
             hide_for: Some(Duration::from_secs(10000)),
+            steps: vec![],
         })
         .await?;
 
@@ -104,6 +106,7 @@ async fn test_create() -> TestResult {
             difficulty: 2,
             time: None,
             hide_for: Some(Duration::from_secs(10000)),
+            steps: vec![],
         })
         .await?;
 
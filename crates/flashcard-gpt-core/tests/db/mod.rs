@@ -1,8 +1,11 @@
 mod binding;
 mod card;
 mod card_group;
+mod connection;
 mod deck;
 mod global_settings;
 mod history;
+mod session;
+mod stats;
 mod tag;
 mod user;
@@ -0,0 +1,215 @@
+use flashcard_gpt_core::connection::DbSettings;
+use flashcard_gpt_core::error::CoreError;
+use flashcard_gpt_core::model::card::CreateCard;
+use flashcard_gpt_core::model::deck::CreateDeck;
+use flashcard_gpt_core::model::deck_card::CreateDeckCard;
+use flashcard_gpt_core::model::user::{RegisterUser, User};
+use flashcard_gpt_core::repo::card::CardRepo;
+use flashcard_gpt_core::repo::deck::DeckRepo;
+use flashcard_gpt_core::repo::user::UserRepo;
+use flashcard_gpt_core::session::{authenticate, UserSessions};
+use std::sync::Arc;
+use surrealdb::engine::any::Any;
+use surrealdb::Surreal;
+use testresult::TestResult;
+use tracing::{span, Level};
+
+// Authenticating switches the session of every clone of the connection, so these tests use a
+// database of their own instead of TEST_DB.
+
+const ACCESS_KEY: &str = "a key of the session tests, long enough to be accepted";
+
+/// An in-memory database whose `user` access is signed with [`ACCESS_KEY`].
+fn settings() -> DbSettings {
+    DbSettings {
+        access_key: Some(Arc::from(ACCESS_KEY)),
+        ..DbSettings::in_memory()
+    }
+}
+
+async fn create_user(db: &Surreal<Any>, name: &str) -> TestResult<User> {
+    let repo = UserRepo::new_user(db.clone(), span!(Level::INFO, "session_user"), false);
+    let user = repo
+        .create_user(RegisterUser {
+            email: format!("{name}@example.com").into(),
+            name: Arc::from(name),
+            password: Arc::from(name),
+        })
+        .await?;
+    Ok(user)
+}
+
+fn card(user: &User, title: &str) -> CreateCard {
+    CreateCard {
+        user: user.id.clone(),
+        title: Arc::from(title),
+        front: Some(Arc::from("front")),
+        back: Some(Arc::from("back")),
+        hints: vec![],
+        difficulty: 1,
+        importance: 1,
+        data: None,
+        choices: None,
+        sibling: None,
+        reverse: false,
+        tags: vec![],
+    }
+}
+
+#[tokio::test]
+async fn test_user_cannot_read_other_users_cards() -> TestResult {
+    let settings = settings();
+    let db = settings.connect().await?;
+    let cards = CardRepo::new_card(db.clone(), span!(Level::INFO, "session_card"), false);
+
+    let alice = create_user(&db, "alice").await?;
+    let bob = create_user(&db, "bob").await?;
+    let alice_card = cards.create(card(&alice, "alice")).await?;
+    let bob_card = cards.create(card(&bob, "bob")).await?;
+
+    authenticate(&db, &settings, &alice.id).await?;
+
+    assert_eq!(
+        cards.get_by_id(alice_card.id.clone()).await?.id,
+        alice_card.id
+    );
+    assert_eq!(cards.list_by_user_id(alice.id.clone()).await?.len(), 1);
+
+    assert!(cards.get_by_id(bob_card.id.clone()).await.is_err());
+    assert!(cards.list_by_user_id(bob.id.clone()).await?.is_empty());
+    assert!(cards.create(card(&bob, "forged")).await.is_err());
+
+    Ok(())
+}
+
+#[tokio::test]
+async fn test_user_cannot_relate_other_users_cards() -> TestResult {
+    let settings = settings();
+    let db = settings.connect().await?;
+    let span = span!(Level::INFO, "session_deck");
+    let cards = CardRepo::new_card(db.clone(), span.clone(), false);
+    let decks = DeckRepo::new_deck(db.clone(), span, false);
+
+    let alice = create_user(&db, "alice").await?;
+    let bob = create_user(&db, "bob").await?;
+    let bob_card = cards.create(card(&bob, "bob")).await?;
+
+    authenticate(&db, &settings, &alice.id).await?;
+
+    let deck = decks
+        .create(CreateDeck {
+            description: None,
+            parent: None,
+            settings: None,
+            tags: vec![],
+            title: Arc::from("alice"),
+            user: alice.id.clone(),
+        })
+        .await?;
+    let relation = CreateDeckCard {
+        deck: deck.id.clone(),
+        card: bob_card.id.clone(),
+    };
+    assert!(decks.relate_card(relation).await.is_err());
+    assert!(decks
+        .list_cards(alice.id.clone(), deck.id)
+        .await?
+        .is_empty());
+
+    Ok(())
+}
+
+#[tokio::test]
+async fn test_token_must_be_signed_with_access_key() -> TestResult {
+    let settings = settings();
+    let db = settings.connect().await?;
+    let alice = create_user(&db, "alice").await?;
+
+    let forged = DbSettings {
+        access_key: Some(Arc::from("not the access key")),
+        ..settings.clone()
+    };
+    assert!(authenticate(&db, &forged, &alice.id).await.is_err());
+
+    Ok(())
+}
+
+#[tokio::test]
+async fn test_migrations_do_not_declare_an_access_key() -> TestResult {
+    let db = DbSettings::in_memory().connect().await?;
+    let alice = create_user(&db, "alice").await?;
+
+    assert!(authenticate(&db, &settings(), &alice.id).await.is_err());
+
+    Ok(())
+}
+
+#[tokio::test]
+async fn test_remote_endpoints_require_an_access_key() -> TestResult {
+    let remote = DbSettings {
+        endpoint: Arc::from("ws://127.0.0.1:8477"),
+        ..DbSettings::in_memory()
+    };
+
+    let result = remote.connect().await;
+    assert!(matches!(result, Err(CoreError::InvalidConfig(_))));
+
+    Ok(())
+}
+
+#[tokio::test]
+async fn test_short_access_keys_are_rejected() -> TestResult {
+    let short = DbSettings {
+        access_key: Some(Arc::from("short")),
+        ..DbSettings::in_memory()
+    };
+
+    let result = short.connect().await;
+    assert!(matches!(result, Err(CoreError::InvalidConfig(_))));
+
+    Ok(())
+}
+
+#[tokio::test]
+async fn test_no_user_sessions_for_embedded_engines() -> TestResult {
+    assert!(UserSessions::new(settings()).is_none());
+
+    let remote = DbSettings {
+        endpoint: Arc::from("ws://127.0.0.1:8477"),
+        ..settings()
+    };
+    assert!(UserSessions::new(remote.clone()).is_some());
+
+    let without_key = DbSettings {
+        access_key: None,
+        ..remote
+    };
+    assert!(UserSessions::new(without_key).is_none());
+
+    Ok(())
+}
+
+/// Without sessions the connection of an embedded engine is not authenticated as any user, so
+/// only the `user` filters of the queries keep the cards of other users out.
+#[tokio::test]
+async fn test_embedded_engines_rely_on_user_filters() -> TestResult {
+    let settings = DbSettings::in_memory();
+    let db = settings.connect().await?;
+    let cards = CardRepo::new_card(db.clone(), span!(Level::INFO, "session_embedded"), false);
+
+    let alice = create_user(&db, "alice").await?;
+    let bob = create_user(&db, "bob").await?;
+    cards.create(card(&alice, "alice")).await?;
+    let bob_card = cards.create(card(&bob, "bob")).await?;
+
+    assert!(UserSessions::new(settings).is_none());
+
+    let listed = cards.list_by_user_id(alice.id.clone()).await?;
+    assert_eq!(listed.len(), 1);
+    assert_eq!(listed[0].user.id, alice.id);
+
+    // Nothing but the filters: a lookup by id is not checked against any user.
+    assert_eq!(cards.get_by_id(bob_card.id.clone()).await?.id, bob_card.id);
+
+    Ok(())
+}
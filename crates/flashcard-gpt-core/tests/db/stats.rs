@@ -0,0 +1,165 @@
+use chrono::{DateTime, TimeZone, Utc};
+use chrono_tz::Tz;
+use flashcard_gpt_core::model::deck_card::CreateDeckCard;
+use flashcard_gpt_core::model::deck_card_group::CreateDeckCardGroup;
+use flashcard_gpt_core::model::history::CreateHistory;
+use flashcard_gpt_core::model::stats::StatsQuery;
+use flashcard_gpt_core::model::time::Time;
+use flashcard_gpt_core::store::stats::StatsRepo;
+use flashcard_gpt_core::store::surreal::SurrealStorage;
+use flashcard_gpt_tests::db::utils::{
+    create_card, create_card_group, create_deck, create_deck_repo, create_history_repo, create_tag,
+    create_user,
+};
+use surrealdb::sql::Thing;
+use testresult::TestResult;
+
+fn at(day: u32, hour: u32) -> DateTime<Utc> {
+    Utc.with_ymd_and_hms(2024, 10, day, hour, 0, 0).unwrap()
+}
+
+fn answer(
+    user: &Thing,
+    deck_card: Option<&Thing>,
+    deck_card_group: Option<&Thing>,
+    difficulty: u8,
+    created_at: DateTime<Utc>,
+) -> CreateHistory {
+    CreateHistory {
+        user: user.clone(),
+        deck_card: deck_card.cloned(),
+        deck_card_group: deck_card_group.cloned(),
+        difficulty,
+        time: Some(Time {
+            created_at,
+            updated_at: created_at,
+            deleted_at: None,
+        }),
+        hide_for: None,
+        steps: vec![],
+    }
+}
+
+#[tokio::test]
+async fn test_get_stats() -> TestResult {
+    let deck_repo = create_deck_repo().await?;
+    let history_repo = create_history_repo().await?;
+    let stats_repo = StatsRepo::<SurrealStorage>::new(history_repo.clone());
+    let user = create_user("stats_get").await?;
+
+    let tag = create_tag()
+        .name("stats")
+        .slug("stats")
+        .user(user.id.clone())
+        .call()
+        .await?;
+    let other_tag = create_tag()
+        .name("stats other")
+        .slug("stats_other")
+        .user(user.id.clone())
+        .call()
+        .await?;
+    let deck = create_deck()
+        .title("stats deck")
+        .tags([&tag])
+        .user(user.id.clone())
+        .call()
+        .await?;
+    let other_deck = create_deck()
+        .title("stats other deck")
+        .tags([&other_tag])
+        .user(user.id.clone())
+        .call()
+        .await?;
+    let tagged = create_card()
+        .user(user.id.clone())
+        .title("tagged")
+        .tags([&tag])
+        .call()
+        .await?;
+    let untagged = create_card()
+        .user(user.id.clone())
+        .title("untagged")
+        .tags([&other_tag])
+        .call()
+        .await?;
+    let card_group = create_card_group()
+        .user(user.id.clone())
+        .title("group")
+        .tags([&other_tag])
+        .cards([&untagged])
+        .call()
+        .await?;
+
+    let tagged = deck_repo
+        .relate_card(CreateDeckCard {
+            deck: deck.id.clone(),
+            card: tagged.id.clone(),
+        })
+        .await?;
+    let untagged = deck_repo
+        .relate_card(CreateDeckCard {
+            deck: other_deck.id.clone(),
+            card: untagged.id.clone(),
+        })
+        .await?;
+    let card_group = deck_repo
+        .relate_card_group(CreateDeckCardGroup {
+            deck: deck.id.clone(),
+            card_group: card_group.id.clone(),
+        })
+        .await?;
+
+    let answers = [
+        answer(&user.id, Some(&tagged.id), None, 8, at(1, 9)),
+        answer(&user.id, Some(&tagged.id), None, 2, at(2, 9)),
+        answer(&user.id, Some(&untagged.id), None, 1, at(2, 18)),
+        answer(&user.id, None, Some(&card_group.id), 6, at(3, 9)),
+        // outside of the range
+        answer(&user.id, Some(&tagged.id), None, 10, at(9, 9)),
+    ];
+    for answer in answers {
+        history_repo.create_custom(answer).await?;
+    }
+
+    let query = StatsQuery::builder()
+        .user(user.id.clone())
+        .from(at(1, 0))
+        .till(at(4, 0))
+        .build();
+    let stats = stats_repo.get_stats(query.clone(), Tz::UTC).await?;
+    assert_eq!(stats.reviews, 4);
+    assert_eq!(
+        stats.reviews_per_day.values().collect::<Vec<_>>(),
+        [&1, &2, &1]
+    );
+    assert_eq!(stats.average_difficulty, Some(17.0 / 4.0));
+    assert_eq!(stats.retention, Some(0.5));
+    assert_eq!((stats.current_streak, stats.best_streak), (3, 3));
+    assert_eq!(stats.hours[9], 3);
+    assert_eq!(stats.hardest[0].title.as_ref(), "group");
+    assert_eq!(stats.hardest[0].item, card_group.card_group.id);
+
+    let by_deck = StatsQuery {
+        deck: Some(deck.id.clone()),
+        ..query.clone()
+    };
+    assert_eq!(stats_repo.get_stats(by_deck, Tz::UTC).await?.reviews, 3);
+
+    let by_tag = StatsQuery {
+        tag: Some(tag.id.clone()),
+        ..query.clone()
+    };
+    let stats = stats_repo.get_stats(by_tag, Tz::UTC).await?;
+    assert_eq!(stats.reviews, 2);
+    assert_eq!(stats.average_difficulty, Some(5.0));
+
+    let other_user = create_user("stats_other").await?;
+    let other_user = StatsQuery {
+        user: other_user.id,
+        ..query
+    };
+    assert_eq!(stats_repo.get_stats(other_user, Tz::UTC).await?.reviews, 0);
+
+    Ok(())
+}
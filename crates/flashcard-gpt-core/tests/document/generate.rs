@@ -0,0 +1,133 @@
+use flashcard_gpt_core::document::generate::{DocumentGenerator, GenerateOptions};
+use flashcard_gpt_core::document::Document;
+use flashcard_gpt_core::llm::card_generator_service::CardGeneratorService;
+use flashcard_gpt_core::llm::custom_executor::CustomExecutor;
+use flashcard_gpt_core::llm::mock_executor::MockExecutor;
+use flashcard_gpt_core::store::memory::{MemoryCardGroupRepo, MemoryDb, MemoryRepo, MemoryStorage};
+use flashcard_gpt_core::store::CardGroupStore;
+use flashcard_gpt_tests::db::memory::{create_deck, create_user};
+use testresult::TestResult;
+
+static FLASHCARDS: &str = r#"```json
+{
+  "title": "Leader Election",
+  "difficulty": 3,
+  "importance": 8,
+  "tags": ["Raft"],
+  "cards": [
+    {
+      "title": "Election Timeout",
+      "front": "What starts an election in Raft?",
+      "back": "A follower that hears nothing from a leader for an election timeout",
+      "hints": ["Heartbeats"],
+      "difficulty": 3,
+      "importance": 8,
+      "tags": ["Raft"]
+    }
+  ]
+}
+```"#;
+
+static NO_FLASHCARDS: &str = r#"```json
+{
+  "title": "Nothing",
+  "difficulty": 1,
+  "importance": 1,
+  "tags": [],
+  "cards": []
+}
+```"#;
+
+static DOCUMENT: &str = "\
+# Raft
+
+## Leader election
+
+A follower becomes a candidate when it hears nothing from a leader.
+
+## Log replication
+
+The leader appends the command to its log and replicates it.
+";
+
+fn document_generator(db: &MemoryDb, mock: MockExecutor) -> DocumentGenerator<MemoryStorage> {
+    DocumentGenerator::new(CardGeneratorService::new(
+        CustomExecutor::mock(mock),
+        MemoryRepo::new(db.clone()),
+        MemoryRepo::new(db.clone()),
+        MemoryRepo::new(db.clone()),
+        MemoryRepo::new(db.clone()),
+    ))
+}
+
+#[tokio::test]
+async fn test_document_generation() -> TestResult {
+    let db = MemoryDb::new();
+    let user = create_user(&db, "test_document_generation").await?;
+    let deck = create_deck()
+        .db(&db)
+        .user(&user)
+        .title("Distributed Systems")
+        .call()
+        .await?;
+    let document = Document::parse("raft.md", DOCUMENT.as_bytes().to_vec()).await?;
+    let mock = MockExecutor::new([("Create Flashcards From Text", FLASHCARDS)]);
+
+    let report = document_generator(&db, mock.clone())
+        .generate(&user, &deck, &document, GenerateOptions::default())
+        .await?;
+    assert_eq!(report.chunks, 2);
+    assert_eq!(report.created.len(), 2);
+    assert_eq!(
+        &*report.created[0].source,
+        "raft.md › Raft › Leader election"
+    );
+    assert_eq!(
+        &*report.created[1].source,
+        "raft.md › Raft › Log replication"
+    );
+    assert!(report.empty.is_empty() && report.failed.is_empty());
+
+    let calls = mock.calls()?;
+    assert_eq!(calls.len(), 2);
+    assert!(calls.iter().any(|call| {
+        let text = call.inputs["text"].to_string();
+        text.starts_with("Document: raft.md\nSection: Raft › Log replication\n")
+            && text.contains("replicates it")
+    }));
+
+    let card_groups = MemoryCardGroupRepo::new(db.clone())
+        .list_by_user_id(&user)
+        .await?;
+    assert_eq!(card_groups.len(), 2);
+    for card_group in &card_groups {
+        let data = card_group.data.as_ref().unwrap();
+        let source = data.source.as_ref().unwrap();
+        assert_eq!(&*source.file, "raft.md");
+        assert!(data.article.is_some());
+        for card in &card_group.cards {
+            let card_data = card.data.as_ref().unwrap();
+            assert_eq!(card_data.source.as_ref(), Some(source));
+        }
+    }
+
+    // a chunk without cards is reported, a failing one doesn't stop the others
+    let report = document_generator(
+        &db,
+        MockExecutor::new([("Create Flashcards From Text", NO_FLASHCARDS)]),
+    )
+    .generate(&user, &deck, &document, GenerateOptions::default())
+    .await?;
+    assert_eq!(report.empty.len(), 2);
+    assert!(report.created.is_empty());
+
+    let report = document_generator(&db, MockExecutor::new([("Code Comment", "// commented")]))
+        .generate(&user, &deck, &document, GenerateOptions::default())
+        .await?;
+    assert_eq!(report.failed.len(), 2);
+    assert!(report
+        .to_string()
+        .contains("Failed: 2\n  raft.md › Raft › Leader election: "));
+
+    Ok(())
+}
@@ -0,0 +1,166 @@
+use flashcard_gpt_core::error::CoreError;
+use flashcard_gpt_core::llm::answer_grader::AnswerGrader;
+use flashcard_gpt_core::llm::custom_executor::CustomExecutor;
+use flashcard_gpt_core::llm::mock_executor::MockExecutor;
+use flashcard_gpt_core::model::card::{Card, CreateCard};
+use flashcard_gpt_core::model::card_group::CreateCardGroup;
+use flashcard_gpt_core::model::deck_card_group::CreateDeckCardGroup;
+use flashcard_gpt_core::model::history::CreateHistory;
+use flashcard_gpt_core::model::progress;
+use flashcard_gpt_core::model::stats::RECALLED_DIFFICULTY;
+use flashcard_gpt_core::model::user::User;
+use flashcard_gpt_core::store::memory::{
+    MemoryCardGroupRepo, MemoryCardRepo, MemoryDb, MemoryDeckRepo, MemoryHistoryRepo,
+};
+use flashcard_gpt_core::store::{CardGroupStore, CardStore, DeckStore, HistoryStore};
+use flashcard_gpt_tests::db::memory::{self, create_deck, create_user};
+use std::sync::Arc;
+use testresult::TestResult;
+
+const ARTICLE: &str = "Sorting by concatenation order is a total order.";
+
+async fn create_card(db: &MemoryDb, name: &str) -> TestResult<(User, Card)> {
+    let user = create_user(db, name).await?;
+    let card = MemoryCardRepo::new(db.clone())
+        .create(CreateCard {
+            title: Arc::from("Largest Number"),
+            hints: vec![Arc::from("Compare 3 and 30")],
+            difficulty: 5,
+            importance: 5,
+            ..memory::card(
+                &user,
+                "How are the numbers sorted?",
+                Some("By comparing ab with ba as strings."),
+            )
+        })
+        .await?;
+    Ok((user, card))
+}
+
+#[tokio::test]
+async fn test_grade_answer() -> TestResult {
+    let db = MemoryDb::new();
+    let (user, card) = create_card(&db, "test_grade_answer").await?;
+
+    let mock = MockExecutor::new([(
+        "Grade Answer",
+        r#"```json
+        {"score": 7, "missed_points": ["Ties"], "misconceptions": ["Sorts numerically"]}
+        ```"#,
+    )]);
+    let grader = AnswerGrader::new(CustomExecutor::mock(mock.clone()));
+
+    let grade = grader
+        .grade(&card, Some(ARTICLE), "Sort them in descending order")
+        .await?;
+    assert_eq!(grade.score, 7);
+    assert_eq!(grade.missed_points, vec![Arc::<str>::from("Ties")]);
+    assert_eq!(grade.difficulty(), RECALLED_DIFFICULTY + 1);
+
+    let calls = mock.calls()?;
+    assert_eq!(calls.len(), 1);
+    let context = calls[0].inputs["answer_context"].as_ref();
+    for expected in [
+        "By comparing ab with ba as strings.",
+        "Compare 3 and 30",
+        ARTICLE,
+        "Sort them in descending order",
+    ] {
+        assert!(context.contains(expected), "{expected} not in {context}");
+    }
+
+    // a card group is committed the way the bot does it: every graded answer is a step and the
+    // group's difficulty is recorded once the last step is reached
+    let first = MemoryCardRepo::new(db.clone())
+        .create(CreateCard {
+            title: Arc::from("Concatenation"),
+            difficulty: 5,
+            importance: 5,
+            ..memory::card(&user, "What is compared?", Some("The two concatenations."))
+        })
+        .await?;
+    let card_group = MemoryCardGroupRepo::new(db.clone())
+        .create(CreateCardGroup {
+            user: user.id.clone(),
+            title: Arc::from("Largest Number"),
+            importance: 5,
+            difficulty: 5,
+            data: None,
+            cards: vec![first.id.clone(), card.id.clone()],
+            tags: vec![],
+        })
+        .await?;
+    let deck = create_deck()
+        .db(&db)
+        .user(&user)
+        .title("test_grade_answer")
+        .call()
+        .await?;
+    let deck_card_group = MemoryDeckRepo::new(db.clone())
+        .relate_card_group(CreateDeckCardGroup {
+            deck: deck.id,
+            card_group: card_group.id,
+        })
+        .await?;
+
+    let first_grade = AnswerGrader::new(CustomExecutor::mock(MockExecutor::new([(
+        "Grade Answer",
+        r#"{"score": 9, "missed_points": [], "misconceptions": []}"#,
+    )])))
+    .grade(&first, None, "ab and ba")
+    .await?;
+    assert_eq!(first_grade.difficulty(), 1);
+
+    let mut steps = vec![];
+    assert_eq!(
+        progress::record_step(&mut steps, 0, first_grade.difficulty(), 2),
+        None
+    );
+    let difficulty = progress::record_step(&mut steps, 1, grade.difficulty(), 2)
+        .expect("the last step finishes the group");
+    assert_eq!(difficulty, 3);
+
+    let history = MemoryHistoryRepo::new(db);
+    history
+        .create_custom(CreateHistory {
+            user: user.id,
+            deck_card: None,
+            deck_card_group: Some(deck_card_group.id.clone()),
+            difficulty,
+            time: None,
+            hide_for: None,
+            steps,
+        })
+        .await?;
+    let record = history
+        .get_last_answer(deck_card_group.id)
+        .await?
+        .expect("the group was answered");
+    assert_eq!(record.difficulty, 3);
+    assert_eq!(record.steps, vec![Some(1), Some(RECALLED_DIFFICULTY + 1)]);
+
+    Ok(())
+}
+
+#[tokio::test]
+async fn test_grade_answer_fails_on_malformed_response() -> TestResult {
+    let db = MemoryDb::new();
+    let (user, card) = create_card(&db, "test_grade_answer_fails").await?;
+
+    let grader = AnswerGrader::new(CustomExecutor::mock(MockExecutor::new([(
+        "Grade Answer",
+        "Looks good to me!",
+    )])));
+    let err = grader.grade(&card, None, "answer").await.unwrap_err();
+    assert!(matches!(err, CoreError::JsonParseError(_)), "{err:?}");
+    // nothing is committed for an answer that couldn't be graded
+    assert!(MemoryHistoryRepo::new(db)
+        .list_by_user_id(user.id)
+        .await?
+        .is_empty());
+
+    let grader = AnswerGrader::new(CustomExecutor::mock(MockExecutor::default()));
+    assert!(grader.grade(&card, None, "answer").await.is_err());
+
+    Ok(())
+}
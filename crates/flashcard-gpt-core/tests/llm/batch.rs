@@ -0,0 +1,134 @@
+use flashcard_gpt_core::llm::batch::{read_dir, BatchGenerator, BatchOptions, Grouping};
+use flashcard_gpt_core::llm::card_generator_service::CardGeneratorService;
+use flashcard_gpt_core::llm::custom_executor::CustomExecutor;
+use flashcard_gpt_core::llm::mock_executor::MockExecutor;
+use flashcard_gpt_core::store::memory::{MemoryCardGroupRepo, MemoryDb, MemoryRepo, MemoryStorage};
+use flashcard_gpt_core::store::CardGroupStore;
+use flashcard_gpt_tests::db::memory::{create_deck, create_user};
+use std::path::Path;
+use std::sync::Arc;
+use testresult::TestResult;
+
+static FLASHCARDS: &str = r#"```json
+{
+  "title": "Two Sum",
+  "difficulty": 2,
+  "importance": 8,
+  "tags": ["Hash Table"],
+  "cards": [
+    {
+      "title": "Complement",
+      "front": "What is looked up for every number?",
+      "back": "Its complement in a map of the numbers seen so far",
+      "hints": ["One pass"],
+      "difficulty": 2,
+      "importance": 8,
+      "tags": ["Hash Table"]
+    }
+  ]
+}
+```"#;
+
+fn mock() -> MockExecutor {
+    MockExecutor::new([
+        ("Code Comment", "// commented"),
+        ("Write Article", "The article"),
+        ("Create Flashcards", FLASHCARDS),
+    ])
+}
+
+fn batch_generator(db: &MemoryDb, mock: MockExecutor) -> BatchGenerator<MemoryStorage> {
+    BatchGenerator::new(CardGeneratorService::new(
+        CustomExecutor::mock(mock),
+        MemoryRepo::new(db.clone()),
+        MemoryRepo::new(db.clone()),
+        MemoryRepo::new(db.clone()),
+        MemoryRepo::new(db.clone()),
+    ))
+}
+
+async fn write(dir: &Path, path: &str, contents: &str) -> TestResult {
+    let path = dir.join(path);
+    tokio::fs::create_dir_all(path.parent().unwrap()).await?;
+    tokio::fs::write(path, contents).await?;
+    Ok(())
+}
+
+#[tokio::test]
+async fn test_batch_generation() -> TestResult {
+    let dir = std::env::temp_dir().join(format!("batch_generation_{}", std::process::id()));
+    if tokio::fs::try_exists(&dir).await? {
+        tokio::fs::remove_dir_all(&dir).await?;
+    }
+    write(&dir, "easy/two_sum.rs", "fn two_sum() {}").await?;
+    write(&dir, "easy/two_sum.py", "def two_sum(): pass").await?;
+    write(&dir, "medium/add_two.rs", "fn add_two() {}").await?;
+    write(&dir, "README.md", "# Solutions").await?;
+    write(&dir, "target/debug/build.rs", "fn main() {}").await?;
+
+    let db = MemoryDb::new();
+    let user = create_user(&db, "test_batch_generation").await?;
+    let deck = create_deck()
+        .db(&db)
+        .user(&user)
+        .title("LeetCode")
+        .call()
+        .await?;
+    let mock = mock();
+    let options = BatchOptions::builder()
+        .grouping(Grouping::Stem)
+        .concurrency(2)
+        .build();
+
+    let files = read_dir(&dir).await?;
+    assert_eq!(files.len(), 3);
+    let report = batch_generator(&db, mock.clone())
+        .generate(&user, &deck, files, options.clone())
+        .await?;
+    assert_eq!(report.files, 3);
+    assert_eq!(report.created.len(), 2);
+    assert_eq!(&*report.created[0].problem, "easy/two_sum");
+    assert_eq!(&*report.created[0].title, "Two Sum");
+    assert!(report.skipped.is_empty() && report.failed.is_empty());
+
+    let calls = mock.calls()?;
+    let commented = calls
+        .iter()
+        .filter(|call| &*call.step == "Code Comment")
+        .map(|call| call.inputs["code"].to_string())
+        .collect::<Vec<_>>();
+    assert_eq!(commented.len(), 2);
+    assert!(commented.contains(&"fn add_two() {}".to_string()));
+
+    let card_groups = MemoryCardGroupRepo::new(db.clone())
+        .list_by_user_id(&user)
+        .await?;
+    assert_eq!(card_groups.len(), 2);
+    for card_group in &card_groups {
+        let data = card_group.data.as_ref().unwrap();
+        assert!(data.source_hash.is_some());
+        assert_eq!(data.article.as_deref(), Some("The article"));
+    }
+
+    // only the changed problem is generated again
+    write(&dir, "medium/add_two.rs", "fn add_two(a: i32) {}").await?;
+    let report = batch_generator(&db, mock())
+        .generate(&user, &deck, read_dir(&dir).await?, options.clone())
+        .await?;
+    assert_eq!(report.created.len(), 1);
+    assert_eq!(report.skipped, [Arc::<str>::from("easy/two_sum")]);
+
+    // a failing problem doesn't stop the others
+    write(&dir, "hard/trap.rs", "fn trap() {}").await?;
+    let failing = MockExecutor::new([("Code Comment", "// commented")]);
+    let report = batch_generator(&db, failing)
+        .generate(&user, &deck, read_dir(&dir).await?, options)
+        .await?;
+    assert_eq!(report.skipped.len(), 2);
+    assert_eq!(report.failed.len(), 1);
+    assert_eq!(&*report.failed[0].problem, "hard/trap");
+    assert!(report.to_string().contains("Failed: 1\n  hard/trap: "));
+
+    tokio::fs::remove_dir_all(&dir).await?;
+    Ok(())
+}
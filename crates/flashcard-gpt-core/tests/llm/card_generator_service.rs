@@ -1,33 +1,43 @@
 use flashcard_gpt_core::llm::custom_executor::CustomExecutor;
+use flashcard_gpt_core::llm::fixtures::LlmFixtures;
 use std::sync::Arc;
 
-use flashcard_gpt_core::model::llm::{GptCard, GptCardGroup};
 use flashcard_gpt_core::llm::card_generator_service::CardGeneratorService;
+use flashcard_gpt_core::llm::mock_executor::MockExecutor;
+use flashcard_gpt_core::llm::provider::openai::{OpenAiProvider, DEFAULT_BASE_URL, DEFAULT_MODEL};
+use flashcard_gpt_core::model::card_data::CardData;
+use flashcard_gpt_core::model::llm::{GptCard, GptCardGroup};
+use flashcard_gpt_core::store::memory::{
+    MemoryCardGroupRepo, MemoryCardRepo, MemoryDb, MemoryDeckRepo, MemoryStorage, MemoryTagRepo,
+};
+use flashcard_gpt_core::store::surreal::SurrealStorage;
+use flashcard_gpt_tests::db::memory;
 use flashcard_gpt_tests::db::utils::{
     create_card_group_repo, create_card_repo, create_deck, create_deck_repo, create_tag,
     create_tag_repo, create_user,
 };
-use llm_chain::options::{ModelRef, Opt, Options};
-use llm_chain::traits::Executor;
-use serde_json::json;
 use testresult::TestResult;
-use tracing::error;
 
+/// Replays the responses in `generate_code_cards.json`, with `LLM_FIXTURES_MODE=record` and
+/// `OPENAI_API_KEY` set they are recorded from OpenAI again.
 #[tokio::test]
 async fn test_generate_card() -> TestResult {
-    let Ok(api_key) = std::env::var("OPENAI_API_KEY") else {
-        error!("OPENAI_API_KEY not set");
-        return Ok(());
+    let fixtures = concat!(
+        env!("CARGO_MANIFEST_DIR"),
+        "/tests/llm/generate_code_cards.json"
+    );
+    let record = std::env::var("LLM_FIXTURES_MODE").is_ok_and(|mode| mode == "record");
+    let generator = match std::env::var("OPENAI_API_KEY") {
+        Ok(api_key) if record => CustomExecutor::new(OpenAiProvider::new(
+            DEFAULT_BASE_URL,
+            Some(Arc::from(api_key)),
+            DEFAULT_MODEL,
+        ))
+        .with_fixtures(LlmFixtures::record(fixtures)),
+        _ => CustomExecutor::replay(fixtures)?,
     };
-    let mut options = Options::builder();
-    options.add_option(Opt::ApiKey(api_key));
-    options.add_option(Opt::Model(ModelRef::from_model_name("chatgpt-4o-latest")));
-    let options = options.build();
-
-    let exec = llm_chain_openai::chatgpt::Executor::new_with_options(options)?;
-    let generator = CustomExecutor::new(exec);
 
-    let card_generator_service = CardGeneratorService {
+    let card_generator_service = CardGeneratorService::<SurrealStorage> {
         card_generator: generator,
         cards: create_card_repo().await?,
         card_groups: create_card_group_repo().await?,
@@ -35,34 +45,40 @@ async fn test_generate_card() -> TestResult {
         tags: create_tag_repo().await?,
     };
 
-    let code = include_str!("./sample_code.txt");
-    card_generator_service.generate_code_cards(code).await?;
-
-    Ok(())
-}
-
-#[tokio::test]
-async fn test_create_cards() -> TestResult {
-    let user = create_user("test_create_cards").await?;
-    let tag = create_tag()
-        .user(&user)
-        .name("test_create_cards")
-        .slug("test_create_cards")
-        .call()
-        .await?;
+    let user = create_user("test_generate_card").await?;
     let deck = create_deck()
         .user(&user)
-        .title("test_create_cards")
-        .tags([&tag])
+        .title("test_generate_card")
         .call()
         .await?;
 
-    let gpt_card_group = GptCardGroup {
+    let code = include_str!("./sample_code.txt");
+    let gpt_card_group = card_generator_service
+        .generate_code_card_group(code, false)
+        .await?;
+    let data = gpt_card_group.data.as_ref().unwrap();
+    assert!(data.article.is_some());
+    assert!(data.commented_code.is_some());
+
+    let deck_card_group = card_generator_service
+        .create_cards(user.id, deck.id, gpt_card_group)
+        .await?;
+    assert!(!deck_card_group.card_group.cards.is_empty());
+
+    Ok(())
+}
+
+fn sample_gpt_card_group() -> GptCardGroup {
+    GptCardGroup {
         importance: 10,
         difficulty: 2,
         title: Arc::from("title"),
         tags: vec![Arc::from("tag1"), Arc::from("tag2"), Arc::from("tag3")],
-        data: Some(json!({"leetcode_link": "https://leetcode.com"})),
+        data: Some(
+            CardData::builder()
+                .source_link(Arc::from("https://leetcode.com"))
+                .build(),
+        ),
         cards: vec![
             GptCard {
                 title: Arc::from("title1"),
@@ -71,6 +87,8 @@ async fn test_create_cards() -> TestResult {
                 hints: vec![Arc::from("hint1")],
                 difficulty: 2,
                 importance: 2,
+                distractors: vec![],
+                reversible: false,
                 tags: vec![Arc::from("tag1"), Arc::from("tag one"), Arc::from("tag 1")],
             },
             GptCard {
@@ -80,15 +98,34 @@ async fn test_create_cards() -> TestResult {
                 hints: vec![Arc::from("hint2")],
                 difficulty: 3,
                 importance: 3,
+                distractors: vec![],
+                reversible: false,
                 tags: vec![Arc::from("tag2"), Arc::from("tag two"), Arc::from("tag 2")],
             },
         ],
-    };
+    }
+}
 
-    let card_generator_service = CardGeneratorService {
-        card_generator: CustomExecutor::new(llm_chain_openai::chatgpt::Executor::new_with_options(
-            Options::default(),
-        )?),
+#[tokio::test]
+async fn test_create_cards() -> TestResult {
+    let user = create_user("test_create_cards").await?;
+    let tag = create_tag()
+        .user(&user)
+        .name("test_create_cards")
+        .slug("test_create_cards")
+        .call()
+        .await?;
+    let deck = create_deck()
+        .user(&user)
+        .title("test_create_cards")
+        .tags([&tag])
+        .call()
+        .await?;
+
+    let gpt_card_group = sample_gpt_card_group();
+
+    let card_generator_service = CardGeneratorService::<SurrealStorage> {
+        card_generator: CustomExecutor::mock(MockExecutor::default()),
         cards: create_card_repo().await?,
         card_groups: create_card_group_repo().await?,
         decks: create_deck_repo().await?,
@@ -103,3 +140,34 @@ async fn test_create_cards() -> TestResult {
 
     Ok(())
 }
+
+#[tokio::test]
+async fn test_create_cards_in_memory() -> TestResult {
+    let db = MemoryDb::new();
+    let user = memory::create_user(&db, "test_create_cards_in_memory").await?;
+
+    let card_generator_service = CardGeneratorService::<MemoryStorage>::new(
+        CustomExecutor::mock(MockExecutor::default()),
+        MemoryCardRepo::new(db.clone()),
+        MemoryCardGroupRepo::new(db.clone()),
+        MemoryDeckRepo::new(db.clone()),
+        MemoryTagRepo::new(db.clone()),
+    );
+
+    let deck = memory::create_deck()
+        .db(&db)
+        .user(&user)
+        .title("test_create_cards_in_memory")
+        .call()
+        .await?;
+
+    let deck_card_group = card_generator_service
+        .create_cards(&user, &deck, sample_gpt_card_group())
+        .await?;
+
+    assert_eq!(deck_card_group.card_group.cards.len(), 2);
+    assert_eq!(deck_card_group.card_group.tags.len(), 3);
+    assert_eq!(deck_card_group.card_group.cards[0].tags.len(), 3);
+
+    Ok(())
+}
@@ -0,0 +1,69 @@
+use flashcard_gpt_core::error::CoreError;
+use flashcard_gpt_core::llm::custom_executor::{CustomExecutor, CustomStep};
+use flashcard_gpt_core::llm::fixtures::{Fixture, LlmFixtures};
+use flashcard_gpt_core::llm::mock_executor::MockExecutor;
+use std::collections::BTreeMap;
+use std::sync::Arc;
+use testresult::TestResult;
+
+fn steps() -> [CustomStep; 2] {
+    [
+        CustomStep {
+            name: Arc::from("Summarize"),
+            system_template: Arc::from("Summarize the text."),
+            user_template: Arc::from("Text:\n{{text}}"),
+            input_param_names: vec![Arc::from("text")],
+            output_param_name: Arc::from("summary"),
+        },
+        CustomStep {
+            name: Arc::from("Title"),
+            system_template: Arc::from("Title the summary."),
+            user_template: Arc::from("Summary:\n{{summary}}"),
+            input_param_names: vec![Arc::from("summary")],
+            output_param_name: Arc::from("title"),
+        },
+    ]
+}
+
+#[tokio::test]
+async fn test_record_and_replay() -> TestResult {
+    let path = std::env::temp_dir().join(format!(
+        "flashcard_gpt_fixtures_{}/fixtures.json",
+        std::process::id()
+    ));
+    let mock = MockExecutor::new([("Summarize", "A summary"), ("Title", "A title")]);
+    let recorder =
+        CustomExecutor::mock(mock.clone()).with_fixtures(LlmFixtures::record(path.clone()));
+    let recorded = recorder
+        .execute_custom_chain("summary", &steps(), "Some text")
+        .await?;
+    assert_eq!(recorded.0, "A title");
+    assert_eq!(mock.calls()?.len(), 2);
+
+    let fixtures: BTreeMap<Arc<str>, Fixture> = serde_json::from_slice(&std::fs::read(&path)?)?;
+    assert_eq!(fixtures.len(), 2);
+    let fixture = fixtures
+        .values()
+        .find(|fixture| fixture.step.as_ref() == "Summarize")
+        .unwrap();
+    assert_eq!(fixture.system, "Summarize the text.");
+    assert_eq!(fixture.user, "Text:\nSome text");
+    assert_eq!(fixture.response.as_deref(), Some("A summary"));
+
+    let replayer = CustomExecutor::replay(&path)?;
+    let replayed = replayer
+        .execute_custom_chain("summary", &steps(), "Some text")
+        .await?;
+    assert_eq!(replayed, recorded);
+
+    // a prompt that wasn't recorded fails instead of reaching a provider
+    let err = replayer
+        .execute_custom_chain("summary", &steps(), "Other text")
+        .await
+        .unwrap_err();
+    assert!(matches!(err, CoreError::LlmFixtureMissing(_)));
+    assert!(err.to_string().contains("Summarize"));
+
+    std::fs::remove_dir_all(path.parent().unwrap())?;
+    Ok(())
+}
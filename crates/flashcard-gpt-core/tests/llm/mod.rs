@@ -1 +1,5 @@
+mod answer_grader;
+mod batch;
 mod card_generator_service;
+mod fixtures;
+mod provider;
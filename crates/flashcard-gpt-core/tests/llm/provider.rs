@@ -0,0 +1,144 @@
+use flashcard_gpt_core::llm::answer_grader::GRADE_ANSWER_CHAIN;
+use flashcard_gpt_core::llm::card_generator_service::CODE_CARDS_CHAIN;
+use flashcard_gpt_core::llm::custom_executor::{CustomExecutor, CustomStep};
+use flashcard_gpt_core::llm::mock_executor::MockExecutor;
+use flashcard_gpt_core::llm::provider::ollama::OllamaProvider;
+use flashcard_gpt_core::llm::provider::openai::OpenAiProvider;
+use flashcard_gpt_core::llm::provider::{LlmChoice, ProviderKind};
+use serde_json::{json, Value};
+use std::sync::Arc;
+use testresult::TestResult;
+use tokio::io::{AsyncReadExt, AsyncWriteExt};
+use tokio::net::TcpListener;
+use tokio::task::JoinHandle;
+
+fn echo_step() -> CustomStep {
+    CustomStep {
+        name: Arc::from("Echo"),
+        system_template: Arc::from("Repeat the text."),
+        user_template: Arc::from("Text:\n{{text}}"),
+        input_param_names: vec![Arc::from("text")],
+        output_param_name: Arc::from("echo"),
+    }
+}
+
+/// Answers a single HTTP request with `response` and returns the path and the JSON body of the
+/// request.
+async fn serve_once(response: Value) -> TestResult<(String, JoinHandle<(String, Value)>)> {
+    let listener = TcpListener::bind("127.0.0.1:0").await?;
+    let url = format!("http://{}", listener.local_addr()?);
+    let handle = tokio::spawn(async move {
+        let (mut stream, _) = listener.accept().await.unwrap();
+        let mut request = vec![];
+        let mut buf = [0; 4096];
+        let (head, body_start) = loop {
+            let read = stream.read(&mut buf).await.unwrap();
+            request.extend_from_slice(&buf[..read]);
+            if let Some(end) = request.windows(4).position(|window| window == b"\r\n\r\n") {
+                break (
+                    String::from_utf8_lossy(&request[..end]).to_string(),
+                    end + 4,
+                );
+            }
+        };
+        let length = head
+            .lines()
+            .filter_map(|line| line.split_once(':'))
+            .find(|(name, _)| name.eq_ignore_ascii_case("content-length"))
+            .map(|(_, value)| value.trim().parse::<usize>().unwrap())
+            .unwrap_or_default();
+        while request.len() < body_start + length {
+            let read = stream.read(&mut buf).await.unwrap();
+            request.extend_from_slice(&buf[..read]);
+        }
+
+        let body = response.to_string();
+        let reply = format!(
+            "HTTP/1.1 200 OK\r\ncontent-type: application/json\r\ncontent-length: {}\r\n\
+             connection: close\r\n\r\n{body}",
+            body.len()
+        );
+        stream.write_all(reply.as_bytes()).await.unwrap();
+
+        let path = head.split_whitespace().nth(1).unwrap().to_string();
+        let body = serde_json::from_slice(&request[body_start..body_start + length]).unwrap();
+        (path, body)
+    });
+    Ok((url, handle))
+}
+
+#[tokio::test]
+async fn test_openai_compatible_provider() -> TestResult {
+    let (url, server) = serve_once(json!({
+        "choices": [{"message": {"role": "assistant", "content": "echoed"}}]
+    }))
+    .await?;
+    let executor = CustomExecutor::new(OpenAiProvider::new(
+        format!("{url}/v1"),
+        None,
+        "local-model",
+    ));
+
+    let (result, params) = executor
+        .execute_custom_chain(CODE_CARDS_CHAIN, &[echo_step()], "hello")
+        .await?;
+    assert_eq!(result, "echoed");
+    assert_eq!(params.get("echo").map(AsRef::as_ref), Some("echoed"));
+
+    let (path, body) = server.await?;
+    assert_eq!(path, "/v1/chat/completions");
+    assert_eq!(body["model"], "local-model");
+    assert_eq!(body["messages"][0]["role"], "system");
+    assert_eq!(body["messages"][0]["content"], "Repeat the text.");
+    assert_eq!(body["messages"][1]["content"], "Text:\nhello");
+    Ok(())
+}
+
+#[tokio::test]
+async fn test_ollama_provider() -> TestResult {
+    let (url, server) = serve_once(json!({
+        "model": "qwen2.5",
+        "message": {"role": "assistant", "content": "echoed"},
+        "done": true
+    }))
+    .await?;
+    // the chain chooses the model
+    let choice = LlmChoice {
+        provider: ProviderKind::Ollama,
+        model: Some(Arc::from("qwen2.5")),
+    };
+    let executor = CustomExecutor::new(OllamaProvider::new(url, "llama3.1"))
+        .with_chain(GRADE_ANSWER_CHAIN, choice);
+
+    let (result, _) = executor
+        .execute_custom_chain(GRADE_ANSWER_CHAIN, &[echo_step()], "hello")
+        .await?;
+    assert_eq!(result, "echoed");
+
+    let (path, body) = server.await?;
+    assert_eq!(path, "/api/chat");
+    assert_eq!(body["model"], "qwen2.5");
+    assert_eq!(body["stream"], false);
+    assert_eq!(body["messages"][1]["content"], "Text:\nhello");
+    Ok(())
+}
+
+#[test]
+fn test_choice() {
+    let mock = MockExecutor::new([("Echo", "echoed")]);
+    let ollama = LlmChoice::new(ProviderKind::Ollama);
+    let executor = CustomExecutor::mock(mock).with_chain(GRADE_ANSWER_CHAIN, ollama.clone());
+    let mock = LlmChoice::new(ProviderKind::Mock);
+
+    // a provider that is not configured is skipped
+    assert_eq!(executor.choice(GRADE_ANSWER_CHAIN), &mock);
+    let executor =
+        executor.with_provider(OllamaProvider::new("http://localhost:11434", "llama3.1"));
+    assert_eq!(executor.choice(GRADE_ANSWER_CHAIN), &ollama);
+    assert_eq!(executor.choice(CODE_CARDS_CHAIN), &mock);
+
+    // the choice of the user comes first
+    let user = executor.for_user(Some(mock.clone()));
+    assert_eq!(user.choice(GRADE_ANSWER_CHAIN), &mock);
+    assert_eq!(executor.for_user(None).choice(GRADE_ANSWER_CHAIN), &ollama);
+}
@@ -0,0 +1,251 @@
+use flashcard_gpt_core::markdown_folder::export::{ExportOptions, MarkdownExporter};
+use flashcard_gpt_core::markdown_folder::import::{ImportOptions, MarkdownImporter};
+use flashcard_gpt_core::markdown_folder::MarkdownFile;
+use flashcard_gpt_core::model::card::CreateCard;
+use flashcard_gpt_core::model::card_group::CreateCardGroup;
+use flashcard_gpt_core::model::deck_card::CreateDeckCard;
+use flashcard_gpt_core::model::deck_card_group::CreateDeckCardGroup;
+use flashcard_gpt_core::model::user::User;
+use flashcard_gpt_core::store::memory::{
+    MemoryCardGroupRepo, MemoryCardRepo, MemoryDb, MemoryDeckRepo, MemoryRepo, MemoryStorage,
+    MemoryTagRepo,
+};
+use flashcard_gpt_core::store::{CardGroupStore, CardStore, DeckStore, TagStore};
+use flashcard_gpt_tests::db::memory::{card, create_deck, create_user};
+use std::path::Path;
+use std::sync::Arc;
+use testresult::TestResult;
+
+/// Decks Languages and Spanish in it, a card with hints and a tag in Spanish and a card group in
+/// Languages.
+async fn create_decks(db: &MemoryDb, user: &User) -> TestResult {
+    let cards = MemoryCardRepo::new(db.clone());
+    let decks = MemoryDeckRepo::new(db.clone());
+    let languages = create_deck()
+        .db(db)
+        .user(user)
+        .title("Languages")
+        .call()
+        .await?;
+    let spanish = create_deck()
+        .db(db)
+        .user(user)
+        .title("Spanish")
+        .parent(languages.id.clone())
+        .call()
+        .await?;
+
+    let tags = MemoryTagRepo::new(db.clone())
+        .get_or_create_tags(user.id.clone(), [Arc::from("verbs")])
+        .await?;
+    let hablar = cards
+        .create(CreateCard {
+            hints: vec![Arc::from("an -ar verb")],
+            difficulty: 3,
+            tags: tags.into_iter().map(|tag| tag.id).collect(),
+            ..card(user, "hablar", Some("to speak"))
+        })
+        .await?;
+    decks
+        .relate_card(CreateDeckCard {
+            deck: spanish.id,
+            card: hablar.id,
+        })
+        .await?;
+
+    let mut words = vec![];
+    for (front, back) in [("perro", "dog"), ("gato", "cat")] {
+        words.push(cards.create(card(user, front, Some(back))).await?.id);
+    }
+    let card_group = MemoryCardGroupRepo::new(db.clone())
+        .create(CreateCardGroup {
+            user: user.id.clone(),
+            title: Arc::from("Common words"),
+            importance: 2,
+            difficulty: 0,
+            data: None,
+            cards: words,
+            tags: vec![],
+        })
+        .await?;
+    decks
+        .relate_card_group(CreateDeckCardGroup {
+            deck: languages.id,
+            card_group: card_group.id,
+        })
+        .await?;
+
+    Ok(())
+}
+
+fn importer(db: &MemoryDb) -> MarkdownImporter<MemoryStorage> {
+    MarkdownImporter::new(
+        MemoryRepo::new(db.clone()),
+        MemoryRepo::new(db.clone()),
+        MemoryRepo::new(db.clone()),
+        MemoryRepo::new(db.clone()),
+    )
+}
+
+async fn read(path: &Path) -> TestResult<MarkdownFile> {
+    Ok(MarkdownFile::parse(
+        &tokio::fs::read_to_string(path).await?,
+    )?)
+}
+
+#[tokio::test]
+async fn test_markdown_folder_sync() -> TestResult {
+    let dir = std::env::temp_dir().join(format!("markdown_folder_sync_{}", std::process::id()));
+    if tokio::fs::try_exists(&dir).await? {
+        tokio::fs::remove_dir_all(&dir).await?;
+    }
+
+    let db = MemoryDb::new();
+    let user = create_user(&db, "markdown").await?;
+    create_decks(&db, &user).await?;
+
+    let exporter = MarkdownExporter::<MemoryStorage>::new(MemoryRepo::new(db.clone()));
+    let report = exporter
+        .export(&user, &dir, ExportOptions::default())
+        .await?;
+    assert_eq!(report.decks, 2);
+    assert_eq!(report.cards, 1);
+    assert_eq!(report.card_groups, 1);
+
+    let hablar_path = dir.join("Languages/Spanish/hablar.md");
+    let hablar = read(&hablar_path).await?;
+    assert!(hablar.front_matter.id.is_some());
+    assert_eq!(
+        hablar.front_matter.deck.as_deref(),
+        Some("Languages::Spanish")
+    );
+    assert_eq!(hablar.front_matter.tags, vec!["verbs".to_string()]);
+    assert_eq!(hablar.cards[0].hints.len(), 1);
+    let group = read(&dir.join("Languages/common-words.md")).await?;
+    assert_eq!(group.front_matter.cards.len(), 2);
+
+    // edit a card, and add a card and a card in a new deck without ids
+    let text = tokio::fs::read_to_string(&hablar_path).await?;
+    tokio::fs::write(&hablar_path, text.replace("to speak", "to talk")).await?;
+    let comer_path = dir.join("Languages/Spanish/comer.md");
+    tokio::fs::write(&comer_path, "## Front\n\ncomer\n\n## Back\n\nto eat\n").await?;
+    tokio::fs::create_dir_all(dir.join("French")).await?;
+    let bonjour_path = dir.join("French/bonjour.md");
+    tokio::fs::write(
+        &bonjour_path,
+        "---\ntags: [greetings]\n---\n\n## Front\n\nbonjour\n",
+    )
+    .await?;
+
+    let report = importer(&db)
+        .import(&user, &dir, ImportOptions::default())
+        .await?;
+    assert!(report.errors.is_empty(), "{report}");
+    assert_eq!(report.files, 4);
+    assert_eq!(report.decks, 1);
+    assert_eq!(report.created_cards, 2);
+    // hablar and the cards of the group
+    assert_eq!(report.updated_cards, 3);
+    assert_eq!(report.updated_card_groups, 1);
+
+    let cards = MemoryCardRepo::new(db.clone());
+    let hablar_id = hablar.front_matter.id.as_deref().unwrap();
+    let hablar = cards
+        .list_by_user_id(&user)
+        .await?
+        .into_iter()
+        .find(|card| card.id.to_string() == hablar_id)
+        .unwrap();
+    assert_eq!(hablar.back.as_deref(), Some("to talk"));
+    assert_eq!(hablar.difficulty, 3);
+    assert_eq!(hablar.tags.len(), 1);
+
+    // the ids of the new cards are written back, the rest of the file is kept
+    let comer = read(&comer_path).await?;
+    let comer_id = comer.front_matter.id.clone().unwrap();
+    assert_eq!(comer.cards[0].back.as_deref(), Some("to eat"));
+    let decks = MemoryDeckRepo::new(db.clone());
+    let french = decks
+        .list_by_user_id(&user)
+        .await?
+        .into_iter()
+        .find(|deck| &*deck.title == "French")
+        .unwrap();
+    let french_cards = decks.list_cards(&user, french.id).await?;
+    assert_eq!(french_cards.len(), 1);
+    assert_eq!(&*french_cards[0].tags[0].name, "greetings");
+
+    // importing again updates the same cards
+    let report = importer(&db)
+        .import(&user, &dir, ImportOptions::default())
+        .await?;
+    assert_eq!(report.created_cards, 0);
+    assert_eq!(report.updated_cards, 5);
+    assert_eq!(report.created_card_groups, 0);
+    assert_eq!(cards.list_by_user_id(&user).await?.len(), 5);
+    assert_eq!(
+        read(&comer_path).await?.front_matter.id.as_deref(),
+        Some(comer_id.as_str())
+    );
+
+    // exporting again overwrites the files in place
+    exporter
+        .export(&user, &dir, ExportOptions::default())
+        .await?;
+    assert!(!tokio::fs::try_exists(dir.join("Languages/Spanish/hablar-2.md")).await?);
+    assert_eq!(
+        read(&comer_path).await?.front_matter.title.as_deref(),
+        Some("comer")
+    );
+
+    tokio::fs::remove_dir_all(&dir).await?;
+    Ok(())
+}
+
+#[tokio::test]
+async fn test_markdown_folder_errors() -> TestResult {
+    let dir = std::env::temp_dir().join(format!("markdown_folder_errors_{}", std::process::id()));
+    if tokio::fs::try_exists(&dir).await? {
+        tokio::fs::remove_dir_all(&dir).await?;
+    }
+    tokio::fs::create_dir_all(&dir).await?;
+
+    let db = MemoryDb::new();
+    let user = create_user(&db, "markdown").await?;
+    let deck = create_deck()
+        .db(&db)
+        .user(&user)
+        .title("Inbox")
+        .call()
+        .await?;
+
+    tokio::fs::write(dir.join("no-front.md"), "## Back\n\nto speak\n").await?;
+    tokio::fs::write(
+        dir.join("difficulty.md"),
+        "---\ndifficulty: 11\n---\n\n## Front\n\nhablar\n",
+    )
+    .await?;
+    tokio::fs::write(dir.join("valid.md"), "## Front\n\nhablar\n").await?;
+
+    // files at the root without a deck go to the deck of the options
+    let options = ImportOptions::builder().deck(deck.id.clone()).build();
+    let report = importer(&db).import(&user, &dir, options).await?;
+    assert_eq!(report.files, 3);
+    assert_eq!(report.created_cards, 1);
+    let mut failed = report
+        .errors
+        .iter()
+        .map(|error| error.path.to_string_lossy().to_string())
+        .collect::<Vec<_>>();
+    failed.sort();
+    assert_eq!(failed, vec!["difficulty.md", "no-front.md"]);
+
+    let cards = MemoryDeckRepo::new(db.clone())
+        .list_cards(&user, deck.id)
+        .await?;
+    assert_eq!(cards.len(), 1);
+    assert_eq!(&*cards[0].title, "hablar");
+
+    tokio::fs::remove_dir_all(&dir).await?;
+    Ok(())
+}
@@ -1,2 +1,9 @@
+mod anki;
+mod backup;
+mod csv;
 mod db;
+mod document;
 mod llm;
+mod markdown_folder;
+mod review_log;
+mod store;
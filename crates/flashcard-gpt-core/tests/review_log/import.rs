@@ -0,0 +1,164 @@
+use crate::anki::import::{importer, package};
+use chrono::{TimeDelta, Utc};
+use flashcard_gpt_core::anki::import::ImportOptions as AnkiImportOptions;
+use flashcard_gpt_core::model::card::Card;
+use flashcard_gpt_core::model::deck::Deck;
+use flashcard_gpt_core::model::deck_card::CreateDeckCard;
+use flashcard_gpt_core::model::user::User;
+use flashcard_gpt_core::review_log::import::{ImportOptions, ReviewImporter};
+use flashcard_gpt_core::review_log::{from_anki, from_csv, GradeMapping};
+use flashcard_gpt_core::store::memory::{
+    MemoryCardRepo, MemoryDb, MemoryDeckRepo, MemoryHistoryRepo, MemoryRepo, MemoryStorage,
+};
+use flashcard_gpt_core::store::{CardStore, DeckStore, HistoryStore};
+use flashcard_gpt_tests::db::memory::{card, create_deck, create_user};
+use testresult::TestResult;
+
+fn review_importer(db: &MemoryDb) -> ReviewImporter<MemoryStorage> {
+    ReviewImporter::new(MemoryRepo::new(db.clone()), MemoryRepo::new(db.clone()))
+}
+
+async fn create_card(db: &MemoryDb, user: &User, deck: &Deck, front: &str) -> TestResult<Card> {
+    let card = MemoryCardRepo::new(db.clone())
+        .create(card(user, front, None))
+        .await?;
+    MemoryDeckRepo::new(db.clone())
+        .relate_card(CreateDeckCard {
+            deck: deck.id.clone(),
+            card: card.id.clone(),
+        })
+        .await?;
+    Ok(card)
+}
+
+#[tokio::test]
+async fn test_import_anki_reviews() -> TestResult {
+    let db = MemoryDb::new();
+    let user = create_user(&db, "anki").await?;
+    importer(&db)
+        .import(&user, package()?, AnkiImportOptions::default())
+        .await?;
+
+    // the basic card three times, once rescheduled by hand, and a card of the three-way note
+    let reviews = from_anki(package()?).await?;
+    assert_eq!(reviews.len(), 4);
+    assert_eq!(&*reviews[0].card, "**hablar**");
+    assert_eq!(reviews[0].interval, Some(TimeDelta::minutes(10)));
+    assert_eq!(reviews[1].interval, Some(TimeDelta::days(3)));
+
+    let report = review_importer(&db)
+        .import(&user, reviews.clone(), ImportOptions::default())
+        .await?;
+    assert_eq!(report.reviews, 4);
+    assert_eq!(report.history, 3);
+    assert_eq!(report.ungraded, 1);
+    assert_eq!(report.unmatched + report.ambiguous, 0);
+    // reviewed long ago
+    assert_eq!(report.hidden, 0);
+
+    let history = MemoryHistoryRepo::new(db.clone())
+        .list_by_user_id(&user)
+        .await?;
+    assert_eq!(
+        history
+            .iter()
+            .filter(|record| record.deck_card_group.is_some())
+            .count(),
+        1
+    );
+    let mut times = history
+        .iter()
+        .map(|record| record.time.created_at)
+        .collect::<Vec<_>>();
+    times.sort();
+    assert_eq!(times[0], reviews[0].time);
+
+    // the same log again adds nothing
+    let report = review_importer(&db)
+        .import(&user, reviews, ImportOptions::default())
+        .await?;
+    assert_eq!(report.history, 0);
+    assert_eq!(report.duplicates, 3);
+
+    Ok(())
+}
+
+#[tokio::test]
+async fn test_import_csv_reviews() -> TestResult {
+    let db = MemoryDb::new();
+    let user = create_user(&db, "anki").await?;
+    let spanish = create_deck()
+        .db(&db)
+        .user(&user)
+        .title("Spanish")
+        .call()
+        .await?;
+    let verbs = create_deck()
+        .db(&db)
+        .user(&user)
+        .title("Verbs")
+        .call()
+        .await?;
+    let hablar = create_card(&db, &user, &spanish, "hablar").await?;
+    create_card(&db, &user, &spanish, "comer").await?;
+    create_card(&db, &user, &spanish, "ser").await?;
+    create_card(&db, &user, &verbs, "ser").await?;
+
+    let days_ago = |days: i64| (Utc::now() - TimeDelta::days(days)).to_rfc3339();
+    let file = format!(
+        "card,timestamp,grade,interval\n\
+         {hablar},{two},3,4\n\
+         hablar,{five},1,1\n\
+         comer,{one},0,\n\
+         ser,{one},2,1\n\
+         nadar,{one},2,1\n\
+         comer,{five},9,1\n",
+        hablar = hablar.id,
+        one = days_ago(1),
+        two = days_ago(2),
+        five = days_ago(5),
+    );
+    let reviews = from_csv(file.as_bytes(), b',')?;
+    let options = ImportOptions::builder()
+        .grades("0=10,1=7,2=3,3=0".parse::<GradeMapping>()?)
+        .build();
+    let report = review_importer(&db).import(&user, reviews, options).await?;
+    assert_eq!(report.reviews, 6);
+    assert_eq!(report.history, 3);
+    assert_eq!(report.ambiguous, 1);
+    assert_eq!(report.unmatched, 1);
+    assert_eq!(report.ungraded, 1);
+    assert_eq!(report.hidden, 1);
+
+    // only the latest answer to hablar hides it, for the two days left of its interval
+    let history = MemoryHistoryRepo::new(db.clone())
+        .list_by_user_id(&user)
+        .await?;
+    let mut hablar_answers = history
+        .iter()
+        .filter(|record| {
+            record
+                .deck_card
+                .as_ref()
+                .is_some_and(|deck_card| deck_card.card.id == hablar.id)
+        })
+        .collect::<Vec<_>>();
+    hablar_answers.sort_by_key(|record| record.time.created_at);
+    assert_eq!(hablar_answers.len(), 2);
+    assert_eq!(hablar_answers[0].difficulty, 7);
+    assert!(hablar_answers[0].hide_for.is_none());
+    assert_eq!(hablar_answers[1].difficulty, 0);
+    assert!(hablar_answers[1].hide_for.is_some());
+
+    let ranked = MemoryDeckRepo::new(db.clone())
+        .list_top_ranked_cards(&user, Utc::now() - TimeDelta::hours(3))
+        .await?;
+    let titles = ranked
+        .iter()
+        .map(|deck_card| deck_card.card.title.to_string())
+        .collect::<Vec<_>>();
+    assert!(!titles.contains(&"hablar".to_string()));
+    assert!(titles.contains(&"comer".to_string()));
+
+    Ok(())
+}
@@ -0,0 +1,1320 @@
+//! Behaviour every storage backend must share. The tests are generic over [`Storage`] and
+//! instantiated for each backend by `conformance!` at the bottom of the file. The SurrealDB
+//! backend runs on the shared test database, so every test names its records after itself.
+
+use chrono::{TimeDelta, Utc};
+use flashcard_gpt_core::llm::provider::{LlmChoice, ProviderKind};
+use flashcard_gpt_core::model::annotation::{Annotations, Flag};
+use flashcard_gpt_core::model::binding::GetOrCreateBinding;
+use flashcard_gpt_core::model::card::{CreateCard, UpdateCard};
+use flashcard_gpt_core::model::card_data::CardData;
+use flashcard_gpt_core::model::card_group::{CreateCardGroup, UpdateCardGroup};
+use flashcard_gpt_core::model::deck::{CreateDeck, Deck, DeckSettings};
+use flashcard_gpt_core::model::deck_card::{CreateCardInDeck, CreateDeckCard};
+use flashcard_gpt_core::model::deck_card_group::CreateDeckCardGroup;
+use flashcard_gpt_core::model::global_settings::CreateGlobalSettings;
+use flashcard_gpt_core::model::history::CreateHistory;
+use flashcard_gpt_core::model::media::{CardSide, Media, MediaKind};
+use flashcard_gpt_core::model::multiple_choice::MultipleChoice;
+use flashcard_gpt_core::model::stats::StatsQuery;
+use flashcard_gpt_core::model::user::{RegisterUser, User};
+use flashcard_gpt_core::repo::binding::BindingRepo;
+use flashcard_gpt_core::repo::card::CardRepo;
+use flashcard_gpt_core::repo::card_group::CardGroupRepo;
+use flashcard_gpt_core::repo::deck::DeckRepo;
+use flashcard_gpt_core::repo::global_settings::GlobalSettingsRepo;
+use flashcard_gpt_core::repo::history::HistoryRepo;
+use flashcard_gpt_core::repo::tag::TagRepo;
+use flashcard_gpt_core::repo::user::UserRepo;
+use flashcard_gpt_core::store::memory::{MemoryDb, MemoryRepo, MemoryStorage};
+use flashcard_gpt_core::store::siblings::CardSiblingsExt;
+use flashcard_gpt_core::store::sqlite::{SqliteDb, SqliteRepo, SqliteStorage};
+use flashcard_gpt_core::store::surreal::SurrealStorage;
+use flashcard_gpt_core::store::{
+    BindingStore, CardGroupStore, CardStore, DeckStore, GlobalSettingsStore, HistoryStore, Storage,
+    TagStore, UserStore,
+};
+use flashcard_gpt_tests::db::{TestDbExt, TEST_DB};
+use std::collections::BTreeSet;
+use std::sync::Arc;
+use surrealdb::sql::{Duration, Thing};
+use testresult::TestResult;
+use tracing::{span, Level};
+
+struct Stores<S: Storage> {
+    users: S::Users,
+    tags: S::Tags,
+    cards: S::Cards,
+    card_groups: S::CardGroups,
+    decks: S::Decks,
+    history: S::History,
+    bindings: S::Bindings,
+    global_settings: S::GlobalSettings,
+}
+
+async fn memory() -> TestResult<Stores<MemoryStorage>> {
+    let db = MemoryDb::new();
+    Ok(Stores {
+        users: MemoryRepo::new(db.clone()),
+        tags: MemoryRepo::new(db.clone()),
+        cards: MemoryRepo::new(db.clone()),
+        card_groups: MemoryRepo::new(db.clone()),
+        decks: MemoryRepo::new(db.clone()),
+        history: MemoryRepo::new(db.clone()),
+        bindings: MemoryRepo::new(db.clone()),
+        global_settings: MemoryRepo::new(db),
+    })
+}
+
+async fn sqlite() -> TestResult<Stores<SqliteStorage>> {
+    let db = SqliteDb::in_memory()?;
+    Ok(Stores {
+        users: SqliteRepo::new(db.clone()),
+        tags: SqliteRepo::new(db.clone()),
+        cards: SqliteRepo::new(db.clone()),
+        card_groups: SqliteRepo::new(db.clone()),
+        decks: SqliteRepo::new(db.clone()),
+        history: SqliteRepo::new(db.clone()),
+        bindings: SqliteRepo::new(db.clone()),
+        global_settings: SqliteRepo::new(db),
+    })
+}
+
+async fn surreal() -> TestResult<Stores<SurrealStorage>> {
+    let db = TEST_DB.get_client().await?;
+    let span = span!(Level::INFO, "conformance");
+    Ok(Stores {
+        users: UserRepo::new_user(db.clone(), span.clone(), false),
+        tags: TagRepo::new_tag(db.clone(), span.clone(), false),
+        cards: CardRepo::new_card(db.clone(), span.clone(), false),
+        card_groups: CardGroupRepo::new_card_group(db.clone(), span.clone(), false),
+        decks: DeckRepo::new_deck(db.clone(), span.clone(), false),
+        history: HistoryRepo::new_history(db.clone(), span.clone(), false),
+        bindings: BindingRepo::new_binding(db.clone(), span.clone(), false),
+        global_settings: GlobalSettingsRepo::new_global_settings(db, span, false),
+    })
+}
+
+async fn create_user<S: Storage>(stores: &Stores<S>, name: &str) -> TestResult<User> {
+    let user = stores
+        .users
+        .create_user(RegisterUser {
+            email: format!("{name}@example.com").into(),
+            name: Arc::from(name),
+            password: Arc::from(name),
+        })
+        .await?;
+    Ok(user)
+}
+
+async fn create_deck<S: Storage>(
+    stores: &Stores<S>,
+    user: &User,
+    daily_limit: usize,
+) -> TestResult<Deck> {
+    let deck = stores
+        .decks
+        .create(CreateDeck {
+            description: None,
+            parent: None,
+            settings: Some(DeckSettings {
+                daily_limit,
+                reverse_cards: false,
+            }),
+            tags: vec![],
+            title: Arc::from("deck"),
+            user: user.id.clone(),
+        })
+        .await?;
+    Ok(deck)
+}
+
+fn card(user: &User, title: &str, importance: u8) -> CreateCard {
+    CreateCard {
+        user: user.id.clone(),
+        title: Arc::from(title),
+        front: Some(Arc::from("front")),
+        back: Some(Arc::from("back")),
+        hints: vec![Arc::from("hint")],
+        difficulty: 1,
+        importance,
+        data: None,
+        choices: None,
+        sibling: None,
+        reverse: false,
+        tags: vec![],
+    }
+}
+
+fn answer(user: &User, deck_card: Thing, hide_for: Option<Duration>) -> CreateHistory {
+    CreateHistory {
+        user: user.id.clone(),
+        deck_card: Some(deck_card),
+        deck_card_group: None,
+        difficulty: 5,
+        time: None,
+        hide_for,
+        steps: vec![],
+    }
+}
+
+async fn users<S: Storage>(stores: Stores<S>, name: &str) -> TestResult {
+    let user = create_user(&stores, name).await?;
+    let fetched = stores.users.get_by_id(&user).await?;
+    assert_eq!(fetched.id, user.id);
+    assert_eq!(fetched.email.as_ref(), format!("{name}@example.com"));
+
+    assert!(create_user(&stores, name).await.is_err());
+
+    Ok(())
+}
+
+async fn tags<S: Storage>(stores: Stores<S>, name: &str) -> TestResult {
+    let user = create_user(&stores, name).await?;
+
+    let created = stores
+        .tags
+        .get_or_create_tags(
+            &user,
+            [Arc::from("Graphs"), Arc::from("Dynamic Programming")],
+        )
+        .await?;
+    let slugs = created
+        .iter()
+        .map(|tag| tag.slug.as_ref())
+        .collect::<Vec<_>>();
+    assert_eq!(slugs, ["dynamic-programming", "graphs"]);
+
+    let again = stores
+        .tags
+        .get_or_create_tags(&user, [Arc::from("Graphs"), Arc::from("Graphs")])
+        .await?;
+    assert_eq!(again.len(), 1);
+    assert_eq!(again[0].id, created[1].id);
+    assert_eq!(stores.tags.list_by_user_id(&user).await?.len(), 2);
+
+    Ok(())
+}
+
+async fn cards<S: Storage>(stores: Stores<S>, name: &str) -> TestResult {
+    let user = create_user(&stores, name).await?;
+    let tags = stores
+        .tags
+        .get_or_create_tags(&user, [Arc::from("tag")])
+        .await?;
+
+    let mut dto = card(&user, "card", 3);
+    dto.tags = tags.iter().map(|tag| tag.id.clone()).collect();
+    let card = stores.cards.create(dto).await?;
+    assert_eq!(card.user.id, user.id);
+    assert_eq!(card.tags.len(), 1);
+    assert_eq!(card.hints, vec![Arc::<str>::from("hint")]);
+
+    let patched = stores
+        .cards
+        .patch(
+            &card,
+            UpdateCard {
+                importance: None,
+                difficulty: Some(7),
+                sibling: None,
+                annotations: None,
+                title: None,
+                front: None,
+                back: None,
+                hints: None,
+                tags: None,
+            },
+        )
+        .await?;
+    assert_eq!(patched.difficulty, 7);
+    assert_eq!(patched.importance, 3);
+    assert_eq!(stores.cards.get_by_id(&card).await?.difficulty, 7);
+
+    let card_group = stores
+        .card_groups
+        .create(CreateCardGroup {
+            user: user.id.clone(),
+            title: Arc::from("group"),
+            importance: 1,
+            difficulty: 2,
+            data: None,
+            cards: vec![card.id.clone()],
+            tags: vec![],
+        })
+        .await?;
+    let card_group = stores.card_groups.get_by_id(card_group.id.clone()).await?;
+    assert_eq!(card_group.cards.len(), 1);
+    assert_eq!(card_group.cards[0].id, card.id);
+    assert_eq!(stores.card_groups.list_by_user_id(&user).await?.len(), 1);
+
+    assert_eq!(stores.cards.list_by_user_id(&user).await?.len(), 1);
+    stores.cards.delete(&card).await?;
+    assert!(stores.cards.list_by_user_id(&user).await?.is_empty());
+
+    let choices = MultipleChoice {
+        options: vec![Arc::from("a"), Arc::from("b"), Arc::from("c")],
+        correct: BTreeSet::from([0, 2]),
+    };
+    let dto = CreateCard {
+        choices: Some(choices.clone()),
+        ..card(&user, "multiple choice", 1)
+    };
+    let card = stores.cards.create(dto).await?;
+    assert_eq!(card.choices.as_ref(), Some(&choices));
+    assert_eq!(stores.cards.get_by_id(&card).await?.choices, Some(choices));
+
+    Ok(())
+}
+
+async fn decks<S: Storage>(stores: Stores<S>, name: &str) -> TestResult {
+    let user = create_user(&stores, name).await?;
+    let parent = create_deck(&stores, &user, 10).await?;
+    let deck = stores
+        .decks
+        .create(CreateDeck {
+            description: Some(Arc::from("description")),
+            parent: Some(parent.id.clone()),
+            settings: Some(DeckSettings {
+                daily_limit: 20,
+                reverse_cards: false,
+            }),
+            tags: vec![],
+            title: Arc::from("child"),
+            user: user.id.clone(),
+        })
+        .await?;
+    let deck = stores.decks.get_by_id(&deck).await?;
+    assert_eq!(deck.parent.as_ref(), Some(&parent.id));
+    assert_eq!(deck.settings.map(|settings| settings.daily_limit), Some(20));
+    assert_eq!(stores.decks.list_by_user_id(&user).await?.len(), 2);
+
+    for title in ["b", "a"] {
+        let card = stores.cards.create(card(&user, title, 1)).await?;
+        let deck_card = stores
+            .decks
+            .relate_card(CreateDeckCard {
+                deck: parent.id.clone(),
+                card: card.id.clone(),
+            })
+            .await?;
+        let deck_card = stores.decks.get_deck_card(deck_card.id).await?;
+        assert_eq!(deck_card.card.id, card.id);
+        assert_eq!(deck_card.deck.id, parent.id);
+
+        let duplicate = CreateDeckCard {
+            deck: parent.id.clone(),
+            card: card.id.clone(),
+        };
+        assert!(stores.decks.relate_card(duplicate).await.is_err());
+    }
+
+    let titles = stores
+        .decks
+        .list_cards(&user, &parent)
+        .await?
+        .into_iter()
+        .map(|card| card.title.to_string())
+        .collect::<Vec<_>>();
+    assert_eq!(titles, ["a", "b"]);
+
+    let card = stores.cards.create(card(&user, "grouped", 1)).await?;
+    let card_group = stores
+        .card_groups
+        .create(CreateCardGroup {
+            user: user.id.clone(),
+            title: Arc::from("group"),
+            importance: 1,
+            difficulty: 2,
+            data: None,
+            cards: vec![card.id.clone()],
+            tags: vec![],
+        })
+        .await?;
+    stores
+        .decks
+        .relate_card_group(CreateDeckCardGroup {
+            deck: deck.id.clone(),
+            card_group: card_group.id.clone(),
+        })
+        .await?;
+    let card_groups = stores.decks.list_card_groups(&user, &deck).await?;
+    assert_eq!(card_groups.len(), 1);
+    assert_eq!(card_groups[0].id, card_group.id);
+    assert_eq!(card_groups[0].cards[0].id, card.id);
+    assert!(stores
+        .decks
+        .list_card_groups(&user, &parent)
+        .await?
+        .is_empty());
+
+    let deck_cards = stores.decks.list_deck_cards(&user).await?;
+    assert_eq!(deck_cards.len(), 2);
+    assert!(deck_cards
+        .iter()
+        .all(|deck_card| deck_card.deck.id == parent.id));
+    let deck_card_groups = stores.decks.list_deck_card_groups(&user).await?;
+    assert_eq!(deck_card_groups.len(), 1);
+    assert_eq!(deck_card_groups[0].card_group.cards[0].id, card.id);
+    let other = create_user(&stores, &format!("{name}_other")).await?;
+    assert!(stores.decks.list_deck_cards(&other).await?.is_empty());
+
+    Ok(())
+}
+
+async fn create_cards<S: Storage>(stores: Stores<S>, name: &str) -> TestResult {
+    let user = create_user(&stores, name).await?;
+    let deck = create_deck(&stores, &user, 10).await?;
+
+    let in_deck = |deck: &Thing, title: &str, tag: &str| CreateCardInDeck {
+        deck: deck.clone(),
+        card: card(&user, title, 1),
+        tag_names: vec![Arc::from(tag)],
+    };
+    let ids = stores
+        .decks
+        .create_cards(vec![
+            in_deck(&deck.id, "b", "shared"),
+            in_deck(&deck.id, "a", "shared"),
+        ])
+        .await?;
+    assert_eq!(ids.len(), 2);
+    let cards = stores.decks.list_cards(&user, &deck).await?;
+    let titles = cards
+        .iter()
+        .map(|card| card.title.to_string())
+        .collect::<Vec<_>>();
+    assert_eq!(titles, ["a", "b"]);
+    assert_eq!(cards[1].id, ids[0]);
+    // the tag is created once and added to both cards
+    assert_eq!(stores.tags.list_by_user_id(&user).await?.len(), 1);
+    assert!(cards
+        .iter()
+        .all(|card| card.tags.len() == 1 && &*card.tags[0].name == "shared"));
+
+    // a card is not a deck, the card created before it and its tag are rolled back
+    let result = stores
+        .decks
+        .create_cards(vec![
+            in_deck(&deck.id, "c", "orphan"),
+            in_deck(&ids[0], "d", "orphan"),
+        ])
+        .await;
+    assert!(result.is_err());
+    assert_eq!(stores.cards.list_by_user_id(&user).await?.len(), 2);
+    assert_eq!(stores.tags.list_by_user_id(&user).await?.len(), 1);
+
+    Ok(())
+}
+
+async fn patch_content<S: Storage>(stores: Stores<S>, name: &str) -> TestResult {
+    let user = create_user(&stores, name).await?;
+    let tags = stores
+        .tags
+        .get_or_create_tags(&user, [Arc::from("old"), Arc::from("new")])
+        .await?;
+    let tag = |name: &str| {
+        let tag = tags.iter().find(|tag| &*tag.name == name).unwrap();
+        tag.id.clone()
+    };
+    let (old, new) = (tag("old"), tag("new"));
+
+    let mut dto = card(&user, "card", 3);
+    dto.tags = vec![old.clone()];
+    let card = stores.cards.create(dto).await?;
+    let patched = stores
+        .cards
+        .patch(
+            &card,
+            UpdateCard::builder()
+                .title(Arc::from("title"))
+                .front(Arc::from("new front"))
+                .back(Arc::from("new back"))
+                .hints(vec![Arc::from("a"), Arc::from("b")])
+                .tags(vec![new.clone()])
+                .build(),
+        )
+        .await?;
+    assert_eq!(&*patched.title, "title");
+    assert_eq!(patched.front.as_deref(), Some("new front"));
+    assert_eq!(patched.back.as_deref(), Some("new back"));
+    assert_eq!(patched.hints.len(), 2);
+    assert_eq!(patched.tags.len(), 1);
+    assert_eq!(patched.tags[0].id, new);
+    // fields left out keep their values
+    assert_eq!(patched.importance, 3);
+    assert_eq!(patched.difficulty, 1);
+
+    let card_group = stores
+        .card_groups
+        .create(CreateCardGroup {
+            user: user.id.clone(),
+            title: Arc::from("group"),
+            importance: 1,
+            difficulty: 2,
+            data: None,
+            cards: vec![card.id.clone()],
+            tags: vec![old],
+        })
+        .await?;
+    let patched = stores
+        .card_groups
+        .patch(
+            card_group.id.clone(),
+            UpdateCardGroup::builder()
+                .title(Arc::from("renamed"))
+                .tags(vec![new.clone()])
+                .build(),
+        )
+        .await?;
+    assert_eq!(&*patched.title, "renamed");
+    assert_eq!(patched.tags.len(), 1);
+    assert_eq!(patched.tags[0].id, new);
+    assert_eq!(patched.cards.len(), 1);
+    assert_eq!(patched.importance, 1);
+
+    Ok(())
+}
+
+async fn top_ranked_cards<S: Storage>(stores: Stores<S>, name: &str) -> TestResult {
+    let user = create_user(&stores, name).await?;
+    let deck = create_deck(&stores, &user, 10).await?;
+
+    let mut deck_cards = vec![];
+    for (title, importance) in [("minor", 1), ("major", 9)] {
+        let card = stores.cards.create(card(&user, title, importance)).await?;
+        let deck_card = stores
+            .decks
+            .relate_card(CreateDeckCard {
+                deck: deck.id.clone(),
+                card: card.id.clone(),
+            })
+            .await?;
+        deck_cards.push(deck_card);
+    }
+
+    let since = Utc::now() - TimeDelta::hours(3);
+    let ranked = stores.decks.list_top_ranked_cards(&user, since).await?;
+    let titles = ranked
+        .iter()
+        .map(|dc| dc.card.title.as_ref())
+        .collect::<Vec<_>>();
+    assert_eq!(titles, ["major", "minor"]);
+
+    stores
+        .history
+        .create_custom(answer(&user, deck_cards[1].id.clone(), None))
+        .await?;
+    let ranked = stores.decks.list_top_ranked_cards(&user, since).await?;
+    assert_eq!(ranked.len(), 1);
+    assert_eq!(ranked[0].card.title.as_ref(), "minor");
+
+    // answers before `later` are not counted, hiding still applies
+    let later = Utc::now() + TimeDelta::minutes(1);
+    stores
+        .history
+        .create_custom(answer(
+            &user,
+            deck_cards[0].id.clone(),
+            Some(Duration::from_hours(1)),
+        ))
+        .await?;
+    let ranked = stores.decks.list_top_ranked_cards(&user, later).await?;
+    assert_eq!(ranked.len(), 1);
+    assert_eq!(ranked[0].card.title.as_ref(), "major");
+
+    Ok(())
+}
+
+async fn sibling_cards<S: Storage>(stores: Stores<S>, name: &str) -> TestResult {
+    let user = create_user(&stores, name).await?;
+    let deck = create_deck(&stores, &user, 10).await?;
+
+    let dto = CreateCard {
+        reverse: true,
+        ..card(&user, "word", 3)
+    };
+    let cards = stores.cards.create_with_sibling(dto).await?;
+    let [card, reversed] = cards.as_slice() else {
+        panic!("expected a card and its reversed sibling, got {cards:?}");
+    };
+    assert_eq!(card.sibling.as_ref(), Some(&reversed.id));
+    assert_eq!(reversed.sibling.as_ref(), Some(&card.id));
+    assert_eq!(reversed.front.as_deref(), Some("back"));
+    assert_eq!(reversed.back.as_deref(), Some("front"));
+    assert!(reversed.hints.is_empty());
+
+    let patched = stores
+        .cards
+        .patch_with_sibling(
+            card,
+            UpdateCard {
+                importance: Some(8),
+                difficulty: Some(6),
+                sibling: None,
+                annotations: None,
+                title: Some(Arc::from("renamed")),
+                front: Some(Arc::from("new front")),
+                back: None,
+                hints: Some(vec![Arc::from("new hint")]),
+                tags: None,
+            },
+        )
+        .await?;
+    assert_eq!(patched.sibling.as_ref(), Some(&reversed.id));
+    assert_eq!(patched.hints, vec![Arc::<str>::from("new hint")]);
+    let patched_reversed = stores.cards.get_by_id(reversed).await?;
+    assert_eq!(patched_reversed.importance, 8);
+    assert_eq!(patched_reversed.difficulty, reversed.difficulty);
+    assert_eq!(patched_reversed.title.as_ref(), "renamed (reversed)");
+    assert_eq!(patched_reversed.front.as_deref(), Some("back"));
+    assert_eq!(patched_reversed.back.as_deref(), Some("new front"));
+    // hints are never shared
+    assert!(patched_reversed.hints.is_empty());
+
+    // edits of the reversed side reach the original card too
+    stores
+        .cards
+        .patch_with_sibling(
+            reversed,
+            UpdateCard {
+                importance: None,
+                difficulty: None,
+                sibling: None,
+                annotations: None,
+                title: Some(Arc::from("renamed again (reversed)")),
+                front: Some(Arc::from("new back")),
+                back: None,
+                hints: None,
+                tags: None,
+            },
+        )
+        .await?;
+    let patched = stores.cards.get_by_id(card).await?;
+    assert_eq!(patched.title.as_ref(), "renamed again");
+    assert_eq!(patched.front.as_deref(), Some("new front"));
+    assert_eq!(patched.back.as_deref(), Some("new back"));
+
+    // answering one side buries the other one
+    let mut deck_cards = vec![];
+    for card in [card, reversed] {
+        let deck_card = stores
+            .decks
+            .relate_card(CreateDeckCard {
+                deck: deck.id.clone(),
+                card: card.id.clone(),
+            })
+            .await?;
+        deck_cards.push(deck_card);
+    }
+    let since = Utc::now() - TimeDelta::hours(3);
+    assert_eq!(
+        stores
+            .decks
+            .list_top_ranked_cards(&user, since)
+            .await?
+            .len(),
+        2
+    );
+    stores
+        .history
+        .create_custom(answer(&user, deck_cards[0].id.clone(), None))
+        .await?;
+    assert!(stores
+        .decks
+        .list_top_ranked_cards(&user, since)
+        .await?
+        .is_empty());
+
+    // cloze and multiple-choice cards are not reversed
+    let dto = CreateCard {
+        front: Some(Arc::from("{{c1::Rust}} is a language")),
+        reverse: true,
+        ..card(&user, "cloze", 1)
+    };
+    assert_eq!(stores.cards.create_with_sibling(dto).await?.len(), 1);
+
+    stores.cards.delete_with_sibling(reversed).await?;
+    let titles = stores
+        .cards
+        .list_by_user_id(&user)
+        .await?
+        .into_iter()
+        .map(|card| card.title)
+        .collect::<Vec<_>>();
+    assert_eq!(titles, vec![Arc::<str>::from("cloze")]);
+
+    Ok(())
+}
+
+async fn card_data<S: Storage>(stores: Stores<S>, name: &str) -> TestResult {
+    let user = create_user(&stores, name).await?;
+
+    let data = CardData::builder()
+        .source_link(Arc::from("https://leetcode.com/problems/largest-number/"))
+        .article(Arc::from("Sort by concatenation"))
+        .cloze(2)
+        .media(vec![Media::builder()
+            .kind(MediaKind::Photo)
+            .side(CardSide::Back)
+            .file_id(Arc::from("AgACAgIAAxkBAAI"))
+            .blob(Arc::from("AgADBQ.jpg"))
+            .build()])
+        .build();
+    let dto = CreateCard {
+        data: Some(data.clone()),
+        ..card(&user, "card", 1)
+    };
+    let card = stores.cards.create(dto).await?;
+    assert_eq!(card.data.as_ref(), Some(&data));
+    assert_eq!(
+        stores.cards.get_by_id(&card).await?.data,
+        Some(data.clone())
+    );
+
+    let card_group = stores
+        .card_groups
+        .create(CreateCardGroup {
+            user: user.id.clone(),
+            title: Arc::from("group"),
+            importance: 1,
+            difficulty: 2,
+            data: Some(data.clone()),
+            cards: vec![card.id.clone()],
+            tags: vec![],
+        })
+        .await?;
+    let card_group = stores.card_groups.get_by_id(card_group.id.clone()).await?;
+    assert_eq!(card_group.data, Some(data));
+
+    let invalid = CardData::builder()
+        .source_link(Arc::from("leetcode.com"))
+        .build();
+    let dto = CreateCard {
+        data: Some(invalid.clone()),
+        ..card(&user, "invalid", 1)
+    };
+    assert!(stores.cards.create(dto).await.is_err());
+    let dto = CreateCardGroup {
+        user: user.id.clone(),
+        title: Arc::from("invalid"),
+        importance: 1,
+        difficulty: 2,
+        data: Some(invalid),
+        cards: vec![],
+        tags: vec![],
+    };
+    assert!(stores.card_groups.create(dto).await.is_err());
+    assert_eq!(stores.cards.list_by_user_id(&user).await?.len(), 1);
+
+    Ok(())
+}
+
+async fn annotations<S: Storage>(stores: Stores<S>, name: &str) -> TestResult {
+    let user = create_user(&stores, name).await?;
+    let card = stores.cards.create(card(&user, "card", 1)).await?;
+    assert!(card.annotations.is_empty());
+
+    let mut annotations = Annotations::default();
+    annotations
+        .notes
+        .push(Arc::from("I confuse this with the reversed one"));
+    annotations.toggle(Flag::NeedsFix);
+    let patched = stores
+        .cards
+        .patch(
+            &card,
+            UpdateCard {
+                importance: None,
+                difficulty: None,
+                sibling: None,
+                annotations: Some(annotations.clone()),
+                title: None,
+                front: None,
+                back: None,
+                hints: None,
+                tags: None,
+            },
+        )
+        .await?;
+    assert_eq!(patched.annotations, annotations);
+    assert_eq!(patched.difficulty, card.difficulty);
+    assert_eq!(
+        stores.cards.get_by_id(&card).await?.annotations,
+        annotations
+    );
+
+    let card_group = stores
+        .card_groups
+        .create(CreateCardGroup {
+            user: user.id.clone(),
+            title: Arc::from("group"),
+            importance: 1,
+            difficulty: 2,
+            data: None,
+            cards: vec![card.id.clone()],
+            tags: vec![],
+        })
+        .await?;
+    assert!(card_group.annotations.is_empty());
+    assert_eq!(card_group.cards[0].annotations, annotations);
+
+    let mut group_annotations = Annotations::default();
+    group_annotations.toggle(Flag::Bookmark);
+    group_annotations.toggle(Flag::Favorite);
+    stores
+        .card_groups
+        .patch(
+            card_group.id.clone(),
+            UpdateCardGroup {
+                importance: None,
+                difficulty: None,
+                cards: None,
+                annotations: Some(group_annotations.clone()),
+                title: None,
+                tags: None,
+            },
+        )
+        .await?;
+    let card_group = stores.card_groups.get_by_id(card_group.id.clone()).await?;
+    assert_eq!(card_group.annotations, group_annotations);
+    assert_eq!(card_group.cards.len(), 1);
+
+    Ok(())
+}
+
+async fn top_ranked_respects_daily_limit<S: Storage>(stores: Stores<S>, name: &str) -> TestResult {
+    let user = create_user(&stores, name).await?;
+    let deck = create_deck(&stores, &user, 0).await?;
+
+    let mut deck_cards = vec![];
+    for title in ["first", "second"] {
+        let card = stores.cards.create(card(&user, title, 1)).await?;
+        let deck_card = stores
+            .decks
+            .relate_card(CreateDeckCard {
+                deck: deck.id.clone(),
+                card: card.id.clone(),
+            })
+            .await?;
+        deck_cards.push(deck_card);
+    }
+
+    let since = Utc::now() - TimeDelta::hours(3);
+    assert_eq!(
+        stores
+            .decks
+            .list_top_ranked_cards(&user, since)
+            .await?
+            .len(),
+        2
+    );
+
+    stores
+        .history
+        .create_custom(answer(&user, deck_cards[0].id.clone(), None))
+        .await?;
+    assert!(stores
+        .decks
+        .list_top_ranked_cards(&user, since)
+        .await?
+        .is_empty());
+
+    Ok(())
+}
+
+async fn top_ranked_card_groups<S: Storage>(stores: Stores<S>, name: &str) -> TestResult {
+    let user = create_user(&stores, name).await?;
+    let deck = create_deck(&stores, &user, 10).await?;
+
+    let card = stores.cards.create(card(&user, "grouped", 1)).await?;
+    stores
+        .decks
+        .relate_card(CreateDeckCard {
+            deck: deck.id.clone(),
+            card: card.id.clone(),
+        })
+        .await?;
+    let card_group = stores
+        .card_groups
+        .create(CreateCardGroup {
+            user: user.id.clone(),
+            title: Arc::from("group"),
+            importance: 1,
+            difficulty: 1,
+            data: None,
+            cards: vec![card.id.clone()],
+            tags: vec![],
+        })
+        .await?;
+    let deck_card_group = stores
+        .decks
+        .relate_card_group(CreateDeckCardGroup {
+            deck: deck.id.clone(),
+            card_group: card_group.id.clone(),
+        })
+        .await?;
+
+    let since = Utc::now() - TimeDelta::hours(3);
+    // cards that belong to a group in the same deck are asked as part of the group
+    assert!(stores
+        .decks
+        .list_top_ranked_cards(&user, since)
+        .await?
+        .is_empty());
+
+    let ranked = stores
+        .decks
+        .list_top_ranked_card_groups(&user, since)
+        .await?;
+    assert_eq!(ranked.len(), 1);
+    assert_eq!(ranked[0].card_group.cards.len(), 1);
+    assert_eq!(ranked[0].num_answered, Some(0));
+
+    stores
+        .history
+        .create_custom(CreateHistory {
+            user: user.id.clone(),
+            deck_card: None,
+            deck_card_group: Some(deck_card_group.id.clone()),
+            difficulty: 3,
+            time: None,
+            hide_for: None,
+            steps: vec![],
+        })
+        .await?;
+    assert!(stores
+        .decks
+        .list_top_ranked_card_groups(&user, since)
+        .await?
+        .is_empty());
+
+    let history = stores.history.list_by_user_id(&user).await?;
+    assert_eq!(history.len(), 1);
+    assert_eq!(
+        history[0].deck_card_group.as_ref().map(|dcg| &dcg.id),
+        Some(&deck_card_group.id)
+    );
+
+    Ok(())
+}
+
+async fn progressive_card_groups<S: Storage>(stores: Stores<S>, name: &str) -> TestResult {
+    let user = create_user(&stores, name).await?;
+    let deck = create_deck(&stores, &user, 10).await?;
+
+    let mut cards = vec![];
+    for title in ["first", "second", "third"] {
+        cards.push(stores.cards.create(card(&user, title, 1)).await?.id);
+    }
+    let card_group = stores
+        .card_groups
+        .create(CreateCardGroup {
+            user: user.id.clone(),
+            title: Arc::from("group"),
+            importance: 1,
+            difficulty: 1,
+            data: None,
+            cards,
+            tags: vec![],
+        })
+        .await?;
+
+    let order = card_group.reordered(&[2, 0, 1]).expect("a permutation");
+    assert!(card_group.reordered(&[0, 0, 1]).is_none());
+    let card_group = stores
+        .card_groups
+        .patch(
+            card_group.id.clone(),
+            UpdateCardGroup {
+                importance: None,
+                difficulty: None,
+                cards: Some(order),
+                annotations: None,
+                title: None,
+                tags: None,
+            },
+        )
+        .await?;
+    let titles = card_group
+        .cards
+        .iter()
+        .map(|card| card.title.as_ref())
+        .collect::<Vec<_>>();
+    assert_eq!(titles, ["third", "first", "second"]);
+
+    let deck_card_group = stores
+        .decks
+        .relate_card_group(CreateDeckCardGroup {
+            deck: deck.id.clone(),
+            card_group: card_group.id.clone(),
+        })
+        .await?;
+    assert!(stores
+        .history
+        .get_last_answer(deck_card_group.id.clone())
+        .await?
+        .is_none());
+
+    for steps in [vec![Some(2), Some(8)], vec![Some(1), None, Some(3)]] {
+        stores
+            .history
+            .create_custom(CreateHistory {
+                user: user.id.clone(),
+                deck_card: None,
+                deck_card_group: Some(deck_card_group.id.clone()),
+                difficulty: 5,
+                time: None,
+                hide_for: None,
+                steps,
+            })
+            .await?;
+    }
+    let last = stores
+        .history
+        .get_last_answer(deck_card_group.id.clone())
+        .await?
+        .expect("the second answer");
+    assert_eq!(last.steps, [Some(1), None, Some(3)]);
+
+    Ok(())
+}
+
+async fn answers<S: Storage>(stores: Stores<S>, name: &str) -> TestResult {
+    let user = create_user(&stores, name).await?;
+    let deck = create_deck(&stores, &user, 10).await?;
+    let other_deck = create_deck(&stores, &user, 10).await?;
+    let tags = stores
+        .tags
+        .get_or_create_tags(&user, [Arc::from("Stats")])
+        .await?;
+
+    let tagged = stores
+        .cards
+        .create(CreateCard {
+            tags: vec![tags[0].id.clone()],
+            ..card(&user, "tagged", 1)
+        })
+        .await?;
+    let untagged = stores.cards.create(card(&user, "untagged", 1)).await?;
+    let card_group = stores
+        .card_groups
+        .create(CreateCardGroup {
+            user: user.id.clone(),
+            title: Arc::from("group"),
+            importance: 1,
+            difficulty: 1,
+            data: None,
+            cards: vec![untagged.id.clone()],
+            tags: vec![tags[0].id.clone()],
+        })
+        .await?;
+
+    let tagged = stores
+        .decks
+        .relate_card(CreateDeckCard {
+            deck: deck.id.clone(),
+            card: tagged.id.clone(),
+        })
+        .await?;
+    let untagged = stores
+        .decks
+        .relate_card(CreateDeckCard {
+            deck: other_deck.id.clone(),
+            card: untagged.id.clone(),
+        })
+        .await?;
+    let card_group = stores
+        .decks
+        .relate_card_group(CreateDeckCardGroup {
+            deck: deck.id.clone(),
+            card_group: card_group.id.clone(),
+        })
+        .await?;
+
+    let from = Utc::now() - TimeDelta::hours(1);
+    for deck_card in [&tagged, &untagged] {
+        stores
+            .history
+            .create_custom(answer(&user, deck_card.id.clone(), None))
+            .await?;
+    }
+    stores
+        .history
+        .create_custom(CreateHistory {
+            user: user.id.clone(),
+            deck_card: None,
+            deck_card_group: Some(card_group.id.clone()),
+            difficulty: 2,
+            time: None,
+            hide_for: None,
+            steps: vec![],
+        })
+        .await?;
+
+    let query = StatsQuery::builder()
+        .user(user.id.clone())
+        .from(from)
+        .till(Utc::now() + TimeDelta::hours(1))
+        .build();
+    let answers = stores.history.list_answers(query.clone()).await?;
+    let titles = answers
+        .iter()
+        .map(|answer| answer.title.as_ref())
+        .collect::<Vec<_>>();
+    assert_eq!(titles, ["tagged", "untagged", "group"]);
+    assert_eq!(answers[1].deck, other_deck.id);
+    assert_eq!(answers[2].item, card_group.card_group.id);
+    assert_eq!(answers[2].difficulty, 2);
+
+    let by_deck = StatsQuery {
+        deck: Some(deck.id.clone()),
+        ..query.clone()
+    };
+    assert_eq!(stores.history.list_answers(by_deck).await?.len(), 2);
+
+    let by_tag = StatsQuery {
+        tag: Some(tags[0].id.clone()),
+        ..query.clone()
+    };
+    assert_eq!(stores.history.list_answers(by_tag).await?.len(), 2);
+
+    let before = StatsQuery {
+        till: from,
+        ..query
+    };
+    assert!(stores.history.list_answers(before).await?.is_empty());
+
+    Ok(())
+}
+
+async fn bindings<S: Storage>(stores: Stores<S>, name: &str) -> TestResult {
+    let source_id = Arc::<str>::from(format!("{name}:1"));
+    let dto = || GetOrCreateBinding {
+        source_id: source_id.clone(),
+        type_name: Arc::from("telegram"),
+        email: format!("{name}@example.com").into(),
+        name: Arc::from(name),
+        password: Arc::from(name),
+        data: None,
+    };
+
+    let binding = stores.bindings.get_or_create_binding(dto()).await?;
+    let same = stores.bindings.get_or_create_binding(dto()).await?;
+    assert_eq!(binding.id, same.id);
+    assert_eq!(binding.user.id, same.user.id);
+    let user = stores.users.get_by_id(binding.user.id.clone()).await?;
+    assert_eq!(user.email, binding.user.email);
+
+    let found = stores.bindings.get_by_source_id(source_id.clone()).await?;
+    assert_eq!(found.map(|binding| binding.id), Some(binding.id.clone()));
+    let missing = Arc::<str>::from(format!("{name}:missing"));
+    assert!(stores.bindings.get_by_source_id(missing).await?.is_none());
+
+    let not_banned = stores.bindings.list_all_not_banned().await?;
+    assert!(not_banned.iter().any(|b| b.id == binding.id));
+
+    stores.bindings.set_banned(&binding).await?;
+    let not_banned = stores.bindings.list_all_not_banned().await?;
+    assert!(not_banned.iter().all(|b| b.id != binding.id));
+
+    Ok(())
+}
+
+async fn global_settings<S: Storage>(stores: Stores<S>, name: &str) -> TestResult {
+    let user = create_user(&stores, name).await?;
+    let dto = || CreateGlobalSettings {
+        user: user.id.clone(),
+        daily_limit: 50,
+        timetable: vec![[Duration::from_hours(10), Duration::from_hours(23)]],
+        timezone: chrono_tz::Tz::Europe__Dublin,
+        render_images: false,
+        multiple_choice: false,
+        llm: None,
+    };
+
+    assert!(stores.global_settings.get_by_user_id(&user).await.is_err());
+    stores.global_settings.create(dto()).await?;
+    assert!(stores.global_settings.create(dto()).await.is_err());
+
+    let settings = stores.global_settings.get_by_user_id(&user).await?;
+    assert_eq!(settings.daily_limit, 50);
+    assert_eq!(settings.timetable, dto().timetable);
+    assert_eq!(settings.timezone, chrono_tz::Tz::Europe__Dublin);
+    assert_eq!(settings.user.id, user.id);
+    assert!(!settings.render_images);
+
+    let settings = stores
+        .global_settings
+        .set_render_images(&user, true)
+        .await?;
+    assert!(settings.render_images);
+    assert_eq!(settings.daily_limit, 50);
+    assert!(
+        stores
+            .global_settings
+            .get_by_user_id(&user)
+            .await?
+            .render_images
+    );
+
+    assert!(!settings.multiple_choice);
+    let settings = stores
+        .global_settings
+        .set_multiple_choice(&user, true)
+        .await?;
+    assert!(settings.multiple_choice);
+    assert!(settings.render_images);
+    assert!(
+        stores
+            .global_settings
+            .get_by_user_id(&user)
+            .await?
+            .multiple_choice
+    );
+
+    assert_eq!(settings.llm, None);
+    let llm = LlmChoice {
+        provider: ProviderKind::Ollama,
+        model: Some(Arc::from("llama3.1:8b")),
+    };
+    let settings = stores
+        .global_settings
+        .set_llm(&user, Some(llm.clone()))
+        .await?;
+    assert_eq!(settings.llm.as_ref(), Some(&llm));
+    assert!(settings.render_images);
+    let settings = stores.global_settings.get_by_user_id(&user).await?;
+    assert_eq!(settings.llm, Some(llm));
+    let settings = stores.global_settings.set_llm(&user, None).await?;
+    assert_eq!(settings.llm, None);
+
+    Ok(())
+}
+
+async fn clear_data<S: Storage>(stores: Stores<S>, name: &str) -> TestResult {
+    let user = create_user(&stores, name).await?;
+    let other = create_user(&stores, &format!("{name}_other")).await?;
+
+    for user in [&user, &other] {
+        let tags = stores
+            .tags
+            .get_or_create_tags(user, [Arc::from("tag")])
+            .await?;
+        let deck = create_deck(&stores, user, 10).await?;
+        let mut dto = card(user, "card", 1);
+        dto.tags = vec![tags[0].id.clone()];
+        let card = stores.cards.create(dto).await?;
+        let deck_card = stores
+            .decks
+            .relate_card(CreateDeckCard {
+                deck: deck.id.clone(),
+                card: card.id.clone(),
+            })
+            .await?;
+        stores
+            .history
+            .create_custom(answer(user, deck_card.id, None))
+            .await?;
+        let card_group = stores
+            .card_groups
+            .create(CreateCardGroup {
+                user: user.id.clone(),
+                title: Arc::from("group"),
+                importance: 1,
+                difficulty: 1,
+                data: None,
+                cards: vec![card.id],
+                tags: vec![],
+            })
+            .await?;
+        stores
+            .decks
+            .relate_card_group(CreateDeckCardGroup {
+                deck: deck.id,
+                card_group: card_group.id,
+            })
+            .await?;
+        stores
+            .global_settings
+            .create(CreateGlobalSettings {
+                user: user.id.clone(),
+                daily_limit: 50,
+                timetable: vec![],
+                timezone: chrono_tz::Tz::UTC,
+                render_images: false,
+                multiple_choice: false,
+                llm: None,
+            })
+            .await?;
+    }
+
+    stores.users.clear_data(&user).await?;
+    assert!(stores.tags.list_by_user_id(&user).await?.is_empty());
+    assert!(stores.decks.list_by_user_id(&user).await?.is_empty());
+    assert!(stores.cards.list_by_user_id(&user).await?.is_empty());
+    assert!(stores.card_groups.list_by_user_id(&user).await?.is_empty());
+    assert!(stores.history.list_by_user_id(&user).await?.is_empty());
+    assert!(stores.global_settings.get_by_user_id(&user).await.is_err());
+    // the user stays, and the records of other users are left alone
+    assert_eq!(stores.users.get_by_id(&user).await?.id, user.id);
+    assert_eq!(stores.tags.list_by_user_id(&other).await?.len(), 1);
+    let decks = stores.decks.list_by_user_id(&other).await?;
+    assert_eq!(decks.len(), 1);
+    assert_eq!(
+        stores
+            .decks
+            .list_cards(&other, decks[0].id.clone())
+            .await?
+            .len(),
+        1
+    );
+    assert_eq!(stores.card_groups.list_by_user_id(&other).await?.len(), 1);
+    assert_eq!(stores.history.list_by_user_id(&other).await?.len(), 1);
+    stores.global_settings.get_by_user_id(&other).await?;
+
+    Ok(())
+}
+
+macro_rules! conformance {
+    (backends: [$($backend:ident),*], tests: $tests:tt) => {
+        $( conformance!(@backend $backend, $tests); )*
+    };
+    (@backend $backend:ident, [$($test:ident),*]) => {
+        mod $backend {
+            use testresult::TestResult;
+
+            $(
+                #[tokio::test]
+                async fn $test() -> TestResult {
+                    let stores = super::$backend().await?;
+                    let name = concat!("conformance_", stringify!($backend), "_", stringify!($test));
+                    super::$test(stores, name).await
+                }
+            )*
+        }
+    };
+}
+
+conformance!(
+    backends: [memory, sqlite, surreal],
+    tests: [
+        users,
+        tags,
+        cards,
+        decks,
+        create_cards,
+        patch_content,
+        top_ranked_cards,
+        sibling_cards,
+        card_data,
+        annotations,
+        top_ranked_respects_daily_limit,
+        top_ranked_card_groups,
+        progressive_card_groups,
+        answers,
+        bindings,
+        global_settings,
+        clear_data
+    ]
+);
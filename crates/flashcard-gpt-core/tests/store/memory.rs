@@ -0,0 +1,113 @@
+use flashcard_gpt_core::error::CoreError;
+use flashcard_gpt_core::model::card::{Card, CreateCard, UpdateCard};
+use flashcard_gpt_core::model::deck_card::CreateDeckCard;
+use flashcard_gpt_core::store::memory::{MemoryCardRepo, MemoryDb, MemoryDeckRepo};
+use flashcard_gpt_core::store::siblings::CardSiblingsExt;
+use flashcard_gpt_core::store::{CardStore, DeckStore};
+use flashcard_gpt_tests::db::memory::{card, create_deck, create_user};
+use std::sync::Arc;
+use surrealdb::sql::Thing;
+use testresult::TestResult;
+
+#[tokio::test]
+async fn test_references_must_exist() -> TestResult {
+    let db = MemoryDb::new();
+    let user = create_user(&db, "references").await?;
+    let cards = MemoryCardRepo::new(db.clone());
+    let decks = MemoryDeckRepo::new(db.clone());
+
+    let mut orphan = card(&user, "orphan", Some("back"));
+    orphan.user = Thing::from(("user", "missing"));
+    assert!(cards.create(orphan).await.is_err());
+
+    let deck = create_deck()
+        .db(&db)
+        .user(&user)
+        .title("deck")
+        .call()
+        .await?;
+    let card = cards.create(card(&user, "card", Some("back"))).await?;
+    let relation = CreateDeckCard {
+        deck: deck.id.clone(),
+        card: card.id.clone(),
+    };
+    decks.relate_card(relation).await?;
+
+    let duplicate = CreateDeckCard {
+        deck: deck.id.clone(),
+        card: card.id.clone(),
+    };
+    assert!(decks.relate_card(duplicate).await.is_err());
+    assert_eq!(decks.list_cards(&user, &deck).await?.len(), 1);
+
+    Ok(())
+}
+
+/// Cards of the memory store with writes that fail on demand.
+struct FailingCards {
+    cards: MemoryCardRepo,
+    /// Fails to create reversed siblings.
+    reversed: bool,
+    /// Fails to patch cards.
+    patch: bool,
+}
+
+impl FailingCards {
+    fn error() -> CoreError {
+        CoreError::DbQueryHasErrors(Arc::from("failing write"))
+    }
+}
+
+impl CardStore for FailingCards {
+    async fn create(&self, dto: CreateCard) -> Result<Card, CoreError> {
+        if self.reversed && dto.sibling.is_some() {
+            return Err(Self::error());
+        }
+        self.cards.create(dto).await
+    }
+
+    async fn get_by_id(&self, id: impl Into<Thing> + Send) -> Result<Card, CoreError> {
+        self.cards.get_by_id(id).await
+    }
+
+    async fn patch(
+        &self,
+        id: impl Into<Thing> + Send,
+        update: UpdateCard,
+    ) -> Result<Card, CoreError> {
+        if self.patch {
+            return Err(Self::error());
+        }
+        self.cards.patch(id, update).await
+    }
+
+    async fn list_by_user_id(&self, id: impl Into<Thing> + Send) -> Result<Vec<Card>, CoreError> {
+        self.cards.list_by_user_id(id).await
+    }
+
+    async fn delete(&self, id: impl Into<Thing> + Send) -> Result<(), CoreError> {
+        self.cards.delete(id).await
+    }
+}
+
+#[tokio::test]
+async fn test_half_created_siblings_are_deleted() -> TestResult {
+    let db = MemoryDb::new();
+    let user = create_user(&db, "siblings").await?;
+    let dto = CreateCard {
+        reverse: true,
+        ..card(&user, "word", Some("back"))
+    };
+
+    for (reversed, patch) in [(true, false), (false, true)] {
+        let cards = FailingCards {
+            cards: MemoryCardRepo::new(db.clone()),
+            reversed,
+            patch,
+        };
+        assert!(cards.create_with_sibling(dto.clone()).await.is_err());
+        assert!(cards.list_by_user_id(&user).await?.is_empty());
+    }
+
+    Ok(())
+}
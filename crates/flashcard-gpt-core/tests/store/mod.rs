@@ -0,0 +1,3 @@
+mod conformance;
+mod memory;
+mod sqlite;
@@ -0,0 +1,133 @@
+use flashcard_gpt_core::model::card::CreateCard;
+use flashcard_gpt_core::model::card_data::CardData;
+use flashcard_gpt_core::model::user::RegisterUser;
+use flashcard_gpt_core::store::sqlite::{
+    apply_migrations, SqliteCardRepo, SqliteDb, SqliteUserRepo,
+};
+use flashcard_gpt_core::store::{CardStore, UserStore};
+use rusqlite::Connection;
+use serde_json::json;
+use std::sync::Arc;
+use surrealdb::sql::{Id, Thing};
+use testresult::TestResult;
+
+fn register(name: &str) -> RegisterUser {
+    RegisterUser {
+        email: format!("{name}@example.com").into(),
+        name: Arc::from(name),
+        password: Arc::from(name),
+    }
+}
+
+#[tokio::test]
+async fn test_references_must_exist() -> TestResult {
+    let db = SqliteDb::in_memory()?;
+    let cards = SqliteCardRepo::new(db);
+
+    let orphan = CreateCard {
+        user: Thing::from(("user", Id::Number(42))),
+        title: Arc::from("orphan"),
+        front: None,
+        back: None,
+        hints: vec![],
+        difficulty: 0,
+        importance: 0,
+        data: None,
+        choices: None,
+        sibling: None,
+        reverse: false,
+        tags: vec![],
+    };
+    assert!(cards.create(orphan).await.is_err());
+
+    Ok(())
+}
+
+#[tokio::test]
+async fn test_reopen_keeps_data() -> TestResult {
+    let path =
+        std::env::temp_dir().join(format!("flashcard-gpt-test-{}.sqlite3", std::process::id()));
+    let _ = std::fs::remove_file(&path);
+
+    let user = SqliteUserRepo::new(SqliteDb::open(&path)?)
+        .create_user(register("reopen"))
+        .await?;
+
+    // migrations that have been applied already are skipped
+    let users = SqliteUserRepo::new(SqliteDb::open(&path)?);
+    assert_eq!(users.get_by_id(&user).await?.email, user.email);
+
+    std::fs::remove_file(&path)?;
+
+    Ok(())
+}
+
+/// The migrations that ran before `data` got its fields.
+static BEFORE_TYPED_CARD_DATA: [(&str, &str); 4] = [
+    (
+        "20241019_000000_Initial",
+        include_str!("../../sqlite-migrations/20241019_000000_Initial.sql"),
+    ),
+    (
+        "20241020_000000_MultipleChoice",
+        include_str!("../../sqlite-migrations/20241020_000000_MultipleChoice.sql"),
+    ),
+    (
+        "20241021_000000_ReversedCards",
+        include_str!("../../sqlite-migrations/20241021_000000_ReversedCards.sql"),
+    ),
+    (
+        "20241022_000000_ProgressiveCardGroups",
+        include_str!("../../sqlite-migrations/20241022_000000_ProgressiveCardGroups.sql"),
+    ),
+];
+
+#[test]
+fn test_typed_card_data_keeps_unknown_keys() -> TestResult {
+    let mut conn = Connection::open_in_memory()?;
+    conn.execute_batch(
+        "create table script_migration (
+            script_name text primary key,
+            executed_at text not null
+        )",
+    )?;
+    for (name, script) in BEFORE_TYPED_CARD_DATA {
+        conn.execute_batch(script)?;
+        conn.execute(
+            "insert into script_migration (script_name, executed_at) values (?1, '')",
+            [name],
+        )?;
+    }
+
+    let legacy = json!({
+        "leetcode_link": "leetcode.com/problems/largest-number",
+        "article": "article",
+        "difficulty_notes": "hard"
+    });
+    conn.execute_batch(
+        "insert into user (id, email, name, password, created_at, updated_at)
+        values (1, 'alice@example.com', 'alice', 'alice', '', '')",
+    )?;
+    conn.execute(
+        "insert into card (id, user, title, data, difficulty, importance, created_at, updated_at)
+        values (1, 1, 'legacy', ?1, 0, 0, '', '')",
+        [legacy.to_string()],
+    )?;
+
+    apply_migrations(&mut conn)?;
+
+    let data: String =
+        conn.query_row("select data from card where id = 1", [], |row| row.get(0))?;
+    let data: CardData = serde_json::from_str(&data)?;
+    assert_eq!(data.source_link, None);
+    assert_eq!(data.article.as_deref(), Some("article"));
+    assert_eq!(
+        serde_json::Value::Object(data.extra),
+        json!({
+            "leetcode_link": "leetcode.com/problems/largest-number",
+            "difficulty_notes": "hard"
+        })
+    );
+
+    Ok(())
+}
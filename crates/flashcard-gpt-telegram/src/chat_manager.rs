@@ -2,25 +2,36 @@ use crate::command::answer::AnswerCommand;
 use crate::command::ext::CommandExt;
 use crate::db::repositories::Repositories;
 use crate::ext::binding::ChatIdExt;
-use crate::ext::card::ExtractValueExt;
-use crate::ext::json_value::ValueExt;
 use crate::ext::markdown::MarkdownFormatter;
 use crate::ext::menu_repr::IteratorMenuReprExt;
 use crate::message_render::RenderMessageTextHelper;
+use crate::render;
 use crate::state::bot_state::{BotState, FlashGptDialogue};
+use crate::state::pending_poll::{PendingPoll, PendingPolls, PollTarget};
 use crate::state::state_description::StateDescription;
 use crate::state::state_fields::StateFields;
 use anyhow::bail;
 use chrono::{TimeDelta, Utc};
+use flashcard_gpt_core::blob::BlobStore;
+use flashcard_gpt_core::model::annotation::Annotations;
 use flashcard_gpt_core::model::binding::Binding;
 use flashcard_gpt_core::model::card::{Card, UpdateCard};
+use flashcard_gpt_core::model::card_data::Attachment;
 use flashcard_gpt_core::model::card_group::{CardGroup, UpdateCardGroup};
+use flashcard_gpt_core::model::cloze;
 use flashcard_gpt_core::model::history::CreateHistory;
+use flashcard_gpt_core::model::media::{blob_key, CardSide, Media, MediaKind};
+use flashcard_gpt_core::model::multiple_choice::MultipleChoice;
+use flashcard_gpt_core::model::progress;
 use flashcard_gpt_core::model::tag::Tag;
 use flashcard_gpt_core::model::user::User;
 use flashcard_gpt_core::llm::card_generator_service::CardGeneratorService;
 use flashcard_gpt_core::reexports::db::sql::{Duration, Thing};
+use flashcard_gpt_core::store::any::AnyStorage;
+use flashcard_gpt_core::store::siblings::CardSiblingsExt;
+use flashcard_gpt_core::store::{CardGroupStore, DeckStore, HistoryStore, Storage};
 use itertools::Itertools;
+use rand::seq::SliceRandom;
 use rand::Rng;
 use std::fmt::Debug;
 use std::ops::Sub;
@@ -28,10 +39,12 @@ use std::str::pattern::Pattern;
 use std::str::FromStr;
 use std::sync::Arc;
 use teloxide::adaptors::DefaultParseMode;
-use teloxide::payloads::SendMessageSetters;
+use teloxide::net::Download;
+use teloxide::payloads::{SendMessageSetters, SendPhotoSetters, SendPollSetters};
 use teloxide::prelude::{Message, Requester};
-use teloxide::types::{InlineKeyboardButton, InlineKeyboardMarkup};
+use teloxide::types::{FileMeta, InlineKeyboardButton, InlineKeyboardMarkup, InputFile, PollType};
 use teloxide::utils::command::BotCommands;
+use teloxide::utils::html;
 use teloxide::Bot;
 use tracing::{debug, warn, Span};
 
@@ -39,19 +52,26 @@ static DIGITS: [&str; 11] = [
     "0️⃣", "1️⃣", "2️⃣", "3️⃣", "4️⃣", "5️⃣", "6️⃣", "7️⃣", "8️⃣", "9️⃣", "🔟",
 ];
 
+const MAX_POLL_QUESTION_LEN: usize = 300;
+const MAX_POLL_OPTION_LEN: usize = 100;
+/// Code blocks and math beyond this are left as text.
+const MAX_RENDERED_IMAGES: usize = 10;
+
 #[derive(Debug, Clone)]
-pub struct ChatManager {
-    pub repo: Repositories,
-    pub generator: CardGeneratorService,
+pub struct ChatManager<S: Storage = AnyStorage> {
+    pub repo: Repositories<S>,
+    pub generator: CardGeneratorService<S>,
     pub formatter: MarkdownFormatter,
     pub binding: Arc<Binding>,
     pub bot: DefaultParseMode<Bot>,
     pub dialogue: FlashGptDialogue,
     pub message: Option<Arc<Message>>,
+    pub polls: PendingPolls,
+    pub blobs: Option<BlobStore>,
     pub span: Span,
 }
 
-impl ChatManager {
+impl<S: Storage> ChatManager<S> {
     pub async fn update_state(&self, next_state: BotState) -> anyhow::Result<StateDescription> {
         let desc = next_state.get_state_description(self.message.as_deref());
         self.dialogue.update(next_state).await?;
@@ -232,14 +252,33 @@ impl ChatManager {
         self.send_customized_menu::<T>(|kb| kb).await
     }
 
+    /// The difficulty suggested by grading a typed answer is marked, pressing it confirms it. A
+    /// multiple-choice card is answered by voting on its poll, it gets no difficulties.
     pub async fn send_answer_menu(&self) -> anyhow::Result<()> {
+        let suggested = match self.get_state().await? {
+            BotState::Answering(StateFields::Answer { difficulty, .. }) => difficulty,
+            _ => None,
+        };
+        let poll = self.get_answering_card().await?.is_some_and(|(card, _)| {
+            card.choices
+                .as_ref()
+                .is_some_and(|choices| choices.is_valid())
+        });
+        if poll {
+            return self.send_menu::<AnswerCommand>().await;
+        }
+
+        let button = |difficulty: u8| {
+            let text = if suggested == Some(difficulty) {
+                format!("✅ {difficulty}")
+            } else {
+                difficulty.to_string()
+            };
+            InlineKeyboardButton::callback(text, difficulty.to_string())
+        };
         self.send_customized_menu::<AnswerCommand>(|kb| {
-            let range_buttons_top = (0..5).map(|difficulty| {
-                InlineKeyboardButton::callback(difficulty.to_string(), difficulty.to_string())
-            });
-            let range_buttons_down = (5..11).map(|difficulty| {
-                InlineKeyboardButton::callback(difficulty.to_string(), difficulty.to_string())
-            });
+            let range_buttons_top = (0..5).map(button);
+            let range_buttons_down = (5..11).map(button);
             kb.append_row(range_buttons_top)
                 .append_row(range_buttons_down)
         })
@@ -287,7 +326,11 @@ impl ChatManager {
 
     pub async fn send_card_group(&self, cg: &CardGroup) -> anyhow::Result<()> {
         let title = format!("<b>{}</b>", self.formatter.to_html(cg.title.as_ref())?);
-        let title = if let Some(link) = cg.extract_str("leetcode_link") {
+        let link = cg
+            .data
+            .as_ref()
+            .and_then(|data| data.source_link.as_deref());
+        let title = if let Some(link) = link {
             format!(r#"<a href="{}">{title}</a>"#, link)
         } else {
             title
@@ -301,7 +344,8 @@ impl ChatManager {
             DIGITS[cg.importance as usize % 11],
         );
 
-        let message = format!("[front] {title}\n\n{stats}\n\n{tags}");
+        let annotations = render_annotations(&cg.annotations);
+        let message = format!("[front] {title}\n\n{stats}{annotations}\n\n{tags}");
         self.send_message(message).await?;
 
         Ok(())
@@ -309,14 +353,25 @@ impl ChatManager {
 
     pub async fn send_card(&self, card: &Card) -> anyhow::Result<()> {
         let title = format!("<b>{}</b>", self.formatter.to_html(card.title.as_ref())?);
-        let title = if let Some(link) = card.extract_str("leetcode_link") {
+        let link = card
+            .data
+            .as_ref()
+            .and_then(|data| data.source_link.as_deref());
+        let title = if let Some(link) = link {
             format!(r#"<a href="{}">{title}</a>"#, link)
         } else {
             title
         };
 
         let front = if let Some(front) = card.front.as_ref() {
-            self.formatter.to_html(front.as_ref())?
+            let front = self.formatter.to_html(front.as_ref())?;
+            match card.cloze_index() {
+                Some(index) => cloze::render(&front, index, |deletion| match deletion.hint {
+                    Some(hint) => format!("[{hint}] <tg-spoiler>{}</tg-spoiler>", deletion.answer),
+                    None => format!("<tg-spoiler>{}</tg-spoiler>", deletion.answer),
+                }),
+                None => front,
+            }
         } else {
             String::new()
         };
@@ -340,7 +395,19 @@ impl ChatManager {
             DIGITS[card.importance as usize % 11],
         );
 
-        let front_message = format!("[front] {title}\n\n{stats}\n\n{front}\n\n{tags}");
+        let choices = card.choices.as_ref().filter(|choices| choices.is_valid());
+        let typed = card.typed_answer().is_some();
+        let front = if typed {
+            format!("{front}\n\n<i>Reply with your answer</i>")
+        } else {
+            front
+        };
+        let annotations = render_annotations(&card.annotations);
+        let front_message = if choices.is_some() {
+            format!("[front] {title}\n\n{stats}{annotations}\n\n{tags}")
+        } else {
+            format!("[front] {title}\n\n{stats}{annotations}\n\n{front}\n\n{tags}")
+        };
         let hint_messages = hints
             .iter()
             .enumerate()
@@ -353,13 +420,240 @@ impl ChatManager {
             .join("\n");
 
         self.send_message(front_message).await?;
+        self.send_media(card, CardSide::Front).await?;
+        if card.cloze_index().is_none() {
+            self.send_rendered(card.front.as_deref(), CardSide::Front)
+                .await?;
+        }
         if !hints.is_empty() {
             self.send_message(hint_messages).await?;
         } else {
             warn!(?card, "No hint message");
         }
-        if let Some(back) = back {
-            self.send_message(back).await?;
+        if let Some(choices) = choices {
+            let question = card.front.as_deref().unwrap_or(card.title.as_ref());
+            self.send_poll(question, choices).await?;
+        } else if !typed {
+            if let Some(back) = back {
+                self.send_message(back).await?;
+            }
+            self.send_media(card, CardSide::Back).await?;
+            self.send_rendered(card.back.as_deref(), CardSide::Back)
+                .await?;
+        }
+
+        Ok(())
+    }
+
+    /// Sends the code blocks and math of `text` as images if the user prefers them. The images of
+    /// the back are hidden behind a spoiler, failed renders are only logged.
+    async fn send_rendered(&self, text: Option<&str>, side: CardSide) -> anyhow::Result<()> {
+        let (Some(text), Some(cache)) = (text, self.blobs.as_ref()) else {
+            return Ok(());
+        };
+        if !render::ENABLED {
+            return Ok(());
+        }
+        let renderables = self.formatter.renderables(text)?;
+        if renderables.is_empty() {
+            return Ok(());
+        }
+        let settings = self
+            .repo
+            .get_global_settings_or_default(self.get_user_id().clone())
+            .await?;
+        if !settings.render_images {
+            return Ok(());
+        }
+
+        for renderable in renderables.iter().take(MAX_RENDERED_IMAGES) {
+            match render::render(cache, renderable).await {
+                Ok(path) => {
+                    self.bot
+                        .send_photo(self.dialogue.chat_id(), InputFile::file(path))
+                        .has_spoiler(side == CardSide::Back)
+                        .await?;
+                }
+                Err(err) => warn!(?err, ?renderable, "Failed to render"),
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Sends the media of a side by their `file_id`, or from the local copy when Telegram does not
+    /// know the id. Photos of the back are hidden behind a spoiler.
+    async fn send_media(&self, card: &Card, side: CardSide) -> anyhow::Result<()> {
+        let Some(data) = card.data.as_ref() else {
+            return Ok(());
+        };
+        for media in data.media(side) {
+            // imported media only have their local copy
+            if media.file_id.trim().is_empty() {
+                let (Some(blobs), Some(blob)) = (self.blobs.as_ref(), media.blob.as_deref()) else {
+                    warn!(
+                        ?media,
+                        "Media without a file id and a blob store, skipping it"
+                    );
+                    continue;
+                };
+                self.send_media_file(media, InputFile::file(blobs.path(blob)?))
+                    .await?;
+                continue;
+            }
+            let file = InputFile::file_id(media.file_id.to_string().into());
+            if let Err(err) = self.send_media_file(media, file).await {
+                let (Some(blobs), Some(blob)) = (self.blobs.as_ref(), media.blob.as_deref()) else {
+                    return Err(err);
+                };
+                warn!(
+                    ?err,
+                    ?media,
+                    "Failed to send media by file id, sending its copy"
+                );
+                let file = InputFile::file(blobs.path(blob)?);
+                self.send_media_file(media, file).await?;
+            }
+        }
+        Ok(())
+    }
+
+    async fn send_media_file(&self, media: &Media, file: InputFile) -> anyhow::Result<()> {
+        let chat_id = self.dialogue.chat_id();
+        match media.kind {
+            MediaKind::Photo => {
+                self.bot
+                    .send_photo(chat_id, file)
+                    .has_spoiler(media.side == CardSide::Back)
+                    .await?;
+            }
+            MediaKind::Document => {
+                self.bot.send_document(chat_id, file).await?;
+            }
+        }
+        Ok(())
+    }
+
+    /// The photo or the document of the current message as media of `side`, with a local copy
+    /// when a [`BlobStore`] is configured.
+    pub async fn receive_media(&self, side: CardSide) -> anyhow::Result<Option<Media>> {
+        let Some(message) = self.message.as_deref() else {
+            return Ok(None);
+        };
+        let largest_photo = message
+            .photo()
+            .and_then(|sizes| sizes.iter().max_by_key(|size| size.width));
+        let (kind, file, file_name, mime_type) = if let Some(photo) = largest_photo {
+            (MediaKind::Photo, &photo.file, None, None)
+        } else if let Some(document) = message.document() {
+            let file_name = document.file_name.as_deref().map(Arc::from);
+            let mime_type = document
+                .mime_type
+                .as_ref()
+                .map(|mime| Arc::from(mime.to_string()));
+            (MediaKind::Document, &document.file, file_name, mime_type)
+        } else {
+            return Ok(None);
+        };
+
+        let blob = if let Some(blobs) = self.blobs.as_ref() {
+            let name = match kind {
+                MediaKind::Photo => Some("photo.jpg"),
+                MediaKind::Document => file_name.as_deref(),
+            };
+            let key = blob_key(&file.unique_id.to_string(), name);
+            let bytes = self.download_file(file).await?;
+            blobs.put(&key, &bytes).await?;
+            Some(key)
+        } else {
+            None
+        };
+
+        Ok(Some(Media {
+            kind,
+            side,
+            file_id: Arc::from(file.id.to_string()),
+            file_name,
+            mime_type,
+            blob,
+        }))
+    }
+
+    pub async fn download_file(&self, file: &FileMeta) -> anyhow::Result<Vec<u8>> {
+        let remote = self.bot.get_file(file.id.clone()).await?;
+        let mut bytes = Vec::new();
+        self.bot
+            .inner()
+            .download_file(&remote.path, &mut bytes)
+            .await?;
+        Ok(bytes)
+    }
+
+    /// Sends `bytes` as a document named `file_name`.
+    pub async fn send_file(&self, file_name: &str, bytes: Vec<u8>) -> anyhow::Result<()> {
+        let file = InputFile::memory(bytes).file_name(file_name.to_owned());
+        self.bot
+            .send_document(self.dialogue.chat_id(), file)
+            .await?;
+        Ok(())
+    }
+
+    /// Sends the options in random order as a quiz, or as a poll with multiple answers if more
+    /// than one option is correct. Votes on polls of the deck card being answered are committed
+    /// as its answer, and votes on a card of a deck card group as a step of the group.
+    async fn send_poll(&self, question: &str, choices: &MultipleChoice) -> anyhow::Result<()> {
+        let mut order = (0..choices.options.len()).collect_vec();
+        order.shuffle(&mut rand::thread_rng());
+        let options = order
+            .iter()
+            .map(|&index| truncate(&choices.options[index], MAX_POLL_OPTION_LEN))
+            .collect_vec();
+
+        let request = self
+            .bot
+            .send_poll(
+                self.dialogue.chat_id(),
+                truncate(question, MAX_POLL_QUESTION_LEN),
+                options,
+            )
+            .is_anonymous(false);
+        let correct = order
+            .iter()
+            .position(|index| choices.correct.contains(index));
+        let message = match correct {
+            Some(correct) if choices.is_quiz() => {
+                request
+                    .type_(PollType::Quiz)
+                    .correct_option_id(correct as u8)
+                    .await?
+            }
+            _ => request.allows_multiple_answers(true).await?,
+        };
+
+        let fields = self.get_state().await?.into_fields();
+        let target = if let Some(Some(id)) = fields.deck_card_group_id()
+            && let Some(Some(seq)) = fields.deck_card_group_card_seq()
+        {
+            Some(PollTarget::DeckCardGroup {
+                id: id.clone(),
+                seq: *seq,
+            })
+        } else if let Some(Some(id)) = fields.deck_card_id() {
+            Some(PollTarget::DeckCard(id.clone()))
+        } else {
+            None
+        };
+        if let Some(poll) = message.poll()
+            && let Some(target) = target
+        {
+            let pending = PendingPoll {
+                binding: self.binding.clone(),
+                target,
+                choices: choices.clone(),
+                order,
+                sent_at: Utc::now(),
+            };
+            self.polls.insert(poll.id.clone(), pending).await;
         }
 
         Ok(())
@@ -396,6 +690,7 @@ impl ChatManager {
                     difficulty,
                     time: None,
                     hide_for,
+                    steps: fields.steps().cloned().unwrap_or_default(),
                 })
                 .await?;
             return Ok(());
@@ -411,6 +706,7 @@ impl ChatManager {
                     difficulty,
                     time: None,
                     hide_for,
+                    steps: vec![],
                 })
                 .await?;
             return Ok(());
@@ -419,6 +715,29 @@ impl ChatManager {
         bail!("No active deck card or deck card group in the state");
     }
 
+    /// The card being answered: the deck card, or the current card of the deck card group
+    /// together with the group.
+    pub async fn get_answering_card(
+        &self,
+    ) -> anyhow::Result<Option<(Arc<Card>, Option<Arc<CardGroup>>)>> {
+        let fields = self.get_state().await?.into_fields();
+        if let Some(Some(dc_id)) = fields.deck_card_id() {
+            let deck_card = self.repo.decks.get_deck_card(dc_id.clone()).await?;
+            return Ok(Some((deck_card.card, None)));
+        }
+
+        if let Some(Some(dcg_id)) = fields.deck_card_group_id()
+            && let Some(Some(seq)) = fields.deck_card_group_card_seq()
+        {
+            let deck_card_group = self.repo.decks.get_deck_card_group(dcg_id.clone()).await?;
+            let card_group = deck_card_group.card_group;
+            let card = card_group.cards.get(*seq).cloned();
+            return Ok(card.map(|card| (card, Some(card_group))));
+        }
+
+        Ok(None)
+    }
+
     pub fn get_user(&self) -> &User {
         self.binding.user.as_ref()
     }
@@ -426,9 +745,28 @@ impl ChatManager {
     pub fn get_user_id(&self) -> &Thing {
         &self.get_user().id
     }
+
+    /// The card generator with the LLM provider and model the user chose, see
+    /// [`CardGeneratorService::for_user`].
+    pub async fn user_generator(&self) -> anyhow::Result<CardGeneratorService<S>> {
+        let settings = self
+            .repo
+            .get_global_settings_or_default(self.get_user_id().clone())
+            .await?;
+        Ok(self.generator.for_user(settings.llm))
+    }
+
+    /// Whether the user generates multiple-choice cards, off unless they turn it on.
+    pub async fn multiple_choice(&self) -> anyhow::Result<bool> {
+        let settings = self
+            .repo
+            .get_global_settings_or_default(self.get_user_id().clone())
+            .await?;
+        Ok(settings.multiple_choice)
+    }
 }
 
-impl ChatManager {
+impl<S: Storage> ChatManager<S> {
     pub async fn answer_with_card(&self) -> anyhow::Result<bool> {
         let user = self.get_user();
         let chat_id = self.binding.get_chat_id()?;
@@ -450,6 +788,7 @@ impl ChatManager {
         self.update_state(BotState::Answering(StateFields::Answer {
             deck_card_group_id: None,
             deck_card_group_card_seq: None,
+            steps: vec![],
             deck_card_id: Some(dc.id),
             difficulty: None,
         }))
@@ -477,26 +816,41 @@ impl ChatManager {
         }
         let card_id = rand::thread_rng().gen_range(0..dcgs.len());
         let dcg = dcgs.swap_remove(card_id);
+
+        // a session that stopped at a wrong card is resumed from it
+        let cards = &dcg.card_group.cards;
+        let last_steps = self
+            .repo
+            .history
+            .get_last_answer(dcg.id.clone())
+            .await?
+            .map(|last| last.steps)
+            .unwrap_or_default();
+        let seq = progress::resume_seq(&last_steps, cards.len());
+        let Some(card) = cards.get(seq).cloned() else {
+            warn!(?dcg, "Card group without cards");
+            return Ok(false);
+        };
+
         self.update_state(BotState::Answering(StateFields::Answer {
-            deck_card_group_id: Some(dcg.id),
-            deck_card_group_card_seq: Some(0),
+            deck_card_group_id: Some(dcg.id.clone()),
+            deck_card_group_card_seq: Some(seq),
+            steps: last_steps[..seq].to_vec(),
             deck_card_id: None,
             difficulty: None,
         }))
         .await?;
         self.send_card_group(dcg.card_group.as_ref()).await?;
-        self.send_card(dcg.card_group.cards[0].as_ref()).await?;
+        if seq > 0 {
+            self.send_message(format!("Resuming from card {} of {}", seq + 1, cards.len()))
+                .await?;
+        }
+        self.send_card(card.as_ref()).await?;
 
         Ok(true)
     }
 
-    pub async fn send_card_group_data_by_key(
-        &self,
-        id: &Thing,
-        key: impl AsRef<str>,
-    ) -> anyhow::Result<()> {
-        let key = key.as_ref();
-
+    pub async fn send_attachment(&self, id: &Thing, attachment: Attachment) -> anyhow::Result<()> {
         let data = match id.tb.as_str() {
             "deck_card_group" => self
                 .repo
@@ -526,14 +880,9 @@ impl ChatManager {
             return Ok(());
         };
 
-        let Some(value) = data.get_value_by(key) else {
-            self.send_message(format!("Card group data does not contain key {key}"))
-                .await?;
-            return Ok(());
-        };
-
-        let Some(value) = value.as_str() else {
-            self.send_message("Cannot represent the given data value as string")
+        let Some(value) = data.attachment(attachment) else {
+            let name = attachment.name();
+            self.send_message(format!("Card group data does not contain the {name}"))
                 .await?;
             return Ok(());
         };
@@ -551,7 +900,7 @@ impl ChatManager {
         let cg_id = self
             .repo
             .decks
-            .get_deck_card_group(dcg_id)
+            .get_deck_card_group(dcg_id.into())
             .await?
             .card_group
             .id
@@ -569,8 +918,49 @@ impl ChatManager {
         dc_id: impl Into<Thing>,
         update_card: UpdateCard,
     ) -> anyhow::Result<Card> {
-        let card_id = self.repo.decks.get_deck_card(dc_id).await?.card.id.clone();
-        let cg = self.repo.cards.patch(card_id, update_card).await?;
+        let card_id = self.repo.decks.get_deck_card(dc_id.into()).await?.card.id.clone();
+        let cg = self
+            .repo
+            .cards
+            .patch_with_sibling(card_id, update_card)
+            .await?;
         Ok(cg)
     }
+
+    /// Deletes the card of the deck card and its reversed sibling, returns the deleted card.
+    pub async fn delete_deck_card_inner(&self, dc_id: impl Into<Thing>) -> anyhow::Result<Card> {
+        let card = self.repo.decks.get_deck_card(dc_id.into()).await?.card;
+        self.repo.cards.delete_with_sibling(card.id.clone()).await?;
+        Ok(card)
+    }
+}
+
+/// The flags and the notes of a card or card group, prefixed with a blank line, or nothing.
+fn render_annotations(annotations: &Annotations) -> String {
+    if annotations.is_empty() {
+        return String::new();
+    }
+
+    let mut text = String::from("\n");
+    if !annotations.flags.is_empty() {
+        let flags = annotations
+            .flags
+            .iter()
+            .map(|flag| format!("{} {}", flag.icon(), flag.name()))
+            .join(" ");
+        text.push_str(&format!("\n{flags}"));
+    }
+    for note in &annotations.notes {
+        text.push_str(&format!("\n📝 <i>{}</i>", html::escape(note)));
+    }
+    text
+}
+
+fn truncate(text: &str, max_chars: usize) -> String {
+    if text.chars().count() <= max_chars {
+        return text.to_string();
+    }
+    let mut truncated = text.chars().take(max_chars - 1).collect::<String>();
+    truncated.push('…');
+    truncated
 }
@@ -25,6 +25,18 @@ pub enum AnswerCommand {
     /// Set importance for this card / card group
     Importance(u8),
 
+    /// Reorder the cards of this card group (use /order 3 1 2)
+    Order(String),
+
+    /// Add a personal note to this card / card group (use /note I confuse this with X)
+    Note(String),
+
+    /// Toggle a flag of this card / card group: bookmark, needs_fix or favorite
+    Flag(String),
+
+    /// Delete this card, and its reversed sibling
+    Delete,
+
     /// Cancel answering
     Cancel,
 }
@@ -35,6 +47,10 @@ impl CommandExt for AnswerCommand {
             .filter(|cmd| !matches!(cmd, AnswerCommand::Hide(_)))
             .filter(|cmd| !matches!(cmd, AnswerCommand::Difficulty(_)))
             .filter(|cmd| !matches!(cmd, AnswerCommand::Importance(_)))
+            .filter(|cmd| !matches!(cmd, AnswerCommand::Order(_)))
+            .filter(|cmd| !matches!(cmd, AnswerCommand::Note(_)))
+            .filter(|cmd| !matches!(cmd, AnswerCommand::Flag(_)))
+            .filter(|cmd| !matches!(cmd, AnswerCommand::Delete))
             .map(|cmd| InlineKeyboardButton::callback(cmd.as_ref(), cmd.as_ref()))
     }
 
@@ -46,6 +62,7 @@ impl CommandExt for AnswerCommand {
         BotState::Answering(StateFields::Answer {
             deck_card_group_id: None,
             deck_card_group_card_seq: None,
+            steps: vec![],
             deck_card_id: None,
             difficulty: None,
         })
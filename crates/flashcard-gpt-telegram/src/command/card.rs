@@ -18,6 +18,9 @@ pub enum CardCommand {
     /// Generate cards using ChatGPT and add them to the deck
     Generate,
 
+    /// Show bookmarked, favorite and to-be-fixed cards
+    Flagged,
+
     /// Continue to the next state
     Next,
 
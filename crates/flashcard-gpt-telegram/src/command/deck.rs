@@ -15,6 +15,12 @@ pub enum DeckCommand {
     /// Create a new deck
     Create,
 
+    /// Import an Anki package (.apkg or .colpkg)
+    Import,
+
+    /// Export all decks to an Anki package (.apkg)
+    Export,
+
     /// Continue to the next state
     Next,
 
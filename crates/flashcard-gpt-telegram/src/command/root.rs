@@ -25,6 +25,10 @@ pub enum RootCommand {
     Tag,
     /// Edit card groups
     CardGroup,
+    /// Show answer statistics
+    Stats,
+    /// Toggle sending code and math as images
+    Images,
 }
 
 impl CommandExt for RootCommand {
@@ -56,6 +60,8 @@ impl CommandExt for RootCommand {
             RootCommand::Card => "💳",
             RootCommand::Tag => "📎",
             RootCommand::CardGroup => "📂",
+            RootCommand::Stats => "📊",
+            RootCommand::Images => "🖼",
         }
     }
 }
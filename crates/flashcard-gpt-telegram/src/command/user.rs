@@ -18,6 +18,18 @@ pub enum UserCommand {
     /// Edit password
     Password,
 
+    /// Back up all decks, cards, history and settings to a file
+    Backup,
+
+    /// Restore a backup file
+    Restore,
+
+    /// Choose the LLM provider and model
+    Llm,
+
+    /// Toggle multiple-choice questions in generated cards
+    MultipleChoice,
+
     /// Cancel the current operation
     Cancel,
 }
@@ -1,53 +1,121 @@
 use crate::ext::binding::{BindingEntity, BindingExt};
 use crate::ext::menu_repr::IteratorMenuReprExt;
 use chrono_tz::Tz;
+use flashcard_gpt_core::anki::export::AnkiExporter;
+use flashcard_gpt_core::anki::import::AnkiImporter;
+use flashcard_gpt_core::backup::export::BackupExporter;
+use flashcard_gpt_core::backup::restore::BackupRestorer;
 use flashcard_gpt_core::model::binding::Binding;
 use flashcard_gpt_core::model::global_settings::{CreateGlobalSettings, GlobalSettings};
 use flashcard_gpt_core::error::CoreError;
-use flashcard_gpt_core::reexports::db::engine::remote::ws::Client;
 use flashcard_gpt_core::reexports::db::sql::{Duration, Thing};
-use flashcard_gpt_core::reexports::db::Surreal;
-use flashcard_gpt_core::repo::binding::BindingRepo;
-use flashcard_gpt_core::repo::card::CardRepo;
-use flashcard_gpt_core::repo::card_group::CardGroupRepo;
-use flashcard_gpt_core::repo::deck::DeckRepo;
-use flashcard_gpt_core::repo::global_settings::GlobalSettingsRepo;
-use flashcard_gpt_core::repo::history::HistoryRepo;
-use flashcard_gpt_core::repo::tag::TagRepo;
-use flashcard_gpt_core::repo::user::UserRepo;
+use flashcard_gpt_core::session::UserSessions;
+use flashcard_gpt_core::store::any::{
+    AnyBindingRepo, AnyCardGroupRepo, AnyCardRepo, AnyDb, AnyDeckRepo, AnyGlobalSettingsRepo,
+    AnyHistoryRepo, AnyStorage, AnyTagRepo, AnyUserRepo,
+};
+use flashcard_gpt_core::store::memory::{MemoryDb, MemoryRepo, MemoryStorage};
+use flashcard_gpt_core::store::stats::StatsRepo;
+use flashcard_gpt_core::store::{DeckStore, GlobalSettingsStore, Storage, TagStore};
 use teloxide::types::InlineKeyboardMarkup;
 use tracing::{error, Span};
 
 #[derive(Debug, Clone)]
-pub struct Repositories {
-    pub tags: TagRepo,
-    pub decks: DeckRepo,
-    pub users: UserRepo,
-    pub cards: CardRepo,
-    pub card_groups: CardGroupRepo,
-    pub bindings: BindingRepo,
-    pub global_settings: GlobalSettingsRepo,
-    pub history: HistoryRepo,
+pub struct Repositories<S: Storage = AnyStorage> {
+    pub tags: S::Tags,
+    pub decks: S::Decks,
+    pub users: S::Users,
+    pub cards: S::Cards,
+    pub card_groups: S::CardGroups,
+    pub bindings: S::Bindings,
+    pub global_settings: S::GlobalSettings,
+    pub history: S::History,
 }
 
 impl Repositories {
-    pub fn new(db: Surreal<Client>, span: Span) -> Self {
+    pub fn new(db: AnyDb, span: Span) -> Self {
         Self {
-            tags: TagRepo::new_tag(db.clone(), span.clone(), true),
-            decks: DeckRepo::new_deck(db.clone(), span.clone(), true),
-            users: UserRepo::new_user(db.clone(), span.clone(), true),
-            cards: CardRepo::new_card(db.clone(), span.clone(), true),
-            card_groups: CardGroupRepo::new_card_group(db.clone(), span.clone(), true),
-            bindings: BindingRepo::new_binding(db.clone(), span.clone(), true),
-            global_settings: GlobalSettingsRepo::new_global_settings(
-                db.clone(),
-                span.clone(),
-                true,
-            ),
-            history: HistoryRepo::new_history(db, span, true),
+            tags: AnyTagRepo::new(&db, span.clone()),
+            decks: AnyDeckRepo::new(&db, span.clone()),
+            users: AnyUserRepo::new(&db, span.clone()),
+            cards: AnyCardRepo::new(&db, span.clone()),
+            card_groups: AnyCardGroupRepo::new(&db, span.clone()),
+            bindings: AnyBindingRepo::new(&db, span.clone()),
+            global_settings: AnyGlobalSettingsRepo::new(&db, span.clone()),
+            history: AnyHistoryRepo::new(&db, span),
         }
     }
 
+    /// Repositories that run every query in the database session of `user`.
+    pub async fn for_user(
+        &self,
+        sessions: &UserSessions,
+        user: &Thing,
+        span: Span,
+    ) -> Result<Self, CoreError> {
+        let db = sessions.connect(user).await?;
+        Ok(Self::new(AnyDb::Surreal(db), span))
+    }
+}
+
+impl Repositories<MemoryStorage> {
+    pub fn in_memory(db: MemoryDb) -> Self {
+        Self {
+            tags: MemoryRepo::new(db.clone()),
+            decks: MemoryRepo::new(db.clone()),
+            users: MemoryRepo::new(db.clone()),
+            cards: MemoryRepo::new(db.clone()),
+            card_groups: MemoryRepo::new(db.clone()),
+            bindings: MemoryRepo::new(db.clone()),
+            global_settings: MemoryRepo::new(db.clone()),
+            history: MemoryRepo::new(db),
+        }
+    }
+}
+
+impl<S: Storage> Repositories<S> {
+    pub fn stats(&self) -> StatsRepo<S> {
+        StatsRepo::new(self.history.clone())
+    }
+
+    pub fn anki_importer(&self) -> AnkiImporter<S> {
+        AnkiImporter::new(
+            self.cards.clone(),
+            self.card_groups.clone(),
+            self.decks.clone(),
+            self.tags.clone(),
+            self.history.clone(),
+        )
+    }
+
+    pub fn anki_exporter(&self) -> AnkiExporter<S> {
+        AnkiExporter::new(self.decks.clone())
+    }
+
+    pub fn backup_exporter(&self) -> BackupExporter<S> {
+        BackupExporter::new(
+            self.users.clone(),
+            self.tags.clone(),
+            self.cards.clone(),
+            self.card_groups.clone(),
+            self.decks.clone(),
+            self.history.clone(),
+            self.global_settings.clone(),
+        )
+    }
+
+    pub fn backup_restorer(&self) -> BackupRestorer<S> {
+        BackupRestorer::new(
+            self.users.clone(),
+            self.tags.clone(),
+            self.cards.clone(),
+            self.card_groups.clone(),
+            self.decks.clone(),
+            self.history.clone(),
+            self.global_settings.clone(),
+        )
+    }
+
     pub async fn build_tag_menu(&self, user_id: Thing) -> Result<InlineKeyboardMarkup, CoreError> {
         Ok(self
             .tags
@@ -95,6 +163,9 @@ impl Repositories {
                             // [chrono::Duration::hours(17), chrono::Duration::hours(18)],
                         ],
                         timezone: Tz::Europe__Dublin,
+                        render_images: false,
+                        multiple_choice: false,
+                        llm: None,
                     })
                     .await?
             }
@@ -1,7 +1,7 @@
 use anyhow::anyhow;
 use flashcard_gpt_core::model::binding::{Binding, GetOrCreateBinding};
 use flashcard_gpt_core::error::CoreError;
-use flashcard_gpt_core::repo::binding::BindingRepo;
+use flashcard_gpt_core::store::BindingStore;
 use serde::{Deserialize, Serialize};
 use serde_json::json;
 use std::fmt::Display;
@@ -13,14 +13,14 @@ use teloxide::types::{Chat, User};
 pub trait BindingExt {
     fn get_or_create_telegram_binding(
         &self,
-        entity: impl Into<BindingEntity<'_>>,
-    ) -> impl Future<Output = Result<Binding, CoreError>>;
+        entity: impl Into<BindingEntity<'_>> + Send,
+    ) -> impl Future<Output = Result<Binding, CoreError>> + Send;
 }
 
-impl BindingExt for BindingRepo {
+impl<T: BindingStore> BindingExt for T {
     async fn get_or_create_telegram_binding(
         &self,
-        entity: impl Into<BindingEntity<'_>>,
+        entity: impl Into<BindingEntity<'_>> + Send,
     ) -> Result<Binding, CoreError> {
         let entity = entity.into();
         let source_id = entity.id();
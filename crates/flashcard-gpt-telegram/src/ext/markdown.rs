@@ -1,3 +1,4 @@
+use crate::render::Renderable;
 use anyhow::anyhow;
 use itertools::Itertools;
 use markdown::mdast::Node;
@@ -24,6 +25,37 @@ impl MarkdownFormatter {
         let ast = markdown::to_mdast(text, &opts).map_err(|e| anyhow!("{e:?}"))?;
         Ok(ast.to_tg_html())
     }
+
+    /// The code blocks and math of `text`, in their order.
+    pub fn renderables(&self, text: &str) -> anyhow::Result<Vec<Renderable>> {
+        let opts = self.opts.lock().map_err(|e| anyhow!("{e:?}"))?;
+        let ast = markdown::to_mdast(text, &opts).map_err(|e| anyhow!("{e:?}"))?;
+        let mut renderables = vec![];
+        collect_renderables(&ast, &mut renderables);
+        Ok(renderables)
+    }
+}
+
+fn collect_renderables(node: &Node, renderables: &mut Vec<Renderable>) {
+    match node {
+        Node::Code(code) => renderables.push(Renderable::Code {
+            lang: code.lang.clone(),
+            code: code.value.clone(),
+        }),
+        Node::Math(math) => renderables.push(Renderable::Math {
+            tex: math.value.clone(),
+            display: true,
+        }),
+        Node::InlineMath(math) => renderables.push(Renderable::Math {
+            tex: math.value.clone(),
+            display: false,
+        }),
+        _ => {
+            for child in node.children().into_iter().flatten() {
+                collect_renderables(child, renderables);
+            }
+        }
+    }
 }
 
 pub trait TgHtml {
@@ -374,4 +406,38 @@ Here is a table:
 
         Ok(())
     }
+
+    #[test]
+    fn test_renderables() -> TestResult {
+        let formatter = MarkdownFormatter::new(ParseOptions {
+            constructs: Constructs {
+                math_flow: true,
+                math_text: true,
+                ..Constructs::gfm()
+            },
+            ..ParseOptions::gfm()
+        });
+        let text = "Energy is $E = mc^2$:\n\n> ```rust\n> let e = m * c * c;\n> ```\n\n$$\n\\sum_i x_i\n$$";
+
+        assert_eq!(
+            formatter.renderables(text)?,
+            vec![
+                Renderable::Math {
+                    tex: "E = mc^2".to_string(),
+                    display: false,
+                },
+                Renderable::Code {
+                    lang: Some("rust".to_string()),
+                    code: "let e = m * c * c;".to_string(),
+                },
+                Renderable::Math {
+                    tex: "\\sum_i x_i".to_string(),
+                    display: true,
+                },
+            ]
+        );
+        assert!(formatter.renderables("Plain `code`")?.is_empty());
+
+        Ok(())
+    }
 }
@@ -13,6 +13,7 @@ pub mod llm;
 pub mod macros;
 pub mod message_render;
 mod notifier_task;
+pub mod render;
 pub mod schema;
 pub mod state;
 
@@ -22,15 +23,12 @@ use crate::ext::markdown::MarkdownFormatter;
 use crate::notifier_task::init_notifier;
 use crate::schema::schema;
 use crate::state::bot_state::BotState;
+use crate::state::pending_poll::PendingPolls;
+use flashcard_gpt_core::blob::BlobStore;
 use flashcard_gpt_core::llm::card_generator_service::CardGeneratorService;
 use flashcard_gpt_core::llm::custom_executor::CustomExecutor;
 use flashcard_gpt_core::logging::init_tracing;
-use flashcard_gpt_core::reexports::db::engine::remote::ws::{Client, Ws};
-use flashcard_gpt_core::reexports::db::opt::auth::Root;
-use flashcard_gpt_core::reexports::db::Surreal;
-use llm_chain::options::{ModelRef, Opt, Options};
-use llm_chain::traits::Executor as _;
-use llm_chain_openai::chatgpt::Executor;
+use flashcard_gpt_core::store::any::StorageSettings;
 use markdown::{Constructs, ParseOptions};
 use std::sync::Arc;
 use teloxide::adaptors::DefaultParseMode;
@@ -42,13 +40,7 @@ use tracing::{info, span, warn, Level};
 fn init_card_generator_service(
     repositories: &Repositories,
 ) -> anyhow::Result<CardGeneratorService> {
-    let openai_api_key = std::env::var("OPENAI_API_KEY")?;
-    let mut options = Options::builder();
-    options.add_option(Opt::ApiKey(openai_api_key));
-    options.add_option(Opt::Model(ModelRef::from_model_name("chatgpt-4o-latest")));
-    let options = options.build();
-    let exec = Executor::new_with_options(options)?;
-    let card_generator = CustomExecutor::new(exec);
+    let card_generator = CustomExecutor::from_env()?;
 
     Ok(CardGeneratorService::new(
         card_generator,
@@ -70,15 +62,9 @@ async fn main() -> anyhow::Result<()> {
     init_tracing()?;
     info!("Starting dialogue bot...");
 
-    let db: Surreal<Client> = Surreal::init();
-    db.connect::<Ws>("127.0.0.1:8477").await?;
-    db.signin(Root {
-        username: "root",
-        password: "root",
-    })
-    .await?;
-
-    db.use_ns("flashcards_gpt").use_db("flashcards").await?;
+    let storage_settings = StorageSettings::from_env()?;
+    let db = storage_settings.connect().await?;
+    let sessions = storage_settings.user_sessions();
 
     let repositories = Repositories::new(db.clone(), span!(Level::INFO, "root"));
     let card_generation_service = init_card_generator_service(&repositories)?;
@@ -96,6 +82,8 @@ async fn main() -> anyhow::Result<()> {
     let bot: DefaultParseMode<Bot> = Bot::from_env().parse_mode(ParseMode::Html);
     set_bot_commands(&bot).await;
     let state: Arc<InMemStorage<BotState>> = InMemStorage::<BotState>::new();
+    let polls = PendingPolls::default();
+    let blobs = BlobStore::from_env();
 
     let notifier = init_notifier(
         bot.clone(),
@@ -103,6 +91,9 @@ async fn main() -> anyhow::Result<()> {
         state.clone(),
         formatter.clone(),
         repositories.clone(),
+        sessions.clone(),
+        polls.clone(),
+        blobs.clone(),
         span.clone(),
     );
 
@@ -110,9 +101,12 @@ async fn main() -> anyhow::Result<()> {
         .dependencies(dptree::deps![
             state,
             repositories,
+            sessions,
             span,
             card_generation_service,
-            formatter
+            formatter,
+            polls,
+            blobs
         ])
         .enable_ctrlc_handler()
         .build();
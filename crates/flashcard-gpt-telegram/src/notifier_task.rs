@@ -3,8 +3,12 @@ use crate::db::repositories::Repositories;
 use crate::ext::binding::ChatIdExt;
 use crate::ext::markdown::MarkdownFormatter;
 use crate::state::bot_state::{BotState, FlashGptDialogue};
+use crate::state::pending_poll::PendingPolls;
 use chrono::{Timelike, Utc};
+use flashcard_gpt_core::blob::BlobStore;
 use flashcard_gpt_core::llm::card_generator_service::CardGeneratorService;
+use flashcard_gpt_core::session::UserSessions;
+use flashcard_gpt_core::store::BindingStore;
 use std::sync::Arc;
 use std::time::Duration;
 use teloxide::adaptors::DefaultParseMode;
@@ -19,6 +23,9 @@ pub async fn init_notifier(
     storage: Arc<InMemStorage<BotState>>,
     formatter: MarkdownFormatter,
     repositories: Repositories,
+    sessions: Option<UserSessions>,
+    polls: PendingPolls,
+    blobs: Option<BlobStore>,
     span: Span,
 ) -> anyhow::Result<()> {
     loop {
@@ -42,14 +49,32 @@ pub async fn init_notifier(
             let chat_id = binding.get_chat_id()?;
             let dialogue = FlashGptDialogue::new(storage.clone(), chat_id);
 
+            let repo = match &sessions {
+                Some(sessions) => {
+                    repositories
+                        .for_user(sessions, &user.id, span.clone())
+                        .await
+                }
+                None => Ok(repositories.clone()),
+            };
+            let repo = match repo {
+                Ok(repo) => repo,
+                Err(err) => {
+                    warn!(?err, %user, "Unable to open the user session");
+                    continue;
+                }
+            };
+
             let manager = ChatManager {
-                repo: repositories.clone(),
+                repo,
                 generator,
                 formatter: formatter.clone(),
                 binding: binding.clone(),
                 bot: bot.clone(),
                 dialogue,
                 message: None,
+                polls: polls.clone(),
+                blobs: blobs.clone(),
                 span: span.clone(),
             };
 
@@ -66,7 +91,7 @@ pub async fn init_notifier(
                         err.downcast_ref::<RequestError>()
                     {
                         warn!(%user, "Bot blocked by user");
-                        manager.repo.bindings.set_banned(binding.id.clone()).await?;
+                        repositories.bindings.set_banned(binding.id.clone()).await?;
                     }
                     false
                 }
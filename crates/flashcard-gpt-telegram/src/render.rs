@@ -0,0 +1,291 @@
+//! Code blocks and math of cards rendered to PNG images, which read better on mobile than `<pre>`
+//! and raw LaTeX. Code is highlighted with `syntect`, math is typeset by the local `latex` and
+//! `dvipng`. The images are cached in the [`BlobStore`] under a hash of their content.
+//!
+//! Rendering needs the `render` feature, without it [`render`] always fails.
+
+use flashcard_gpt_core::blob::BlobStore;
+use std::hash::{DefaultHasher, Hash, Hasher};
+use std::path::PathBuf;
+
+/// Whether the bot is built with the `render` feature.
+pub const ENABLED: bool = cfg!(feature = "render");
+
+/// Bumped when the images change, so that the cached ones are rendered again.
+const RENDER_VERSION: u8 = 1;
+
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub enum Renderable {
+    Code { lang: Option<String>, code: String },
+    Math { tex: String, display: bool },
+}
+
+impl Renderable {
+    /// `DefaultHasher` may change between Rust releases, which only costs a re-render.
+    pub fn cache_key(&self) -> String {
+        let mut hasher = DefaultHasher::new();
+        (RENDER_VERSION, self).hash(&mut hasher);
+        format!("render-{:016x}.png", hasher.finish())
+    }
+}
+
+/// The path of the rendered image, rendered on the first call.
+pub async fn render(cache: &BlobStore, renderable: &Renderable) -> anyhow::Result<PathBuf> {
+    let key = renderable.cache_key();
+    let path = cache.path(&key)?;
+    if cache.contains(&key).await? {
+        return Ok(path);
+    }
+
+    let png = match renderable.clone() {
+        Renderable::Code { lang, code } => {
+            tokio::task::spawn_blocking(move || png::code(lang.as_deref(), &code)).await??
+        }
+        Renderable::Math { tex, display } => png::math(&tex, display).await?,
+    };
+    cache.put(&key, &png).await?;
+
+    Ok(path)
+}
+
+#[cfg(not(feature = "render"))]
+mod png {
+    use anyhow::bail;
+
+    pub fn code(_lang: Option<&str>, _code: &str) -> anyhow::Result<Vec<u8>> {
+        bail!("Built without the `render` feature")
+    }
+
+    pub async fn math(_tex: &str, _display: bool) -> anyhow::Result<Vec<u8>> {
+        bail!("Built without the `render` feature")
+    }
+}
+
+#[cfg(feature = "render")]
+mod png {
+    use anyhow::{anyhow, bail, Context};
+    use resvg::tiny_skia::{Pixmap, Transform};
+    use resvg::usvg;
+    use std::fmt::Write;
+    use std::process::Stdio;
+    use std::sync::{Arc, LazyLock};
+    use std::time::Duration;
+    use syntect::easy::HighlightLines;
+    use syntect::highlighting::{Color, FontStyle, Theme, ThemeSet};
+    use syntect::parsing::SyntaxSet;
+    use syntect::util::LinesWithEndings;
+    use tokio::process::Command;
+
+    const THEME: &str = "InspiredGitHub";
+    const FONT_SIZE: f32 = 14.0;
+    /// Advance of a monospace glyph, relative to the font size.
+    const CHAR_WIDTH: f32 = 0.6;
+    const LINE_HEIGHT: f32 = 1.4;
+    const PADDING: f32 = 12.0;
+    /// Images are rendered at twice their size to stay sharp on high density screens.
+    const SCALE: f32 = 2.0;
+    const DPI: &str = "300";
+    const LATEX_TIMEOUT: Duration = Duration::from_secs(10);
+
+    static SYNTAXES: LazyLock<SyntaxSet> = LazyLock::new(SyntaxSet::load_defaults_newlines);
+    static THEMES: LazyLock<ThemeSet> = LazyLock::new(ThemeSet::load_defaults);
+    static FONTS: LazyLock<Arc<usvg::fontdb::Database>> = LazyLock::new(|| {
+        let mut fonts = usvg::fontdb::Database::new();
+        fonts.load_system_fonts();
+        Arc::new(fonts)
+    });
+
+    pub fn code(lang: Option<&str>, code: &str) -> anyhow::Result<Vec<u8>> {
+        let svg = code_svg(lang, code)?;
+        let options = usvg::Options {
+            fontdb: FONTS.clone(),
+            ..usvg::Options::default()
+        };
+        let tree = usvg::Tree::from_str(&svg, &options)?;
+        let size = tree
+            .size()
+            .to_int_size()
+            .scale_by(SCALE)
+            .context("Empty image")?;
+        let mut pixmap = Pixmap::new(size.width(), size.height()).context("Image is too large")?;
+        resvg::render(
+            &tree,
+            Transform::from_scale(SCALE, SCALE),
+            &mut pixmap.as_mut(),
+        );
+        Ok(pixmap.encode_png()?)
+    }
+
+    fn code_svg(lang: Option<&str>, code: &str) -> anyhow::Result<String> {
+        let theme: &Theme = THEMES
+            .themes
+            .get(THEME)
+            .ok_or_else(|| anyhow!("Theme {THEME} is missing"))?;
+        let syntax = lang
+            .and_then(|lang| SYNTAXES.find_syntax_by_token(lang))
+            .unwrap_or_else(|| SYNTAXES.find_syntax_plain_text());
+        let mut highlighter = HighlightLines::new(syntax, theme);
+
+        let code = code.replace('\t', "    ");
+        let mut lines = vec![];
+        for line in LinesWithEndings::from(&code) {
+            lines.push(highlighter.highlight_line(line, &SYNTAXES)?);
+        }
+
+        let columns = code.lines().map(|line| line.chars().count()).max();
+        let width = columns.unwrap_or(0) as f32 * FONT_SIZE * CHAR_WIDTH + 2.0 * PADDING;
+        let height = lines.len() as f32 * FONT_SIZE * LINE_HEIGHT + 2.0 * PADDING;
+        let background = theme.settings.background.unwrap_or(Color::WHITE);
+
+        let mut svg = format!(
+            r#"<svg xmlns="http://www.w3.org/2000/svg" width="{width}" height="{height}">"#
+        );
+        write!(
+            svg,
+            r#"<rect width="100%" height="100%" fill="{}"/>"#,
+            hex(background)
+        )?;
+        write!(
+            svg,
+            r#"<text font-family="monospace" font-size="{FONT_SIZE}" xml:space="preserve">"#
+        )?;
+        for (index, regions) in lines.iter().enumerate() {
+            let y = PADDING + (index as f32 + 1.0) * FONT_SIZE * LINE_HEIGHT - FONT_SIZE * 0.4;
+            write!(svg, r#"<tspan x="{PADDING}" y="{y}">"#)?;
+            for (style, text) in regions {
+                let text = escape_xml(text.trim_end_matches(['\r', '\n']));
+                let weight = if style.font_style.contains(FontStyle::BOLD) {
+                    r#" font-weight="bold""#
+                } else {
+                    ""
+                };
+                let italic = if style.font_style.contains(FontStyle::ITALIC) {
+                    r#" font-style="italic""#
+                } else {
+                    ""
+                };
+                write!(
+                    svg,
+                    r#"<tspan fill="{}"{weight}{italic}>{text}</tspan>"#,
+                    hex(style.foreground)
+                )?;
+            }
+            svg.push_str("</tspan>");
+        }
+        svg.push_str("</text></svg>");
+
+        Ok(svg)
+    }
+
+    /// Typesets `tex` in a temporary directory. `openin_any` and `openout_any` keep TeX from
+    /// reading or writing files outside of it.
+    pub async fn math(tex: &str, display: bool) -> anyhow::Result<Vec<u8>> {
+        let math = if display {
+            format!("$\\displaystyle {tex}$")
+        } else {
+            format!("${tex}$")
+        };
+        let document = format!(
+            "\\documentclass[preview,border=4pt]{{standalone}}\n\
+             \\usepackage{{amsmath,amssymb}}\n\
+             \\begin{{document}}\n{math}\n\\end{{document}}\n"
+        );
+
+        let dir = std::env::temp_dir().join(format!(
+            "flashcard-gpt-math-{}",
+            uuid::Uuid::new_v4().simple()
+        ));
+        tokio::fs::create_dir_all(&dir).await?;
+        let result: anyhow::Result<Vec<u8>> = async {
+            tokio::fs::write(dir.join("math.tex"), document).await?;
+            run(Command::new("latex")
+                .args([
+                    "-interaction=nonstopmode",
+                    "-halt-on-error",
+                    "-no-shell-escape",
+                ])
+                .arg("math.tex")
+                .current_dir(&dir))
+            .await?;
+            run(Command::new("dvipng")
+                .args(["-D", DPI, "-T", "tight", "-bg", "White", "-o", "math.png"])
+                .arg("math.dvi")
+                .current_dir(&dir))
+            .await?;
+            Ok(tokio::fs::read(dir.join("math.png")).await?)
+        }
+        .await;
+        tokio::fs::remove_dir_all(&dir).await?;
+
+        result
+    }
+
+    async fn run(command: &mut Command) -> anyhow::Result<()> {
+        let output = command
+            .env("openin_any", "p")
+            .env("openout_any", "p")
+            .stdin(Stdio::null())
+            .kill_on_drop(true)
+            .output();
+        let output = tokio::time::timeout(LATEX_TIMEOUT, output)
+            .await
+            .context("Timed out")??;
+        if !output.status.success() {
+            let stdout = String::from_utf8_lossy(&output.stdout);
+            let tail = stdout.lines().rev().take(10).collect::<Vec<_>>();
+            bail!(
+                "{:?} failed with {}: {}",
+                command.as_std().get_program(),
+                output.status,
+                tail.into_iter().rev().collect::<Vec<_>>().join("\n")
+            );
+        }
+        Ok(())
+    }
+
+    fn hex(color: Color) -> String {
+        format!("#{:02x}{:02x}{:02x}", color.r, color.g, color.b)
+    }
+
+    fn escape_xml(text: &str) -> String {
+        text.replace('&', "&amp;")
+            .replace('<', "&lt;")
+            .replace('>', "&gt;")
+    }
+
+    #[cfg(test)]
+    mod tests {
+        use super::*;
+
+        #[test]
+        fn test_code_svg() -> anyhow::Result<()> {
+            let svg = code_svg(Some("rust"), "fn main() {\n\tlet a = 1 < 2;\n}")?;
+            assert!(svg.starts_with("<svg"));
+            assert!(svg.contains("&lt;") && !svg.contains('\t'));
+            assert_eq!(svg.matches(r#"<tspan x="#).count(), 3);
+            Ok(())
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_cache_key() {
+        let code = |code: &str| Renderable::Code {
+            lang: Some("rust".to_string()),
+            code: code.to_string(),
+        };
+        assert_eq!(
+            code("fn main() {}").cache_key(),
+            code("fn main() {}").cache_key()
+        );
+        assert_ne!(
+            code("fn main() {}").cache_key(),
+            code("fn run() {}").cache_key()
+        );
+        assert!(BlobStore::is_valid_key(&code("").cache_key()));
+    }
+}
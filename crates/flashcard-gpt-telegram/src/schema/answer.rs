@@ -3,14 +3,24 @@ use crate::command::answer::AnswerCommand;
 use crate::command::root::RootCommand;
 use crate::schema::root::handle_show_generic_menu;
 use crate::state::bot_state::BotState;
+use crate::state::state_fields::StateFields;
 use anyhow::bail;
-use flashcard_gpt_core::model::card::UpdateCard;
-use flashcard_gpt_core::model::card_group::UpdateCardGroup;
+use flashcard_gpt_core::grading::{self, Diff};
+use flashcard_gpt_core::llm::answer_grader::AnswerGrader;
+use flashcard_gpt_core::model::annotation::{Annotations, Flag};
+use flashcard_gpt_core::model::card::{Card, UpdateCard};
+use flashcard_gpt_core::model::card_data::Attachment;
+use flashcard_gpt_core::model::card_group::{CardGroup, UpdateCardGroup};
+use flashcard_gpt_core::model::progress;
 use flashcard_gpt_core::reexports::db::syn;
+use flashcard_gpt_core::store::DeckStore;
+use itertools::Itertools;
+use std::sync::Arc;
 use teloxide::dispatching::{DpHandlerDescription, UpdateFilterExt};
 use teloxide::dptree::{case, Handler};
 use teloxide::prelude::{DependencyMap, Update};
-use tracing::info;
+use teloxide::utils::html;
+use tracing::{info, warn};
 
 pub fn answering_schema(
 ) -> Handler<'static, DependencyMap, anyhow::Result<()>, DpHandlerDescription> {
@@ -22,6 +32,10 @@ pub fn answering_schema(
             .branch(case![AnswerCommand::Hide(hide_time)].endpoint(handle_hide_card))
             .branch(case![AnswerCommand::Difficulty(difficulty)].endpoint(handle_set_difficulty))
             .branch(case![AnswerCommand::Importance(importance)].endpoint(handle_set_importance))
+            .branch(case![AnswerCommand::Order(order)].endpoint(handle_reorder_cards))
+            .branch(case![AnswerCommand::Note(note)].endpoint(handle_add_note))
+            .branch(case![AnswerCommand::Flag(flag)].endpoint(handle_toggle_flag))
+            .branch(case![AnswerCommand::Delete].endpoint(handle_delete_card))
             .branch(case![AnswerCommand::Cancel].endpoint(handle_cancel_answer)),
     );
 
@@ -94,6 +108,123 @@ async fn handle_set_importance(manager: ChatManager, importance: u8) -> anyhow::
     Ok(())
 }
 
+async fn handle_add_note(manager: ChatManager, note: String) -> anyhow::Result<()> {
+    let note = note.trim();
+    if note.is_empty() {
+        manager
+            .send_message("Use /note with the text of the note, e.g. /note I confuse this with X")
+            .await?;
+        return Ok(());
+    }
+
+    let annotated = update_annotations(&manager, |annotations| {
+        annotations.notes.push(Arc::from(note));
+    })
+    .await?;
+    if annotated.is_some() {
+        manager.send_message("📝 Noted").await?;
+    }
+
+    Ok(())
+}
+
+async fn handle_toggle_flag(manager: ChatManager, flag: String) -> anyhow::Result<()> {
+    let flag = match flag.parse::<Flag>() {
+        Ok(flag) => flag,
+        Err(err) => {
+            let flags = Flag::ALL.iter().map(|flag| flag.name()).join(", ");
+            manager
+                .send_message(format!("{err}, use one of: {flags}"))
+                .await?;
+            return Ok(());
+        }
+    };
+
+    let annotated = update_annotations(&manager, |annotations| {
+        annotations.toggle(flag);
+    })
+    .await?;
+    if let Some(annotations) = annotated {
+        let state = if annotations.flags.contains(&flag) {
+            "set"
+        } else {
+            "cleared"
+        };
+        manager
+            .send_message(format!("{} {} {state}", flag.icon(), flag.name()))
+            .await?;
+    }
+
+    Ok(())
+}
+
+/// Deletes the card being answered, a reversed card goes together with its sibling.
+async fn handle_delete_card(manager: ChatManager) -> anyhow::Result<()> {
+    let fields = manager.get_state().await?.into_fields();
+    let Some(Some(dc_id)) = fields.deck_card_id() else {
+        manager
+            .send_message("Only a single card can be deleted, not a card of a card group")
+            .await?;
+        manager.send_answer_menu().await?;
+        return Ok(());
+    };
+
+    let card = manager.delete_deck_card_inner(dc_id.clone()).await?;
+    let message = if card.sibling.is_some() {
+        format!(
+            "Deleted {} and its reversed sibling",
+            html::escape(&card.title)
+        )
+    } else {
+        format!("Deleted {}", html::escape(&card.title))
+    };
+    manager.send_message(message).await?;
+    handle_show_generic_menu::<RootCommand>(manager).await?;
+    Ok(())
+}
+
+/// Applies `annotate` to the annotations of the card group or the card being answered, returns
+/// the updated annotations or `None` when nothing is being answered.
+async fn update_annotations(
+    manager: &ChatManager,
+    annotate: impl FnOnce(&mut Annotations),
+) -> anyhow::Result<Option<Annotations>> {
+    let fields = manager.get_state().await?.into_fields();
+    if let Some(Some(dcg_id)) = fields.deck_card_group_id() {
+        let deck_card_group = manager
+            .repo
+            .decks
+            .get_deck_card_group(dcg_id.clone())
+            .await?;
+        let mut annotations = deck_card_group.card_group.annotations.clone();
+        annotate(&mut annotations);
+        let card_group = manager
+            .update_deck_card_group_inner(
+                dcg_id.clone(),
+                UpdateCardGroup::builder().annotations(annotations).build(),
+            )
+            .await?;
+        return Ok(Some(card_group.annotations));
+    }
+
+    if let Some(Some(dc_id)) = fields.deck_card_id() {
+        let deck_card = manager.repo.decks.get_deck_card(dc_id.clone()).await?;
+        let mut annotations = deck_card.card.annotations.clone();
+        annotate(&mut annotations);
+        let card = manager
+            .clone()
+            .update_deck_card_inner(
+                dc_id.clone(),
+                UpdateCard::builder().annotations(annotations).build(),
+            )
+            .await?;
+        return Ok(Some(card.annotations));
+    }
+
+    warn!(?fields, "No card or card group is being answered");
+    Ok(None)
+}
+
 pub async fn handle_show_article(manager: ChatManager) -> anyhow::Result<()> {
     let state = manager.get_state().await?;
     let fields = state.as_fields();
@@ -105,21 +236,130 @@ pub async fn handle_show_article(manager: ChatManager) -> anyhow::Result<()> {
         bail!("State has no active deck card / card group: {state:?}");
     };
 
-    manager.send_card_group_data_by_key(pk, "article").await?;
+    manager.send_attachment(pk, Attachment::Article).await?;
     manager
-        .send_card_group_data_by_key(pk, "commented_code")
+        .send_attachment(pk, Attachment::CommentedCode)
         .await?;
     manager.send_answer_menu().await?;
 
     Ok(())
 }
 
+/// Cards of a deck card group are graded one by one. The answer to the group is committed with a
+/// difficulty derived from the steps once the last card is graded or a card is answered wrong,
+/// see [`progress`].
 pub async fn handle_commit_answer(manager: ChatManager, difficulty: u8) -> anyhow::Result<()> {
-    manager.commit_answer(difficulty, None).await?;
+    let mut fields = manager.get_state().await?.into_fields();
+    let StateFields::Answer {
+        deck_card_group_id: Some(dcg_id),
+        deck_card_group_card_seq: Some(seq),
+        steps,
+        difficulty: suggested,
+        ..
+    } = &mut fields
+    else {
+        manager.commit_answer(difficulty, None).await?;
+        handle_show_generic_menu::<RootCommand>(manager).await?;
+        return Ok(());
+    };
+
+    let deck_card_group = manager
+        .repo
+        .decks
+        .get_deck_card_group(dcg_id.clone())
+        .await?;
+    let cards = &deck_card_group.card_group.cards;
+    // cards passed with /next are not graded
+    let finished = progress::record_step(steps, *seq, difficulty, cards.len());
+    *suggested = None;
+
+    let Some(group_difficulty) = finished else {
+        *seq += 1;
+        let card = cards[*seq].clone();
+        manager.update_state(BotState::Answering(fields)).await?;
+        manager.send_card(&card).await?;
+        manager.send_answer_menu().await?;
+        return Ok(());
+    };
+
+    let message = if steps.len() < cards.len() {
+        format!(
+            "Stopped at card {} of {}, the next session resumes from it. \
+             Card group committed with difficulty {group_difficulty}",
+            steps.len(),
+            cards.len()
+        )
+    } else {
+        format!("Card group committed with difficulty {group_difficulty}")
+    };
+    manager.update_state(BotState::Answering(fields)).await?;
+    manager.commit_answer(group_difficulty, None).await?;
+    manager.send_message(message).await?;
     handle_show_generic_menu::<RootCommand>(manager).await?;
     Ok(())
 }
 
+/// The cards are numbered from 1 in their current order, the group starts over in the new one.
+async fn handle_reorder_cards(manager: ChatManager, order: String) -> anyhow::Result<()> {
+    let mut fields = manager.get_state().await?.into_fields();
+    let StateFields::Answer {
+        deck_card_group_id: Some(dcg_id),
+        deck_card_group_card_seq: seq,
+        steps,
+        difficulty,
+        ..
+    } = &mut fields
+    else {
+        manager
+            .send_message("Only the cards of a card group can be reordered")
+            .await?;
+        manager.send_answer_menu().await?;
+        return Ok(());
+    };
+
+    let card_group = manager
+        .repo
+        .decks
+        .get_deck_card_group(dcg_id.clone())
+        .await?
+        .card_group;
+    let cards = order
+        .split_whitespace()
+        .map(|number| number.parse::<usize>().ok()?.checked_sub(1))
+        .collect::<Option<Vec<_>>>()
+        .and_then(|order| card_group.reordered(&order));
+    let Some(cards) = cards else {
+        manager
+            .send_message(format!(
+                "Expected the numbers 1 to {} in the new order",
+                card_group.cards.len()
+            ))
+            .await?;
+        manager.send_answer_menu().await?;
+        return Ok(());
+    };
+
+    let card_group = manager
+        .update_deck_card_group_inner(
+            dcg_id.clone(),
+            UpdateCardGroup::builder().cards(cards).build(),
+        )
+        .await?;
+    *seq = Some(0);
+    steps.clear();
+    *difficulty = None;
+    manager.update_state(BotState::Answering(fields)).await?;
+
+    manager
+        .send_message("Reordered the cards, starting over")
+        .await?;
+    if let Some(card) = card_group.cards.first() {
+        manager.send_card(card).await?;
+    }
+    manager.send_answer_menu().await?;
+    Ok(())
+}
+
 pub async fn handle_skip_answer(manager: ChatManager) -> anyhow::Result<()> {
     handle_show_generic_menu::<RootCommand>(manager).await?;
     Ok(())
@@ -167,7 +407,103 @@ pub async fn handle_cancel_answer(manager: ChatManager) -> anyhow::Result<()> {
     Ok(())
 }
 
+/// Typed answers to cards with an expected answer are graded locally and get a suggested
+/// difficulty, which the user confirms or overrides with the answer menu. Free-form explanations
+/// of other cards are graded by the LLM and committed right away, as a step of a card group.
 async fn handle_answering_message(manager: ChatManager) -> anyhow::Result<()> {
     info!(?manager, "Answering message");
+    let Some(answer) = manager
+        .message
+        .as_deref()
+        .and_then(|message| message.text())
+    else {
+        manager.send_invalid_input().await?;
+        return Ok(());
+    };
+
+    let Some((card, card_group)) = manager.get_answering_card().await? else {
+        manager.send_message("No card is being answered").await?;
+        return Ok(());
+    };
+
+    match card.expected_answer() {
+        Some(expected) => grade_typed_answer(manager, &expected, answer).await,
+        None => grade_free_form_answer(manager, &card, card_group.as_deref(), answer).await,
+    }
+}
+
+async fn grade_typed_answer(
+    manager: ChatManager,
+    expected: &str,
+    answer: &str,
+) -> anyhow::Result<()> {
+    let grade = grading::grade(expected, answer);
+    let mut fields = manager.get_state().await?.into_fields();
+    if let StateFields::Answer { difficulty, .. } = &mut fields {
+        *difficulty = Some(grade.difficulty);
+    }
+    manager.update_state(BotState::Answering(fields)).await?;
+
+    let diff = render_diff(&grading::diff(expected, answer));
+    manager
+        .send_message(format!(
+            "{diff}\n\n<b>Expected:</b> {}\n\nSimilarity: {:.0}%, suggested difficulty: {}",
+            html::escape(expected),
+            grade.similarity * 100.0,
+            grade.difficulty,
+        ))
+        .await?;
+    manager.send_answer_menu().await?;
+
     Ok(())
 }
+
+async fn grade_free_form_answer(
+    manager: ChatManager,
+    card: &Card,
+    card_group: Option<&CardGroup>,
+    answer: &str,
+) -> anyhow::Result<()> {
+    let article = card_group
+        .and_then(|card_group| card_group.data.as_ref()?.article.as_deref())
+        .or_else(|| card.data.as_ref()?.article.as_deref());
+
+    let grader = AnswerGrader::new(manager.user_generator().await?.card_generator);
+    let grade = match grader.grade(card, article, answer).await {
+        Ok(grade) => grade,
+        Err(err) => {
+            warn!(?err, "Failed to grade the answer");
+            manager
+                .send_message("Failed to grade the answer, please rate it yourself")
+                .await?;
+            manager.send_answer_menu().await?;
+            return Ok(());
+        }
+    };
+
+    let difficulty = grade.difficulty();
+    let mut message = format!("**Score:** {}/10, difficulty {difficulty}", grade.score);
+    for (title, points) in [
+        ("Missed points", &grade.missed_points),
+        ("Misconceptions", &grade.misconceptions),
+    ] {
+        if !points.is_empty() {
+            message.push_str(&format!("\n\n**{title}**\n"));
+            message.push_str(&points.iter().map(|point| format!("- {point}")).join("\n"));
+        }
+    }
+    manager.send_markdown_message(message).await?;
+    handle_commit_answer(manager, difficulty).await
+}
+
+/// Missing words are underlined, extra words struck through.
+fn render_diff(diff: &[Diff]) -> String {
+    diff.iter()
+        .map(|word| match word {
+            Diff::Same(word) => html::escape(word),
+            Diff::Missing(word) => format!("<u>{}</u>", html::escape(word)),
+            Diff::Extra(word) => format!("<s>{}</s>", html::escape(word)),
+        })
+        .collect::<Vec<_>>()
+        .join(" ")
+}
@@ -7,22 +7,34 @@ use crate::schema::root::{cancel, handle_show_generic_menu};
 use crate::state::bot_state::BotState;
 use crate::state::state_fields::StateFields;
 use anyhow::anyhow;
+use flashcard_gpt_core::document::generate::{DocumentGenerator, GenerateOptions};
+use flashcard_gpt_core::document::{Document as SourceDocument, DocumentFormat};
+use flashcard_gpt_core::error::CoreError;
+use flashcard_gpt_core::llm::batch::{
+    read_zip, BatchGenerator, BatchOptions, Grouping, SourceFile,
+};
+use flashcard_gpt_core::model::annotation::Flag;
 use flashcard_gpt_core::model::card::CreateCard;
+use flashcard_gpt_core::model::card_data::{Attachment, CardData};
 use flashcard_gpt_core::model::deck_card::CreateDeckCard;
-use flashcard_gpt_core::model::llm::GptCardGroup;
-use serde_json::Value;
+use flashcard_gpt_core::model::media::CardSide;
+use flashcard_gpt_core::store::siblings::CardSiblingsExt;
+use flashcard_gpt_core::store::{CardGroupStore, CardStore, DeckStore, TagStore};
 use std::collections::BTreeSet;
 use std::sync::Arc;
 use teloxide::dispatching::{DpHandlerDescription, UpdateFilterExt};
 use teloxide::dptree::{case, Handler};
-use teloxide::prelude::{DependencyMap, Update};
-use tracing::{error, info, warn};
+use teloxide::prelude::{DependencyMap, Message, Update};
+use teloxide::types::Document;
+use teloxide::utils::html;
+use tracing::{error, info};
 
 pub fn card_schema() -> Handler<'static, DependencyMap, anyhow::Result<()>, DpHandlerDescription> {
     let card_command_handler = teloxide::filter_command::<CardCommand, _>().branch(
         case![BotState::InsideCardMenu(fields)]
             .branch(case![CardCommand::Create].endpoint(handle_create_card))
-            .branch(case![CardCommand::Generate].endpoint(handle_generate_cards)),
+            .branch(case![CardCommand::Generate].endpoint(handle_generate_cards))
+            .branch(case![CardCommand::Flagged].endpoint(handle_show_flagged)),
     );
 
     let card_message_handler = Update::filter_message()
@@ -85,6 +97,48 @@ pub async fn handle_create_card(manager: ChatManager) -> anyhow::Result<()> {
     Ok(())
 }
 
+/// Titles of the flagged cards and card groups of the user, by flag.
+pub async fn handle_show_flagged(manager: ChatManager) -> anyhow::Result<()> {
+    let user = manager.get_user_id().clone();
+    let cards = manager.repo.cards.list_by_user_id(user.clone()).await?;
+    let card_groups = manager.repo.card_groups.list_by_user_id(user).await?;
+
+    let flagged = cards
+        .iter()
+        .map(|card| (&card.annotations, card.title.as_ref(), "💳"))
+        .chain(
+            card_groups
+                .iter()
+                .map(|cg| (&cg.annotations, cg.title.as_ref(), "📂")),
+        )
+        .collect::<Vec<_>>();
+
+    let mut text = String::new();
+    for flag in Flag::ALL {
+        let titles = flagged
+            .iter()
+            .filter(|(annotations, _, _)| annotations.flags.contains(&flag))
+            .map(|(_, title, icon)| format!(" - {icon} {}", html::escape(title)))
+            .collect::<Vec<_>>();
+        if titles.is_empty() {
+            continue;
+        }
+        text.push_str(&format!(
+            "<b>{} {}</b>\n{}\n\n",
+            flag.icon(),
+            flag.name(),
+            titles.join("\n")
+        ));
+    }
+    if text.is_empty() {
+        text.push_str("No flagged cards, flag them with /flag while answering.");
+    }
+
+    manager.send_message(text).await?;
+    handle_show_generic_menu::<CardCommand>(manager).await?;
+    Ok(())
+}
+
 async fn receive_card_title(manager: ChatManager) -> anyhow::Result<()> {
     let Some(next_title) = manager.parse_html() else {
         manager.send_invalid_input().await?;
@@ -104,16 +158,27 @@ async fn receive_card_title(manager: ChatManager) -> anyhow::Result<()> {
     Ok(())
 }
 
+/// The front is the text or the caption of the message, a photo or a document sent with it is
+/// shown with the front.
 async fn receive_card_front(manager: ChatManager) -> anyhow::Result<()> {
-    let Some(next_front) = manager.parse_html() else {
+    let next_front = manager.parse_html();
+    let next_media = manager.receive_media(CardSide::Front).await?;
+    if next_front.is_none() && next_media.is_none() {
         manager.send_invalid_input().await?;
         return Ok(());
-    };
+    }
 
     let fields = patch_state!(
         manager,
-        StateFields::Card { front },
-        |front: &mut Option<Arc<str>>| { front.replace(next_front) }
+        StateFields::Card { front, data },
+        |front: &mut Option<Arc<str>>, data: &mut Option<CardData>| {
+            if let Some(next_front) = next_front {
+                front.replace(next_front);
+            }
+            if let Some(media) = next_media {
+                data.get_or_insert_with(Default::default).media.push(media);
+            }
+        }
     );
     manager
         .update_state(BotState::ReceiveCardBack(fields))
@@ -123,16 +188,25 @@ async fn receive_card_front(manager: ChatManager) -> anyhow::Result<()> {
 }
 
 async fn receive_card_back(manager: ChatManager) -> anyhow::Result<()> {
-    let Some(next_back) = manager.parse_html() else {
+    let next_back = manager.parse_html();
+    let next_media = manager.receive_media(CardSide::Back).await?;
+    if next_back.is_none() && next_media.is_none() {
         manager.send_invalid_input().await?;
         return Ok(());
-    };
+    }
 
-    let fields = patch_state!(manager, StateFields::Card { back }, |back: &mut Option<
-        Arc<str>,
-    >| {
-        back.replace(next_back)
-    });
+    let fields = patch_state!(
+        manager,
+        StateFields::Card { back, data },
+        |back: &mut Option<Arc<str>>, data: &mut Option<CardData>| {
+            if let Some(next_back) = next_back {
+                back.replace(next_back);
+            }
+            if let Some(media) = next_media {
+                data.get_or_insert_with(Default::default).media.push(media);
+            }
+        }
+    );
 
     manager
         .update_state(BotState::ReceiveCardHints(fields))
@@ -255,38 +329,51 @@ async fn create_card(manager: ChatManager) -> anyhow::Result<()> {
 
     let title = title.ok_or_else(|| anyhow!("Title was not provided"))?;
 
-    let card = manager
-        .repo
-        .cards
-        .create(CreateCard {
-            user: user.id.clone(),
-            title,
-            front,
-            back,
-            hints,
-            difficulty: difficulty.unwrap_or(0),
-            importance: importance.unwrap_or(0),
-            data,
-            tags,
-        })
-        .await?;
+    let deck = match &deck {
+        Some(deck) => Some(manager.repo.decks.get_by_id(deck.as_thing()?).await?),
+        None => None,
+    };
+    let reverse = deck
+        .as_ref()
+        .and_then(|deck| deck.settings.as_ref())
+        .is_some_and(|settings| settings.reverse_cards);
 
-    manager
-        .send_message(format!("Created a new card: {card:?}"))
-        .await?;
+    let dto = CreateCard {
+        user: user.id.clone(),
+        title,
+        front,
+        back,
+        hints,
+        difficulty: difficulty.unwrap_or(0),
+        importance: importance.unwrap_or(0),
+        data,
+        choices: None,
+        sibling: None,
+        reverse,
+        tags,
+    };
 
-    if let Some(deck) = deck {
-        let rel = manager
-            .repo
-            .decks
-            .relate_card(CreateDeckCard {
-                deck: deck.as_thing()?,
-                card: card.id.clone(),
-            })
-            .await?;
-        manager
-            .send_message(format!("Related card to deck: {rel:?}"))
-            .await?;
+    // a cloze card becomes a card per cloze index, other cards may get a reversed sibling
+    for dto in dto.into_cloze_cards() {
+        for card in manager.repo.cards.create_with_sibling(dto).await? {
+            manager
+                .send_message(format!("Created a new card: {card:?}"))
+                .await?;
+
+            if let Some(deck) = &deck {
+                let rel = manager
+                    .repo
+                    .decks
+                    .relate_card(CreateDeckCard {
+                        deck: deck.id.clone(),
+                        card: card.id.clone(),
+                    })
+                    .await?;
+                manager
+                    .send_message(format!("Related card to deck: {rel:?}"))
+                    .await?;
+            }
+        }
     }
 
     manager.dialogue.exit().await?;
@@ -307,6 +394,15 @@ pub async fn handle_generate_cards(manager: ChatManager) -> anyhow::Result<()> {
 }
 
 async fn receive_generator_prompt(manager: ChatManager) -> anyhow::Result<()> {
+    if let Some(document) = manager.message.as_deref().and_then(Message::document) {
+        let document = document.clone();
+        let name = document.file_name.as_deref().unwrap_or_default();
+        if DocumentFormat::from_file_name(name).is_some() {
+            return generate_cards_from_document(manager, document).await;
+        }
+        return generate_cards_from_files(manager, document).await;
+    }
+
     let Some(text) = manager.parse_html() else {
         manager.send_invalid_input().await?;
         error!("Prompt was not provided.");
@@ -342,29 +438,12 @@ pub async fn generate_cards(manager: ChatManager) -> anyhow::Result<()> {
 
     let user = manager.binding.user.clone();
 
-    let (code_cards, params) = manager
-        .generator
-        .generate_code_cards(prompt.as_ref())
+    let generator = manager.user_generator().await?;
+    let multiple_choice = manager.multiple_choice().await?;
+    let gpt_card_group = generator
+        .generate_code_card_group(prompt.as_ref(), multiple_choice)
         .await?;
-    let mut gpt_card_group = GptCardGroup::from_gpt_response(&code_cards)?;
-
-    if let Some(data) = gpt_card_group.data.as_mut()
-        && let Value::Object(map) = data
-    {
-        for (param, value) in params {
-            if let Some(prev) = map.insert(param.to_string(), Value::String(value.to_string())) {
-                warn!(
-                    ?prev,
-                    ?param,
-                    ?value,
-                    "Replaced an existing param in card group data map"
-                );
-            }
-        }
-    }
-
-    let deck_card_group = manager
-        .generator
+    let deck_card_group = generator
         .create_cards(user.as_ref(), deck.as_thing()?, gpt_card_group)
         .await?;
 
@@ -376,13 +455,157 @@ pub async fn generate_cards(manager: ChatManager) -> anyhow::Result<()> {
     }
 
     manager
-        .send_card_group_data_by_key(&deck_card_group.id, "article")
+        .send_attachment(&deck_card_group.id, Attachment::Article)
         .await?;
     manager
-        .send_card_group_data_by_key(&deck_card_group.id, "commented_code")
+        .send_attachment(&deck_card_group.id, Attachment::CommentedCode)
         .await?;
 
     handle_show_generic_menu::<CardCommand>(manager).await?;
 
     Ok(())
 }
+
+/// Generates a card group for every problem of an uploaded zip archive or source file, the
+/// caption can choose how the files are grouped into problems.
+#[tracing::instrument(level = "debug", skip_all, parent = manager.span.clone(), err)]
+async fn generate_cards_from_files(manager: ChatManager, document: Document) -> anyhow::Result<()> {
+    let StateFields::GenerateCard {
+        deck: Some(deck), ..
+    } = manager.get_state().await?.into_fields()
+    else {
+        manager.send_invalid_input().await?;
+        return Ok(());
+    };
+
+    let name = document.file_name.clone().unwrap_or_default();
+    let is_zip = name.to_lowercase().ends_with(".zip");
+    if !is_zip && !SourceFile::is_source(&name) {
+        manager
+            .send_message(
+                "Send the code as text, a source file, a zip archive of source files \
+                 or a Markdown, text or PDF document.",
+            )
+            .await?;
+        return Ok(());
+    }
+    let caption = manager.message.as_deref().and_then(Message::caption);
+    let grouping = match caption.map(str::parse::<Grouping>) {
+        None => Grouping::default(),
+        Some(Ok(grouping)) => grouping,
+        Some(Err(err)) => {
+            manager.send_message(html::escape(&err)).await?;
+            return Ok(());
+        }
+    };
+
+    let bytes = manager.download_file(&document.file).await?;
+    let files = if is_zip {
+        match read_zip(&bytes) {
+            Ok(files) => files,
+            Err(CoreError::ZipError(err)) => {
+                manager
+                    .send_message(format!(
+                        "Can't read the archive: {}",
+                        html::escape(&err.to_string())
+                    ))
+                    .await?;
+                return Ok(());
+            }
+            Err(err) => return Err(err.into()),
+        }
+    } else {
+        let Ok(code) = String::from_utf8(bytes) else {
+            manager.send_invalid_input().await?;
+            return Ok(());
+        };
+        vec![SourceFile {
+            path: Arc::from(name),
+            code: Arc::from(code),
+        }]
+    };
+
+    manager
+        .send_message(format!(
+            "Generating cards from {} source files, it may take a while...",
+            files.len()
+        ))
+        .await?;
+    let options = BatchOptions::builder()
+        .grouping(grouping)
+        .distractors(manager.multiple_choice().await?)
+        .build();
+    let report = BatchGenerator::new(manager.user_generator().await?)
+        .generate(
+            manager.get_user_id().clone(),
+            deck.as_thing()?,
+            files,
+            options,
+        )
+        .await?;
+
+    manager
+        .send_message(format!(
+            "Generated the cards:\n<pre>{}</pre>",
+            html::escape(&report.to_string())
+        ))
+        .await?;
+    handle_show_generic_menu::<CardCommand>(manager).await?;
+
+    Ok(())
+}
+
+/// Generates card groups from the sections of an uploaded Markdown, text or PDF document.
+#[tracing::instrument(level = "debug", skip_all, parent = manager.span.clone(), err)]
+async fn generate_cards_from_document(
+    manager: ChatManager,
+    document: Document,
+) -> anyhow::Result<()> {
+    let StateFields::GenerateCard {
+        deck: Some(deck), ..
+    } = manager.get_state().await?.into_fields()
+    else {
+        manager.send_invalid_input().await?;
+        return Ok(());
+    };
+
+    let name = document.file_name.clone().unwrap_or_default();
+    let bytes = manager.download_file(&document.file).await?;
+    let document = match SourceDocument::parse(&name, bytes).await {
+        Ok(document) => document,
+        Err(err @ CoreError::InvalidDocument(_)) => {
+            manager.send_message(html::escape(&err.to_string())).await?;
+            return Ok(());
+        }
+        Err(err) => return Err(err.into()),
+    };
+
+    let options = GenerateOptions::builder()
+        .distractors(manager.multiple_choice().await?)
+        .build();
+    let chunks = document.chunks(options.max_tokens);
+    manager
+        .send_message(format!(
+            "Generating cards from {} parts of the document, it may take a while...",
+            chunks.len()
+        ))
+        .await?;
+    let report = DocumentGenerator::new(manager.user_generator().await?)
+        .generate_chunks(
+            manager.get_user_id().clone(),
+            deck.as_thing()?,
+            chunks,
+            options,
+        )
+        .await?;
+
+    manager
+        .send_message(format!(
+            "Generated the cards:\n<pre>{}</pre>",
+            html::escape(&report.to_string())
+        ))
+        .await?;
+    handle_show_generic_menu::<CardCommand>(manager).await?;
+
+    Ok(())
+}
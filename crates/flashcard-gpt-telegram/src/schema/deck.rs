@@ -9,17 +9,24 @@ use crate::patch_state;
 use crate::state::bot_state::{BotState, FlashGptDialogue};
 use crate::state::state_fields::StateFields;
 use anyhow::anyhow;
+use flashcard_gpt_core::anki::export::ExportOptions;
+use flashcard_gpt_core::anki::import::ImportOptions;
+use flashcard_gpt_core::error::CoreError;
 use flashcard_gpt_core::model::deck::{CreateDeck, DeckSettings};
+use flashcard_gpt_core::store::{DeckStore, TagStore};
 use std::collections::BTreeSet;
 use std::sync::Arc;
 use teloxide::dispatching::{DpHandlerDescription, UpdateFilterExt};
 use teloxide::dptree::{case, Handler};
 use teloxide::prelude::{DependencyMap, Message, Update};
+use teloxide::utils::html;
 
 pub fn deck_schema() -> Handler<'static, DependencyMap, anyhow::Result<()>, DpHandlerDescription> {
     let deck_command_handler = teloxide::filter_command::<DeckCommand, _>().branch(
         case![BotState::InsideDeckMenu(fields)]
-            .branch(case![DeckCommand::Create].endpoint(handle_create_deck)),
+            .branch(case![DeckCommand::Create].endpoint(handle_create_deck))
+            .branch(case![DeckCommand::Import].endpoint(handle_import_anki))
+            .branch(case![DeckCommand::Export].endpoint(handle_export_anki)),
     );
 
     let deck_message_handler = Update::filter_message()
@@ -29,6 +36,7 @@ pub fn deck_schema() -> Handler<'static, DependencyMap, anyhow::Result<()>, DpHa
                 .branch(case![DeckCommand::Cancel].endpoint(cancel)),
         )
         .branch(case![BotState::ReceiveDeckTitle(fields)].endpoint(receive_deck_title))
+        .branch(case![BotState::ReceiveAnkiPackage(fields)].endpoint(receive_anki_package))
         .branch(
             case![BotState::ReceiveDeckTags(fields)]
                 .branch(
@@ -77,6 +85,97 @@ pub async fn handle_create_deck(manager: ChatManager) -> anyhow::Result<()> {
     Ok(())
 }
 
+pub async fn handle_import_anki(manager: ChatManager) -> anyhow::Result<()> {
+    manager
+        .send_message(
+            "Send the Anki package as a document. Export it from Anki with \"Support older Anki \
+             versions\" checked, the review history is imported too.\nUse /cancel to exit.",
+        )
+        .await?;
+    manager
+        .update_state(BotState::ReceiveAnkiPackage(StateFields::Empty))
+        .await?;
+    manager.send_state_and_prompt().await?;
+    Ok(())
+}
+
+pub async fn handle_export_anki(manager: ChatManager) -> anyhow::Result<()> {
+    let options = ExportOptions::builder()
+        .maybe_blobs(manager.blobs.clone())
+        .build();
+    let (package, report) = manager
+        .repo
+        .anki_exporter()
+        .export(manager.get_user_id().clone(), options)
+        .await?;
+    if report.notes == 0 {
+        manager
+            .send_message("There are no cards to export.")
+            .await?;
+        return Ok(());
+    }
+
+    manager.send_file("flashcards.apkg", package).await?;
+    manager
+        .send_message(format!(
+            "Exported the decks:\n<pre>{}</pre>",
+            html::escape(&report.to_string())
+        ))
+        .await?;
+    Ok(())
+}
+
+async fn receive_anki_package(
+    manager: ChatManager,
+    msg: Message,
+    dialogue: FlashGptDialogue,
+) -> anyhow::Result<()> {
+    let document = msg.document().filter(|document| {
+        document
+            .file_name
+            .as_deref()
+            .is_some_and(|name| name.ends_with(".apkg") || name.ends_with(".colpkg"))
+    });
+    let Some(document) = document else {
+        manager.send_invalid_input().await?;
+        return Ok(());
+    };
+
+    manager.send_message("Importing the package...").await?;
+    let package = manager.download_file(&document.file).await?;
+    let options = ImportOptions::builder()
+        .history(true)
+        .maybe_blobs(manager.blobs.clone())
+        .build();
+    let report = match manager
+        .repo
+        .anki_importer()
+        .import(manager.get_user_id().clone(), package, options)
+        .await
+    {
+        Ok(report) => report,
+        Err(CoreError::InvalidAnkiPackage(reason)) => {
+            manager
+                .send_message(format!(
+                    "Can't import the package: {}",
+                    html::escape(&reason)
+                ))
+                .await?;
+            return Ok(());
+        }
+        Err(err) => return Err(err.into()),
+    };
+
+    manager
+        .send_message(format!(
+            "Imported the package:\n<pre>{}</pre>",
+            html::escape(&report.to_string())
+        ))
+        .await?;
+    dialogue.exit().await?;
+    Ok(())
+}
+
 async fn receive_deck_title(manager: ChatManager, msg: Message) -> anyhow::Result<()> {
     let Some(next_title) = msg.text().map(ToOwned::to_owned) else {
         manager.send_invalid_input().await?;
@@ -209,7 +308,10 @@ async fn create_deck(
             parent,
             user: user_id,
             tags,
-            settings: daily_limit.map(|limit| DeckSettings { daily_limit: limit }),
+            settings: daily_limit.map(|limit| DeckSettings {
+                daily_limit: limit,
+                reverse_cards: false,
+            }),
         })
         .await?;
 
@@ -5,10 +5,15 @@ use crate::ext::markdown::MarkdownFormatter;
 use crate::schema::answer::answering_schema;
 use crate::schema::card::card_schema;
 use crate::schema::deck::deck_schema;
+use crate::schema::poll::poll_answer_schema;
 use crate::schema::root::{receive_inline_query, receive_root_menu_item, root_schema};
+use crate::schema::user::user_schema;
 use crate::state::bot_state::{BotState, FlashGptDialogue};
+use crate::state::pending_poll::PendingPolls;
+use flashcard_gpt_core::blob::BlobStore;
 use flashcard_gpt_core::model::binding::Binding;
 use flashcard_gpt_core::llm::card_generator_service::CardGeneratorService;
+use flashcard_gpt_core::session::UserSessions;
 use std::sync::Arc;
 use teloxide::adaptors::DefaultParseMode;
 use teloxide::dispatching::dialogue::InMemStorage;
@@ -21,7 +26,10 @@ use tracing::{debug, warn, Span};
 mod answer;
 mod card;
 mod deck;
+mod poll;
 mod root;
+mod stats;
+mod user;
 
 pub fn schema() -> UpdateHandler<anyhow::Error> {
     let root_menu_handler = Update::filter_callback_query().endpoint(receive_root_menu_item);
@@ -30,9 +38,12 @@ pub fn schema() -> UpdateHandler<anyhow::Error> {
 
     let main_branch = dialogue::enter::<Update, InMemStorage<BotState>, BotState, _>()
         .filter_map_async(create_binding)
+        .filter_map_async(scope_repositories)
         .map(init_chat_manager)
+        .map(attach_blob_store)
         .branch(card_schema())
         .branch(deck_schema())
+        .branch(user_schema())
         .branch(root_schema())
         .branch(answering_schema())
         .branch(root_menu_handler)
@@ -41,6 +52,7 @@ pub fn schema() -> UpdateHandler<anyhow::Error> {
     dptree::entry()
         .inspect(|update: Update| debug!(?update, "Received update"))
         .branch(inline_query_handler)
+        .branch(poll_answer_schema())
         .branch(main_branch)
 }
 
@@ -52,6 +64,7 @@ fn init_chat_manager(
     bot: DefaultParseMode<Bot>,
     dialogue: FlashGptDialogue,
     markdown_formatter: MarkdownFormatter,
+    polls: PendingPolls,
     span: Span,
 ) -> ChatManager {
     let message = match update.kind {
@@ -59,18 +72,34 @@ fn init_chat_manager(
         _ => None,
     };
 
+    let generator = CardGeneratorService::new(
+        generator.card_generator,
+        repositories.cards.clone(),
+        repositories.card_groups.clone(),
+        repositories.decks.clone(),
+        repositories.tags.clone(),
+    );
+
     ChatManager {
         repo: repositories,
         binding,
         bot,
         dialogue,
         message,
+        polls,
+        blobs: None,
         span,
         formatter: markdown_formatter,
         generator,
     }
 }
 
+/// Separate from [`init_chat_manager`], which already takes as many dependencies as an endpoint
+/// can have.
+fn attach_blob_store(manager: ChatManager, blobs: Option<BlobStore>) -> ChatManager {
+    ChatManager { blobs, ..manager }
+}
+
 async fn create_binding(update: Update, repositories: Repositories) -> Option<Arc<Binding>> {
     let Ok(entity) = BindingEntity::try_from(&update) else {
         warn!(?update, "Unable to create binding entity from the update.");
@@ -84,6 +113,27 @@ async fn create_binding(update: Update, repositories: Repositories) -> Option<Ar
     Some(binding.into())
 }
 
+/// Replaces the root repositories with the ones of the binding's user for the handlers below.
+async fn scope_repositories(
+    binding: Arc<Binding>,
+    repositories: Repositories,
+    sessions: Option<UserSessions>,
+    span: Span,
+) -> Option<Repositories> {
+    let Some(sessions) = sessions else {
+        return Some(repositories);
+    };
+
+    let user = &binding.user.id;
+    match repositories.for_user(&sessions, user, span).await {
+        Ok(repositories) => Some(repositories),
+        Err(err) => {
+            warn!(?err, %user, "Unable to open the user session.");
+            None
+        }
+    }
+}
+
 async fn receive_next(manager: ChatManager) -> anyhow::Result<()> {
     match manager.get_state().await? {
         BotState::ReceiveDeckTags(fields) => {
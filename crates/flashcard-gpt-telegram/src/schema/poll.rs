@@ -0,0 +1,108 @@
+use crate::chat_manager::ChatManager;
+use crate::command::root::RootCommand;
+use crate::ext::binding::ChatIdExt;
+use crate::schema::answer::handle_commit_answer;
+use crate::schema::root::handle_show_generic_menu;
+use crate::schema::{attach_blob_store, init_chat_manager, scope_repositories};
+use crate::state::bot_state::{BotState, FlashGptDialogue};
+use crate::state::pending_poll::{PendingPoll, PendingPolls, PollTarget};
+use flashcard_gpt_core::model::binding::Binding;
+use flashcard_gpt_core::model::history::CreateHistory;
+use flashcard_gpt_core::store::HistoryStore;
+use std::sync::Arc;
+use teloxide::dispatching::dialogue::InMemStorage;
+use teloxide::dispatching::{DpHandlerDescription, UpdateFilterExt};
+use teloxide::dptree::Handler;
+use teloxide::prelude::{DependencyMap, Update};
+use teloxide::types::PollAnswer;
+use tracing::{debug, warn};
+
+/// Poll answers come without a chat, the chat and the deck card or the step of a deck card group
+/// are taken from the poll they answer.
+pub fn poll_answer_schema(
+) -> Handler<'static, DependencyMap, anyhow::Result<()>, DpHandlerDescription> {
+    Update::filter_poll_answer()
+        .filter_map_async(take_pending_poll)
+        .map(|poll: PendingPoll| poll.binding.clone())
+        .filter_map_async(scope_repositories)
+        .filter_map(poll_dialogue)
+        .map(init_chat_manager)
+        .map(attach_blob_store)
+        .endpoint(receive_poll_answer)
+}
+
+async fn take_pending_poll(answer: PollAnswer, polls: PendingPolls) -> Option<PendingPoll> {
+    let poll = polls.take(&answer.poll_id).await;
+    if poll.is_none() {
+        debug!(poll_id = %answer.poll_id, "Answer to an unknown poll");
+    }
+    poll
+}
+
+fn poll_dialogue(
+    binding: Arc<Binding>,
+    storage: Arc<InMemStorage<BotState>>,
+) -> Option<FlashGptDialogue> {
+    match binding.get_chat_id() {
+        Ok(chat_id) => Some(FlashGptDialogue::new(storage, chat_id)),
+        Err(err) => {
+            warn!(?err, "Unable to get the chat of the poll binding");
+            None
+        }
+    }
+}
+
+async fn receive_poll_answer(
+    manager: ChatManager,
+    answer: PollAnswer,
+    poll: PendingPoll,
+) -> anyhow::Result<()> {
+    let selected = poll.selected(answer.option_ids.iter().map(|&id| id as usize));
+    let difficulty = poll.choices.difficulty(&selected);
+
+    let deck_card_id = match poll.target {
+        PollTarget::DeckCard(id) => id,
+        PollTarget::DeckCardGroup { id, seq } => {
+            // the step is graded in the state of the group, which moves on with every answer
+            let fields = manager.get_state().await?.into_fields();
+            if fields.deck_card_group_id() != Some(&Some(id))
+                || fields.deck_card_group_card_seq() != Some(&Some(seq))
+            {
+                manager
+                    .send_message("The card group has moved on, the vote is not counted")
+                    .await?;
+                return Ok(());
+            }
+            manager
+                .send_message(format!("Answered with difficulty {difficulty}"))
+                .await?;
+            return handle_commit_answer(manager, difficulty).await;
+        }
+    };
+
+    manager
+        .repo
+        .history
+        .create_custom(CreateHistory {
+            user: manager.binding.user.id.clone(),
+            deck_card: Some(deck_card_id.clone()),
+            deck_card_group: None,
+            difficulty,
+            time: None,
+            hide_for: None,
+            steps: vec![],
+        })
+        .await?;
+
+    manager
+        .send_message(format!("Answer committed with difficulty {difficulty}"))
+        .await?;
+
+    // the user may have moved on while the poll was open
+    let fields = manager.get_state().await?.into_fields();
+    if fields.deck_card_id() == Some(&Some(deck_card_id)) {
+        handle_show_generic_menu::<RootCommand>(manager).await?;
+    }
+
+    Ok(())
+}
@@ -8,16 +8,24 @@ use crate::command::ext::CommandExt;
 use crate::command::root::RootCommand;
 use crate::command::tag::TagCommand;
 use crate::command::user::UserCommand;
+use crate::render;
 use crate::schema::answer::{
     handle_cancel_answer, handle_commit_answer, handle_show_article, handle_show_next_card,
     handle_skip_answer,
 };
-use crate::schema::card::{generate_cards, handle_create_card, handle_generate_cards};
-use crate::schema::deck::handle_create_deck;
+use crate::schema::card::{
+    generate_cards, handle_create_card, handle_generate_cards, handle_show_flagged,
+};
+use crate::schema::deck::{handle_create_deck, handle_export_anki, handle_import_anki};
 use crate::schema::receive_next;
+use crate::schema::stats::handle_show_stats;
+use crate::schema::user::{
+    handle_backup, handle_llm, handle_restore, handle_toggle_multiple_choice,
+};
 use crate::state::bot_state::{BotState, FlashGptDialogue};
 use crate::state::state_fields::StateFields;
 use anyhow::bail;
+use flashcard_gpt_core::store::GlobalSettingsStore;
 use std::str::FromStr;
 use teloxide::adaptors::DefaultParseMode;
 use teloxide::dispatching::{DpHandlerDescription, UpdateFilterExt};
@@ -43,7 +51,9 @@ pub fn root_schema() -> Handler<'static, DependencyMap, anyhow::Result<()>, DpHa
                 .branch(
                     case![RootCommand::CardGroup]
                         .endpoint(handle_show_generic_menu::<CardGroupCommand>),
-                ),
+                )
+                .branch(case![RootCommand::Stats].endpoint(handle_show_stats))
+                .branch(case![RootCommand::Images].endpoint(handle_toggle_images)),
         )
         .branch(case![RootCommand::Cancel].endpoint(cancel));
 
@@ -63,6 +73,32 @@ pub async fn cancel(manager: ChatManager) -> anyhow::Result<()> {
     Ok(())
 }
 
+async fn handle_toggle_images(manager: ChatManager) -> anyhow::Result<()> {
+    let user = manager.get_user_id().clone();
+    let settings = manager
+        .repo
+        .get_global_settings_or_default(user.clone())
+        .await?;
+    let settings = manager
+        .repo
+        .global_settings
+        .set_render_images(user, !settings.render_images)
+        .await?;
+
+    let can_render = render::ENABLED && manager.blobs.is_some();
+    let text = match (settings.render_images, can_render) {
+        (true, true) => "Code blocks and math will also be sent as images.",
+        (true, false) => {
+            "Code blocks and math will be sent as images once the bot is built with the \
+             <code>render</code> feature and <code>BLOB_STORAGE_PATH</code> is set."
+        }
+        (false, _) => "Code blocks and math will be sent as text only.",
+    };
+    manager.send_message(text).await?;
+    handle_show_generic_menu::<RootCommand>(manager).await?;
+    Ok(())
+}
+
 async fn handle_start(manager: ChatManager) -> anyhow::Result<()> {
     manager.delete_current_message().await?;
     handle_show_generic_menu::<RootCommand>(manager).await?;
@@ -133,6 +169,12 @@ pub(super) async fn receive_root_menu_item(
                 RootCommand::Tag => {
                     handle_show_generic_menu::<TagCommand>(manager).await?;
                 }
+                RootCommand::Stats => {
+                    handle_show_stats(manager).await?;
+                }
+                RootCommand::Images => {
+                    handle_toggle_images(manager).await?;
+                }
                 RootCommand::Help => {
                     handle_root_help(manager).await?;
                 }
@@ -153,6 +195,7 @@ pub(super) async fn receive_root_menu_item(
                 }
                 CardCommand::Create => handle_create_card(manager).await?,
                 CardCommand::Generate => handle_generate_cards(manager).await?,
+                CardCommand::Flagged => handle_show_flagged(manager).await?,
                 CardCommand::Next => receive_next(manager).await?,
                 CardCommand::Cancel => cancel(manager).await?,
             }
@@ -163,6 +206,12 @@ pub(super) async fn receive_root_menu_item(
                 DeckCommand::Create => {
                     handle_create_deck(manager).await?;
                 }
+                DeckCommand::Import => {
+                    handle_import_anki(manager).await?;
+                }
+                DeckCommand::Export => {
+                    handle_export_anki(manager).await?;
+                }
                 DeckCommand::Cancel => {
                     cancel(manager).await?;
                 }
@@ -172,6 +221,20 @@ pub(super) async fn receive_root_menu_item(
                 }
             }
         }
+
+        (Some(BotState::InsideUserMenu(_)), item) if let Ok(cmd) = UserCommand::from_str(item) => {
+            match cmd {
+                UserCommand::Backup => handle_backup(manager).await?,
+                UserCommand::Restore => handle_restore(manager).await?,
+                UserCommand::Llm => handle_llm(manager).await?,
+                UserCommand::MultipleChoice => handle_toggle_multiple_choice(manager).await?,
+                UserCommand::Cancel => cancel(manager).await?,
+                _ => {
+                    bot.send_message(dialogue.chat_id(), "Not implemented yet")
+                        .await?;
+                }
+            }
+        }
         (Some(BotState::ReceiveDeckTags(mut fields)), tag) => {
             if let Some(tags) = fields.tags_mut() {
                 tags.insert(tag.into());
@@ -238,8 +301,12 @@ pub(super) async fn receive_root_menu_item(
                 AnswerCommand::Skip => handle_skip_answer(manager).await?,
                 AnswerCommand::Importance(_) => {}
                 AnswerCommand::Difficulty(_) => {}
-                AnswerCommand::Hide(duration) => {
-                    warn!(%duration, "Received an impossible state from menu item");
+                cmd @ (AnswerCommand::Hide(_)
+                | AnswerCommand::Order(_)
+                | AnswerCommand::Note(_)
+                | AnswerCommand::Flag(_)
+                | AnswerCommand::Delete) => {
+                    warn!(?cmd, "Received an impossible state from menu item");
                 }
             }
         }
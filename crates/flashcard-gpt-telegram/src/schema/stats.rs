@@ -0,0 +1,100 @@
+use crate::chat_manager::ChatManager;
+use crate::command::root::RootCommand;
+use crate::schema::root::handle_show_generic_menu;
+use chrono::{TimeDelta, Utc};
+use flashcard_gpt_core::model::stats::{Stats, StatsQuery};
+use std::fmt::Write;
+
+/// How far back `/stats` looks.
+const STATS_DAYS: i64 = 30;
+
+const SPARKS: [char; 8] = ['▁', '▂', '▃', '▄', '▅', '▆', '▇', '█'];
+
+#[tracing::instrument(level = "info", skip_all, parent = &manager.span, err, fields(
+        chat_id = ?manager.dialogue.chat_id(),
+    ))]
+pub async fn handle_show_stats(manager: ChatManager) -> anyhow::Result<()> {
+    let user = manager.get_user_id().clone();
+    let timezone = manager
+        .repo
+        .get_global_settings_or_default(user.clone())
+        .await?
+        .timezone;
+
+    let till = Utc::now();
+    let query = StatsQuery::builder()
+        .user(user)
+        .from(till - TimeDelta::days(STATS_DAYS))
+        .till(till)
+        .build();
+    let stats = manager.repo.stats().get_stats(query, timezone).await?;
+
+    manager
+        .send_message(render_stats(&manager, &stats)?)
+        .await?;
+    handle_show_generic_menu::<RootCommand>(manager).await?;
+    Ok(())
+}
+
+fn render_stats(manager: &ChatManager, stats: &Stats) -> anyhow::Result<String> {
+    let mut text = format!("📊 <b>Last {STATS_DAYS} days</b>\n\n");
+    if stats.reviews == 0 {
+        text.push_str("No answers yet.");
+        return Ok(text);
+    }
+
+    let max = stats.reviews_per_day.values().max().copied().unwrap_or(1);
+    let sparkline = stats
+        .reviews_per_day
+        .values()
+        .map(|&reviews| SPARKS[(reviews * (SPARKS.len() - 1)).div_ceil(max)])
+        .collect::<String>();
+    writeln!(
+        text,
+        "Reviews: {} on {} days {sparkline}",
+        stats.reviews,
+        stats.reviews_per_day.len()
+    )?;
+
+    if let Some(average) = stats.average_difficulty {
+        write!(text, "Average difficulty: {average:.1}")?;
+        if let Some(trend) = stats.difficulty_trend {
+            let arrow = if trend < 0.0 { "↘" } else { "↗" };
+            write!(text, " {arrow} {trend:+.2} per day")?;
+        }
+        text.push('\n');
+    }
+    if let Some(retention) = stats.retention {
+        writeln!(text, "Retention: {:.0}%", retention * 100.0)?;
+    }
+    writeln!(
+        text,
+        "Streak: {} days, best {} days",
+        stats.current_streak, stats.best_streak
+    )?;
+
+    let busiest = stats
+        .hours
+        .iter()
+        .enumerate()
+        .max_by_key(|&(hour, &reviews)| (reviews, std::cmp::Reverse(hour)));
+    if let Some((hour, reviews)) = busiest {
+        writeln!(text, "Busiest hour: {hour:02}:00 ({reviews} answers)")?;
+    }
+
+    if !stats.hardest.is_empty() {
+        text.push_str("\n<b>Hardest</b>\n");
+        for (index, item) in stats.hardest.iter().enumerate() {
+            writeln!(
+                text,
+                "{}. {} — {:.1} ({} answers)",
+                index + 1,
+                manager.formatter.to_html(item.title.as_ref())?,
+                item.average_difficulty,
+                item.answers
+            )?;
+        }
+    }
+
+    Ok(text)
+}
@@ -0,0 +1,234 @@
+use crate::chat_manager::ChatManager;
+use crate::command::user::UserCommand;
+use crate::schema::root::cancel;
+use crate::state::bot_state::{BotState, FlashGptDialogue};
+use crate::state::state_fields::StateFields;
+use flashcard_gpt_core::backup::restore::{RestoreMode, RestoreOptions};
+use flashcard_gpt_core::backup::{Archive, EXTENSION};
+use flashcard_gpt_core::error::CoreError;
+use flashcard_gpt_core::llm::provider::LlmChoice;
+use flashcard_gpt_core::store::GlobalSettingsStore;
+use itertools::Itertools;
+use teloxide::dispatching::{DpHandlerDescription, UpdateFilterExt};
+use teloxide::dptree::{case, Handler};
+use teloxide::prelude::{DependencyMap, Message, Update};
+use teloxide::utils::html;
+
+pub fn user_schema() -> Handler<'static, DependencyMap, anyhow::Result<()>, DpHandlerDescription> {
+    let user_command_handler = teloxide::filter_command::<UserCommand, _>().branch(
+        case![BotState::InsideUserMenu(fields)]
+            .branch(case![UserCommand::Backup].endpoint(handle_backup))
+            .branch(case![UserCommand::Restore].endpoint(handle_restore))
+            .branch(case![UserCommand::Llm].endpoint(handle_llm))
+            .branch(case![UserCommand::MultipleChoice].endpoint(handle_toggle_multiple_choice)),
+    );
+
+    Update::filter_message()
+        .branch(user_command_handler)
+        .branch(
+            teloxide::filter_command::<UserCommand, _>()
+                .branch(case![UserCommand::Cancel].endpoint(cancel)),
+        )
+        .branch(case![BotState::ReceiveBackup(fields)].endpoint(receive_backup))
+        .branch(case![BotState::ReceiveLlmChoice(fields)].endpoint(receive_llm_choice))
+}
+
+pub async fn handle_backup(manager: ChatManager) -> anyhow::Result<()> {
+    let archive = manager
+        .repo
+        .backup_exporter()
+        .export(manager.get_user_id().clone())
+        .await?;
+
+    let name = format!(
+        "flashcards-{}.{EXTENSION}",
+        archive.created_at.format("%Y-%m-%d")
+    );
+    manager.send_file(&name, archive.to_json()?).await?;
+    manager
+        .send_message(format!(
+            "Backed up the account:\n<pre>{}</pre>\nSend the file with /restore to restore it \
+             here or to another account.",
+            html::escape(&archive.report().to_string())
+        ))
+        .await?;
+    Ok(())
+}
+
+pub async fn handle_restore(manager: ChatManager) -> anyhow::Result<()> {
+    manager
+        .send_message(
+            "Send the backup as a document. It is merged into your decks, add the caption \
+             <code>replace</code> to delete your tags, decks, cards, history and settings \
+             first.\nUse /cancel to exit.",
+        )
+        .await?;
+    manager
+        .update_state(BotState::ReceiveBackup(StateFields::Empty))
+        .await?;
+    manager.send_state_and_prompt().await?;
+    Ok(())
+}
+
+async fn receive_backup(
+    manager: ChatManager,
+    msg: Message,
+    dialogue: FlashGptDialogue,
+) -> anyhow::Result<()> {
+    let document = msg.document().filter(|document| {
+        document
+            .file_name
+            .as_deref()
+            .is_some_and(|name| name.ends_with(&format!(".{EXTENSION}")))
+    });
+    let Some(document) = document else {
+        manager.send_invalid_input().await?;
+        return Ok(());
+    };
+    let mode = match msg.caption().map(str::parse::<RestoreMode>) {
+        None => RestoreMode::Merge,
+        Some(Ok(mode)) => mode,
+        Some(Err(err)) => {
+            manager.send_message(html::escape(&err)).await?;
+            return Ok(());
+        }
+    };
+
+    manager.send_message("Restoring the backup...").await?;
+    let json = manager.download_file(&document.file).await?;
+    let options = RestoreOptions::builder().mode(mode).build();
+    let result = match Archive::parse(&json) {
+        Ok(archive) => {
+            manager
+                .repo
+                .backup_restorer()
+                .restore(manager.get_user_id().clone(), &archive, options)
+                .await
+        }
+        Err(err) => Err(err),
+    };
+    let report = match result {
+        Ok(report) => report,
+        Err(err @ (CoreError::InvalidBackup(_) | CoreError::JsonParseError(_))) => {
+            manager
+                .send_message(format!(
+                    "Can't restore the backup: {}",
+                    html::escape(&err.to_string())
+                ))
+                .await?;
+            return Ok(());
+        }
+        Err(err) => return Err(err.into()),
+    };
+
+    manager
+        .send_message(format!(
+            "Restored the backup:\n<pre>{}</pre>",
+            html::escape(&report.to_string())
+        ))
+        .await?;
+    dialogue.exit().await?;
+    Ok(())
+}
+
+pub async fn handle_toggle_multiple_choice(manager: ChatManager) -> anyhow::Result<()> {
+    let user = manager.get_user_id().clone();
+    let settings = manager
+        .repo
+        .get_global_settings_or_default(user.clone())
+        .await?;
+    let settings = manager
+        .repo
+        .global_settings
+        .set_multiple_choice(user, !settings.multiple_choice)
+        .await?;
+
+    let text = if settings.multiple_choice {
+        "Generated cards will have wrong options too and be asked as quiz polls."
+    } else {
+        "Generated cards will be answered and rated as usual."
+    };
+    manager.send_message(text).await?;
+    Ok(())
+}
+
+pub async fn handle_llm(manager: ChatManager) -> anyhow::Result<()> {
+    let settings = manager
+        .repo
+        .get_global_settings_or_default(manager.get_user_id().clone())
+        .await?;
+    let current = match &settings.llm {
+        Some(choice) => choice.to_string(),
+        None => "default".to_string(),
+    };
+    let providers = manager.generator.card_generator.providers().join(", ");
+    manager
+        .send_message(format!(
+            "Your LLM: <code>{}</code>\nConfigured providers: <code>{}</code>\n\nSend a \
+             provider, like <code>ollama</code>, a provider and a model, like \
+             <code>ollama:llama3.1</code>, or <code>default</code> to use the ones of the \
+             bot.\nUse /cancel to exit.",
+            html::escape(&current),
+            html::escape(&providers)
+        ))
+        .await?;
+    manager
+        .update_state(BotState::ReceiveLlmChoice(StateFields::Empty))
+        .await?;
+    manager.send_state_and_prompt().await?;
+    Ok(())
+}
+
+async fn receive_llm_choice(
+    manager: ChatManager,
+    msg: Message,
+    dialogue: FlashGptDialogue,
+) -> anyhow::Result<()> {
+    let Some(text) = msg.text().map(str::trim) else {
+        manager.send_invalid_input().await?;
+        return Ok(());
+    };
+    let choice = match text {
+        "default" => None,
+        text => match text.parse::<LlmChoice>() {
+            Ok(choice) => Some(choice),
+            Err(err) => {
+                manager.send_message(html::escape(&err)).await?;
+                return Ok(());
+            }
+        },
+    };
+    if let Some(choice) = &choice
+        && !manager
+            .generator
+            .card_generator
+            .providers()
+            .any(|provider| provider == choice.provider)
+    {
+        manager
+            .send_message(format!(
+                "The provider <code>{}</code> is not configured in this bot.",
+                choice.provider
+            ))
+            .await?;
+        return Ok(());
+    }
+
+    let user = manager.get_user_id().clone();
+    // the settings are created with the defaults first
+    manager
+        .repo
+        .get_global_settings_or_default(user.clone())
+        .await?;
+    let settings = manager.repo.global_settings.set_llm(user, choice).await?;
+    let text = match &settings.llm {
+        Some(choice) => format!(
+            "Cards are generated and answers graded with <code>{}</code>.",
+            html::escape(&choice.to_string())
+        ),
+        None => "Cards are generated and answers graded with the LLM of the bot.".to_string(),
+    };
+    manager.send_message(text).await?;
+    dialogue.exit().await?;
+    Ok(())
+}
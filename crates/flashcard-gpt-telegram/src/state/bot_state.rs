@@ -39,6 +39,15 @@ pub enum BotState {
     #[strum(props(name = "Deck Creation Confirmation (/next)"))]
     ReceiveDeckConfirm(StateFields),
 
+    #[strum(props(name = "Anki Package (.apkg or .colpkg)"))]
+    ReceiveAnkiPackage(StateFields),
+
+    #[strum(props(name = "Backup (.json)"))]
+    ReceiveBackup(StateFields),
+
+    #[strum(props(name = "LLM (provider, provider:model or default)"))]
+    ReceiveLlmChoice(StateFields),
+
     #[strum(props(name = "Card Title"))]
     ReceiveCardTitle(StateFields),
     #[strum(props(name = "Card Front"))]
@@ -61,7 +70,7 @@ pub enum BotState {
     #[strum(props(name = "a deck that will be used for the card generation"))]
     ReceiveGenerateCardDeck(StateFields),
 
-    #[strum(props(name = "Card Prompt"))]
+    #[strum(props(name = "Card Prompt (code, a source file, a zip archive or a document)"))]
     ReceiveGenerateCardPrompt(StateFields),
 
     #[strum(props(name = "Confirm card generation (use /next)"))]
@@ -107,6 +116,9 @@ impl BotState {
             BotState::ReceiveDeckParent(_) => false,
             BotState::ReceiveDeckSettingsDailyLimit(_) => false,
             BotState::ReceiveDeckConfirm(_) => false,
+            BotState::ReceiveAnkiPackage(_) => false,
+            BotState::ReceiveBackup(_) => false,
+            BotState::ReceiveLlmChoice(_) => false,
             BotState::ReceiveCardTitle(_) => false,
             BotState::ReceiveCardFront(_) => false,
             BotState::ReceiveCardBack(_) => false,
@@ -169,6 +181,9 @@ state_variants! {
     ReceiveDeckParent,
     ReceiveDeckSettingsDailyLimit,
     ReceiveDeckConfirm,
+    ReceiveAnkiPackage,
+    ReceiveBackup,
+    ReceiveLlmChoice,
     ReceiveCardTitle,
     ReceiveCardFront,
     ReceiveCardBack,
@@ -1,3 +1,4 @@
 pub mod bot_state;
+pub mod pending_poll;
 pub mod state_description;
 pub mod state_fields;
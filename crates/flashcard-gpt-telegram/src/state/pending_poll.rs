@@ -0,0 +1,58 @@
+use chrono::{DateTime, TimeDelta, Utc};
+use flashcard_gpt_core::model::binding::Binding;
+use flashcard_gpt_core::model::multiple_choice::MultipleChoice;
+use flashcard_gpt_core::reexports::db::sql::Thing;
+use std::collections::HashMap;
+use std::sync::Arc;
+use tokio::sync::Mutex;
+
+/// Unanswered polls are forgotten after this long.
+const POLL_TTL: TimeDelta = TimeDelta::days(1);
+
+/// What the vote on a poll answers.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum PollTarget {
+    /// A deck card answered on its own, the vote is committed as its answer.
+    DeckCard(Thing),
+    /// The card at `seq` of a deck card group, the vote is a step of the group.
+    DeckCardGroup { id: Thing, seq: usize },
+}
+
+/// A multiple-choice card sent as a poll. Poll answers carry neither the chat nor the message, so
+/// everything needed to commit the answer is kept until the user votes.
+#[derive(Debug, Clone)]
+pub struct PendingPoll {
+    pub binding: Arc<Binding>,
+    pub target: PollTarget,
+    pub choices: MultipleChoice,
+    /// The option index of the card for every option in the order they were shown.
+    pub order: Vec<usize>,
+    pub sent_at: DateTime<Utc>,
+}
+
+impl PendingPoll {
+    /// Maps the option ids of a poll answer back to the options of the card.
+    pub fn selected(&self, option_ids: impl IntoIterator<Item = usize>) -> Vec<usize> {
+        option_ids
+            .into_iter()
+            .filter_map(|id| self.order.get(id).copied())
+            .collect()
+    }
+}
+
+#[derive(Debug, Clone, Default)]
+pub struct PendingPolls(Arc<Mutex<HashMap<String, PendingPoll>>>);
+
+impl PendingPolls {
+    pub async fn insert(&self, poll_id: String, poll: PendingPoll) {
+        let mut polls = self.0.lock().await;
+        let now = Utc::now();
+        polls.retain(|_, poll| now - poll.sent_at < POLL_TTL);
+        polls.insert(poll_id, poll);
+    }
+
+    /// Polls are answered once, a retracted or changed vote is not committed again.
+    pub async fn take(&self, poll_id: &str) -> Option<PendingPoll> {
+        self.0.lock().await.remove(poll_id)
+    }
+}
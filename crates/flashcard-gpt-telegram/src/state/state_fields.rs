@@ -1,6 +1,6 @@
 use crate::ext::rendering::{DisplayJoinOrDash, OptionDisplayExt};
+use flashcard_gpt_core::model::card_data::CardData;
 use flashcard_gpt_core::reexports::db::sql::Thing;
-use serde_json::Value;
 use std::collections::BTreeSet;
 use std::fmt;
 use std::fmt::Display;
@@ -27,7 +27,7 @@ pub enum StateFields {
         hints: Vec<Arc<str>>,
         difficulty: Option<u8>,
         importance: Option<u8>,
-        data: Option<Arc<Value>>,
+        data: Option<CardData>,
         tags: BTreeSet<Arc<str>>,
         deck: Option<Arc<str>>,
     },
@@ -40,6 +40,9 @@ pub enum StateFields {
     Answer {
         deck_card_group_id: Option<Thing>,
         deck_card_group_card_seq: Option<usize>,
+        /// Difficulties of the cards of the group answered so far, see
+        /// [`flashcard_gpt_core::model::progress`].
+        steps: Vec<Option<u8>>,
         deck_card_id: Option<Thing>,
         difficulty: Option<u8>,
     },
@@ -94,6 +97,7 @@ impl Display for StateFields {
             StateFields::Answer {
                 deck_card_group_id: card_group_id,
                 deck_card_group_card_seq: card_group_card_seq,
+                steps,
                 deck_card_id: card_id,
                 difficulty,
             } => {
@@ -103,6 +107,11 @@ impl Display for StateFields {
                     card_group_id.to_string_or_dash(),
                     card_group_card_seq.to_string_or_dash()
                 )?;
+                let steps = steps
+                    .iter()
+                    .map(OptionDisplayExt::to_string_or_dash)
+                    .collect::<Vec<_>>();
+                writeln!(f, "<b>Steps:</b> {}", steps.join_or_dash())?;
                 writeln!(f, "<b>Card:</b> {}", card_id.to_string_or_dash())?;
                 write!(f, "<b>Difficulty:</b> {}", difficulty.to_string_or_dash())
             }
@@ -141,6 +150,7 @@ impl StateFields {
         Self::Answer {
             deck_card_group_id: None,
             deck_card_group_card_seq: None,
+            steps: vec![],
             deck_card_id: None,
             difficulty: None,
         }
@@ -0,0 +1,65 @@
+//! Fixtures of the memory stores, the counterpart of [`super::utils`] for tests that don't need
+//! a database.
+
+use bon::builder;
+use flashcard_gpt_core::model::card::CreateCard;
+use flashcard_gpt_core::model::deck::{CreateDeck, Deck, DeckSettings};
+use flashcard_gpt_core::model::user::{RegisterUser, User};
+use flashcard_gpt_core::store::memory::{MemoryDb, MemoryDeckRepo, MemoryUserRepo};
+use flashcard_gpt_core::store::{DeckStore, UserStore};
+use std::sync::Arc;
+use surrealdb::sql::Thing;
+use testresult::TestResult;
+
+pub async fn create_user(db: &MemoryDb, name: &str) -> TestResult<User> {
+    let user = MemoryUserRepo::new(db.clone())
+        .create_user(RegisterUser {
+            email: format!("{}@example.com", name.to_lowercase()).into(),
+            name: Arc::from(name),
+            password: Arc::from(name),
+        })
+        .await?;
+
+    Ok(user)
+}
+
+#[builder]
+pub async fn create_deck(
+    db: &MemoryDb,
+    user: &User,
+    title: &str,
+    parent: Option<Thing>,
+    description: Option<&str>,
+    settings: Option<DeckSettings>,
+) -> TestResult<Deck> {
+    let deck = MemoryDeckRepo::new(db.clone())
+        .create(CreateDeck {
+            description: description.map(Arc::from),
+            parent,
+            settings,
+            tags: vec![],
+            title: Arc::from(title),
+            user: user.id.clone(),
+        })
+        .await?;
+
+    Ok(deck)
+}
+
+/// A card of `user` titled by its front, to be stored by the test.
+pub fn card(user: &User, front: &str, back: Option<&str>) -> CreateCard {
+    CreateCard {
+        user: user.id.clone(),
+        title: Arc::from(front),
+        front: Some(Arc::from(front)),
+        back: back.map(Arc::from),
+        hints: vec![],
+        difficulty: 0,
+        importance: 0,
+        data: None,
+        choices: None,
+        sibling: None,
+        reverse: false,
+        tags: vec![],
+    }
+}
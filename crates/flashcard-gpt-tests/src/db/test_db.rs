@@ -1,55 +1,44 @@
-use crate::db::surreal_test_container::{SurrealDbTestContainer, SURREALDB_PORT};
-use surrealdb::engine::remote::ws::{Client, Ws};
-use surrealdb::opt::auth::Root;
+use flashcard_gpt_core::connection::DbSettings;
+use std::sync::LazyLock;
+use surrealdb::engine::any::Any;
 use surrealdb::Surreal;
-use testcontainers::runners::AsyncRunner;
-use testcontainers::ContainerAsync;
 use testresult::TestResult;
-use tracing::{error, info};
+use tokio::runtime::Runtime;
+use tracing::info;
+
+/// Every `#[tokio::test]` runs on its own runtime, while the embedded engine lives
+/// on the runtime it was started from. Keeping it on a dedicated one lets all tests
+/// share the same in-memory database.
+static DB_RUNTIME: LazyLock<Runtime> = LazyLock::new(|| {
+    tokio::runtime::Builder::new_multi_thread()
+        .worker_threads(2)
+        .thread_name("test-db")
+        .enable_all()
+        .build()
+        .expect("Failed to build the test database runtime")
+});
 
 pub struct TestDb {
-    pub container: ContainerAsync<SurrealDbTestContainer>,
+    pub db: Surreal<Any>,
 }
 
 impl TestDb {
     pub async fn new() -> TestResult<Self> {
-        let container = prepare_database().await?;
-        Ok(Self { container })
+        let db = DB_RUNTIME
+            .spawn(prepare_database())
+            .await?
+            .map_err(std::io::Error::other)?;
+        Ok(Self { db })
     }
 }
 
-pub async fn prepare_database() -> TestResult<ContainerAsync<SurrealDbTestContainer>> {
-    let node = SurrealDbTestContainer::default().start().await?;
-    let host_port = node.get_host_port_ipv4(SURREALDB_PORT).await?;
-    let url = format!("127.0.0.1:{host_port}");
-
-    let db: Surreal<Client> = Surreal::init();
-    db.connect::<Ws>(url).await?;
-    db.signin(Root {
-        username: "root",
-        password: "root",
-    })
-    .await?;
-
-    db.use_ns("test").use_db("test").await?;
-
-    let migration_data = include_str!(
-        "../../../flashcard-gpt-core/db-migrations/migrations/20240902_185441_Initial.surql"
-    );
-    let mut response = db.query(migration_data).await?;
-
-    let mut last_error = None;
-
-    for (id, err) in response.take_errors() {
-        error!(%id, ?err, "Query failed");
-        last_error = Some(err);
-    }
-
-    if let Some(err) = last_error {
-        Err(err)?;
-    }
+pub async fn prepare_database() -> Result<Surreal<Any>, String> {
+    let db = DbSettings::in_memory()
+        .connect()
+        .await
+        .map_err(|err| err.to_string())?;
 
     info!("Migration complete");
 
-    Ok(node)
+    Ok(db)
 }
@@ -154,6 +154,9 @@ where
             difficulty: difficulty.unwrap_or(0),
             importance: importance.unwrap_or(0),
             data: None,
+            choices: None,
+            sibling: None,
+            reverse: false,
             tags,
         })
         .await?;